@@ -5,8 +5,9 @@ use crate::{
 use camino::{Utf8Path, Utf8PathBuf};
 use pageserver_api::{
     controller_api::{
-        NodeConfigureRequest, NodeRegisterRequest, TenantCreateRequest, TenantCreateResponse,
-        TenantLocateResponse, TenantShardMigrateRequest, TenantShardMigrateResponse,
+        MigrationConfig, NodeConfigureRequest, NodeRegisterRequest, ObservedLocation,
+        ShardSchedulingPolicy, TenantCreateRequest, TenantCreateResponse, TenantLocateResponse,
+        TenantShardMigrateRequest, TenantShardMigrateResponse,
     },
     models::{
         TenantShardSplitRequest, TenantShardSplitResponse, TimelineCreateRequest, TimelineInfo,
@@ -61,6 +62,17 @@ pub struct InspectRequest {
 #[derive(Serialize, Deserialize)]
 pub struct InspectResponse {
     pub attachment: Option<(u32, NodeId)>,
+    /// The shard's intended secondary locations
+    #[serde(default)]
+    pub secondaries: Vec<NodeId>,
+    /// How the controller last saw the shard's locations configured, by node ID
+    #[serde(default)]
+    pub observed: Vec<ObservedLocation>,
+    /// None if the shard does not exist
+    #[serde(default)]
+    pub scheduling_policy: Option<ShardSchedulingPolicy>,
+    #[serde(default)]
+    pub is_reconciling: bool,
 }
 
 impl StorageController {
@@ -537,6 +549,7 @@ impl StorageController {
             Some(TenantShardMigrateRequest {
                 tenant_shard_id,
                 node_id,
+                migration_config: MigrationConfig::default(),
             }),
         )
         .await
@@ -4,8 +4,8 @@ use std::{str::FromStr, time::Duration};
 use clap::{Parser, Subcommand};
 use pageserver_api::{
     controller_api::{
-        NodeAvailabilityWrapper, NodeDescribeResponse, ShardSchedulingPolicy, TenantCreateRequest,
-        TenantDescribeResponse, TenantPolicyRequest,
+        MigrationConfig, NodeAvailabilityWrapper, NodeDescribeResponse, ReconcilePriority,
+        ShardSchedulingPolicy, TenantCreateRequest, TenantDescribeResponse, TenantPolicyRequest,
     },
     models::{
         EvictionPolicy, EvictionPolicyLayerAccessThreshold, LocationConfigSecondary,
@@ -70,6 +70,10 @@ enum Command {
         /// unavailable, and are only for use in emergencies.
         #[arg(long)]
         scheduling: Option<ShardSchedulingPolicyArg>,
+        /// Reconcile priority decides which tenants reconcile first when many shards need it at
+        /// once, e.g. while draining a node: `high`, `normal` or `low`.
+        #[arg(long)]
+        priority: Option<ReconcilePriorityArg>,
     },
     /// List nodes known to the storage controller
     Nodes {},
@@ -101,6 +105,9 @@ enum Command {
         tenant_shard_id: TenantShardId,
         #[arg(long)]
         node: NodeId,
+        /// If the tenant is detached, attach it rather than refusing to migrate it
+        #[arg(long)]
+        attach_detached: bool,
     },
     /// Modify the pageserver tenant configuration of a tenant: this is the configuration structure
     /// that is passed through to pageservers, and does not affect storage controller behavior.
@@ -230,6 +237,24 @@ impl FromStr for ShardSchedulingPolicyArg {
     }
 }
 
+#[derive(Debug, Clone)]
+struct ReconcilePriorityArg(ReconcilePriority);
+
+impl FromStr for ReconcilePriorityArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high" => Ok(Self(ReconcilePriority::High)),
+            "normal" => Ok(Self(ReconcilePriority::Normal)),
+            "low" => Ok(Self(ReconcilePriority::Low)),
+            _ => Err(anyhow::anyhow!(
+                "Unknown reconcile priority '{s}', try high,normal,low"
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct NodeAvailabilityArg(NodeAvailabilityWrapper);
 
@@ -331,6 +356,7 @@ async fn main() -> anyhow::Result<()> {
                         listen_pg_port,
                         listen_http_addr,
                         listen_http_port,
+                        capabilities: None,
                     }),
                 )
                 .await?;
@@ -427,10 +453,12 @@ async fn main() -> anyhow::Result<()> {
             tenant_id,
             placement,
             scheduling,
+            priority,
         } => {
             let req = TenantPolicyRequest {
                 scheduling: scheduling.map(|s| s.0),
                 placement: placement.map(|p| p.0),
+                priority: priority.map(|p| p.0),
             };
             storcon_client
                 .dispatch::<_, ()>(
@@ -472,10 +500,12 @@ async fn main() -> anyhow::Result<()> {
         Command::TenantShardMigrate {
             tenant_shard_id,
             node,
+            attach_detached,
         } => {
             let req = TenantShardMigrateRequest {
                 tenant_shard_id,
                 node_id: node,
+                migration_config: MigrationConfig { attach_detached },
             };
 
             storcon_client
@@ -814,6 +844,7 @@ async fn main() -> anyhow::Result<()> {
                                 Some(TenantShardMigrateRequest {
                                     tenant_shard_id: mv.tenant_shard_id,
                                     node_id: mv.to,
+                                    migration_config: MigrationConfig::default(),
                                 }),
                             )
                             .await
@@ -1,14 +1,17 @@
+use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 /// Request/response types for the storage controller
 /// API (`/control/v1` prefix).  Implemented by the server
 /// in [`storage_controller::http`]
 use serde::{Deserialize, Serialize};
 use utils::id::{NodeId, TenantId};
+use utils::serde_system_time;
 
 use crate::{
-    models::{ShardParameters, TenantConfig},
-    shard::{ShardStripeSize, TenantShardId},
+    models::{LocationConfig, SecondaryProgress, ShardParameters, TenantConfig},
+    shard::{ShardCount, ShardStripeSize, TenantShardId},
 };
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -53,6 +56,28 @@ pub struct NodeRegisterRequest {
 
     pub listen_http_addr: String,
     pub listen_http_port: u16,
+
+    /// Omitted by nodes that predate capability reporting
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<NodeCapabilities>,
+}
+
+/// Optional pageserver APIs, which the storage controller checks a node implements before
+/// relying on them.  Fields default to false, so that a node which reports its capabilities
+/// but predates a particular field is treated as lacking it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NodeCapabilities {
+    /// The node can split attached tenant shards in place
+    #[serde(default)]
+    pub shard_split: bool,
+}
+
+impl NodeCapabilities {
+    /// The capabilities of a pageserver built from this tree
+    pub fn current() -> Self {
+        Self { shard_split: true }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -67,6 +92,216 @@ pub struct NodeConfigureRequest {
 pub struct TenantPolicyRequest {
     pub placement: Option<PlacementPolicy>,
     pub scheduling: Option<ShardSchedulingPolicy>,
+    #[serde(default)]
+    pub priority: Option<ReconcilePriority>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantsReconcileRequest {
+    pub tenant_ids: Vec<TenantId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantEvacuateNodeRequest {
+    pub node_id: NodeId,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantEvacuateNodeResponse {
+    /// Shards which had a location on the node, and were moved elsewhere
+    pub shards: Vec<TenantShardId>,
+}
+
+/// Where one shard of a tenant should be located
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TenantShardPlacement {
+    pub tenant_shard_id: TenantShardId,
+    pub attached: Option<NodeId>,
+    #[serde(default)]
+    pub secondaries: Vec<NodeId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantSetPlacementRequest {
+    /// Shards not listed here keep their current placement
+    pub shards: Vec<TenantShardPlacement>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantSetPlacementResponse {
+    /// Shards whose placement was changed
+    pub shards: Vec<TenantShardId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeMigrateTenantsRequest {
+    pub tenant_ids: Vec<TenantId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeMigrateTenantsResponse {
+    /// Shards whose attachment was moved from the node to one of their secondary locations
+    pub migrated: Vec<TenantShardId>,
+    /// Shards attached to the node which could not be moved, e.g. for lack of a secondary
+    pub failed: Vec<TenantShardId>,
+}
+
+/// Auto-splitting settings of the storage controller, which may be changed at runtime
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutosplitConfig {
+    /// How large must a shard grow in bytes before we split it?  None disables auto-splitting.
+    pub split_threshold: Option<u64>,
+    /// The largest shard count that auto-splitting will split a tenant into
+    pub split_to: ShardCount,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardCountRecommendation {
+    pub tenant_id: TenantId,
+    pub shard_count: ShardCount,
+    /// Last known size of the tenant in bytes, if the storage controller has learned it.  Which size
+    /// this is depends on how the storage controller is configured to measure tenants for splitting.
+    pub size: Option<u64>,
+    pub recommended_shard_count: ShardCount,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantPlacementStatusShard {
+    pub tenant_shard_id: TenantShardId,
+    pub policy: PlacementPolicy,
+    pub attached: Option<NodeId>,
+    pub secondary: Vec<NodeId>,
+    /// Whether the intent has as many locations as the policy asks for
+    pub satisfied: bool,
+    /// Whether there are enough schedulable nodes to place as many locations as the policy asks for
+    pub satisfiable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantPlacementStatusResponse {
+    pub tenant_id: TenantId,
+    /// How many nodes may currently have locations scheduled onto them
+    pub schedulable_nodes: usize,
+    pub shards: Vec<TenantPlacementStatusShard>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantRedundancyStatusShard {
+    pub tenant_shard_id: TenantShardId,
+    /// How many secondary locations the placement policy asks for
+    pub secondaries_wanted: usize,
+    /// Secondary locations in the shard's intent
+    pub secondaries_scheduled: Vec<NodeId>,
+    /// Scheduled secondary locations which are on an available node and have been observed
+    /// configured as secondaries, i.e. which could take over if the attached location was lost
+    pub secondaries_ready: Vec<NodeId>,
+    /// Whether as many secondaries as the policy asks for are ready
+    pub redundant: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantRedundancyStatusResponse {
+    pub tenant_id: TenantId,
+    /// Whether every shard is redundant
+    pub redundant: bool,
+    pub shards: Vec<TenantRedundancyStatusShard>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantRebalanceResponse {
+    /// Shards whose locations were changed to improve the tenant's placement
+    pub shards: Vec<TenantShardId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardStickyRequest {
+    pub sticky: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardDetachRequest {
+    /// Permit detaching shard zero, which tenant-wide operations depend on
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantColocateRequest {
+    pub colocate: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantCanaryRequest {
+    pub canary: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardReadReplicaRequest {
+    /// If not set, the storage controller picks a node
+    #[serde(default)]
+    pub node_id: Option<NodeId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardReadReplicaResponse {
+    pub node_id: NodeId,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardSetGenerationRequest {
+    pub generation: u32,
+    /// Permit setting a generation lower than the current one
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardIncrementGenerationResponse {
+    /// The newly issued generation
+    pub generation: u32,
+    /// The attached node which the generation was issued to
+    pub node_id: NodeId,
+}
+
+/// Freezes are held in the storage controller's memory only: they do not survive a restart.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantGenerationFreezeRequest {
+    pub frozen: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantCancelReconcilesResponse {
+    /// How many shards had an in-flight reconcile that was cancelled
+    pub cancelled: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ObservedLocation {
+    pub node_id: NodeId,
+    /// None if we don't know how the location is configured on the node
+    pub conf: Option<LocationConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardCancelReconcileResponse {
+    /// Whether the shard had an in-flight reconcile that was cancelled
+    pub cancelled: bool,
+    /// The shard's observed locations once any cancelled reconcile has stopped, by node ID
+    pub observed: Vec<ObservedLocation>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantSecondaryDownloadResponse {
+    /// Totals across all secondary locations: `heatmap_mtime` is the most recent of them
+    #[serde(flatten)]
+    pub progress: SecondaryProgress,
+    /// The oldest heatmap downloaded by any secondary location: None if any of them has
+    /// not downloaded a heatmap yet
+    pub min_heatmap_mtime: Option<serde_system_time::SystemTime>,
+    /// The shard whose secondary location has the lowest fraction of its heatmap's bytes on disk
+    pub least_ready_shard: Option<TenantShardId>,
+    /// The fraction of `bytes_total` downloaded by `least_ready_shard`'s secondary location
+    pub least_ready_bytes_ratio: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -84,10 +319,14 @@ pub struct TenantLocateResponseShard {
 #[derive(Serialize, Deserialize)]
 pub struct TenantLocateResponse {
     pub shards: Vec<TenantLocateResponseShard>,
+    /// Additional locations in AttachedMulti mode that may serve reads for a shard.  There may
+    /// be any number of these per shard, including none.
+    #[serde(default)]
+    pub read_replicas: Vec<TenantLocateResponseShard>,
     pub shard_params: ShardParameters,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TenantDescribeResponse {
     pub tenant_id: TenantId,
     pub shards: Vec<TenantDescribeResponseShard>,
@@ -96,6 +335,20 @@ pub struct TenantDescribeResponse {
     pub config: TenantConfig,
 }
 
+/// Aggregate counts across all tenants, for callers which don't need the full
+/// [`TenantDescribeResponse`] of every tenant
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantSummaryResponse {
+    pub tenant_count: usize,
+    pub shard_count: usize,
+    pub reconciling_shard_count: usize,
+    pub splitting_shard_count: usize,
+    /// Shards whose last reconcile left an error behind
+    pub errored_shard_count: usize,
+    /// Attached shards per node, including nodes with none
+    pub node_attached_shards: BTreeMap<NodeId, usize>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct NodeDescribeResponse {
     pub id: NodeId,
@@ -108,9 +361,217 @@ pub struct NodeDescribeResponse {
 
     pub listen_pg_addr: String,
     pub listen_pg_port: u16,
+
+    #[serde(default)]
+    pub last_offline: Option<NodeOfflineReason>,
+
+    #[serde(default)]
+    pub capabilities: Option<NodeCapabilities>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ComputeNotificationDrift {
+    pub tenant_id: TenantId,
+    /// Attached pageserver of each shard, indexed by shard number
+    pub attached: Vec<NodeId>,
+    /// Pageservers in the last successful compute notification, if any
+    pub notified: Option<Vec<NodeId>>,
+    pub pending_compute_notification: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantRoutingStatusResponse {
+    pub tenant_id: TenantId,
+    pub shard0: TenantShardId,
+    /// Pageserver for shard zero in the last successful compute notification, if any
+    pub published: Option<NodeId>,
+    /// Pageserver that shard zero is intended to be attached to
+    pub intent: Option<NodeId>,
+    /// Pageserver that shard zero is attached to, if no change is in progress
+    pub stably_attached: Option<NodeId>,
+    /// True if computes may be routing shard zero requests to a different pageserver
+    /// than the one it is attached to
+    pub mismatch: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DelayedReconcileShard {
+    pub tenant_shard_id: TenantShardId,
+    /// How long the shard has been waiting for reconciler concurrency units
+    #[serde(with = "humantime_serde")]
+    pub waiting: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeRescanResponse {
+    /// Nodes that responded to the location listing
+    pub nodes_scanned: usize,
+    /// Shards whose observed state was rebuilt from the listings
+    pub shards_updated: usize,
+    pub reconciles_spawned: usize,
+    /// Locations of unknown shards that will be detached in the background
+    pub cleanup_locations: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationDriftKind {
+    /// The pageserver has a location that we have no record of
+    Unexpected,
+    /// We believe the pageserver has a location, but it does not
+    Missing,
+    /// The pageserver's location differs from ours in mode or generation
+    Mismatch,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LocationDrift {
+    pub tenant_shard_id: TenantShardId,
+    pub node_id: NodeId,
+    pub kind: LocationDriftKind,
+    /// What we last configured on the node, if we have a record of it
+    pub observed: Option<LocationConfig>,
+    /// What the node reported
+    pub reported: Option<LocationConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LocationDriftResponse {
+    /// Nodes that responded to the location listing: only their locations were audited
+    pub nodes_scanned: usize,
+    /// Shards left out because they reconciled or were splitting while we scanned
+    pub shards_skipped: usize,
+    pub drift: Vec<LocationDrift>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReconcileThroughputResponse {
+    /// The period over which rates are measured
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    pub succeeded_per_minute: f64,
+    pub failed_per_minute: f64,
+    /// Shards currently waiting for reconciler concurrency units
+    pub queued: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReconcileStatus {
+    /// The shard's latest intent has been reconciled
+    Done,
+    /// The most recent attempt to reconcile the shard's latest intent failed
+    Failed,
+    /// The shard's latest intent has not been reconciled yet
+    InProgress,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardReconcileStatus {
+    pub tenant_shard_id: TenantShardId,
+    pub status: ReconcileStatus,
+    /// Set if `status` is `Failed`
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantReconcileWatchResponse {
+    pub shards: Vec<TenantShardReconcileStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReconcileAttentionShard {
+    pub tenant_shard_id: TenantShardId,
+    /// Consecutive reconcile failures towards the shard's current intent
+    pub failures: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeFillPreviewShard {
+    pub tenant_shard_id: TenantShardId,
+    /// The node from which the shard's attachment would be moved
+    pub from_node_id: NodeId,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeFillPreviewResponse {
+    /// Shards whose secondary location on the node would be promoted by a fill
+    pub shards: Vec<NodeFillPreviewShard>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConsistencyCheckRelaxedResponse {
+    /// How many nodes matched between memory and the database
+    pub checked_nodes: usize,
+    /// Nodes that were not compared, because they changed during the check
+    pub skipped_nodes: Vec<NodeId>,
+    /// How many shards matched between memory and the database
+    pub checked_shards: usize,
+    /// Shards that were not compared, because they were reconciling, splitting or otherwise
+    /// changing during the check, or were only present in one of memory and the database
+    pub skipped_shards: Vec<TenantShardId>,
+}
+
+/// Liveness of one of the storage controller's internal background processors
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackgroundProcessorStatus {
+    pub name: String,
+    /// False if the processor exited unexpectedly: the storage controller needs restarting
+    pub alive: bool,
+    /// When the processor last did some work, if it has since the storage controller started
+    #[serde(default, with = "humantime_serde")]
+    pub last_active: Option<SystemTime>,
+    /// Items queued for the processor, for processors fed by a queue
+    pub backlog: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BackgroundProcessorsResponse {
+    pub processors: Vec<BackgroundProcessorStatus>,
+    /// Shards waiting for reconciler concurrency units
+    pub delayed_reconciles: usize,
+}
+
+/// Why and when the storage controller last marked a node offline, to help triage node failures
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NodeOfflineReason {
+    pub reason: String,
+    #[serde(with = "humantime_serde")]
+    pub at: SystemTime,
+}
+
+/// What caused a change to a node's availability or scheduling policy
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum NodeTransitionSource {
+    /// The heartbeater saw the node go offline or come back
+    Heartbeat,
+    /// An operator request to the node configuration API
+    Operator,
+    /// The storage controller itself, e.g. a drain or fill finishing or being cancelled
+    Controller,
+}
+
+/// One change to a node's availability and/or scheduling policy
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NodeTransition {
+    #[serde(with = "humantime_serde")]
+    pub at: SystemTime,
+    pub source: NodeTransitionSource,
+    /// The node's state after the transition
+    pub availability: NodeAvailabilityWrapper,
+    pub scheduling: NodeSchedulingPolicy,
+    /// Set if the heartbeater marked the node offline
+    pub offline_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeHistoryResponse {
+    pub node_id: NodeId,
+    /// Oldest first.  Only the most recent transitions are kept, and none survive a restart
+    /// of the storage controller.
+    pub transitions: Vec<NodeTransition>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TenantDescribeResponseShard {
     pub tenant_shard_id: TenantShardId,
 
@@ -118,6 +579,10 @@ pub struct TenantDescribeResponseShard {
     pub node_secondary: Vec<NodeId>,
 
     pub last_error: String,
+    /// Why the shard could not be scheduled the last time we tried, e.g. because too few nodes
+    /// were available.  None if the last attempt succeeded.
+    #[serde(default)]
+    pub last_scheduling_error: Option<String>,
 
     /// A task is currently running to reconcile this tenant's intent state with the state on pageservers
     pub is_reconciling: bool,
@@ -127,6 +592,22 @@ pub struct TenantDescribeResponseShard {
     pub is_splitting: bool,
 
     pub scheduling_policy: ShardSchedulingPolicy,
+    /// The optimizer will not move this shard
+    #[serde(default)]
+    pub sticky: bool,
+    /// The tenant's shards are packed onto as few nodes as possible
+    #[serde(default)]
+    pub colocate: bool,
+    /// Background optimization and auto-splitting leave this tenant alone
+    #[serde(default)]
+    pub canary: bool,
+    #[serde(default)]
+    pub priority: ReconcilePriority,
+    /// When the shard's intended locations last changed, if they have changed since the storage
+    /// controller started.  Once `is_reconciling` is false, the time since then is how long the
+    /// shard took to converge.
+    #[serde(default, with = "humantime_serde")]
+    pub intent_changed_at: Option<SystemTime>,
 }
 
 /// Explicitly migrating a particular shard is a low level operation
@@ -136,6 +617,24 @@ pub struct TenantDescribeResponseShard {
 pub struct TenantShardMigrateRequest {
     pub tenant_shard_id: TenantShardId,
     pub node_id: NodeId,
+    #[serde(default)]
+    pub migration_config: MigrationConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct MigrationConfig {
+    /// If the tenant is Detached, switch it to `Attached(0)` and place the shard in the same
+    /// call, rather than refusing to migrate it.  The policy applies to all the tenant's shards.
+    #[serde(default)]
+    pub attach_detached: bool,
+}
+
+/// Move one of a shard's secondary locations, e.g. to warm up a node ahead of migrating the
+/// attached location there
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TenantShardMigrateSecondaryRequest {
+    pub from_node_id: NodeId,
+    pub to_node_id: NodeId,
 }
 
 /// Utilisation score indicating how good a candidate a pageserver
@@ -225,6 +724,22 @@ impl Default for ShardSchedulingPolicy {
     }
 }
 
+/// Order in which tenants' shards get reconciler units when there are fewer units than shards
+/// needing reconciliation, e.g. during a node drain.  Variants are ordered from most to least
+/// urgent.
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum ReconcilePriority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Default for ReconcilePriority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
 pub enum NodeSchedulingPolicy {
     Active,
@@ -267,7 +782,9 @@ impl From<NodeSchedulingPolicy> for String {
 /// to create secondary locations.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum PlacementPolicy {
-    /// Normal live state: one attached pageserver and zero or more secondaries.
+    /// Normal live state: one attached pageserver and zero or more secondaries.  The value is
+    /// the number of secondary locations, so e.g. `Attached(2)` is one attached location plus
+    /// two secondaries.
     Attached(usize),
     /// Create one secondary mode locations. This is useful when onboarding
     /// a tenant, or for an idle tenant that we might want to bring online quickly.
@@ -282,6 +799,26 @@ pub enum PlacementPolicy {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TenantShardMigrateResponse {}
 
+/// The `code` in the body of an error response from the storage controller, when a request it
+/// passed through to a pageserver failed.  This tells apart failures with the same status code.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, strum_macros::IntoStaticStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum PassthroughErrorCode {
+    /// The pageserver could not be reached, or is temporarily unavailable: retry later
+    NodeUnavailable,
+    /// The pageserver does not have what was requested
+    NotFound,
+    /// The pageserver refused the storage controller's credentials: this is a misconfiguration
+    NodeUnauthorized,
+    /// The pageserver refused the request, e.g. because it conflicts with the pageserver's state
+    NodeRejected,
+    /// The storage controller is shutting down
+    ShuttingDown,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -2,6 +2,7 @@ use hyper::{header, Body, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 use std::error::Error as StdError;
+use std::time::Duration;
 use thiserror::Error;
 use tracing::{error, info, warn};
 
@@ -28,6 +29,10 @@ pub enum ApiError {
     #[error("Resource temporarily unavailable: {0}")]
     ResourceUnavailable(Cow<'static, str>),
 
+    /// Like [`Self::ResourceUnavailable`], with a hint of how long the client should wait before retrying
+    #[error("Resource temporarily unavailable: {0}")]
+    Busy(Cow<'static, str>, Duration),
+
     #[error("Shutting down")]
     ShuttingDown,
 
@@ -39,52 +44,60 @@ pub enum ApiError {
 
     #[error(transparent)]
     InternalServerError(anyhow::Error),
+
+    /// Like the wrapped error, with a machine-readable code in the response body, so that clients
+    /// can tell apart errors that have the same status
+    #[error("{1}")]
+    WithCode(&'static str, Box<ApiError>),
 }
 
 impl ApiError {
     pub fn into_response(self) -> Response<Body> {
+        self.into_response_with_code(None)
+    }
+
+    fn into_response_with_code(self, code: Option<&'static str>) -> Response<Body> {
+        let response = |msg: String, status: StatusCode| {
+            HttpErrorBody {
+                msg,
+                code: code.map(str::to_string),
+            }
+            .to_response(status)
+        };
+
         match self {
-            ApiError::BadRequest(err) => HttpErrorBody::response_from_msg_and_status(
+            ApiError::BadRequest(err) => response(
                 format!("{err:#?}"), // use debug printing so that we give the cause
                 StatusCode::BAD_REQUEST,
             ),
-            ApiError::Forbidden(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::FORBIDDEN)
+            ApiError::Forbidden(_) => response(self.to_string(), StatusCode::FORBIDDEN),
+            ApiError::Unauthorized(_) => response(self.to_string(), StatusCode::UNAUTHORIZED),
+            ApiError::NotFound(_) => response(self.to_string(), StatusCode::NOT_FOUND),
+            ApiError::Conflict(_) => response(self.to_string(), StatusCode::CONFLICT),
+            ApiError::PreconditionFailed(_) => {
+                response(self.to_string(), StatusCode::PRECONDITION_FAILED)
             }
-            ApiError::Unauthorized(_) => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::UNAUTHORIZED,
-            ),
-            ApiError::NotFound(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::NOT_FOUND)
+            ApiError::ShuttingDown => {
+                response("Shutting down".to_string(), StatusCode::SERVICE_UNAVAILABLE)
             }
-            ApiError::Conflict(_) => {
-                HttpErrorBody::response_from_msg_and_status(self.to_string(), StatusCode::CONFLICT)
+            ApiError::ResourceUnavailable(err) => {
+                response(err.to_string(), StatusCode::SERVICE_UNAVAILABLE)
             }
-            ApiError::PreconditionFailed(_) => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::PRECONDITION_FAILED,
-            ),
-            ApiError::ShuttingDown => HttpErrorBody::response_from_msg_and_status(
-                "Shutting down".to_string(),
-                StatusCode::SERVICE_UNAVAILABLE,
-            ),
-            ApiError::ResourceUnavailable(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::SERVICE_UNAVAILABLE,
-            ),
-            ApiError::Timeout(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::REQUEST_TIMEOUT,
-            ),
-            ApiError::Cancelled => HttpErrorBody::response_from_msg_and_status(
-                self.to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ),
-            ApiError::InternalServerError(err) => HttpErrorBody::response_from_msg_and_status(
-                err.to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ),
+            ApiError::Busy(err, retry_after) => {
+                let mut response = response(err.to_string(), StatusCode::SERVICE_UNAVAILABLE);
+                // Retry-After is in whole seconds: round up so that clients don't retry too early
+                let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, header::HeaderValue::from(secs));
+                response
+            }
+            ApiError::Timeout(err) => response(err.to_string(), StatusCode::REQUEST_TIMEOUT),
+            ApiError::Cancelled => response(self.to_string(), StatusCode::INTERNAL_SERVER_ERROR),
+            ApiError::InternalServerError(err) => {
+                response(err.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            ApiError::WithCode(code, err) => err.into_response_with_code(Some(code)),
         }
     }
 }
@@ -92,15 +105,18 @@ impl ApiError {
 #[derive(Serialize, Deserialize)]
 pub struct HttpErrorBody {
     pub msg: String,
+    /// Set for errors returned as [`ApiError::WithCode`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl HttpErrorBody {
     pub fn from_msg(msg: String) -> Self {
-        HttpErrorBody { msg }
+        HttpErrorBody { msg, code: None }
     }
 
     pub fn response_from_msg_and_status(msg: String, status: StatusCode) -> Response<Body> {
-        HttpErrorBody { msg }.to_response(status)
+        HttpErrorBody::from_msg(msg).to_response(status)
     }
 
     pub fn to_response(&self, status: StatusCode) -> Response<Body> {
@@ -135,9 +151,14 @@ pub fn api_error_handler(api_error: ApiError) -> Response<Body> {
         ApiError::Forbidden(_) | ApiError::Unauthorized(_) => {
             warn!("Error processing HTTP request: {api_error:#}")
         }
-        ApiError::ResourceUnavailable(_) => info!("Error processing HTTP request: {api_error:#}"),
+        ApiError::ResourceUnavailable(_) | ApiError::Busy(..) => {
+            info!("Error processing HTTP request: {api_error:#}")
+        }
         ApiError::NotFound(_) => info!("Error processing HTTP request: {api_error:#}"),
         ApiError::InternalServerError(_) => error!("Error processing HTTP request: {api_error:?}"),
+        ApiError::WithCode(_, ref err) if matches!(**err, ApiError::InternalServerError(_)) => {
+            error!("Error processing HTTP request: {api_error:?}")
+        }
         ApiError::ShuttingDown => info!("Shut down while processing HTTP request"),
         ApiError::Timeout(_) => info!("Timeout while processing HTTP request: {api_error:#}"),
         ApiError::Cancelled => info!("Request cancelled while processing HTTP request"),
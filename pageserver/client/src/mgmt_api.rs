@@ -48,7 +48,7 @@ impl ResponseErrorMessageExt for reqwest::Response {
 
         let url = self.url().to_owned();
         Err(match self.json::<HttpErrorBody>().await {
-            Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+            Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
             Err(_) => {
                 Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), url))
             }
@@ -296,15 +296,15 @@ impl Client {
             .map_err(Error::ReceiveBody)
     }
 
-    pub async fn tenant_heatmap_upload(&self, tenant_id: TenantShardId) -> Result<()> {
+    pub async fn tenant_heatmap_upload(&self, tenant_id: TenantShardId) -> Result<StatusCode> {
         let path = reqwest::Url::parse(&format!(
             "{}/v1/tenant/{}/heatmap_upload",
             self.mgmt_api_endpoint, tenant_id
         ))
         .expect("Cannot build URL");
 
-        self.request(Method::POST, path, ()).await?;
-        Ok(())
+        let response = self.request(Method::POST, path, ()).await?;
+        Ok(response.status())
     }
 
     pub async fn location_config(
@@ -525,7 +525,7 @@ impl Client {
             StatusCode::NOT_MODIFIED => Ok(false),
             // TODO: dedupe this pattern / introduce separate error variant?
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -549,7 +549,7 @@ impl Client {
             StatusCode::NOT_MODIFIED => Ok(false),
             // TODO: dedupe this pattern / introduce separate error variant?
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -573,7 +573,7 @@ impl Client {
         match resp.status() {
             StatusCode::OK => Ok(true),
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -602,7 +602,7 @@ impl Client {
                 Ok(resp)
             }
             status => Err(match resp.json::<HttpErrorBody>().await {
-                Ok(HttpErrorBody { msg }) => Error::ApiError(status, msg),
+                Ok(HttpErrorBody { msg, .. }) => Error::ApiError(status, msg),
                 Err(_) => {
                     Error::ReceiveErrorBody(format!("Http error ({}) at {}.", status.as_u16(), uri))
                 }
@@ -37,10 +37,12 @@ pub fn api_error_into_response(this: ApiError) -> Response<Full<Bytes>> {
             "Shutting down".to_string(),
             StatusCode::SERVICE_UNAVAILABLE,
         ),
-        ApiError::ResourceUnavailable(err) => HttpErrorBody::response_from_msg_and_status(
-            err.to_string(),
-            StatusCode::SERVICE_UNAVAILABLE,
-        ),
+        ApiError::ResourceUnavailable(err) | ApiError::Busy(err, _) => {
+            HttpErrorBody::response_from_msg_and_status(
+                err.to_string(),
+                StatusCode::SERVICE_UNAVAILABLE,
+            )
+        }
         ApiError::Timeout(err) => HttpErrorBody::response_from_msg_and_status(
             err.to_string(),
             StatusCode::REQUEST_TIMEOUT,
@@ -53,6 +55,7 @@ pub fn api_error_into_response(this: ApiError) -> Response<Full<Bytes>> {
             err.to_string(),
             StatusCode::INTERNAL_SERVER_ERROR,
         ),
+        ApiError::WithCode(_, err) => api_error_into_response(*err),
     }
 }
 
@@ -0,0 +1 @@
+ALTER TABLE tenant_shards add sticky BOOLEAN NOT NULL DEFAULT false;
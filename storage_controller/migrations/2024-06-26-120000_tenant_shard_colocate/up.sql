@@ -0,0 +1 @@
+ALTER TABLE tenant_shards add colocate BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1 @@
+ALTER TABLE tenant_shards drop canary;
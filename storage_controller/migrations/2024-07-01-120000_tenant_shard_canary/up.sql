@@ -0,0 +1 @@
+ALTER TABLE tenant_shards add canary BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1 @@
+ALTER TABLE nodes ADD capabilities VARCHAR;
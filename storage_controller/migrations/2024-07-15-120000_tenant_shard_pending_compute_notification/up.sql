@@ -0,0 +1 @@
+ALTER TABLE tenant_shards add pending_compute_notification BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1 @@
+ALTER TABLE tenant_shards add reconcile_priority VARCHAR NOT NULL DEFAULT '"Normal"';
@@ -3,6 +3,8 @@ use std::{borrow::Cow, fmt::Debug, fmt::Display};
 use tokio_util::sync::CancellationToken;
 use utils::id::NodeId;
 
+/// Reconciles that background node operations may have in flight between them: when several
+/// operations run at once, they share this budget, see [`operation_reconcile_budget`].
 pub(crate) const MAX_RECONCILES_PER_OPERATION: usize = 32;
 
 #[derive(Copy, Clone)]
@@ -37,6 +39,12 @@ pub(crate) struct OperationHandler {
     pub(crate) cancel: CancellationToken,
 }
 
+/// How many reconciles each operation may have in flight while `concurrent_operations` of them
+/// are running.  Every operation gets at least one, so that it always makes progress.
+pub(crate) fn operation_reconcile_budget(concurrent_operations: usize) -> usize {
+    (MAX_RECONCILES_PER_OPERATION / concurrent_operations.max(1)).max(1)
+}
+
 impl Display for Drain {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "drain {}", self.node_id)
@@ -57,3 +65,21 @@ impl Display for Operation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconcile_budget_is_shared() {
+        assert_eq!(operation_reconcile_budget(0), MAX_RECONCILES_PER_OPERATION);
+        assert_eq!(operation_reconcile_budget(1), MAX_RECONCILES_PER_OPERATION);
+        for concurrent in [2, 3, 5, 32] {
+            let budget = operation_reconcile_budget(concurrent);
+            assert!(budget >= 1);
+            assert!(budget * concurrent <= MAX_RECONCILES_PER_OPERATION);
+        }
+        // Operations beyond the budget still get to make progress
+        assert_eq!(operation_reconcile_budget(100), 1);
+    }
+}
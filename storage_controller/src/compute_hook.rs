@@ -15,6 +15,7 @@ use utils::{
     id::{NodeId, TenantId},
 };
 
+use crate::metrics::METRICS_REGISTRY;
 use crate::service::Config;
 
 const SLOWDOWN_DELAY: Duration = Duration::from_secs(5);
@@ -23,6 +24,13 @@ const NOTIFY_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub(crate) const API_CONCURRENCY: usize = 32;
 
+fn record_reachable(reachable: bool) {
+    METRICS_REGISTRY
+        .metrics_group
+        .storage_controller_compute_hook_reachable
+        .set(reachable as i64);
+}
+
 struct UnshardedComputeHookTenant {
     // Which node is this tenant attached to
     node_id: NodeId,
@@ -108,20 +116,42 @@ impl ComputeHookTenant {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 struct ComputeHookNotifyRequestShard {
     node_id: NodeId,
     shard_number: ShardNumber,
 }
 
 /// Request body that we send to the control plane to notify it of where a tenant is attached
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 struct ComputeHookNotifyRequest {
     tenant_id: TenantId,
     stripe_size: Option<ShardStripeSize>,
     shards: Vec<ComputeHookNotifyRequestShard>,
 }
 
+/// Request body for notifying the control plane about several tenants at once, sent instead of
+/// [`ComputeHookNotifyRequest`] when [`Config::compute_hook_batch_size`] is more than one.
+#[derive(Serialize, Deserialize, Debug)]
+struct ComputeHookNotifyBatchRequest {
+    tenants: Vec<ComputeHookNotifyRequest>,
+}
+
+/// A 2xx response to a [`ComputeHookNotifyBatchRequest`] must have a JSON body, which may list
+/// tenants that could not be updated, with the status code that a request for that tenant alone
+/// would have returned.  All other tenants in the batch were updated.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ComputeHookNotifyBatchResponse {
+    #[serde(default)]
+    failed: Vec<ComputeHookNotifyBatchFailure>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct ComputeHookNotifyBatchFailure {
+    tenant_id: TenantId,
+    status: u16,
+}
+
 /// Error type for attempts to call into the control plane compute notification hook
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum NotifyError {
@@ -149,6 +179,110 @@ pub(crate) enum NotifyError {
 
     #[error("neon_local error: {0}")]
     NeonLocal(anyhow::Error),
+
+    // Like Request, for a batched request: the error is shared by all tenants in the batch
+    #[error("Sending batched request: {0}")]
+    BatchRequest(String),
+}
+
+impl NotifyError {
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        match status {
+            reqwest::StatusCode::TOO_MANY_REQUESTS => NotifyError::SlowDown,
+            reqwest::StatusCode::LOCKED => NotifyError::Busy,
+            reqwest::StatusCode::SERVICE_UNAVAILABLE => {
+                NotifyError::Unavailable(StatusCode::SERVICE_UNAVAILABLE)
+            }
+            reqwest::StatusCode::GATEWAY_TIMEOUT => {
+                NotifyError::Unavailable(StatusCode::GATEWAY_TIMEOUT)
+            }
+            reqwest::StatusCode::BAD_GATEWAY => NotifyError::Unavailable(StatusCode::BAD_GATEWAY),
+
+            reqwest::StatusCode::BAD_REQUEST => NotifyError::Fatal(StatusCode::BAD_REQUEST),
+            reqwest::StatusCode::UNAUTHORIZED => NotifyError::Fatal(StatusCode::UNAUTHORIZED),
+            reqwest::StatusCode::FORBIDDEN => NotifyError::Fatal(StatusCode::FORBIDDEN),
+            status => NotifyError::Unexpected(
+                hyper::StatusCode::from_u16(status.as_u16())
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            ),
+        }
+    }
+
+    /// When a batched request fails as a whole, each tenant in the batch gets its own copy of
+    /// the error.
+    fn for_batch_member(&self) -> Self {
+        match self {
+            NotifyError::Request(e) => NotifyError::BatchRequest(e.to_string()),
+            NotifyError::Busy => NotifyError::Busy,
+            NotifyError::SlowDown => NotifyError::SlowDown,
+            NotifyError::Unavailable(s) => NotifyError::Unavailable(*s),
+            NotifyError::Unexpected(s) => NotifyError::Unexpected(*s),
+            NotifyError::ShuttingDown => NotifyError::ShuttingDown,
+            NotifyError::Fatal(s) => NotifyError::Fatal(*s),
+            NotifyError::NeonLocal(e) => NotifyError::BatchRequest(e.to_string()),
+            NotifyError::BatchRequest(e) => NotifyError::BatchRequest(e.clone()),
+        }
+    }
+}
+
+type BatchResultSender = tokio::sync::oneshot::Sender<Result<(), NotifyError>>;
+
+/// Notifications waiting to be sent together in one [`ComputeHookNotifyBatchRequest`]
+struct PendingBatch {
+    id: u64,
+    requests: Vec<(ComputeHookNotifyRequest, BatchResultSender)>,
+}
+
+#[derive(Default)]
+struct BatchState {
+    next_id: u64,
+    pending: Option<PendingBatch>,
+}
+
+enum BatchPush {
+    /// Started a new batch: the caller must arrange for it to be flushed after the batch interval
+    Started(u64),
+    /// Joined a batch that will be flushed by whoever started it
+    Joined,
+    /// Filled the batch: the caller must send it now
+    Full(PendingBatch),
+}
+
+impl BatchState {
+    fn push(
+        &mut self,
+        request: ComputeHookNotifyRequest,
+        tx: BatchResultSender,
+        max_size: usize,
+    ) -> BatchPush {
+        let started = self.pending.is_none();
+        let next_id = &mut self.next_id;
+        let batch = self.pending.get_or_insert_with(|| {
+            *next_id += 1;
+            PendingBatch {
+                id: *next_id,
+                requests: Vec::new(),
+            }
+        });
+        batch.requests.push((request, tx));
+
+        if batch.requests.len() >= max_size {
+            BatchPush::Full(self.pending.take().unwrap())
+        } else if started {
+            BatchPush::Started(batch.id)
+        } else {
+            BatchPush::Joined
+        }
+    }
+
+    /// Take the batch with this ID, unless it has already been taken because it filled up
+    fn take(&mut self, id: u64) -> Option<PendingBatch> {
+        if self.pending.as_ref().map(|b| b.id) == Some(id) {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
 }
 
 enum MaybeSendResult {
@@ -241,6 +375,12 @@ impl ComputeHookTenant {
 pub(super) struct ComputeHook {
     config: Config,
     state: std::sync::Mutex<HashMap<TenantId, ComputeHookTenant>>,
+
+    // The shard zero pageserver in the last notification successfully sent for each tenant.  Unlike
+    // the send lock contents, this is never cleared by [`Self::forget_notified`], so it always reflects
+    // what computes were last told.
+    published_shard0: std::sync::Mutex<HashMap<TenantId, NodeId>>,
+
     authorization_header: Option<String>,
 
     // Concurrency limiter, so that we do not overload the cloud control plane when updating
@@ -253,10 +393,17 @@ pub(super) struct ComputeHook {
     // We share a client across all notifications to enable connection re-use etc when
     // sending large numbers of notifications
     client: reqwest::Client,
+
+    // Notifications waiting to be sent in a batch, if batching is enabled
+    batch: std::sync::Mutex<BatchState>,
+
+    // Batches are sent by background tasks, rather than by whichever caller happens to fill one:
+    // this lets those tasks outlive any one caller's cancellation.
+    cancel: CancellationToken,
 }
 
 impl ComputeHook {
-    pub(super) fn new(config: Config) -> Self {
+    pub(super) fn new(config: Config, cancel: CancellationToken) -> Self {
         let authorization_header = config
             .control_plane_jwt_token
             .clone()
@@ -269,11 +416,14 @@ impl ComputeHook {
 
         Self {
             state: Default::default(),
+            published_shard0: Default::default(),
             config,
             authorization_header,
             neon_local_lock: Default::default(),
             api_concurrency: tokio::sync::Semaphore::new(API_CONCURRENCY),
             client,
+            batch: Default::default(),
+            cancel,
         }
     }
 
@@ -332,12 +482,12 @@ impl ComputeHook {
         Ok(())
     }
 
-    async fn do_notify_iteration(
+    async fn do_notify_iteration<B: Serialize + std::fmt::Debug>(
         &self,
         url: &String,
-        reconfigure_request: &ComputeHookNotifyRequest,
+        reconfigure_request: &B,
         cancel: &CancellationToken,
-    ) -> Result<(), NotifyError> {
+    ) -> Result<reqwest::Response, NotifyError> {
         let req = self.client.request(reqwest::Method::PUT, url);
         let req = if let Some(value) = &self.authorization_header {
             req.header(reqwest::header::AUTHORIZATION, value)
@@ -351,6 +501,7 @@ impl ComputeHook {
             reconfigure_request
         );
         let send_result = req.json(&reconfigure_request).send().await;
+        record_reachable(send_result.is_ok());
         let response = match send_result {
             Ok(r) => r,
             Err(e) => return Err(e.into()),
@@ -369,7 +520,7 @@ impl ComputeHook {
                 );
             }
 
-            return Ok(());
+            return Ok(response);
         }
 
         // Error response codes
@@ -381,33 +532,36 @@ impl ComputeHook {
                 tokio::time::timeout(SLOWDOWN_DELAY, cancel.cancelled())
                     .await
                     .ok();
-                Err(NotifyError::SlowDown)
             }
             reqwest::StatusCode::LOCKED => {
                 // We consider this fatal, because it's possible that the operation blocking the control one is
                 // also the one that is waiting for this reconcile.  We should let the reconciler calling
                 // this hook fail, to give control plane a chance to un-lock.
                 tracing::info!("Control plane reports tenant is locked, dropping out of notify");
-                Err(NotifyError::Busy)
-            }
-            reqwest::StatusCode::SERVICE_UNAVAILABLE => {
-                Err(NotifyError::Unavailable(StatusCode::SERVICE_UNAVAILABLE))
-            }
-            reqwest::StatusCode::GATEWAY_TIMEOUT => {
-                Err(NotifyError::Unavailable(StatusCode::GATEWAY_TIMEOUT))
-            }
-            reqwest::StatusCode::BAD_GATEWAY => {
-                Err(NotifyError::Unavailable(StatusCode::BAD_GATEWAY))
             }
-
-            reqwest::StatusCode::BAD_REQUEST => Err(NotifyError::Fatal(StatusCode::BAD_REQUEST)),
-            reqwest::StatusCode::UNAUTHORIZED => Err(NotifyError::Fatal(StatusCode::UNAUTHORIZED)),
-            reqwest::StatusCode::FORBIDDEN => Err(NotifyError::Fatal(StatusCode::FORBIDDEN)),
-            status => Err(NotifyError::Unexpected(
-                hyper::StatusCode::from_u16(status.as_u16())
-                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-            )),
+            _ => {}
         }
+        Err(NotifyError::from_status(response.status()))
+    }
+
+    /// Check that the configured compute hook URL can be reached, without notifying anything.  Any
+    /// HTTP response counts as success, even an error status: we only want to detect a URL that is
+    /// misconfigured or blocked by the network, before it causes every notification to fail.
+    pub(super) async fn probe(&self) -> Result<(), reqwest::Error> {
+        let Some(url) = &self.config.compute_hook_url else {
+            return Ok(());
+        };
+
+        let req = self.client.request(reqwest::Method::HEAD, url);
+        let req = if let Some(value) = &self.authorization_header {
+            req.header(reqwest::header::AUTHORIZATION, value)
+        } else {
+            req
+        };
+
+        let result = req.send().await.map(|_| ());
+        record_reachable(result.is_ok());
+        result
     }
 
     async fn do_notify(
@@ -442,6 +596,181 @@ impl ComputeHook {
         .await
         .ok_or_else(|| NotifyError::ShuttingDown)
         .and_then(|x| x)
+        .map(|_| ())
+    }
+
+    /// Like [`Self::do_notify`], but add the request to a batch of notifications for several
+    /// tenants, and wait for the batch to be sent.
+    async fn do_notify_batched(
+        self: &Arc<Self>,
+        url: &str,
+        reconfigure_request: ComputeHookNotifyRequest,
+        cancel: &CancellationToken,
+    ) -> Result<(), NotifyError> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let push = self.batch.lock().unwrap().push(
+            reconfigure_request,
+            tx,
+            self.config.compute_hook_batch_size,
+        );
+
+        match push {
+            BatchPush::Started(id) => {
+                let this = self.clone();
+                let url = url.to_string();
+                tokio::task::spawn(async move {
+                    tokio::select! {
+                        _ = tokio::time::sleep(this.config.compute_hook_batch_interval) => {},
+                        _ = this.cancel.cancelled() => {}
+                    }
+                    let batch = this.batch.lock().unwrap().take(id);
+                    if let Some(batch) = batch {
+                        this.send_batch(&url, batch).await;
+                    }
+                });
+            }
+            BatchPush::Joined => {}
+            BatchPush::Full(batch) => {
+                let this = self.clone();
+                let url = url.to_string();
+                tokio::task::spawn(async move { this.send_batch(&url, batch).await });
+            }
+        }
+
+        tokio::select! {
+            // If the batch was dropped unsent, we are shutting down
+            result = rx => result.unwrap_or(Err(NotifyError::ShuttingDown)),
+            _ = cancel.cancelled() => Err(NotifyError::ShuttingDown)
+        }
+    }
+
+    /// Send a batch of notifications, with the same retries as [`Self::do_notify`], and pass each
+    /// tenant's result back to its caller.
+    async fn send_batch(&self, url: &str, batch: PendingBatch) {
+        let (requests, senders): (Vec<_>, Vec<_>) = batch.requests.into_iter().unzip();
+        let tenant_ids = requests.iter().map(|r| r.tenant_id).collect::<Vec<_>>();
+        let body = ComputeHookNotifyBatchRequest { tenants: requests };
+        let url = url.to_string();
+
+        let result = match self.api_concurrency.acquire().await {
+            Err(_) => Err(NotifyError::ShuttingDown),
+            Ok(_units) => backoff::retry(
+                || async {
+                    let response = self.do_notify_iteration(&url, &body, &self.cancel).await?;
+                    // We can only tell which tenants succeeded from the body: if we can't read
+                    // it, treat the whole batch as failed rather than assume that all succeeded.
+                    let status = response.status();
+                    response
+                        .json::<ComputeHookNotifyBatchResponse>()
+                        .await
+                        .map_err(|e| {
+                            tracing::warn!("Unreadable batched compute notification response: {e}");
+                            NotifyError::Unexpected(status)
+                        })
+                },
+                |e| {
+                    matches!(
+                        e,
+                        NotifyError::Fatal(_) | NotifyError::Unexpected(_) | NotifyError::Busy
+                    )
+                },
+                3,
+                10,
+                "Send batched compute notification",
+                &self.cancel,
+            )
+            .await
+            .ok_or_else(|| NotifyError::ShuttingDown)
+            .and_then(|x| x),
+        };
+
+        let failed = match &result {
+            Ok(response) => response
+                .failed
+                .iter()
+                .map(|f| (f.tenant_id, f.status))
+                .collect::<HashMap<_, _>>(),
+            Err(e) => {
+                tracing::warn!(
+                    "Batched compute notification for {} tenants failed: {e}",
+                    tenant_ids.len()
+                );
+                HashMap::new()
+            }
+        };
+        if !failed.is_empty() {
+            tracing::warn!(
+                "Batched compute notification failed for {}/{} tenants",
+                failed.len(),
+                tenant_ids.len()
+            );
+        }
+
+        for (tenant_id, tx) in tenant_ids.into_iter().zip(senders) {
+            let tenant_result = match &result {
+                Err(e) => Err(e.for_batch_member()),
+                Ok(_) => match failed.get(&tenant_id) {
+                    None => Ok(()),
+                    Some(status) => Err(NotifyError::from_status(
+                        reqwest::StatusCode::from_u16(*status)
+                            .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR),
+                    )),
+                },
+            };
+            // The caller may have given up waiting
+            tx.send(tenant_result).ok();
+        }
+    }
+
+    /// The pageservers in the last notification successfully sent for each tenant, indexed by
+    /// shard number.  Tenants that we have never notified are absent, and tenants with a
+    /// notification currently in flight map to None.
+    pub(super) fn last_notified(&self) -> HashMap<TenantId, Option<Vec<NodeId>>> {
+        let state_locked = self.state.lock().unwrap();
+
+        let mut result = HashMap::new();
+        for (tenant_id, tenant) in state_locked.iter() {
+            // Lock order: only try_lock the send lock while holding [`Self::state`], as in maybe_send
+            match tenant.get_send_lock().try_lock() {
+                Ok(sent) => {
+                    if let Some(request) = sent.as_ref() {
+                        result.insert(
+                            *tenant_id,
+                            Some(request.shards.iter().map(|s| s.node_id).collect()),
+                        );
+                    }
+                }
+                Err(_) => {
+                    result.insert(*tenant_id, None);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The shard zero pageserver in the last notification successfully sent for a tenant, i.e. where
+    /// computes were last told to route requests for shard zero.  None if we have never notified
+    /// the compute about this tenant since startup.
+    pub(super) fn published_shard0(&self, tenant_id: TenantId) -> Option<NodeId> {
+        self.published_shard0
+            .lock()
+            .unwrap()
+            .get(&tenant_id)
+            .copied()
+    }
+
+    /// Forget what we last sent for every tenant, so that the next notification for each tenant
+    /// is sent even if it is identical to the last one.  Tenants with a notification in flight
+    /// are skipped: whatever they are sending is about to become the last-sent value anyway.
+    pub(super) fn forget_notified(&self) {
+        let state_locked = self.state.lock().unwrap();
+        for tenant in state_locked.values() {
+            // Lock order: only try_lock the send lock while holding [`Self::state`], as in maybe_send
+            if let Ok(mut sent) = tenant.get_send_lock().try_lock() {
+                *sent = None;
+            }
+        }
     }
 
     /// Synchronous phase: update the per-tenant state for the next intended notification
@@ -470,7 +799,7 @@ impl ComputeHook {
     }
 
     async fn notify_execute(
-        &self,
+        self: &Arc<Self>,
         maybe_send_result: MaybeSendResult,
         tenant_shard_id: TenantShardId,
         cancel: &CancellationToken,
@@ -510,7 +839,12 @@ impl ComputeHook {
         };
 
         let result = if let Some(notify_url) = &self.config.compute_hook_url {
-            self.do_notify(notify_url, &request, cancel).await
+            if self.config.compute_hook_batch_size > 1 {
+                self.do_notify_batched(notify_url, request.clone(), cancel)
+                    .await
+            } else {
+                self.do_notify(notify_url, &request, cancel).await
+            }
         } else {
             self.do_notify_local(&request).await.map_err(|e| {
                 // This path is for testing only, so munge the error into our prod-style error type.
@@ -520,6 +854,17 @@ impl ComputeHook {
         };
 
         if result.is_ok() {
+            if let Some(shard0) = request
+                .shards
+                .iter()
+                .find(|s| s.shard_number == ShardNumber(0))
+            {
+                self.published_shard0
+                    .lock()
+                    .unwrap()
+                    .insert(request.tenant_id, shard0.node_id);
+            }
+
             // Before dropping the send lock, stash the request we just sent so that
             // subsequent callers can avoid redundantly re-sending the same thing.
             *send_lock_guard = Some(request);
@@ -604,7 +949,7 @@ impl ComputeHook {
     /// the proper pageserver nodes for a tenant.
     #[tracing::instrument(skip_all, fields(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), node_id))]
     pub(super) async fn notify(
-        &self,
+        self: &Arc<Self>,
         tenant_shard_id: TenantShardId,
         node_id: NodeId,
         stripe_size: ShardStripeSize,
@@ -694,4 +1039,80 @@ pub(crate) mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn batch_assembly() -> anyhow::Result<()> {
+        let request = || ComputeHookNotifyRequest {
+            tenant_id: TenantId::generate(),
+            stripe_size: None,
+            shards: vec![ComputeHookNotifyRequestShard {
+                node_id: NodeId(1),
+                shard_number: ShardNumber(0),
+            }],
+        };
+        let mut state = BatchState::default();
+        let mut receivers = Vec::new();
+        let mut push = |state: &mut BatchState| {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            receivers.push(rx);
+            state.push(request(), tx, 3)
+        };
+
+        // The first caller starts a batch, which fills up on the third
+        let BatchPush::Started(first_id) = push(&mut state) else {
+            anyhow::bail!("Expected a new batch");
+        };
+        assert!(matches!(push(&mut state), BatchPush::Joined));
+        let BatchPush::Full(full) = push(&mut state) else {
+            anyhow::bail!("Expected a full batch");
+        };
+        assert_eq!(full.id, first_id);
+        assert_eq!(full.requests.len(), 3);
+
+        // The filled batch was taken, so the first caller's timer finds nothing to send
+        assert!(state.take(first_id).is_none());
+
+        // The next caller starts a new batch, which its timer can take
+        let BatchPush::Started(second_id) = push(&mut state) else {
+            anyhow::bail!("Expected a new batch");
+        };
+        assert_ne!(second_id, first_id);
+        assert_eq!(state.take(second_id).map(|b| b.requests.len()), Some(1));
+        assert!(state.take(second_id).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_response() {
+        let tenant_id = TenantId::generate();
+
+        // An empty object means no tenant failed, but the body may not be missing
+        let response: ComputeHookNotifyBatchResponse = serde_json::from_str("{}").unwrap();
+        assert!(response.failed.is_empty());
+        assert!(serde_json::from_str::<ComputeHookNotifyBatchResponse>("").is_err());
+
+        let response: ComputeHookNotifyBatchResponse = serde_json::from_str(&format!(
+            r#"{{"failed": [{{"tenant_id": "{tenant_id}", "status": 423}}]}}"#
+        ))
+        .unwrap();
+        assert_eq!(response.failed.len(), 1);
+        assert_eq!(response.failed[0].tenant_id, tenant_id);
+        assert!(matches!(
+            NotifyError::from_status(
+                reqwest::StatusCode::from_u16(response.failed[0].status).unwrap()
+            ),
+            NotifyError::Busy
+        ));
+
+        // Errors for a whole batch are copied to each of its tenants
+        assert!(matches!(
+            NotifyError::Fatal(StatusCode::BAD_REQUEST).for_batch_member(),
+            NotifyError::Fatal(StatusCode::BAD_REQUEST)
+        ));
+        assert!(matches!(
+            NotifyError::NeonLocal(anyhow::anyhow!("failed")).for_batch_member(),
+            NotifyError::BatchRequest(_)
+        ));
+    }
 }
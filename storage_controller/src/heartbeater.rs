@@ -33,7 +33,9 @@ pub(crate) enum PageserverState {
         utilization: PageserverUtilization,
         new: bool,
     },
-    Offline,
+    Offline {
+        reason: String,
+    },
 }
 
 #[derive(Debug)]
@@ -157,14 +159,15 @@ impl HeartbeaterTask {
                         }
                     };
 
-                    let status = if let Ok(utilization) = response {
-                        PageserverState::Available {
+                    let status = match response {
+                        Ok(utilization) => PageserverState::Available {
                             last_seen_at: Instant::now(),
                             utilization,
                             new: new_node,
-                        }
-                    } else {
-                        PageserverState::Offline
+                        },
+                        Err(e) => PageserverState::Offline {
+                            reason: e.to_string(),
+                        },
                     };
 
                     Some((*node_id, status))
@@ -196,7 +199,7 @@ impl HeartbeaterTask {
                     PageserverState::Available { .. } => {
                         false
                     }
-                    PageserverState::Offline => true,
+                    PageserverState::Offline { .. } => true,
                 })
                 .count()
         );
@@ -210,8 +213,11 @@ impl HeartbeaterTask {
             let mut needs_update = false;
             match entry {
                 Occupied(ref occ) => match (occ.get(), &ps_state) {
-                    (PageserverState::Offline, PageserverState::Offline) => {}
-                    (PageserverState::Available { last_seen_at, .. }, PageserverState::Offline) => {
+                    (PageserverState::Offline { .. }, PageserverState::Offline { .. }) => {}
+                    (
+                        PageserverState::Available { last_seen_at, .. },
+                        PageserverState::Offline { .. },
+                    ) => {
                         if now - *last_seen_at >= self.max_unavailable_interval {
                             deltas.push((node_id, ps_state.clone()));
                             needs_update = true;
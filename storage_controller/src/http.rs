@@ -3,7 +3,8 @@ use crate::metrics::{
     METRICS_REGISTRY,
 };
 use crate::reconciler::ReconcileError;
-use crate::service::{Service, STARTUP_RECONCILE_TIMEOUT};
+use crate::service::{Service, RECONCILE_WATCH_MAX_TIMEOUT};
+use crate::tenant_shard::TenantFlag;
 use anyhow::Context;
 use futures::Future;
 use hyper::header::CONTENT_TYPE;
@@ -22,6 +23,7 @@ use std::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 use utils::auth::{Scope, SwappableJwtAuth};
 use utils::failpoint_support::failpoints_handler;
+use utils::generation::Generation;
 use utils::http::endpoint::{auth_middleware, check_permission_with, request_span};
 use utils::http::request::{must_get_query_param, parse_query_param, parse_request_param};
 use utils::id::{TenantId, TimelineId};
@@ -37,8 +39,12 @@ use utils::{
 };
 
 use pageserver_api::controller_api::{
-    NodeAvailability, NodeConfigureRequest, NodeRegisterRequest, TenantPolicyRequest,
-    TenantShardMigrateRequest,
+    AutosplitConfig, NodeAvailability, NodeConfigureRequest, NodeMigrateTenantsRequest,
+    NodeRegisterRequest, NodeTransitionSource, TenantCanaryRequest, TenantColocateRequest,
+    TenantEvacuateNodeRequest, TenantGenerationFreezeRequest, TenantPolicyRequest,
+    TenantSetPlacementRequest, TenantShardDetachRequest, TenantShardMigrateRequest,
+    TenantShardMigrateSecondaryRequest, TenantShardReadReplicaRequest,
+    TenantShardSetGenerationRequest, TenantShardStickyRequest, TenantsReconcileRequest,
 };
 use pageserver_api::upcall_api::{ReAttachRequest, ValidateRequest};
 
@@ -135,11 +141,13 @@ async fn handle_tenant_create(
 ) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::PageServerApi)?;
 
+    let reconcile_timeout =
+        parse_query_param(&req, "reconcile_timeout_ms")?.map(Duration::from_millis);
     let create_req = json_request::<TenantCreateRequest>(&mut req).await?;
 
     json_response(
         StatusCode::CREATED,
-        service.tenant_create(create_req).await?,
+        service.tenant_create(create_req, reconcile_timeout).await?,
     )
 }
 
@@ -150,11 +158,13 @@ async fn handle_tenant_location_config(
     let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
     check_permissions(&req, Scope::PageServerApi)?;
 
+    let reconcile_timeout =
+        parse_query_param(&req, "reconcile_timeout_ms")?.map(Duration::from_millis);
     let config_req = json_request::<TenantLocationConfigRequest>(&mut req).await?;
     json_response(
         StatusCode::OK,
         service
-            .tenant_location_config(tenant_shard_id, config_req)
+            .tenant_location_config(tenant_shard_id, config_req, reconcile_timeout)
             .await?,
     )
 }
@@ -410,6 +420,16 @@ async fn handle_tenant_locate(
     json_response(StatusCode::OK, service.tenant_locate(tenant_id)?)
 }
 
+async fn handle_tenant_validate_layout(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.tenant_validate_layout(tenant_id)?)
+}
+
 async fn handle_tenant_describe(
     service: Arc<Service>,
     req: Request<Body>,
@@ -420,6 +440,23 @@ async fn handle_tenant_describe(
     json_response(StatusCode::OK, service.tenant_describe(tenant_id)?)
 }
 
+async fn handle_tenant_reconcile_watch(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let wait = parse_query_param(&req, "wait_ms")?
+        .map(Duration::from_millis)
+        .unwrap_or(RECONCILE_WATCH_MAX_TIMEOUT);
+
+    json_response(
+        StatusCode::OK,
+        service.tenant_reconcile_watch(tenant_id, wait).await?,
+    )
+}
+
 async fn handle_tenant_list(
     service: Arc<Service>,
     req: Request<Body>,
@@ -429,6 +466,15 @@ async fn handle_tenant_list(
     json_response(StatusCode::OK, service.tenant_list())
 }
 
+async fn handle_tenant_summary(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    json_response(StatusCode::OK, service.tenant_summary())
+}
+
 async fn handle_node_register(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
@@ -456,6 +502,14 @@ async fn handle_node_drop(req: Request<Body>) -> Result<Response<Body>, ApiError
     json_response(StatusCode::OK, state.service.node_drop(node_id).await?)
 }
 
+async fn handle_node_delete(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+    json_response(StatusCode::OK, state.service.node_delete(node_id).await?)
+}
+
 async fn handle_node_configure(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
@@ -476,11 +530,62 @@ async fn handle_node_configure(mut req: Request<Body>) -> Result<Response<Body>,
                 config_req.node_id,
                 config_req.availability.map(NodeAvailability::from),
                 config_req.scheduling,
+                NodeTransitionSource::Operator,
+                None,
             )
             .await?,
     )
 }
 
+async fn handle_node_migrate_tenants(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+    let migrate_req = json_request::<NodeMigrateTenantsRequest>(&mut req).await?;
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .migrate_tenants_off_node(node_id, migrate_req.tenant_ids)
+            .await?,
+    )
+}
+
+async fn handle_get_autosplit_config(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.get_autosplit_config())
+}
+
+async fn handle_update_autosplit_config(
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let config_req = json_request::<AutosplitConfig>(&mut req).await?;
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .update_autosplit_config(config_req.split_threshold, config_req.split_to)?,
+    )
+}
+
+async fn handle_node_history(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    json_response(StatusCode::OK, state.service.node_history(node_id)?)
+}
+
 async fn handle_node_status(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
@@ -489,154 +594,643 @@ async fn handle_node_status(req: Request<Body>) -> Result<Response<Body>, ApiErr
 
     let node_status = state.service.get_node(node_id).await?;
 
-    json_response(StatusCode::OK, node_status)
+    json_response(StatusCode::OK, node_status)
+}
+
+async fn handle_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.start_node_drain(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_node_cordon(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.node_cordon(node_id).await?;
+
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_node_uncordon(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.node_uncordon(node_id).await?;
+
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_cancel_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.cancel_node_drain(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.start_node_fill(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_node_fill_preview(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    json_response(StatusCode::OK, state.service.fill_node_preview(node_id)?)
+}
+
+async fn handle_cancel_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+
+    state.service.cancel_node_fill(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_tenant_shard_split(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let split_req = json_request::<TenantShardSplitRequest>(&mut req).await?;
+
+    json_response(
+        StatusCode::OK,
+        service.tenant_shard_split(tenant_id, split_req).await?,
+    )
+}
+
+async fn handle_tenant_shard_migrate(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let reconcile_timeout =
+        parse_query_param(&req, "reconcile_timeout_ms")?.map(Duration::from_millis);
+    let migrate_req = json_request::<TenantShardMigrateRequest>(&mut req).await?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_shard_migrate(tenant_shard_id, migrate_req, reconcile_timeout)
+            .await?,
+    )
+}
+
+async fn handle_tenant_shard_migrate_secondary(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let migrate_req = json_request::<TenantShardMigrateSecondaryRequest>(&mut req).await?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_shard_migrate_secondary(
+                tenant_shard_id,
+                migrate_req.from_node_id,
+                migrate_req.to_node_id,
+            )
+            .await?,
+    )
+}
+
+async fn handle_tenant_shard_heatmap_upload(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let status = service
+        .tenant_shard_heatmap_upload(tenant_shard_id)
+        .await
+        .and_then(map_reqwest_hyper_status)?;
+    json_response(status, ())
+}
+
+async fn handle_tenant_shard_failover(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    json_response(
+        StatusCode::OK,
+        service.tenant_shard_failover(tenant_shard_id).await?,
+    )
+}
+
+async fn handle_tenant_shard_cancel_reconcile(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_shard_cancel_reconcile(tenant_shard_id)
+            .await?,
+    )
+}
+
+async fn handle_tenant_shard_increment_generation(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_shard_increment_generation(tenant_shard_id)
+            .await?,
+    )
+}
+
+async fn handle_tenant_update_policy(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let update_req = json_request::<TenantPolicyRequest>(&mut req).await?;
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .tenant_update_policy(tenant_id, update_req)
+            .await?,
+    )
+}
+
+async fn handle_tenant_generation_freeze(
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let freeze_req = json_request::<TenantGenerationFreezeRequest>(&mut req).await?;
+    let state = get_state(&req);
+
+    state
+        .service
+        .tenant_generation_freeze(tenant_id, freeze_req.frozen);
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_evacuate_node(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let evacuate_req = json_request::<TenantEvacuateNodeRequest>(&mut req).await?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_evacuate_node(tenant_id, evacuate_req.node_id)
+            .await?,
+    )
+}
+
+async fn handle_tenant_recommend_shard_count(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.recommend_shard_count(tenant_id)?)
+}
+
+async fn handle_tenant_redundancy_status(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.tenant_redundancy_status(tenant_id)?)
+}
+
+async fn handle_tenant_placement_status(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.tenant_placement_status(tenant_id)?)
+}
+
+async fn handle_tenant_routing_status(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.tenant_routing_status(tenant_id)?)
+}
+
+async fn handle_tenant_shard_split_cancel(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    service.cancel_tenant_shard_split(tenant_id)?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_rebalance(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    json_response(StatusCode::OK, service.tenant_rebalance(tenant_id).await?)
+}
+
+async fn handle_tenant_set_placement(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let placement_req = json_request::<TenantSetPlacementRequest>(&mut req).await?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_set_placement(tenant_id, placement_req)
+            .await?,
+    )
+}
+
+async fn handle_tenant_shard_sticky(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let sticky_req = json_request::<TenantShardStickyRequest>(&mut req).await?;
+    service
+        .tenant_shard_set_sticky(tenant_shard_id, sticky_req.sticky)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_shard_detach(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let detach_req = json_request::<TenantShardDetachRequest>(&mut req).await?;
+    service
+        .tenant_shard_detach(tenant_shard_id, detach_req.force)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_shard_reattach(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    service.tenant_shard_reattach(tenant_shard_id).await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_colocate(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let colocate_req = json_request::<TenantColocateRequest>(&mut req).await?;
+    service
+        .tenant_set_flag(tenant_id, TenantFlag::Colocate, colocate_req.colocate)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_canary(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let canary_req = json_request::<TenantCanaryRequest>(&mut req).await?;
+    service
+        .tenant_set_flag(tenant_id, TenantFlag::Canary, canary_req.canary)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_shard_add_read_replica(
+    service: Arc<Service>,
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let replica_req = json_request::<TenantShardReadReplicaRequest>(&mut req).await?;
+    json_response(
+        StatusCode::OK,
+        service
+            .tenant_shard_add_read_replica(tenant_shard_id, replica_req)
+            .await?,
+    )
+}
+
+async fn handle_tenant_shard_remove_read_replica(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let node_id: NodeId = parse_request_param(&req, "node_id")?;
+    service
+        .tenant_shard_remove_read_replica(tenant_shard_id, node_id)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_shard_set_generation(
+    mut req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let set_req = json_request::<TenantShardSetGenerationRequest>(&mut req).await?;
+    let state = get_state(&req);
+
+    state
+        .service
+        .tenant_shard_set_generation(
+            tenant_shard_id,
+            Generation::new(set_req.generation),
+            set_req.force,
+        )
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
+async fn handle_tenant_cancel_reconciles(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::OK,
+        state.service.tenant_cancel_reconciles(tenant_id)?,
+    )
+}
+
+async fn handle_tenant_drop(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    check_permissions(&req, Scope::PageServerApi)?;
+
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.tenant_drop(tenant_id).await?)
+}
+
+async fn handle_tenant_import(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    check_permissions(&req, Scope::PageServerApi)?;
+
+    let state = get_state(&req);
+
+    json_response(
+        StatusCode::OK,
+        state.service.tenant_import(tenant_id).await?,
+    )
+}
+
+/// Whether the client will accept a gzip-compressed response body
+fn accepts_gzip(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(hyper::header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|encoding| encoding.split(';').next().map(str::trim) == Some("gzip"))
+}
+
+async fn handle_tenants_dump(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    state.service.tenants_dump(accepts_gzip(&req))
+}
+
+async fn handle_tenant_shard_observed(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
+    let state = get_state(&req);
+    json_response(
+        StatusCode::OK,
+        state.service.tenant_shard_observed(tenant_shard_id)?,
+    )
+}
+
+async fn handle_scheduler_dump(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    state.service.scheduler_dump(accepts_gzip(&req))
+}
+
+async fn handle_state_snapshot(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+    state.service.state_snapshot(accepts_gzip(&req))
+}
+
+async fn handle_delayed_reconciles(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.delayed_reconcile_tenants())
 }
 
-async fn handle_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_detect_drift(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    let node_id: NodeId = parse_request_param(&req, "node_id")?;
-
-    state.service.start_node_drain(node_id).await?;
 
-    json_response(StatusCode::ACCEPTED, ())
+    json_response(StatusCode::OK, state.service.detect_drift().await?)
 }
 
-async fn handle_cancel_node_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_renotify_compute(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    let node_id: NodeId = parse_request_param(&req, "node_id")?;
 
-    state.service.cancel_node_drain(node_id).await?;
-
-    json_response(StatusCode::ACCEPTED, ())
+    json_response(StatusCode::OK, state.service.renotify_all_compute())
 }
 
-async fn handle_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_background_processors(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    let node_id: NodeId = parse_request_param(&req, "node_id")?;
-
-    state.service.start_node_fill(node_id).await?;
 
-    json_response(StatusCode::ACCEPTED, ())
+    json_response(StatusCode::OK, state.service.background_processors())
 }
 
-async fn handle_cancel_node_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_reconcile_throughput(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    let node_id: NodeId = parse_request_param(&req, "node_id")?;
-
-    state.service.cancel_node_fill(node_id).await?;
 
-    json_response(StatusCode::ACCEPTED, ())
+    json_response(StatusCode::OK, state.service.reconcile_throughput())
 }
 
-async fn handle_tenant_shard_split(
-    service: Arc<Service>,
-    mut req: Request<Body>,
-) -> Result<Response<Body>, ApiError> {
+async fn handle_reconcile_attention(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
-    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
-    let split_req = json_request::<TenantShardSplitRequest>(&mut req).await?;
+    let state = get_state(&req);
 
-    json_response(
-        StatusCode::OK,
-        service.tenant_shard_split(tenant_id, split_req).await?,
-    )
+    json_response(StatusCode::OK, state.service.reconcile_attention_tenants())
 }
 
-async fn handle_tenant_shard_migrate(
-    service: Arc<Service>,
-    mut req: Request<Body>,
-) -> Result<Response<Body>, ApiError> {
+async fn handle_compute_notification_drift(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
-    let tenant_shard_id: TenantShardId = parse_request_param(&req, "tenant_shard_id")?;
-    let migrate_req = json_request::<TenantShardMigrateRequest>(&mut req).await?;
-    json_response(
-        StatusCode::OK,
-        service
-            .tenant_shard_migrate(tenant_shard_id, migrate_req)
-            .await?,
-    )
+    let state = get_state(&req);
+
+    json_response(StatusCode::OK, state.service.compute_notification_drift())
 }
 
-async fn handle_tenant_update_policy(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_stripe_sizes(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
-    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
-    let update_req = json_request::<TenantPolicyRequest>(&mut req).await?;
     let state = get_state(&req);
 
-    json_response(
-        StatusCode::OK,
-        state
-            .service
-            .tenant_update_policy(tenant_id, update_req)
-            .await?,
-    )
+    json_response(StatusCode::OK, state.service.stripe_sizes_in_use())
 }
 
-async fn handle_tenant_drop(req: Request<Body>) -> Result<Response<Body>, ApiError> {
-    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
-    check_permissions(&req, Scope::PageServerApi)?;
+async fn handle_autosplit_candidates(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
 
-    json_response(StatusCode::OK, state.service.tenant_drop(tenant_id).await?)
+    json_response(StatusCode::OK, state.service.autosplit_candidates().await)
 }
 
-async fn handle_tenant_import(req: Request<Body>) -> Result<Response<Body>, ApiError> {
-    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
-    check_permissions(&req, Scope::PageServerApi)?;
+async fn handle_consistency_check_relaxed(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
 
     json_response(
         StatusCode::OK,
-        state.service.tenant_import(tenant_id).await?,
+        state.service.consistency_check_relaxed().await?,
     )
 }
 
-async fn handle_tenants_dump(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_consistency_check(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    state.service.tenants_dump()
+
+    json_response(StatusCode::OK, state.service.consistency_check().await?)
 }
 
-async fn handle_scheduler_dump(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_rescan_all_nodes(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
-    state.service.scheduler_dump()
+
+    json_response(StatusCode::OK, state.service.rescan_all_nodes().await?)
 }
 
-async fn handle_consistency_check(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_reconcile_all(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
     let state = get_state(&req);
 
-    json_response(StatusCode::OK, state.service.consistency_check().await?)
+    json_response(StatusCode::OK, state.service.reconcile_all_now().await?)
 }
 
-async fn handle_reconcile_all(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+async fn handle_reconcile_tenants(mut req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Admin)?;
 
+    let reconcile_req = json_request::<TenantsReconcileRequest>(&mut req).await?;
     let state = get_state(&req);
 
-    json_response(StatusCode::OK, state.service.reconcile_all_now().await?)
+    json_response(
+        StatusCode::OK,
+        state.service.reconcile_tenants(&reconcile_req.tenant_ids),
+    )
 }
 
-/// Status endpoint is just used for checking that our HTTP listener is up
-async fn handle_status(_req: Request<Body>) -> Result<Response<Body>, ApiError> {
-    json_response(StatusCode::OK, ())
+/// Status endpoint is used for checking that our HTTP listener is up, and that our background
+/// processors are still running: this is intended for use as a kubernetes liveness probe, so that
+/// we are restarted rather than carrying on with e.g. reconcile results going unprocessed.
+async fn handle_status(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let state = get_state(&req);
+    let dead = state.service.dead_background_processors();
+    if dead.is_empty() {
+        json_response(StatusCode::OK, ())
+    } else {
+        json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Background processors exited: {dead:?}"),
+        )
+    }
 }
 
 /// Readiness endpoint indicates when we're done doing startup I/O (e.g. reconciling
@@ -671,7 +1265,8 @@ where
     let service = state.service.clone();
 
     let startup_complete = service.startup_complete.clone();
-    if tokio::time::timeout(STARTUP_RECONCILE_TIMEOUT, startup_complete.wait())
+    let startup_reconcile_timeout = service.get_config().startup_reconcile_timeout;
+    if tokio::time::timeout(startup_reconcile_timeout, startup_complete.wait())
         .await
         .is_err()
     {
@@ -848,9 +1443,23 @@ pub fn make_router(
                 RequestName("debug_v1_tenant_import"),
             )
         })
+        .put("/debug/v1/tenant/:tenant_shard_id/generation", |r| {
+            named_request_span(
+                r,
+                handle_tenant_shard_set_generation,
+                RequestName("debug_v1_tenant_shard_set_generation"),
+            )
+        })
         .get("/debug/v1/tenant", |r| {
             named_request_span(r, handle_tenants_dump, RequestName("debug_v1_tenant"))
         })
+        .get("/debug/v1/tenant/:tenant_shard_id/observed", |r| {
+            named_request_span(
+                r,
+                handle_tenant_shard_observed,
+                RequestName("debug_v1_tenant_shard_observed"),
+            )
+        })
         .get("/debug/v1/tenant/:tenant_id/locate", |r| {
             tenant_service_handler(
                 r,
@@ -858,9 +1467,68 @@ pub fn make_router(
                 RequestName("debug_v1_tenant_locate"),
             )
         })
+        .get("/debug/v1/tenant/:tenant_id/validate_layout", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_validate_layout,
+                RequestName("debug_v1_tenant_validate_layout"),
+            )
+        })
         .get("/debug/v1/scheduler", |r| {
             named_request_span(r, handle_scheduler_dump, RequestName("debug_v1_scheduler"))
         })
+        .get("/debug/v1/state_snapshot", |r| {
+            named_request_span(
+                r,
+                handle_state_snapshot,
+                RequestName("debug_v1_state_snapshot"),
+            )
+        })
+        .get("/debug/v1/delayed_reconciles", |r| {
+            named_request_span(
+                r,
+                handle_delayed_reconciles,
+                RequestName("debug_v1_delayed_reconciles"),
+            )
+        })
+        .get("/debug/v1/background_processors", |r| {
+            named_request_span(
+                r,
+                handle_background_processors,
+                RequestName("debug_v1_background_processors"),
+            )
+        })
+        .get("/debug/v1/reconcile_throughput", |r| {
+            named_request_span(
+                r,
+                handle_reconcile_throughput,
+                RequestName("debug_v1_reconcile_throughput"),
+            )
+        })
+        .get("/debug/v1/reconcile_attention", |r| {
+            named_request_span(
+                r,
+                handle_reconcile_attention,
+                RequestName("debug_v1_reconcile_attention"),
+            )
+        })
+        .get("/debug/v1/compute_notification_drift", |r| {
+            named_request_span(
+                r,
+                handle_compute_notification_drift,
+                RequestName("debug_v1_compute_notification_drift"),
+            )
+        })
+        .get("/debug/v1/stripe_sizes", |r| {
+            named_request_span(r, handle_stripe_sizes, RequestName("debug_v1_stripe_sizes"))
+        })
+        .get("/debug/v1/autosplit_candidates", |r| {
+            named_request_span(
+                r,
+                handle_autosplit_candidates,
+                RequestName("debug_v1_autosplit_candidates"),
+            )
+        })
         .post("/debug/v1/consistency_check", |r| {
             named_request_span(
                 r,
@@ -868,9 +1536,40 @@ pub fn make_router(
                 RequestName("debug_v1_consistency_check"),
             )
         })
+        .post("/debug/v1/consistency_check_relaxed", |r| {
+            named_request_span(
+                r,
+                handle_consistency_check_relaxed,
+                RequestName("debug_v1_consistency_check_relaxed"),
+            )
+        })
         .post("/debug/v1/reconcile_all", |r| {
             request_span(r, handle_reconcile_all)
         })
+        .post("/debug/v1/reconcile_tenants", |r| {
+            named_request_span(
+                r,
+                handle_reconcile_tenants,
+                RequestName("debug_v1_reconcile_tenants"),
+            )
+        })
+        .post("/debug/v1/rescan_all_nodes", |r| {
+            named_request_span(
+                r,
+                handle_rescan_all_nodes,
+                RequestName("debug_v1_rescan_all_nodes"),
+            )
+        })
+        .get("/debug/v1/drift", |r| {
+            named_request_span(r, handle_detect_drift, RequestName("debug_v1_drift"))
+        })
+        .post("/debug/v1/renotify_compute", |r| {
+            named_request_span(
+                r,
+                handle_renotify_compute,
+                RequestName("debug_v1_renotify_compute"),
+            )
+        })
         .put("/debug/v1/failpoints", |r| {
             request_span(r, |r| failpoints_handler(r, CancellationToken::new()))
         })
@@ -891,6 +1590,33 @@ pub fn make_router(
         .get("/control/v1/node/:node_id", |r| {
             named_request_span(r, handle_node_status, RequestName("control_v1_node_status"))
         })
+        .delete("/control/v1/node/:node_id", |r| {
+            named_request_span(r, handle_node_delete, RequestName("control_v1_node_delete"))
+        })
+        .post("/control/v1/node/:node_id/migrate_tenants", |r| {
+            named_request_span(
+                r,
+                handle_node_migrate_tenants,
+                RequestName("control_v1_node_migrate_tenants"),
+            )
+        })
+        .get("/control/v1/node/:node_id/history", |r| {
+            named_request_span(
+                r,
+                handle_node_history,
+                RequestName("control_v1_node_history"),
+            )
+        })
+        .put("/control/v1/node/:node_id/cordon", |r| {
+            named_request_span(r, handle_node_cordon, RequestName("control_v1_node_cordon"))
+        })
+        .put("/control/v1/node/:node_id/uncordon", |r| {
+            named_request_span(
+                r,
+                handle_node_uncordon,
+                RequestName("control_v1_node_uncordon"),
+            )
+        })
         .put("/control/v1/node/:node_id/drain", |r| {
             named_request_span(r, handle_node_drain, RequestName("control_v1_node_drain"))
         })
@@ -904,6 +1630,13 @@ pub fn make_router(
         .put("/control/v1/node/:node_id/fill", |r| {
             named_request_span(r, handle_node_fill, RequestName("control_v1_node_fill"))
         })
+        .get("/control/v1/node/:node_id/fill", |r| {
+            named_request_span(
+                r,
+                handle_node_fill_preview,
+                RequestName("control_v1_node_fill_preview"),
+            )
+        })
         .delete("/control/v1/node/:node_id/fill", |r| {
             named_request_span(
                 r,
@@ -912,6 +1645,20 @@ pub fn make_router(
             )
         })
         // TODO(vlad): endpoint for cancelling drain and fill
+        .get("/control/v1/autosplit", |r| {
+            named_request_span(
+                r,
+                handle_get_autosplit_config,
+                RequestName("control_v1_get_autosplit_config"),
+            )
+        })
+        .put("/control/v1/autosplit", |r| {
+            named_request_span(
+                r,
+                handle_update_autosplit_config,
+                RequestName("control_v1_update_autosplit_config"),
+            )
+        })
         // Tenant Shard operations
         .put("/control/v1/tenant/:tenant_shard_id/migrate", |r| {
             tenant_service_handler(
@@ -920,6 +1667,154 @@ pub fn make_router(
                 RequestName("control_v1_tenant_migrate"),
             )
         })
+        .put(
+            "/control/v1/tenant/:tenant_shard_id/migrate_secondary",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_shard_migrate_secondary,
+                    RequestName("control_v1_tenant_migrate_secondary"),
+                )
+            },
+        )
+        .put("/control/v1/tenant/:tenant_shard_id/failover", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_failover,
+                RequestName("control_v1_tenant_failover"),
+            )
+        })
+        .put(
+            "/control/v1/tenant/:tenant_shard_id/cancel_reconcile",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_shard_cancel_reconcile,
+                    RequestName("control_v1_tenant_shard_cancel_reconcile"),
+                )
+            },
+        )
+        .put(
+            "/control/v1/tenant/:tenant_shard_id/increment_generation",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_shard_increment_generation,
+                    RequestName("control_v1_tenant_shard_increment_generation"),
+                )
+            },
+        )
+        .put("/control/v1/tenant/:tenant_id/evacuate", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_evacuate_node,
+                RequestName("control_v1_tenant_evacuate"),
+            )
+        })
+        .get(
+            "/control/v1/tenant/:tenant_id/recommended_shard_count",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_recommend_shard_count,
+                    RequestName("control_v1_tenant_recommended_shard_count"),
+                )
+            },
+        )
+        .get("/control/v1/tenant/:tenant_id/redundancy_status", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_redundancy_status,
+                RequestName("control_v1_tenant_redundancy_status"),
+            )
+        })
+        .get("/control/v1/tenant/:tenant_id/placement_status", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_placement_status,
+                RequestName("control_v1_tenant_placement_status"),
+            )
+        })
+        .get("/control/v1/tenant/:tenant_id/routing_status", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_routing_status,
+                RequestName("control_v1_tenant_routing_status"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_id/rebalance", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_rebalance,
+                RequestName("control_v1_tenant_rebalance"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_id/placement", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_set_placement,
+                RequestName("control_v1_tenant_set_placement"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_shard_id/sticky", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_sticky,
+                RequestName("control_v1_tenant_sticky"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_shard_id/detach", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_detach,
+                RequestName("control_v1_tenant_shard_detach"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_shard_id/reattach", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_reattach,
+                RequestName("control_v1_tenant_shard_reattach"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_id/colocate", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_colocate,
+                RequestName("control_v1_tenant_colocate"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_id/canary", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_canary,
+                RequestName("control_v1_tenant_canary"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_shard_id/read_replica", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_add_read_replica,
+                RequestName("control_v1_tenant_add_read_replica"),
+            )
+        })
+        .delete(
+            "/control/v1/tenant/:tenant_shard_id/read_replica/:node_id",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_shard_remove_read_replica,
+                    RequestName("control_v1_tenant_remove_read_replica"),
+                )
+            },
+        )
+        .post("/control/v1/tenant/:tenant_shard_id/heatmap_upload", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_heatmap_upload,
+                RequestName("control_v1_tenant_heatmap_upload"),
+            )
+        })
         .put("/control/v1/tenant/:tenant_id/shard_split", |r| {
             tenant_service_handler(
                 r,
@@ -927,6 +1822,13 @@ pub fn make_router(
                 RequestName("control_v1_tenant_shard_split"),
             )
         })
+        .delete("/control/v1/tenant/:tenant_id/shard_split", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_split_cancel,
+                RequestName("control_v1_tenant_shard_split_cancel"),
+            )
+        })
         .get("/control/v1/tenant/:tenant_id", |r| {
             tenant_service_handler(
                 r,
@@ -934,9 +1836,23 @@ pub fn make_router(
                 RequestName("control_v1_tenant_describe"),
             )
         })
+        .get("/control/v1/tenant/:tenant_id/reconcile_watch", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_reconcile_watch,
+                RequestName("control_v1_tenant_reconcile_watch"),
+            )
+        })
         .get("/control/v1/tenant", |r| {
             tenant_service_handler(r, handle_tenant_list, RequestName("control_v1_tenant_list"))
         })
+        .get("/control/v1/tenant_summary", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_summary,
+                RequestName("control_v1_tenant_summary"),
+            )
+        })
         .put("/control/v1/tenant/:tenant_id/policy", |r| {
             named_request_span(
                 r,
@@ -944,6 +1860,20 @@ pub fn make_router(
                 RequestName("control_v1_tenant_policy"),
             )
         })
+        .put("/control/v1/tenant/:tenant_id/generation_freeze", |r| {
+            named_request_span(
+                r,
+                handle_tenant_generation_freeze,
+                RequestName("control_v1_tenant_generation_freeze"),
+            )
+        })
+        .put("/control/v1/tenant/:tenant_id/cancel_reconciles", |r| {
+            named_request_span(
+                r,
+                handle_tenant_cancel_reconciles,
+                RequestName("control_v1_tenant_cancel_reconciles"),
+            )
+        })
         // Tenant operations
         // The ^/v1/ endpoints act as a "Virtual Pageserver", enabling shard-naive clients to call into
         // this service to manage tenants that actually consist of many tenant shards, as if they are a single entity.
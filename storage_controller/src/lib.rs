@@ -7,10 +7,12 @@ mod compute_hook;
 mod heartbeater;
 pub mod http;
 mod id_lock_map;
+mod liveness;
 pub mod metrics;
 mod node;
 mod pageserver_client;
 pub mod persistence;
+mod reconcile_events;
 mod reconciler;
 mod scheduler;
 mod schema;
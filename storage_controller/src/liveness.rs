@@ -0,0 +1,249 @@
+//! Liveness tracking for the long-running background loops of [`crate::service::Service`].
+//!
+//! If one of these loops exits early, e.g. because it panicked, the controller carries on serving
+//! requests but is quietly broken: for example, if the abort processor is gone then aborted shard
+//! splits are never cleaned up.  Each loop holds a [`ProcessorGuard`] that notices if it exits
+//! before shutdown, so that we can fail the health check and get restarted instead.
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+use pageserver_api::controller_api::BackgroundProcessorStatus;
+use tokio::sync::mpsc::error::SendError;
+use tokio_util::sync::CancellationToken;
+
+use crate::metrics::METRICS_REGISTRY;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::Display)]
+pub(crate) enum Processor {
+    /// Applies the results of reconciles, and starts delayed reconciles
+    Results,
+    /// Cleans up after aborted shard splits
+    Aborts,
+    /// Periodically reconciles, optimizes and auto-splits tenants
+    BackgroundReconcile,
+    /// Heartbeats pageservers to track their availability
+    Heartbeats,
+}
+
+impl Processor {
+    const ALL: [Processor; 4] = [
+        Processor::Results,
+        Processor::Aborts,
+        Processor::BackgroundReconcile,
+        Processor::Heartbeats,
+    ];
+}
+
+#[derive(Default)]
+struct ProcessorState {
+    /// Set if the processor's task exited while we were not shutting down
+    died: AtomicBool,
+
+    /// When the processor last did some work
+    last_active: Mutex<Option<Instant>>,
+
+    /// Items sent to the processor's queue and not received yet, for processors fed by a
+    /// [`CountedSender`]
+    queued: Arc<AtomicUsize>,
+}
+
+#[derive(Default)]
+pub(crate) struct ProcessorLiveness {
+    processors: [Arc<ProcessorState>; Processor::ALL.len()],
+}
+
+impl ProcessorLiveness {
+    fn state(&self, processor: Processor) -> &Arc<ProcessorState> {
+        &self.processors[processor as usize]
+    }
+
+    /// Call at the start of a processor's task, and hold the guard until the task ends.
+    pub(crate) fn start(&self, processor: Processor, cancel: CancellationToken) -> ProcessorGuard {
+        ProcessorGuard {
+            processor,
+            state: self.state(processor).clone(),
+            cancel,
+        }
+    }
+
+    /// Record that a processor is making progress
+    pub(crate) fn beat(&self, processor: Processor) {
+        *self.state(processor).last_active.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Create the queue that feeds a processor, counting its backlog
+    pub(crate) fn channel<T>(
+        &self,
+        processor: Processor,
+    ) -> (CountedSender<T>, CountedReceiver<T>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let queued = self.state(processor).queued.clone();
+        (
+            CountedSender {
+                inner: tx,
+                queued: queued.clone(),
+            },
+            CountedReceiver { inner: rx, queued },
+        )
+    }
+
+    /// Processors whose tasks have exited unexpectedly
+    pub(crate) fn dead(&self) -> Vec<Processor> {
+        Processor::ALL
+            .into_iter()
+            .filter(|p| self.state(*p).died.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    pub(crate) fn status(&self) -> Vec<BackgroundProcessorStatus> {
+        Processor::ALL
+            .into_iter()
+            .map(|processor| {
+                let state = self.state(processor);
+                let last_active = *state.last_active.lock().unwrap();
+                BackgroundProcessorStatus {
+                    name: processor.to_string(),
+                    alive: !state.died.load(Ordering::Relaxed),
+                    last_active: last_active
+                        .and_then(|t| SystemTime::now().checked_sub(t.elapsed())),
+                    backlog: state.queued.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Held by a processor's task for as long as it runs.  Dropping it, including by unwinding from
+/// a panic, marks the processor dead unless we are shutting down.
+pub(crate) struct ProcessorGuard {
+    processor: Processor,
+    state: Arc<ProcessorState>,
+    cancel: CancellationToken,
+}
+
+impl Drop for ProcessorGuard {
+    fn drop(&mut self) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+
+        tracing::error!(
+            "Background processor {} exited unexpectedly",
+            self.processor
+        );
+        self.state.died.store(true, Ordering::Relaxed);
+        METRICS_REGISTRY
+            .metrics_group
+            .storage_controller_background_processor_died
+            .inc();
+    }
+}
+
+/// An unbounded channel sender that counts what it sends, so that the receiving processor's
+/// backlog can be reported.
+pub(crate) struct CountedSender<T> {
+    inner: tokio::sync::mpsc::UnboundedSender<T>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl<T> Clone for CountedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            queued: self.queued.clone(),
+        }
+    }
+}
+
+impl<T> CountedSender<T> {
+    pub(crate) fn send(&self, value: T) -> Result<(), SendError<T>> {
+        // Count before sending, so that the receiver never sees the count go negative
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let result = self.inner.send(value);
+        if result.is_err() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+pub(crate) struct CountedReceiver<T> {
+    inner: tokio::sync::mpsc::UnboundedReceiver<T>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl<T> CountedReceiver<T> {
+    pub(crate) async fn recv(&mut self) -> Option<T> {
+        let value = self.inner.recv().await;
+        if value.is_some() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn processor_death() {
+        let liveness = Arc::new(ProcessorLiveness::default());
+        let cancel = CancellationToken::new();
+
+        // A processor that panics is dead
+        let task = tokio::task::spawn({
+            let liveness = liveness.clone();
+            let cancel = cancel.clone();
+            async move {
+                let _guard = liveness.start(Processor::Aborts, cancel);
+                liveness.beat(Processor::Aborts);
+                panic!("Processor failed");
+            }
+        });
+        assert!(task.await.unwrap_err().is_panic());
+        assert_eq!(liveness.dead(), vec![Processor::Aborts]);
+
+        let status = liveness.status();
+        let aborts = status.iter().find(|s| s.name == "Aborts").unwrap();
+        assert!(!aborts.alive);
+        assert!(aborts.last_active.is_some());
+        assert!(status
+            .iter()
+            .filter(|s| s.name != "Aborts")
+            .all(|s| s.alive));
+
+        // Processors that exit on shutdown are not
+        let guard = liveness.start(Processor::Results, cancel.clone());
+        cancel.cancel();
+        drop(guard);
+        assert_eq!(liveness.dead(), vec![Processor::Aborts]);
+    }
+
+    #[tokio::test]
+    async fn counted_channel_backlog() {
+        let liveness = ProcessorLiveness::default();
+        let backlog = |liveness: &ProcessorLiveness| {
+            liveness
+                .status()
+                .into_iter()
+                .find(|s| s.name == "Results")
+                .unwrap()
+                .backlog
+        };
+
+        let (tx, mut rx) = liveness.channel::<u32>(Processor::Results);
+        tx.send(1).unwrap();
+        tx.clone().send(2).unwrap();
+        assert_eq!(backlog(&liveness), 2);
+
+        assert_eq!(rx.recv().await, Some(1));
+        assert_eq!(backlog(&liveness), 1);
+
+        // Sends that fail are not counted
+        drop(rx);
+        assert!(tx.send(3).is_err());
+        assert_eq!(backlog(&liveness), 1);
+    }
+}
@@ -1,16 +1,24 @@
 use anyhow::{anyhow, Context};
 use camino::Utf8PathBuf;
+use chrono::NaiveTime;
 use clap::Parser;
 use diesel::Connection;
 use metrics::launch_timestamp::LaunchTimestamp;
 use metrics::BuildInfo;
+use pageserver_api::controller_api::PlacementPolicy;
+use pageserver_api::models::TenantSorting;
 use std::path::PathBuf;
 use std::sync::Arc;
 use storage_controller::http::make_router;
 use storage_controller::metrics::preinitialize_metrics;
 use storage_controller::persistence::Persistence;
 use storage_controller::service::{
-    Config, Service, MAX_UNAVAILABLE_INTERVAL_DEFAULT, RECONCILER_CONCURRENCY_DEFAULT,
+    Config, Service, BACKGROUND_JITTER_PCT_DEFAULT, COMPUTE_HOOK_BATCH_INTERVAL_DEFAULT,
+    COMPUTE_HOOK_BATCH_SIZE_DEFAULT, MAX_DEBUG_DUMP_BYTES_DEFAULT,
+    MAX_OPTIMIZATIONS_EXEC_PER_PASS_DEFAULT, MAX_OPTIMIZATIONS_PLAN_PER_PASS_DEFAULT,
+    MAX_UNAVAILABLE_INTERVAL_DEFAULT, ORPHANED_NODE_OPERATION_TIMEOUT_DEFAULT,
+    RECONCILER_CONCURRENCY_DEFAULT, STARTUP_RECONCILE_TIMEOUT_DEFAULT,
+    STARTUP_SCAN_TIMEOUT_DEFAULT,
 };
 use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
@@ -51,6 +59,15 @@ struct Cli {
     #[arg(long)]
     compute_hook_url: Option<String>,
 
+    /// Send compute notifications for up to this many tenants in one request.  The control plane
+    /// must accept batched requests if this is more than one.
+    #[arg(long)]
+    compute_hook_batch_size: Option<usize>,
+
+    /// How long a compute notification may wait for others to batch with
+    #[arg(long)]
+    compute_hook_batch_interval: Option<humantime::Duration>,
+
     /// Path to the .json file to store state (will be created if it doesn't exist)
     #[arg(short, long)]
     path: Option<Utf8PathBuf>,
@@ -67,14 +84,107 @@ struct Cli {
     #[arg(long)]
     max_unavailable_interval: Option<humantime::Duration>,
 
+    /// How long a node may stay Draining or Filling without the operation that set this, before
+    /// it is reset to Active
+    #[arg(long)]
+    orphaned_node_operation_timeout: Option<humantime::Duration>,
+
     /// Size threshold for automatically splitting shards (disabled by default)
     #[arg(long)]
     split_threshold: Option<u64>,
 
+    /// Compare shards' resident size against the split threshold, rather than their largest
+    /// timeline's logical size
+    #[arg(long, default_value = "false")]
+    split_by_resident_size: bool,
+
+    /// Only start automatic splits within this window of time of day in UTC, such as `22:00-04:00`
+    /// (unrestricted by default).  Manual splits are unaffected.
+    #[arg(long)]
+    autosplit_window: Option<String>,
+
+    /// Refuse to create tenants beyond this count (unlimited by default)
+    #[arg(long)]
+    max_tenants: Option<usize>,
+
+    /// Refuse tenants with more secondary locations per shard than this (unlimited by default)
+    #[arg(long)]
+    max_secondaries_per_shard: Option<usize>,
+
+    /// Refuse tenants with more shards than this, whether created or split (unlimited by default)
+    #[arg(long)]
+    max_shards_per_tenant: Option<usize>,
+
+    /// How long startup may take before we stop waiting for unresponsive pageservers
+    #[arg(long)]
+    startup_reconcile_timeout: Option<humantime::Duration>,
+
+    /// How much of the startup reconcile timeout may be spent waiting for pageservers to list
+    /// their locations.  Raise this if slow-but-healthy pageservers are left out at startup.
+    #[arg(long)]
+    startup_scan_timeout: Option<humantime::Duration>,
+
     /// Maximum number of reconcilers that may run in parallel
     #[arg(long)]
     reconciler_concurrency: Option<usize>,
 
+    /// Percentage by which background reconciliation and heartbeat periods are randomized either
+    /// way, to avoid synchronized bursts
+    #[arg(long)]
+    background_jitter_pct: Option<u32>,
+
+    /// How many attached shards the difference between the least and most utilized pageservers
+    /// is worth when scheduling new shards.  Zero schedules on shard counts alone.
+    #[arg(long)]
+    scheduler_utilization_weight: Option<usize>,
+
+    /// Maximum number of reconcilers that may run in parallel during startup (defaults to
+    /// the steady-state reconciler concurrency)
+    #[arg(long)]
+    startup_reconciler_concurrency: Option<usize>,
+
+    /// Refuse generation validation for shards where a pageserver holds a generation newer than
+    /// we issued (by default, this is only logged)
+    #[arg(long, default_value = "false")]
+    fence_generation_violations: bool,
+
+    /// Keep live migration destinations in multi-attached mode if their pageserver restarts
+    /// mid-migration, rather than making them the single attached location right away
+    #[arg(long, default_value = "false")]
+    reattach_preserve_multi: bool,
+
+    /// Maximum number of optimizations executed per background optimization pass: higher values
+    /// rebalance faster, but cause more concurrent migrations.  Must be greater than zero.
+    #[arg(long)]
+    max_optimizations_exec_per_pass: Option<usize>,
+
+    /// Maximum number of candidate optimizations planned per background optimization pass: must be
+    /// at least `--max-optimizations-exec-per-pass`
+    #[arg(long)]
+    max_optimizations_plan_per_pass: Option<usize>,
+
+    /// Maximum size in bytes of the uncompressed JSON bodies returned by debug dump APIs
+    #[arg(long)]
+    max_debug_dump_bytes: Option<usize>,
+
+    /// Serve the tenant list from a snapshot at most this old (disabled by default)
+    #[arg(long)]
+    tenant_list_cache_ttl: Option<humantime::Duration>,
+
+    /// URL to which reconcile results are published as JSON events (disabled by default)
+    #[arg(long)]
+    reconcile_event_url: Option<String>,
+
+    /// Treat this as a single-node deployment (true) or not (false), rather than inferring it from
+    /// how many pageservers are registered
+    #[arg(long)]
+    single_node: Option<bool>,
+
+    /// Placement policy for tenants imported in an attached mode, as JSON such as
+    /// `{"Attached":2}` (one secondary location by default)
+    #[arg(long)]
+    default_import_placement: Option<String>,
+
     /// How long to wait for the initial database connection to be available.
     #[arg(long, default_value = "5s")]
     db_connect_timeout: humantime::Duration,
@@ -160,6 +270,23 @@ impl Secrets {
     }
 }
 
+/// Parse a window of time of day such as `22:00-04:00`: the window may span midnight.
+fn parse_time_window(window: &str) -> anyhow::Result<(NaiveTime, NaiveTime)> {
+    let (start, end) = window
+        .split_once('-')
+        .with_context(|| format!("Time window {window:?} is not of the form HH:MM-HH:MM"))?;
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .with_context(|| format!("Invalid time {time:?} in window {window:?}"))
+    };
+
+    let (start, end) = (parse(start)?, parse(end)?);
+    if start == end {
+        anyhow::bail!("Time window {window:?} is empty");
+    }
+    Ok((start, end))
+}
+
 /// Execute the diesel migrations that are built into this binary
 async fn migration_run(database_url: &str) -> anyhow::Result<()> {
     use diesel::PgConnection;
@@ -255,18 +382,117 @@ async fn async_main() -> anyhow::Result<()> {
         }
     }
 
+    let reconciler_concurrency = args
+        .reconciler_concurrency
+        .unwrap_or(RECONCILER_CONCURRENCY_DEFAULT);
+
+    let startup_reconcile_timeout = args
+        .startup_reconcile_timeout
+        .map(humantime::Duration::into)
+        .unwrap_or(STARTUP_RECONCILE_TIMEOUT_DEFAULT);
+    let startup_scan_timeout = args
+        .startup_scan_timeout
+        .map(humantime::Duration::into)
+        .unwrap_or(STARTUP_SCAN_TIMEOUT_DEFAULT);
+    if startup_scan_timeout > startup_reconcile_timeout {
+        anyhow::bail!(
+            "--startup-scan-timeout ({startup_scan_timeout:?}) must not exceed the startup reconcile timeout ({startup_reconcile_timeout:?})"
+        );
+    }
+
+    let background_jitter_pct = args
+        .background_jitter_pct
+        .unwrap_or(BACKGROUND_JITTER_PCT_DEFAULT);
+    if background_jitter_pct >= 100 {
+        anyhow::bail!("--background-jitter-pct ({background_jitter_pct}) must be less than 100");
+    }
+
+    let autosplit_window = args
+        .autosplit_window
+        .as_deref()
+        .map(parse_time_window)
+        .transpose()
+        .context("--autosplit-window")?;
+
+    let default_import_placement = args
+        .default_import_placement
+        .as_deref()
+        .map(serde_json::from_str::<PlacementPolicy>)
+        .transpose()
+        .context("--default-import-placement")?;
+    if let Some(policy) = &default_import_placement {
+        if !matches!(policy, PlacementPolicy::Attached(_)) {
+            anyhow::bail!("--default-import-placement ({policy:?}) must be an Attached policy");
+        }
+    }
+
+    let max_optimizations_exec_per_pass = args
+        .max_optimizations_exec_per_pass
+        .unwrap_or(MAX_OPTIMIZATIONS_EXEC_PER_PASS_DEFAULT);
+    let max_optimizations_plan_per_pass = args
+        .max_optimizations_plan_per_pass
+        .unwrap_or(MAX_OPTIMIZATIONS_PLAN_PER_PASS_DEFAULT);
+    if max_optimizations_exec_per_pass == 0 {
+        anyhow::bail!("--max-optimizations-exec-per-pass must be greater than zero");
+    }
+    if max_optimizations_exec_per_pass > max_optimizations_plan_per_pass {
+        // Planned optimizations are the candidates for execution: we can never execute more
+        // than we planned.  This also rules out a plan limit of zero.
+        anyhow::bail!(
+            "--max-optimizations-exec-per-pass ({max_optimizations_exec_per_pass}) must not exceed --max-optimizations-plan-per-pass ({max_optimizations_plan_per_pass})"
+        );
+    }
+
     let config = Config {
         jwt_token: secrets.jwt_token,
         control_plane_jwt_token: secrets.control_plane_jwt_token,
         compute_hook_url: args.compute_hook_url,
+        compute_hook_batch_size: args
+            .compute_hook_batch_size
+            .unwrap_or(COMPUTE_HOOK_BATCH_SIZE_DEFAULT),
+        compute_hook_batch_interval: args
+            .compute_hook_batch_interval
+            .map(humantime::Duration::into)
+            .unwrap_or(COMPUTE_HOOK_BATCH_INTERVAL_DEFAULT),
         max_unavailable_interval: args
             .max_unavailable_interval
             .map(humantime::Duration::into)
             .unwrap_or(MAX_UNAVAILABLE_INTERVAL_DEFAULT),
-        reconciler_concurrency: args
-            .reconciler_concurrency
-            .unwrap_or(RECONCILER_CONCURRENCY_DEFAULT),
+        orphaned_node_operation_timeout: args
+            .orphaned_node_operation_timeout
+            .map(humantime::Duration::into)
+            .unwrap_or(ORPHANED_NODE_OPERATION_TIMEOUT_DEFAULT),
+        startup_reconcile_timeout,
+        startup_scan_timeout,
+        reconciler_concurrency,
+        startup_reconciler_concurrency: args
+            .startup_reconciler_concurrency
+            .unwrap_or(reconciler_concurrency),
+        background_jitter_pct,
+        scheduler_utilization_weight: args
+            .scheduler_utilization_weight
+            .unwrap_or(SCHEDULER_UTILIZATION_WEIGHT_DEFAULT),
+        max_optimizations_exec_per_pass,
+        max_optimizations_plan_per_pass,
+        max_debug_dump_bytes: args
+            .max_debug_dump_bytes
+            .unwrap_or(MAX_DEBUG_DUMP_BYTES_DEFAULT),
+        tenant_list_cache_ttl: args.tenant_list_cache_ttl.map(humantime::Duration::into),
         split_threshold: args.split_threshold,
+        split_sorting: if args.split_by_resident_size {
+            TenantSorting::ResidentSize
+        } else {
+            TenantSorting::MaxLogicalSize
+        },
+        autosplit_window,
+        max_tenants: args.max_tenants,
+        max_secondaries_per_shard: args.max_secondaries_per_shard,
+        max_shards_per_tenant: args.max_shards_per_tenant,
+        fence_generation_violations: args.fence_generation_violations,
+        reattach_preserve_multi: args.reattach_preserve_multi,
+        reconcile_event_url: args.reconcile_event_url,
+        single_node: args.single_node,
+        default_import_placement,
         neon_local_repo_dir: args.neon_local_repo_dir,
     };
 
@@ -40,6 +40,34 @@ pub(crate) struct StorageControllerMetricGroup {
     /// Count of how many times we make an optimization change to a tenant's scheduling
     pub(crate) storage_controller_schedule_optimization: measured::Counter,
 
+    /// Count of how many times a shard's reconciles failed often enough that it needs attention
+    pub(crate) storage_controller_reconcile_attention: measured::Counter,
+
+    /// Reconciles completed (successfully or not) per minute, over the last ten minutes
+    pub(crate) storage_controller_reconcile_throughput: measured::Gauge,
+
+    /// Reconcilers currently holding a reconciler concurrency unit
+    pub(crate) storage_controller_reconcile_in_flight: measured::Gauge,
+
+    /// Shards queued to reconcile once a reconciler concurrency unit is free
+    pub(crate) storage_controller_reconcile_delayed_queue_depth: measured::Gauge,
+
+    /// Count of how many times a shard could not be queued to reconcile later because the
+    /// delayed reconcile queue was full
+    pub(crate) storage_controller_reconcile_delayed_queue_full: measured::Counter,
+
+    /// Count of background processors (e.g. reconcile result processing) that exited unexpectedly.
+    /// Any increase means the controller is running degraded and should be restarted.
+    pub(crate) storage_controller_background_processor_died: measured::Counter,
+
+    /// Count of how many times an auto-split was skipped because the cluster could not
+    /// schedule the child shards
+    pub(crate) storage_controller_autosplit_deferred: measured::Counter,
+
+    /// Whether the compute hook responded (1) or could not be reached (0) the last time we
+    /// sent it a request.  Only meaningful when a compute hook URL is configured.
+    pub(crate) storage_controller_compute_hook_reachable: measured::Gauge,
+
     /// HTTP request status counters for handled requests
     pub(crate) storage_controller_http_request_status:
         measured::CounterVec<HttpRequestStatusLabelGroupSet>,
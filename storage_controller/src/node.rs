@@ -1,8 +1,13 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
 
 use pageserver_api::{
     controller_api::{
-        NodeAvailability, NodeDescribeResponse, NodeRegisterRequest, NodeSchedulingPolicy,
+        NodeAvailability, NodeCapabilities, NodeDescribeResponse, NodeOfflineReason,
+        NodeRegisterRequest, NodeSchedulingPolicy, NodeTransition, NodeTransitionSource,
         TenantLocateResponseShard, UtilizationScore,
     },
     shard::TenantShardId,
@@ -36,12 +41,27 @@ pub(crate) struct Node {
     listen_pg_addr: String,
     listen_pg_port: u16,
 
+    /// Why and when the heartbeater last marked this node offline.  This is not persistent: it
+    /// only helps operators to triage why a node dropped out.
+    last_offline: Option<NodeOfflineReason>,
+
+    /// What the node reported it supports when it registered.  None if it did not say, in which
+    /// case we assume it supports everything, as nodes did before they reported capabilities.
+    capabilities: Option<NodeCapabilities>,
+
+    /// The most recent changes to this node's availability and scheduling policy, oldest first,
+    /// bounded by [`NODE_HISTORY_SIZE`].  Like `last_offline`, this is not persistent.
+    history: VecDeque<NodeTransition>,
+
     // This cancellation token means "stop any RPCs in flight to this node, and don't start
     // any more". It is not related to process shutdown.
     #[serde(skip)]
     cancel: CancellationToken,
 }
 
+/// How many transitions to remember in [`Node::history`]
+const NODE_HISTORY_SIZE: usize = 64;
+
 /// When updating [`Node::availability`] we use this type to indicate to the caller
 /// whether/how they changed it.
 pub(crate) enum AvailabilityTransition {
@@ -77,6 +97,18 @@ impl Node {
             && self.listen_pg_port == register_req.listen_pg_port
     }
 
+    pub(crate) fn get_capabilities(&self) -> Option<NodeCapabilities> {
+        self.capabilities
+    }
+
+    pub(crate) fn set_capabilities(&mut self, capabilities: Option<NodeCapabilities>) {
+        self.capabilities = capabilities
+    }
+
+    pub(crate) fn supports_shard_split(&self) -> bool {
+        self.capabilities.map(|c| c.shard_split).unwrap_or(true)
+    }
+
     /// For a shard located on this node, populate a response object
     /// with this node's address information.
     pub(crate) fn shard_location(&self, shard_id: TenantShardId) -> TenantLocateResponseShard {
@@ -174,6 +206,9 @@ impl Node {
             listen_pg_port,
             scheduling: NodeSchedulingPolicy::Active,
             availability: NodeAvailability::Offline,
+            last_offline: None,
+            capabilities: None,
+            history: VecDeque::new(),
             cancel: CancellationToken::new(),
         }
     }
@@ -186,6 +221,9 @@ impl Node {
             listen_http_port: self.listen_http_port as i32,
             listen_pg_addr: self.listen_pg_addr.clone(),
             listen_pg_port: self.listen_pg_port as i32,
+            capabilities: self
+                .capabilities
+                .map(|c| serde_json::to_string(&c).unwrap()),
         }
     }
 
@@ -200,10 +238,64 @@ impl Node {
             listen_http_port: np.listen_http_port as u16,
             listen_pg_addr: np.listen_pg_addr,
             listen_pg_port: np.listen_pg_port as u16,
+            last_offline: None,
+            capabilities: np
+                .capabilities
+                .map(|c| serde_json::from_str(&c).expect("Bad capabilities in DB")),
+            history: VecDeque::new(),
             cancel: CancellationToken::new(),
         }
     }
 
+    /// Record why the node was marked offline: this does not modify its availability.
+    pub(crate) fn set_last_offline(&mut self, reason: String) {
+        self.last_offline = Some(NodeOfflineReason {
+            reason,
+            at: SystemTime::now(),
+        });
+    }
+
+    /// Apply a change of availability and/or scheduling policy, and record it in the node's
+    /// history if it changed anything.  Changes to the utilization score of an active node
+    /// are not transitions.
+    pub(crate) fn apply_transition(
+        &mut self,
+        availability: Option<NodeAvailability>,
+        scheduling: Option<NodeSchedulingPolicy>,
+        source: NodeTransitionSource,
+        offline_reason: Option<String>,
+    ) {
+        let changed = availability.is_some_and(|a| a != self.availability)
+            || scheduling.is_some_and(|s| s != self.scheduling);
+
+        if let Some(availability) = availability {
+            self.set_availability(availability);
+        }
+        if let Some(scheduling) = scheduling {
+            self.set_scheduling(scheduling);
+        }
+        if let Some(reason) = &offline_reason {
+            self.set_last_offline(reason.clone());
+        }
+
+        if changed {
+            if self.history.len() >= NODE_HISTORY_SIZE {
+                self.history.pop_front();
+            }
+            self.history.push_back(NodeTransition {
+                at: SystemTime::now(),
+                source,
+                availability: self.availability.into(),
+                scheduling: self.scheduling,
+                offline_reason,
+            });
+        }
+    }
+
+    pub(crate) fn get_history(&self) -> impl Iterator<Item = &NodeTransition> {
+        self.history.iter()
+    }
+
     /// Wrapper for issuing requests to pageserver management API: takes care of generic
     /// retry/backoff for retryable HTTP status codes.
     ///
@@ -283,6 +375,8 @@ impl Node {
             listen_http_port: self.listen_http_port,
             listen_pg_addr: self.listen_pg_addr.clone(),
             listen_pg_port: self.listen_pg_port,
+            last_offline: self.last_offline.clone(),
+            capabilities: self.capabilities,
         }
     }
 }
@@ -298,3 +392,54 @@ impl std::fmt::Debug for Node {
         write!(f, "{} ({})", self.id, self.listen_http_addr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::test_utils::make_test_nodes;
+
+    #[test]
+    fn history_is_bounded() {
+        let mut nodes = make_test_nodes(1);
+        let node = nodes.get_mut(&NodeId(1)).unwrap();
+
+        // Heartbeat updates to the utilization score are not transitions
+        node.apply_transition(
+            Some(NodeAvailability::Active(UtilizationScore(1))),
+            None,
+            NodeTransitionSource::Heartbeat,
+            None,
+        );
+        assert_eq!(node.get_history().count(), 0);
+
+        node.apply_transition(
+            Some(NodeAvailability::Offline),
+            None,
+            NodeTransitionSource::Heartbeat,
+            Some("timeout".to_string()),
+        );
+        let last = node.get_history().last().unwrap();
+        assert_eq!(last.source, NodeTransitionSource::Heartbeat);
+        assert_eq!(last.offline_reason.as_deref(), Some("timeout"));
+        assert!(node.last_offline.is_some());
+
+        for i in 0..NODE_HISTORY_SIZE {
+            let scheduling = if i % 2 == 0 {
+                NodeSchedulingPolicy::Draining
+            } else {
+                NodeSchedulingPolicy::Active
+            };
+            node.apply_transition(None, Some(scheduling), NodeTransitionSource::Operator, None);
+        }
+
+        // The offline transition has been pushed out by the newer ones
+        assert_eq!(node.get_history().count(), NODE_HISTORY_SIZE);
+        assert!(node
+            .get_history()
+            .all(|t| t.source == NodeTransitionSource::Operator));
+        assert_eq!(
+            node.get_history().last().unwrap().scheduling,
+            NodeSchedulingPolicy::Active
+        );
+    }
+}
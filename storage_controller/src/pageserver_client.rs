@@ -126,7 +126,10 @@ impl PageserverClient {
         )
     }
 
-    pub(crate) async fn tenant_heatmap_upload(&self, tenant_id: TenantShardId) -> Result<()> {
+    pub(crate) async fn tenant_heatmap_upload(
+        &self,
+        tenant_id: TenantShardId,
+    ) -> Result<StatusCode> {
         measured_request!(
             "tenant_heatmap_upload",
             crate::metrics::Method::Post,
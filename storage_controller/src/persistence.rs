@@ -10,8 +10,8 @@ use camino::Utf8PathBuf;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::Connection;
-use pageserver_api::controller_api::ShardSchedulingPolicy;
-use pageserver_api::controller_api::{NodeSchedulingPolicy, PlacementPolicy};
+use pageserver_api::controller_api::{NodeCapabilities, NodeSchedulingPolicy, PlacementPolicy};
+use pageserver_api::controller_api::{ReconcilePriority, ShardSchedulingPolicy};
 use pageserver_api::models::TenantConfig;
 use pageserver_api::shard::ShardConfigError;
 use pageserver_api::shard::ShardIdentity;
@@ -25,6 +25,7 @@ use crate::metrics::{
     DatabaseQueryErrorLabelGroup, DatabaseQueryLatencyLabelGroup, METRICS_REGISTRY,
 };
 use crate::node::Node;
+use crate::tenant_shard::TenantFlag;
 
 /// ## What do we store?
 ///
@@ -78,12 +79,15 @@ pub(crate) enum DatabaseError {
     ConnectionPool(#[from] r2d2::Error),
     #[error("Logical error: {0}")]
     Logical(String),
+    #[error("Generation of tenant {0} is frozen")]
+    GenerationFrozen(TenantId),
 }
 
 #[derive(measured::FixedCardinalityLabel, Copy, Clone)]
 pub(crate) enum DatabaseOperation {
     InsertNode,
     UpdateNode,
+    UpdateNodeCapabilities,
     DeleteNode,
     ListNodes,
     BeginShardSplit,
@@ -92,9 +96,13 @@ pub(crate) enum DatabaseOperation {
     Detach,
     ReAttach,
     IncrementGeneration,
+    SetGeneration,
     ListTenantShards,
     InsertTenantShards,
     UpdateTenantShard,
+    UpdateTenantShards,
+    SetTenantFlag,
+    SetPendingComputeNotification,
     DeleteTenant,
     UpdateTenantConfig,
 }
@@ -299,6 +307,32 @@ impl Persistence {
         }
     }
 
+    pub(crate) async fn update_node_capabilities(
+        &self,
+        input_node_id: NodeId,
+        input_capabilities: Option<NodeCapabilities>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::nodes::dsl::*;
+        let input_capabilities = input_capabilities.map(|c| serde_json::to_string(&c).unwrap());
+        let updated = self
+            .with_measured_conn(DatabaseOperation::UpdateNodeCapabilities, move |conn| {
+                let updated = diesel::update(nodes)
+                    .filter(node_id.eq(input_node_id.0 as i64))
+                    .set(capabilities.eq(input_capabilities.clone()))
+                    .execute(conn)?;
+                Ok(updated)
+            })
+            .await?;
+
+        if updated != 1 {
+            Err(DatabaseError::Logical(format!(
+                "Node {node_id:?} not found for update",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
     /// At startup, load the high level state for shards, such as their config + policy.  This will
     /// be enriched at runtime with state discovered on pageservers.
     pub(crate) async fn list_tenant_shards(&self) -> DatabaseResult<Vec<TenantShardPersistence>> {
@@ -346,6 +380,11 @@ impl Persistence {
                 shard.scheduling_policy =
                     serde_json::to_string(&ShardSchedulingPolicy::default()).unwrap();
             }
+
+            if shard.reconcile_priority.is_empty() {
+                shard.reconcile_priority =
+                    serde_json::to_string(&ReconcilePriority::default()).unwrap();
+            }
         }
 
         let tenants: Vec<TenantShardPersistence> = decoded.tenants.into_values().collect();
@@ -438,17 +477,39 @@ impl Persistence {
 
     /// When a tenant invokes the /re-attach API, this function is responsible for doing an efficient
     /// batched increment of the generations of all tenants whose generation_pageserver is equal to
-    /// the node that called /re-attach.
+    /// the node that called /re-attach.  If the node holds any shards of `frozen_tenants`, nothing
+    /// is incremented and [`DatabaseError::GenerationFrozen`] is returned.
     #[tracing::instrument(skip_all, fields(node_id))]
     pub(crate) async fn re_attach(
         &self,
         input_node_id: NodeId,
+        frozen_tenants: &[TenantId],
     ) -> DatabaseResult<HashMap<TenantShardId, Generation>> {
         use crate::schema::nodes::dsl::scheduling_policy;
         use crate::schema::nodes::dsl::*;
         use crate::schema::tenant_shards::dsl::*;
+        let frozen_tenants = frozen_tenants
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
         let updated = self
             .with_measured_conn(DatabaseOperation::ReAttach, move |conn| {
+                // Refuse the whole re-attach if it would increment any frozen generations: we must
+                // not omit those shards from the response, as the pageserver would drop them.
+                let frozen = tenant_shards
+                    .filter(generation_pageserver.eq(input_node_id.0 as i64))
+                    .filter(tenant_id.eq_any(&frozen_tenants))
+                    .select(tenant_id)
+                    .first::<String>(conn)
+                    .optional()?;
+                if let Some(frozen) = frozen {
+                    return Err(DatabaseError::GenerationFrozen(
+                        TenantId::from_str(&frozen).map_err(|e| {
+                            DatabaseError::Logical(format!("Malformed tenant id: {e}"))
+                        })?,
+                    ));
+                }
+
                 let rows_updated = diesel::update(tenant_shards)
                     .filter(generation_pageserver.eq(input_node_id.0 as i64))
                     .set(generation.eq(generation + 1))
@@ -542,6 +603,39 @@ impl Persistence {
         Ok(Generation::new(g as u32))
     }
 
+    /// Overwrite a shard's generation, for operators recovering from incidents.  The update only
+    /// applies if the shard's generation is still `expect`, or is already `input_generation` so
+    /// that retries are idempotent: returns false if the generation was changed concurrently.
+    pub(crate) async fn set_generation(
+        &self,
+        tenant_shard_id: TenantShardId,
+        expect: Option<Generation>,
+        input_generation: Generation,
+    ) -> DatabaseResult<bool> {
+        use crate::schema::tenant_shards::dsl::*;
+        let expect = expect.map(|g| g.into().unwrap() as i32);
+        let input_generation = input_generation.into().unwrap() as i32;
+        let updated = self
+            .with_measured_conn(DatabaseOperation::SetGeneration, move |conn| {
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
+                    .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
+                    .filter(shard_count.eq(tenant_shard_id.shard_count.literal() as i32))
+                    .filter(
+                        generation
+                            .is_not_distinct_from(expect)
+                            .or(generation.is_not_distinct_from(Some(input_generation))),
+                    )
+                    .set(generation.eq(input_generation))
+                    .execute(conn)?;
+
+                Ok(updated)
+            })
+            .await?;
+
+        Ok(updated > 0)
+    }
+
     /// For use when updating a persistent property of a tenant, such as its config or placement_policy.
     ///
     /// Do not use this for settting generation, unless in the special onboarding code path (/location_config)
@@ -554,6 +648,7 @@ impl Persistence {
         input_config: Option<TenantConfig>,
         input_generation: Option<Generation>,
         input_scheduling_policy: Option<ShardSchedulingPolicy>,
+        input_reconcile_priority: Option<ReconcilePriority>,
     ) -> DatabaseResult<()> {
         use crate::schema::tenant_shards::dsl::*;
 
@@ -569,28 +664,15 @@ impl Persistence {
                     .into_boxed(),
             };
 
-            #[derive(AsChangeset)]
-            #[diesel(table_name = crate::schema::tenant_shards)]
-            struct ShardUpdate {
-                generation: Option<i32>,
-                placement_policy: Option<String>,
-                config: Option<String>,
-                scheduling_policy: Option<String>,
-            }
-
-            let update = ShardUpdate {
-                generation: input_generation.map(|g| g.into().unwrap() as i32),
-                placement_policy: input_placement_policy
-                    .as_ref()
-                    .map(|p| serde_json::to_string(&p).unwrap()),
-                config: input_config
-                    .as_ref()
-                    .map(|c| serde_json::to_string(&c).unwrap()),
-                scheduling_policy: input_scheduling_policy
-                    .map(|p| serde_json::to_string(&p).unwrap()),
-            };
+            let changeset = ShardChangeset::new(
+                input_placement_policy.as_ref(),
+                input_config.as_ref(),
+                input_generation,
+                input_scheduling_policy,
+                input_reconcile_priority,
+            );
 
-            query.set(update).execute(conn)?;
+            query.set(changeset).execute(conn)?;
 
             Ok(())
         })
@@ -599,6 +681,97 @@ impl Persistence {
         Ok(())
     }
 
+    /// Like [`Self::update_tenant_shard`], for several shards in one transaction: either all the
+    /// updates are applied, or none of them are.  Use this when updating more than one shard of a
+    /// tenant with different values, so that a failure partway through cannot leave the database
+    /// with a mix of old and new state.
+    pub(crate) async fn update_tenant_shards(
+        &self,
+        updates: Vec<ShardUpdate>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::tenant_shards::dsl::*;
+
+        self.with_measured_conn(DatabaseOperation::UpdateTenantShards, move |conn| {
+            for (i, update) in updates.iter().enumerate() {
+                if i > 0 {
+                    fail::fail_point!("update-tenant-shards-partial", |_| Err(
+                        DatabaseError::Logical("failpoint".to_string())
+                    ));
+                }
+
+                let changeset = ShardChangeset::new(
+                    Some(&update.placement_policy),
+                    Some(&update.tenant_config),
+                    update.generation,
+                    None,
+                    None,
+                );
+
+                let tenant_shard_id = update.tenant_shard_id;
+                diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
+                    .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
+                    .filter(shard_count.eq(tenant_shard_id.shard_count.literal() as i32))
+                    .set(changeset)
+                    .execute(conn)?;
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    pub(crate) async fn set_pending_compute_notification(
+        &self,
+        tenant_shard_id: TenantShardId,
+        input_pending: bool,
+    ) -> DatabaseResult<()> {
+        use crate::schema::tenant_shards::dsl::*;
+        self.with_measured_conn(
+            DatabaseOperation::SetPendingComputeNotification,
+            move |conn| {
+                diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
+                    .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
+                    .filter(shard_count.eq(tenant_shard_id.shard_count.literal() as i32))
+                    .set(pending_compute_notification.eq(input_pending))
+                    .execute(conn)?;
+
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    pub(crate) async fn set_tenant_flag(
+        &self,
+        tenant: TenantFilter,
+        flag: TenantFlag,
+        value: bool,
+    ) -> DatabaseResult<()> {
+        use crate::schema::tenant_shards::dsl::*;
+        self.with_measured_conn(DatabaseOperation::SetTenantFlag, move |conn| {
+            let query = match tenant {
+                TenantFilter::Shard(tenant_shard_id) => diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(tenant_shard_id.tenant_id.to_string()))
+                    .filter(shard_number.eq(tenant_shard_id.shard_number.0 as i32))
+                    .filter(shard_count.eq(tenant_shard_id.shard_count.literal() as i32))
+                    .into_boxed(),
+                TenantFilter::Tenant(input_tenant_id) => diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(input_tenant_id.to_string()))
+                    .into_boxed(),
+            };
+            match flag {
+                TenantFlag::Sticky => query.set(sticky.eq(value)).execute(conn)?,
+                TenantFlag::Colocate => query.set(colocate.eq(value)).execute(conn)?,
+                TenantFlag::Canary => query.set(canary.eq(value)).execute(conn)?,
+            };
+
+            Ok(())
+        })
+        .await
+    }
+
     pub(crate) async fn detach(&self, tenant_shard_id: TenantShardId) -> anyhow::Result<()> {
         use crate::schema::tenant_shards::dsl::*;
         self.with_measured_conn(DatabaseOperation::Detach, move |conn| {
@@ -790,6 +963,56 @@ pub(crate) struct TenantShardPersistence {
     pub(crate) config: String,
     #[serde(default)]
     pub(crate) scheduling_policy: String,
+    #[serde(default)]
+    pub(crate) sticky: bool,
+    #[serde(default)]
+    pub(crate) colocate: bool,
+    #[serde(default)]
+    pub(crate) canary: bool,
+    #[serde(default)]
+    pub(crate) pending_compute_notification: bool,
+    #[serde(default)]
+    pub(crate) reconcile_priority: String,
+}
+
+/// An update to one shard's persistent properties, for [`Persistence::update_tenant_shards`]
+#[derive(Clone)]
+pub(crate) struct ShardUpdate {
+    pub(crate) tenant_shard_id: TenantShardId,
+    pub(crate) placement_policy: PlacementPolicy,
+    pub(crate) tenant_config: TenantConfig,
+
+    /// If this is None, generation is not updated.
+    pub(crate) generation: Option<Generation>,
+}
+
+/// Changes to the mutable properties of a tenant shard.  Fields left as None are not updated.
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::tenant_shards)]
+struct ShardChangeset {
+    generation: Option<i32>,
+    placement_policy: Option<String>,
+    config: Option<String>,
+    scheduling_policy: Option<String>,
+    reconcile_priority: Option<String>,
+}
+
+impl ShardChangeset {
+    fn new(
+        placement_policy: Option<&PlacementPolicy>,
+        config: Option<&TenantConfig>,
+        generation: Option<Generation>,
+        scheduling_policy: Option<ShardSchedulingPolicy>,
+        reconcile_priority: Option<ReconcilePriority>,
+    ) -> Self {
+        Self {
+            generation: generation.map(|g| g.into().unwrap() as i32),
+            placement_policy: placement_policy.map(|p| serde_json::to_string(p).unwrap()),
+            config: config.map(|c| serde_json::to_string(c).unwrap()),
+            scheduling_policy: scheduling_policy.map(|p| serde_json::to_string(&p).unwrap()),
+            reconcile_priority: reconcile_priority.map(|p| serde_json::to_string(&p).unwrap()),
+        }
+    }
 }
 
 impl TenantShardPersistence {
@@ -824,4 +1047,7 @@ pub(crate) struct NodePersistence {
     pub(crate) listen_http_port: i32,
     pub(crate) listen_pg_addr: String,
     pub(crate) listen_pg_port: i32,
+    /// JSON-encoded [`NodeCapabilities`], if the node reported them
+    #[serde(default)]
+    pub(crate) capabilities: Option<String>,
 }
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use pageserver_api::models::LocationConfigMode;
+use pageserver_api::shard::TenantShardId;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use utils::id::NodeId;
+
+use crate::tenant_shard::{ObservedState, ReconcileResult};
+use crate::Sequence;
+
+const EVENT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many events may be buffered for sending before we start dropping them.  Event
+/// export is best-effort: it must never apply backpressure to [`crate::service::Service::process_result`].
+pub(crate) const RECONCILE_EVENT_QUEUE_SIZE: usize = 1024;
+
+/// Summary of a [`ReconcileResult`], as published to the configured reconcile event URL.
+#[derive(Serialize, Debug)]
+pub(crate) struct ReconcileEvent {
+    tenant_shard_id: TenantShardId,
+    sequence: Sequence,
+    generation: Option<u32>,
+    success: bool,
+    error: Option<String>,
+
+    /// Observed location modes before applying the result (None means detached or unknown)
+    old_locations: HashMap<NodeId, Option<LocationConfigMode>>,
+    /// Location modes reported by the reconciler
+    new_locations: HashMap<NodeId, Option<LocationConfigMode>>,
+}
+
+fn location_modes(observed: &ObservedState) -> HashMap<NodeId, Option<LocationConfigMode>> {
+    observed
+        .locations
+        .iter()
+        .map(|(node_id, loc)| (*node_id, loc.conf.as_ref().map(|c| c.mode)))
+        .collect()
+}
+
+impl ReconcileEvent {
+    pub(crate) fn new(old_observed: &ObservedState, result: &ReconcileResult) -> Self {
+        Self {
+            tenant_shard_id: result.tenant_shard_id,
+            sequence: result.sequence,
+            generation: result.generation.and_then(|g| g.into()),
+            success: result.result.is_ok(),
+            error: result.result.as_ref().err().map(|e| e.to_string()),
+            old_locations: location_modes(old_observed),
+            new_locations: location_modes(&result.observed),
+        }
+    }
+}
+
+/// Publishes [`ReconcileEvent`]s to an external HTTP endpoint.  Events are POSTed one
+/// at a time in the order they were produced: failures are logged and the event is dropped.
+pub(crate) struct ReconcileEventSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl ReconcileEventSink {
+    pub(crate) fn new(url: String) -> Self {
+        let client = reqwest::ClientBuilder::new()
+            .timeout(EVENT_REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to construct HTTP client");
+
+        Self { url, client }
+    }
+
+    async fn send(&self, event: &ReconcileEvent) -> Result<(), reqwest::Error> {
+        self.client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Consume events until the channel closes or we are cancelled.
+    pub(crate) async fn run(
+        self,
+        mut event_rx: tokio::sync::mpsc::Receiver<ReconcileEvent>,
+        cancel: CancellationToken,
+    ) {
+        loop {
+            let event = tokio::select! {
+                e = event_rx.recv() => {
+                    match e {
+                        Some(e) => e,
+                        None => break,
+                    }
+                }
+                _ = cancel.cancelled() => break,
+            };
+
+            let sent = tokio::select! {
+                r = self.send(&event) => r,
+                _ = cancel.cancelled() => break,
+            };
+
+            if let Err(e) = sent {
+                tracing::warn!(
+                    tenant_id=%event.tenant_shard_id.tenant_id, shard_id=%event.tenant_shard_id.shard_slug(),
+                    "Failed to publish reconcile event: {e}"
+                );
+            }
+        }
+    }
+}
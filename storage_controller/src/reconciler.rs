@@ -1,6 +1,6 @@
 use crate::pageserver_client::PageserverClient;
 use crate::persistence::Persistence;
-use crate::service;
+use crate::service::{self, FrozenGenerations};
 use pageserver_api::controller_api::PlacementPolicy;
 use pageserver_api::models::{
     LocationConfig, LocationConfigMode, LocationConfigSecondary, TenantConfig,
@@ -9,6 +9,7 @@ use pageserver_api::shard::{ShardIdentity, TenantShardId};
 use pageserver_client::mgmt_api;
 use reqwest::StatusCode;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
@@ -70,6 +71,45 @@ pub(super) struct Reconciler {
 
     /// Access to persistent storage for updating generation numbers
     pub(crate) persistence: Arc<Persistence>,
+
+    /// Tenants whose generations we must not increment
+    pub(crate) frozen_generations: Arc<FrozenGenerations>,
+
+    /// The newest generation issued for this shard, which may advance while we run
+    pub(crate) generation_floor: Arc<GenerationFloor>,
+}
+
+/// The newest generation issued for a shard, shared between a
+/// [`crate::tenant_shard::TenantShard`] and its reconcilers.  A reconciler that was spawned before
+/// a newer generation was issued, e.g. by a re-attach, must not then attach a location with its
+/// older generation: a pageserver attached in an older generation may overwrite remote data
+/// written in the newer one.
+#[derive(Default)]
+pub(crate) struct GenerationFloor(AtomicU32);
+
+impl GenerationFloor {
+    pub(crate) fn raise(&self, generation: Generation) {
+        if let Some(generation) = generation.into() {
+            self.0.fetch_max(generation, Ordering::Relaxed);
+        }
+    }
+
+    /// Unlike [`Self::raise`], this may move the floor backwards: only for use when no
+    /// reconciler is running.
+    pub(crate) fn reset(&self, generation: Option<Generation>) {
+        let generation = generation.and_then(|g| g.into()).unwrap_or(0);
+        self.0.store(generation, Ordering::Relaxed);
+    }
+
+    pub(crate) fn check(&self, wanted: Option<u32>) -> Result<(), ReconcileError> {
+        let known = self.0.load(Ordering::Relaxed);
+        match wanted {
+            Some(wanted) if wanted < known => {
+                Err(ReconcileError::StaleGeneration { wanted, known })
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// RAII resource units granted to a Reconciler, which it should keep alive until it finishes doing I/O
@@ -92,6 +132,7 @@ impl ReconcileUnits {
 pub(crate) struct TargetState {
     pub(crate) attached: Option<Node>,
     pub(crate) secondary: Vec<Node>,
+    pub(crate) read_replicas: Vec<Node>,
 }
 
 impl TargetState {
@@ -113,6 +154,16 @@ impl TargetState {
                         .clone()
                 })
                 .collect(),
+            read_replicas: intent
+                .get_read_replicas()
+                .iter()
+                .map(|n| {
+                    nodes
+                        .get(n)
+                        .expect("Intent read replica referenced non-existent node")
+                        .clone()
+                })
+                .collect(),
         }
     }
 }
@@ -125,6 +176,8 @@ pub(crate) enum ReconcileError {
     Notify(#[from] NotifyError),
     #[error("Cancelled")]
     Cancel,
+    #[error("Refusing to configure location in generation {wanted}, a newer generation {known} was issued")]
+    StaleGeneration { wanted: u32, known: u32 },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -146,6 +199,10 @@ impl Reconciler {
             return Ok(());
         }
 
+        // Checked immediately before each request, so that we notice a newer generation issued
+        // at any point while we were running, e.g. while waiting for a secondary to warm up.
+        self.generation_floor.check(config.generation)?;
+
         self.observed
             .locations
             .insert(node.get_id(), ObservedStateLocation { conf: None });
@@ -475,11 +532,14 @@ impl Reconciler {
         }
 
         // Increment generation before attaching to new pageserver
-        self.generation = Some(
-            self.persistence
-                .increment_generation(self.tenant_shard_id, dest_ps.get_id())
-                .await?,
-        );
+        self.frozen_generations
+            .check(self.tenant_shard_id.tenant_id)?;
+        let generation = self
+            .persistence
+            .increment_generation(self.tenant_shard_id, dest_ps.get_id())
+            .await?;
+        self.generation_floor.raise(generation);
+        self.generation = Some(generation);
 
         let dest_conf = build_location_config(
             &self.shard,
@@ -684,10 +744,13 @@ impl Reconciler {
                     };
 
                     if increment_generation {
+                        self.frozen_generations
+                            .check(self.tenant_shard_id.tenant_id)?;
                         let generation = self
                             .persistence
                             .increment_generation(self.tenant_shard_id, node.get_id())
                             .await?;
+                        self.generation_floor.raise(generation);
                         self.generation = Some(generation);
                         wanted_conf.generation = generation.into();
                     }
@@ -725,6 +788,35 @@ impl Reconciler {
             }
         }
 
+        // Configure read replicas: these are attached in multi mode with the same generation as
+        // the attached location, so they must be configured after it (which may have incremented
+        // the generation).  Compute is not notified about these locations.
+        if !self.intent.read_replicas.is_empty() {
+            let Some(generation) = self.generation else {
+                return Err(ReconcileError::Other(anyhow::anyhow!(
+                    "Attempted to configure read replica with NULL generation"
+                )));
+            };
+            for node in &self.intent.read_replicas {
+                let wanted_conf = read_replica_location_conf(
+                    generation,
+                    &self.shard,
+                    &self.config,
+                    &self.placement_policy,
+                );
+                match self.observed.locations.get(&node.get_id()) {
+                    Some(conf) if conf.conf.as_ref() == Some(&wanted_conf) => {
+                        // Nothing to do
+                        tracing::info!(node_id=%node.get_id(), "Observed configuration already correct.")
+                    }
+                    _ => {
+                        tracing::info!(node_id=%node.get_id(), "Observed configuration requires update.");
+                        changes.push((node.clone(), wanted_conf))
+                    }
+                }
+            }
+        }
+
         // Detach any extraneous pageservers that are no longer referenced
         // by our intent.
         for node in &self.detach {
@@ -823,6 +915,20 @@ pub(crate) fn attached_location_conf(
     }
 }
 
+/// Location configuration for a read replica: like [`attached_location_conf`], but in multi mode
+/// so that the replica can serve reads alongside the attached location in the same generation.
+pub(crate) fn read_replica_location_conf(
+    generation: Generation,
+    shard: &ShardIdentity,
+    config: &TenantConfig,
+    policy: &PlacementPolicy,
+) -> LocationConfig {
+    LocationConfig {
+        mode: LocationConfigMode::AttachedMulti,
+        ..attached_location_conf(generation, shard, config, policy)
+    }
+}
+
 pub(crate) fn secondary_location_conf(
     shard: &ShardIdentity,
     config: &TenantConfig,
@@ -61,6 +61,10 @@ impl Eq for SchedulerNode {}
 #[derive(Serialize)]
 pub(crate) struct Scheduler {
     nodes: HashMap<NodeId, SchedulerNode>,
+
+    /// How many attached shards the spread between the least and most utilized candidate
+    /// nodes is worth, when choosing where to schedule.  Zero schedules on shard counts alone.
+    utilization_weight: usize,
 }
 
 /// Score for soft constraint scheduling: lower scores are preferred to higher scores.
@@ -114,6 +118,12 @@ pub(crate) struct ScheduleContext {
     pub(crate) attached_nodes: HashMap<NodeId, usize>,
 
     pub(crate) mode: ScheduleMode,
+
+    /// Prefer nodes that are already in use within this context, rather than avoiding them: for
+    /// packing a tenant's shards onto as few nodes as possible.  See [`TenantShard::colocate`].
+    ///
+    /// [`TenantShard::colocate`]: crate::tenant_shard::TenantShard::colocate
+    pub(crate) colocate: bool,
 }
 
 impl ScheduleContext {
@@ -168,7 +178,25 @@ impl Scheduler {
 
         Self {
             nodes: scheduler_nodes,
+            utilization_weight: 0,
+        }
+    }
+
+    pub(crate) fn set_utilization_weight(&mut self, utilization_weight: usize) {
+        self.utilization_weight = utilization_weight;
+    }
+
+    /// Express a node's utilization as a number of attached shards, scaled by
+    /// `utilization_weight` across the range of utilization seen on the candidate nodes:
+    /// the least utilized candidate gets zero, and the most utilized gets the full weight.
+    fn utilization_penalty(&self, score: UtilizationScore, range: (u64, u64)) -> usize {
+        let (min, max) = range;
+        if self.utilization_weight == 0 || max <= min {
+            return 0;
         }
+
+        let relative = (score.0.clamp(min, max) - min) as u128;
+        (relative * self.utilization_weight as u128 / (max - min) as u128) as usize
     }
 
     /// For debug/support: check that our internal statistics are in sync with the state of
@@ -207,7 +235,12 @@ impl Scheduler {
                 }
             }
 
-            for node_id in shard.intent.get_secondary() {
+            for node_id in shard
+                .intent
+                .get_secondary()
+                .iter()
+                .chain(shard.intent.get_read_replicas())
+            {
                 match expect_nodes.get_mut(node_id) {
                     Some(node) => node.shard_count += 1,
                     None => anyhow::bail!(
@@ -391,31 +424,55 @@ impl Scheduler {
             return Err(ScheduleError::NoPageservers);
         }
 
-        let mut scores: Vec<(NodeId, AffinityScore, usize, usize)> = self
+        let candidates: Vec<(&NodeId, &SchedulerNode, UtilizationScore)> = self
             .nodes
             .iter()
-            .filter_map(|(k, v)| {
-                if hard_exclude.contains(k) || v.may_schedule == MaySchedule::No {
-                    None
-                } else {
-                    Some((
-                        *k,
-                        context.nodes.get(k).copied().unwrap_or(AffinityScore::FREE),
-                        v.shard_count,
-                        v.attached_shard_count,
-                    ))
+            .filter_map(|(k, v)| match v.may_schedule {
+                MaySchedule::Yes(utilization) if !hard_exclude.contains(k) => {
+                    Some((k, v, utilization))
                 }
+                _ => None,
+            })
+            .collect();
+
+        let utilization_range = candidates
+            .iter()
+            .map(|(_, _, utilization)| utilization.0)
+            .minmax()
+            .into_option()
+            .unwrap_or_default();
+
+        let mut scores: Vec<(NodeId, AffinityScore, usize, usize, usize)> = candidates
+            .into_iter()
+            .map(|(k, v, utilization)| {
+                (
+                    *k,
+                    context.nodes.get(k).copied().unwrap_or(AffinityScore::FREE),
+                    v.shard_count,
+                    v.attached_shard_count
+                        + self.utilization_penalty(utilization, utilization_range),
+                    context.get_node_attachments(*k),
+                )
             })
             .collect();
 
         // Sort by, in order of precedence:
         //  1st: Affinity score.  We should never pick a higher-score node if a lower-score node is available
-        //  2nd: Attached shard count.  Within nodes with the same affinity, we always pick the node with
-        //  the least number of attached shards.
-        //  3rd: Total shard count.  Within nodes with the same affinity and attached shard count, use nodes
+        //  2nd: Attached shard count within the context.  Amongst nodes holding equally many of this tenant's
+        //  locations, prefer those where they are secondaries, so that the tenant's attachments (which carry
+        //  its load) spread out independently of its secondaries.
+        //  3rd: Attached shard count.  Within nodes with the same affinity, we always pick the node with
+        //  the least number of attached shards, after weighting each node's count by its utilization.
+        //  4th: Total shard count.  Within nodes with the same affinity and attached shard count, use nodes
         //  with the lower total shard count.
-        //  4th: Node ID.  This is a convenience to make selection deterministic in tests and empty systems.
-        scores.sort_by_key(|i| (i.1, i.3, i.2, i.0));
+        //  5th: Node ID.  This is a convenience to make selection deterministic in tests and empty systems.
+        //
+        // When co-locating, the affinity score is inverted: we prefer nodes this tenant already uses.
+        if context.colocate {
+            scores.sort_by_key(|i| (std::cmp::Reverse(i.1), i.3, i.2, i.0));
+        } else {
+            scores.sort_by_key(|i| (i.1, i.4, i.3, i.2, i.0));
+        }
 
         if scores.is_empty() {
             // After applying constraints, no pageservers were left.
@@ -500,6 +557,7 @@ mod tests {
     use super::*;
 
     use crate::tenant_shard::IntentState;
+    use pageserver_api::controller_api::{NodeAvailability, NodeSchedulingPolicy};
     #[test]
     fn scheduler_basic() -> anyhow::Result<()> {
         let nodes = test_utils::make_test_nodes(2);
@@ -557,4 +615,110 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn scheduler_weights_utilization() -> anyhow::Result<()> {
+        let mut nodes = test_utils::make_test_nodes(2);
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(UtilizationScore(1000)));
+        nodes
+            .get_mut(&NodeId(2))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(UtilizationScore(10)));
+
+        // Place shards one by one, as if for a stream of new single-shard tenants
+        let place = |scheduler: &mut Scheduler| -> anyhow::Result<Vec<IntentState>> {
+            let mut intents = Vec::new();
+            for _ in 0..12 {
+                let mut intent = IntentState::new();
+                let node_id = scheduler.schedule_shard(&[], &ScheduleContext::default())?;
+                intent.set_attached(scheduler, Some(node_id));
+                intents.push(intent);
+            }
+            Ok(intents)
+        };
+
+        // Without a weight, only shard counts matter
+        let mut scheduler = Scheduler::new(nodes.values());
+        let mut intents = place(&mut scheduler)?;
+        assert_eq!(scheduler.get_node_attached_shard_count(NodeId(1)), 6);
+        assert_eq!(scheduler.get_node_attached_shard_count(NodeId(2)), 6);
+        intents.iter_mut().for_each(|i| i.clear(&mut scheduler));
+
+        // With a weight, the heavily utilized node takes fewer shards than its idle peer, which
+        // started from the same shard count
+        let mut scheduler = Scheduler::new(nodes.values());
+        scheduler.set_utilization_weight(4);
+        let mut intents = place(&mut scheduler)?;
+        assert_eq!(scheduler.get_node_attached_shard_count(NodeId(1)), 4);
+        assert_eq!(scheduler.get_node_attached_shard_count(NodeId(2)), 8);
+        intents.iter_mut().for_each(|i| i.clear(&mut scheduler));
+
+        Ok(())
+    }
+
+    #[test]
+    fn scheduler_skips_paused_nodes() -> anyhow::Result<()> {
+        let mut nodes = test_utils::make_test_nodes(2);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let context = ScheduleContext::default();
+
+        // A paused (cordoned) node keeps its shards, but is never picked for new ones, even
+        // when it is the emptiest node
+        let mut existing = IntentState::new();
+        existing.set_attached(&mut scheduler, Some(NodeId(2)));
+        let node = nodes.get_mut(&NodeId(1)).unwrap();
+        node.set_scheduling(NodeSchedulingPolicy::Pause);
+        scheduler.node_upsert(node);
+
+        let mut intents = Vec::new();
+        for _ in 0..3 {
+            let mut intent = IntentState::new();
+            let node_id = scheduler.schedule_shard(&[], &context)?;
+            intent.set_attached(&mut scheduler, Some(node_id));
+            intents.push(intent);
+        }
+        assert_eq!(scheduler.get_node_shard_count(NodeId(1)), 0);
+        assert_eq!(scheduler.get_node_shard_count(NodeId(2)), 4);
+        assert!(matches!(
+            scheduler.schedule_shard(&[NodeId(2)], &context),
+            Err(ScheduleError::ImpossibleConstraint)
+        ));
+
+        existing.clear(&mut scheduler);
+        intents.iter_mut().for_each(|i| i.clear(&mut scheduler));
+        Ok(())
+    }
+
+    #[test]
+    fn scheduler_spreads_tenant_attachments() -> anyhow::Result<()> {
+        let nodes = test_utils::make_test_nodes(2);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        // Node 2 carries more attachments from other tenants
+        let mut other_intent = IntentState::new();
+        other_intent.set_attached(&mut scheduler, Some(NodeId(2)));
+
+        // This tenant has one location on each node: attached on node 1, secondary on node 2
+        let mut sibling_intent = IntentState::new();
+        sibling_intent.set_attached(&mut scheduler, Some(NodeId(1)));
+        sibling_intent.push_secondary(&mut scheduler, NodeId(2));
+        let mut context = ScheduleContext::default();
+        context.avoid(&sibling_intent.all_pageservers());
+        context.push_attached(NodeId(1));
+
+        // With equal affinity, prefer the node where this tenant has no attachment, even though
+        // it is busier overall
+        assert_eq!(scheduler.schedule_shard(&[], &context)?, NodeId(2));
+
+        // Anti-affinity is a soft preference: with nowhere else to go, co-locate
+        assert_eq!(scheduler.schedule_shard(&[NodeId(2)], &context)?, NodeId(1));
+
+        other_intent.clear(&mut scheduler);
+        sibling_intent.clear(&mut scheduler);
+
+        Ok(())
+    }
 }
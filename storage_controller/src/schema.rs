@@ -8,6 +8,7 @@ diesel::table! {
         listen_http_port -> Int4,
         listen_pg_addr -> Varchar,
         listen_pg_port -> Int4,
+        capabilities -> Nullable<Varchar>,
     }
 }
 
@@ -23,6 +24,11 @@ diesel::table! {
         splitting -> Int2,
         config -> Text,
         scheduling_policy -> Varchar,
+        sticky -> Bool,
+        colocate -> Bool,
+        canary -> Bool,
+        pending_compute_notification -> Bool,
+        reconcile_priority -> Varchar,
     }
 }
 
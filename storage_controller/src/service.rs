@@ -1,25 +1,31 @@
 use std::{
     borrow::Cow,
     cmp::Ordering,
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     path::PathBuf,
     str::FromStr,
-    sync::Arc,
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::{
     background_node_operations::{
-        Drain, Fill, Operation, OperationError, OperationHandler, MAX_RECONCILES_PER_OPERATION,
+        operation_reconcile_budget, Drain, Fill, Operation, OperationError, OperationHandler,
+        MAX_RECONCILES_PER_OPERATION,
     },
     compute_hook::NotifyError,
     id_lock_map::{trace_exclusive_lock, trace_shared_lock, IdLockMap, TracingExclusiveGuard},
-    persistence::{AbortShardSplitStatus, TenantFilter},
+    liveness::{CountedReceiver, CountedSender, Processor, ProcessorLiveness},
+    persistence::{AbortShardSplitStatus, ShardUpdate, TenantFilter},
+    reconcile_events::{ReconcileEvent, ReconcileEventSink, RECONCILE_EVENT_QUEUE_SIZE},
     reconciler::{ReconcileError, ReconcileUnits},
     scheduler::{MaySchedule, ScheduleContext, ScheduleMode},
     tenant_shard::{
         MigrateAttachment, ReconcileNeeded, ReconcilerStatus, ScheduleOptimization,
-        ScheduleOptimizationAction,
+        ScheduleOptimizationAction, TenantFlag, TenantFlags,
     },
 };
 use anyhow::Context;
@@ -27,19 +33,37 @@ use control_plane::storage_controller::{
     AttachHookRequest, AttachHookResponse, InspectRequest, InspectResponse,
 };
 use diesel::result::DatabaseErrorKind;
-use futures::{stream::FuturesUnordered, StreamExt};
+use futures::{stream::FuturesUnordered, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use pageserver_api::{
     controller_api::{
-        NodeAvailability, NodeRegisterRequest, NodeSchedulingPolicy, PlacementPolicy,
-        ShardSchedulingPolicy, TenantCreateRequest, TenantCreateResponse,
-        TenantCreateResponseShard, TenantDescribeResponse, TenantDescribeResponseShard,
-        TenantLocateResponse, TenantPolicyRequest, TenantShardMigrateRequest,
-        TenantShardMigrateResponse, UtilizationScore,
+        AutosplitConfig, BackgroundProcessorsResponse, ComputeNotificationDrift,
+        ConsistencyCheckRelaxedResponse, DelayedReconcileShard, LocationDrift, LocationDriftKind,
+        LocationDriftResponse, NodeAvailability, NodeFillPreviewResponse, NodeFillPreviewShard,
+        NodeHistoryResponse, NodeMigrateTenantsResponse, NodeRegisterRequest, NodeRescanResponse,
+        NodeSchedulingPolicy, NodeTransitionSource, ObservedLocation, PassthroughErrorCode,
+        PlacementPolicy, ReconcileAttentionShard, ReconcilePriority, ReconcileStatus,
+        ReconcileThroughputResponse, ShardSchedulingPolicy, TenantCancelReconcilesResponse,
+        TenantCreateRequest, TenantCreateResponse, TenantCreateResponseShard,
+        TenantDescribeResponse, TenantDescribeResponseShard, TenantEvacuateNodeResponse,
+        TenantLocateResponse, TenantPlacementStatusResponse, TenantPlacementStatusShard,
+        TenantPolicyRequest, TenantRebalanceResponse, TenantReconcileWatchResponse,
+        TenantRedundancyStatusResponse, TenantRedundancyStatusShard, TenantRoutingStatusResponse,
+        TenantSecondaryDownloadResponse, TenantSetPlacementRequest, TenantSetPlacementResponse,
+        TenantShardCancelReconcileResponse, TenantShardCountRecommendation,
+        TenantShardIncrementGenerationResponse, TenantShardMigrateRequest,
+        TenantShardMigrateResponse, TenantShardPlacement, TenantShardReadReplicaRequest,
+        TenantShardReadReplicaResponse, TenantShardReconcileStatus, TenantSummaryResponse,
+        UtilizationScore,
+    },
+    models::{
+        SecondaryProgress, TenantConfigRequest, TenantSorting, TopTenantShardItem,
+        TopTenantShardsRequest,
     },
-    models::{SecondaryProgress, TenantConfigRequest, TopTenantShardsRequest},
 };
+use rand::Rng;
 use reqwest::StatusCode;
+use serde::Serialize;
 use tracing::{instrument, Instrument};
 
 use crate::pageserver_client::PageserverClient;
@@ -71,8 +95,12 @@ use utils::{
 use crate::{
     compute_hook::ComputeHook,
     heartbeater::{Heartbeater, PageserverState},
+    metrics::METRICS_REGISTRY,
     node::{AvailabilityTransition, Node},
-    persistence::{split_state::SplitState, DatabaseError, Persistence, TenantShardPersistence},
+    persistence::{
+        split_state::SplitState, DatabaseError, NodePersistence, Persistence,
+        TenantShardPersistence,
+    },
     reconciler::attached_location_conf,
     scheduler::Scheduler,
     tenant_shard::{
@@ -88,6 +116,21 @@ const SHORT_RECONCILE_TIMEOUT: Duration = Duration::from_secs(5);
 // some data in it.
 pub const RECONCILE_TIMEOUT: Duration = Duration::from_secs(30);
 
+// Upper bound on reconcile timeouts that callers may request in place of the defaults above, so
+// that a client cannot hold a request handler open indefinitely.
+const MAX_RECONCILE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The timeout to wait for reconciles with, given a caller's requested override
+fn reconcile_timeout(requested: Option<Duration>, default: Duration) -> Duration {
+    requested
+        .map(|t| std::cmp::min(t, MAX_RECONCILE_TIMEOUT))
+        .unwrap_or(default)
+}
+
+// Bounds on the Retry-After hint we give callers when reconciles are queued behind the concurrency limit
+const RETRY_AFTER_MIN: Duration = Duration::from_secs(1);
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(300);
+
 // If we receive a call using Secondary mode initially, it will omit generation.  We will initialize
 // tenant shards into this generation, and as long as it remains in this generation, we will accept
 // input generation from future requests as authoritative.
@@ -95,7 +138,78 @@ const INITIAL_GENERATION: Generation = Generation::new(0);
 
 /// How long [`Service::startup_reconcile`] is allowed to take before it should give
 /// up on unresponsive pageservers and proceed.
-pub(crate) const STARTUP_RECONCILE_TIMEOUT: Duration = Duration::from_secs(30);
+pub const STARTUP_RECONCILE_TIMEOUT_DEFAULT: Duration = Duration::from_secs(30);
+
+/// How much of [`STARTUP_RECONCILE_TIMEOUT_DEFAULT`] may be spent waiting for pageservers to
+/// list their locations, before we proceed without those that haven't responded.
+pub const STARTUP_SCAN_TIMEOUT_DEFAULT: Duration = Duration::from_secs(15);
+
+/// A shard's observed locations, ordered by node ID
+fn observed_locations(shard: &TenantShard) -> Vec<ObservedLocation> {
+    let mut observed = shard
+        .observed
+        .locations
+        .iter()
+        .map(|(node_id, location)| ObservedLocation {
+            node_id: *node_id,
+            conf: location.conf.clone(),
+        })
+        .collect::<Vec<_>>();
+    observed.sort_by_key(|l| l.node_id);
+    observed
+}
+
+/// Available nodes in Draining or Filling with no ongoing background operation for them: such
+/// a policy was set by an operation which ended without finalizing.
+fn orphaned_operation_nodes(
+    nodes: &HashMap<NodeId, Node>,
+    ongoing_operations: &HashMap<NodeId, OperationHandler>,
+) -> HashSet<NodeId> {
+    nodes
+        .values()
+        .filter(|node| {
+            node.is_available()
+                && matches!(
+                    node.get_scheduling(),
+                    NodeSchedulingPolicy::Draining | NodeSchedulingPolicy::Filling
+                )
+                && !ongoing_operations.contains_key(&node.get_id())
+        })
+        .map(|node| node.get_id())
+        .collect()
+}
+
+/// Compare what we last observed of a shard on a node with what the node reported in its location
+/// listing.  A detached location is as good as none.  Locations either side is unsure of are not
+/// drift: our `conf` is None when the outcome of configuring it is unknown, and the node reports
+/// None while the location is still being configured.
+fn location_drift(
+    observed: Option<&ObservedStateLocation>,
+    reported: Option<&Option<LocationConfig>>,
+) -> Option<LocationDriftKind> {
+    let detached = |conf: &LocationConfig| conf.mode == LocationConfigMode::Detached;
+    let observed = observed
+        .map(|o| o.conf.as_ref())
+        .filter(|conf| !conf.is_some_and(detached));
+    let reported = reported
+        .map(|r| r.as_ref())
+        .filter(|conf| !conf.is_some_and(detached));
+
+    match (observed, reported) {
+        (None, None) => None,
+        (None, Some(_)) => Some(LocationDriftKind::Unexpected),
+        (Some(None), _) | (Some(Some(_)), Some(None)) => None,
+        (Some(Some(_)), None) => Some(LocationDriftKind::Missing),
+        (Some(Some(observed)), Some(Some(reported))) => (observed.mode != reported.mode
+            || observed.generation != reported.generation)
+            .then_some(LocationDriftKind::Mismatch),
+    }
+}
+
+/// Each pageserver gets this long to respond to a location listing during the startup scan,
+/// per [`NODE_SCAN_NODES_PER_STEP`] nodes being scanned at once.
+const NODE_SCAN_REQUEST_TIMEOUT_STEP: Duration = Duration::from_secs(1);
+const NODE_SCAN_NODES_PER_STEP: usize = 10;
 
 /// How long a node may be unresponsive to heartbeats before we declare it offline.
 /// This must be long enough to cover node restarts as well as normal operations: in future
@@ -103,6 +217,10 @@ pub(crate) const STARTUP_RECONCILE_TIMEOUT: Duration = Duration::from_secs(30);
 /// (`<https://github.com/neondatabase/neon/issues/7552>`)
 pub const MAX_UNAVAILABLE_INTERVAL_DEFAULT: Duration = Duration::from_secs(300);
 
+/// How long an available node may sit in Draining or Filling without a matching background
+/// operation before the heartbeat driver resets it to Active.
+pub const ORPHANED_NODE_OPERATION_TIMEOUT_DEFAULT: Duration = Duration::from_secs(600);
+
 #[derive(Clone, strum_macros::Display)]
 enum TenantOperations {
     Create,
@@ -115,5637 +233,11485 @@ enum TenantOperations {
     SecondaryDownload,
     TimelineCreate,
     TimelineDelete,
+    SetGeneration,
+    IncrementGeneration,
+    SetFlag,
+    ShardDetach,
+    SetPlacement,
+    Failover,
 }
 
 #[derive(Clone, strum_macros::Display)]
 enum NodeOperations {
     Register,
     Configure,
+    Delete,
 }
 
 pub const RECONCILER_CONCURRENCY_DEFAULT: usize = 128;
 
+/// Spread out the ticks of periodic background work by this percentage either way, so that
+/// several controllers (or one that restarted at the same time as others) don't act in lockstep.
+pub const BACKGROUND_JITTER_PCT_DEFAULT: u32 = 20;
+
+/// By default, schedule on shard counts alone: pageservers do not yet report a meaningful
+/// utilization score.
+pub const SCHEDULER_UTILIZATION_WEIGHT_DEFAULT: usize = 0;
+
+/// By default, send one compute notification per tenant
+pub const COMPUTE_HOOK_BATCH_SIZE_DEFAULT: usize = 1;
+pub const COMPUTE_HOOK_BATCH_INTERVAL_DEFAULT: Duration = Duration::from_millis(100);
+
+pub const MAX_DEBUG_DUMP_BYTES_DEFAULT: usize = 256 * 1024 * 1024;
+
+pub const MAX_OPTIMIZATIONS_EXEC_PER_PASS_DEFAULT: usize = 2;
+pub const MAX_OPTIMIZATIONS_PLAN_PER_PASS_DEFAULT: usize = 8;
+
 // Depth of the channel used to enqueue shards for reconciliation when they can't do it immediately.
 // This channel is finite-size to avoid using excessive memory if we get into a state where reconciles are finishing more slowly
 // than they're being pushed onto the queue.
 const MAX_DELAYED_RECONCILES: usize = 10000;
 
-// Top level state available to all HTTP handlers
-struct ServiceState {
-    tenants: BTreeMap<TenantShardId, TenantShard>,
+// How many shards each node recovers at once during time travel recovery
+const TIME_TRAVEL_CONCURRENCY_PER_NODE: usize = 4;
 
-    nodes: Arc<HashMap<NodeId, Node>>,
+// The largest shard count that auto-splitting will split a tenant into, until changed
+// with [`Service::update_autosplit_config`]
+const SPLIT_TO_MAX_DEFAULT: ShardCount = ShardCount::new(8);
 
-    scheduler: Scheduler,
+// The largest power of two that fits in a [`ShardCount`]: auto-splitting can't go beyond this
+const SPLIT_TO_LIMIT: ShardCount = ShardCount::new(128);
 
-    /// Ongoing background operation on the cluster if any is running.
-    /// Note that only one such operation may run at any given time,
-    /// hence the type choice.
-    ongoing_operation: Option<OperationHandler>,
+/// The largest stripe size a shard split may set, in pages: 4GiB, or 16 times the default.  Larger
+/// stripes would leave most of a tenant's data on a few shards, defeating the point of splitting.
+const SPLIT_STRIPE_SIZE_LIMIT: ShardStripeSize = ShardStripeSize(4 * 1024 * 1024 / 8);
 
-    /// Queue of tenants who are waiting for concurrency limits to permit them to reconcile
-    delayed_reconcile_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
+/// Period over which [`Service::reconcile_throughput`] measures completed reconciles
+const RECONCILE_THROUGHPUT_WINDOW: Duration = Duration::from_secs(600);
+
+/// Granularity of the counts that [`ReconcileThroughput`] keeps
+const RECONCILE_THROUGHPUT_BUCKET: Duration = Duration::from_secs(60);
+
+// Depth of the channel that notifies [`Service::tenant_reconcile_watch`] callers of completed reconciles.
+// A watcher which falls this far behind re-reads its tenant's status rather than missing an update.
+const RECONCILE_WATCH_QUEUE_SIZE: usize = 1024;
+
+/// Upper bound on how long [`Service::tenant_reconcile_watch`] holds a request open
+pub(crate) const RECONCILE_WATCH_MAX_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Counts of completed reconciles, in [`RECONCILE_THROUGHPUT_BUCKET`]-long buckets covering the
+/// last [`RECONCILE_THROUGHPUT_WINDOW`].
+struct ReconcileThroughput {
+    /// When we started counting: until a whole window has passed, rates are over the time since then
+    since: Instant,
+    buckets: VecDeque<ReconcileThroughputBucket>,
 }
 
-/// Transform an error from a pageserver into an error to return to callers of a storage
-/// controller API.
-fn passthrough_api_error(node: &Node, e: mgmt_api::Error) -> ApiError {
-    match e {
-        mgmt_api::Error::ReceiveErrorBody(str) => {
-            // Presume errors receiving body are connectivity/availability issues
-            ApiError::ResourceUnavailable(
-                format!("{node} error receiving error body: {str}").into(),
-            )
-        }
-        mgmt_api::Error::ReceiveBody(str) => {
-            // Presume errors receiving body are connectivity/availability issues
-            ApiError::ResourceUnavailable(format!("{node} error receiving body: {str}").into())
-        }
-        mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, msg) => {
-            ApiError::NotFound(anyhow::anyhow!(format!("{node}: {msg}")).into())
+struct ReconcileThroughputBucket {
+    start: Instant,
+    succeeded: usize,
+    failed: usize,
+}
+
+impl ReconcileThroughput {
+    fn new(now: Instant) -> Self {
+        Self {
+            since: now,
+            buckets: VecDeque::new(),
         }
-        mgmt_api::Error::ApiError(StatusCode::SERVICE_UNAVAILABLE, msg) => {
-            ApiError::ResourceUnavailable(format!("{node}: {msg}").into())
+    }
+
+    fn record(&mut self, now: Instant, success: bool) {
+        if !self
+            .buckets
+            .back()
+            .is_some_and(|b| now.duration_since(b.start) < RECONCILE_THROUGHPUT_BUCKET)
+        {
+            self.buckets.push_back(ReconcileThroughputBucket {
+                start: now,
+                succeeded: 0,
+                failed: 0,
+            });
         }
-        mgmt_api::Error::ApiError(status @ StatusCode::UNAUTHORIZED, msg)
-        | mgmt_api::Error::ApiError(status @ StatusCode::FORBIDDEN, msg) => {
-            // Auth errors talking to a pageserver are not auth errors for the caller: they are
-            // internal server errors, showing that something is wrong with the pageserver or
-            // storage controller's auth configuration.
-            ApiError::InternalServerError(anyhow::anyhow!("{node} {status}: {msg}"))
+        let bucket = self.buckets.back_mut().expect("Pushed above");
+        if success {
+            bucket.succeeded += 1;
+        } else {
+            bucket.failed += 1;
         }
-        mgmt_api::Error::ApiError(status, msg) => {
-            // Presume general case of pageserver API errors is that we tried to do something
-            // that can't be done right now.
-            ApiError::Conflict(format!("{node} {status}: {status} {msg}"))
+
+        while self
+            .buckets
+            .front()
+            .is_some_and(|b| now.duration_since(b.start) >= RECONCILE_THROUGHPUT_WINDOW)
+        {
+            self.buckets.pop_front();
         }
-        mgmt_api::Error::Cancelled => ApiError::ShuttingDown,
     }
-}
 
-impl ServiceState {
-    fn new(
-        nodes: HashMap<NodeId, Node>,
-        tenants: BTreeMap<TenantShardId, TenantShard>,
-        scheduler: Scheduler,
-        delayed_reconcile_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
-    ) -> Self {
-        Self {
-            tenants,
-            nodes: Arc::new(nodes),
-            scheduler,
-            ongoing_operation: None,
-            delayed_reconcile_rx,
-        }
+    /// Reconciles completed per minute over the window, as (succeeded, failed)
+    fn per_minute(&self, now: Instant) -> (f64, f64) {
+        let (succeeded, failed) = self
+            .buckets
+            .iter()
+            .filter(|b| now.duration_since(b.start) < RECONCILE_THROUGHPUT_WINDOW)
+            .fold((0, 0), |(s, f), b| (s + b.succeeded, f + b.failed));
+
+        // Shortly after startup, measure over the time since then, but not so short a time that a
+        // few reconciles look like a high rate.
+        let minutes = now
+            .duration_since(self.since)
+            .clamp(RECONCILE_THROUGHPUT_BUCKET, RECONCILE_THROUGHPUT_WINDOW)
+            .as_secs_f64()
+            / 60.0;
+
+        (succeeded as f64 / minutes, failed as f64 / minutes)
     }
 
-    fn parts_mut(
-        &mut self,
-    ) -> (
-        &mut Arc<HashMap<NodeId, Node>>,
-        &mut BTreeMap<TenantShardId, TenantShard>,
-        &mut Scheduler,
-    ) {
-        (&mut self.nodes, &mut self.tenants, &mut self.scheduler)
+    fn publish(&self, now: Instant) {
+        let (succeeded, failed) = self.per_minute(now);
+        METRICS_REGISTRY
+            .metrics_group
+            .storage_controller_reconcile_throughput
+            .set((succeeded + failed).round() as i64);
     }
 }
 
-#[derive(Clone)]
-pub struct Config {
-    // All pageservers managed by one instance of this service must have
-    // the same public key.  This JWT token will be used to authenticate
-    // this service to the pageservers it manages.
-    pub jwt_token: Option<String>,
+/// The shard count at which each shard of a tenant with size `size` would be no larger than
+/// `split_threshold`, rounded up to a power of two and capped at `max`.
+fn recommended_shard_count(size: u64, split_threshold: u64, max: ShardCount) -> ShardCount {
+    let needed = size.div_ceil(std::cmp::max(split_threshold, 1)).max(1);
+    let rounded = needed.checked_next_power_of_two().unwrap_or(u64::MAX);
+    ShardCount::new(std::cmp::min(rounded, max.count() as u64) as u8)
+}
 
-    // This JWT token will be used to authenticate this service to the control plane.
-    pub control_plane_jwt_token: Option<String>,
+/// Whether a time of day falls within a window, which spans midnight if it ends before it starts.
+fn time_in_window(window: (chrono::NaiveTime, chrono::NaiveTime), time: chrono::NaiveTime) -> bool {
+    let (start, end) = window;
+    if start <= end {
+        start <= time && time < end
+    } else {
+        start <= time || time < end
+    }
+}
 
-    /// Where the compute hook should send notifications of pageserver attachment locations
-    /// (this URL points to the control plane in prod). If this is None, the compute hook will
-    /// assume it is running in a test environment and try to update neon_local.
-    pub compute_hook_url: Option<String>,
+/// Jitter a period by an integer percentage: returned values are uniform in the range
+/// 100-pct..100+pct percent of `period`.
+fn period_jitter(period: Duration, pct: u32) -> Duration {
+    if period == Duration::ZERO || pct == 0 {
+        period
+    } else {
+        rand::thread_rng().gen_range((period * (100 - pct)) / 100..(period * (100 + pct)) / 100)
+    }
+}
 
-    /// Grace period within which a pageserver does not respond to heartbeats, but is still
-    /// considered active. Once the grace period elapses, the next heartbeat failure will
-    /// mark the pagseserver offline.
-    pub max_unavailable_interval: Duration,
+/// How long each pageserver's location listing request may take during [`Service::scan_node_locations`].
+/// The more nodes we scan at once, the more time we allow, since we and the network are busier:
+/// slow-but-healthy pageservers shouldn't be left out.  This never exceeds the whole scan's timeout.
+fn node_scan_request_timeout(node_count: usize, scan_timeout: Duration) -> Duration {
+    let steps = std::cmp::max(node_count.div_ceil(NODE_SCAN_NODES_PER_STEP), 1);
+    std::cmp::min(
+        NODE_SCAN_REQUEST_TIMEOUT_STEP.saturating_mul(steps as u32),
+        scan_timeout,
+    )
+}
 
-    /// How many Reconcilers may be spawned concurrently
-    pub reconciler_concurrency: usize,
+/// Auto-splitting splits tenants into a power of two number of shards, up to [`SPLIT_TO_LIMIT`]
+fn validate_split_to(split_to: ShardCount) -> Result<(), ApiError> {
+    if split_to.count() > SPLIT_TO_LIMIT.count() {
+        return Err(ApiError::BadRequest(anyhow::anyhow!(
+            "Cannot auto-split to {} shards: the maximum is {}",
+            split_to.count(),
+            SPLIT_TO_LIMIT.count()
+        )));
+    }
+    if split_to.count() < 2 || !split_to.count().is_power_of_two() {
+        return Err(ApiError::BadRequest(anyhow::anyhow!(
+            "Cannot auto-split to {} shards: must be a power of two, of at least 2",
+            split_to.literal()
+        )));
+    }
 
-    /// How large must a shard grow in bytes before we split it?
-    /// None disables auto-splitting.
-    pub split_threshold: Option<u64>,
+    Ok(())
+}
 
-    // TODO: make this cfg(feature  = "testing")
-    pub neon_local_repo_dir: Option<PathBuf>,
+/// Shard splits may set a new stripe size, up to [`SPLIT_STRIPE_SIZE_LIMIT`].  Stripe sizes are
+/// counted in pages, so any nonzero value is a whole number of pages: we only check the bounds.
+fn validate_split_stripe_size(stripe_size: ShardStripeSize) -> Result<(), ApiError> {
+    if stripe_size.0 == 0 || stripe_size.0 > SPLIT_STRIPE_SIZE_LIMIT.0 {
+        return Err(ApiError::BadRequest(anyhow::anyhow!(
+            "Invalid stripe size {} pages: must be between 1 and {}",
+            stripe_size.0,
+            SPLIT_STRIPE_SIZE_LIMIT.0
+        )));
+    }
+
+    Ok(())
 }
 
-impl From<DatabaseError> for ApiError {
-    fn from(err: DatabaseError) -> ApiError {
-        match err {
-            DatabaseError::Query(e) => ApiError::InternalServerError(e.into()),
-            // FIXME: ApiError doesn't have an Unavailable variant, but ShuttingDown maps to 503.
-            DatabaseError::Connection(_) | DatabaseError::ConnectionPool(_) => {
-                ApiError::ShuttingDown
-            }
-            DatabaseError::Logical(reason) => {
-                ApiError::InternalServerError(anyhow::anyhow!(reason))
-            }
+/// Rank the tenants of shards reported by pageservers for auto-splitting, largest first, measuring
+/// each by `sorting`.  Shards of the same tenant may report different sizes, so take the largest.
+/// Canary tenants are never auto-split, so they are left out.
+fn rank_autosplit_candidates(
+    top_n: &[TopTenantShardItem],
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+    sorting: &TenantSorting,
+) -> Vec<(TenantId, u64)> {
+    let mut sizes: HashMap<TenantId, u64> = HashMap::new();
+    for item in top_n {
+        if tenants.get(&item.id).is_some_and(|shard| shard.canary) {
+            continue;
         }
+
+        let item_size = match sorting {
+            TenantSorting::ResidentSize => item.resident_size,
+            TenantSorting::MaxLogicalSize => item.max_logical_size,
+        };
+        let size = sizes.entry(item.id.tenant_id).or_default();
+        *size = std::cmp::max(*size, item_size);
     }
-}
 
-pub struct Service {
-    inner: Arc<std::sync::RwLock<ServiceState>>,
-    config: Config,
-    persistence: Arc<Persistence>,
-    compute_hook: Arc<ComputeHook>,
-    result_tx: tokio::sync::mpsc::UnboundedSender<ReconcileResult>,
+    let mut ranked = sizes.into_iter().collect::<Vec<_>>();
+    ranked.sort_by_key(|(tenant_id, size)| (std::cmp::Reverse(*size), *tenant_id));
+    ranked
+}
 
-    heartbeater: Heartbeater,
+/// Where a shard goes in [`reconcile_order`]: lower sorts first
+fn reconcile_rank(shard: &TenantShard) -> (ReconcilePriority, bool) {
+    (shard.priority, !shard.tenant_shard_id.is_shard_zero())
+}
 
-    // Channel for background cleanup from failed operations that require cleanup, such as shard split
-    abort_tx: tokio::sync::mpsc::UnboundedSender<TenantShardSplitAbort>,
+/// Visit shards of higher priority tenants first, and within each priority all shard zero shards
+/// first: when there are fewer reconciler units than shards needing reconciliation, whichever
+/// shards we visit first get units.  Shard zero serves tenant-wide operations and most timeline
+/// reads, so it should converge before a tenant's other shards.
+fn reconcile_order(
+    tenants: &mut BTreeMap<TenantShardId, TenantShard>,
+) -> impl Iterator<Item = &mut TenantShard> {
+    let mut shards = tenants.values_mut().collect::<Vec<_>>();
+    // Stable sort: within a rank, keep shards in tenant order
+    shards.sort_by_key(|shard| reconcile_rank(shard));
+    shards.into_iter()
+}
 
-    // Locking on a tenant granularity (covers all shards in the tenant):
-    // - Take exclusively for rare operations that mutate the tenant's persistent state (e.g. create/delete/split)
-    // - Take in shared mode for operations that need the set of shards to stay the same to complete reliably (e.g. timeline CRUD)
-    tenant_op_locks: IdLockMap<TenantId, TenantOperations>,
+/// Like [`reconcile_order`], for just the shards of `tenant_ids`
+fn tenant_shards_reconcile_order(
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+    tenant_ids: &[TenantId],
+) -> Vec<TenantShardId> {
+    let mut shard_ids = tenant_ids
+        .iter()
+        .unique()
+        .flat_map(|tenant_id| {
+            tenants
+                .range(TenantShardId::tenant_range(*tenant_id))
+                .map(|(tenant_shard_id, _)| *tenant_shard_id)
+        })
+        .collect::<Vec<_>>();
+    // Stable sort: within a rank, keep shards in tenant order
+    shard_ids.sort_by_key(|tenant_shard_id| reconcile_rank(&tenants[tenant_shard_id]));
+    shard_ids
+}
 
-    // Locking for node-mutating operations: take exclusively for operations that modify the node's persistent state, or
-    // that transition it to/from Active.
-    node_op_locks: IdLockMap<NodeId, NodeOperations>,
+/// Whether this is effectively a single-node (dev/test) deployment, where tenants cannot have
+/// HA locations.  In that case:
+/// - tenants onboarded via `/location_config` are placed without secondaries, so that
+///   scheduling them succeeds.
+/// - when the node goes offline, we do not try to reschedule its shards, since there is
+///   nowhere else for them to go and doing so would only emit scheduling failures.
+fn single_node_deployment(configured: Option<bool>, nodes: &HashMap<NodeId, Node>) -> bool {
+    configured.unwrap_or(nodes.len() <= 1)
+}
 
-    // Limit how many Reconcilers we will spawn concurrently
-    reconciler_concurrency: Arc<tokio::sync::Semaphore>,
+/// Take the next shard that is waiting for reconciler units, preferring shard zero shards and
+/// high priority tenants: see [`Service::delayed_reconcile_priority_tx`].
+fn next_delayed_reconcile(
+    priority_rx: &mut tokio::sync::mpsc::Receiver<TenantShardId>,
+    rx: &mut tokio::sync::mpsc::Receiver<TenantShardId>,
+) -> Option<TenantShardId> {
+    priority_rx.try_recv().or_else(|_| rx.try_recv()).ok()
+}
 
-    /// Queue of tenants who are waiting for concurrency limits to permit them to reconcile
-    /// Send into this queue to promptly attempt to reconcile this shard next time units are available.
-    ///
-    /// Note that this state logically lives inside ServiceInner, but carrying Sender here makes the code simpler
-    /// by avoiding needing a &mut ref to something inside the ServiceInner.  This could be optimized to
-    /// use a VecDeque instead of a channel to reduce synchronization overhead, at the cost of some code complexity.
-    delayed_reconcile_tx: tokio::sync::mpsc::Sender<TenantShardId>,
+/// Whether the children of splitting `parents` could be given the locations their policy requires.
+/// Children start out attached to their parent's pageserver, so what we check is that each parent's
+/// children can get their secondaries on other nodes: all children of one parent have the same
+/// constraints, so one speculative placement per parent suffices.
+fn split_children_schedulable<'a>(
+    scheduler: &Scheduler,
+    parents: impl Iterator<Item = &'a TenantShard>,
+) -> bool {
+    let context = ScheduleContext {
+        mode: ScheduleMode::Speculative,
+        ..Default::default()
+    };
+
+    for parent in parents {
+        let Some(attached) = *parent.intent.get_attached() else {
+            return false;
+        };
+        let secondary_count = match parent.policy {
+            PlacementPolicy::Attached(n) => n,
+            PlacementPolicy::Secondary | PlacementPolicy::Detached => 0,
+        };
 
-    // Process shutdown will fire this token
-    cancel: CancellationToken,
+        let mut used = vec![attached];
+        for _ in 0..secondary_count {
+            match scheduler.schedule_shard(&used, &context) {
+                Ok(node_id) => used.push(node_id),
+                Err(_) => return false,
+            }
+        }
+    }
 
-    // Background tasks will hold this gate
-    gate: Gate,
+    true
+}
 
-    /// This waits for initial reconciliation with pageservers to complete.  Until this barrier
-    /// passes, it isn't safe to do any actions that mutate tenants.
-    pub(crate) startup_complete: Barrier,
+/// A value that is recomputed at most once per `ttl`, for serving frequently polled read APIs
+/// without building a fresh response on every call.
+struct TtlCache<T> {
+    ttl: Duration,
+    cached: std::sync::Mutex<Option<(Instant, T)>>,
 }
 
-impl From<ReconcileWaitError> for ApiError {
-    fn from(value: ReconcileWaitError) -> Self {
-        match value {
-            ReconcileWaitError::Shutdown => ApiError::ShuttingDown,
-            e @ ReconcileWaitError::Timeout(_) => ApiError::Timeout(format!("{e}").into()),
-            e @ ReconcileWaitError::Failed(..) => ApiError::InternalServerError(anyhow::anyhow!(e)),
+impl<T: Clone> TtlCache<T> {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cached: std::sync::Mutex::new(None),
         }
     }
-}
 
-impl From<OperationError> for ApiError {
-    fn from(value: OperationError) -> Self {
-        match value {
-            OperationError::NodeStateChanged(err) | OperationError::FinalizeError(err) => {
-                ApiError::InternalServerError(anyhow::anyhow!(err))
+    fn get_or_refresh(&self, refresh: impl FnOnce() -> T) -> T {
+        // Hold the lock while refreshing, so that concurrent callers wait for one refresh
+        // rather than all doing the same work.
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((refreshed_at, value)) = cached.as_ref() {
+            if refreshed_at.elapsed() < self.ttl {
+                return value.clone();
             }
-            OperationError::Cancelled => ApiError::Conflict("Operation was cancelled".into()),
         }
+
+        let value = refresh();
+        *cached = Some((Instant::now(), value.clone()));
+        value
     }
 }
 
-#[allow(clippy::large_enum_variant)]
-enum TenantCreateOrUpdate {
-    Create(TenantCreateRequest),
-    Update(Vec<ShardUpdate>),
-}
+/// Like [`available_nodes_by_preference`], for operations on many shards: spread the shards evenly
+/// across available nodes, so that the operation may run on several nodes at once.
+fn spread_over_available_nodes(
+    scheduler: &Scheduler,
+    nodes: &HashMap<NodeId, Node>,
+    shard_ids: impl Iterator<Item = TenantShardId>,
+) -> Result<Vec<(Node, Vec<TenantShardId>)>, ApiError> {
+    // Each pick makes the node less attractive for the next one, so nodes are used round-robin
+    let mut context = ScheduleContext {
+        mode: ScheduleMode::Speculative,
+        ..Default::default()
+    };
+    let mut assignments: HashMap<NodeId, Vec<TenantShardId>> = HashMap::new();
+    for tenant_shard_id in shard_ids {
+        let node_id = scheduler.schedule_shard(&[], &context).map_err(|_| {
+            ApiError::ResourceUnavailable(
+                "no schedulable nodes available for remote operation".into(),
+            )
+        })?;
+        context.avoid(&[node_id]);
+        assignments
+            .entry(node_id)
+            .or_default()
+            .push(tenant_shard_id);
+    }
 
-struct ShardSplitParams {
-    old_shard_count: ShardCount,
-    new_shard_count: ShardCount,
-    new_stripe_size: Option<ShardStripeSize>,
-    targets: Vec<ShardSplitTarget>,
-    policy: PlacementPolicy,
-    config: TenantConfig,
-    shard_ident: ShardIdentity,
+    Ok(assignments
+        .into_iter()
+        .map(|(node_id, shard_ids)| {
+            let node = nodes
+                .get(&node_id)
+                .expect("Pageservers may not be deleted while lock is active")
+                .clone();
+            (node, shard_ids)
+        })
+        .collect())
 }
 
-// When preparing for a shard split, we may either choose to proceed with the split,
-// or find that the work is already done and return NoOp.
-enum ShardSplitAction {
-    Split(ShardSplitParams),
-    NoOp(TenantShardSplitResponse),
-}
+/// List the available nodes for operations on a tenant's remote storage, which do not have to run
+/// where the tenant is attached, in the scheduler's order of preference: callers may fall back to
+/// the next node if one turns out to be unreachable.
+fn available_nodes_by_preference(
+    scheduler: &Scheduler,
+    nodes: &HashMap<NodeId, Node>,
+) -> Result<Vec<Node>, ApiError> {
+    let context = ScheduleContext {
+        mode: ScheduleMode::Speculative,
+        ..Default::default()
+    };
+    let mut picked = Vec::new();
+    while let Ok(node_id) = scheduler.schedule_shard(&picked, &context) {
+        picked.push(node_id);
+    }
 
-// A parent shard which will be split
-struct ShardSplitTarget {
-    parent_id: TenantShardId,
-    node: Node,
-    child_ids: Vec<TenantShardId>,
+    if picked.is_empty() {
+        return Err(ApiError::ResourceUnavailable(
+            "no schedulable nodes available for remote operation".into(),
+        ));
+    }
+
+    Ok(picked
+        .into_iter()
+        .map(|node_id| {
+            nodes
+                .get(&node_id)
+                .expect("Pageservers may not be deleted while lock is active")
+                .clone()
+        })
+        .collect())
 }
 
-/// When we tenant shard split operation fails, we may not be able to clean up immediately, because nodes
-/// might not be available.  We therefore use a queue of abort operations processed in the background.
-struct TenantShardSplitAbort {
-    tenant_id: TenantId,
-    /// The target values from the request that failed
-    new_shard_count: ShardCount,
-    new_stripe_size: Option<ShardStripeSize>,
-    /// Until this abort op is complete, no other operations may be done on the tenant
-    _tenant_lock: TracingExclusiveGuard<TenantOperations>,
+/// Whether an error from a pageserver means we could not reach it, rather than that it
+/// handled the request and failed.  [`mgmt_api::Error::Cancelled`] counts as unreachable: the
+/// caller must check for its own cancellation first, so that this means the node went offline.
+fn node_unreachable(e: &mgmt_api::Error) -> bool {
+    matches!(
+        e,
+        mgmt_api::Error::ReceiveBody(_)
+            | mgmt_api::Error::ReceiveErrorBody(_)
+            | mgmt_api::Error::ApiError(
+                StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+                    | StatusCode::REQUEST_TIMEOUT,
+                _
+            )
+            | mgmt_api::Error::Cancelled
+    )
 }
 
-#[derive(thiserror::Error, Debug)]
-enum TenantShardSplitAbortError {
-    #[error(transparent)]
-    Database(#[from] DatabaseError),
-    #[error(transparent)]
-    Remote(#[from] mgmt_api::Error),
-    #[error("Unavailable")]
-    Unavailable,
+/// A writer that refuses to accept more than a fixed number of bytes, so that serialization
+/// of an oversized debug dump is abandoned as soon as it crosses the limit.
+struct LimitedWriter<W> {
+    inner: W,
+    remaining: usize,
+    exceeded: bool,
 }
 
-struct ShardUpdate {
-    tenant_shard_id: TenantShardId,
-    placement_policy: PlacementPolicy,
-    tenant_config: TenantConfig,
+impl<W: std::io::Write> std::io::Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.len() > self.remaining {
+            self.exceeded = true;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "size limit exceeded",
+            ));
+        }
+
+        let n = self.inner.write(buf)?;
+        self.remaining -= n;
+        Ok(n)
+    }
 
-    /// If this is None, generation is not updated.
-    generation: Option<Generation>,
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
 }
 
-impl Service {
-    pub fn get_config(&self) -> &Config {
-        &self.config
+/// The fraction of its heatmap's layer bytes that a secondary location has on disk.  Before it
+/// has downloaded a heatmap, nothing on disk is known to be useful.
+fn secondary_download_ratio(progress: &SecondaryProgress) -> f64 {
+    if progress.heatmap_mtime.is_none() {
+        0.0
+    } else if progress.bytes_total == 0 {
+        1.0
+    } else {
+        progress.bytes_downloaded as f64 / progress.bytes_total as f64
     }
+}
 
-    /// Called once on startup, this function attempts to contact all pageservers to build an up-to-date
-    /// view of the world, and determine which pageservers are responsive.
-    #[instrument(skip_all)]
-    async fn startup_reconcile(
-        self: &Arc<Service>,
-        bg_compute_notify_result_tx: tokio::sync::mpsc::Sender<
-            Result<(), (TenantShardId, NotifyError)>,
-        >,
-    ) {
-        // For all tenant shards, a vector of observed states on nodes (where None means
-        // indeterminate, same as in [`ObservedStateLocation`])
-        let mut observed: HashMap<TenantShardId, Vec<(NodeId, Option<LocationConfig>)>> =
-            HashMap::new();
+/// Combine the download progress of a tenant's secondary locations.  As well as totals, report
+/// the least ready location: a single lagging shard is enough to make a migration slow.
+fn aggregate_secondary_progress(
+    progress: &[(TenantShardId, SecondaryProgress)],
+) -> TenantSecondaryDownloadResponse {
+    let mut aggregate = SecondaryProgress::default();
+    let mut min_heatmap_mtime = None;
+    let mut least_ready: Option<(TenantShardId, f64)> = None;
+    for (tenant_shard_id, progress) in progress {
+        aggregate.layers_downloaded += progress.layers_downloaded;
+        aggregate.layers_total += progress.layers_total;
+        aggregate.bytes_downloaded += progress.bytes_downloaded;
+        aggregate.bytes_total += progress.bytes_total;
+        aggregate.heatmap_mtime = std::cmp::max(
+            aggregate.heatmap_mtime.clone(),
+            progress.heatmap_mtime.clone(),
+        );
 
-        // Startup reconciliation does I/O to other services: whether they
-        // are responsive or not, we should aim to finish within our deadline, because:
-        // - If we don't, a k8s readiness hook watching /ready will kill us.
-        // - While we're waiting for startup reconciliation, we are not fully
-        //   available for end user operations like creating/deleting tenants and timelines.
-        //
-        // We set multiple deadlines to break up the time available between the phases of work: this is
-        // arbitrary, but avoids a situation where the first phase could burn our entire timeout period.
-        let start_at = Instant::now();
-        let node_scan_deadline = start_at
-            .checked_add(STARTUP_RECONCILE_TIMEOUT / 2)
-            .expect("Reconcile timeout is a modest constant");
+        // A location without a heatmap sorts first, making the minimum None
+        min_heatmap_mtime = Some(match min_heatmap_mtime {
+            None => progress.heatmap_mtime.clone(),
+            Some(min) => std::cmp::min(min, progress.heatmap_mtime.clone()),
+        });
 
-        // Accumulate a list of any tenant locations that ought to be detached
-        let mut cleanup = Vec::new();
+        let ratio = secondary_download_ratio(progress);
+        least_ready = match least_ready {
+            Some((least_id, least_ratio))
+                if least_ratio < ratio || least_ratio == ratio && least_id < *tenant_shard_id =>
+            {
+                Some((least_id, least_ratio))
+            }
+            _ => Some((*tenant_shard_id, ratio)),
+        };
+    }
 
-        let node_listings = self.scan_node_locations(node_scan_deadline).await;
-        // Send initial heartbeat requests to nodes that replied to the location listing above.
-        let nodes_online = self.initial_heartbeat_round(node_listings.keys()).await;
+    TenantSecondaryDownloadResponse {
+        progress: aggregate,
+        min_heatmap_mtime: min_heatmap_mtime.flatten(),
+        least_ready_shard: least_ready.map(|(id, _)| id),
+        least_ready_bytes_ratio: least_ready.map(|(_, ratio)| ratio),
+    }
+}
 
-        for (node_id, list_response) in node_listings {
-            let tenant_shards = list_response.tenant_shards;
-            tracing::info!(
-                "Received {} shard statuses from pageserver {}, setting it to Active",
-                tenant_shards.len(),
-                node_id
-            );
+/// Serialize a debug dump as JSON, or None if it would exceed `max_bytes`.  Callers serialize
+/// under the `inner` lock and build the response with [`debug_dump_response`] after releasing it,
+/// so that compression does not hold up writers.
+fn serialize_debug_dump<T: Serialize>(
+    value: &T,
+    max_bytes: usize,
+) -> Result<Option<Vec<u8>>, ApiError> {
+    let mut writer = LimitedWriter {
+        inner: Vec::new(),
+        remaining: max_bytes,
+        exceeded: false,
+    };
+    match serde_json::to_writer(&mut writer, value) {
+        Ok(()) => Ok(Some(writer.inner)),
+        Err(_) if writer.exceeded => Ok(None),
+        Err(e) => Err(ApiError::InternalServerError(e.into())),
+    }
+}
 
-            for (tenant_shard_id, conf_opt) in tenant_shards {
-                let shard_observations = observed.entry(tenant_shard_id).or_default();
-                shard_observations.push((node_id, conf_opt));
-            }
+/// Respond with a debug dump from [`serialize_debug_dump`], optionally gzip-compressed.  If the
+/// uncompressed JSON exceeded `max_bytes`, respond with 413 instead.
+fn debug_dump_response(
+    json: Option<Vec<u8>>,
+    max_bytes: usize,
+    gzip: bool,
+) -> Result<hyper::Response<hyper::Body>, ApiError> {
+    let body = if gzip {
+        json.map(|json| {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            std::io::Write::write_all(&mut encoder, &json)?;
+            encoder.finish()
+        })
+        .transpose()
+        .map_err(|e| ApiError::InternalServerError(e.into()))?
+    } else {
+        json
+    };
+
+    let Some(body) = body else {
+        return hyper::Response::builder()
+            .status(hyper::StatusCode::PAYLOAD_TOO_LARGE)
+            .body(hyper::Body::from(format!(
+                "Dump exceeds {max_bytes} bytes: use a per-tenant or per-shard API instead, or raise --max-debug-dump-bytes"
+            )))
+            .map_err(|e| ApiError::InternalServerError(e.into()));
+    };
+
+    let mut response = hyper::Response::builder()
+        .status(hyper::StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "application/json");
+    if gzip {
+        response = response.header(hyper::header::CONTENT_ENCODING, "gzip");
+    }
+    response
+        .body(hyper::Body::from(body))
+        .map_err(|e| ApiError::InternalServerError(e.into()))
+}
+
+/// Version of the [`StateSnapshot`] format: bump this when making changes that readers of older
+/// snapshots would misinterpret.
+const STATE_SNAPSHOT_VERSION: u32 = 2;
+
+/// All of the controller's in-memory state, captured under a single lock so that it is internally
+/// consistent, for offline analysis.  See [`Service::state_snapshot`].
+#[derive(Serialize)]
+struct StateSnapshot<'a> {
+    version: u32,
+    nodes: Vec<&'a Node>,
+    tenants: Vec<&'a TenantShard>,
+    scheduler: &'a Scheduler,
+    ongoing_operations: Vec<String>,
+}
+
+/// Scan shards for possible scheduling optimizations, returning at most `max_plan` of them.  Canary
+/// tenants are skipped: unlike [`Service::tenant_rebalance`], this is the background pass.
+fn plan_optimizations(
+    nodes: &HashMap<NodeId, Node>,
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+    scheduler: &Scheduler,
+    max_plan: usize,
+) -> Vec<(TenantShardId, ScheduleOptimization)> {
+    let mut schedule_context = ScheduleContext::default();
+
+    let mut tenant_shards: Vec<&TenantShard> = Vec::new();
+
+    let mut work = Vec::new();
+
+    for (tenant_shard_id, shard) in tenants.iter() {
+        if tenant_shard_id.is_shard_zero() {
+            // Reset accumulators on the first shard in a tenant
+            schedule_context = ScheduleContext::default();
+            schedule_context.mode = ScheduleMode::Speculative;
+            tenant_shards.clear();
         }
 
-        // List of tenants for which we will attempt to notify compute of their location at startup
-        let mut compute_notifications = Vec::new();
+        if work.len() >= max_plan {
+            break;
+        }
 
-        // Populate intent and observed states for all tenants, based on reported state on pageservers
-        tracing::info!("Populating tenant shards' states from initial pageserver scan...");
-        let shard_count = {
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
+        match shard.get_scheduling_policy() {
+            ShardSchedulingPolicy::Active => {
+                // Ok to do optimization
+            }
+            ShardSchedulingPolicy::Essential
+            | ShardSchedulingPolicy::Pause
+            | ShardSchedulingPolicy::Stop => {
+                // Policy prevents optimizing this shard.
+                continue;
+            }
+        }
 
-            // Mark nodes online if they responded to us: nodes are offline by default after a restart.
-            let mut new_nodes = (**nodes).clone();
-            for (node_id, node) in new_nodes.iter_mut() {
-                if let Some(utilization) = nodes_online.get(node_id) {
-                    node.set_availability(NodeAvailability::Active(UtilizationScore(
-                        utilization.utilization_score,
-                    )));
-                    scheduler.node_upsert(node);
-                }
+        // Accumulate the schedule context for all the shards in a tenant: we must have
+        // the total view of all shards before we can try to optimize any of them.
+        schedule_context.avoid(&shard.intent.all_pageservers());
+        if let Some(attached) = shard.intent.get_attached() {
+            schedule_context.push_attached(*attached);
+        }
+        tenant_shards.push(shard);
+
+        // Once we have seen the last shard in the tenant, proceed to search across all shards
+        // in the tenant for optimizations
+        if shard.shard.number.0 == shard.shard.count.count() - 1 {
+            if tenant_shards.iter().any(|s| s.reconciler.is_some()) {
+                // Do not start any optimizations while another change to the tenant is ongoing: this
+                // is not necessary for correctness, but simplifies operations and implicitly throttles
+                // optimization changes to happen in a "trickle" over time.
+                continue;
             }
-            *nodes = Arc::new(new_nodes);
 
-            for (tenant_shard_id, shard_observations) in observed {
-                for (node_id, observed_loc) in shard_observations {
-                    let Some(tenant_shard) = tenants.get_mut(&tenant_shard_id) else {
-                        cleanup.push((tenant_shard_id, node_id));
-                        continue;
-                    };
-                    tenant_shard
-                        .observed
-                        .locations
-                        .insert(node_id, ObservedStateLocation { conf: observed_loc });
-                }
+            if tenant_shards.iter().any(|s| {
+                !matches!(s.splitting, SplitState::Idle)
+                    || matches!(s.policy, PlacementPolicy::Detached)
+            }) {
+                // Never attempt to optimize a tenant that is currently being split, or
+                // a tenant that is meant to be detached
+                continue;
             }
 
-            // Populate each tenant's intent state
-            let mut schedule_context = ScheduleContext::default();
-            for (tenant_shard_id, tenant_shard) in tenants.iter_mut() {
-                if tenant_shard_id.shard_number == ShardNumber(0) {
-                    // Reset scheduling context each time we advance to the next Tenant
-                    schedule_context = ScheduleContext::default();
-                }
+            if tenant_shards.iter().any(|s| s.canary) {
+                // Canary tenants only move when an operator asks for it
+                continue;
+            }
 
-                tenant_shard.intent_from_observed(scheduler);
-                if let Err(e) = tenant_shard.schedule(scheduler, &mut schedule_context) {
-                    // Non-fatal error: we are unable to properly schedule the tenant, perhaps because
-                    // not enough pageservers are available.  The tenant may well still be available
-                    // to clients.
-                    tracing::error!("Failed to schedule tenant {tenant_shard_id} at startup: {e}");
-                } else {
-                    // If we're both intending and observed to be attached at a particular node, we will
-                    // emit a compute notification for this. In the case where our observed state does not
-                    // yet match our intent, we will eventually reconcile, and that will emit a compute notification.
-                    if let Some(attached_at) = tenant_shard.stably_attached() {
-                        compute_notifications.push((
-                            *tenant_shard_id,
-                            attached_at,
-                            tenant_shard.shard.stripe_size,
-                        ));
-                    }
+            // TODO: optimization calculations are relatively expensive: create some fast-path for
+            // the common idle case (avoiding the search on tenants that we have recently checked)
+
+            for shard in &tenant_shards {
+                if let Some(optimization) =
+                    // If idle, maybe ptimize attachments: if a shard has a secondary location that is preferable to
+                    // its primary location based on soft constraints, cut it over.
+                    shard.optimize_attachment(nodes, &schedule_context)
+                {
+                    work.push((shard.tenant_shard_id, optimization));
+                    break;
+                } else if let Some(optimization) =
+                    // If idle, maybe optimize secondary locations: if a shard has a secondary location that would be
+                    // better placed on another node, based on ScheduleContext, then adjust it.  This
+                    // covers cases like after a shard split, where we might have too many shards
+                    // in the same tenant with secondary locations on the node where they originally split.
+                    shard.optimize_secondary(scheduler, &schedule_context)
+                {
+                    work.push((shard.tenant_shard_id, optimization));
+                    break;
                 }
+
+                // TODO: extend this mechanism to prefer attaching on nodes with fewer attached
+                // tenants (i.e. extend schedule state to distinguish attached from secondary counts),
+                // for the total number of attachments on a node (not just within a tenant.)
             }
+        }
+    }
 
-            tenants.len()
+    work
+}
+
+/// Apply validated optimizations to shards' intent, stopping once `max_apply` of them have been applied.
+/// Returns the shards whose intent changed: the caller is responsible for reconciling them.
+fn apply_optimizations(
+    tenants: &mut BTreeMap<TenantShardId, TenantShard>,
+    scheduler: &mut Scheduler,
+    work: Vec<(TenantShardId, ScheduleOptimization)>,
+    max_apply: usize,
+) -> Vec<TenantShardId> {
+    let mut applied = Vec::new();
+    for (tenant_shard_id, optimization) in work {
+        if applied.len() >= max_apply {
+            break;
+        }
+
+        let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+            // Shard was dropped between planning and execution;
+            continue;
         };
+        if shard.apply_optimization(scheduler, optimization) {
+            applied.push(tenant_shard_id);
+        }
+    }
 
-        // TODO: if any tenant's intent now differs from its loaded generation_pageserver, we should clear that
-        // generation_pageserver in the database.
+    applied
+}
 
-        // Emit compute hook notifications for all tenants which are already stably attached.  Other tenants
-        // will emit compute hook notifications when they reconcile.
-        //
-        // Ordering: our calls to notify_background synchronously establish a relative order for these notifications vs. any later
-        // calls into the ComputeHook for the same tenant: we can leave these to run to completion in the background and any later
-        // calls will be correctly ordered wrt these.
-        //
-        // Concurrency: we call notify_background for all tenants, which will create O(N) tokio tasks, but almost all of them
-        // will just wait on the ComputeHook::API_CONCURRENCY semaphore immediately, so very cheap until they get that semaphore
-        // unit and start doing I/O.
-        tracing::info!(
-            "Sending {} compute notifications",
-            compute_notifications.len()
-        );
-        self.compute_hook.notify_background(
-            compute_notifications,
-            bg_compute_notify_result_tx.clone(),
-            &self.cancel,
-        );
+/// Choose the mode for a location that is receiving a new generation in a re-attach response.
+///
+/// Locations are only put into multi or stale modes in the middle of a [`crate::reconciler::Reconciler::live_migrate`].
+/// If a pageserver restarts during that process, the reconcile fails and the next one starts from
+/// whatever state the locations are left in.  By default we respond with AttachedSingle, which
+/// discards the intermediate state.
+///
+/// With `preserve_multi`, a restarting migration destination stays in AttachedMulti if all of
+/// these are true:
+///  - the node is the shard's intended attached location,
+///  - we last configured it in AttachedMulti mode, and
+///  - some other location was last configured in an attached mode (i.e. the migration origin
+///    may still be serving reads).
+///
+/// This is safe because:
+///  - In AttachedMulti mode, a pageserver does not execute deletions.  That is more conservative
+///    than AttachedSingle, so the destination cannot delete layers that the origin still uses.
+///  - Our observed state for the destination stays AttachedMulti, which differs from the intended
+///    AttachedSingle.  So the shard remains dirty, and a later reconcile will switch the
+///    destination to AttachedSingle.  That switch keeps the same generation.
+///
+/// AttachedStale is never preserved.  Only the origin is put into that mode, and the origin
+/// only gets a new generation here if the migration had not yet attached the destination.
+/// Making it stale in its new generation would leave no location for the next live migration
+/// to use as its origin, so the destination would be attached without being warmed up.
+fn reattach_mode(shard: &TenantShard, node_id: NodeId, preserve_multi: bool) -> LocationConfigMode {
+    use LocationConfigMode::*;
+
+    let observed_mode = |conf: &ObservedStateLocation| conf.conf.as_ref().map(|c| c.mode);
+
+    let is_multi_destination = shard.intent.get_attached() == &Some(node_id)
+        && shard
+            .observed
+            .locations
+            .get(&node_id)
+            .and_then(observed_mode)
+            == Some(AttachedMulti);
+    let other_attached = shard.observed.locations.iter().any(|(other_id, loc)| {
+        *other_id != node_id
+            && matches!(
+                observed_mode(loc),
+                Some(AttachedSingle | AttachedStale | AttachedMulti)
+            )
+    });
 
-        // Finally, now that the service is up and running, launch reconcile operations for any tenants
-        // which require it: under normal circumstances this should only include tenants that were in some
-        // transient state before we restarted, or any tenants whose compute hooks failed above.
-        tracing::info!("Checking for shards in need of reconciliation...");
-        let reconcile_tasks = self.reconcile_all();
-        // We will not wait for these reconciliation tasks to run here: we're now done with startup and
-        // normal operations may proceed.
+    if preserve_multi && is_multi_destination && other_attached {
+        AttachedMulti
+    } else {
+        AttachedSingle
+    }
+}
 
-        // Clean up any tenants that were found on pageservers but are not known to us.  Do this in the
-        // background because it does not need to complete in order to proceed with other work.
-        if !cleanup.is_empty() {
-            tracing::info!("Cleaning up {} locations in the background", cleanup.len());
-            tokio::task::spawn({
-                let cleanup_self = self.clone();
-                async move { cleanup_self.cleanup_locations(cleanup).await }
-            });
+/// Refuse to create `tenant_id` if that would take us over `max_tenants`.  Tenants that already
+/// exist may always be created again, as tenant creation is idempotent.
+fn check_tenant_limit(
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+    tenant_id: TenantId,
+    max_tenants: Option<usize>,
+) -> Result<(), ApiError> {
+    let Some(max_tenants) = max_tenants else {
+        return Ok(());
+    };
+
+    if tenants
+        .range(TenantShardId::tenant_range(tenant_id))
+        .next()
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    // During a split the parent and child shards are both present, so count distinct tenant
+    // IDs rather than shard zeros.
+    let tenant_count = tenants.keys().map(|id| id.tenant_id).dedup().count();
+    if tenant_count >= max_tenants {
+        return Err(ApiError::ResourceUnavailable(
+            format!("Tenant limit reached ({tenant_count}/{max_tenants})").into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Refuse placement policies with more than `max_secondaries_per_shard` secondary locations.
+fn check_secondaries_limit(
+    policy: &PlacementPolicy,
+    max_secondaries_per_shard: Option<usize>,
+) -> Result<(), ApiError> {
+    let secondaries = match policy {
+        PlacementPolicy::Attached(n) => *n,
+        PlacementPolicy::Secondary => 1,
+        PlacementPolicy::Detached => 0,
+    };
+    match max_secondaries_per_shard {
+        Some(max) if secondaries > max => Err(ApiError::BadRequest(anyhow::anyhow!(
+            "Placement policy {policy:?} has {secondaries} secondary locations per shard: the \
+             maximum is {max}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Refuse tenants with more than `max_shards_per_tenant` shards.
+fn check_shards_limit(
+    shard_count: ShardCount,
+    max_shards_per_tenant: Option<usize>,
+) -> Result<(), ApiError> {
+    match max_shards_per_tenant {
+        Some(max) if shard_count.count() as usize > max => {
+            Err(ApiError::BadRequest(anyhow::anyhow!(
+                "{} shards requested: the maximum is {max}",
+                shard_count.count()
+            )))
         }
+        _ => Ok(()),
+    }
+}
 
-        tracing::info!("Startup complete, spawned {reconcile_tasks} reconciliation tasks ({shard_count} shards total)");
+/// Count tenants by the stripe size of their shard zero.  During a split the parent and child shard
+/// zeros are both present, in which case we use whichever sorts first (the parent).
+fn stripe_sizes_in_use(
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+) -> HashMap<ShardStripeSize, usize> {
+    let mut result = HashMap::new();
+    for (_tenant_id, mut tenant_shards) in &tenants.iter().group_by(|(id, _shard)| id.tenant_id) {
+        let (_id, shard0) = tenant_shards.next().expect("Groups are always non-empty");
+        *result.entry(shard0.shard.stripe_size).or_default() += 1;
     }
+    result
+}
 
-    async fn initial_heartbeat_round<'a>(
-        &self,
-        node_ids: impl Iterator<Item = &'a NodeId>,
-    ) -> HashMap<NodeId, PageserverUtilization> {
-        assert!(!self.startup_complete.is_ready());
+/// Validate time travel timestamps before doing any work, rather than having every shard's request
+/// fail on the pageserver.  These are the same checks that the pageserver applies.
+fn validate_time_travel_timestamps(timestamp: &str, done_if_after: &str) -> Result<(), ApiError> {
+    let timestamp = humantime::parse_rfc3339(timestamp)
+        .with_context(|| format!("Invalid time for travel_to: {timestamp:?}"))
+        .map_err(ApiError::BadRequest)?;
+    let done_if_after = humantime::parse_rfc3339(done_if_after)
+        .with_context(|| format!("Invalid time for done_if_after: {done_if_after:?}"))
+        .map_err(ApiError::BadRequest)?;
+
+    if timestamp > done_if_after {
+        return Err(ApiError::BadRequest(anyhow::anyhow!(
+            "The done_if_after timestamp comes before the timestamp to recover to"
+        )));
+    }
 
-        let all_nodes = {
-            let locked = self.inner.read().unwrap();
-            locked.nodes.clone()
-        };
+    Ok(())
+}
 
-        let mut nodes_to_heartbeat = HashMap::new();
-        for node_id in node_ids {
-            match all_nodes.get(node_id) {
-                Some(node) => {
-                    nodes_to_heartbeat.insert(*node_id, node.clone());
-                }
-                None => {
-                    tracing::warn!("Node {node_id} was removed during start-up");
-                }
+/// Check that a fill of `node_id` may continue: promoting secondaries onto a node that was removed,
+/// taken out of the Filling policy, or became unavailable is futile.
+fn check_fill_target(nodes: &HashMap<NodeId, Node>, node_id: NodeId) -> Result<(), OperationError> {
+    let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
+        format!("node {node_id} was removed").into(),
+    ))?;
+
+    let current_policy = node.get_scheduling();
+    if !matches!(current_policy, NodeSchedulingPolicy::Filling) {
+        // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
+        // about it
+        return Err(OperationError::NodeStateChanged(
+            format!("node {node_id} changed state to {current_policy:?}").into(),
+        ));
+    }
+
+    if !node.is_available() {
+        return Err(OperationError::NodeStateChanged(
+            format!("node {node_id} became unavailable").into(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The reconciler concurrency semaphore starts out with enough units for the startup concurrency:
+/// once startup is complete, permanently remove the excess units to drop back to the steady-state
+/// concurrency.  This waits for any reconcilers holding excess units to complete.
+async fn retire_startup_reconcile_units(
+    semaphore: &tokio::sync::Semaphore,
+    startup_concurrency: usize,
+    steady_concurrency: usize,
+    cancel: &CancellationToken,
+) {
+    let excess = startup_concurrency.saturating_sub(steady_concurrency);
+    if excess == 0 {
+        return;
+    }
+
+    tokio::select! {
+        units = semaphore.acquire_many(excess as u32) => {
+            if let Ok(units) = units {
+                units.forget();
+                tracing::info!("Reduced reconciler concurrency from {startup_concurrency} to {steady_concurrency}");
             }
         }
+        _ = cancel.cancelled() => {}
+    }
+}
 
-        tracing::info!("Sending initial heartbeats...");
-        let res = self
-            .heartbeater
-            .heartbeat(Arc::new(nodes_to_heartbeat))
-            .await;
-
-        let mut online_nodes = HashMap::new();
-        if let Ok(deltas) = res {
-            for (node_id, status) in deltas.0 {
-                match status {
-                    PageserverState::Available { utilization, .. } => {
-                        online_nodes.insert(node_id, utilization);
-                    }
-                    PageserverState::Offline => {}
+/// How often to log progress while waiting for a batch of reconciles
+const WAIT_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wait for each of `waiters` in turn until `deadline`, periodically logging how many have
+/// completed so that operators can see a long wait making progress.  Unless `ignore_errors` is
+/// set, stop at the first error.
+async fn wait_with_progress(
+    waiters: &[ReconcilerWaiter],
+    deadline: Instant,
+    ignore_errors: bool,
+) -> Result<(), ReconcileWaitError> {
+    let mut progress = tokio::time::interval_at(
+        tokio::time::Instant::now() + WAIT_PROGRESS_INTERVAL,
+        WAIT_PROGRESS_INTERVAL,
+    );
+
+    for waiter in waiters {
+        let wait = waiter.wait_timeout(deadline.duration_since(Instant::now()));
+        tokio::pin!(wait);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut wait => break result,
+                _ = progress.tick() => {
+                    let complete = waiters
+                        .iter()
+                        .filter(|w| !matches!(w.get_status(), ReconcilerStatus::InProgress))
+                        .count();
+                    tracing::info!(
+                        "Waiting for reconciles: {complete}/{} complete",
+                        waiters.len()
+                    );
                 }
             }
+        };
+
+        if !ignore_errors {
+            result?;
         }
+    }
 
-        online_nodes
+    Ok(())
+}
+
+/// Check that every shard with a location on `node_id` has some other schedulable node to move
+/// that location to, so that [`Service::node_delete`] can refuse before it changes anything.
+fn check_node_evacuable<'a>(
+    node_id: NodeId,
+    nodes: &HashMap<NodeId, Node>,
+    shards: impl Iterator<Item = (&'a TenantShardId, &'a TenantShard)>,
+) -> Result<(), ApiError> {
+    let candidates = nodes
+        .values()
+        .filter(|n| n.get_id() != node_id && matches!(n.may_schedule(), MaySchedule::Yes(_)))
+        .map(|n| n.get_id())
+        .collect::<Vec<_>>();
+
+    let stuck = shards
+        .filter(|(_, shard)| shard.intent.references(node_id))
+        .filter(|(_, shard)| {
+            let existing = shard.intent.all_pageservers();
+            !candidates.iter().any(|n| !existing.contains(n))
+        })
+        .map(|(tenant_shard_id, _)| *tenant_shard_id)
+        .collect::<Vec<_>>();
+
+    match stuck.first() {
+        None => Ok(()),
+        Some(example) => Err(ApiError::PreconditionFailed(
+            format!(
+                "Cannot delete node {node_id}: {} shards have nowhere to move to, e.g. {example}",
+                stuck.len()
+            )
+            .into(),
+        )),
     }
+}
 
-    /// Used during [`Self::startup_reconcile`]: issue GETs to all nodes concurrently, with a deadline.
-    ///
-    /// The result includes only nodes which responded within the deadline
-    async fn scan_node_locations(
-        &self,
-        deadline: Instant,
-    ) -> HashMap<NodeId, LocationConfigListResponse> {
-        let nodes = {
-            let locked = self.inner.read().unwrap();
-            locked.nodes.clone()
+/// Check an explicit placement for [`Service::tenant_set_placement`] against the tenant's shards
+/// and the nodes we know about, so that a bad spec is rejected before anything is changed.
+fn validate_placement(
+    tenant_id: TenantId,
+    placement: &[TenantShardPlacement],
+    tenants: &BTreeMap<TenantShardId, TenantShard>,
+    nodes: &HashMap<NodeId, Node>,
+) -> Result<(), ApiError> {
+    let mut seen = HashSet::new();
+    for shard_placement in placement {
+        let tenant_shard_id = shard_placement.tenant_shard_id;
+        if tenant_shard_id.tenant_id != tenant_id {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Shard {tenant_shard_id} does not belong to tenant {tenant_id}"
+            )));
+        }
+        if !seen.insert(tenant_shard_id) {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Shard {tenant_shard_id} is listed more than once"
+            )));
+        }
+        let Some(shard) = tenants.get(&tenant_shard_id) else {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Shard {tenant_shard_id} not found"
+            )));
         };
 
-        let mut node_results = HashMap::new();
-
-        let mut node_list_futs = FuturesUnordered::new();
+        let locations = shard_placement
+            .attached
+            .iter()
+            .chain(shard_placement.secondaries.iter())
+            .collect::<Vec<_>>();
+        if let Some(node_id) = locations.iter().find(|n| !nodes.contains_key(**n)) {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Node {node_id} not found"
+            )));
+        }
+        if locations.iter().collect::<HashSet<_>>().len() != locations.len() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Shard {tenant_shard_id} has more than one location on the same node"
+            )));
+        }
 
-        tracing::info!("Scanning shards on {} nodes...", nodes.len());
-        for node in nodes.values() {
-            node_list_futs.push({
-                async move {
-                    tracing::info!("Scanning shards on node {node}...");
-                    let timeout = Duration::from_secs(1);
-                    let response = node
-                        .with_client_retries(
-                            |client| async move { client.list_location_config().await },
-                            &self.config.jwt_token,
-                            1,
-                            5,
-                            timeout,
-                            &self.cancel,
-                        )
-                        .await;
-                    (node.get_id(), response)
-                }
-            });
+        let attached = shard_placement.attached.is_some();
+        let secondaries = shard_placement.secondaries.len();
+        let valid = match shard.policy {
+            PlacementPolicy::Attached(n) => attached && secondaries == n,
+            PlacementPolicy::Secondary => !attached && secondaries == 1,
+            PlacementPolicy::Detached => false,
+        };
+        if !valid {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Placement of shard {tenant_shard_id} does not match its policy {:?}",
+                shard.policy
+            )));
         }
+    }
 
-        loop {
-            let (node_id, result) = tokio::select! {
-                next = node_list_futs.next() => {
-                    match next {
-                        Some(result) => result,
-                        None =>{
-                            // We got results for all our nodes
-                            break;
-                        }
+    Ok(())
+}
 
-                    }
-                },
-                _ = tokio::time::sleep(deadline.duration_since(Instant::now())) => {
-                    // Give up waiting for anyone who hasn't responded: we will yield the results that we have
-                    tracing::info!("Reached deadline while waiting for nodes to respond to location listing requests");
-                    break;
-                }
-            };
+// Top level state available to all HTTP handlers
+struct ServiceState {
+    tenants: BTreeMap<TenantShardId, TenantShard>,
 
-            let Some(list_response) = result else {
-                tracing::info!("Shutdown during startup_reconcile");
-                break;
-            };
+    nodes: Arc<HashMap<NodeId, Node>>,
 
-            match list_response {
-                Err(e) => {
-                    tracing::warn!("Could not scan node {} ({e})", node_id);
-                }
-                Ok(listing) => {
-                    node_results.insert(node_id, listing);
-                }
-            }
-        }
+    scheduler: Scheduler,
 
-        node_results
-    }
+    /// Ongoing background operations on the cluster, by the node they operate on.
+    /// Several nodes may be drained or filled at once, but each node has at most one
+    /// such operation at any given time, hence the type choice.
+    ongoing_operations: HashMap<NodeId, OperationHandler>,
 
-    /// Used during [`Self::startup_reconcile`]: detach a list of unknown-to-us tenants from pageservers.
-    ///
-    /// This is safe to run in the background, because if we don't have this TenantShardId in our map of
-    /// tenants, then it is probably something incompletely deleted before: we will not fight with any
-    /// other task trying to attach it.
-    #[instrument(skip_all)]
-    async fn cleanup_locations(&self, cleanup: Vec<(TenantShardId, NodeId)>) {
-        let nodes = self.inner.read().unwrap().nodes.clone();
+    /// Queue of tenants who are waiting for concurrency limits to permit them to reconcile
+    delayed_reconcile_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
 
-        for (tenant_shard_id, node_id) in cleanup {
-            // A node reported a tenant_shard_id which is unknown to us: detach it.
-            let Some(node) = nodes.get(&node_id) else {
-                // This is legitimate; we run in the background and [`Self::startup_reconcile`] might have identified
-                // a location to clean up on a node that has since been removed.
-                tracing::info!(
-                    "Not cleaning up location {node_id}/{tenant_shard_id}: node not found"
-                );
-                continue;
-            };
+    /// Like `delayed_reconcile_rx`, for shard zero shards: this queue is drained first.
+    delayed_reconcile_priority_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
 
-            if self.cancel.is_cancelled() {
-                break;
-            }
+    /// Moving average of how long reconciles take, for estimating how long queued reconciles will wait
+    reconcile_duration_avg: Option<Duration>,
 
-            let client = PageserverClient::new(
-                node.get_id(),
-                node.base_url(),
-                self.config.jwt_token.as_deref(),
-            );
-            match client
-                .location_config(
-                    tenant_shard_id,
-                    LocationConfig {
-                        mode: LocationConfigMode::Detached,
-                        generation: None,
-                        secondary_conf: None,
-                        shard_number: tenant_shard_id.shard_number.0,
-                        shard_count: tenant_shard_id.shard_count.literal(),
-                        shard_stripe_size: 0,
-                        tenant_conf: models::TenantConfig::default(),
-                    },
-                    None,
-                    false,
-                )
-                .await
+    /// Recently completed reconciles, for [`Service::reconcile_throughput`]
+    reconcile_throughput: ReconcileThroughput,
+
+    /// Last known size of tenants, as reported by pageservers during [`Service::autosplit_candidates`]
+    /// and measured by [`Config::split_sorting`].  Only tenants that were candidates for splitting are included.
+    tenant_sizes: HashMap<TenantId, u64>,
+}
+
+/// The outcome of comparing objects' state in memory and in the database, for
+/// [`Service::consistency_check_relaxed`]
+struct QuiescentComparison<K, V> {
+    checked: usize,
+    skipped: Vec<K>,
+    /// Objects whose state differs, with their state in memory and in the database
+    mismatched: Vec<(K, V, V)>,
+}
+
+/// Compare objects' state in the database with two snapshots of their state in memory, taken before
+/// and after reading the database, where `None` means an object was busy at the time.  An object is
+/// only compared if it was in both snapshots, not busy and unchanged in between, and present in the
+/// database: otherwise memory and database may legitimately disagree, so the object is skipped.
+fn compare_quiescent<K: Ord + Copy, V: PartialEq>(
+    before: BTreeMap<K, Option<V>>,
+    mut after: BTreeMap<K, Option<V>>,
+    mut database: BTreeMap<K, V>,
+) -> QuiescentComparison<K, V> {
+    let mut result = QuiescentComparison {
+        checked: 0,
+        skipped: Vec::new(),
+        mismatched: Vec::new(),
+    };
+
+    for (key, before_state) in before {
+        match (before_state, after.remove(&key), database.remove(&key)) {
+            (Some(before_state), Some(Some(after_state)), Some(db_state))
+                if before_state == after_state =>
             {
-                Ok(()) => {
-                    tracing::info!(
-                        "Detached unknown shard {tenant_shard_id} on pageserver {node_id}"
-                    );
-                }
-                Err(e) => {
-                    // Non-fatal error: leaving a tenant shard behind that we are not managing shouldn't
-                    // break anything.
-                    tracing::error!(
-                        "Failed to detach unknkown shard {tenant_shard_id} on pageserver {node_id}: {e}"
-                    );
+                result.checked += 1;
+                if after_state != db_state {
+                    result.mismatched.push((key, after_state, db_state));
                 }
             }
+            _ => result.skipped.push(key),
         }
     }
 
-    /// Long running background task that periodically wakes up and looks for shards that need
-    /// reconciliation.  Reconciliation is fallible, so any reconciliation tasks that fail during
-    /// e.g. a tenant create/attach/migrate must eventually be retried: this task is responsible
-    /// for those retries.
-    #[instrument(skip_all)]
-    async fn background_reconcile(self: &Arc<Self>) {
-        self.startup_complete.clone().wait().await;
+    // Whatever is left appeared in memory after the first snapshot, or is only in the database
+    result.skipped.extend(after.into_keys());
+    result.skipped.extend(database.into_keys());
+    result.skipped.sort();
+    result.skipped.dedup();
 
-        const BACKGROUND_RECONCILE_PERIOD: Duration = Duration::from_secs(20);
+    result
+}
 
-        let mut interval = tokio::time::interval(BACKGROUND_RECONCILE_PERIOD);
-        while !self.cancel.is_cancelled() {
-            tokio::select! {
-              _ = interval.tick() => {
-                let reconciles_spawned = self.reconcile_all();
-                if reconciles_spawned == 0 {
-                    // Run optimizer only when we didn't find any other work to do
-                    let optimizations = self.optimize_all().await;
-                    if optimizations == 0 {
-                        // Run new splits only when no optimizations are pending
-                        self.autosplit_tenants().await;
-                    }
-                }
-            }
-              _ = self.cancel.cancelled() => return
-            }
+/// Transform an error from a pageserver into an error to return to callers of a storage
+/// controller API.
+fn passthrough_api_error(node: &Node, e: mgmt_api::Error) -> ApiError {
+    let (code, err) = match e {
+        mgmt_api::Error::ReceiveErrorBody(str) => {
+            // Presume errors receiving body are connectivity/availability issues
+            (
+                PassthroughErrorCode::NodeUnavailable,
+                ApiError::ResourceUnavailable(
+                    format!("{node} error receiving error body: {str}").into(),
+                ),
+            )
+        }
+        mgmt_api::Error::ReceiveBody(str) => {
+            // Presume errors receiving body are connectivity/availability issues
+            (
+                PassthroughErrorCode::NodeUnavailable,
+                ApiError::ResourceUnavailable(format!("{node} error receiving body: {str}").into()),
+            )
+        }
+        mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, msg) => (
+            PassthroughErrorCode::NotFound,
+            ApiError::NotFound(anyhow::anyhow!(format!("{node}: {msg}")).into()),
+        ),
+        mgmt_api::Error::ApiError(StatusCode::SERVICE_UNAVAILABLE, msg) => (
+            PassthroughErrorCode::NodeUnavailable,
+            ApiError::ResourceUnavailable(format!("{node}: {msg}").into()),
+        ),
+        mgmt_api::Error::ApiError(status @ StatusCode::UNAUTHORIZED, msg)
+        | mgmt_api::Error::ApiError(status @ StatusCode::FORBIDDEN, msg) => {
+            // Auth errors talking to a pageserver are not auth errors for the caller: they are
+            // internal server errors, showing that something is wrong with the pageserver or
+            // storage controller's auth configuration.
+            (
+                PassthroughErrorCode::NodeUnauthorized,
+                ApiError::InternalServerError(anyhow::anyhow!("{node} {status}: {msg}")),
+            )
+        }
+        mgmt_api::Error::ApiError(status, msg) => {
+            // Presume general case of pageserver API errors is that we tried to do something
+            // that can't be done right now.
+            (
+                PassthroughErrorCode::NodeRejected,
+                ApiError::Conflict(format!("{node} {status}: {status} {msg}")),
+            )
+        }
+        mgmt_api::Error::Cancelled => (PassthroughErrorCode::ShuttingDown, ApiError::ShuttingDown),
+    };
+
+    // The code lets clients tell apart errors with the same status, without parsing messages
+    ApiError::WithCode(code.into(), Box::new(err))
+}
+
+impl ServiceState {
+    fn new(
+        nodes: HashMap<NodeId, Node>,
+        tenants: BTreeMap<TenantShardId, TenantShard>,
+        scheduler: Scheduler,
+        delayed_reconcile_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
+        delayed_reconcile_priority_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
+    ) -> Self {
+        Self {
+            tenants,
+            nodes: Arc::new(nodes),
+            scheduler,
+            ongoing_operations: HashMap::new(),
+            delayed_reconcile_rx,
+            delayed_reconcile_priority_rx,
+            reconcile_duration_avg: None,
+            reconcile_throughput: ReconcileThroughput::new(Instant::now()),
+            tenant_sizes: HashMap::new(),
         }
     }
-    #[instrument(skip_all)]
-    async fn spawn_heartbeat_driver(&self) {
-        self.startup_complete.clone().wait().await;
 
-        const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+    fn record_reconcile_duration(&mut self, duration: Duration) {
+        self.reconcile_duration_avg = Some(match self.reconcile_duration_avg {
+            None => duration,
+            // Exponentially weighted: each new sample contributes 1/8th
+            Some(avg) => (avg * 7 + duration) / 8,
+        });
+    }
 
-        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
-        while !self.cancel.is_cancelled() {
-            tokio::select! {
-              _ = interval.tick() => { }
-              _ = self.cancel.cancelled() => return
-            };
+    fn parts_mut(
+        &mut self,
+    ) -> (
+        &mut Arc<HashMap<NodeId, Node>>,
+        &mut BTreeMap<TenantShardId, TenantShard>,
+        &mut Scheduler,
+    ) {
+        (&mut self.nodes, &mut self.tenants, &mut self.scheduler)
+    }
+}
 
-            let nodes = {
-                let locked = self.inner.read().unwrap();
-                locked.nodes.clone()
-            };
+#[derive(Clone)]
+pub struct Config {
+    // All pageservers managed by one instance of this service must have
+    // the same public key.  This JWT token will be used to authenticate
+    // this service to the pageservers it manages.
+    pub jwt_token: Option<String>,
 
-            let res = self.heartbeater.heartbeat(nodes).await;
-            if let Ok(deltas) = res {
-                for (node_id, state) in deltas.0 {
-                    let (new_node, new_availability) = match state {
-                        PageserverState::Available {
-                            utilization, new, ..
-                        } => (
-                            new,
-                            NodeAvailability::Active(UtilizationScore(
-                                utilization.utilization_score,
-                            )),
-                        ),
-                        PageserverState::Offline => (false, NodeAvailability::Offline),
-                    };
+    // This JWT token will be used to authenticate this service to the control plane.
+    pub control_plane_jwt_token: Option<String>,
 
-                    if new_node {
-                        // When the heartbeats detect a newly added node, we don't wish
-                        // to attempt to reconcile the shards assigned to it. The node
-                        // is likely handling it's re-attach response, so reconciling now
-                        // would be counterproductive.
-                        //
-                        // Instead, update the in-memory state with the details learned about the
-                        // node.
-                        let mut locked = self.inner.write().unwrap();
-                        let (nodes, _tenants, scheduler) = locked.parts_mut();
+    /// Where the compute hook should send notifications of pageserver attachment locations
+    /// (this URL points to the control plane in prod). If this is None, the compute hook will
+    /// assume it is running in a test environment and try to update neon_local.
+    pub compute_hook_url: Option<String>,
 
-                        let mut new_nodes = (**nodes).clone();
+    /// If more than one, send compute notifications for up to this many tenants in one request
+    /// to [`Self::compute_hook_url`], which must then accept the batched request format.
+    pub compute_hook_batch_size: usize,
 
-                        if let Some(node) = new_nodes.get_mut(&node_id) {
-                            node.set_availability(new_availability);
-                            scheduler.node_upsert(node);
-                        }
+    /// How long a compute notification may wait for others to join its batch before it is sent
+    pub compute_hook_batch_interval: Duration,
 
-                        locked.nodes = Arc::new(new_nodes);
-                    } else {
-                        // This is the code path for geniune availability transitions (i.e node
-                        // goes unavailable and/or comes back online).
-                        let res = self
-                            .node_configure(node_id, Some(new_availability), None)
-                            .await;
+    /// Grace period within which a pageserver does not respond to heartbeats, but is still
+    /// considered active. Once the grace period elapses, the next heartbeat failure will
+    /// mark the pagseserver offline.
+    pub max_unavailable_interval: Duration,
 
-                        match res {
-                            Ok(()) => {}
-                            Err(ApiError::NotFound(_)) => {
-                                // This should be rare, but legitimate since the heartbeats are done
-                                // on a snapshot of the nodes.
-                                tracing::info!(
-                                    "Node {} was not found after heartbeat round",
-                                    node_id
-                                );
-                            }
-                            Err(err) => {
-                                tracing::error!(
-                                    "Failed to update node {} after heartbeat round: {}",
-                                    node_id,
-                                    err
-                                );
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+    /// How long a node must stay available in Draining or Filling, without the background
+    /// operation that set this policy, before it is reset to Active.  This recovers nodes whose
+    /// drain or fill task ended without finalizing.
+    pub orphaned_node_operation_timeout: Duration,
 
-    /// Apply the contents of a [`ReconcileResult`] to our in-memory state: if the reconciliation
-    /// was successful and intent hasn't changed since the Reconciler was spawned, this will update
-    /// the observed state of the tenant such that subsequent calls to [`TenantShard::get_reconcile_needed`]
-    /// will indicate that reconciliation is not needed.
-    #[instrument(skip_all, fields(
-        tenant_id=%result.tenant_shard_id.tenant_id, shard_id=%result.tenant_shard_id.shard_slug(),
-        sequence=%result.sequence
-    ))]
-    fn process_result(&self, result: ReconcileResult) {
-        let mut locked = self.inner.write().unwrap();
-        let Some(tenant) = locked.tenants.get_mut(&result.tenant_shard_id) else {
-            // A reconciliation result might race with removing a tenant: drop results for
-            // tenants that aren't in our map.
-            return;
-        };
+    /// How long [`Service::startup_reconcile`] should take at most, before we stop waiting for
+    /// unresponsive pageservers.  API requests wait this long for startup to complete.
+    pub startup_reconcile_timeout: Duration,
 
-        // Usually generation should only be updated via this path, so the max() isn't
-        // needed, but it is used to handle out-of-band updates via. e.g. test hook.
-        tenant.generation = std::cmp::max(tenant.generation, result.generation);
+    /// How much of `startup_reconcile_timeout` may be spent scanning pageservers' locations.
+    pub startup_scan_timeout: Duration,
 
-        // If the reconciler signals that it failed to notify compute, set this state on
-        // the shard so that a future [`TenantShard::maybe_reconcile`] will try again.
-        tenant.pending_compute_notification = result.pending_compute_notification;
+    /// How many Reconcilers may be spawned concurrently
+    pub reconciler_concurrency: usize,
 
-        // Let the TenantShard know it is idle.
-        tenant.reconcile_complete(result.sequence);
+    /// How many Reconcilers may be spawned concurrently during startup, to converge quickly after
+    /// a restart.  Once startup is complete, we drop back to `reconciler_concurrency`.
+    pub startup_reconciler_concurrency: usize,
 
-        match result.result {
-            Ok(()) => {
-                for (node_id, loc) in &result.observed.locations {
-                    if let Some(conf) = &loc.conf {
-                        tracing::info!("Updating observed location {}: {:?}", node_id, conf);
-                    } else {
-                        tracing::info!("Setting observed location {} to None", node_id,)
-                    }
-                }
-                tenant.observed = result.observed;
-                tenant.waiter.advance(result.sequence);
-            }
-            Err(e) => {
-                match e {
-                    ReconcileError::Cancel => {
-                        tracing::info!("Reconciler was cancelled");
-                    }
-                    ReconcileError::Remote(mgmt_api::Error::Cancelled) => {
-                        // This might be due to the reconciler getting cancelled, or it might
-                        // be due to the `Node` being marked offline.
-                        tracing::info!("Reconciler cancelled during pageserver API call");
-                    }
-                    _ => {
-                        tracing::warn!("Reconcile error: {}", e);
-                    }
-                }
+    /// Percentage by which the periods of background reconciliation and heartbeats are randomly
+    /// lengthened or shortened on each tick.  Must be less than 100.
+    pub background_jitter_pct: u32,
 
-                // Ordering: populate last_error before advancing error_seq,
-                // so that waiters will see the correct error after waiting.
-                tenant.set_last_error(result.sequence, e);
+    /// How many attached shards a node's utilization counts for when scheduling, between the
+    /// least and most utilized candidate nodes.  Zero disables utilization-aware scheduling.
+    pub scheduler_utilization_weight: usize,
 
-                for (node_id, o) in result.observed.locations {
-                    tenant.observed.locations.insert(node_id, o);
-                }
-            }
-        }
+    /// Limit on how many optimizations each background optimization pass will execute.  Combined
+    /// with the frequency of background passes, this acts as an implicit rate limit that runs a small
+    /// trickle of optimizations, rather than executing a large number in parallel when a change occurs.
+    /// Higher values rebalance the cluster faster, at the cost of more concurrent migrations.
+    pub max_optimizations_exec_per_pass: usize,
 
-        // Maybe some other work can proceed now that this job finished.
-        if self.reconciler_concurrency.available_permits() > 0 {
-            while let Ok(tenant_shard_id) = locked.delayed_reconcile_rx.try_recv() {
-                let (nodes, tenants, _scheduler) = locked.parts_mut();
-                if let Some(shard) = tenants.get_mut(&tenant_shard_id) {
-                    shard.delayed_reconcile = false;
-                    self.maybe_reconcile_shard(shard, nodes);
-                }
+    /// How many candidate optimizations each background optimization pass will generate, before
+    /// evaluating them for readiness: setting this higher than `max_optimizations_exec_per_pass`
+    /// gives us a chance to execute some work even if the first few optimizations are not ready.
+    /// Must be at least `max_optimizations_exec_per_pass`, which must be greater than zero.
+    pub max_optimizations_plan_per_pass: usize,
 
-                if self.reconciler_concurrency.available_permits() == 0 {
-                    break;
-                }
-            }
-        }
-    }
+    /// Size ceiling in bytes for the serialized (uncompressed) bodies of debug dump APIs such as
+    /// [`Service::tenants_dump`], to avoid building huge responses in memory on large clusters.
+    pub max_debug_dump_bytes: usize,
 
-    async fn process_results(
-        &self,
-        mut result_rx: tokio::sync::mpsc::UnboundedReceiver<ReconcileResult>,
-        mut bg_compute_hook_result_rx: tokio::sync::mpsc::Receiver<
-            Result<(), (TenantShardId, NotifyError)>,
-        >,
-    ) {
-        loop {
-            // Wait for the next result, or for cancellation
-            tokio::select! {
-                r = result_rx.recv() => {
-                    match r {
-                        Some(result) => {self.process_result(result);},
-                        None => {break;}
-                    }
-                }
-                _ = async{
-                    match bg_compute_hook_result_rx.recv().await {
-                        Some(result) => {
-                            if let Err((tenant_shard_id, notify_error)) = result {
-                                tracing::warn!("Marking shard {tenant_shard_id} for notification retry, due to error {notify_error}");
-                                let mut locked = self.inner.write().unwrap();
-                                if let Some(shard) = locked.tenants.get_mut(&tenant_shard_id) {
-                                    shard.pending_compute_notification = true;
-                                }
+    /// If set, [`Service::tenant_list`] serves a snapshot that is at most this old, to reduce
+    /// lock contention from frequent polling on large clusters.
+    pub tenant_list_cache_ttl: Option<Duration>,
 
-                            }
-                        },
-                        None => {
-                            // This channel is dead, but we don't want to terminate the outer loop{}: just wait for shutdown
-                            self.cancel.cancelled().await;
-                        }
-                    }
-                } => {},
-                _ = self.cancel.cancelled() => {
-                    break;
-                }
-            };
-        }
+    /// How large must a shard grow in bytes before we split it?
+    /// None disables auto-splitting.  This is the initial value: it may be changed at runtime
+    /// with [`Service::update_autosplit_config`].
+    pub split_threshold: Option<u64>,
 
-        // We should only fall through on shutdown
-        assert!(self.cancel.is_cancelled());
-    }
+    /// Which size of a shard is compared against `split_threshold`, and used to pick the
+    /// biggest tenant to split first.
+    pub split_sorting: TenantSorting,
 
-    async fn process_aborts(
-        &self,
-        mut abort_rx: tokio::sync::mpsc::UnboundedReceiver<TenantShardSplitAbort>,
-    ) {
-        loop {
-            // Wait for the next result, or for cancellation
-            let op = tokio::select! {
-                r = abort_rx.recv() => {
-                    match r {
-                        Some(op) => {op},
-                        None => {break;}
-                    }
-                }
-                _ = self.cancel.cancelled() => {
-                    break;
-                }
-            };
+    /// If set, automatic splits only start between these times of day (UTC), to confine their
+    /// disruption to a maintenance window.  The window spans midnight if it ends before it starts.
+    /// Manual splits are unaffected.
+    pub autosplit_window: Option<(chrono::NaiveTime, chrono::NaiveTime)>,
 
-            // Retry until shutdown: we must keep this request object alive until it is properly
-            // processed, as it holds a lock guard that prevents other operations trying to do things
-            // to the tenant while it is in a weird part-split state.
-            while !self.cancel.is_cancelled() {
-                match self.abort_tenant_shard_split(&op).await {
-                    Ok(_) => break,
-                    Err(e) => {
-                        tracing::warn!(
-                            "Failed to abort shard split on {}, will retry: {e}",
-                            op.tenant_id
-                        );
+    /// If set, creating tenants is refused once this many tenants exist.  Existing tenants
+    /// are unaffected.
+    pub max_tenants: Option<usize>,
 
-                        // If a node is unavailable, we hope that it has been properly marked Offline
-                        // when we retry, so that the abort op will succeed.  If the abort op is failing
-                        // for some other reason, we will keep retrying forever, or until a human notices
-                        // and does something about it (either fixing a pageserver or restarting the controller).
-                        tokio::time::timeout(Duration::from_secs(5), self.cancel.cancelled())
-                            .await
-                            .ok();
-                    }
-                }
+    /// If set, tenant creations and policy updates that ask for more secondary locations per
+    /// shard than this are refused up front, before anything is persisted.
+    pub max_secondaries_per_shard: Option<usize>,
+
+    /// If set, tenant creations and shard splits that ask for more shards than this are refused
+    /// up front, before anything is persisted.
+    pub max_shards_per_tenant: Option<usize>,
+
+    /// If a pageserver is seen holding a newer generation than we issued for a shard, we always
+    /// log an error.  If this is set, we also refuse to validate generations for that shard
+    /// until our observed state is consistent again.
+    pub fence_generation_violations: bool,
+
+    /// If set, a live migration destination that re-attaches mid-migration is kept in
+    /// AttachedMulti mode rather than being made AttachedSingle: see [`reattach_mode`].
+    pub reattach_preserve_multi: bool,
+
+    /// If set, a summary of each reconcile result is POSTed to this URL.  This is best-effort:
+    /// events are dropped if the endpoint is slow or unavailable.
+    pub reconcile_event_url: Option<String>,
+
+    /// Whether to treat this as a single-node deployment: see [`single_node_deployment`].  If
+    /// unset, this is inferred from how many nodes are registered.
+    pub single_node: Option<bool>,
+
+    /// Placement policy for tenants imported in an attached mode via the location_config API.  If
+    /// unset, this is `Attached(1)`.  Single-node deployments always use `Attached(0)`, as nothing
+    /// else would be schedulable.
+    pub default_import_placement: Option<PlacementPolicy>,
+
+    // TODO: make this cfg(feature  = "testing")
+    pub neon_local_repo_dir: Option<PathBuf>,
+}
+
+impl From<DatabaseError> for ApiError {
+    fn from(err: DatabaseError) -> ApiError {
+        match err {
+            DatabaseError::Query(e) => ApiError::InternalServerError(e.into()),
+            // FIXME: ApiError doesn't have an Unavailable variant, but ShuttingDown maps to 503.
+            DatabaseError::Connection(_) | DatabaseError::ConnectionPool(_) => {
+                ApiError::ShuttingDown
             }
+            DatabaseError::Logical(reason) => {
+                ApiError::InternalServerError(anyhow::anyhow!(reason))
+            }
+            // Retryable: the pageserver keeps retrying its re-attach until the tenant is unfrozen
+            DatabaseError::GenerationFrozen(tenant_id) => ApiError::ResourceUnavailable(
+                format!("Generation of tenant {tenant_id} is frozen").into(),
+            ),
         }
     }
+}
 
-    pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Result<Arc<Self>> {
-        let (result_tx, result_rx) = tokio::sync::mpsc::unbounded_channel();
-        let (abort_tx, abort_rx) = tokio::sync::mpsc::unbounded_channel();
+pub struct Service {
+    inner: Arc<std::sync::RwLock<ServiceState>>,
+    config: Config,
+    persistence: Arc<Persistence>,
+    compute_hook: Arc<ComputeHook>,
+    result_tx: CountedSender<ReconcileResult>,
 
-        tracing::info!("Loading nodes from database...");
-        let nodes = persistence
-            .list_nodes()
-            .await?
-            .into_iter()
-            .map(Node::from_persistent)
-            .collect::<Vec<_>>();
-        let nodes: HashMap<NodeId, Node> = nodes.into_iter().map(|n| (n.get_id(), n)).collect();
-        tracing::info!("Loaded {} nodes from database.", nodes.len());
+    // Results of background compute notifications, consumed by [`Self::process_results`]
+    bg_compute_notify_result_tx:
+        tokio::sync::mpsc::Sender<Result<(), (TenantShardId, NotifyError)>>,
 
-        tracing::info!("Loading shards from database...");
-        let mut tenant_shard_persistence = persistence.list_tenant_shards().await?;
-        tracing::info!(
-            "Loaded {} shards from database.",
-            tenant_shard_persistence.len()
-        );
+    heartbeater: Heartbeater,
 
-        // If any shard splits were in progress, reset the database state to abort them
-        let mut tenant_shard_count_min_max: HashMap<TenantId, (ShardCount, ShardCount)> =
-            HashMap::new();
-        for tsp in &mut tenant_shard_persistence {
-            let shard = tsp.get_shard_identity()?;
-            let tenant_shard_id = tsp.get_tenant_shard_id()?;
-            let entry = tenant_shard_count_min_max
-                .entry(tenant_shard_id.tenant_id)
-                .or_insert_with(|| (shard.count, shard.count));
-            entry.0 = std::cmp::min(entry.0, shard.count);
-            entry.1 = std::cmp::max(entry.1, shard.count);
-        }
+    tenant_list_cache: Option<TtlCache<Vec<TenantDescribeResponse>>>,
 
-        for (tenant_id, (count_min, count_max)) in tenant_shard_count_min_max {
-            if count_min != count_max {
-                // Aborting the split in the database and dropping the child shards is sufficient: the reconciliation in
-                // [`Self::startup_reconcile`] will implicitly drop the child shards on remote pageservers, or they'll
-                // be dropped later in [`Self::node_activate_reconcile`] if it isn't available right now.
-                tracing::info!("Aborting shard split {tenant_id} {count_min:?} -> {count_max:?}");
-                let abort_status = persistence.abort_shard_split(tenant_id, count_max).await?;
+    // Channel for background cleanup from failed operations that require cleanup, such as shard split
+    abort_tx: CountedSender<TenantShardSplitAbort>,
 
-                // We may never see the Complete status here: if the split was complete, we wouldn't have
-                // identified this tenant has having mismatching min/max counts.
-                assert!(matches!(abort_status, AbortShardSplitStatus::Aborted));
+    // Liveness and backlog of the background tasks spawned in [`Self::spawn`]
+    liveness: ProcessorLiveness,
 
-                // Clear the splitting status in-memory, to reflect that we just aborted in the database
-                tenant_shard_persistence.iter_mut().for_each(|tsp| {
-                    // Set idle split state on those shards that we will retain.
-                    let tsp_tenant_id = TenantId::from_str(tsp.tenant_id.as_str()).unwrap();
-                    if tsp_tenant_id == tenant_id
-                        && tsp.get_shard_identity().unwrap().count == count_min
-                    {
-                        tsp.splitting = SplitState::Idle;
-                    } else if tsp_tenant_id == tenant_id {
-                        // Leave the splitting state on the child shards: this will be used next to
-                        // drop them.
-                        tracing::info!(
-                            "Shard {tsp_tenant_id} will be dropped after shard split abort",
-                        );
-                    }
-                });
+    // Settings read by [`Self::autosplit_tenants`], which may be changed at runtime
+    autosplit_config: std::sync::RwLock<AutosplitConfig>,
 
-                // Drop shards for this tenant which we didn't just mark idle (i.e. child shards of the aborted split)
-                tenant_shard_persistence.retain(|tsp| {
-                    TenantId::from_str(tsp.tenant_id.as_str()).unwrap() != tenant_id
-                        || tsp.splitting == SplitState::Idle
-                });
-            }
-        }
+    shard_splits: ShardSplitTracker,
 
-        let mut tenants = BTreeMap::new();
+    frozen_generations: Arc<FrozenGenerations>,
 
-        let mut scheduler = Scheduler::new(nodes.values());
+    // Held while [`Self::rescan_all_nodes`] runs, so that only one rescan runs at a time
+    rescan_lock: tokio::sync::Mutex<()>,
 
-        #[cfg(feature = "testing")]
-        {
-            // Hack: insert scheduler state for all nodes referenced by shards, as compatibility
-            // tests only store the shards, not the nodes.  The nodes will be loaded shortly
-            // after when pageservers start up and register.
-            let mut node_ids = HashSet::new();
-            for tsp in &tenant_shard_persistence {
-                if let Some(node_id) = tsp.generation_pageserver {
-                    node_ids.insert(node_id);
-                }
-            }
-            for node_id in node_ids {
-                tracing::info!("Creating node {} in scheduler for tests", node_id);
-                let node = Node::new(
-                    NodeId(node_id as u64),
-                    "".to_string(),
-                    123,
-                    "".to_string(),
-                    123,
-                );
+    // Locking on a tenant granularity (covers all shards in the tenant):
+    // - Take exclusively for rare operations that mutate the tenant's persistent state (e.g. create/delete/split)
+    // - Take in shared mode for operations that need the set of shards to stay the same to complete reliably (e.g. timeline CRUD)
+    tenant_op_locks: IdLockMap<TenantId, TenantOperations>,
 
-                scheduler.node_upsert(&node);
-            }
-        }
-        for tsp in tenant_shard_persistence {
-            let tenant_shard_id = tsp.get_tenant_shard_id()?;
+    // Locking for node-mutating operations: take exclusively for operations that modify the node's persistent state, or
+    // that transition it to/from Active.
+    node_op_locks: IdLockMap<NodeId, NodeOperations>,
 
-            // We will populate intent properly later in [`Self::startup_reconcile`], initially populate
-            // it with what we can infer: the node for which a generation was most recently issued.
-            let mut intent = IntentState::new();
-            if let Some(generation_pageserver) = tsp.generation_pageserver {
-                intent.set_attached(&mut scheduler, Some(NodeId(generation_pageserver as u64)));
-            }
-            let new_tenant = TenantShard::from_persistent(tsp, intent)?;
+    // Limit how many Reconcilers we will spawn concurrently
+    reconciler_concurrency: Arc<tokio::sync::Semaphore>,
 
-            tenants.insert(tenant_shard_id, new_tenant);
-        }
+    /// How many units `reconciler_concurrency` was created with, less any retired after startup:
+    /// the units in use are this minus the available permits.
+    reconciler_concurrency_limit: AtomicUsize,
 
-        let (startup_completion, startup_complete) = utils::completion::channel();
+    /// Queue of tenants who are waiting for concurrency limits to permit them to reconcile
+    /// Send into this queue to promptly attempt to reconcile this shard next time units are available.
+    ///
+    /// Note that this state logically lives inside ServiceInner, but carrying Sender here makes the code simpler
+    /// by avoiding needing a &mut ref to something inside the ServiceInner.  This could be optimized to
+    /// use a VecDeque instead of a channel to reduce synchronization overhead, at the cost of some code complexity.
+    delayed_reconcile_tx: tokio::sync::mpsc::Sender<TenantShardId>,
 
-        // This channel is continuously consumed by process_results, so doesn't need to be very large.
-        let (bg_compute_notify_result_tx, bg_compute_notify_result_rx) =
-            tokio::sync::mpsc::channel(512);
+    /// Like `delayed_reconcile_tx`, for shard zero shards and shards of [`ReconcilePriority::High`]
+    /// tenants.  Shard zero serves tenant-wide operations and most timeline reads, so when
+    /// reconciler units are scarce it should converge first, as should the tenants we care most
+    /// about.
+    delayed_reconcile_priority_tx: tokio::sync::mpsc::Sender<TenantShardId>,
 
-        let (delayed_reconcile_tx, delayed_reconcile_rx) =
-            tokio::sync::mpsc::channel(MAX_DELAYED_RECONCILES);
+    /// Fires with a shard's ID whenever one of its reconciles completes or fails, for
+    /// [`Service::tenant_reconcile_watch`].  Sends are dropped if nobody is watching.
+    reconcile_watch_tx: tokio::sync::broadcast::Sender<TenantShardId>,
 
-        let cancel = CancellationToken::new();
-        let heartbeater = Heartbeater::new(
-            config.jwt_token.clone(),
-            config.max_unavailable_interval,
-            cancel.clone(),
-        );
-        let this = Arc::new(Self {
-            inner: Arc::new(std::sync::RwLock::new(ServiceState::new(
-                nodes,
-                tenants,
-                scheduler,
-                delayed_reconcile_rx,
-            ))),
-            config: config.clone(),
-            persistence,
-            compute_hook: Arc::new(ComputeHook::new(config.clone())),
-            result_tx,
-            heartbeater,
-            reconciler_concurrency: Arc::new(tokio::sync::Semaphore::new(
-                config.reconciler_concurrency,
-            )),
-            delayed_reconcile_tx,
-            abort_tx,
-            startup_complete: startup_complete.clone(),
-            cancel,
-            gate: Gate::default(),
-            tenant_op_locks: Default::default(),
-            node_op_locks: Default::default(),
-        });
+    /// Queue of reconcile results to publish, if [`Config::reconcile_event_url`] is set.
+    reconcile_event_tx: Option<tokio::sync::mpsc::Sender<ReconcileEvent>>,
 
-        let result_task_this = this.clone();
-        tokio::task::spawn(async move {
-            // Block shutdown until we're done (we must respect self.cancel)
-            if let Ok(_gate) = result_task_this.gate.enter() {
-                result_task_this
-                    .process_results(result_rx, bg_compute_notify_result_rx)
-                    .await
-            }
-        });
+    // Process shutdown will fire this token
+    cancel: CancellationToken,
 
-        tokio::task::spawn({
-            let this = this.clone();
-            async move {
-                // Block shutdown until we're done (we must respect self.cancel)
-                if let Ok(_gate) = this.gate.enter() {
-                    this.process_aborts(abort_rx).await
-                }
-            }
-        });
+    // Background tasks will hold this gate
+    gate: Gate,
 
-        tokio::task::spawn({
-            let this = this.clone();
-            async move {
-                if let Ok(_gate) = this.gate.enter() {
-                    loop {
-                        tokio::select! {
-                            _ = this.cancel.cancelled() => {
-                                break;
-                            },
-                            _ = tokio::time::sleep(Duration::from_secs(60)) => {}
-                        };
-                        this.tenant_op_locks.housekeeping();
-                    }
-                }
-            }
-        });
+    /// This waits for initial reconciliation with pageservers to complete.  Until this barrier
+    /// passes, it isn't safe to do any actions that mutate tenants.
+    pub(crate) startup_complete: Barrier,
+}
 
-        tokio::task::spawn({
-            let this = this.clone();
-            // We will block the [`Service::startup_complete`] barrier until [`Self::startup_reconcile`]
-            // is done.
-            let startup_completion = startup_completion.clone();
-            async move {
-                // Block shutdown until we're done (we must respect self.cancel)
-                let Ok(_gate) = this.gate.enter() else {
-                    return;
-                };
+impl From<ReconcileWaitError> for ApiError {
+    fn from(value: ReconcileWaitError) -> Self {
+        match value {
+            ReconcileWaitError::Shutdown => ApiError::ShuttingDown,
+            e @ ReconcileWaitError::Timeout(_) => ApiError::Timeout(format!("{e}").into()),
+            e @ ReconcileWaitError::Failed(..) => ApiError::InternalServerError(anyhow::anyhow!(e)),
+        }
+    }
+}
 
-                this.startup_reconcile(bg_compute_notify_result_tx).await;
-                drop(startup_completion);
+impl From<OperationError> for ApiError {
+    fn from(value: OperationError) -> Self {
+        match value {
+            OperationError::NodeStateChanged(err) | OperationError::FinalizeError(err) => {
+                ApiError::InternalServerError(anyhow::anyhow!(err))
             }
-        });
+            OperationError::Cancelled => ApiError::Conflict("Operation was cancelled".into()),
+        }
+    }
+}
 
-        tokio::task::spawn({
-            let this = this.clone();
-            let startup_complete = startup_complete.clone();
-            async move {
-                startup_complete.wait().await;
-                this.background_reconcile().await;
-            }
-        });
+#[allow(clippy::large_enum_variant)]
+enum TenantCreateOrUpdate {
+    Create(TenantCreateRequest),
+    Update(Vec<ShardUpdate>),
+}
 
-        tokio::task::spawn({
-            let this = this.clone();
-            let startup_complete = startup_complete.clone();
-            async move {
-                startup_complete.wait().await;
-                this.spawn_heartbeat_driver().await;
-            }
-        });
+struct ShardSplitParams {
+    old_shard_count: ShardCount,
+    new_shard_count: ShardCount,
+    new_stripe_size: Option<ShardStripeSize>,
+    targets: Vec<ShardSplitTarget>,
+    policy: PlacementPolicy,
+    config: TenantConfig,
+    flags: TenantFlags,
+    priority: ReconcilePriority,
+    shard_ident: ShardIdentity,
+}
 
-        Ok(this)
+// When preparing for a shard split, we may either choose to proceed with the split,
+// or find that the work is already done and return NoOp.
+enum ShardSplitAction {
+    Split(ShardSplitParams),
+    NoOp(TenantShardSplitResponse),
+}
+
+// A parent shard which will be split
+struct ShardSplitTarget {
+    parent_id: TenantShardId,
+    node: Node,
+    child_ids: Vec<TenantShardId>,
+}
+
+/// Check that every node we would ask to split a shard is able to, so that a split fails
+/// before it starts rather than partway through, leaving us to abort it.
+fn check_split_targets(targets: &[ShardSplitTarget]) -> Result<(), ApiError> {
+    match targets.iter().find(|t| !t.node.supports_shard_split()) {
+        Some(target) => Err(ApiError::PreconditionFailed(
+            format!(
+                "Cannot split shard {}: pageserver {} does not support shard splitting",
+                target.parent_id, target.node
+            )
+            .into(),
+        )),
+        None => Ok(()),
     }
+}
 
-    pub(crate) async fn attach_hook(
-        &self,
-        attach_req: AttachHookRequest,
-    ) -> anyhow::Result<AttachHookResponse> {
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            attach_req.tenant_shard_id.tenant_id,
-            TenantOperations::ShardSplit,
-        )
-        .await;
+/// When we tenant shard split operation fails, we may not be able to clean up immediately, because nodes
+/// might not be available.  We therefore use a queue of abort operations processed in the background.
+struct TenantShardSplitAbort {
+    tenant_id: TenantId,
+    /// The target values from the request that failed
+    new_shard_count: ShardCount,
+    new_stripe_size: Option<ShardStripeSize>,
+    /// Until this abort op is complete, no other operations may be done on the tenant
+    _tenant_lock: TracingExclusiveGuard<TenantOperations>,
+}
 
-        // This is a test hook.  To enable using it on tenants that were created directly with
-        // the pageserver API (not via this service), we will auto-create any missing tenant
-        // shards with default state.
-        let insert = {
-            let locked = self.inner.write().unwrap();
-            !locked.tenants.contains_key(&attach_req.tenant_shard_id)
-        };
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ShardSplitPhase {
+    /// Nothing has been done on pageservers yet: the split may be cancelled
+    Prepare,
+    /// Pageservers have been asked to split: the split can only complete, or fail and be aborted
+    Remote,
+}
 
-        if insert {
-            let tsp = TenantShardPersistence {
-                tenant_id: attach_req.tenant_shard_id.tenant_id.to_string(),
-                shard_number: attach_req.tenant_shard_id.shard_number.0 as i32,
-                shard_count: attach_req.tenant_shard_id.shard_count.literal() as i32,
-                shard_stripe_size: 0,
-                generation: attach_req.generation_override.or(Some(0)),
-                generation_pageserver: None,
-                placement_policy: serde_json::to_string(&PlacementPolicy::Attached(0)).unwrap(),
-                config: serde_json::to_string(&TenantConfig::default()).unwrap(),
-                splitting: SplitState::default(),
-                scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
-                    .unwrap(),
-            };
+struct ShardSplitInProgress {
+    phase: ShardSplitPhase,
+    cancelled: bool,
+}
 
-            match self.persistence.insert_tenant_shards(vec![tsp]).await {
-                Err(e) => match e {
-                    DatabaseError::Query(diesel::result::Error::DatabaseError(
-                        DatabaseErrorKind::UniqueViolation,
-                        _,
-                    )) => {
-                        tracing::info!(
-                            "Raced with another request to insert tenant {}",
-                            attach_req.tenant_shard_id
-                        )
-                    }
-                    _ => return Err(e.into()),
-                },
-                Ok(()) => {
-                    tracing::info!("Inserted shard {} in database", attach_req.tenant_shard_id);
+/// Tracks the phase of each in-flight [`Service::tenant_shard_split`], so that a split can be
+/// cancelled up until the point where we start calling into pageservers.
+#[derive(Default)]
+struct ShardSplitTracker {
+    splits: std::sync::Mutex<HashMap<TenantId, ShardSplitInProgress>>,
+}
 
-                    let mut locked = self.inner.write().unwrap();
-                    locked.tenants.insert(
-                        attach_req.tenant_shard_id,
-                        TenantShard::new(
-                            attach_req.tenant_shard_id,
-                            ShardIdentity::unsharded(),
-                            PlacementPolicy::Attached(0),
-                        ),
-                    );
-                    tracing::info!("Inserted shard {} in memory", attach_req.tenant_shard_id);
-                }
-            }
-        }
+/// Removes a split from its [`ShardSplitTracker`] when dropped, including when the split's request
+/// future is dropped partway through (e.g. the client disconnected).
+struct ShardSplitGuard<'a> {
+    tracker: &'a ShardSplitTracker,
+    tenant_id: TenantId,
+}
 
-        let new_generation = if let Some(req_node_id) = attach_req.node_id {
-            let maybe_tenant_conf = {
-                let locked = self.inner.write().unwrap();
-                locked
-                    .tenants
-                    .get(&attach_req.tenant_shard_id)
-                    .map(|t| t.config.clone())
-            };
+impl Drop for ShardSplitGuard<'_> {
+    fn drop(&mut self) {
+        self.tracker.splits.lock().unwrap().remove(&self.tenant_id);
+    }
+}
 
-            match maybe_tenant_conf {
-                Some(conf) => {
-                    let new_generation = self
-                        .persistence
-                        .increment_generation(attach_req.tenant_shard_id, req_node_id)
-                        .await?;
+impl ShardSplitTracker {
+    /// Track a split until the returned guard is dropped
+    fn begin(&self, tenant_id: TenantId) -> ShardSplitGuard<'_> {
+        self.splits.lock().unwrap().insert(
+            tenant_id,
+            ShardSplitInProgress {
+                phase: ShardSplitPhase::Prepare,
+                cancelled: false,
+            },
+        );
+        ShardSplitGuard {
+            tracker: self,
+            tenant_id,
+        }
+    }
 
-                    // Persist the placement policy update. This is required
-                    // when we reattaching a detached tenant.
-                    self.persistence
-                        .update_tenant_shard(
-                            TenantFilter::Shard(attach_req.tenant_shard_id),
-                            Some(PlacementPolicy::Attached(0)),
-                            Some(conf),
-                            None,
-                            None,
-                        )
-                        .await?;
-                    Some(new_generation)
-                }
-                None => {
-                    anyhow::bail!("Attach hook handling raced with tenant removal")
-                }
-            }
-        } else {
-            self.persistence.detach(attach_req.tenant_shard_id).await?;
-            None
+    /// Called by the split at phase boundaries: fails if the split has been cancelled, in which
+    /// case the split should bail out and be aborted.
+    fn advance(&self, tenant_id: TenantId, phase: ShardSplitPhase) -> Result<(), ApiError> {
+        let mut splits = self.splits.lock().unwrap();
+        let Some(split) = splits.get_mut(&tenant_id) else {
+            return Ok(());
         };
 
-        let mut locked = self.inner.write().unwrap();
-        let (_nodes, tenants, scheduler) = locked.parts_mut();
-
-        let tenant_shard = tenants
-            .get_mut(&attach_req.tenant_shard_id)
-            .expect("Checked for existence above");
-
-        if let Some(new_generation) = new_generation {
-            tenant_shard.generation = Some(new_generation);
-            tenant_shard.policy = PlacementPolicy::Attached(0);
-        } else {
-            // This is a detach notification.  We must update placement policy to avoid re-attaching
-            // during background scheduling/reconciliation, or during storage controller restart.
-            assert!(attach_req.node_id.is_none());
-            tenant_shard.policy = PlacementPolicy::Detached;
+        if split.cancelled {
+            return Err(ApiError::Conflict("Shard split was cancelled".to_string()));
         }
+        split.phase = phase;
+        Ok(())
+    }
 
-        if let Some(attaching_pageserver) = attach_req.node_id.as_ref() {
-            tracing::info!(
-                tenant_id = %attach_req.tenant_shard_id,
-                ps_id = %attaching_pageserver,
-                generation = ?tenant_shard.generation,
-                "issuing",
-            );
-        } else if let Some(ps_id) = tenant_shard.intent.get_attached() {
-            tracing::info!(
-                tenant_id = %attach_req.tenant_shard_id,
-                %ps_id,
-                generation = ?tenant_shard.generation,
-                "dropping",
-            );
-        } else {
-            tracing::info!(
-            tenant_id = %attach_req.tenant_shard_id,
-            "no-op: tenant already has no pageserver");
+    fn cancel(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        let mut splits = self.splits.lock().unwrap();
+        let Some(split) = splits.get_mut(&tenant_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("No shard split in progress for tenant {tenant_id}").into(),
+            ));
+        };
+
+        match split.phase {
+            ShardSplitPhase::Prepare => {
+                split.cancelled = true;
+                Ok(())
+            }
+            ShardSplitPhase::Remote => Err(ApiError::PreconditionFailed(
+                "Shard split has already started on pageservers and can no longer be cancelled"
+                    .into(),
+            )),
         }
-        tenant_shard
-            .intent
-            .set_attached(scheduler, attach_req.node_id);
+    }
+}
 
-        tracing::info!(
-            "attach_hook: tenant {} set generation {:?}, pageserver {}",
-            attach_req.tenant_shard_id,
-            tenant_shard.generation,
-            // TODO: this is an odd number of 0xf's
-            attach_req.node_id.unwrap_or(utils::id::NodeId(0xfffffff))
+/// Persist a change to a shard's [`TenantShard::pending_compute_notification`].  This is best
+/// effort: if it fails, the notification is still retried unless the controller restarts.
+pub(crate) async fn persist_pending_compute_notification(
+    persistence: &Persistence,
+    tenant_shard_id: TenantShardId,
+    pending: bool,
+) {
+    if let Err(e) = persistence
+        .set_pending_compute_notification(tenant_shard_id, pending)
+        .await
+    {
+        tracing::warn!(
+            %tenant_shard_id,
+            "Failed to persist pending compute notification ({pending}): {e}"
         );
+    }
+}
 
-        // Trick the reconciler into not doing anything for this tenant: this helps
-        // tests that manually configure a tenant on the pagesrever, and then call this
-        // attach hook: they don't want background reconciliation to modify what they
-        // did to the pageserver.
-        #[cfg(feature = "testing")]
-        {
-            if let Some(node_id) = attach_req.node_id {
-                tenant_shard.observed.locations = HashMap::from([(
-                    node_id,
-                    ObservedStateLocation {
-                        conf: Some(attached_location_conf(
-                            tenant_shard.generation.unwrap(),
-                            &tenant_shard.shard,
-                            &tenant_shard.config,
-                            &PlacementPolicy::Attached(0),
-                        )),
-                    },
-                )]);
-            } else {
-                tenant_shard.observed.locations.clear();
-            }
-        }
+/// Part of [`Service::tenant_shard_set_generation`]: check whether `generation` may overwrite a
+/// shard's generation.
+fn check_set_generation(
+    shard: &TenantShard,
+    generation: Generation,
+    force: bool,
+) -> Result<(), ApiError> {
+    if shard.reconciler.is_some() {
+        // A reconciler's result would overwrite the generation we set here
+        return Err(ApiError::PreconditionFailed(
+            "A reconcile is in progress: stop scheduling and cancel reconciles first".into(),
+        ));
+    }
 
-        Ok(AttachHookResponse {
-            gen: attach_req
-                .node_id
-                .map(|_| tenant_shard.generation.expect("Test hook, not used on tenants that are mid-onboarding with a NULL generation").into().unwrap()),
-        })
+    if let Some(current) = shard.generation {
+        if generation < current && !force {
+            return Err(ApiError::PreconditionFailed(
+                format!(
+                    "Refusing to move generation backwards from {current:?} to {generation:?} without force"
+                )
+                .into(),
+            ));
+        }
     }
 
-    pub(crate) fn inspect(&self, inspect_req: InspectRequest) -> InspectResponse {
-        let locked = self.inner.read().unwrap();
+    Ok(())
+}
 
-        let tenant_shard = locked.tenants.get(&inspect_req.tenant_shard_id);
+/// Tenants whose generations must not be incremented, e.g. during the window where authority over
+/// their generations is being transferred into or out of this controller (see
+/// [`Service::tenant_location_config`]).  This is not persistent, and is cleared when the
+/// controller restarts: it is a guard for operators to hold while carrying out such a transfer.
+/// Shared with reconcilers, which check it before incrementing a generation.
+#[derive(Default)]
+pub(crate) struct FrozenGenerations(std::sync::Mutex<HashSet<TenantId>>);
+
+impl FrozenGenerations {
+    fn set(&self, tenant_id: TenantId, frozen: bool) {
+        let mut locked = self.0.lock().unwrap();
+        if frozen {
+            locked.insert(tenant_id);
+        } else {
+            locked.remove(&tenant_id);
+        }
+    }
 
-        InspectResponse {
-            attachment: tenant_shard.and_then(|s| {
-                s.intent
-                    .get_attached()
-                    .map(|ps| (s.generation.expect("Test hook, not used on tenants that are mid-onboarding with a NULL generation").into().unwrap(), ps))
-            }),
+    pub(crate) fn check(&self, tenant_id: TenantId) -> anyhow::Result<()> {
+        if self.0.lock().unwrap().contains(&tenant_id) {
+            anyhow::bail!("Generation of tenant {tenant_id} is frozen");
         }
+        Ok(())
     }
 
-    // When the availability state of a node transitions to active, we must do a full reconciliation
-    // of LocationConfigs on that node.  This is because while a node was offline:
-    // - we might have proceeded through startup_reconcile without checking for extraneous LocationConfigs on this node
-    // - aborting a tenant shard split might have left rogue child shards behind on this node.
-    //
-    // This function must complete _before_ setting a `Node` to Active: once it is set to Active, other
-    // Reconcilers might communicate with the node, and these must not overlap with the work we do in
-    // this function.
-    //
-    // The reconciliation logic in here is very similar to what [`Self::startup_reconcile`] does, but
-    // for written for a single node rather than as a batch job for all nodes.
-    #[tracing::instrument(skip_all, fields(node_id=%node.get_id()))]
-    async fn node_activate_reconcile(
-        &self,
-        mut node: Node,
-        _lock: &TracingExclusiveGuard<NodeOperations>,
-    ) -> Result<(), ApiError> {
-        // This Node is a mutable local copy: we will set it active so that we can use its
-        // API client to reconcile with the node.  The Node in [`Self::nodes`] will get updated
-        // later.
-        node.set_availability(NodeAvailability::Active(UtilizationScore::worst()));
+    fn list(&self) -> Vec<TenantId> {
+        self.0.lock().unwrap().iter().copied().collect()
+    }
+}
 
-        let configs = match node
-            .with_client_retries(
-                |client| async move { client.list_location_config().await },
-                &self.config.jwt_token,
-                1,
-                5,
-                SHORT_RECONCILE_TIMEOUT,
-                &self.cancel,
-            )
-            .await
-        {
-            None => {
-                // We're shutting down (the Node's cancellation token can't have fired, because
-                // we're the only scope that has a reference to it, and we didn't fire it).
-                return Err(ApiError::ShuttingDown);
-            }
-            Some(Err(e)) => {
-                // This node didn't succeed listing its locations: it may not proceed to active state
-                // as it is apparently unavailable.
-                return Err(ApiError::PreconditionFailed(
-                    format!("Failed to query node location configs, cannot activate ({e})").into(),
-                ));
-            }
-            Some(Ok(configs)) => configs,
-        };
-        tracing::info!("Loaded {} LocationConfigs", configs.tenant_shards.len());
+#[derive(thiserror::Error, Debug)]
+enum TenantShardSplitAbortError {
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    Remote(#[from] mgmt_api::Error),
+    #[error("Unavailable")]
+    Unavailable,
+}
 
-        let mut cleanup = Vec::new();
-        {
-            let mut locked = self.inner.write().unwrap();
+impl Service {
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
 
-            for (tenant_shard_id, observed_loc) in configs.tenant_shards {
-                let Some(tenant_shard) = locked.tenants.get_mut(&tenant_shard_id) else {
-                    cleanup.push(tenant_shard_id);
-                    continue;
-                };
-                tenant_shard
-                    .observed
-                    .locations
-                    .insert(node.get_id(), ObservedStateLocation { conf: observed_loc });
-            }
-        }
-
-        for tenant_shard_id in cleanup {
-            tracing::info!("Detaching {tenant_shard_id}");
-            match node
-                .with_client_retries(
-                    |client| async move {
-                        let config = LocationConfig {
-                            mode: LocationConfigMode::Detached,
-                            generation: None,
-                            secondary_conf: None,
-                            shard_number: tenant_shard_id.shard_number.0,
-                            shard_count: tenant_shard_id.shard_count.literal(),
-                            shard_stripe_size: 0,
-                            tenant_conf: models::TenantConfig::default(),
-                        };
-                        client
-                            .location_config(tenant_shard_id, config, None, false)
-                            .await
-                    },
-                    &self.config.jwt_token,
-                    1,
-                    5,
-                    SHORT_RECONCILE_TIMEOUT,
-                    &self.cancel,
-                )
-                .await
-            {
-                None => {
-                    // We're shutting down (the Node's cancellation token can't have fired, because
-                    // we're the only scope that has a reference to it, and we didn't fire it).
-                    return Err(ApiError::ShuttingDown);
-                }
-                Some(Err(e)) => {
-                    // Do not let the node proceed to Active state if it is not responsive to requests
-                    // to detach.  This could happen if e.g. a shutdown bug in the pageserver is preventing
-                    // detach completing: we should not let this node back into the set of nodes considered
-                    // okay for scheduling.
-                    return Err(ApiError::Conflict(format!(
-                        "Node {node} failed to detach {tenant_shard_id}: {e}"
-                    )));
-                }
-                Some(Ok(_)) => {}
-            };
-        }
+    /// Called once on startup, this function attempts to contact all pageservers to build an up-to-date
+    /// view of the world, and determine which pageservers are responsive.
+    #[instrument(skip_all)]
+    async fn startup_reconcile(
+        self: &Arc<Service>,
+        bg_compute_notify_result_tx: tokio::sync::mpsc::Sender<
+            Result<(), (TenantShardId, NotifyError)>,
+        >,
+    ) {
+        // For all tenant shards, a vector of observed states on nodes (where None means
+        // indeterminate, same as in [`ObservedStateLocation`])
+        let mut observed: HashMap<TenantShardId, Vec<(NodeId, Option<LocationConfig>)>> =
+            HashMap::new();
 
-        Ok(())
-    }
+        // Startup reconciliation does I/O to other services: whether they
+        // are responsive or not, we should aim to finish within our deadline, because:
+        // - If we don't, a k8s readiness hook watching /ready will kill us.
+        // - While we're waiting for startup reconciliation, we are not fully
+        //   available for end user operations like creating/deleting tenants and timelines.
+        //
+        // We set multiple deadlines to break up the time available between the phases of work: this is
+        // arbitrary, but avoids a situation where the first phase could burn our entire timeout period.
+        let start_at = Instant::now();
+        let node_scan_deadline = start_at
+            .checked_add(self.config.startup_scan_timeout)
+            .expect("Scan timeout is a modest duration");
 
-    pub(crate) async fn re_attach(
-        &self,
-        reattach_req: ReAttachRequest,
-    ) -> Result<ReAttachResponse, ApiError> {
-        if let Some(register_req) = reattach_req.register {
-            self.node_register(register_req).await?;
-        }
+        // Accumulate a list of any tenant locations that ought to be detached
+        let mut cleanup = Vec::new();
 
-        // Ordering: we must persist generation number updates before making them visible in the in-memory state
-        let incremented_generations = self.persistence.re_attach(reattach_req.node_id).await?;
+        let node_listings = self.scan_node_locations(node_scan_deadline).await;
+        // Send initial heartbeat requests to nodes that replied to the location listing above.
+        let nodes_online = self.initial_heartbeat_round(node_listings.keys()).await;
 
-        tracing::info!(
-            node_id=%reattach_req.node_id,
-            "Incremented {} tenant shards' generations",
-            incremented_generations.len()
-        );
+        for (node_id, list_response) in node_listings {
+            let tenant_shards = list_response.tenant_shards;
+            tracing::info!(
+                "Received {} shard statuses from pageserver {}, setting it to Active",
+                tenant_shards.len(),
+                node_id
+            );
 
-        // Apply the updated generation to our in-memory state, and
-        // gather discover secondary locations.
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
+            for (tenant_shard_id, conf_opt) in tenant_shards {
+                let shard_observations = observed.entry(tenant_shard_id).or_default();
+                shard_observations.push((node_id, conf_opt));
+            }
+        }
 
-        let mut response = ReAttachResponse {
-            tenants: Vec::new(),
-        };
+        // List of tenants for which we will attempt to notify compute of their location at startup
+        let mut compute_notifications = Vec::new();
 
-        // TODO: cancel/restart any running reconciliation for this tenant, it might be trying
-        // to call location_conf API with an old generation.  Wait for cancellation to complete
-        // before responding to this request.  Requires well implemented CancellationToken logic
-        // all the way to where we call location_conf.  Even then, there can still be a location_conf
-        // request in flight over the network: TODO handle that by making location_conf API refuse
-        // to go backward in generations.
+        // Populate intent and observed states for all tenants, based on reported state on pageservers
+        tracing::info!("Populating tenant shards' states from initial pageserver scan...");
+        let shard_count = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
 
-        // Scan through all shards, applying updates for ones where we updated generation
-        // and identifying shards that intend to have a secondary location on this node.
-        for (tenant_shard_id, shard) in tenants {
-            if let Some(new_gen) = incremented_generations.get(tenant_shard_id) {
-                let new_gen = *new_gen;
-                response.tenants.push(ReAttachResponseTenant {
-                    id: *tenant_shard_id,
-                    gen: Some(new_gen.into().unwrap()),
-                    // A tenant is only put into multi or stale modes in the middle of a [`Reconciler::live_migrate`]
-                    // execution.  If a pageserver is restarted during that process, then the reconcile pass will
-                    // fail, and start from scratch, so it doesn't make sense for us to try and preserve
-                    // the stale/multi states at this point.
-                    mode: LocationConfigMode::AttachedSingle,
-                });
+            // Mark nodes online if they responded to us: nodes are offline by default after a restart.
+            let mut new_nodes = (**nodes).clone();
+            for (node_id, node) in new_nodes.iter_mut() {
+                if let Some(utilization) = nodes_online.get(node_id) {
+                    node.set_availability(NodeAvailability::Active(UtilizationScore(
+                        utilization.utilization_score,
+                    )));
+                    scheduler.node_upsert(node);
+                }
+            }
+            *nodes = Arc::new(new_nodes);
 
-                shard.generation = std::cmp::max(shard.generation, Some(new_gen));
-                if let Some(observed) = shard.observed.locations.get_mut(&reattach_req.node_id) {
-                    // Why can we update `observed` even though we're not sure our response will be received
-                    // by the pageserver?  Because the pageserver will not proceed with startup until
-                    // it has processed response: if it loses it, we'll see another request and increment
-                    // generation again, avoiding any uncertainty about dirtiness of tenant's state.
-                    if let Some(conf) = observed.conf.as_mut() {
-                        conf.generation = new_gen.into();
-                    }
-                } else {
-                    // This node has no observed state for the shard: perhaps it was offline
-                    // when the pageserver restarted.  Insert a None, so that the Reconciler
-                    // will be prompted to learn the location's state before it makes changes.
-                    shard
+            for (tenant_shard_id, shard_observations) in observed {
+                for (node_id, observed_loc) in shard_observations {
+                    let Some(tenant_shard) = tenants.get_mut(&tenant_shard_id) else {
+                        cleanup.push((tenant_shard_id, node_id));
+                        continue;
+                    };
+                    tenant_shard
                         .observed
                         .locations
-                        .insert(reattach_req.node_id, ObservedStateLocation { conf: None });
+                        .insert(node_id, ObservedStateLocation { conf: observed_loc });
                 }
-            } else if shard.intent.get_secondary().contains(&reattach_req.node_id) {
-                // Ordering: pageserver will not accept /location_config requests until it has
-                // finished processing the response from re-attach.  So we can update our in-memory state
-                // now, and be confident that we are not stamping on the result of some later location config.
-                // TODO: however, we are not strictly ordered wrt ReconcileResults queue,
-                // so we might update observed state here, and then get over-written by some racing
-                // ReconcileResult.  The impact is low however, since we have set state on pageserver something
-                // that matches intent, so worst case if we race then we end up doing a spurious reconcile.
-
-                response.tenants.push(ReAttachResponseTenant {
-                    id: *tenant_shard_id,
-                    gen: None,
-                    mode: LocationConfigMode::Secondary,
-                });
-
-                // We must not update observed, because we have no guarantee that our
-                // response will be received by the pageserver. This could leave it
-                // falsely dirty, but the resulting reconcile should be idempotent.
             }
-        }
-
-        // We consider a node Active once we have composed a re-attach response, but we
-        // do not call [`Self::node_activate_reconcile`]: the handling of the re-attach response
-        // implicitly synchronizes the LocationConfigs on the node.
-        //
-        // Setting a node active unblocks any Reconcilers that might write to the location config API,
-        // but those requests will not be accepted by the node until it has finished processing
-        // the re-attach response.
-        //
-        // Additionally, reset the nodes scheduling policy to match the conditional update done
-        // in [`Persistence::re_attach`].
-        if let Some(node) = nodes.get(&reattach_req.node_id) {
-            let reset_scheduling = matches!(
-                node.get_scheduling(),
-                NodeSchedulingPolicy::PauseForRestart
-                    | NodeSchedulingPolicy::Draining
-                    | NodeSchedulingPolicy::Filling
-            );
 
-            if !node.is_available() || reset_scheduling {
-                let mut new_nodes = (**nodes).clone();
-                if let Some(node) = new_nodes.get_mut(&reattach_req.node_id) {
-                    if !node.is_available() {
-                        node.set_availability(NodeAvailability::Active(UtilizationScore::worst()));
-                    }
+            // Populate each tenant's intent state
+            let mut schedule_context = ScheduleContext::default();
+            for (tenant_shard_id, tenant_shard) in tenants.iter_mut() {
+                if tenant_shard_id.shard_number == ShardNumber(0) {
+                    // Reset scheduling context each time we advance to the next Tenant
+                    schedule_context = ScheduleContext::default();
+                }
 
-                    if reset_scheduling {
-                        node.set_scheduling(NodeSchedulingPolicy::Active);
+                self.check_generation_authority(tenant_shard);
+                tenant_shard.intent_from_observed(scheduler);
+                if let Err(e) = tenant_shard.schedule(scheduler, &mut schedule_context) {
+                    // Non-fatal error: we are unable to properly schedule the tenant, perhaps because
+                    // not enough pageservers are available.  The tenant may well still be available
+                    // to clients.
+                    tracing::error!("Failed to schedule tenant {tenant_shard_id} at startup: {e}");
+                } else {
+                    // If we're both intending and observed to be attached at a particular node, we will
+                    // emit a compute notification for this. In the case where our observed state does not
+                    // yet match our intent, we will eventually reconcile, and that will emit a compute notification.
+                    if let Some(attached_at) = tenant_shard.stably_attached() {
+                        compute_notifications.push((
+                            *tenant_shard_id,
+                            attached_at,
+                            tenant_shard.shard.stripe_size,
+                        ));
                     }
-
-                    scheduler.node_upsert(node);
-                    let new_nodes = Arc::new(new_nodes);
-                    *nodes = new_nodes;
                 }
             }
-        }
-
-        Ok(response)
-    }
-
-    pub(crate) fn validate(&self, validate_req: ValidateRequest) -> ValidateResponse {
-        let locked = self.inner.read().unwrap();
 
-        let mut response = ValidateResponse {
-            tenants: Vec::new(),
+            tenants.len()
         };
 
-        for req_tenant in validate_req.tenants {
-            if let Some(tenant_shard) = locked.tenants.get(&req_tenant.id) {
-                let valid = tenant_shard.generation == Some(Generation::new(req_tenant.gen));
-                tracing::info!(
-                    "handle_validate: {}(gen {}): valid={valid} (latest {:?})",
-                    req_tenant.id,
-                    req_tenant.gen,
-                    tenant_shard.generation
-                );
-                response.tenants.push(ValidateResponseTenant {
-                    id: req_tenant.id,
-                    valid,
-                });
-            } else {
-                // After tenant deletion, we may approve any validation.  This avoids
-                // spurious warnings on the pageserver if it has pending LSN updates
-                // at the point a deletion happens.
-                response.tenants.push(ValidateResponseTenant {
-                    id: req_tenant.id,
-                    valid: true,
-                });
-            }
-        }
-        response
-    }
+        // TODO: if any tenant's intent now differs from its loaded generation_pageserver, we should clear that
+        // generation_pageserver in the database.
 
-    pub(crate) async fn tenant_create(
-        &self,
-        create_req: TenantCreateRequest,
-    ) -> Result<TenantCreateResponse, ApiError> {
-        let tenant_id = create_req.new_tenant_id.tenant_id;
+        // Emit compute hook notifications for all tenants which are already stably attached.  Other tenants
+        // will emit compute hook notifications when they reconcile.
+        //
+        // Ordering: our calls to notify_background synchronously establish a relative order for these notifications vs. any later
+        // calls into the ComputeHook for the same tenant: we can leave these to run to completion in the background and any later
+        // calls will be correctly ordered wrt these.
+        //
+        // Concurrency: we call notify_background for all tenants, which will create O(N) tokio tasks, but almost all of them
+        // will just wait on the ComputeHook::API_CONCURRENCY semaphore immediately, so very cheap until they get that semaphore
+        // unit and start doing I/O.
+        tracing::info!(
+            "Sending {} compute notifications",
+            compute_notifications.len()
+        );
+        self.compute_hook.notify_background(
+            compute_notifications,
+            bg_compute_notify_result_tx.clone(),
+            &self.cancel,
+        );
 
-        // Exclude any concurrent attempts to create/access the same tenant ID
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            create_req.new_tenant_id.tenant_id,
-            TenantOperations::Create,
-        )
-        .await;
-        let (response, waiters) = self.do_tenant_create(create_req).await?;
+        // Finally, now that the service is up and running, launch reconcile operations for any tenants
+        // which require it: under normal circumstances this should only include tenants that were in some
+        // transient state before we restarted, or any tenants whose compute hooks failed above.
+        tracing::info!("Checking for shards in need of reconciliation...");
+        let reconcile_tasks = self.reconcile_all();
+        // We will not wait for these reconciliation tasks to run here: we're now done with startup and
+        // normal operations may proceed.
 
-        if let Err(e) = self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
-            // Avoid deadlock: reconcile may fail while notifying compute, if the cloud control plane refuses to
-            // accept compute notifications while it is in the process of creating.  Reconciliation will
-            // be retried in the background.
-            tracing::warn!(%tenant_id, "Reconcile not done yet while creating tenant ({e})");
+        // Clean up any tenants that were found on pageservers but are not known to us.  Do this in the
+        // background because it does not need to complete in order to proceed with other work.
+        if !cleanup.is_empty() {
+            tracing::info!("Cleaning up {} locations in the background", cleanup.len());
+            tokio::task::spawn({
+                let cleanup_self = self.clone();
+                async move { cleanup_self.cleanup_locations(cleanup).await }
+            });
         }
-        Ok(response)
-    }
 
-    pub(crate) async fn do_tenant_create(
-        &self,
-        create_req: TenantCreateRequest,
-    ) -> Result<(TenantCreateResponse, Vec<ReconcilerWaiter>), ApiError> {
-        let placement_policy = create_req
-            .placement_policy
-            .clone()
-            // As a default, zero secondaries is convenient for tests that don't choose a policy.
-            .unwrap_or(PlacementPolicy::Attached(0));
+        tracing::info!("Startup complete, spawned {reconcile_tasks} reconciliation tasks ({shard_count} shards total)");
+        if start_at.elapsed() > self.config.startup_reconcile_timeout {
+            tracing::warn!(
+                "Startup took {:?}, longer than the startup reconcile timeout {:?}",
+                start_at.elapsed(),
+                self.config.startup_reconcile_timeout
+            );
+        }
+    }
 
-        // This service expects to handle sharding itself: it is an error to try and directly create
-        // a particular shard here.
-        let tenant_id = if !create_req.new_tenant_id.is_unsharded() {
-            return Err(ApiError::BadRequest(anyhow::anyhow!(
-                "Attempted to create a specific shard, this API is for creating the whole tenant"
-            )));
-        } else {
-            create_req.new_tenant_id.tenant_id
+    /// Scan all pageservers' locations again and rebuild shards' observed state from what they report,
+    /// then reconcile shards that differ from their intent: this is the node scan and reconcile part of
+    /// [`Self::startup_reconcile`], for refreshing our view after pageservers were changed externally.
+    ///
+    /// Unlike at startup, intents are left alone: they are already authoritative, and it is only our
+    /// picture of the pageservers that may be stale.  Shards that reconciled or started splitting
+    /// while we were scanning are skipped, as what we scanned may predate their latest changes.
+    pub(crate) async fn rescan_all_nodes(self: &Arc<Self>) -> Result<NodeRescanResponse, ApiError> {
+        let Ok(_rescan_guard) = self.rescan_lock.try_lock() else {
+            return Err(ApiError::Conflict(
+                "A rescan is already in progress".to_string(),
+            ));
         };
 
-        tracing::info!(
-            "Creating tenant {}, shard_count={:?}",
-            create_req.new_tenant_id,
-            create_req.shard_parameters.count,
-        );
-
-        let create_ids = (0..create_req.shard_parameters.count.count())
-            .map(|i| TenantShardId {
-                tenant_id,
-                shard_number: ShardNumber(i),
-                shard_count: create_req.shard_parameters.count,
-            })
-            .collect::<Vec<_>>();
+        if !self.startup_complete.is_ready() {
+            return Err(ApiError::ResourceUnavailable(
+                "Startup reconciliation has not completed".into(),
+            ));
+        }
 
-        // If the caller specifies a None generation, it means "start from default".  This is different
-        // to [`Self::tenant_location_config`], where a None generation is used to represent
-        // an incompletely-onboarded tenant.
-        let initial_generation = if matches!(placement_policy, PlacementPolicy::Secondary) {
-            tracing::info!(
-                "tenant_create: secondary mode, generation is_some={}",
-                create_req.generation.is_some()
-            );
-            create_req.generation.map(Generation::new)
-        } else {
-            tracing::info!(
-                "tenant_create: not secondary mode, generation is_some={}",
-                create_req.generation.is_some()
-            );
-            Some(
-                create_req
-                    .generation
-                    .map(Generation::new)
-                    .unwrap_or(INITIAL_GENERATION),
-            )
+        let applied_before = {
+            let locked = self.inner.read().unwrap();
+            locked
+                .tenants
+                .iter()
+                .map(|(id, shard)| (*id, shard.applied_sequence))
+                .collect::<HashMap<_, _>>()
         };
 
-        // Ordering: we persist tenant shards before creating them on the pageserver.  This enables a caller
-        // to clean up after themselves by issuing a tenant deletion if something goes wrong and we restart
-        // during the creation, rather than risking leaving orphan objects in S3.
-        let persist_tenant_shards = create_ids
-            .iter()
-            .map(|tenant_shard_id| TenantShardPersistence {
-                tenant_id: tenant_shard_id.tenant_id.to_string(),
-                shard_number: tenant_shard_id.shard_number.0 as i32,
-                shard_count: tenant_shard_id.shard_count.literal() as i32,
-                shard_stripe_size: create_req.shard_parameters.stripe_size.0 as i32,
-                generation: initial_generation.map(|g| g.into().unwrap() as i32),
-                // The pageserver is not known until scheduling happens: we will set this column when
-                // incrementing the generation the first time we attach to a pageserver.
-                generation_pageserver: None,
-                placement_policy: serde_json::to_string(&placement_policy).unwrap(),
-                config: serde_json::to_string(&create_req.config).unwrap(),
-                splitting: SplitState::default(),
-                scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
-                    .unwrap(),
-            })
-            .collect();
+        let deadline = Instant::now()
+            .checked_add(self.config.startup_scan_timeout)
+            .expect("Scan timeout is a modest duration");
+        let node_listings = self.scan_node_locations(deadline).await;
 
-        match self
-            .persistence
-            .insert_tenant_shards(persist_tenant_shards)
-            .await
-        {
-            Ok(_) => {}
-            Err(DatabaseError::Query(diesel::result::Error::DatabaseError(
-                DatabaseErrorKind::UniqueViolation,
-                _,
-            ))) => {
-                // Unique key violation: this is probably a retry.  Because the shard count is part of the unique key,
-                // if we see a unique key violation it means that the creation request's shard count matches the previous
-                // creation's shard count.
-                tracing::info!("Tenant shards already present in database, proceeding with idempotent creation...");
+        let mut reported: HashMap<TenantShardId, HashMap<NodeId, Option<LocationConfig>>> =
+            HashMap::new();
+        for (node_id, list_response) in &node_listings {
+            for (tenant_shard_id, conf_opt) in &list_response.tenant_shards {
+                reported
+                    .entry(*tenant_shard_id)
+                    .or_default()
+                    .insert(*node_id, conf_opt.clone());
             }
-            // Any other database error is unexpected and a bug.
-            Err(e) => return Err(ApiError::InternalServerError(anyhow::anyhow!(e))),
-        };
-
-        let mut schedule_context = ScheduleContext::default();
+        }
 
-        let (waiters, response_shards) = {
+        let mut cleanup = Vec::new();
+        let mut shards_updated = 0;
+        {
             let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
-
-            let mut response_shards = Vec::new();
-            let mut schcedule_error = None;
-
-            for tenant_shard_id in create_ids {
-                tracing::info!("Creating shard {tenant_shard_id}...");
-
-                use std::collections::btree_map::Entry;
-                match tenants.entry(tenant_shard_id) {
-                    Entry::Occupied(mut entry) => {
-                        tracing::info!(
-                            "Tenant shard {tenant_shard_id} already exists while creating"
-                        );
-
-                        // TODO: schedule() should take an anti-affinity expression that pushes
-                        // attached and secondary locations (independently) away frorm those
-                        // pageservers also holding a shard for this tenant.
-
-                        entry
-                            .get_mut()
-                            .schedule(scheduler, &mut schedule_context)
-                            .map_err(|e| {
-                                ApiError::Conflict(format!(
-                                    "Failed to schedule shard {tenant_shard_id}: {e}"
-                                ))
-                            })?;
+            let (nodes, tenants, _scheduler) = locked.parts_mut();
 
-                        if let Some(node_id) = entry.get().intent.get_attached() {
-                            let generation = entry
-                                .get()
-                                .generation
-                                .expect("Generation is set when in attached mode");
-                            response_shards.push(TenantCreateResponseShard {
-                                shard_id: tenant_shard_id,
-                                node_id: *node_id,
-                                generation: generation.into().unwrap(),
-                            });
-                        }
+            for (tenant_shard_id, shard) in tenants.iter_mut() {
+                if shard.reconciler.is_some()
+                    || !matches!(shard.splitting, SplitState::Idle)
+                    || applied_before.get(tenant_shard_id) != Some(&shard.applied_sequence)
+                {
+                    continue;
+                }
 
+                let mut shard_reported = reported.remove(tenant_shard_id).unwrap_or_default();
+                for node_id in node_listings.keys() {
+                    if !nodes.contains_key(node_id) {
+                        // Node was removed while we were scanning
                         continue;
                     }
-                    Entry::Vacant(entry) => {
-                        let state = entry.insert(TenantShard::new(
-                            tenant_shard_id,
-                            ShardIdentity::from_params(
-                                tenant_shard_id.shard_number,
-                                &create_req.shard_parameters,
-                            ),
-                            placement_policy.clone(),
-                        ));
 
-                        state.generation = initial_generation;
-                        state.config = create_req.config.clone();
-                        if let Err(e) = state.schedule(scheduler, &mut schedule_context) {
-                            schcedule_error = Some(e);
+                    // A node that did not list this shard does not have a location for it
+                    match shard_reported.remove(node_id) {
+                        Some(conf) => {
+                            shard
+                                .observed
+                                .locations
+                                .insert(*node_id, ObservedStateLocation { conf });
                         }
-
-                        // Only include shards in result if we are attaching: the purpose
-                        // of the response is to tell the caller where the shards are attached.
-                        if let Some(node_id) = state.intent.get_attached() {
-                            let generation = state
-                                .generation
-                                .expect("Generation is set when in attached mode");
-                            response_shards.push(TenantCreateResponseShard {
-                                shard_id: tenant_shard_id,
-                                node_id: *node_id,
-                                generation: generation.into().unwrap(),
-                            });
+                        None => {
+                            shard.observed.locations.remove(node_id);
                         }
                     }
-                };
+                }
+                shards_updated += 1;
             }
 
-            // If we failed to schedule shards, then they are still created in the controller,
-            // but we return an error to the requester to avoid a silent failure when someone
-            // tries to e.g. create a tenant whose placement policy requires more nodes than
-            // are present in the system.  We do this here rather than in the above loop, to
-            // avoid situations where we only create a subset of shards in the tenant.
-            if let Some(e) = schcedule_error {
-                return Err(ApiError::Conflict(format!(
-                    "Failed to schedule shard(s): {e}"
-                )));
+            // Locations for shards we don't know about are garbage, unless their tenant is splitting: the
+            // children of a split exist on pageservers before we learn about them.
+            for (tenant_shard_id, node_reports) in reported {
+                if tenants.contains_key(&tenant_shard_id) {
+                    continue;
+                }
+                let splitting = tenants
+                    .range(TenantShardId::tenant_range(tenant_shard_id.tenant_id))
+                    .any(|(_id, shard)| !matches!(shard.splitting, SplitState::Idle));
+                if splitting {
+                    continue;
+                }
+                for node_id in node_reports.into_keys() {
+                    cleanup.push((tenant_shard_id, node_id));
+                }
             }
+        }
 
-            let waiters = tenants
-                .range_mut(TenantShardId::tenant_range(tenant_id))
-                .filter_map(|(_shard_id, shard)| self.maybe_reconcile_shard(shard, nodes))
-                .collect::<Vec<_>>();
-            (waiters, response_shards)
-        };
-
-        Ok((
-            TenantCreateResponse {
-                shards: response_shards,
-            },
-            waiters,
-        ))
-    }
+        let reconciles_spawned = self.reconcile_all();
 
-    /// Helper for functions that reconcile a number of shards, and would like to do a timeout-bounded
-    /// wait for reconciliation to complete before responding.
-    async fn await_waiters(
-        &self,
-        waiters: Vec<ReconcilerWaiter>,
-        timeout: Duration,
-    ) -> Result<(), ReconcileWaitError> {
-        let deadline = Instant::now().checked_add(timeout).unwrap();
-        for waiter in waiters {
-            let timeout = deadline.duration_since(Instant::now());
-            waiter.wait_timeout(timeout).await?;
+        let cleanup_locations = cleanup.len();
+        if !cleanup.is_empty() {
+            tracing::info!("Cleaning up {cleanup_locations} locations in the background");
+            tokio::task::spawn({
+                let cleanup_self = self.clone();
+                async move { cleanup_self.cleanup_locations(cleanup).await }
+            });
         }
 
-        Ok(())
+        tracing::info!("Rescan complete: scanned {} nodes, updated {shards_updated} shards, spawned {reconciles_spawned} reconciliation tasks", node_listings.len());
+
+        Ok(NodeRescanResponse {
+            nodes_scanned: node_listings.len(),
+            shards_updated,
+            reconciles_spawned,
+            cleanup_locations,
+        })
     }
 
-    /// Same as [`Service::await_waiters`], but returns the waiters which are still
-    /// in progress
-    async fn await_waiters_remainder(
-        &self,
-        waiters: Vec<ReconcilerWaiter>,
-        timeout: Duration,
-    ) -> Vec<ReconcilerWaiter> {
-        let deadline = Instant::now().checked_add(timeout).unwrap();
-        for waiter in waiters.iter() {
-            let timeout = deadline.duration_since(Instant::now());
-            let _ = waiter.wait_timeout(timeout).await;
+    /// Scan all pageservers' locations and compare them with shards' observed state, reporting the
+    /// locations where they differ, e.g. because a reconcile result was lost.  This is a read-only
+    /// audit: unlike [`Self::rescan_all_nodes`], nothing is updated or reconciled.
+    ///
+    /// Shards that reconciled or were splitting while we scanned are skipped, as what we scanned
+    /// may predate their latest changes.
+    pub(crate) async fn detect_drift(&self) -> Result<LocationDriftResponse, ApiError> {
+        if !self.startup_complete.is_ready() {
+            return Err(ApiError::ResourceUnavailable(
+                "Startup reconciliation has not completed".into(),
+            ));
         }
 
-        waiters
-            .into_iter()
-            .filter(|waiter| matches!(waiter.get_status(), ReconcilerStatus::InProgress))
-            .collect::<Vec<_>>()
-    }
+        let applied_before = {
+            let locked = self.inner.read().unwrap();
+            locked
+                .tenants
+                .iter()
+                .map(|(id, shard)| (*id, shard.applied_sequence))
+                .collect::<HashMap<_, _>>()
+        };
 
-    /// Part of [`Self::tenant_location_config`]: dissect an incoming location config request,
-    /// and transform it into either a tenant creation of a series of shard updates.
-    ///
-    /// If the incoming request makes no changes, a [`TenantCreateOrUpdate::Update`] result will
-    /// still be returned.
-    fn tenant_location_config_prepare(
-        &self,
-        tenant_id: TenantId,
-        req: TenantLocationConfigRequest,
-    ) -> TenantCreateOrUpdate {
-        let mut updates = Vec::new();
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, _scheduler) = locked.parts_mut();
-        let tenant_shard_id = TenantShardId::unsharded(tenant_id);
+        let deadline = Instant::now()
+            .checked_add(self.config.startup_scan_timeout)
+            .expect("Scan timeout is a modest duration");
+        let node_listings = self.scan_node_locations(deadline).await;
 
-        // Use location config mode as an indicator of policy.
-        let placement_policy = match req.config.mode {
-            LocationConfigMode::Detached => PlacementPolicy::Detached,
-            LocationConfigMode::Secondary => PlacementPolicy::Secondary,
-            LocationConfigMode::AttachedMulti
-            | LocationConfigMode::AttachedSingle
-            | LocationConfigMode::AttachedStale => {
-                if nodes.len() > 1 {
-                    PlacementPolicy::Attached(1)
-                } else {
-                    // Convenience for dev/test: if we just have one pageserver, import
-                    // tenants into non-HA mode so that scheduling will succeed.
-                    PlacementPolicy::Attached(0)
-                }
+        let mut reported: HashMap<TenantShardId, HashMap<NodeId, Option<LocationConfig>>> =
+            HashMap::new();
+        for (node_id, list_response) in &node_listings {
+            for (tenant_shard_id, conf_opt) in &list_response.tenant_shards {
+                reported
+                    .entry(*tenant_shard_id)
+                    .or_default()
+                    .insert(*node_id, conf_opt.clone());
             }
-        };
+        }
 
-        let mut create = true;
-        for (shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
-            // Saw an existing shard: this is not a creation
-            create = false;
+        let mut drift = Vec::new();
+        let mut shards_skipped = 0;
+        {
+            let locked = self.inner.read().unwrap();
 
-            // Shards may have initially been created by a Secondary request, where we
-            // would have left generation as None.
-            //
-            // We only update generation the first time we see an attached-mode request,
-            // and if there is no existing generation set. The caller is responsible for
-            // ensuring that no non-storage-controller pageserver ever uses a higher
-            // generation than they passed in here.
-            use LocationConfigMode::*;
-            let set_generation = match req.config.mode {
-                AttachedMulti | AttachedSingle | AttachedStale if shard.generation.is_none() => {
-                    req.config.generation.map(Generation::new)
+            for (tenant_shard_id, shard) in locked.tenants.iter() {
+                let shard_reported = reported.remove(tenant_shard_id).unwrap_or_default();
+                if shard.reconciler.is_some()
+                    || !matches!(shard.splitting, SplitState::Idle)
+                    || applied_before.get(tenant_shard_id) != Some(&shard.applied_sequence)
+                {
+                    shards_skipped += 1;
+                    continue;
                 }
-                _ => None,
-            };
 
-            updates.push(ShardUpdate {
-                tenant_shard_id: *shard_id,
-                placement_policy: placement_policy.clone(),
-                tenant_config: req.config.tenant_conf.clone(),
-                generation: set_generation,
-            });
-        }
+                for node_id in node_listings.keys() {
+                    if !locked.nodes.contains_key(node_id) {
+                        // Node was removed while we were scanning
+                        continue;
+                    }
 
-        if create {
-            use LocationConfigMode::*;
-            let generation = match req.config.mode {
-                AttachedMulti | AttachedSingle | AttachedStale => req.config.generation,
-                // If a caller provided a generation in a non-attached request, ignore it
-                // and leave our generation as None: this enables a subsequent update to set
-                // the generation when setting an attached mode for the first time.
-                _ => None,
-            };
+                    let observed = shard.observed.locations.get(node_id);
+                    let node_reported = shard_reported.get(node_id);
+                    if let Some(kind) = location_drift(observed, node_reported) {
+                        drift.push(LocationDrift {
+                            tenant_shard_id: *tenant_shard_id,
+                            node_id: *node_id,
+                            kind,
+                            observed: observed.and_then(|o| o.conf.clone()),
+                            reported: node_reported.cloned().flatten(),
+                        });
+                    }
+                }
+            }
 
-            TenantCreateOrUpdate::Create(
-                // Synthesize a creation request
-                TenantCreateRequest {
-                    new_tenant_id: tenant_shard_id,
-                    generation,
-                    shard_parameters: ShardParameters {
-                        count: tenant_shard_id.shard_count,
-                        // We only import un-sharded or single-sharded tenants, so stripe
-                        // size can be made up arbitrarily here.
-                        stripe_size: ShardParameters::DEFAULT_STRIPE_SIZE,
-                    },
-                    placement_policy: Some(placement_policy),
-                    config: req.config.tenant_conf,
-                },
-            )
-        } else {
-            assert!(!updates.is_empty());
-            TenantCreateOrUpdate::Update(updates)
+            // Locations for shards we don't know about, unless their tenant is splitting: the
+            // children of a split exist on pageservers before we learn about them.
+            for (tenant_shard_id, node_reports) in reported {
+                let splitting = locked
+                    .tenants
+                    .range(TenantShardId::tenant_range(tenant_shard_id.tenant_id))
+                    .any(|(_id, shard)| !matches!(shard.splitting, SplitState::Idle));
+                if splitting {
+                    continue;
+                }
+                for (node_id, conf) in node_reports {
+                    if let Some(kind) = location_drift(None, Some(&conf)) {
+                        drift.push(LocationDrift {
+                            tenant_shard_id,
+                            node_id,
+                            kind,
+                            observed: None,
+                            reported: conf,
+                        });
+                    }
+                }
+            }
         }
+
+        drift.sort_by_key(|d| (d.tenant_shard_id, d.node_id));
+        tracing::info!(
+            "Drift check complete: scanned {} nodes, found {} drifted locations, \
+             skipped {shards_skipped} shards",
+            node_listings.len(),
+            drift.len()
+        );
+
+        Ok(LocationDriftResponse {
+            nodes_scanned: node_listings.len(),
+            shards_skipped,
+            drift,
+        })
     }
 
-    /// This API is used by the cloud control plane to migrate unsharded tenants that it created
-    /// directly with pageservers into this service.
-    ///
-    /// Cloud control plane MUST NOT continue issuing GENERATION NUMBERS for this tenant once it
-    /// has attempted to call this API. Failure to oblige to this rule may lead to S3 corruption.
-    /// Think of the first attempt to call this API as a transfer of absolute authority over the
-    /// tenant's source of generation numbers.
-    ///
-    /// The mode in this request coarse-grained control of tenants:
-    /// - Call with mode Attached* to upsert the tenant.
-    /// - Call with mode Secondary to either onboard a tenant without attaching it, or
-    ///   to set an existing tenant to PolicyMode::Secondary
-    /// - Call with mode Detached to switch to PolicyMode::Detached
-    pub(crate) async fn tenant_location_config(
+    async fn initial_heartbeat_round<'a>(
         &self,
-        tenant_shard_id: TenantShardId,
-        req: TenantLocationConfigRequest,
-    ) -> Result<TenantLocationConfigResponse, ApiError> {
-        // We require an exclusive lock, because we are updating both persistent and in-memory state
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            tenant_shard_id.tenant_id,
-            TenantOperations::LocationConfig,
-        )
-        .await;
+        node_ids: impl Iterator<Item = &'a NodeId>,
+    ) -> HashMap<NodeId, PageserverUtilization> {
+        assert!(!self.startup_complete.is_ready());
 
-        if !tenant_shard_id.is_unsharded() {
-            return Err(ApiError::BadRequest(anyhow::anyhow!(
-                "This API is for importing single-sharded or unsharded tenants"
-            )));
+        let all_nodes = {
+            let locked = self.inner.read().unwrap();
+            locked.nodes.clone()
+        };
+
+        let mut nodes_to_heartbeat = HashMap::new();
+        for node_id in node_ids {
+            match all_nodes.get(node_id) {
+                Some(node) => {
+                    nodes_to_heartbeat.insert(*node_id, node.clone());
+                }
+                None => {
+                    tracing::warn!("Node {node_id} was removed during start-up");
+                }
+            }
         }
 
-        // First check if this is a creation or an update
-        let create_or_update = self.tenant_location_config_prepare(tenant_shard_id.tenant_id, req);
+        tracing::info!("Sending initial heartbeats...");
+        let res = self
+            .heartbeater
+            .heartbeat(Arc::new(nodes_to_heartbeat))
+            .await;
 
-        let mut result = TenantLocationConfigResponse {
-            shards: Vec::new(),
-            stripe_size: None,
-        };
-        let waiters = match create_or_update {
-            TenantCreateOrUpdate::Create(create_req) => {
-                let (create_resp, waiters) = self.do_tenant_create(create_req).await?;
-                result.shards = create_resp
-                    .shards
-                    .into_iter()
-                    .map(|s| TenantShardLocation {
-                        node_id: s.node_id,
-                        shard_id: s.shard_id,
-                    })
-                    .collect();
-                waiters
-            }
-            TenantCreateOrUpdate::Update(updates) => {
-                // Persist updates
-                // Ordering: write to the database before applying changes in-memory, so that
-                // we will not appear time-travel backwards on a restart.
-                let mut schedule_context = ScheduleContext::default();
-                for ShardUpdate {
-                    tenant_shard_id,
-                    placement_policy,
-                    tenant_config,
-                    generation,
-                } in &updates
-                {
-                    self.persistence
-                        .update_tenant_shard(
-                            TenantFilter::Shard(*tenant_shard_id),
-                            Some(placement_policy.clone()),
-                            Some(tenant_config.clone()),
-                            *generation,
-                            None,
-                        )
-                        .await?;
+        let mut online_nodes = HashMap::new();
+        if let Ok(deltas) = res {
+            for (node_id, status) in deltas.0 {
+                match status {
+                    PageserverState::Available { utilization, .. } => {
+                        online_nodes.insert(node_id, utilization);
+                    }
+                    PageserverState::Offline => {}
                 }
+            }
+        }
 
-                // Apply updates in-memory
-                let mut waiters = Vec::new();
-                {
-                    let mut locked = self.inner.write().unwrap();
-                    let (nodes, tenants, scheduler) = locked.parts_mut();
+        online_nodes
+    }
 
-                    for ShardUpdate {
-                        tenant_shard_id,
-                        placement_policy,
-                        tenant_config,
-                        generation: update_generation,
-                    } in updates
-                    {
-                        let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
-                            tracing::warn!("Shard {tenant_shard_id} removed while updating");
-                            continue;
-                        };
+    /// Used during [`Self::startup_reconcile`]: issue GETs to all nodes concurrently, with a deadline.
+    ///
+    /// The result includes only nodes which responded within the deadline
+    async fn scan_node_locations(
+        &self,
+        deadline: Instant,
+    ) -> HashMap<NodeId, LocationConfigListResponse> {
+        let nodes = {
+            let locked = self.inner.read().unwrap();
+            locked.nodes.clone()
+        };
 
-                        // Update stripe size
-                        if result.stripe_size.is_none() && shard.shard.count.count() > 1 {
-                            result.stripe_size = Some(shard.shard.stripe_size);
-                        }
+        let mut node_results = HashMap::new();
 
-                        shard.policy = placement_policy;
-                        shard.config = tenant_config;
-                        if let Some(generation) = update_generation {
-                            shard.generation = Some(generation);
-                        }
+        let mut node_list_futs = FuturesUnordered::new();
 
-                        shard.schedule(scheduler, &mut schedule_context)?;
+        let timeout = node_scan_request_timeout(nodes.len(), self.config.startup_scan_timeout);
+        tracing::info!(
+            "Scanning shards on {} nodes, with request timeout {timeout:?}...",
+            nodes.len()
+        );
+        for node in nodes.values() {
+            node_list_futs.push({
+                async move {
+                    tracing::info!("Scanning shards on node {node}...");
+                    let response = node
+                        .with_client_retries(
+                            |client| async move { client.list_location_config().await },
+                            &self.config.jwt_token,
+                            1,
+                            5,
+                            timeout,
+                            &self.cancel,
+                        )
+                        .await;
+                    (node.get_id(), response)
+                }
+            });
+        }
 
-                        let maybe_waiter = self.maybe_reconcile_shard(shard, nodes);
-                        if let Some(waiter) = maybe_waiter {
-                            waiters.push(waiter);
+        loop {
+            let (node_id, result) = tokio::select! {
+                next = node_list_futs.next() => {
+                    match next {
+                        Some(result) => result,
+                        None =>{
+                            // We got results for all our nodes
+                            break;
                         }
 
-                        if let Some(node_id) = shard.intent.get_attached() {
-                            result.shards.push(TenantShardLocation {
-                                shard_id: tenant_shard_id,
-                                node_id: *node_id,
-                            })
-                        }
                     }
+                },
+                _ = tokio::time::sleep(deadline.duration_since(Instant::now())) => {
+                    // Give up waiting for anyone who hasn't responded: we will yield the results that we have
+                    tracing::info!("Reached deadline while waiting for nodes to respond to location listing requests");
+                    break;
                 }
-                waiters
-            }
-        };
+            };
 
-        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
-            // Do not treat a reconcile error as fatal: we have already applied any requested
-            // Intent changes, and the reconcile can fail for external reasons like unavailable
-            // compute notification API.  In these cases, it is important that we do not
-            // cause the cloud control plane to retry forever on this API.
-            tracing::warn!(
-                "Failed to reconcile after /location_config: {e}, returning success anyway"
-            );
-        }
+            let Some(list_response) = result else {
+                tracing::info!("Shutdown during startup_reconcile");
+                break;
+            };
 
-        // Logging the full result is useful because it lets us cross-check what the cloud control
-        // plane's tenant_shards table should contain.
-        tracing::info!("Complete, returning {result:?}");
+            match list_response {
+                Err(e) => {
+                    tracing::warn!("Could not scan node {} ({e})", node_id);
+                }
+                Ok(listing) => {
+                    node_results.insert(node_id, listing);
+                }
+            }
+        }
 
-        Ok(result)
+        node_results
     }
 
-    pub(crate) async fn tenant_config_set(&self, req: TenantConfigRequest) -> Result<(), ApiError> {
-        // We require an exclusive lock, because we are updating persistent and in-memory state
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            req.tenant_id,
-            TenantOperations::ConfigSet,
-        )
-        .await;
+    /// Used during [`Self::startup_reconcile`]: detach a list of unknown-to-us tenants from pageservers.
+    ///
+    /// This is safe to run in the background, because if we don't have this TenantShardId in our map of
+    /// tenants, then it is probably something incompletely deleted before: we will not fight with any
+    /// other task trying to attach it.
+    #[instrument(skip_all)]
+    async fn cleanup_locations(&self, cleanup: Vec<(TenantShardId, NodeId)>) {
+        let nodes = self.inner.read().unwrap().nodes.clone();
 
-        let tenant_id = req.tenant_id;
-        let config = req.config;
+        for (tenant_shard_id, node_id) in cleanup {
+            // A node reported a tenant_shard_id which is unknown to us: detach it.
+            let Some(node) = nodes.get(&node_id) else {
+                // This is legitimate; we run in the background and [`Self::startup_reconcile`] might have identified
+                // a location to clean up on a node that has since been removed.
+                tracing::info!(
+                    "Not cleaning up location {node_id}/{tenant_shard_id}: node not found"
+                );
+                continue;
+            };
 
-        self.persistence
-            .update_tenant_shard(
-                TenantFilter::Tenant(req.tenant_id),
-                None,
-                Some(config.clone()),
-                None,
-                None,
-            )
-            .await?;
+            if self.cancel.is_cancelled() {
+                break;
+            }
 
-        let waiters = {
-            let mut waiters = Vec::new();
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, _scheduler) = locked.parts_mut();
-            for (_shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
-                shard.config = config.clone();
-                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
-                    waiters.push(waiter);
+            let client = PageserverClient::new(
+                node.get_id(),
+                node.base_url(),
+                self.config.jwt_token.as_deref(),
+            );
+            match client
+                .location_config(
+                    tenant_shard_id,
+                    LocationConfig {
+                        mode: LocationConfigMode::Detached,
+                        generation: None,
+                        secondary_conf: None,
+                        shard_number: tenant_shard_id.shard_number.0,
+                        shard_count: tenant_shard_id.shard_count.literal(),
+                        shard_stripe_size: 0,
+                        tenant_conf: models::TenantConfig::default(),
+                    },
+                    None,
+                    false,
+                )
+                .await
+            {
+                Ok(()) => {
+                    tracing::info!(
+                        "Detached unknown shard {tenant_shard_id} on pageserver {node_id}"
+                    );
+                }
+                Err(e) => {
+                    // Non-fatal error: leaving a tenant shard behind that we are not managing shouldn't
+                    // break anything.
+                    tracing::error!(
+                        "Failed to detach unknkown shard {tenant_shard_id} on pageserver {node_id}: {e}"
+                    );
                 }
             }
-            waiters
-        };
-
-        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
-            // Treat this as success because we have stored the configuration.  If e.g.
-            // a node was unavailable at this time, it should not stop us accepting a
-            // configuration change.
-            tracing::warn!(%tenant_id, "Accepted configuration update but reconciliation failed: {e}");
         }
-
-        Ok(())
     }
 
-    pub(crate) fn tenant_config_get(
-        &self,
-        tenant_id: TenantId,
-    ) -> Result<HashMap<&str, serde_json::Value>, ApiError> {
-        let config = {
-            let locked = self.inner.read().unwrap();
+    /// Long running background task that periodically wakes up and looks for shards that need
+    /// reconciliation.  Reconciliation is fallible, so any reconciliation tasks that fail during
+    /// e.g. a tenant create/attach/migrate must eventually be retried: this task is responsible
+    /// for those retries.
+    #[instrument(skip_all)]
+    async fn background_reconcile(self: &Arc<Self>) {
+        self.startup_complete.clone().wait().await;
 
-            match locked
-                .tenants
-                .range(TenantShardId::tenant_range(tenant_id))
-                .next()
-            {
-                Some((_tenant_shard_id, shard)) => shard.config.clone(),
-                None => {
-                    return Err(ApiError::NotFound(
-                        anyhow::anyhow!("Tenant not found").into(),
-                    ))
+        const BACKGROUND_RECONCILE_PERIOD: Duration = Duration::from_secs(20);
+
+        // The first pass runs straight away, and later ones start a jittered period after the
+        // previous one started, as they would with a [`tokio::time::Interval`].
+        let mut next = tokio::time::Instant::now();
+        while !self.cancel.is_cancelled() {
+            tokio::select! {
+              _ = tokio::time::sleep_until(next) => {
+                next = tokio::time::Instant::now()
+                    + period_jitter(BACKGROUND_RECONCILE_PERIOD, self.config.background_jitter_pct);
+                self.liveness.beat(Processor::BackgroundReconcile);
+
+                // Refresh the throughput metric even if no reconciles are completing
+                self.inner.read().unwrap().reconcile_throughput.publish(Instant::now());
+                self.prune_observed_all();
+                let reconciles_spawned = self.reconcile_all();
+                if reconciles_spawned == 0 {
+                    // Run optimizer only when we didn't find any other work to do
+                    let optimizations = self.optimize_all().await;
+                    if optimizations == 0 {
+                        // Run new splits only when no optimizations are pending
+                        self.autosplit_tenants().await;
+                    }
                 }
             }
-        };
+              _ = self.cancel.cancelled() => return
+            }
+        }
+    }
+    #[instrument(skip_all)]
+    async fn spawn_heartbeat_driver(&self) {
+        self.startup_complete.clone().wait().await;
 
-        // Unlike the pageserver, we do not have a set of global defaults: the config is
-        // entirely per-tenant.  Therefore the distinction between `tenant_specific_overrides`
-        // and `effective_config` in the response is meaningless, but we retain that syntax
-        // in order to remain compatible with the pageserver API.
+        const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 
-        let response = HashMap::from([
-            (
-                "tenant_specific_overrides",
-                serde_json::to_value(&config)
-                    .context("serializing tenant specific overrides")
-                    .map_err(ApiError::InternalServerError)?,
-            ),
-            (
-                "effective_config",
-                serde_json::to_value(&config)
-                    .context("serializing effective config")
-                    .map_err(ApiError::InternalServerError)?,
-            ),
-        ]);
+        // When each node was first seen in an orphaned Draining or Filling state, for
+        // [`Self::reset_orphaned_node_operations`]
+        let mut orphaned_since = HashMap::new();
 
-        Ok(response)
-    }
+        // Jittered as in [`Self::background_reconcile`]
+        let mut next = tokio::time::Instant::now();
+        while !self.cancel.is_cancelled() {
+            tokio::select! {
+              _ = tokio::time::sleep_until(next) => { }
+              _ = self.cancel.cancelled() => return
+            };
+            next = tokio::time::Instant::now()
+                + period_jitter(HEARTBEAT_INTERVAL, self.config.background_jitter_pct);
+            self.liveness.beat(Processor::Heartbeats);
 
-    pub(crate) async fn tenant_time_travel_remote_storage(
-        &self,
-        time_travel_req: &TenantTimeTravelRequest,
-        tenant_id: TenantId,
-        timestamp: Cow<'_, str>,
-        done_if_after: Cow<'_, str>,
-    ) -> Result<(), ApiError> {
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::TimeTravelRemoteStorage,
-        )
-        .await;
+            let nodes = {
+                let locked = self.inner.read().unwrap();
+                locked.nodes.clone()
+            };
 
-        let node = {
-            let locked = self.inner.read().unwrap();
-            // Just a sanity check to prevent misuse: the API expects that the tenant is fully
-            // detached everywhere, and nothing writes to S3 storage. Here, we verify that,
-            // but only at the start of the process, so it's really just to prevent operator
-            // mistakes.
-            for (shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id)) {
-                if shard.intent.get_attached().is_some() || !shard.intent.get_secondary().is_empty()
-                {
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "We want tenant to be attached in shard with tenant_shard_id={shard_id}"
-                    )));
-                }
-                let maybe_attached = shard
-                    .observed
-                    .locations
-                    .iter()
-                    .filter_map(|(node_id, observed_location)| {
-                        observed_location
-                            .conf
-                            .as_ref()
-                            .map(|loc| (node_id, observed_location, loc.mode))
-                    })
-                    .find(|(_, _, mode)| *mode != LocationConfigMode::Detached);
-                if let Some((node_id, _observed_location, mode)) = maybe_attached {
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!("We observed attached={mode:?} tenant in node_id={node_id} shard with tenant_shard_id={shard_id}")));
-                }
-            }
-            let scheduler = &locked.scheduler;
-            // Right now we only perform the operation on a single node without parallelization
-            // TODO fan out the operation to multiple nodes for better performance
-            let node_id = scheduler.schedule_shard(&[], &ScheduleContext::default())?;
-            let node = locked
-                .nodes
-                .get(&node_id)
-                .expect("Pageservers may not be deleted while lock is active");
-            node.clone()
-        };
+            let res = self.heartbeater.heartbeat(nodes).await;
+            if let Ok(deltas) = res {
+                for (node_id, state) in deltas.0 {
+                    let (new_node, new_availability, offline_reason) = match state {
+                        PageserverState::Available {
+                            utilization, new, ..
+                        } => (
+                            new,
+                            NodeAvailability::Active(UtilizationScore(
+                                utilization.utilization_score,
+                            )),
+                            None,
+                        ),
+                        PageserverState::Offline { reason } => {
+                            (false, NodeAvailability::Offline, Some(reason))
+                        }
+                    };
 
-        // The shard count is encoded in the remote storage's URL, so we need to handle all historically used shard counts
-        let mut counts = time_travel_req
-            .shard_counts
-            .iter()
-            .copied()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect::<Vec<_>>();
-        counts.sort_unstable();
+                    if new_node {
+                        // When the heartbeats detect a newly added node, we don't wish
+                        // to attempt to reconcile the shards assigned to it. The node
+                        // is likely handling it's re-attach response, so reconciling now
+                        // would be counterproductive.
+                        //
+                        // Instead, update the in-memory state with the details learned about the
+                        // node.
+                        let mut locked = self.inner.write().unwrap();
+                        let (nodes, _tenants, scheduler) = locked.parts_mut();
 
-        for count in counts {
-            let shard_ids = (0..count.count())
-                .map(|i| TenantShardId {
-                    tenant_id,
-                    shard_number: ShardNumber(i),
-                    shard_count: count,
-                })
-                .collect::<Vec<_>>();
-            for tenant_shard_id in shard_ids {
-                let client = PageserverClient::new(
-                    node.get_id(),
-                    node.base_url(),
-                    self.config.jwt_token.as_deref(),
-                );
+                        let mut new_nodes = (**nodes).clone();
 
-                tracing::info!("Doing time travel recovery for shard {tenant_shard_id}",);
+                        if let Some(node) = new_nodes.get_mut(&node_id) {
+                            node.apply_transition(
+                                Some(new_availability),
+                                None,
+                                NodeTransitionSource::Heartbeat,
+                                offline_reason,
+                            );
+                            scheduler.node_upsert(node);
+                        }
 
-                client
-                        .tenant_time_travel_remote_storage(
-                            tenant_shard_id,
-                            &timestamp,
-                            &done_if_after,
-                        )
-                        .await
-                        .map_err(|e| {
-                            ApiError::InternalServerError(anyhow::anyhow!(
-                                "Error doing time travel recovery for shard {tenant_shard_id} on node {}: {e}",
-                                node
-                            ))
-                        })?;
+                        locked.nodes = Arc::new(new_nodes);
+                    } else {
+                        // This is the code path for geniune availability transitions (i.e node
+                        // goes unavailable and/or comes back online).
+                        if let Some(reason) = &offline_reason {
+                            tracing::info!("Node {node_id} marked offline: {reason}");
+                        }
+
+                        let res = self
+                            .node_configure(
+                                node_id,
+                                Some(new_availability),
+                                None,
+                                NodeTransitionSource::Heartbeat,
+                                offline_reason,
+                            )
+                            .await;
+
+                        match res {
+                            Ok(()) => {}
+                            Err(ApiError::NotFound(_)) => {
+                                // This should be rare, but legitimate since the heartbeats are done
+                                // on a snapshot of the nodes.
+                                tracing::info!(
+                                    "Node {} was not found after heartbeat round",
+                                    node_id
+                                );
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    "Failed to update node {} after heartbeat round: {}",
+                                    node_id,
+                                    err
+                                );
+                            }
+                        }
+                    }
+                }
             }
+
+            self.reset_orphaned_node_operations(&mut orphaned_since)
+                .await;
         }
-        Ok(())
     }
 
-    pub(crate) async fn tenant_secondary_download(
-        &self,
-        tenant_id: TenantId,
-        wait: Option<Duration>,
-    ) -> Result<(StatusCode, SecondaryProgress), ApiError> {
-        let _tenant_lock = trace_shared_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::SecondaryDownload,
-        )
-        .await;
-
-        // Acquire lock and yield the collection of shard-node tuples which we will send requests onward to
-        let targets = {
+    /// Reset nodes to Active that were left Draining or Filling by a background operation which
+    /// ended without finalizing, e.g. because its task died.  A node is only reset once it has been
+    /// in that state for [`Config::orphaned_node_operation_timeout`], which `orphaned_since` tracks
+    /// across heartbeat rounds: going offline or starting a new operation restarts the clock.
+    async fn reset_orphaned_node_operations(&self, orphaned_since: &mut HashMap<NodeId, Instant>) {
+        let orphaned = {
             let locked = self.inner.read().unwrap();
-            let mut targets = Vec::new();
-
-            for (tenant_shard_id, shard) in
-                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-            {
-                for node_id in shard.intent.get_secondary() {
-                    let node = locked
-                        .nodes
-                        .get(node_id)
-                        .expect("Pageservers may not be deleted while referenced");
+            orphaned_operation_nodes(&locked.nodes, &locked.ongoing_operations)
+        };
 
-                    targets.push((*tenant_shard_id, node.clone()));
-                }
+        let now = Instant::now();
+        orphaned_since.retain(|node_id, _| orphaned.contains(node_id));
+        for node_id in orphaned {
+            let since = *orphaned_since.entry(node_id).or_insert(now);
+            if now.duration_since(since) < self.config.orphaned_node_operation_timeout {
+                continue;
             }
-            targets
-        };
 
-        // Issue concurrent requests to all shards' locations
-        let mut futs = FuturesUnordered::new();
-        for (tenant_shard_id, node) in targets {
-            let client = PageserverClient::new(
-                node.get_id(),
-                node.base_url(),
-                self.config.jwt_token.as_deref(),
+            tracing::warn!(
+                "Node {node_id} left in a background operation's scheduling policy for {:?} \
+                 without that operation running, resetting it to Active",
+                now.duration_since(since)
             );
-            futs.push(async move {
-                let result = client
-                    .tenant_secondary_download(tenant_shard_id, wait)
-                    .await;
-                (result, node, tenant_shard_id)
-            })
-        }
-
-        // Handle any errors returned by pageservers.  This includes cases like this request racing with
-        // a scheduling operation, such that the tenant shard we're calling doesn't exist on that pageserver any more, as
-        // well as more general cases like 503s, 500s, or timeouts.
-        let mut aggregate_progress = SecondaryProgress::default();
-        let mut aggregate_status: Option<StatusCode> = None;
-        let mut error: Option<mgmt_api::Error> = None;
-        while let Some((result, node, tenant_shard_id)) = futs.next().await {
-            match result {
-                Err(e) => {
-                    // Secondary downloads are always advisory: if something fails, we nevertheless report success, so that whoever
-                    // is calling us will proceed with whatever migration they're doing, albeit with a slightly less warm cache
-                    // than they had hoped for.
-                    tracing::warn!("Secondary download error from pageserver {node}: {e}",);
-                    error = Some(e)
+            match self
+                .node_configure(
+                    node_id,
+                    None,
+                    Some(NodeSchedulingPolicy::Active),
+                    NodeTransitionSource::Controller,
+                    None,
+                )
+                .await
+            {
+                Ok(()) => {
+                    orphaned_since.remove(&node_id);
                 }
-                Ok((status_code, progress)) => {
-                    tracing::info!(%tenant_shard_id, "Shard status={status_code} progress: {progress:?}");
-                    aggregate_progress.layers_downloaded += progress.layers_downloaded;
-                    aggregate_progress.layers_total += progress.layers_total;
-                    aggregate_progress.bytes_downloaded += progress.bytes_downloaded;
-                    aggregate_progress.bytes_total += progress.bytes_total;
-                    aggregate_progress.heatmap_mtime =
-                        std::cmp::max(aggregate_progress.heatmap_mtime, progress.heatmap_mtime);
-                    aggregate_status = match aggregate_status {
-                        None => Some(status_code),
-                        Some(StatusCode::OK) => Some(status_code),
-                        Some(cur) => {
-                            // Other status codes (e.g. 202) -- do not overwrite.
-                            Some(cur)
-                        }
-                    };
+                Err(e) => {
+                    tracing::warn!("Failed to reset scheduling policy of node {node_id}: {e}");
                 }
             }
         }
+    }
 
-        // If any of the shards return 202, indicate our result as 202.
-        match aggregate_status {
-            None => {
-                match error {
-                    Some(e) => {
-                        // No successes, and an error: surface it
-                        Err(ApiError::Conflict(format!("Error from pageserver: {e}")))
-                    }
-                    None => {
-                        // No shards found
-                        Err(ApiError::NotFound(
-                            anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
-                        ))
-                    }
-                }
-            }
-            Some(aggregate_status) => Ok((aggregate_status, aggregate_progress)),
+    /// Apply the contents of a [`ReconcileResult`] to our in-memory state: if the reconciliation
+    /// was successful and intent hasn't changed since the Reconciler was spawned, this will update
+    /// the observed state of the tenant such that subsequent calls to [`TenantShard::get_reconcile_needed`]
+    /// will indicate that reconciliation is not needed.
+    #[instrument(skip_all, fields(
+        tenant_id=%result.tenant_shard_id.tenant_id, shard_id=%result.tenant_shard_id.shard_slug(),
+        sequence=%result.sequence
+    ))]
+    fn process_result(&self, result: ReconcileResult) {
+        let mut locked = self.inner.write().unwrap();
+        locked.record_reconcile_duration(result.duration);
+
+        let now = Instant::now();
+        match &result.result {
+            Ok(()) => locked.reconcile_throughput.record(now, true),
+            // Cancelled or superseded reconciles did not get to finish their work: they don't count
+            Err(
+                ReconcileError::Cancel
+                | ReconcileError::Remote(mgmt_api::Error::Cancelled)
+                | ReconcileError::StaleGeneration { .. },
+            ) => {}
+            Err(_) => locked.reconcile_throughput.record(now, false),
         }
-    }
+        locked.reconcile_throughput.publish(now);
 
-    pub(crate) async fn tenant_delete(&self, tenant_id: TenantId) -> Result<StatusCode, ApiError> {
-        let _tenant_lock =
-            trace_exclusive_lock(&self.tenant_op_locks, tenant_id, TenantOperations::Delete).await;
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
+        let Some(tenant) = tenants.get_mut(&result.tenant_shard_id) else {
+            // A reconciliation result might race with removing a tenant: drop results for
+            // tenants that aren't in our map.
+            return;
+        };
 
-        // Detach all shards
-        let (detach_waiters, shard_ids, node) = {
-            let mut shard_ids = Vec::new();
-            let mut detach_waiters = Vec::new();
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
-            for (tenant_shard_id, shard) in
-                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
-            {
-                shard_ids.push(*tenant_shard_id);
+        let stale = !tenant.accept_reconcile_result(&result);
+        if stale {
+            tracing::info!(
+                tenant_id=%result.tenant_shard_id.tenant_id, shard_id=%result.tenant_shard_id.shard_slug(),
+                "Ignoring result from sequence {}, already applied sequence {}",
+                result.sequence, tenant.applied_sequence
+            );
+        }
 
-                // Update the tenant's intent to remove all attachments
-                shard.policy = PlacementPolicy::Detached;
-                shard
-                    .schedule(scheduler, &mut ScheduleContext::default())
-                    .expect("De-scheduling is infallible");
-                debug_assert!(shard.intent.get_attached().is_none());
-                debug_assert!(shard.intent.get_secondary().is_empty());
+        // Let the TenantShard know it is idle.
+        tenant.reconcile_complete(result.sequence);
 
-                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
-                    detach_waiters.push(waiter);
-                }
+        if let Some(event_tx) = &self.reconcile_event_tx {
+            let event = ReconcileEvent::new(&tenant.observed, &result);
+            if let Err(TrySendError::Full(_)) = event_tx.try_send(event) {
+                tracing::warn!(
+                    tenant_id=%result.tenant_shard_id.tenant_id, shard_id=%result.tenant_shard_id.shard_slug(),
+                    "Reconcile event queue is full, dropping event"
+                );
             }
+        }
 
-            // Pick an arbitrary node to use for remote deletions (does not have to be where the tenant
-            // was attached, just has to be able to see the S3 content)
-            let node_id = scheduler.schedule_shard(&[], &ScheduleContext::default())?;
-            let node = nodes
-                .get(&node_id)
-                .expect("Pageservers may not be deleted while lock is active");
-            (detach_waiters, shard_ids, node.clone())
-        };
-
-        // This reconcile wait can fail in a few ways:
-        //  A there is a very long queue for the reconciler semaphore
-        //  B some pageserver is failing to handle a detach promptly
-        //  C some pageserver goes offline right at the moment we send it a request.
-        //
-        // A and C are transient: the semaphore will eventually become available, and once a node is marked offline
-        // the next attempt to reconcile will silently skip detaches for an offline node and succeed.  If B happens,
-        // it's a bug, and needs resolving at the pageserver level (we shouldn't just leave attachments behind while
-        // deleting the underlying data).
-        self.await_waiters(detach_waiters, RECONCILE_TIMEOUT)
-            .await?;
-
-        let locations = shard_ids
-            .into_iter()
-            .map(|s| (s, node.clone()))
-            .collect::<Vec<_>>();
-        let results = self.tenant_for_shards_api(
-            locations,
-            |tenant_shard_id, client| async move { client.tenant_delete(tenant_shard_id).await },
-            1,
-            3,
-            RECONCILE_TIMEOUT,
-            &self.cancel,
-        )
-        .await;
-        for result in results {
-            match result {
-                Ok(StatusCode::ACCEPTED) => {
-                    // This should never happen: we waited for detaches to finish above
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "Unexpectedly still attached on {}",
-                        node
-                    )));
-                }
-                Ok(_) => {}
-                Err(mgmt_api::Error::Cancelled) => {
-                    return Err(ApiError::ShuttingDown);
+        match result.result {
+            _ if stale => {}
+            Ok(()) => {
+                for (node_id, loc) in &result.observed.locations {
+                    if let Some(conf) = &loc.conf {
+                        tracing::info!("Updating observed location {}: {:?}", node_id, conf);
+                    } else {
+                        tracing::info!("Setting observed location {} to None", node_id,)
+                    }
                 }
-                Err(e) => {
-                    // This is unexpected: remote deletion should be infallible, unless the object store
-                    // at large is unavailable.
-                    tracing::error!("Error deleting via node {}: {e}", node);
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(e)));
+                tenant.apply_observed(result.observed, true);
+                tenant.waiter.advance(result.sequence);
+                tenant.record_reconcile_success();
+                self.reconcile_watch_tx.send(result.tenant_shard_id).ok();
+            }
+            Err(e) => {
+                match e {
+                    ReconcileError::Cancel => {
+                        tracing::info!("Reconciler was cancelled");
+                    }
+                    ReconcileError::Remote(mgmt_api::Error::Cancelled) => {
+                        // This might be due to the reconciler getting cancelled, or it might
+                        // be due to the `Node` being marked offline.
+                        tracing::info!("Reconciler cancelled during pageserver API call");
+                    }
+                    ReconcileError::StaleGeneration { .. } => {
+                        // A newer generation was issued while the reconciler ran: the shard is
+                        // still dirty, and the next reconcile will use the newer generation.
+                        tracing::info!("Reconciler superseded: {e}");
+                    }
+                    _ => {
+                        tracing::warn!("Reconcile error: {}", e);
+                        if tenant.record_reconcile_failure() {
+                            tracing::warn!(
+                                tenant_id=%result.tenant_shard_id.tenant_id, shard_id=%result.tenant_shard_id.shard_slug(),
+                                "Shard needs attention: reconciles keep failing"
+                            );
+                            METRICS_REGISTRY
+                                .metrics_group
+                                .storage_controller_reconcile_attention
+                                .inc();
+                        }
+                    }
                 }
+
+                // Ordering: populate last_error before advancing error_seq,
+                // so that waiters will see the correct error after waiting.
+                tenant.set_last_error(result.sequence, e);
+                self.reconcile_watch_tx.send(result.tenant_shard_id).ok();
+
+                tenant.apply_observed(result.observed, false);
             }
         }
 
-        // Fall through: deletion of the tenant on pageservers is complete, we may proceed to drop
-        // our in-memory state and database state.
-
-        // Ordering: we delete persistent state first: if we then
-        // crash, we will drop the in-memory state.
+        self.check_generation_authority(tenant);
 
-        // Drop persistent state.
-        self.persistence.delete_tenant(tenant_id).await?;
+        // Avoid accumulating observed state for locations we are no longer interested in.
+        tenant.prune_observed(nodes);
 
-        // Drop in-memory state
-        {
-            let mut locked = self.inner.write().unwrap();
-            let (_nodes, tenants, scheduler) = locked.parts_mut();
+        // Maybe some other work can proceed now that this job finished.
+        if self.reconciler_concurrency.available_permits() > 0 {
+            while let Some(tenant_shard_id) = {
+                let state = &mut *locked;
+                next_delayed_reconcile(
+                    &mut state.delayed_reconcile_priority_rx,
+                    &mut state.delayed_reconcile_rx,
+                )
+            } {
+                let (nodes, tenants, _scheduler) = locked.parts_mut();
+                if let Some(shard) = tenants.get_mut(&tenant_shard_id) {
+                    shard.delayed_reconcile = false;
+                    shard.delayed_reconcile_since = None;
+                    self.maybe_reconcile_shard(shard, nodes);
+                }
 
-            // Dereference Scheduler from shards before dropping them
-            for (_tenant_shard_id, shard) in
-                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
-            {
-                shard.intent.clear(scheduler);
+                if self.reconciler_concurrency.available_permits() == 0 {
+                    break;
+                }
             }
+        }
 
-            tenants.retain(|tenant_shard_id, _shard| tenant_shard_id.tenant_id != tenant_id);
-            tracing::info!(
-                "Deleted tenant {tenant_id}, now have {} tenants",
-                locked.tenants.len()
-            );
-        };
-
-        // Success is represented as 404, to imitate the existing pageserver deletion API
-        Ok(StatusCode::NOT_FOUND)
+        self.publish_reconcile_queue_metrics();
     }
 
-    /// Naming: this configures the storage controller's policies for a tenant, whereas [`Self::tenant_config_set`] is "set the TenantConfig"
-    /// for a tenant.  The TenantConfig is passed through to pageservers, whereas this function modifies
-    /// the tenant's policies (configuration) within the storage controller
-    pub(crate) async fn tenant_update_policy(
+    async fn process_results(
         &self,
-        tenant_id: TenantId,
-        req: TenantPolicyRequest,
-    ) -> Result<(), ApiError> {
-        // We require an exclusive lock, because we are updating persistent and in-memory state
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::UpdatePolicy,
-        )
-        .await;
-
-        failpoint_support::sleep_millis_async!("tenant-update-policy-exclusive-lock");
-
-        let TenantPolicyRequest {
-            placement,
-            scheduling,
-        } = req;
-
-        self.persistence
-            .update_tenant_shard(
-                TenantFilter::Tenant(tenant_id),
-                placement.clone(),
-                None,
-                None,
-                scheduling,
-            )
-            .await?;
+        mut result_rx: CountedReceiver<ReconcileResult>,
+        mut bg_compute_hook_result_rx: tokio::sync::mpsc::Receiver<
+            Result<(), (TenantShardId, NotifyError)>,
+        >,
+    ) {
+        loop {
+            // Wait for the next result, or for cancellation
+            tokio::select! {
+                r = result_rx.recv() => {
+                    match r {
+                        Some(result) => {self.process_result(result);},
+                        None => {break;}
+                    }
+                }
+                _ = async{
+                    match bg_compute_hook_result_rx.recv().await {
+                        Some(result) => {
+                            if let Err((tenant_shard_id, notify_error)) = result {
+                                tracing::warn!("Marking shard {tenant_shard_id} for notification retry, due to error {notify_error}");
+                                let pending = self
+                                    .inner
+                                    .read()
+                                    .unwrap()
+                                    .tenants
+                                    .get(&tenant_shard_id)
+                                    .map(|shard| shard.pending_compute_notification);
+                                if pending == Some(false) {
+                                    // Ordering: persist before setting the flag in memory,
+                                    // so that a reconciler that clears the flag after
+                                    // retrying the notification clears it in the database
+                                    // after us.
+                                    persist_pending_compute_notification(
+                                        &self.persistence,
+                                        tenant_shard_id,
+                                        true,
+                                    )
+                                    .await;
+                                    let mut locked = self.inner.write().unwrap();
+                                    if let Some(shard) = locked.tenants.get_mut(&tenant_shard_id) {
+                                        shard.pending_compute_notification = true;
+                                    }
+                                }
+                            }
+                        },
+                        None => {
+                            // This channel is dead, but we don't want to terminate the outer loop{}: just wait for shutdown
+                            self.cancel.cancelled().await;
+                        }
+                    }
+                } => {},
+                _ = self.cancel.cancelled() => {
+                    break;
+                }
+            };
+            self.liveness.beat(Processor::Results);
+        }
 
-        let mut schedule_context = ScheduleContext::default();
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-        for (shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
-            if let Some(placement) = &placement {
-                shard.policy = placement.clone();
+        // We should only fall through on shutdown
+        assert!(self.cancel.is_cancelled());
+    }
 
-                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
-                               "Updated placement policy to {placement:?}");
-            }
+    async fn process_aborts(&self, mut abort_rx: CountedReceiver<TenantShardSplitAbort>) {
+        loop {
+            // Wait for the next result, or for cancellation
+            let op = tokio::select! {
+                r = abort_rx.recv() => {
+                    match r {
+                        Some(op) => {op},
+                        None => {break;}
+                    }
+                }
+                _ = self.cancel.cancelled() => {
+                    break;
+                }
+            };
+            self.liveness.beat(Processor::Aborts);
 
-            if let Some(scheduling) = &scheduling {
-                shard.set_scheduling_policy(*scheduling);
+            // Retry until shutdown: we must keep this request object alive until it is properly
+            // processed, as it holds a lock guard that prevents other operations trying to do things
+            // to the tenant while it is in a weird part-split state.
+            while !self.cancel.is_cancelled() {
+                match self.abort_tenant_shard_split(&op).await {
+                    Ok(_) => break,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to abort shard split on {}, will retry: {e}",
+                            op.tenant_id
+                        );
 
-                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
-                               "Updated scheduling policy to {scheduling:?}");
+                        // If a node is unavailable, we hope that it has been properly marked Offline
+                        // when we retry, so that the abort op will succeed.  If the abort op is failing
+                        // for some other reason, we will keep retrying forever, or until a human notices
+                        // and does something about it (either fixing a pageserver or restarting the controller).
+                        tokio::time::timeout(Duration::from_secs(5), self.cancel.cancelled())
+                            .await
+                            .ok();
+                    }
+                }
             }
-
-            // In case scheduling is being switched back on, try it now.
-            shard.schedule(scheduler, &mut schedule_context).ok();
-            self.maybe_reconcile_shard(shard, nodes);
         }
-
-        Ok(())
     }
 
-    pub(crate) async fn tenant_timeline_create(
-        &self,
-        tenant_id: TenantId,
-        mut create_req: TimelineCreateRequest,
-    ) -> Result<TimelineInfo, ApiError> {
-        tracing::info!(
-            "Creating timeline {}/{}",
-            tenant_id,
-            create_req.new_timeline_id,
-        );
+    pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Result<Arc<Self>> {
+        let liveness = ProcessorLiveness::default();
+        let (result_tx, result_rx) = liveness.channel(Processor::Results);
+        let (abort_tx, abort_rx) = liveness.channel(Processor::Aborts);
 
-        let _tenant_lock = trace_shared_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::TimelineCreate,
-        )
-        .await;
-        failpoint_support::sleep_millis_async!("tenant-create-timeline-shared-lock");
-
-        self.ensure_attached_wait(tenant_id).await?;
-
-        let mut targets = {
-            let locked = self.inner.read().unwrap();
-            let mut targets = Vec::new();
-
-            for (tenant_shard_id, shard) in
-                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-            {
-                let node_id = shard.intent.get_attached().ok_or_else(|| {
-                    ApiError::InternalServerError(anyhow::anyhow!("Shard not scheduled"))
-                })?;
-                let node = locked
-                    .nodes
-                    .get(&node_id)
-                    .expect("Pageservers may not be deleted while referenced");
-
-                targets.push((*tenant_shard_id, node.clone()));
-            }
-            targets
-        };
-
-        if targets.is_empty() {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("Tenant not found").into(),
-            ));
-        };
-        let shard_zero = targets.remove(0);
-
-        async fn create_one(
-            tenant_shard_id: TenantShardId,
-            node: Node,
-            jwt: Option<String>,
-            create_req: TimelineCreateRequest,
-        ) -> Result<TimelineInfo, ApiError> {
-            tracing::info!(
-                "Creating timeline on shard {}/{}, attached to node {node}",
-                tenant_shard_id,
-                create_req.new_timeline_id,
-            );
-            let client = PageserverClient::new(node.get_id(), node.base_url(), jwt.as_deref());
-
-            client
-                .timeline_create(tenant_shard_id, &create_req)
-                .await
-                .map_err(|e| passthrough_api_error(&node, e))
-        }
+        tracing::info!("Loading nodes from database...");
+        let nodes = persistence
+            .list_nodes()
+            .await?
+            .into_iter()
+            .map(Node::from_persistent)
+            .collect::<Vec<_>>();
+        let nodes: HashMap<NodeId, Node> = nodes.into_iter().map(|n| (n.get_id(), n)).collect();
+        tracing::info!("Loaded {} nodes from database.", nodes.len());
 
-        // Because the caller might not provide an explicit LSN, we must do the creation first on a single shard, and then
-        // use whatever LSN that shard picked when creating on subsequent shards.  We arbitrarily use shard zero as the shard
-        // that will get the first creation request, and propagate the LSN to all the >0 shards.
-        let timeline_info = create_one(
-            shard_zero.0,
-            shard_zero.1,
-            self.config.jwt_token.clone(),
-            create_req.clone(),
-        )
-        .await?;
+        tracing::info!("Loading shards from database...");
+        let mut tenant_shard_persistence = persistence.list_tenant_shards().await?;
+        tracing::info!(
+            "Loaded {} shards from database.",
+            tenant_shard_persistence.len()
+        );
 
-        // Propagate the LSN that shard zero picked, if caller didn't provide one
-        if create_req.ancestor_timeline_id.is_some() && create_req.ancestor_start_lsn.is_none() {
-            create_req.ancestor_start_lsn = timeline_info.ancestor_lsn;
+        // If any shard splits were in progress, reset the database state to abort them
+        let mut tenant_shard_count_min_max: HashMap<TenantId, (ShardCount, ShardCount)> =
+            HashMap::new();
+        for tsp in &mut tenant_shard_persistence {
+            let shard = tsp.get_shard_identity()?;
+            let tenant_shard_id = tsp.get_tenant_shard_id()?;
+            let entry = tenant_shard_count_min_max
+                .entry(tenant_shard_id.tenant_id)
+                .or_insert_with(|| (shard.count, shard.count));
+            entry.0 = std::cmp::min(entry.0, shard.count);
+            entry.1 = std::cmp::max(entry.1, shard.count);
         }
 
-        // Create timeline on remaining shards with number >0
-        if !targets.is_empty() {
-            // If we had multiple shards, issue requests for the remainder now.
-            let jwt = self.config.jwt_token.clone();
-            self.tenant_for_shards(targets, |tenant_shard_id: TenantShardId, node: Node| {
-                let create_req = create_req.clone();
-                Box::pin(create_one(tenant_shard_id, node, jwt.clone(), create_req))
-            })
-            .await?;
-        }
+        for (tenant_id, (count_min, count_max)) in tenant_shard_count_min_max {
+            if count_min != count_max {
+                // Aborting the split in the database and dropping the child shards is sufficient: the reconciliation in
+                // [`Self::startup_reconcile`] will implicitly drop the child shards on remote pageservers, or they'll
+                // be dropped later in [`Self::node_activate_reconcile`] if it isn't available right now.
+                tracing::info!("Aborting shard split {tenant_id} {count_min:?} -> {count_max:?}");
+                let abort_status = persistence.abort_shard_split(tenant_id, count_max).await?;
 
-        Ok(timeline_info)
-    }
+                // We may never see the Complete status here: if the split was complete, we wouldn't have
+                // identified this tenant has having mismatching min/max counts.
+                assert!(matches!(abort_status, AbortShardSplitStatus::Aborted));
 
-    /// Helper for concurrently calling a pageserver API on a number of shards, such as timeline creation.
-    ///
-    /// On success, the returned vector contains exactly the same number of elements as the input `locations`.
-    async fn tenant_for_shards<F, R>(
-        &self,
-        locations: Vec<(TenantShardId, Node)>,
-        mut req_fn: F,
-    ) -> Result<Vec<R>, ApiError>
-    where
-        F: FnMut(
-            TenantShardId,
-            Node,
-        )
-            -> std::pin::Pin<Box<dyn futures::Future<Output = Result<R, ApiError>> + Send>>,
-    {
-        let mut futs = FuturesUnordered::new();
-        let mut results = Vec::with_capacity(locations.len());
+                // Clear the splitting status in-memory, to reflect that we just aborted in the database
+                tenant_shard_persistence.iter_mut().for_each(|tsp| {
+                    // Set idle split state on those shards that we will retain.
+                    let tsp_tenant_id = TenantId::from_str(tsp.tenant_id.as_str()).unwrap();
+                    if tsp_tenant_id == tenant_id
+                        && tsp.get_shard_identity().unwrap().count == count_min
+                    {
+                        tsp.splitting = SplitState::Idle;
+                    } else if tsp_tenant_id == tenant_id {
+                        // Leave the splitting state on the child shards: this will be used next to
+                        // drop them.
+                        tracing::info!(
+                            "Shard {tsp_tenant_id} will be dropped after shard split abort",
+                        );
+                    }
+                });
 
-        for (tenant_shard_id, node) in locations {
-            futs.push(req_fn(tenant_shard_id, node));
+                // Drop shards for this tenant which we didn't just mark idle (i.e. child shards of the aborted split)
+                tenant_shard_persistence.retain(|tsp| {
+                    TenantId::from_str(tsp.tenant_id.as_str()).unwrap() != tenant_id
+                        || tsp.splitting == SplitState::Idle
+                });
+            }
         }
 
-        while let Some(r) = futs.next().await {
-            results.push(r?);
-        }
+        let mut tenants = BTreeMap::new();
 
-        Ok(results)
-    }
+        let mut scheduler = Scheduler::new(nodes.values());
+        scheduler.set_utilization_weight(config.scheduler_utilization_weight);
 
-    /// Concurrently invoke a pageserver API call on many shards at once
-    pub(crate) async fn tenant_for_shards_api<T, O, F>(
-        &self,
-        locations: Vec<(TenantShardId, Node)>,
-        op: O,
-        warn_threshold: u32,
-        max_retries: u32,
-        timeout: Duration,
-        cancel: &CancellationToken,
-    ) -> Vec<mgmt_api::Result<T>>
-    where
-        O: Fn(TenantShardId, PageserverClient) -> F + Copy,
-        F: std::future::Future<Output = mgmt_api::Result<T>>,
-    {
-        let mut futs = FuturesUnordered::new();
-        let mut results = Vec::with_capacity(locations.len());
+        #[cfg(feature = "testing")]
+        {
+            // Hack: insert scheduler state for all nodes referenced by shards, as compatibility
+            // tests only store the shards, not the nodes.  The nodes will be loaded shortly
+            // after when pageservers start up and register.
+            let mut node_ids = HashSet::new();
+            for tsp in &tenant_shard_persistence {
+                if let Some(node_id) = tsp.generation_pageserver {
+                    node_ids.insert(node_id);
+                }
+            }
+            for node_id in node_ids {
+                tracing::info!("Creating node {} in scheduler for tests", node_id);
+                let node = Node::new(
+                    NodeId(node_id as u64),
+                    "".to_string(),
+                    123,
+                    "".to_string(),
+                    123,
+                );
 
-        for (tenant_shard_id, node) in locations {
-            futs.push(async move {
-                node.with_client_retries(
-                    |client| op(tenant_shard_id, client),
-                    &self.config.jwt_token,
-                    warn_threshold,
-                    max_retries,
-                    timeout,
-                    cancel,
-                )
-                .await
-            });
+                scheduler.node_upsert(&node);
+            }
         }
+        for tsp in tenant_shard_persistence {
+            let tenant_shard_id = tsp.get_tenant_shard_id()?;
 
-        while let Some(r) = futs.next().await {
-            let r = r.unwrap_or(Err(mgmt_api::Error::Cancelled));
-            results.push(r);
-        }
+            // We will populate intent properly later in [`Self::startup_reconcile`], initially populate
+            // it with what we can infer: the node for which a generation was most recently issued.
+            let mut intent = IntentState::new();
+            if let Some(generation_pageserver) = tsp.generation_pageserver {
+                intent.set_attached(&mut scheduler, Some(NodeId(generation_pageserver as u64)));
+            }
+            let new_tenant = TenantShard::from_persistent(tsp, intent)?;
 
-        results
-    }
+            tenants.insert(tenant_shard_id, new_tenant);
+        }
 
-    pub(crate) async fn tenant_timeline_delete(
-        &self,
-        tenant_id: TenantId,
-        timeline_id: TimelineId,
-    ) -> Result<StatusCode, ApiError> {
-        tracing::info!("Deleting timeline {}/{}", tenant_id, timeline_id,);
-        let _tenant_lock = trace_shared_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::TimelineDelete,
-        )
-        .await;
+        let (startup_completion, startup_complete) = utils::completion::channel();
 
-        self.ensure_attached_wait(tenant_id).await?;
+        // This channel is continuously consumed by process_results, so doesn't need to be very large.
+        let (bg_compute_notify_result_tx, bg_compute_notify_result_rx) =
+            tokio::sync::mpsc::channel(512);
 
-        let mut targets = {
-            let locked = self.inner.read().unwrap();
-            let mut targets = Vec::new();
+        let (delayed_reconcile_tx, delayed_reconcile_rx) =
+            tokio::sync::mpsc::channel(MAX_DELAYED_RECONCILES);
+        let (delayed_reconcile_priority_tx, delayed_reconcile_priority_rx) =
+            tokio::sync::mpsc::channel(MAX_DELAYED_RECONCILES);
 
-            for (tenant_shard_id, shard) in
-                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-            {
-                let node_id = shard.intent.get_attached().ok_or_else(|| {
-                    ApiError::InternalServerError(anyhow::anyhow!("Shard not scheduled"))
-                })?;
-                let node = locked
-                    .nodes
-                    .get(&node_id)
-                    .expect("Pageservers may not be deleted while referenced");
+        let (reconcile_watch_tx, _) = tokio::sync::broadcast::channel(RECONCILE_WATCH_QUEUE_SIZE);
 
-                targets.push((*tenant_shard_id, node.clone()));
+        let (reconcile_event_tx, reconcile_event_rx) = match &config.reconcile_event_url {
+            Some(url) => {
+                let (tx, rx) = tokio::sync::mpsc::channel(RECONCILE_EVENT_QUEUE_SIZE);
+                (Some(tx), Some((ReconcileEventSink::new(url.clone()), rx)))
             }
-            targets
+            None => (None, None),
         };
 
-        if targets.is_empty() {
-            return Err(ApiError::NotFound(
+        let cancel = CancellationToken::new();
+        let heartbeater = Heartbeater::new(
+            config.jwt_token.clone(),
+            config.max_unavailable_interval,
+            cancel.clone(),
+        );
+        let this = Arc::new(Self {
+            inner: Arc::new(std::sync::RwLock::new(ServiceState::new(
+                nodes,
+                tenants,
+                scheduler,
+                delayed_reconcile_rx,
+                delayed_reconcile_priority_rx,
+            ))),
+            config: config.clone(),
+            persistence,
+            compute_hook: Arc::new(ComputeHook::new(config.clone(), cancel.clone())),
+            result_tx,
+            bg_compute_notify_result_tx: bg_compute_notify_result_tx.clone(),
+            heartbeater,
+            tenant_list_cache: config.tenant_list_cache_ttl.map(TtlCache::new),
+            reconciler_concurrency: Arc::new(tokio::sync::Semaphore::new(std::cmp::max(
+                config.reconciler_concurrency,
+                config.startup_reconciler_concurrency,
+            ))),
+            reconciler_concurrency_limit: AtomicUsize::new(std::cmp::max(
+                config.reconciler_concurrency,
+                config.startup_reconciler_concurrency,
+            )),
+            delayed_reconcile_tx,
+            delayed_reconcile_priority_tx,
+            reconcile_watch_tx,
+            reconcile_event_tx,
+            abort_tx,
+            liveness,
+            autosplit_config: std::sync::RwLock::new(AutosplitConfig {
+                split_threshold: config.split_threshold,
+                split_to: SPLIT_TO_MAX_DEFAULT,
+            }),
+            shard_splits: ShardSplitTracker::default(),
+            frozen_generations: Arc::default(),
+            rescan_lock: Default::default(),
+            startup_complete: startup_complete.clone(),
+            cancel,
+            gate: Gate::default(),
+            tenant_op_locks: Default::default(),
+            node_op_locks: Default::default(),
+        });
+
+        let result_task_this = this.clone();
+        tokio::task::spawn(async move {
+            // Block shutdown until we're done (we must respect self.cancel)
+            if let Ok(_gate) = result_task_this.gate.enter() {
+                let _liveness = result_task_this
+                    .liveness
+                    .start(Processor::Results, result_task_this.cancel.clone());
+                result_task_this
+                    .process_results(result_rx, bg_compute_notify_result_rx)
+                    .await
+            }
+        });
+
+        tokio::task::spawn({
+            let this = this.clone();
+            async move {
+                // Block shutdown until we're done (we must respect self.cancel)
+                if let Ok(_gate) = this.gate.enter() {
+                    let _liveness = this.liveness.start(Processor::Aborts, this.cancel.clone());
+                    this.process_aborts(abort_rx).await
+                }
+            }
+        });
+
+        if let Some((sink, event_rx)) = reconcile_event_rx {
+            let this = this.clone();
+            tokio::task::spawn(async move {
+                if let Ok(_gate) = this.gate.enter() {
+                    sink.run(event_rx, this.cancel.clone()).await
+                }
+            });
+        }
+
+        tokio::task::spawn({
+            let this = this.clone();
+            async move {
+                if let Ok(_gate) = this.gate.enter() {
+                    loop {
+                        tokio::select! {
+                            _ = this.cancel.cancelled() => {
+                                break;
+                            },
+                            _ = tokio::time::sleep(Duration::from_secs(60)) => {}
+                        };
+                        this.tenant_op_locks.housekeeping();
+                    }
+                }
+            }
+        });
+
+        if let Some(compute_hook_url) = this.config.compute_hook_url.clone() {
+            // Check the compute hook early, so that a misconfigured URL is obvious in the logs rather
+            // than showing up later as a flood of failed notifications.  This doesn't block startup.
+            tokio::task::spawn({
+                let this = this.clone();
+                async move {
+                    let Ok(_gate) = this.gate.enter() else {
+                        return;
+                    };
+
+                    match this.compute_hook.probe().await {
+                        Ok(()) => tracing::info!("Compute hook {compute_hook_url} is reachable"),
+                        Err(e) => tracing::warn!(
+                            "Compute hook {compute_hook_url} is unreachable ({e}): compute notifications will fail until this is fixed.  Check the compute_hook_url setting."
+                        ),
+                    }
+                }
+            });
+        }
+
+        tokio::task::spawn({
+            let this = this.clone();
+            // We will block the [`Service::startup_complete`] barrier until [`Self::startup_reconcile`]
+            // is done.
+            let startup_completion = startup_completion.clone();
+            async move {
+                // Block shutdown until we're done (we must respect self.cancel)
+                let Ok(_gate) = this.gate.enter() else {
+                    return;
+                };
+
+                this.startup_reconcile(bg_compute_notify_result_tx).await;
+                drop(startup_completion);
+
+                retire_startup_reconcile_units(
+                    &this.reconciler_concurrency,
+                    this.config.startup_reconciler_concurrency,
+                    this.config.reconciler_concurrency,
+                    &this.cancel,
+                )
+                .await;
+                if !this.cancel.is_cancelled() {
+                    this.reconciler_concurrency_limit
+                        .store(this.config.reconciler_concurrency, AtomicOrdering::Relaxed);
+                }
+            }
+        });
+
+        tokio::task::spawn({
+            let this = this.clone();
+            let startup_complete = startup_complete.clone();
+            async move {
+                let _liveness = this
+                    .liveness
+                    .start(Processor::BackgroundReconcile, this.cancel.clone());
+                startup_complete.wait().await;
+                this.background_reconcile().await;
+            }
+        });
+
+        tokio::task::spawn({
+            let this = this.clone();
+            let startup_complete = startup_complete.clone();
+            async move {
+                let _liveness = this
+                    .liveness
+                    .start(Processor::Heartbeats, this.cancel.clone());
+                startup_complete.wait().await;
+                this.spawn_heartbeat_driver().await;
+            }
+        });
+
+        Ok(this)
+    }
+
+    pub(crate) async fn attach_hook(
+        &self,
+        attach_req: AttachHookRequest,
+    ) -> anyhow::Result<AttachHookResponse> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            attach_req.tenant_shard_id.tenant_id,
+            TenantOperations::ShardSplit,
+        )
+        .await;
+
+        // This is a test hook.  To enable using it on tenants that were created directly with
+        // the pageserver API (not via this service), we will auto-create any missing tenant
+        // shards with default state.
+        let insert = {
+            let locked = self.inner.write().unwrap();
+            !locked.tenants.contains_key(&attach_req.tenant_shard_id)
+        };
+
+        if insert {
+            let tsp = TenantShardPersistence {
+                tenant_id: attach_req.tenant_shard_id.tenant_id.to_string(),
+                shard_number: attach_req.tenant_shard_id.shard_number.0 as i32,
+                shard_count: attach_req.tenant_shard_id.shard_count.literal() as i32,
+                shard_stripe_size: 0,
+                generation: attach_req.generation_override.or(Some(0)),
+                generation_pageserver: None,
+                placement_policy: serde_json::to_string(&PlacementPolicy::Attached(0)).unwrap(),
+                config: serde_json::to_string(&TenantConfig::default()).unwrap(),
+                splitting: SplitState::default(),
+                scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
+                    .unwrap(),
+                sticky: false,
+                colocate: false,
+                canary: false,
+                pending_compute_notification: false,
+                reconcile_priority: serde_json::to_string(&ReconcilePriority::default()).unwrap(),
+            };
+
+            match self.persistence.insert_tenant_shards(vec![tsp]).await {
+                Err(e) => match e {
+                    DatabaseError::Query(diesel::result::Error::DatabaseError(
+                        DatabaseErrorKind::UniqueViolation,
+                        _,
+                    )) => {
+                        tracing::info!(
+                            "Raced with another request to insert tenant {}",
+                            attach_req.tenant_shard_id
+                        )
+                    }
+                    _ => return Err(e.into()),
+                },
+                Ok(()) => {
+                    tracing::info!("Inserted shard {} in database", attach_req.tenant_shard_id);
+
+                    let mut locked = self.inner.write().unwrap();
+                    locked.tenants.insert(
+                        attach_req.tenant_shard_id,
+                        TenantShard::new(
+                            attach_req.tenant_shard_id,
+                            ShardIdentity::unsharded(),
+                            PlacementPolicy::Attached(0),
+                        ),
+                    );
+                    tracing::info!("Inserted shard {} in memory", attach_req.tenant_shard_id);
+                }
+            }
+        }
+
+        let new_generation = if let Some(req_node_id) = attach_req.node_id {
+            let maybe_tenant_conf = {
+                let locked = self.inner.write().unwrap();
+                locked
+                    .tenants
+                    .get(&attach_req.tenant_shard_id)
+                    .map(|t| t.config.clone())
+            };
+
+            match maybe_tenant_conf {
+                Some(conf) => {
+                    self.frozen_generations
+                        .check(attach_req.tenant_shard_id.tenant_id)?;
+                    let new_generation = self
+                        .persistence
+                        .increment_generation(attach_req.tenant_shard_id, req_node_id)
+                        .await?;
+
+                    // Persist the placement policy update. This is required
+                    // when we reattaching a detached tenant.
+                    self.persistence
+                        .update_tenant_shard(
+                            TenantFilter::Shard(attach_req.tenant_shard_id),
+                            Some(PlacementPolicy::Attached(0)),
+                            Some(conf),
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
+                    Some(new_generation)
+                }
+                None => {
+                    anyhow::bail!("Attach hook handling raced with tenant removal")
+                }
+            }
+        } else {
+            self.persistence.detach(attach_req.tenant_shard_id).await?;
+            None
+        };
+
+        let mut locked = self.inner.write().unwrap();
+        let (_nodes, tenants, scheduler) = locked.parts_mut();
+
+        let tenant_shard = tenants
+            .get_mut(&attach_req.tenant_shard_id)
+            .expect("Checked for existence above");
+
+        if let Some(new_generation) = new_generation {
+            tenant_shard.advance_generation(new_generation);
+            tenant_shard.policy = PlacementPolicy::Attached(0);
+        } else {
+            // This is a detach notification.  We must update placement policy to avoid re-attaching
+            // during background scheduling/reconciliation, or during storage controller restart.
+            assert!(attach_req.node_id.is_none());
+            tenant_shard.policy = PlacementPolicy::Detached;
+        }
+
+        if let Some(attaching_pageserver) = attach_req.node_id.as_ref() {
+            tracing::info!(
+                tenant_id = %attach_req.tenant_shard_id,
+                ps_id = %attaching_pageserver,
+                generation = ?tenant_shard.generation,
+                "issuing",
+            );
+        } else if let Some(ps_id) = tenant_shard.intent.get_attached() {
+            tracing::info!(
+                tenant_id = %attach_req.tenant_shard_id,
+                %ps_id,
+                generation = ?tenant_shard.generation,
+                "dropping",
+            );
+        } else {
+            tracing::info!(
+            tenant_id = %attach_req.tenant_shard_id,
+            "no-op: tenant already has no pageserver");
+        }
+        tenant_shard
+            .intent
+            .set_attached(scheduler, attach_req.node_id);
+
+        tracing::info!(
+            "attach_hook: tenant {} set generation {:?}, pageserver {}",
+            attach_req.tenant_shard_id,
+            tenant_shard.generation,
+            // TODO: this is an odd number of 0xf's
+            attach_req.node_id.unwrap_or(utils::id::NodeId(0xfffffff))
+        );
+
+        // Trick the reconciler into not doing anything for this tenant: this helps
+        // tests that manually configure a tenant on the pagesrever, and then call this
+        // attach hook: they don't want background reconciliation to modify what they
+        // did to the pageserver.
+        #[cfg(feature = "testing")]
+        {
+            if let Some(node_id) = attach_req.node_id {
+                tenant_shard.observed.locations = HashMap::from([(
+                    node_id,
+                    ObservedStateLocation {
+                        conf: Some(attached_location_conf(
+                            tenant_shard.generation.unwrap(),
+                            &tenant_shard.shard,
+                            &tenant_shard.config,
+                            &PlacementPolicy::Attached(0),
+                        )),
+                    },
+                )]);
+            } else {
+                tenant_shard.observed.locations.clear();
+            }
+        }
+
+        Ok(AttachHookResponse {
+            gen: attach_req
+                .node_id
+                .map(|_| tenant_shard.generation.expect("Test hook, not used on tenants that are mid-onboarding with a NULL generation").into().unwrap()),
+        })
+    }
+
+    pub(crate) fn inspect(&self, inspect_req: InspectRequest) -> InspectResponse {
+        let locked = self.inner.read().unwrap();
+
+        let tenant_shard = locked.tenants.get(&inspect_req.tenant_shard_id);
+
+        InspectResponse {
+            // Shards that are mid-onboarding have no generation yet: report them as unattached
+            attachment: tenant_shard.and_then(|s| {
+                let generation = s.generation?.into()?;
+                s.intent.get_attached().map(|ps| (generation, ps))
+            }),
+            secondaries: tenant_shard
+                .map(|s| s.intent.get_secondary().to_vec())
+                .unwrap_or_default(),
+            observed: tenant_shard.map(observed_locations).unwrap_or_default(),
+            scheduling_policy: tenant_shard.map(|s| *s.get_scheduling_policy()),
+            is_reconciling: tenant_shard.is_some_and(|s| s.reconciler.is_some()),
+        }
+    }
+
+    // When the availability state of a node transitions to active, we must do a full reconciliation
+    // of LocationConfigs on that node.  This is because while a node was offline:
+    // - we might have proceeded through startup_reconcile without checking for extraneous LocationConfigs on this node
+    // - aborting a tenant shard split might have left rogue child shards behind on this node.
+    //
+    // This function must complete _before_ setting a `Node` to Active: once it is set to Active, other
+    // Reconcilers might communicate with the node, and these must not overlap with the work we do in
+    // this function.
+    //
+    // The reconciliation logic in here is very similar to what [`Self::startup_reconcile`] does, but
+    // for written for a single node rather than as a batch job for all nodes.
+    #[tracing::instrument(skip_all, fields(node_id=%node.get_id()))]
+    async fn node_activate_reconcile(
+        &self,
+        mut node: Node,
+        _lock: &TracingExclusiveGuard<NodeOperations>,
+    ) -> Result<(), ApiError> {
+        // This Node is a mutable local copy: we will set it active so that we can use its
+        // API client to reconcile with the node.  The Node in [`Self::nodes`] will get updated
+        // later.
+        node.set_availability(NodeAvailability::Active(UtilizationScore::worst()));
+
+        let configs = match node
+            .with_client_retries(
+                |client| async move { client.list_location_config().await },
+                &self.config.jwt_token,
+                1,
+                5,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await
+        {
+            None => {
+                // We're shutting down (the Node's cancellation token can't have fired, because
+                // we're the only scope that has a reference to it, and we didn't fire it).
+                return Err(ApiError::ShuttingDown);
+            }
+            Some(Err(e)) => {
+                // This node didn't succeed listing its locations: it may not proceed to active state
+                // as it is apparently unavailable.
+                return Err(ApiError::PreconditionFailed(
+                    format!("Failed to query node location configs, cannot activate ({e})").into(),
+                ));
+            }
+            Some(Ok(configs)) => configs,
+        };
+        tracing::info!("Loaded {} LocationConfigs", configs.tenant_shards.len());
+
+        let mut cleanup = Vec::new();
+        {
+            let mut locked = self.inner.write().unwrap();
+
+            for (tenant_shard_id, observed_loc) in configs.tenant_shards {
+                let Some(tenant_shard) = locked.tenants.get_mut(&tenant_shard_id) else {
+                    cleanup.push(tenant_shard_id);
+                    continue;
+                };
+                tenant_shard
+                    .observed
+                    .locations
+                    .insert(node.get_id(), ObservedStateLocation { conf: observed_loc });
+
+                // An in-flight reconcile may already have issued a newer generation than the one
+                // we hold in memory: the check runs again when its result is processed.
+                if tenant_shard.reconciler.is_none() {
+                    self.check_generation_authority(tenant_shard);
+                }
+            }
+        }
+
+        for tenant_shard_id in cleanup {
+            tracing::info!("Detaching {tenant_shard_id}");
+            match node
+                .with_client_retries(
+                    |client| async move {
+                        let config = LocationConfig {
+                            mode: LocationConfigMode::Detached,
+                            generation: None,
+                            secondary_conf: None,
+                            shard_number: tenant_shard_id.shard_number.0,
+                            shard_count: tenant_shard_id.shard_count.literal(),
+                            shard_stripe_size: 0,
+                            tenant_conf: models::TenantConfig::default(),
+                        };
+                        client
+                            .location_config(tenant_shard_id, config, None, false)
+                            .await
+                    },
+                    &self.config.jwt_token,
+                    1,
+                    5,
+                    SHORT_RECONCILE_TIMEOUT,
+                    &self.cancel,
+                )
+                .await
+            {
+                None => {
+                    // We're shutting down (the Node's cancellation token can't have fired, because
+                    // we're the only scope that has a reference to it, and we didn't fire it).
+                    return Err(ApiError::ShuttingDown);
+                }
+                Some(Err(e)) => {
+                    // Do not let the node proceed to Active state if it is not responsive to requests
+                    // to detach.  This could happen if e.g. a shutdown bug in the pageserver is preventing
+                    // detach completing: we should not let this node back into the set of nodes considered
+                    // okay for scheduling.
+                    return Err(ApiError::Conflict(format!(
+                        "Node {node} failed to detach {tenant_shard_id}: {e}"
+                    )));
+                }
+                Some(Ok(_)) => {}
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Part of [`Self::re_attach`]: cancel any reconcilers that may be talking to the node, and
+    /// wait for them to exit.  Cancelled shards stay dirty, and the background reconciler picks
+    /// them up again once the node is active.
+    async fn reattach_cancel_reconcilers(&self, node_id: NodeId) {
+        let cancelled = {
+            let locked = self.inner.read().unwrap();
+            locked
+                .tenants
+                .values()
+                .filter(|shard| {
+                    shard.intent.references(node_id)
+                        || shard.observed.locations.contains_key(&node_id)
+                })
+                .filter_map(|shard| shard.cancel_reconciler_and_wait())
+                .collect::<Vec<_>>()
+        };
+
+        if cancelled.is_empty() {
+            return;
+        }
+
+        tracing::info!(
+            %node_id,
+            "Waiting for {} cancelled reconcilers before re-attach",
+            cancelled.len()
+        );
+        let wait = futures::future::join_all(cancelled);
+        tokio::select! {
+            _ = wait => {}
+            _ = tokio::time::sleep(SHORT_RECONCILE_TIMEOUT) => {
+                // Reconcilers honor cancellation promptly, so this should not happen: rather than
+                // fail the re-attach, which the pageserver would just retry, carry on.
+                tracing::warn!(%node_id, "Timed out waiting for cancelled reconcilers");
+            }
+            _ = self.cancel.cancelled() => {}
+        }
+    }
+
+    pub(crate) async fn re_attach(
+        &self,
+        reattach_req: ReAttachRequest,
+    ) -> Result<ReAttachResponse, ApiError> {
+        if let Some(register_req) = reattach_req.register {
+            self.node_register(register_req).await?;
+        }
+
+        // Ordering: we must persist generation number updates before making them visible in the in-memory state
+        let incremented_generations = self
+            .persistence
+            .re_attach(reattach_req.node_id, &self.frozen_generations.list())
+            .await?;
+
+        tracing::info!(
+            node_id=%reattach_req.node_id,
+            "Incremented {} tenant shards' generations",
+            incremented_generations.len()
+        );
+
+        // A reconciler that was already running for a shard on this node might still be calling
+        // location_conf with the generation we just superseded: cancel it, and wait for it to
+        // stop before we respond.
+        self.reattach_cancel_reconcilers(reattach_req.node_id).await;
+
+        // Apply the updated generation to our in-memory state, and
+        // gather discover secondary locations.
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+
+        let mut response = ReAttachResponse {
+            tenants: Vec::new(),
+        };
+
+        // Reconcilers for this node were cancelled above, and any reconciler that still holds an
+        // older generation will refuse to attach with it once we advance the generation below.
+        // There can still be a location_conf request in flight over the network: TODO handle that
+        // by making the pageserver's location_conf API refuse to go backward in generations.
+
+        // Scan through all shards, applying updates for ones where we updated generation
+        // and identifying shards that intend to have a secondary location on this node.
+        for (tenant_shard_id, shard) in tenants {
+            if let Some(new_gen) = incremented_generations.get(tenant_shard_id) {
+                let new_gen = *new_gen;
+
+                // Before we overwrite observed generations below, check that the pageserver
+                // was not holding a generation that we never issued.
+                self.check_generation_authority(shard);
+                let mode = reattach_mode(
+                    shard,
+                    reattach_req.node_id,
+                    self.config.reattach_preserve_multi,
+                );
+                if mode != LocationConfigMode::AttachedSingle {
+                    tracing::info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                                   "Preserving {mode:?} mode for interrupted live migration");
+                }
+                response.tenants.push(ReAttachResponseTenant {
+                    id: *tenant_shard_id,
+                    gen: Some(new_gen.into().unwrap()),
+                    mode,
+                });
+
+                shard.advance_generation(new_gen);
+                if let Some(observed) = shard.observed.locations.get_mut(&reattach_req.node_id) {
+                    // Why can we update `observed` even though we're not sure our response will be received
+                    // by the pageserver?  Because the pageserver will not proceed with startup until
+                    // it has processed response: if it loses it, we'll see another request and increment
+                    // generation again, avoiding any uncertainty about dirtiness of tenant's state.
+                    if let Some(conf) = observed.conf.as_mut() {
+                        conf.generation = new_gen.into();
+                    }
+                } else {
+                    // This node has no observed state for the shard: perhaps it was offline
+                    // when the pageserver restarted.  Insert a None, so that the Reconciler
+                    // will be prompted to learn the location's state before it makes changes.
+                    shard
+                        .observed
+                        .locations
+                        .insert(reattach_req.node_id, ObservedStateLocation { conf: None });
+                }
+            } else if shard.intent.get_secondary().contains(&reattach_req.node_id) {
+                // Ordering: pageserver will not accept /location_config requests until it has
+                // finished processing the response from re-attach.  So we can update our in-memory state
+                // now, and be confident that we are not stamping on the result of some later location config.
+                // TODO: however, we are not strictly ordered wrt ReconcileResults queue,
+                // so we might update observed state here, and then get over-written by some racing
+                // ReconcileResult.  The impact is low however, since we have set state on pageserver something
+                // that matches intent, so worst case if we race then we end up doing a spurious reconcile.
+
+                response.tenants.push(ReAttachResponseTenant {
+                    id: *tenant_shard_id,
+                    gen: None,
+                    mode: LocationConfigMode::Secondary,
+                });
+
+                // We must not update observed, because we have no guarantee that our
+                // response will be received by the pageserver. This could leave it
+                // falsely dirty, but the resulting reconcile should be idempotent.
+            } else if shard
+                .intent
+                .get_read_replicas()
+                .contains(&reattach_req.node_id)
+            {
+                // Read replicas are not included in the re-attach response, so the pageserver
+                // will drop them.  Mark the location as unknown so that a later reconcile
+                // re-creates it.
+                shard
+                    .observed
+                    .locations
+                    .insert(reattach_req.node_id, ObservedStateLocation { conf: None });
+            }
+        }
+
+        // We consider a node Active once we have composed a re-attach response, but we
+        // do not call [`Self::node_activate_reconcile`]: the handling of the re-attach response
+        // implicitly synchronizes the LocationConfigs on the node.
+        //
+        // Setting a node active unblocks any Reconcilers that might write to the location config API,
+        // but those requests will not be accepted by the node until it has finished processing
+        // the re-attach response.
+        //
+        // Additionally, reset the nodes scheduling policy to match the conditional update done
+        // in [`Persistence::re_attach`].
+        if let Some(node) = nodes.get(&reattach_req.node_id) {
+            let reset_scheduling = matches!(
+                node.get_scheduling(),
+                NodeSchedulingPolicy::PauseForRestart
+                    | NodeSchedulingPolicy::Draining
+                    | NodeSchedulingPolicy::Filling
+            );
+
+            if !node.is_available() || reset_scheduling {
+                let mut new_nodes = (**nodes).clone();
+                if let Some(node) = new_nodes.get_mut(&reattach_req.node_id) {
+                    if !node.is_available() {
+                        node.set_availability(NodeAvailability::Active(UtilizationScore::worst()));
+                    }
+
+                    if reset_scheduling {
+                        node.set_scheduling(NodeSchedulingPolicy::Active);
+                    }
+
+                    scheduler.node_upsert(node);
+                    let new_nodes = Arc::new(new_nodes);
+                    *nodes = new_nodes;
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Detect a pageserver holding a newer generation than we issued for this shard, which should
+    /// be impossible as long as this controller is the only authority for generations.  Depending
+    /// on [`Config::fence_generation_violations`], we either just log it, or also fence the shard
+    /// so that [`Self::validate`] refuses its generations until the inconsistency is resolved.
+    fn check_generation_authority(&self, shard: &mut TenantShard) {
+        match shard.find_generation_violation() {
+            Some((node_id, observed_generation)) => {
+                tracing::error!(
+                    tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                    "Node {node_id} holds generation {observed_generation:?}, newer than our latest {:?}: generations were issued by someone else!",
+                    shard.generation
+                );
+                if self.config.fence_generation_violations && !shard.generation_fenced {
+                    tracing::error!(
+                        tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                        "Fencing shard: generation validation will be refused"
+                    );
+                    shard.generation_fenced = true;
+                }
+            }
+            None => {
+                if shard.generation_fenced {
+                    tracing::info!(
+                        tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                        "Unfencing shard: observed generations are consistent again"
+                    );
+                    shard.generation_fenced = false;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn validate(&self, validate_req: ValidateRequest) -> ValidateResponse {
+        let locked = self.inner.read().unwrap();
+
+        let mut response = ValidateResponse {
+            tenants: Vec::new(),
+        };
+
+        for req_tenant in validate_req.tenants {
+            if let Some(tenant_shard) = locked.tenants.get(&req_tenant.id) {
+                let valid = tenant_shard.generation == Some(Generation::new(req_tenant.gen))
+                    && !tenant_shard.generation_fenced;
+                tracing::info!(
+                    "handle_validate: {}(gen {}): valid={valid} (latest {:?})",
+                    req_tenant.id,
+                    req_tenant.gen,
+                    tenant_shard.generation
+                );
+                response.tenants.push(ValidateResponseTenant {
+                    id: req_tenant.id,
+                    valid,
+                });
+            } else {
+                // After tenant deletion, we may approve any validation.  This avoids
+                // spurious warnings on the pageserver if it has pending LSN updates
+                // at the point a deletion happens.
+                response.tenants.push(ValidateResponseTenant {
+                    id: req_tenant.id,
+                    valid: true,
+                });
+            }
+        }
+        response
+    }
+
+    pub(crate) async fn tenant_create(
+        &self,
+        create_req: TenantCreateRequest,
+        reconcile_timeout_override: Option<Duration>,
+    ) -> Result<TenantCreateResponse, ApiError> {
+        let tenant_id = create_req.new_tenant_id.tenant_id;
+
+        // Exclude any concurrent attempts to create/access the same tenant ID
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            create_req.new_tenant_id.tenant_id,
+            TenantOperations::Create,
+        )
+        .await;
+        let (response, waiters) = self.do_tenant_create(create_req).await?;
+
+        let timeout = reconcile_timeout(reconcile_timeout_override, RECONCILE_TIMEOUT);
+        if let Err(e) = self.await_waiters(waiters, timeout).await {
+            // Avoid deadlock: reconcile may fail while notifying compute, if the cloud control plane refuses to
+            // accept compute notifications while it is in the process of creating.  Reconciliation will
+            // be retried in the background.
+            tracing::warn!(%tenant_id, "Reconcile not done yet while creating tenant ({e})");
+        }
+        Ok(response)
+    }
+
+    pub(crate) async fn do_tenant_create(
+        &self,
+        create_req: TenantCreateRequest,
+    ) -> Result<(TenantCreateResponse, Vec<ReconcilerWaiter>), ApiError> {
+        let placement_policy = create_req
+            .placement_policy
+            .clone()
+            // As a default, zero secondaries is convenient for tests that don't choose a policy.
+            .unwrap_or(PlacementPolicy::Attached(0));
+
+        // This service expects to handle sharding itself: it is an error to try and directly create
+        // a particular shard here.
+        let tenant_id = if !create_req.new_tenant_id.is_unsharded() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Attempted to create a specific shard, this API is for creating the whole tenant"
+            )));
+        } else {
+            create_req.new_tenant_id.tenant_id
+        };
+
+        check_tenant_limit(
+            &self.inner.read().unwrap().tenants,
+            tenant_id,
+            self.config.max_tenants,
+        )?;
+        check_secondaries_limit(&placement_policy, self.config.max_secondaries_per_shard)?;
+        check_shards_limit(
+            create_req.shard_parameters.count,
+            self.config.max_shards_per_tenant,
+        )?;
+
+        tracing::info!(
+            "Creating tenant {}, shard_count={:?}",
+            create_req.new_tenant_id,
+            create_req.shard_parameters.count,
+        );
+
+        let create_ids = (0..create_req.shard_parameters.count.count())
+            .map(|i| TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(i),
+                shard_count: create_req.shard_parameters.count,
+            })
+            .collect::<Vec<_>>();
+
+        // If the caller specifies a None generation, it means "start from default".  This is different
+        // to [`Self::tenant_location_config`], where a None generation is used to represent
+        // an incompletely-onboarded tenant.
+        let initial_generation = if matches!(placement_policy, PlacementPolicy::Secondary) {
+            tracing::info!(
+                "tenant_create: secondary mode, generation is_some={}",
+                create_req.generation.is_some()
+            );
+            create_req.generation.map(Generation::new)
+        } else {
+            tracing::info!(
+                "tenant_create: not secondary mode, generation is_some={}",
+                create_req.generation.is_some()
+            );
+            Some(
+                create_req
+                    .generation
+                    .map(Generation::new)
+                    .unwrap_or(INITIAL_GENERATION),
+            )
+        };
+
+        // Ordering: we persist tenant shards before creating them on the pageserver.  This enables a caller
+        // to clean up after themselves by issuing a tenant deletion if something goes wrong and we restart
+        // during the creation, rather than risking leaving orphan objects in S3.
+        let persist_tenant_shards = create_ids
+            .iter()
+            .map(|tenant_shard_id| TenantShardPersistence {
+                tenant_id: tenant_shard_id.tenant_id.to_string(),
+                shard_number: tenant_shard_id.shard_number.0 as i32,
+                shard_count: tenant_shard_id.shard_count.literal() as i32,
+                shard_stripe_size: create_req.shard_parameters.stripe_size.0 as i32,
+                generation: initial_generation.map(|g| g.into().unwrap() as i32),
+                // The pageserver is not known until scheduling happens: we will set this column when
+                // incrementing the generation the first time we attach to a pageserver.
+                generation_pageserver: None,
+                placement_policy: serde_json::to_string(&placement_policy).unwrap(),
+                config: serde_json::to_string(&create_req.config).unwrap(),
+                splitting: SplitState::default(),
+                scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
+                    .unwrap(),
+                sticky: false,
+                colocate: false,
+                canary: false,
+                pending_compute_notification: false,
+                reconcile_priority: serde_json::to_string(&ReconcilePriority::default()).unwrap(),
+            })
+            .collect();
+
+        match self
+            .persistence
+            .insert_tenant_shards(persist_tenant_shards)
+            .await
+        {
+            Ok(_) => {}
+            Err(DatabaseError::Query(diesel::result::Error::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                _,
+            ))) => {
+                // Unique key violation: this is probably a retry.  Because the shard count is part of the unique key,
+                // if we see a unique key violation it means that the creation request's shard count matches the previous
+                // creation's shard count.
+                tracing::info!("Tenant shards already present in database, proceeding with idempotent creation...");
+            }
+            // Any other database error is unexpected and a bug.
+            Err(e) => return Err(ApiError::InternalServerError(anyhow::anyhow!(e))),
+        };
+
+        let mut schedule_context = ScheduleContext::default();
+
+        let (waiters, response_shards) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let mut response_shards = Vec::new();
+            let mut schcedule_error = None;
+
+            for tenant_shard_id in create_ids {
+                tracing::info!("Creating shard {tenant_shard_id}...");
+
+                use std::collections::btree_map::Entry;
+                match tenants.entry(tenant_shard_id) {
+                    Entry::Occupied(mut entry) => {
+                        tracing::info!(
+                            "Tenant shard {tenant_shard_id} already exists while creating"
+                        );
+
+                        // Sharing schedule_context across shards pushes locations away from
+                        // pageservers already holding a shard for this tenant: see
+                        // Scheduler::schedule_shard.
+                        entry
+                            .get_mut()
+                            .schedule(scheduler, &mut schedule_context)
+                            .map_err(|e| {
+                                ApiError::Conflict(format!(
+                                    "Failed to schedule shard {tenant_shard_id}: {e}"
+                                ))
+                            })?;
+
+                        if let Some(node_id) = entry.get().intent.get_attached() {
+                            let generation = entry
+                                .get()
+                                .generation
+                                .expect("Generation is set when in attached mode");
+                            response_shards.push(TenantCreateResponseShard {
+                                shard_id: tenant_shard_id,
+                                node_id: *node_id,
+                                generation: generation.into().unwrap(),
+                            });
+                        }
+
+                        continue;
+                    }
+                    Entry::Vacant(entry) => {
+                        let state = entry.insert(TenantShard::new(
+                            tenant_shard_id,
+                            ShardIdentity::from_params(
+                                tenant_shard_id.shard_number,
+                                &create_req.shard_parameters,
+                            ),
+                            placement_policy.clone(),
+                        ));
+
+                        state.generation = initial_generation;
+                        state.config = create_req.config.clone();
+                        if let Err(e) = state.schedule(scheduler, &mut schedule_context) {
+                            schcedule_error = Some(e);
+                        }
+
+                        // Only include shards in result if we are attaching: the purpose
+                        // of the response is to tell the caller where the shards are attached.
+                        if let Some(node_id) = state.intent.get_attached() {
+                            let generation = state
+                                .generation
+                                .expect("Generation is set when in attached mode");
+                            response_shards.push(TenantCreateResponseShard {
+                                shard_id: tenant_shard_id,
+                                node_id: *node_id,
+                                generation: generation.into().unwrap(),
+                            });
+                        }
+                    }
+                };
+            }
+
+            // If we failed to schedule shards, then they are still created in the controller,
+            // but we return an error to the requester to avoid a silent failure when someone
+            // tries to e.g. create a tenant whose placement policy requires more nodes than
+            // are present in the system.  We do this here rather than in the above loop, to
+            // avoid situations where we only create a subset of shards in the tenant.
+            if let Some(e) = schcedule_error {
+                return Err(ApiError::Conflict(format!(
+                    "Failed to schedule shard(s): {e}"
+                )));
+            }
+
+            let waiters = tenants
+                .range_mut(TenantShardId::tenant_range(tenant_id))
+                .filter_map(|(_shard_id, shard)| self.maybe_reconcile_shard(shard, nodes))
+                .collect::<Vec<_>>();
+            (waiters, response_shards)
+        };
+
+        Ok((
+            TenantCreateResponse {
+                shards: response_shards,
+            },
+            waiters,
+        ))
+    }
+
+    /// Helper for functions that reconcile a number of shards, and would like to do a timeout-bounded
+    /// wait for reconciliation to complete before responding.
+    async fn await_waiters(
+        &self,
+        waiters: Vec<ReconcilerWaiter>,
+        timeout: Duration,
+    ) -> Result<(), ReconcileWaitError> {
+        let deadline = Instant::now().checked_add(timeout).unwrap();
+        wait_with_progress(&waiters, deadline, false).await
+    }
+
+    /// Same as [`Service::await_waiters`], but returns the waiters which are still
+    /// in progress
+    async fn await_waiters_remainder(
+        &self,
+        waiters: Vec<ReconcilerWaiter>,
+        timeout: Duration,
+    ) -> Vec<ReconcilerWaiter> {
+        let deadline = Instant::now().checked_add(timeout).unwrap();
+        let _ = wait_with_progress(&waiters, deadline, true).await;
+
+        waiters
+            .into_iter()
+            .filter(|waiter| matches!(waiter.get_status(), ReconcilerStatus::InProgress))
+            .collect::<Vec<_>>()
+    }
+
+    /// Part of [`Self::tenant_location_config`]: dissect an incoming location config request,
+    /// and transform it into either a tenant creation of a series of shard updates.
+    ///
+    /// If the incoming request makes no changes, a [`TenantCreateOrUpdate::Update`] result will
+    /// still be returned.
+    fn tenant_location_config_prepare(
+        &self,
+        tenant_id: TenantId,
+        req: TenantLocationConfigRequest,
+    ) -> TenantCreateOrUpdate {
+        let mut updates = Vec::new();
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
+        let tenant_shard_id = TenantShardId::unsharded(tenant_id);
+
+        // Use location config mode as an indicator of policy.
+        let placement_policy = match req.config.mode {
+            LocationConfigMode::Detached => PlacementPolicy::Detached,
+            LocationConfigMode::Secondary => PlacementPolicy::Secondary,
+            LocationConfigMode::AttachedMulti
+            | LocationConfigMode::AttachedSingle
+            | LocationConfigMode::AttachedStale => {
+                if single_node_deployment(self.config.single_node, nodes) {
+                    PlacementPolicy::Attached(0)
+                } else {
+                    self.config
+                        .default_import_placement
+                        .clone()
+                        .unwrap_or(PlacementPolicy::Attached(1))
+                }
+            }
+        };
+
+        let mut create = true;
+        for (shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
+            // Saw an existing shard: this is not a creation
+            create = false;
+
+            // Shards may have initially been created by a Secondary request, where we
+            // would have left generation as None.
+            //
+            // We only update generation the first time we see an attached-mode request,
+            // and if there is no existing generation set. The caller is responsible for
+            // ensuring that no non-storage-controller pageserver ever uses a higher
+            // generation than they passed in here.
+            use LocationConfigMode::*;
+            let set_generation = match req.config.mode {
+                AttachedMulti | AttachedSingle | AttachedStale if shard.generation.is_none() => {
+                    req.config.generation.map(Generation::new)
+                }
+                _ => None,
+            };
+
+            updates.push(ShardUpdate {
+                tenant_shard_id: *shard_id,
+                placement_policy: placement_policy.clone(),
+                tenant_config: req.config.tenant_conf.clone(),
+                generation: set_generation,
+            });
+        }
+
+        if create {
+            use LocationConfigMode::*;
+            let generation = match req.config.mode {
+                AttachedMulti | AttachedSingle | AttachedStale => req.config.generation,
+                // If a caller provided a generation in a non-attached request, ignore it
+                // and leave our generation as None: this enables a subsequent update to set
+                // the generation when setting an attached mode for the first time.
+                _ => None,
+            };
+
+            TenantCreateOrUpdate::Create(
+                // Synthesize a creation request
+                TenantCreateRequest {
+                    new_tenant_id: tenant_shard_id,
+                    generation,
+                    shard_parameters: ShardParameters {
+                        count: tenant_shard_id.shard_count,
+                        // We only import un-sharded or single-sharded tenants, so stripe
+                        // size can be made up arbitrarily here.
+                        stripe_size: ShardParameters::DEFAULT_STRIPE_SIZE,
+                    },
+                    placement_policy: Some(placement_policy),
+                    config: req.config.tenant_conf,
+                },
+            )
+        } else {
+            assert!(!updates.is_empty());
+            TenantCreateOrUpdate::Update(updates)
+        }
+    }
+
+    /// This API is used by the cloud control plane to migrate unsharded tenants that it created
+    /// directly with pageservers into this service.
+    ///
+    /// Cloud control plane MUST NOT continue issuing GENERATION NUMBERS for this tenant once it
+    /// has attempted to call this API. Failure to oblige to this rule may lead to S3 corruption.
+    /// Think of the first attempt to call this API as a transfer of absolute authority over the
+    /// tenant's source of generation numbers.
+    ///
+    /// The mode in this request coarse-grained control of tenants:
+    /// - Call with mode Attached* to upsert the tenant.
+    /// - Call with mode Secondary to either onboard a tenant without attaching it, or
+    ///   to set an existing tenant to PolicyMode::Secondary
+    /// - Call with mode Detached to switch to PolicyMode::Detached
+    pub(crate) async fn tenant_location_config(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantLocationConfigRequest,
+        reconcile_timeout_override: Option<Duration>,
+    ) -> Result<TenantLocationConfigResponse, ApiError> {
+        // We require an exclusive lock, because we are updating both persistent and in-memory state
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::LocationConfig,
+        )
+        .await;
+
+        if !tenant_shard_id.is_unsharded() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "This API is for importing single-sharded or unsharded tenants"
+            )));
+        }
+
+        // First check if this is a creation or an update
+        let create_or_update = self.tenant_location_config_prepare(tenant_shard_id.tenant_id, req);
+
+        let mut result = TenantLocationConfigResponse {
+            shards: Vec::new(),
+            stripe_size: None,
+        };
+        let waiters = match create_or_update {
+            TenantCreateOrUpdate::Create(create_req) => {
+                let (create_resp, waiters) = self.do_tenant_create(create_req).await?;
+                result.shards = create_resp
+                    .shards
+                    .into_iter()
+                    .map(|s| TenantShardLocation {
+                        node_id: s.node_id,
+                        shard_id: s.shard_id,
+                    })
+                    .collect();
+                waiters
+            }
+            TenantCreateOrUpdate::Update(updates) => {
+                // Persist updates
+                // Ordering: write to the database before applying changes in-memory, so that
+                // we will not appear time-travel backwards on a restart.  All shards are written
+                // in one transaction, so that if this fails, neither the database nor our in-memory
+                // state hold a partially applied update.
+                let mut schedule_context = ScheduleContext::default();
+                self.persistence
+                    .update_tenant_shards(updates.clone())
+                    .await?;
+
+                // Apply updates in-memory
+                let mut waiters = Vec::new();
+                {
+                    let mut locked = self.inner.write().unwrap();
+                    let (nodes, tenants, scheduler) = locked.parts_mut();
+
+                    for ShardUpdate {
+                        tenant_shard_id,
+                        placement_policy,
+                        tenant_config,
+                        generation: update_generation,
+                    } in updates
+                    {
+                        let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                            tracing::warn!("Shard {tenant_shard_id} removed while updating");
+                            continue;
+                        };
+
+                        // Update stripe size
+                        if result.stripe_size.is_none() && shard.shard.count.count() > 1 {
+                            result.stripe_size = Some(shard.shard.stripe_size);
+                        }
+
+                        shard.policy = placement_policy;
+                        shard.config = tenant_config;
+                        if let Some(generation) = update_generation {
+                            shard.advance_generation(generation);
+                        }
+
+                        shard.schedule(scheduler, &mut schedule_context)?;
+
+                        let maybe_waiter = self.maybe_reconcile_shard(shard, nodes);
+                        if let Some(waiter) = maybe_waiter {
+                            waiters.push(waiter);
+                        }
+
+                        if let Some(node_id) = shard.intent.get_attached() {
+                            result.shards.push(TenantShardLocation {
+                                shard_id: tenant_shard_id,
+                                node_id: *node_id,
+                            })
+                        }
+                    }
+                }
+                waiters
+            }
+        };
+
+        let timeout = reconcile_timeout(reconcile_timeout_override, SHORT_RECONCILE_TIMEOUT);
+        if let Err(e) = self.await_waiters(waiters, timeout).await {
+            // Do not treat a reconcile error as fatal: we have already applied any requested
+            // Intent changes, and the reconcile can fail for external reasons like unavailable
+            // compute notification API.  In these cases, it is important that we do not
+            // cause the cloud control plane to retry forever on this API.
+            tracing::warn!(
+                "Failed to reconcile after /location_config: {e}, returning success anyway"
+            );
+        }
+
+        // Logging the full result is useful because it lets us cross-check what the cloud control
+        // plane's tenant_shards table should contain.
+        tracing::info!("Complete, returning {result:?}");
+
+        Ok(result)
+    }
+
+    pub(crate) async fn tenant_config_set(&self, req: TenantConfigRequest) -> Result<(), ApiError> {
+        // We require an exclusive lock, because we are updating persistent and in-memory state
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            req.tenant_id,
+            TenantOperations::ConfigSet,
+        )
+        .await;
+
+        let tenant_id = req.tenant_id;
+        let config = req.config;
+
+        self.persistence
+            .update_tenant_shard(
+                TenantFilter::Tenant(req.tenant_id),
+                None,
+                Some(config.clone()),
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let waiters = {
+            let mut waiters = Vec::new();
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, _scheduler) = locked.parts_mut();
+            for (_shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
+                shard.config = config.clone();
+                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                    waiters.push(waiter);
+                }
+            }
+            waiters
+        };
+
+        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
+            // Treat this as success because we have stored the configuration.  If e.g.
+            // a node was unavailable at this time, it should not stop us accepting a
+            // configuration change.
+            tracing::warn!(%tenant_id, "Accepted configuration update but reconciliation failed: {e}");
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn tenant_config_get(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<HashMap<&str, serde_json::Value>, ApiError> {
+        let config = {
+            let locked = self.inner.read().unwrap();
+
+            match locked
+                .tenants
+                .range(TenantShardId::tenant_range(tenant_id))
+                .next()
+            {
+                Some((_tenant_shard_id, shard)) => shard.config.clone(),
+                None => {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Tenant not found").into(),
+                    ))
+                }
+            }
+        };
+
+        // Unlike the pageserver, we do not have a set of global defaults: the config is
+        // entirely per-tenant.  Therefore the distinction between `tenant_specific_overrides`
+        // and `effective_config` in the response is meaningless, but we retain that syntax
+        // in order to remain compatible with the pageserver API.
+
+        let response = HashMap::from([
+            (
+                "tenant_specific_overrides",
+                serde_json::to_value(&config)
+                    .context("serializing tenant specific overrides")
+                    .map_err(ApiError::InternalServerError)?,
+            ),
+            (
+                "effective_config",
+                serde_json::to_value(&config)
+                    .context("serializing effective config")
+                    .map_err(ApiError::InternalServerError)?,
+            ),
+        ]);
+
+        Ok(response)
+    }
+
+    pub(crate) async fn tenant_time_travel_remote_storage(
+        &self,
+        time_travel_req: &TenantTimeTravelRequest,
+        tenant_id: TenantId,
+        timestamp: Cow<'_, str>,
+        done_if_after: Cow<'_, str>,
+    ) -> Result<(), ApiError> {
+        validate_time_travel_timestamps(&timestamp, &done_if_after)?;
+
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimeTravelRemoteStorage,
+        )
+        .await;
+
+        // The shard count is encoded in the remote storage's URL, so we need to handle all historically used shard counts
+        let counts = time_travel_req
+            .shard_counts
+            .iter()
+            .copied()
+            .collect::<HashSet<_>>();
+        let shard_ids = counts.into_iter().flat_map(|count| {
+            (0..count.count()).map(move |i| TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(i),
+                shard_count: count,
+            })
+        });
+
+        let assignments = {
+            let locked = self.inner.read().unwrap();
+            // Just a sanity check to prevent misuse: the API expects that the tenant is fully
+            // detached everywhere, and nothing writes to S3 storage. Here, we verify that,
+            // but only at the start of the process, so it's really just to prevent operator
+            // mistakes.
+            for (shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id)) {
+                if shard.intent.get_attached().is_some() || !shard.intent.get_secondary().is_empty()
+                {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "We want tenant to be attached in shard with tenant_shard_id={shard_id}"
+                    )));
+                }
+                let maybe_attached = shard
+                    .observed
+                    .locations
+                    .iter()
+                    .filter_map(|(node_id, observed_location)| {
+                        observed_location
+                            .conf
+                            .as_ref()
+                            .map(|loc| (node_id, observed_location, loc.mode))
+                    })
+                    .find(|(_, _, mode)| *mode != LocationConfigMode::Detached);
+                if let Some((node_id, _observed_location, mode)) = maybe_attached {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!("We observed attached={mode:?} tenant in node_id={node_id} shard with tenant_shard_id={shard_id}")));
+                }
+            }
+            spread_over_available_nodes(&locked.scheduler, &locked.nodes, shard_ids)?
+        };
+
+        // All nodes work at once.  The first error abandons the whole operation.
+        let mut futs = assignments
+            .into_iter()
+            .map(|(node, shard_ids)| {
+                self.time_travel_on_node(node, shard_ids, &timestamp, &done_if_after)
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        while let Some(result) = futs.next().await {
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Time travel recovery for the shards assigned to one node, a few at a time
+    async fn time_travel_on_node(
+        &self,
+        node: Node,
+        shard_ids: Vec<TenantShardId>,
+        timestamp: &str,
+        done_if_after: &str,
+    ) -> Result<(), ApiError> {
+        let client = PageserverClient::new(
+            node.get_id(),
+            node.base_url(),
+            self.config.jwt_token.as_deref(),
+        );
+
+        futures::stream::iter(shard_ids)
+            .map(|tenant_shard_id| {
+                let client = &client;
+                let node = &node;
+                async move {
+                    tracing::info!("Doing time travel recovery for shard {tenant_shard_id}");
+
+                    client
+                        .tenant_time_travel_remote_storage(tenant_shard_id, timestamp, done_if_after)
+                        .await
+                        .map_err(|e| {
+                            ApiError::InternalServerError(anyhow::anyhow!(
+                                "Error doing time travel recovery for shard {tenant_shard_id} on node {node}: {e}"
+                            ))
+                        })
+                }
+            })
+            .buffer_unordered(TIME_TRAVEL_CONCURRENCY_PER_NODE)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn tenant_secondary_download(
+        &self,
+        tenant_id: TenantId,
+        wait: Option<Duration>,
+    ) -> Result<(StatusCode, TenantSecondaryDownloadResponse), ApiError> {
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::SecondaryDownload,
+        )
+        .await;
+
+        // Acquire lock and yield the collection of shard-node tuples which we will send requests onward to
+        let targets = {
+            let locked = self.inner.read().unwrap();
+            let mut targets = Vec::new();
+
+            for (tenant_shard_id, shard) in
+                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+            {
+                for node_id in shard.intent.get_secondary() {
+                    let node = locked
+                        .nodes
+                        .get(node_id)
+                        .expect("Pageservers may not be deleted while referenced");
+
+                    targets.push((*tenant_shard_id, node.clone()));
+                }
+            }
+            targets
+        };
+
+        // Issue concurrent requests to all shards' locations
+        let mut futs = FuturesUnordered::new();
+        for (tenant_shard_id, node) in targets {
+            let client = PageserverClient::new(
+                node.get_id(),
+                node.base_url(),
+                self.config.jwt_token.as_deref(),
+            );
+            futs.push(async move {
+                let result = client
+                    .tenant_secondary_download(tenant_shard_id, wait)
+                    .await;
+                (result, node, tenant_shard_id)
+            })
+        }
+
+        // Handle any errors returned by pageservers.  This includes cases like this request racing with
+        // a scheduling operation, such that the tenant shard we're calling doesn't exist on that pageserver any more, as
+        // well as more general cases like 503s, 500s, or timeouts.
+        let mut shard_progress = Vec::new();
+        let mut aggregate_status: Option<StatusCode> = None;
+        let mut error: Option<mgmt_api::Error> = None;
+        while let Some((result, node, tenant_shard_id)) = futs.next().await {
+            match result {
+                Err(e) => {
+                    // Secondary downloads are always advisory: if something fails, we nevertheless report success, so that whoever
+                    // is calling us will proceed with whatever migration they're doing, albeit with a slightly less warm cache
+                    // than they had hoped for.
+                    tracing::warn!("Secondary download error from pageserver {node}: {e}",);
+                    error = Some(e)
+                }
+                Ok((status_code, progress)) => {
+                    tracing::info!(%tenant_shard_id, "Shard status={status_code} progress: {progress:?}");
+                    shard_progress.push((tenant_shard_id, progress));
+                    aggregate_status = match aggregate_status {
+                        None => Some(status_code),
+                        Some(StatusCode::OK) => Some(status_code),
+                        Some(cur) => {
+                            // Other status codes (e.g. 202) -- do not overwrite.
+                            Some(cur)
+                        }
+                    };
+                }
+            }
+        }
+
+        // If any of the shards return 202, indicate our result as 202.
+        match aggregate_status {
+            None => {
+                match error {
+                    Some(e) => {
+                        // No successes, and an error: surface it
+                        Err(ApiError::Conflict(format!("Error from pageserver: {e}")))
+                    }
+                    None => {
+                        // No shards found
+                        Err(ApiError::NotFound(
+                            anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
+                        ))
+                    }
+                }
+            }
+            Some(aggregate_status) => Ok((
+                aggregate_status,
+                aggregate_secondary_progress(&shard_progress),
+            )),
+        }
+    }
+
+    pub(crate) async fn tenant_delete(&self, tenant_id: TenantId) -> Result<StatusCode, ApiError> {
+        let _tenant_lock =
+            trace_exclusive_lock(&self.tenant_op_locks, tenant_id, TenantOperations::Delete).await;
+
+        // Detach all shards
+        let (detach_waiters, shard_ids, delete_nodes) = {
+            let mut shard_ids = Vec::new();
+            let mut detach_waiters = Vec::new();
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+            for (tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
+            {
+                shard_ids.push(*tenant_shard_id);
+
+                // Update the tenant's intent to remove all attachments
+                shard.policy = PlacementPolicy::Detached;
+                shard
+                    .schedule(scheduler, &mut ScheduleContext::default())
+                    .expect("De-scheduling is infallible");
+                debug_assert!(shard.intent.get_attached().is_none());
+                debug_assert!(shard.intent.get_secondary().is_empty());
+
+                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                    detach_waiters.push(waiter);
+                }
+            }
+
+            // Pick nodes to use for remote deletions (they do not have to be where the tenant was
+            // attached, just have to be able to see the S3 content)
+            let delete_nodes = available_nodes_by_preference(scheduler, nodes)?;
+            (detach_waiters, shard_ids, delete_nodes)
+        };
+
+        // This reconcile wait can fail in a few ways:
+        //  A there is a very long queue for the reconciler semaphore
+        //  B some pageserver is failing to handle a detach promptly
+        //  C some pageserver goes offline right at the moment we send it a request.
+        //
+        // A and C are transient: the semaphore will eventually become available, and once a node is marked offline
+        // the next attempt to reconcile will silently skip detaches for an offline node and succeed.  If B happens,
+        // it's a bug, and needs resolving at the pageserver level (we shouldn't just leave attachments behind while
+        // deleting the underlying data).
+        self.await_waiters(detach_waiters, RECONCILE_TIMEOUT)
+            .await?;
+
+        // If a node is unreachable, fall back to the next one.  Remote deletion is idempotent, so
+        // it is safe to repeat for all shards, including any that the unreachable node deleted.
+        let mut unreachable = None;
+        for node in &delete_nodes {
+            let locations = shard_ids
+                .iter()
+                .map(|s| (*s, node.clone()))
+                .collect::<Vec<_>>();
+            let results = self
+                .tenant_for_shards_api(
+                    locations,
+                    |tenant_shard_id, client| async move {
+                        client.tenant_delete(tenant_shard_id).await
+                    },
+                    1,
+                    3,
+                    RECONCILE_TIMEOUT,
+                    &self.cancel,
+                )
+                .await;
+
+            unreachable = None;
+            for result in results {
+                match result {
+                    Ok(StatusCode::ACCEPTED) => {
+                        // This should never happen: we waited for detaches to finish above
+                        return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                            "Unexpectedly still attached on {}",
+                            node
+                        )));
+                    }
+                    Ok(_) => {}
+                    Err(mgmt_api::Error::Cancelled) if self.cancel.is_cancelled() => {
+                        return Err(ApiError::ShuttingDown);
+                    }
+                    Err(e) if node_unreachable(&e) => {
+                        unreachable = Some(e);
+                    }
+                    Err(e) => {
+                        // This is unexpected: remote deletion should be infallible, unless the
+                        // object store at large is unavailable.
+                        tracing::error!("Error deleting via node {}: {e}", node);
+                        return Err(ApiError::InternalServerError(anyhow::anyhow!(e)));
+                    }
+                }
+            }
+
+            match &unreachable {
+                None => break,
+                Some(e) => {
+                    tracing::warn!("Node {node} unreachable for remote deletion: {e}");
+                }
+            }
+        }
+
+        if let Some(e) = unreachable {
+            return Err(ApiError::ResourceUnavailable(
+                format!(
+                    "no node was reachable for remote deletion (tried {}): {e}",
+                    delete_nodes.len()
+                )
+                .into(),
+            ));
+        }
+
+        // Fall through: deletion of the tenant on pageservers is complete, we may proceed to drop
+        // our in-memory state and database state.
+
+        // Ordering: we delete persistent state first: if we then
+        // crash, we will drop the in-memory state.
+
+        // Drop persistent state.
+        self.persistence.delete_tenant(tenant_id).await?;
+
+        // Drop in-memory state
+        {
+            let mut locked = self.inner.write().unwrap();
+            let (_nodes, tenants, scheduler) = locked.parts_mut();
+
+            // Dereference Scheduler from shards before dropping them
+            for (_tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
+            {
+                shard.intent.clear(scheduler);
+            }
+
+            tenants.retain(|tenant_shard_id, _shard| tenant_shard_id.tenant_id != tenant_id);
+            locked.tenant_sizes.remove(&tenant_id);
+            tracing::info!(
+                "Deleted tenant {tenant_id}, now have {} tenants",
+                locked.tenants.len()
+            );
+        };
+
+        // Success is represented as 404, to imitate the existing pageserver deletion API
+        Ok(StatusCode::NOT_FOUND)
+    }
+
+    /// Naming: this configures the storage controller's policies for a tenant, whereas [`Self::tenant_config_set`] is "set the TenantConfig"
+    /// for a tenant.  The TenantConfig is passed through to pageservers, whereas this function modifies
+    /// the tenant's policies (configuration) within the storage controller
+    pub(crate) async fn tenant_update_policy(
+        &self,
+        tenant_id: TenantId,
+        req: TenantPolicyRequest,
+    ) -> Result<(), ApiError> {
+        // We require an exclusive lock, because we are updating persistent and in-memory state
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::UpdatePolicy,
+        )
+        .await;
+
+        failpoint_support::sleep_millis_async!("tenant-update-policy-exclusive-lock");
+
+        let TenantPolicyRequest {
+            placement,
+            scheduling,
+            priority,
+        } = req;
+
+        if let Some(placement) = &placement {
+            check_secondaries_limit(placement, self.config.max_secondaries_per_shard)?;
+        }
+
+        self.persistence
+            .update_tenant_shard(
+                TenantFilter::Tenant(tenant_id),
+                placement.clone(),
+                None,
+                None,
+                scheduling,
+                priority,
+            )
+            .await?;
+
+        let mut schedule_context = ScheduleContext::default();
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        for (shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
+            if let Some(placement) = &placement {
+                shard.policy = placement.clone();
+
+                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                               "Updated placement policy to {placement:?}");
+            }
+
+            if let Some(scheduling) = &scheduling {
+                shard.set_scheduling_policy(*scheduling);
+
+                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                               "Updated scheduling policy to {scheduling:?}");
+            }
+
+            if let Some(priority) = priority {
+                shard.priority = priority;
+
+                tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                               "Updated reconcile priority to {priority:?}");
+            }
+
+            // In case scheduling is being switched back on, try it now.
+            shard.schedule(scheduler, &mut schedule_context).ok();
+            self.maybe_reconcile_shard(shard, nodes);
+        }
+
+        Ok(())
+    }
+
+    /// Pin a shard's locations against the optimizer, or release the pin.  A sticky shard is still
+    /// rescheduled when needed for correctness (e.g. its node fails), and may be migrated manually.
+    pub(crate) async fn tenant_shard_set_sticky(
+        &self,
+        tenant_shard_id: TenantShardId,
+        sticky: bool,
+    ) -> Result<(), ApiError> {
+        self.set_flag(
+            TenantFilter::Shard(tenant_shard_id),
+            TenantFlag::Sticky,
+            sticky,
+        )
+        .await
+    }
+
+    /// Quarantine one shard of a sharded tenant, e.g. to repair it, by setting its placement policy to
+    /// [`PlacementPolicy::Detached`]: its locations are removed from all pageservers, while its sibling
+    /// shards stay attached.  [`Self::tenant_shard_reattach`] restores it.
+    ///
+    /// Tenant-wide operations such as timeline creation go to shard zero first, so detaching shard
+    /// zero requires `force`.
+    pub(crate) async fn tenant_shard_detach(
+        &self,
+        tenant_shard_id: TenantShardId,
+        force: bool,
+    ) -> Result<(), ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::ShardDetach,
+        )
+        .await;
+
+        if !self
+            .inner
+            .read()
+            .unwrap()
+            .tenants
+            .contains_key(&tenant_shard_id)
+        {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant shard not found").into(),
+            ));
+        }
+
+        if tenant_shard_id.shard_count.count() <= 1 {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Tenant is not sharded: update its placement policy to detach it"
+            )));
+        }
+
+        if tenant_shard_id.is_shard_zero() {
+            if !force {
+                return Err(ApiError::PreconditionFailed(
+                    "Tenant-wide operations depend on shard zero: set force to detach it anyway"
+                        .into(),
+                ));
+            }
+            tracing::warn!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                "Detaching shard zero: tenant-wide operations will fail until it is reattached");
+        }
+
+        self.set_shard_placement_policy(tenant_shard_id, PlacementPolicy::Detached)
+            .await
+    }
+
+    /// Undo [`Self::tenant_shard_detach`]: the shard gets the same placement policy as its siblings.
+    pub(crate) async fn tenant_shard_reattach(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<(), ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::ShardDetach,
+        )
+        .await;
+
+        let policy = {
+            let locked = self.inner.read().unwrap();
+            if !locked.tenants.contains_key(&tenant_shard_id) {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            }
+
+            locked
+                .tenants
+                .range(TenantShardId::tenant_range(tenant_shard_id.tenant_id))
+                .map(|(_id, shard)| &shard.policy)
+                .find(|policy| !matches!(policy, PlacementPolicy::Detached))
+                .cloned()
+                .ok_or_else(|| {
+                    ApiError::PreconditionFailed(
+                        "No shard of the tenant is attached: update the tenant's placement policy instead"
+                            .into(),
+                    )
+                })?
+        };
+
+        self.set_shard_placement_policy(tenant_shard_id, policy)
+            .await
+    }
+
+    /// Persist and apply a placement policy for one shard, and wait for it to be reconciled.  The
+    /// caller must hold the tenant's exclusive lock.
+    async fn set_shard_placement_policy(
+        &self,
+        tenant_shard_id: TenantShardId,
+        policy: PlacementPolicy,
+    ) -> Result<(), ApiError> {
+        self.persistence
+            .update_tenant_shard(
+                TenantFilter::Shard(tenant_shard_id),
+                Some(policy.clone()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await?;
+
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            // Spread the shard's locations away from its siblings', as if they were scheduled together
+            let mut schedule_context = ScheduleContext::default();
+            for (_id, sibling) in tenants
+                .range(TenantShardId::tenant_range(tenant_shard_id.tenant_id))
+                .filter(|(id, _)| **id != tenant_shard_id)
+            {
+                schedule_context.avoid(&sibling.intent.all_pageservers());
+                if let Some(attached) = sibling.intent.get_attached() {
+                    schedule_context.push_attached(*attached);
+                }
+            }
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            tracing::info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                           "Updated placement policy to {policy:?}");
+            shard.policy = policy;
+            shard.schedule(scheduler, &mut schedule_context)?;
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            self.await_waiters(vec![waiter], SHORT_RECONCILE_TIMEOUT)
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Set or clear one of a tenant's flags on all its shards.  Flags only affect future scheduling
+    /// decisions: the tenant's existing locations are not moved.
+    pub(crate) async fn tenant_set_flag(
+        &self,
+        tenant_id: TenantId,
+        flag: TenantFlag,
+        value: bool,
+    ) -> Result<(), ApiError> {
+        self.set_flag(TenantFilter::Tenant(tenant_id), flag, value)
+            .await
+    }
+
+    /// Persist a flag for a whole tenant or a single shard, then apply it in memory.
+    async fn set_flag(
+        &self,
+        filter: TenantFilter,
+        flag: TenantFlag,
+        value: bool,
+    ) -> Result<(), ApiError> {
+        let (tenant_id, range) = match filter {
+            TenantFilter::Tenant(tenant_id) => (tenant_id, TenantShardId::tenant_range(tenant_id)),
+            TenantFilter::Shard(tenant_shard_id) => {
+                (tenant_shard_id.tenant_id, tenant_shard_id..=tenant_shard_id)
+            }
+        };
+
+        let _tenant_lock =
+            trace_exclusive_lock(&self.tenant_op_locks, tenant_id, TenantOperations::SetFlag).await;
+
+        if self
+            .inner
+            .read()
+            .unwrap()
+            .tenants
+            .range(range.clone())
+            .next()
+            .is_none()
+        {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        }
+
+        self.persistence
+            .set_tenant_flag(filter, flag, value)
+            .await?;
+
+        let mut locked = self.inner.write().unwrap();
+        for (shard_id, shard) in locked.tenants.range_mut(range) {
+            tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                           "Setting {flag}={value}");
+            shard.set_flag(flag, value);
+        }
+
+        Ok(())
+    }
+
+    /// Disaster recovery: overwrite a shard's generation with one that an operator knows to be
+    /// correct, for example after restoring the controller's database from a backup that is older
+    /// than the generations found in remote storage.  The generation is persisted, and then the
+    /// shard is reconciled so that its attached pageserver learns a generation at least this high.
+    ///
+    /// Moving a generation backwards is dangerous, so it is refused unless `force` is set.
+    pub(crate) async fn tenant_shard_set_generation(
+        &self,
+        tenant_shard_id: TenantShardId,
+        generation: Generation,
+        force: bool,
+    ) -> Result<(), ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::SetGeneration,
+        )
+        .await;
+
+        let current = {
+            let locked = self.inner.read().unwrap();
+            let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            check_set_generation(shard, generation, force)?;
+
+            tracing::warn!(
+                tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                "Overwriting generation {:?} with {generation:?} (force={force})",
+                shard.generation
+            );
+            shard.generation
+        };
+
+        // Only overwrite the generation we checked above: a reconciler might have incremented it
+        // since.
+        if !self
+            .persistence
+            .set_generation(tenant_shard_id, current, generation)
+            .await?
+        {
+            return Err(ApiError::PreconditionFailed(
+                "Generation changed concurrently: check it and retry".into(),
+            ));
+        }
+
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, _scheduler) = locked.parts_mut();
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            if shard.reconciler.is_some() {
+                // A reconcile started while we were writing the generation, and its result would
+                // overwrite it.  The database write is idempotent, so the operator can retry.
+                return Err(ApiError::PreconditionFailed(
+                    "A reconcile started concurrently: cancel reconciles and retry".into(),
+                ));
+            }
+
+            shard.override_generation(generation);
+            shard.sequence = shard.sequence.next();
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter.wait_timeout(RECONCILE_TIMEOUT).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Incident recovery: issue a new generation for a shard's attached node, so that whichever
+    /// pageserver holds the old generation can no longer validate writes with it, even if we
+    /// cannot reach that pageserver.  The shard is then reconciled so that the attached pageserver
+    /// learns a current generation: the reconciler may issue a further generation to do so.
+    pub(crate) async fn tenant_shard_increment_generation(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<TenantShardIncrementGenerationResponse, ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::IncrementGeneration,
+        )
+        .await;
+
+        let node_id = {
+            let locked = self.inner.read().unwrap();
+            let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            let Some(node_id) = *shard.intent.get_attached() else {
+                return Err(ApiError::PreconditionFailed(
+                    "Tenant shard has no attached location".into(),
+                ));
+            };
+            node_id
+        };
+
+        self.frozen_generations
+            .check(tenant_shard_id.tenant_id)
+            .map_err(|e| ApiError::PreconditionFailed(e.to_string().into()))?;
+
+        let generation = self
+            .persistence
+            .increment_generation(tenant_shard_id, node_id)
+            .await
+            .map_err(|e| match e.downcast::<DatabaseError>() {
+                Ok(e) => ApiError::from(e),
+                Err(e) => ApiError::InternalServerError(e),
+            })?;
+
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, _scheduler) = locked.parts_mut();
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            tracing::warn!(
+                tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                "Incremented generation from {:?} to {generation:?} for node {node_id}",
+                shard.generation
+            );
+
+            // Any reconciler still running with an older generation will refuse to use it
+            shard.advance_generation(generation);
+            shard.sequence = shard.sequence.next();
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter.wait_timeout(RECONCILE_TIMEOUT).await?;
+        }
+
+        Ok(TenantShardIncrementGenerationResponse {
+            generation: generation.into().unwrap(),
+            node_id,
+        })
+    }
+
+    /// Prevent any generation increments for a tenant's shards, or allow them again.  While frozen,
+    /// attach hook calls and reconciles that would increment a generation fail, and re-attach
+    /// calls from pageservers holding the tenant are refused (the pageserver retries them until
+    /// the tenant is unfrozen).  Operators use this as a guard while transferring authority over a
+    /// tenant's generations into or out of this controller: see [`Self::tenant_location_config`].
+    ///
+    /// Freezes are only held in memory: **restarting the storage controller unfreezes all
+    /// tenants**, so operators must freeze again after a restart if a transfer is still ongoing.
+    pub(crate) fn tenant_generation_freeze(&self, tenant_id: TenantId, frozen: bool) {
+        tracing::info!(%tenant_id, "Setting generation frozen={frozen}");
+        self.frozen_generations.set(tenant_id, frozen);
+    }
+
+    /// Cancel all in-flight reconciles for a tenant's shards, without changing their intent.  This
+    /// is for use by operators before manual interventions: to prevent new reconciles starting, set
+    /// the tenant's scheduling policy to [`ShardSchedulingPolicy::Stop`].
+    pub(crate) fn tenant_cancel_reconciles(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantCancelReconcilesResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        let mut shards = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .peekable();
+        if shards.peek().is_none() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        }
+
+        let cancelled = shards
+            .filter(|(_shard_id, shard)| shard.cancel_reconciler())
+            .count();
+        tracing::info!("Cancelled {cancelled} reconcilers for tenant {tenant_id}");
+
+        Ok(TenantCancelReconcilesResponse { cancelled })
+    }
+
+    /// Cancel a shard's in-flight reconcile, if it has one, and wait until the reconciler has
+    /// stopped and its result has been applied.  Use this to unblock a shard whose reconciler is
+    /// stuck, e.g. on a slow node, before migrating it elsewhere.  As with
+    /// [`Self::tenant_cancel_reconciles`], the shard's intent is unchanged, so it may be
+    /// reconciled again unless its scheduling policy prevents it.
+    pub(crate) async fn tenant_shard_cancel_reconcile(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<TenantShardCancelReconcileResponse, ApiError> {
+        let wait = {
+            let locked = self.inner.read().unwrap();
+            let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard {tenant_shard_id} not found").into(),
+                ));
+            };
+            shard.cancel_reconciler_and_wait()
+        };
+
+        let cancelled = wait.is_some();
+        if let Some(wait) = wait {
+            tokio::select! {
+                _ = wait => {}
+                _ = tokio::time::sleep(RECONCILE_TIMEOUT) => {
+                    return Err(ApiError::Timeout(
+                        format!("Timed out waiting for reconciler of {tenant_shard_id} to stop")
+                            .into(),
+                    ));
+                }
+                _ = self.cancel.cancelled() => return Err(ApiError::ShuttingDown),
+            }
+            tracing::info!("Cancelled reconciler for {tenant_shard_id}");
+        }
+
+        let locked = self.inner.read().unwrap();
+        let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant shard {tenant_shard_id} not found").into(),
+            ));
+        };
+        Ok(TenantShardCancelReconcileResponse {
+            cancelled,
+            observed: observed_locations(shard),
+        })
+    }
+
+    pub(crate) async fn tenant_timeline_create(
+        &self,
+        tenant_id: TenantId,
+        mut create_req: TimelineCreateRequest,
+    ) -> Result<TimelineInfo, ApiError> {
+        tracing::info!(
+            "Creating timeline {}/{}",
+            tenant_id,
+            create_req.new_timeline_id,
+        );
+
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineCreate,
+        )
+        .await;
+        failpoint_support::sleep_millis_async!("tenant-create-timeline-shared-lock");
+
+        self.ensure_attached_wait(tenant_id).await?;
+
+        let mut targets = {
+            let locked = self.inner.read().unwrap();
+            let mut targets = Vec::new();
+
+            for (tenant_shard_id, shard) in
+                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+            {
+                let node_id = shard.intent.get_attached().ok_or_else(|| {
+                    ApiError::InternalServerError(anyhow::anyhow!("Shard not scheduled"))
+                })?;
+                let node = locked
+                    .nodes
+                    .get(&node_id)
+                    .expect("Pageservers may not be deleted while referenced");
+
+                targets.push((*tenant_shard_id, node.clone()));
+            }
+            targets
+        };
+
+        if targets.is_empty() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant not found").into(),
+            ));
+        };
+        let shard_zero = targets.remove(0);
+
+        async fn create_one(
+            tenant_shard_id: TenantShardId,
+            node: Node,
+            jwt: Option<String>,
+            create_req: TimelineCreateRequest,
+            cancel: CancellationToken,
+        ) -> Result<TimelineInfo, ApiError> {
+            tracing::info!(
+                "Creating timeline on shard {}/{}, attached to node {node}",
+                tenant_shard_id,
+                create_req.new_timeline_id,
+            );
+            let client = PageserverClient::new(node.get_id(), node.base_url(), jwt.as_deref());
+
+            tokio::select! {
+                r = client.timeline_create(tenant_shard_id, &create_req) => {
+                    r.map_err(|e| passthrough_api_error(&node, e))
+                }
+                _ = cancel.cancelled() => Err(ApiError::Cancelled),
+            }
+        }
+
+        // Because the caller might not provide an explicit LSN, we must do the creation first on a single shard, and then
+        // use whatever LSN that shard picked when creating on subsequent shards.  We arbitrarily use shard zero as the shard
+        // that will get the first creation request, and propagate the LSN to all the >0 shards.
+        //
+        // If this is a retry, shard zero may already have the timeline: timeline creation on the
+        // pageserver is idempotent, returning the existing timeline (or a conflict if its
+        // parameters differ), so the other shards still get the LSN that shard zero picked first.
+        let timeline_info = create_one(
+            shard_zero.0,
+            shard_zero.1,
+            self.config.jwt_token.clone(),
+            create_req.clone(),
+            self.cancel.clone(),
+        )
+        .await?;
+
+        // Propagate the LSN that shard zero picked, if caller didn't provide one
+        if create_req.ancestor_timeline_id.is_some() && create_req.ancestor_start_lsn.is_none() {
+            create_req.ancestor_start_lsn = timeline_info.ancestor_lsn;
+        }
+
+        // Create timeline on remaining shards with number >0
+        if !targets.is_empty() {
+            // If we had multiple shards, issue requests for the remainder now.
+            let jwt = self.config.jwt_token.clone();
+            self.tenant_for_shards(
+                targets,
+                &self.cancel,
+                |tenant_shard_id: TenantShardId, node: Node, cancel: CancellationToken| {
+                    let create_req = create_req.clone();
+                    Box::pin(create_one(
+                        tenant_shard_id,
+                        node,
+                        jwt.clone(),
+                        create_req,
+                        cancel,
+                    ))
+                },
+            )
+            .await?;
+        }
+
+        Ok(timeline_info)
+    }
+
+    /// Helper for concurrently calling a pageserver API on a number of shards, such as timeline creation.
+    ///
+    /// On success, the returned vector contains exactly the same number of elements as the input `locations`.
+    /// On the first error, the token passed to `req_fn` is cancelled, and the remaining requests
+    /// are awaited before returning the error: none is still running when the caller handles it.
+    async fn tenant_for_shards<F, R>(
+        &self,
+        locations: Vec<(TenantShardId, Node)>,
+        cancel: &CancellationToken,
+        mut req_fn: F,
+    ) -> Result<Vec<R>, ApiError>
+    where
+        F: FnMut(
+            TenantShardId,
+            Node,
+            CancellationToken,
+        )
+            -> std::pin::Pin<Box<dyn futures::Future<Output = Result<R, ApiError>> + Send>>,
+    {
+        // Cancelling this stops the remaining requests, without affecting the caller's token
+        let cancel = cancel.child_token();
+        let mut futs = FuturesUnordered::new();
+        let mut results = Vec::with_capacity(locations.len());
+
+        for (tenant_shard_id, node) in locations {
+            futs.push(req_fn(tenant_shard_id, node, cancel.clone()));
+        }
+
+        while let Some(r) = futs.next().await {
+            match r {
+                Ok(r) => results.push(r),
+                Err(e) => {
+                    cancel.cancel();
+                    while futs.next().await.is_some() {}
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Concurrently invoke a pageserver API call on many shards at once
+    pub(crate) async fn tenant_for_shards_api<T, O, F>(
+        &self,
+        locations: Vec<(TenantShardId, Node)>,
+        op: O,
+        warn_threshold: u32,
+        max_retries: u32,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Vec<mgmt_api::Result<T>>
+    where
+        O: Fn(TenantShardId, PageserverClient) -> F + Copy,
+        F: std::future::Future<Output = mgmt_api::Result<T>>,
+    {
+        let mut futs = FuturesUnordered::new();
+        let mut results = Vec::with_capacity(locations.len());
+
+        for (tenant_shard_id, node) in locations {
+            futs.push(async move {
+                node.with_client_retries(
+                    |client| op(tenant_shard_id, client),
+                    &self.config.jwt_token,
+                    warn_threshold,
+                    max_retries,
+                    timeout,
+                    cancel,
+                )
+                .await
+            });
+        }
+
+        while let Some(r) = futs.next().await {
+            let r = r.unwrap_or(Err(mgmt_api::Error::Cancelled));
+            results.push(r);
+        }
+
+        results
+    }
+
+    pub(crate) async fn tenant_timeline_delete(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+    ) -> Result<StatusCode, ApiError> {
+        tracing::info!("Deleting timeline {}/{}", tenant_id, timeline_id,);
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineDelete,
+        )
+        .await;
+
+        self.ensure_attached_wait(tenant_id).await?;
+
+        let mut targets = {
+            let locked = self.inner.read().unwrap();
+            let mut targets = Vec::new();
+
+            for (tenant_shard_id, shard) in
+                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+            {
+                let node_id = shard.intent.get_attached().ok_or_else(|| {
+                    ApiError::InternalServerError(anyhow::anyhow!("Shard not scheduled"))
+                })?;
+                let node = locked
+                    .nodes
+                    .get(&node_id)
+                    .expect("Pageservers may not be deleted while referenced");
+
+                targets.push((*tenant_shard_id, node.clone()));
+            }
+            targets
+        };
+
+        if targets.is_empty() {
+            return Err(ApiError::NotFound(
                 anyhow::anyhow!("Tenant not found").into(),
             ));
         }
-        let shard_zero = targets.remove(0);
+        let shard_zero = targets.remove(0);
+
+        async fn delete_one(
+            tenant_shard_id: TenantShardId,
+            timeline_id: TimelineId,
+            node: Node,
+            jwt: Option<String>,
+            cancel: CancellationToken,
+        ) -> Result<StatusCode, ApiError> {
+            tracing::info!(
+                "Deleting timeline on shard {tenant_shard_id}/{timeline_id}, attached to node {node}",
+            );
+
+            let client = PageserverClient::new(node.get_id(), node.base_url(), jwt.as_deref());
+            tokio::select! {
+                r = client.timeline_delete(tenant_shard_id, timeline_id) => r.map_err(|e| {
+                    ApiError::InternalServerError(anyhow::anyhow!(
+                    "Error deleting timeline {timeline_id} on {tenant_shard_id} on node {node}: {e}",
+                ))
+                }),
+                _ = cancel.cancelled() => Err(ApiError::Cancelled),
+            }
+        }
+
+        let statuses = self
+            .tenant_for_shards(
+                targets,
+                &self.cancel,
+                |tenant_shard_id: TenantShardId, node: Node, cancel: CancellationToken| {
+                    Box::pin(delete_one(
+                        tenant_shard_id,
+                        timeline_id,
+                        node,
+                        self.config.jwt_token.clone(),
+                        cancel,
+                    ))
+                },
+            )
+            .await?;
+
+        // If any shards >0 haven't finished deletion yet, don't start deletion on shard zero
+        if statuses.iter().any(|s| s != &StatusCode::NOT_FOUND) {
+            return Ok(StatusCode::ACCEPTED);
+        }
+
+        // Delete shard zero last: this is not strictly necessary, but since a caller's GET on a timeline will be routed
+        // to shard zero, it gives a more obvious behavior that a GET returns 404 once the deletion is done.
+        let shard_zero_status = delete_one(
+            shard_zero.0,
+            timeline_id,
+            shard_zero.1,
+            self.config.jwt_token.clone(),
+            self.cancel.clone(),
+        )
+        .await?;
+
+        Ok(shard_zero_status)
+    }
+
+    /// When you need to send an HTTP request to the pageserver that holds shard0 of a tenant, this
+    /// function looks up and returns node. If the tenant isn't found, returns Err(ApiError::NotFound)
+    pub(crate) fn tenant_shard0_node(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<(Node, TenantShardId), ApiError> {
+        let locked = self.inner.read().unwrap();
+        let Some((tenant_shard_id, shard)) = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .next()
+        else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        };
+
+        // TODO: should use the ID last published to compute_hook, rather than the intent: the intent might
+        // point to somewhere we haven't attached yet.  [`Self::tenant_routing_status`] reports tenants
+        // where the two differ.
+        let Some(node_id) = shard.intent.get_attached() else {
+            tracing::warn!(
+                tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                "Shard not scheduled (policy {:?}), cannot generate pass-through URL",
+                shard.policy
+            );
+            return Err(ApiError::Conflict(
+                "Cannot call timeline API on non-attached tenant".to_string(),
+            ));
+        };
+
+        let Some(node) = locked.nodes.get(node_id) else {
+            // This should never happen
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Shard refers to nonexistent node"
+            )));
+        };
+
+        Ok((node.clone(), *tenant_shard_id))
+    }
+
+    pub(crate) fn tenant_locate(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantLocateResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+        tracing::info!("Locating shards for tenant {tenant_id}");
+
+        let mut result = Vec::new();
+        let mut read_replicas = Vec::new();
+        let mut shard_params: Option<ShardParameters> = None;
+
+        for (tenant_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+        {
+            let node_id =
+                shard
+                    .intent
+                    .get_attached()
+                    .ok_or(ApiError::BadRequest(anyhow::anyhow!(
+                        "Cannot locate a tenant that is not attached"
+                    )))?;
+
+            let node = locked
+                .nodes
+                .get(&node_id)
+                .expect("Pageservers may not be deleted while referenced");
+
+            result.push(node.shard_location(*tenant_shard_id));
+
+            for node_id in shard.intent.get_read_replicas() {
+                let node = locked
+                    .nodes
+                    .get(node_id)
+                    .expect("Pageservers may not be deleted while referenced");
+                read_replicas.push(node.shard_location(*tenant_shard_id));
+            }
+
+            match &shard_params {
+                None => {
+                    shard_params = Some(ShardParameters {
+                        stripe_size: shard.shard.stripe_size,
+                        count: shard.shard.count,
+                    });
+                }
+                Some(params) => {
+                    if params.stripe_size != shard.shard.stripe_size {
+                        // This should never happen.  We enforce at runtime because it's simpler than
+                        // adding an extra per-tenant data structure to store the things that should be the same
+                        return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                            "Inconsistent shard stripe size parameters!"
+                        )));
+                    }
+                }
+            }
+        }
+
+        if result.is_empty() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("No shards for this tenant ID found").into(),
+            ));
+        }
+        let shard_params = shard_params.expect("result is non-empty, therefore this is set");
+        tracing::info!(
+            "Located tenant {} with params {:?} on shards {}",
+            tenant_id,
+            shard_params,
+            result
+                .iter()
+                .map(|s| format!("{:?}", s))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+
+        Ok(TenantLocateResponse {
+            shards: result,
+            read_replicas,
+            shard_params,
+        })
+    }
+
+    /// Check that a tenant's shards agree on their shard count and stripe size, and that their
+    /// shard numbers are contiguous from zero.  This is a diagnostic for detecting a corrupt shard
+    /// layout (e.g. from a botched split) before it causes routing errors.
+    pub(crate) fn tenant_validate_layout(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        let shards = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .map(|(_id, shard)| shard)
+            .collect::<Vec<_>>();
+        let Some(first) = shards.first() else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        };
+
+        let mut problems = Vec::new();
+        for shard in &shards {
+            if shard.tenant_shard_id.shard_count != first.tenant_shard_id.shard_count {
+                problems.push(format!(
+                    "Shard {} has shard count {}, expected {}",
+                    shard.tenant_shard_id,
+                    shard.tenant_shard_id.shard_count.literal(),
+                    first.tenant_shard_id.shard_count.literal()
+                ));
+            }
+            if shard.shard.stripe_size != first.shard.stripe_size {
+                problems.push(format!(
+                    "Shard {} has stripe size {}, expected {}",
+                    shard.tenant_shard_id, shard.shard.stripe_size.0, first.shard.stripe_size.0
+                ));
+            }
+            if shard.shard.number != shard.tenant_shard_id.shard_number
+                || shard.shard.count != shard.tenant_shard_id.shard_count
+            {
+                problems.push(format!(
+                    "Shard {} has a shard identity that does not match its ID",
+                    shard.tenant_shard_id
+                ));
+            }
+        }
+
+        // Shards are sorted by ID, so with a consistent shard count we expect exactly 0..count
+        let shard_count = first.tenant_shard_id.shard_count;
+        let shard_numbers = shards
+            .iter()
+            .map(|s| s.tenant_shard_id.shard_number.0)
+            .collect::<Vec<_>>();
+        let expect_numbers = (0..shard_count.count()).collect::<Vec<_>>();
+        if shard_numbers != expect_numbers {
+            problems.push(format!(
+                "Shard numbers {shard_numbers:?} are not contiguous for shard count {}",
+                shard_count.literal()
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            for problem in &problems {
+                tracing::error!("Tenant {tenant_id} layout is inconsistent: {problem}");
+            }
+            Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Tenant {tenant_id} layout is inconsistent: {}",
+                problems.join("; ")
+            )))
+        }
+    }
+
+    /// Returns None if the input iterator of shards does not include a shard with number=0
+    fn tenant_describe_impl<'a>(
+        &self,
+        shards: impl Iterator<Item = &'a TenantShard>,
+    ) -> Option<TenantDescribeResponse> {
+        let mut shard_zero = None;
+        let mut describe_shards = Vec::new();
+
+        for shard in shards {
+            if shard.tenant_shard_id.is_shard_zero() {
+                shard_zero = Some(shard);
+            }
+
+            describe_shards.push(TenantDescribeResponseShard {
+                tenant_shard_id: shard.tenant_shard_id,
+                node_attached: *shard.intent.get_attached(),
+                node_secondary: shard.intent.get_secondary().to_vec(),
+                last_error: shard
+                    .last_error
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map(|e| format!("{e}"))
+                    .unwrap_or("".to_string())
+                    .clone(),
+                last_scheduling_error: shard.last_scheduling_error.clone(),
+                is_reconciling: shard.reconciler.is_some(),
+                is_pending_compute_notification: shard.pending_compute_notification,
+                is_splitting: matches!(shard.splitting, SplitState::Splitting),
+                scheduling_policy: *shard.get_scheduling_policy(),
+                sticky: shard.sticky,
+                colocate: shard.colocate,
+                canary: shard.canary,
+                priority: shard.priority,
+                intent_changed_at: shard
+                    .intent
+                    .get_changed_at()
+                    .and_then(|t| SystemTime::now().checked_sub(t.elapsed())),
+            })
+        }
+
+        let shard_zero = shard_zero?;
+
+        Some(TenantDescribeResponse {
+            tenant_id: shard_zero.tenant_shard_id.tenant_id,
+            shards: describe_shards,
+            stripe_size: shard_zero.shard.stripe_size,
+            policy: shard_zero.policy.clone(),
+            config: shard_zero.config.clone(),
+        })
+    }
+
+    pub(crate) fn tenant_describe(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantDescribeResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        self.tenant_describe_impl(
+            locked
+                .tenants
+                .range(TenantShardId::tenant_range(tenant_id))
+                .map(|(_k, v)| v),
+        )
+        .ok_or_else(|| ApiError::NotFound(anyhow::anyhow!("Tenant {tenant_id} not found").into()))
+    }
+
+    fn tenant_reconcile_status(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<Vec<TenantShardReconcileStatus>, ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        let shards = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .map(|(tenant_shard_id, shard)| {
+                let status = match shard.get_reconcile_status() {
+                    ReconcilerStatus::Done => ReconcileStatus::Done,
+                    ReconcilerStatus::Failed => ReconcileStatus::Failed,
+                    ReconcilerStatus::InProgress => ReconcileStatus::InProgress,
+                };
+                let last_error = match status {
+                    ReconcileStatus::Failed => shard
+                        .last_error
+                        .lock()
+                        .unwrap()
+                        .as_ref()
+                        .map(|e| e.to_string()),
+                    _ => None,
+                };
+                TenantShardReconcileStatus {
+                    tenant_shard_id: *tenant_shard_id,
+                    status,
+                    last_error,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if shards.is_empty() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        }
+
+        Ok(shards)
+    }
+
+    /// Long-poll for progress reconciling a tenant: if any of its shards are still reconciling,
+    /// wait until one of them completes or fails (or `timeout` elapses), then return the status of
+    /// all its shards.  If nothing is in progress, returns immediately.  Callers watching a
+    /// migration call this in a loop until every shard is `Done`, instead of polling describe.
+    pub(crate) async fn tenant_reconcile_watch(
+        &self,
+        tenant_id: TenantId,
+        timeout: Duration,
+    ) -> Result<TenantReconcileWatchResponse, ApiError> {
+        // Subscribe before reading status, so that a reconcile completing in between is not missed
+        let mut watch_rx = self.reconcile_watch_tx.subscribe();
+
+        let shards = self.tenant_reconcile_status(tenant_id)?;
+        if !shards
+            .iter()
+            .any(|s| matches!(s.status, ReconcileStatus::InProgress))
+        {
+            return Ok(TenantReconcileWatchResponse { shards });
+        }
+
+        let deadline = Instant::now() + std::cmp::min(timeout, RECONCILE_WATCH_MAX_TIMEOUT);
+        loop {
+            let recv = tokio::select! {
+                r = tokio::time::timeout_at(deadline.into(), watch_rx.recv()) => r,
+                _ = self.cancel.cancelled() => return Err(ApiError::ShuttingDown),
+            };
+            match recv {
+                // Timed out: report status as it stands
+                Err(_) => break,
+                Ok(Ok(tenant_shard_id)) if tenant_shard_id.tenant_id == tenant_id => break,
+                Ok(Ok(_)) => continue,
+                // We may have missed an update for this tenant: re-read its status to be sure
+                Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => break,
+                Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => {
+                    return Err(ApiError::ShuttingDown)
+                }
+            }
+        }
+
+        Ok(TenantReconcileWatchResponse {
+            shards: self.tenant_reconcile_status(tenant_id)?,
+        })
+    }
+
+    /// How many shards are queued waiting for reconciler concurrency units
+    fn delayed_reconcile_queue_depth(&self) -> usize {
+        (MAX_DELAYED_RECONCILES - self.delayed_reconcile_tx.capacity())
+            + (MAX_DELAYED_RECONCILES - self.delayed_reconcile_priority_tx.capacity())
+    }
+
+    /// Background processors whose tasks have exited unexpectedly.  If this is non-empty, the
+    /// controller is not functioning correctly and should be restarted.
+    pub(crate) fn dead_background_processors(&self) -> Vec<Processor> {
+        self.liveness.dead()
+    }
+
+    pub(crate) fn background_processors(&self) -> BackgroundProcessorsResponse {
+        BackgroundProcessorsResponse {
+            processors: self.liveness.status(),
+            delayed_reconciles: self.delayed_reconcile_queue_depth(),
+        }
+    }
+
+    /// Convert a failure waiting for a reconcile into an API error.  If reconciles are queued
+    /// behind the concurrency limit, timeouts become [`ApiError::Busy`] with a hint of when to
+    /// retry, estimated from the queue depth and recent reconcile durations, so that callers
+    /// back off rather than piling on more work.
+    fn reconcile_wait_error(&self, e: ReconcileWaitError) -> ApiError {
+        if !matches!(e, ReconcileWaitError::Timeout(_)) {
+            return e.into();
+        }
+
+        let queue_depth = self.delayed_reconcile_queue_depth();
+        if queue_depth == 0 {
+            return e.into();
+        }
+
+        let reconcile_duration = self
+            .inner
+            .read()
+            .unwrap()
+            .reconcile_duration_avg
+            .unwrap_or(SHORT_RECONCILE_TIMEOUT);
+        let waves = queue_depth.div_ceil(std::cmp::max(self.config.reconciler_concurrency, 1));
+        let retry_after = reconcile_duration
+            .saturating_mul(waves as u32)
+            .clamp(RETRY_AFTER_MIN, RETRY_AFTER_MAX);
+
+        ApiError::Busy(
+            format!("{e} ({queue_depth} reconciles queued)").into(),
+            retry_after,
+        )
+    }
+
+    /// List the shards that are waiting for reconciler concurrency units, and how long they have
+    /// been waiting.  A long list here suggests that `reconciler_concurrency` is too low.
+    pub(crate) fn delayed_reconcile_tenants(&self) -> Vec<DelayedReconcileShard> {
+        let locked = self.inner.read().unwrap();
+
+        locked
+            .tenants
+            .values()
+            .filter(|shard| shard.delayed_reconcile)
+            .map(|shard| DelayedReconcileShard {
+                tenant_shard_id: shard.tenant_shard_id,
+                waiting: shard
+                    .delayed_reconcile_since
+                    .map(|since| since.elapsed())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// How many reconciles completed recently, per minute.  Compare this with the queue of shards
+    /// waiting for reconciler units to tell whether `reconciler_concurrency` keeps up with the
+    /// rate at which intents change.
+    pub(crate) fn reconcile_throughput(&self) -> ReconcileThroughputResponse {
+        let (succeeded_per_minute, failed_per_minute) = self
+            .inner
+            .read()
+            .unwrap()
+            .reconcile_throughput
+            .per_minute(Instant::now());
+
+        ReconcileThroughputResponse {
+            window: RECONCILE_THROUGHPUT_WINDOW,
+            succeeded_per_minute,
+            failed_per_minute,
+            queued: self.delayed_reconcile_queue_depth(),
+        }
+    }
+
+    /// Shards whose reconciles have failed repeatedly towards an unchanged intent, and which are
+    /// therefore only retried with backoff.
+    pub(crate) fn reconcile_attention_tenants(&self) -> Vec<ReconcileAttentionShard> {
+        let locked = self.inner.read().unwrap();
+
+        locked
+            .tenants
+            .values()
+            .filter_map(|shard| {
+                shard
+                    .reconcile_needs_attention()
+                    .map(|failures| ReconcileAttentionShard {
+                        tenant_shard_id: shard.tenant_shard_id,
+                        failures,
+                        last_error: shard
+                            .last_error
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .map(|e| e.to_string()),
+                    })
+            })
+            .collect()
+    }
+
+    /// Re-send compute notifications for all stably attached shards, even if they are unchanged
+    /// since the last notification, e.g. to re-sync compute routing after the compute hook was
+    /// unavailable.  Notifications are sent in the background, as on startup, and failures
+    /// are retried via `pending_compute_notification`.
+    ///
+    /// Returns the number of shards for which a notification was enqueued.
+    pub(crate) fn renotify_all_compute(&self) -> usize {
+        let compute_notifications = {
+            let locked = self.inner.read().unwrap();
+            locked
+                .tenants
+                .iter()
+                .filter_map(|(tenant_shard_id, shard)| {
+                    shard
+                        .stably_attached()
+                        .map(|node_id| (*tenant_shard_id, node_id, shard.shard.stripe_size))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let count = compute_notifications.len();
+        tracing::info!("Re-sending {count} compute notifications");
+
+        self.compute_hook.forget_notified();
+        self.compute_hook.notify_background(
+            compute_notifications,
+            self.bg_compute_notify_result_tx.clone(),
+            &self.cancel,
+        );
+
+        count
+    }
+
+    /// Find tenants where the locations we last successfully notified to the compute hook differ
+    /// from where their shards are attached, i.e. where computes may be routing to a stale pageserver.
+    ///
+    /// Tenants with a change in progress (reconciling, splitting, or a notification in flight) are
+    /// not checked, as they are expected to differ transiently.  Notification state is not persistent,
+    /// so tenants not yet notified since this controller started are reported too.
+    pub(crate) fn compute_notification_drift(&self) -> Vec<ComputeNotificationDrift> {
+        let last_notified = self.compute_hook.last_notified();
+
+        let locked = self.inner.read().unwrap();
+
+        let mut result = Vec::new();
+        for (tenant_id, tenant_shards) in
+            &locked.tenants.iter().group_by(|(id, _shard)| id.tenant_id)
+        {
+            let shards = tenant_shards.map(|(_k, v)| v).collect::<Vec<_>>();
+            if shards
+                .iter()
+                .any(|s| s.reconciler.is_some() || !matches!(s.splitting, SplitState::Idle))
+            {
+                continue;
+            }
+
+            // Tenants that are not fully attached do not get compute notifications
+            let Some(attached) = shards
+                .iter()
+                .map(|s| *s.intent.get_attached())
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            let notified = match last_notified.get(&tenant_id) {
+                Some(None) => continue,
+                Some(Some(nodes)) => Some(nodes.clone()),
+                None => None,
+            };
+
+            if notified.as_ref() != Some(&attached) {
+                result.push(ComputeNotificationDrift {
+                    tenant_id,
+                    attached,
+                    notified,
+                    pending_compute_notification: shards
+                        .iter()
+                        .any(|s| s.pending_compute_notification),
+                });
+            }
+        }
+
+        result
+    }
+
+    /// Compare the pageserver that computes were last told to use for a tenant's shard zero with
+    /// where shard zero is actually attached.  A mismatch means that computes (and anything else
+    /// routing by the compute notification, unlike [`Self::tenant_shard0_node`] which uses the
+    /// intent) may be sending requests to the wrong pageserver.
+    pub(crate) fn tenant_routing_status(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantRoutingStatusResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+        let Some((tenant_shard_id, shard)) = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .next()
+        else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        };
+
+        let published = self.compute_hook.published_shard0(tenant_id);
+        let intent = *shard.intent.get_attached();
+        let stably_attached = shard.stably_attached();
+
+        // While a change is in progress the published location is expected to lag the intent, so
+        // only compare against a stable attachment with no reconciler running.
+        let mismatch = match (published, stably_attached) {
+            _ if shard.reconciler.is_some() => false,
+            (Some(published), Some(attached)) => published != attached,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+
+        Ok(TenantRoutingStatusResponse {
+            tenant_id,
+            shard0: *tenant_shard_id,
+            published,
+            intent,
+            stably_attached,
+            mismatch,
+        })
+    }
+
+    /// How many tenants use each stripe size, e.g. to find tenants on non-default stripe sizes
+    pub(crate) fn stripe_sizes_in_use(&self) -> HashMap<ShardStripeSize, usize> {
+        let locked = self.inner.read().unwrap();
+        stripe_sizes_in_use(&locked.tenants)
+    }
+
+    pub(crate) fn tenant_list(&self) -> Vec<TenantDescribeResponse> {
+        match &self.tenant_list_cache {
+            Some(cache) => cache.get_or_refresh(|| self.tenant_list_uncached()),
+            None => self.tenant_list_uncached(),
+        }
+    }
+
+    fn tenant_list_uncached(&self) -> Vec<TenantDescribeResponse> {
+        let locked = self.inner.read().unwrap();
+
+        let mut result = Vec::new();
+        for (_tenant_id, tenant_shards) in
+            &locked.tenants.iter().group_by(|(id, _shard)| id.tenant_id)
+        {
+            result.push(
+                self.tenant_describe_impl(tenant_shards.map(|(_k, v)| v))
+                    .expect("Groups are always non-empty"),
+            );
+        }
+
+        result
+    }
+
+    /// A cheap alternative to [`Self::tenant_list`] for callers that only need counts.
+    pub(crate) fn tenant_summary(&self) -> TenantSummaryResponse {
+        let locked = self.inner.read().unwrap();
+
+        let mut summary = TenantSummaryResponse {
+            tenant_count: locked.tenants.keys().map(|id| id.tenant_id).dedup().count(),
+            shard_count: locked.tenants.len(),
+            reconciling_shard_count: 0,
+            splitting_shard_count: 0,
+            errored_shard_count: 0,
+            node_attached_shards: locked.nodes.keys().map(|node_id| (*node_id, 0)).collect(),
+        };
+
+        for shard in locked.tenants.values() {
+            if shard.reconciler.is_some() {
+                summary.reconciling_shard_count += 1;
+            }
+            if matches!(shard.splitting, SplitState::Splitting) {
+                summary.splitting_shard_count += 1;
+            }
+            if shard.last_error.lock().unwrap().is_some() {
+                summary.errored_shard_count += 1;
+            }
+            if let Some(node_id) = shard.intent.get_attached() {
+                *summary.node_attached_shards.entry(*node_id).or_default() += 1;
+            }
+        }
+
+        summary
+    }
+
+    #[instrument(skip_all, fields(tenant_id=%op.tenant_id))]
+    async fn abort_tenant_shard_split(
+        &self,
+        op: &TenantShardSplitAbort,
+    ) -> Result<(), TenantShardSplitAbortError> {
+        // Cleaning up a split:
+        // - Parent shards are not destroyed during a split, just detached.
+        // - Failed pageserver split API calls can leave the remote node with just the parent attached,
+        //   just the children attached, or both.
+        //
+        // Therefore our work to do is to:
+        // 1. Clean up storage controller's internal state to just refer to parents, no children
+        // 2. Call out to pageservers to ensure that children are detached
+        // 3. Call out to pageservers to ensure that parents are attached.
+        //
+        // Crash safety:
+        // - If the storage controller stops running during this cleanup *after* clearing the splitting state
+        //   from our database, then [`Self::startup_reconcile`] will regard child attachments as garbage
+        //   and detach them.
+        // - TODO: If the storage controller stops running during this cleanup *before* clearing the splitting state
+        //   from our database, then we will re-enter this cleanup routine on startup.
+
+        let TenantShardSplitAbort {
+            tenant_id,
+            new_shard_count,
+            new_stripe_size,
+            ..
+        } = op;
+
+        // First abort persistent state, if any exists.
+        match self
+            .persistence
+            .abort_shard_split(*tenant_id, *new_shard_count)
+            .await?
+        {
+            AbortShardSplitStatus::Aborted => {
+                // Proceed to roll back any child shards created on pageservers
+            }
+            AbortShardSplitStatus::Complete => {
+                // The split completed (we might hit that path if e.g. our database transaction
+                // to write the completion landed in the database, but we dropped connection
+                // before seeing the result).
+                //
+                // We must update in-memory state to reflect the successful split.
+                self.tenant_shard_split_commit_inmem(
+                    *tenant_id,
+                    *new_shard_count,
+                    *new_stripe_size,
+                );
+                return Ok(());
+            }
+        }
+
+        // Clean up in-memory state, and accumulate the list of child locations that need detaching
+        let detach_locations: Vec<(Node, TenantShardId)> = {
+            let mut detach_locations = Vec::new();
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            for (tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(op.tenant_id))
+            {
+                if shard.shard.count == op.new_shard_count {
+                    // Surprising: the phase of [`Self::do_tenant_shard_split`] which inserts child shards in-memory
+                    // is infallible, so if we got an error we shouldn't have got that far.
+                    tracing::warn!(
+                        "During split abort, child shard {tenant_shard_id} found in-memory"
+                    );
+                    continue;
+                }
+
+                // Add the children of this shard to this list of things to detach
+                if let Some(node_id) = shard.intent.get_attached() {
+                    for child_id in tenant_shard_id.split(*new_shard_count) {
+                        detach_locations.push((
+                            nodes
+                                .get(node_id)
+                                .expect("Intent references nonexistent node")
+                                .clone(),
+                            child_id,
+                        ));
+                    }
+                } else {
+                    tracing::warn!(
+                        "During split abort, shard {tenant_shard_id} has no attached location"
+                    );
+                }
+
+                tracing::info!("Restoring parent shard {tenant_shard_id}");
+                shard.splitting = SplitState::Idle;
+                if let Err(e) = shard.schedule(scheduler, &mut ScheduleContext::default()) {
+                    // If this shard can't be scheduled now (perhaps due to offline nodes or
+                    // capacity issues), that must not prevent us rolling back a split.  In this
+                    // case it should be eventually scheduled in the background.
+                    tracing::warn!("Failed to schedule {tenant_shard_id} during shard abort: {e}")
+                }
+
+                self.maybe_reconcile_shard(shard, nodes);
+            }
+
+            // We don't expect any new_shard_count shards to exist here, but drop them just in case
+            tenants.retain(|_id, s| s.shard.count != *new_shard_count);
+
+            detach_locations
+        };
+
+        for (node, child_id) in detach_locations {
+            if !node.is_available() {
+                // An unavailable node cannot be cleaned up now: to avoid blocking forever, we will permit this, and
+                // rely on the reconciliation that happens when a node transitions to Active to clean up. Since we have
+                // removed child shards from our in-memory state and database, the reconciliation will implicitly remove
+                // them from the node.
+                tracing::warn!("Node {node} unavailable, can't clean up during split abort. It will be cleaned up when it is reactivated.");
+                continue;
+            }
+
+            // Detach the remote child.  If the pageserver split API call is still in progress, this call will get
+            // a 503 and retry, up to our limit.
+            tracing::info!("Detaching {child_id} on {node}...");
+            match node
+                .with_client_retries(
+                    |client| async move {
+                        let config = LocationConfig {
+                            mode: LocationConfigMode::Detached,
+                            generation: None,
+                            secondary_conf: None,
+                            shard_number: child_id.shard_number.0,
+                            shard_count: child_id.shard_count.literal(),
+                            // Stripe size and tenant config don't matter when detaching
+                            shard_stripe_size: 0,
+                            tenant_conf: TenantConfig::default(),
+                        };
+
+                        client.location_config(child_id, config, None, false).await
+                    },
+                    &self.config.jwt_token,
+                    1,
+                    10,
+                    Duration::from_secs(5),
+                    &self.cancel,
+                )
+                .await
+            {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    // We failed to communicate with the remote node.  This is problematic: we may be
+                    // leaving it with a rogue child shard.
+                    tracing::warn!(
+                        "Failed to detach child {child_id} from node {node} during abort"
+                    );
+                    return Err(e.into());
+                }
+                None => {
+                    // Cancellation: we were shutdown or the node went offline. Shutdown is fine, we'll
+                    // clean up on restart. The node going offline requires a retry.
+                    return Err(TenantShardSplitAbortError::Unavailable);
+                }
+            };
+        }
+
+        tracing::info!("Successfully aborted split");
+        Ok(())
+    }
+
+    /// Infallible final stage of [`Self::tenant_shard_split`]: update the contents
+    /// of the tenant map to reflect the child shards that exist after the split.
+    fn tenant_shard_split_commit_inmem(
+        &self,
+        tenant_id: TenantId,
+        new_shard_count: ShardCount,
+        new_stripe_size: Option<ShardStripeSize>,
+    ) -> (
+        TenantShardSplitResponse,
+        Vec<(TenantShardId, NodeId, ShardStripeSize)>,
+        Vec<ReconcilerWaiter>,
+    ) {
+        let mut response = TenantShardSplitResponse {
+            new_shards: Vec::new(),
+        };
+        let mut child_locations = Vec::new();
+        let mut waiters = Vec::new();
+
+        {
+            let mut locked = self.inner.write().unwrap();
+
+            let parent_ids = locked
+                .tenants
+                .range(TenantShardId::tenant_range(tenant_id))
+                .map(|(shard_id, _)| *shard_id)
+                .collect::<Vec<_>>();
+
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            // One context for all the children, rather than one per parent, so that secondary
+            // locations for children of different parents also avoid each other.
+            let mut schedule_context = ScheduleContext::default();
+            for parent_id in parent_ids {
+                let child_ids = parent_id.split(new_shard_count);
+
+                let (pageserver, generation, policy, parent_ident, config, flags, priority) = {
+                    let mut old_state = tenants
+                        .remove(&parent_id)
+                        .expect("It was present, we just split it");
+
+                    // A non-splitting state is impossible, because [`Self::tenant_shard_split`] holds
+                    // a TenantId lock and passes it through to [`TenantShardSplitAbort`] in case of cleanup:
+                    // nothing else can clear this.
+                    assert!(matches!(old_state.splitting, SplitState::Splitting));
+
+                    let old_attached = old_state.intent.get_attached().unwrap();
+                    old_state.intent.clear(scheduler);
+                    let generation = old_state.generation.expect("Shard must have been attached");
+                    (
+                        old_attached,
+                        generation,
+                        old_state.policy,
+                        old_state.shard,
+                        old_state.config,
+                        old_state.get_flags(),
+                        old_state.priority,
+                    )
+                };
+
+                for child in child_ids {
+                    let mut child_shard = parent_ident;
+                    child_shard.number = child.shard_number;
+                    child_shard.count = child.shard_count;
+                    if let Some(stripe_size) = new_stripe_size {
+                        child_shard.stripe_size = stripe_size;
+                    }
+
+                    let mut child_observed: HashMap<NodeId, ObservedStateLocation> = HashMap::new();
+                    child_observed.insert(
+                        pageserver,
+                        ObservedStateLocation {
+                            conf: Some(attached_location_conf(
+                                generation,
+                                &child_shard,
+                                &config,
+                                &policy,
+                            )),
+                        },
+                    );
+
+                    let mut child_state = TenantShard::new(child, child_shard, policy.clone());
+                    child_state.intent = IntentState::single(scheduler, Some(pageserver));
+                    child_state.observed = ObservedState {
+                        locations: child_observed,
+                    };
+                    child_state.generation = Some(generation);
+                    child_state.config = config.clone();
+                    child_state.set_flags(flags);
+                    child_state.priority = priority;
+
+                    // The child's TenantShard::splitting is intentionally left at the default value of Idle,
+                    // as at this point in the split process we have succeeded and this part is infallible:
+                    // we will never need to do any special recovery from this state.
+
+                    child_locations.push((child, pageserver, child_shard.stripe_size));
+
+                    if let Err(e) = child_state.schedule(scheduler, &mut schedule_context) {
+                        // This is not fatal, because we've implicitly already got an attached
+                        // location for the child shard.  Failure here just means we couldn't
+                        // find a secondary (e.g. because cluster is overloaded).
+                        tracing::warn!("Failed to schedule child shard {child}: {e}");
+                    }
+                    // In the background, attach secondary locations for the new shards
+                    if let Some(waiter) = self.maybe_reconcile_shard(&mut child_state, nodes) {
+                        waiters.push(waiter);
+                    }
+
+                    tenants.insert(child, child_state);
+                    response.new_shards.push(child);
+                }
+            }
+            (response, child_locations, waiters)
+        }
+    }
+
+    async fn tenant_shard_split_start_secondaries(
+        &self,
+        tenant_id: TenantId,
+        waiters: Vec<ReconcilerWaiter>,
+    ) {
+        // Wait for initial reconcile of child shards, this creates the secondary locations
+        if let Err(e) = self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
+            // This is not a failure to split: it's some issue reconciling the new child shards, perhaps
+            // their secondaries couldn't be attached.
+            tracing::warn!("Failed to reconcile after split: {e}");
+            return;
+        }
+
+        // Take the state lock to discover the attached & secondary intents for all shards
+        let (attached, secondary) = {
+            let locked = self.inner.read().unwrap();
+            let mut attached = Vec::new();
+            let mut secondary = Vec::new();
+
+            for (tenant_shard_id, shard) in
+                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+            {
+                let Some(node_id) = shard.intent.get_attached() else {
+                    // Unexpected.  Race with a PlacementPolicy change?
+                    tracing::warn!(
+                        "No attached node on {tenant_shard_id} immediately after shard split!"
+                    );
+                    continue;
+                };
+
+                if shard.intent.get_secondary().is_empty() {
+                    // No secondary location.  Nothing for us to do.
+                    continue;
+                }
+
+                let attached_node = locked
+                    .nodes
+                    .get(node_id)
+                    .expect("Pageservers may not be deleted while referenced");
+                attached.push((*tenant_shard_id, attached_node.clone()));
+
+                // Warm up all secondaries: with PlacementPolicy::Attached(n) for n>1, any of them
+                // may be the one we later cut over to.
+                for secondary_node_id in shard.intent.get_secondary() {
+                    let secondary_node = locked
+                        .nodes
+                        .get(secondary_node_id)
+                        .expect("Pageservers may not be deleted while referenced");
+                    secondary.push((*tenant_shard_id, secondary_node.clone()));
+                }
+            }
+            (attached, secondary)
+        };
+
+        if secondary.is_empty() {
+            // No secondary locations; nothing for us to do
+            return;
+        }
+
+        for result in self
+            .tenant_for_shards_api(
+                attached,
+                |tenant_shard_id, client| async move {
+                    client.tenant_heatmap_upload(tenant_shard_id).await
+                },
+                1,
+                1,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await
+        {
+            if let Err(e) = result {
+                tracing::warn!("Error calling heatmap upload after shard split: {e}");
+                return;
+            }
+        }
+
+        for result in self
+            .tenant_for_shards_api(
+                secondary,
+                |tenant_shard_id, client| async move {
+                    client
+                        .tenant_secondary_download(tenant_shard_id, Some(Duration::ZERO))
+                        .await
+                },
+                1,
+                1,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await
+        {
+            if let Err(e) = result {
+                tracing::warn!("Error calling secondary download after shard split: {e}");
+                return;
+            }
+        }
+    }
+
+    pub(crate) async fn tenant_shard_split(
+        &self,
+        tenant_id: TenantId,
+        split_req: TenantShardSplitRequest,
+    ) -> Result<TenantShardSplitResponse, ApiError> {
+        // TODO: return 503 if we get stuck waiting for this lock
+        // (issue https://github.com/neondatabase/neon/issues/7108)
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::ShardSplit,
+        )
+        .await;
+
+        let new_shard_count = ShardCount::new(split_req.new_shard_count);
+        let new_stripe_size = split_req.new_stripe_size;
+
+        // Validate the request and construct parameters.  This phase is fallible, but does not require
+        // rollback on errors, as it does no I/O and mutates no state.
+        let shard_split_params = match self.prepare_tenant_shard_split(tenant_id, split_req)? {
+            ShardSplitAction::NoOp(resp) => return Ok(resp),
+            ShardSplitAction::Split(params) => params,
+        };
+
+        // Execute this split: this phase mutates state and does remote I/O on pageservers.  If it fails,
+        // we must roll back.
+        let split_guard = self.shard_splits.begin(tenant_id);
+        let r = self
+            .do_tenant_shard_split(tenant_id, shard_split_params)
+            .await;
+        drop(split_guard);
+
+        let (response, waiters) = match r {
+            Ok(r) => r,
+            Err(e) => {
+                // Split might be part-done, we must do work to abort it.
+                tracing::warn!("Enqueuing background abort of split on {tenant_id}");
+                self.abort_tx
+                    .send(TenantShardSplitAbort {
+                        tenant_id,
+                        new_shard_count,
+                        new_stripe_size,
+                        _tenant_lock,
+                    })
+                    // Ignore error sending: that just means we're shutting down: aborts are ephemeral so it's fine to drop it.
+                    .ok();
+                return Err(e);
+            }
+        };
+
+        // The split is now complete.  As an optimization, we will trigger all the child shards to upload
+        // a heatmap immediately, and all their secondary locations to start downloading: this avoids waiting
+        // for the background heatmap/download interval before secondaries get warm enough to migrate shards
+        // in [`Self::optimize_all`]
+        self.tenant_shard_split_start_secondaries(tenant_id, waiters)
+            .await;
+        Ok(response)
+    }
+
+    /// Cancel a split that is in progress, if it has not yet started splitting shards on pageservers.
+    /// The split request then fails, and the split is rolled back in the background like any other
+    /// failed split.  Once pageservers have been called, the split can no longer be cancelled.
+    pub(crate) fn cancel_tenant_shard_split(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        self.shard_splits.cancel(tenant_id)?;
+        tracing::info!("Cancelled shard split for tenant {tenant_id}");
+        Ok(())
+    }
+
+    fn prepare_tenant_shard_split(
+        &self,
+        tenant_id: TenantId,
+        split_req: TenantShardSplitRequest,
+    ) -> Result<ShardSplitAction, ApiError> {
+        fail::fail_point!("shard-split-validation", |_| Err(ApiError::BadRequest(
+            anyhow::anyhow!("failpoint")
+        )));
+
+        if let Some(new_stripe_size) = split_req.new_stripe_size {
+            validate_split_stripe_size(new_stripe_size)?;
+        }
+        check_shards_limit(
+            ShardCount::new(split_req.new_shard_count),
+            self.config.max_shards_per_tenant,
+        )?;
+
+        let mut policy = None;
+        let mut config = None;
+        let mut flags = TenantFlags::default();
+        let mut priority = None;
+        let mut shard_ident = None;
+        // Validate input, and calculate which shards we will create
+        let (old_shard_count, targets) =
+            {
+                let locked = self.inner.read().unwrap();
+
+                let pageservers = locked.nodes.clone();
+
+                let mut targets = Vec::new();
+
+                // In case this is a retry, count how many already-split shards we found
+                let mut children_found = Vec::new();
+                let mut old_shard_count = None;
+
+                for (tenant_shard_id, shard) in
+                    locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+                {
+                    match shard.shard.count.count().cmp(&split_req.new_shard_count) {
+                        Ordering::Equal => {
+                            //  Already split this
+                            children_found.push(*tenant_shard_id);
+                            continue;
+                        }
+                        Ordering::Greater => {
+                            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                                "Requested count {} but already have shards at count {}",
+                                split_req.new_shard_count,
+                                shard.shard.count.count()
+                            )));
+                        }
+                        Ordering::Less => {
+                            // Fall through: this shard has lower count than requested,
+                            // is a candidate for splitting.
+                        }
+                    }
+
+                    match old_shard_count {
+                        None => old_shard_count = Some(shard.shard.count),
+                        Some(old_shard_count) => {
+                            if old_shard_count != shard.shard.count {
+                                // We may hit this case if a caller asked for two splits to
+                                // different sizes, before the first one is complete.
+                                // e.g. 1->2, 2->4, where the 4 call comes while we have a mixture
+                                // of shard_count=1 and shard_count=2 shards in the map.
+                                return Err(ApiError::Conflict(
+                                    "Cannot split, currently mid-split".to_string(),
+                                ));
+                            }
+                        }
+                    }
+                    if policy.is_none() {
+                        policy = Some(shard.policy.clone());
+                    }
+                    if shard_ident.is_none() {
+                        shard_ident = Some(shard.shard);
+                    }
+                    if config.is_none() {
+                        config = Some(shard.config.clone());
+                    }
+                    if priority.is_none() {
+                        priority = Some(shard.priority);
+                    }
+                    flags = flags.merge(shard.get_flags());
+
+                    if tenant_shard_id.shard_count.count() == split_req.new_shard_count {
+                        tracing::info!(
+                            "Tenant shard {} already has shard count {}",
+                            tenant_shard_id,
+                            split_req.new_shard_count
+                        );
+                        continue;
+                    }
+
+                    let node_id = shard.intent.get_attached().ok_or(ApiError::BadRequest(
+                        anyhow::anyhow!("Cannot split a tenant that is not attached"),
+                    ))?;
+
+                    let node = pageservers
+                        .get(&node_id)
+                        .expect("Pageservers may not be deleted while referenced");
+
+                    targets.push(ShardSplitTarget {
+                        parent_id: *tenant_shard_id,
+                        node: node.clone(),
+                        child_ids: tenant_shard_id
+                            .split(ShardCount::new(split_req.new_shard_count)),
+                    });
+                }
+
+                if targets.is_empty() {
+                    if children_found.len() == split_req.new_shard_count as usize {
+                        return Ok(ShardSplitAction::NoOp(TenantShardSplitResponse {
+                            new_shards: children_found,
+                        }));
+                    } else {
+                        // No shards found to split, and no existing children found: the
+                        // tenant doesn't exist at all.
+                        return Err(ApiError::NotFound(
+                            anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
+                        ));
+                    }
+                }
+
+                (old_shard_count, targets)
+            };
+
+        check_split_targets(&targets)?;
+
+        // unwrap safety: we would have returned above if we didn't find at least one shard to split
+        let old_shard_count = old_shard_count.unwrap();
+        let shard_ident = if let Some(new_stripe_size) = split_req.new_stripe_size {
+            // This ShardIdentity will be used as the template for all children, so this implicitly
+            // applies the new stripe size to the children.
+            let mut shard_ident = shard_ident.unwrap();
+            if shard_ident.count.count() > 1 && shard_ident.stripe_size != new_stripe_size {
+                return Err(ApiError::BadRequest(anyhow::anyhow!("Attempted to change stripe size ({:?}->{new_stripe_size:?}) on a tenant with multiple shards", shard_ident.stripe_size)));
+            }
+
+            shard_ident.stripe_size = new_stripe_size;
+            tracing::info!("applied  stripe size {}", shard_ident.stripe_size.0);
+            shard_ident
+        } else {
+            shard_ident.unwrap()
+        };
+        let policy = policy.unwrap();
+        let config = config.unwrap();
+        let priority = priority.unwrap();
+
+        Ok(ShardSplitAction::Split(ShardSplitParams {
+            old_shard_count,
+            new_shard_count: ShardCount::new(split_req.new_shard_count),
+            new_stripe_size: split_req.new_stripe_size,
+            targets,
+            policy,
+            config,
+            flags,
+            priority,
+            shard_ident,
+        }))
+    }
+
+    async fn do_tenant_shard_split(
+        &self,
+        tenant_id: TenantId,
+        params: ShardSplitParams,
+    ) -> Result<(TenantShardSplitResponse, Vec<ReconcilerWaiter>), ApiError> {
+        // FIXME: we have dropped self.inner lock, and not yet written anything to the database: another
+        // request could occur here, deleting or mutating the tenant.  begin_shard_split checks that the
+        // parent shards exist as expected, but it would be neater to do the above pre-checks within the
+        // same database transaction rather than pre-check in-memory and then maybe-fail the database write.
+        // (https://github.com/neondatabase/neon/issues/6676)
+
+        let ShardSplitParams {
+            old_shard_count,
+            new_shard_count,
+            new_stripe_size,
+            mut targets,
+            policy,
+            config,
+            flags,
+            priority,
+            shard_ident,
+        } = params;
+
+        // Drop any secondary locations: pageservers do not support splitting these, and in any case the
+        // end-state for a split tenant will usually be to have secondary locations on different nodes.
+        // The reconciliation calls in this block also implicitly cancel+barrier wrt any ongoing reconciliation
+        // at the time of split.
+        let waiters = {
+            let mut locked = self.inner.write().unwrap();
+            let mut waiters = Vec::new();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+            for target in &mut targets {
+                let Some(shard) = tenants.get_mut(&target.parent_id) else {
+                    // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shard {} not found",
+                        target.parent_id
+                    )));
+                };
+
+                if shard.intent.get_attached() != &Some(target.node.get_id()) {
+                    // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
+                    return Err(ApiError::Conflict(format!(
+                        "Shard {} unexpectedly rescheduled during split",
+                        target.parent_id
+                    )));
+                }
+
+                // Irrespective of PlacementPolicy, clear secondary locations and read replicas from intent
+                shard.intent.clear_secondary(scheduler);
+                shard.intent.clear_read_replicas(scheduler);
+
+                // Run Reconciler to execute detach fo secondary locations.
+                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                    waiters.push(waiter);
+                }
+            }
+            waiters
+        };
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        // Before creating any new child shards in memory or on the pageservers, persist them: this
+        // enables us to ensure that we will always be able to clean up if something goes wrong.  This also
+        // acts as the protection against two concurrent attempts to split: one of them will get a database
+        // error trying to insert the child shards.
+        let mut child_tsps = Vec::new();
+        for target in &targets {
+            let mut this_child_tsps = Vec::new();
+            for child in &target.child_ids {
+                let mut child_shard = shard_ident;
+                child_shard.number = child.shard_number;
+                child_shard.count = child.shard_count;
+
+                tracing::info!(
+                    "Create child shard persistence with stripe size {}",
+                    shard_ident.stripe_size.0
+                );
+
+                this_child_tsps.push(TenantShardPersistence {
+                    tenant_id: child.tenant_id.to_string(),
+                    shard_number: child.shard_number.0 as i32,
+                    shard_count: child.shard_count.literal() as i32,
+                    shard_stripe_size: shard_ident.stripe_size.0 as i32,
+                    // Note: this generation is a placeholder, [`Persistence::begin_shard_split`] will
+                    // populate the correct generation as part of its transaction, to protect us
+                    // against racing with changes in the state of the parent.
+                    generation: None,
+                    generation_pageserver: Some(target.node.get_id().0 as i64),
+                    placement_policy: serde_json::to_string(&policy).unwrap(),
+                    config: serde_json::to_string(&config).unwrap(),
+                    splitting: SplitState::Splitting,
+
+                    // Scheduling policies do not carry through to children
+                    scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
+                        .unwrap(),
+                    sticky: false,
+                    // Co-location, canary status and priority are properties of the tenant, so
+                    // children inherit them
+                    colocate: flags.colocate,
+                    canary: flags.canary,
+                    pending_compute_notification: false,
+                    reconcile_priority: serde_json::to_string(&priority).unwrap(),
+                });
+            }
+
+            child_tsps.push((target.parent_id, this_child_tsps));
+        }
+
+        self.shard_splits
+            .advance(tenant_id, ShardSplitPhase::Prepare)?;
+
+        if let Err(e) = self
+            .persistence
+            .begin_shard_split(old_shard_count, tenant_id, child_tsps)
+            .await
+        {
+            match e {
+                DatabaseError::Query(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => {
+                    // Inserting a child shard violated a unique constraint: we raced with another call to
+                    // this function
+                    tracing::warn!("Conflicting attempt to split {tenant_id}: {e}");
+                    return Err(ApiError::Conflict("Tenant is already splitting".into()));
+                }
+                _ => return Err(ApiError::InternalServerError(e.into())),
+            }
+        }
+        fail::fail_point!("shard-split-post-begin", |_| Err(
+            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
+        ));
+
+        // Now that I have persisted the splitting state, apply it in-memory.  This is infallible, so
+        // callers may assume that if splitting is set in memory, then it was persisted, and if splitting
+        // is not set in memory, then it was not persisted.
+        {
+            let mut locked = self.inner.write().unwrap();
+            for target in &targets {
+                if let Some(parent_shard) = locked.tenants.get_mut(&target.parent_id) {
+                    parent_shard.splitting = SplitState::Splitting;
+                    // Put the observed state to None, to reflect that it is indeterminate once we start the
+                    // split operation.
+                    parent_shard
+                        .observed
+                        .locations
+                        .insert(target.node.get_id(), ObservedStateLocation { conf: None });
+                }
+            }
+        }
+
+        // Last chance to cancel: from here on we call into pageservers
+        self.shard_splits
+            .advance(tenant_id, ShardSplitPhase::Remote)?;
+
+        // TODO: issue split calls concurrently (this only matters once we're splitting
+        // N>1 shards into M shards -- initially we're usually splitting 1 shard into N).
+
+        for target in &targets {
+            let ShardSplitTarget {
+                parent_id,
+                node,
+                child_ids,
+            } = target;
+            let client = PageserverClient::new(
+                node.get_id(),
+                node.base_url(),
+                self.config.jwt_token.as_deref(),
+            );
+            let response = client
+                .tenant_shard_split(
+                    *parent_id,
+                    TenantShardSplitRequest {
+                        new_shard_count: new_shard_count.literal(),
+                        new_stripe_size,
+                    },
+                )
+                .await
+                .map_err(|e| ApiError::Conflict(format!("Failed to split {}: {}", parent_id, e)))?;
+
+            fail::fail_point!("shard-split-post-remote", |_| Err(ApiError::Conflict(
+                "failpoint".to_string()
+            )));
+
+            tracing::info!(
+                "Split {} into {}",
+                parent_id,
+                response
+                    .new_shards
+                    .iter()
+                    .map(|s| format!("{:?}", s))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+
+            if &response.new_shards != child_ids {
+                // This should never happen: the pageserver should agree with us on how shard splits work.
+                return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                    "Splitting shard {} resulted in unexpected IDs: {:?} (expected {:?})",
+                    parent_id,
+                    response.new_shards,
+                    child_ids
+                )));
+            }
+        }
+
+        // TODO: if the pageserver restarted concurrently with our split API call,
+        // the actual generation of the child shard might differ from the generation
+        // we expect it to have.  In order for our in-database generation to end up
+        // correct, we should carry the child generation back in the response and apply it here
+        // in complete_shard_split (and apply the correct generation in memory)
+        // (or, we can carry generation in the request and reject the request if
+        //  it doesn't match, but that requires more retry logic on this side)
+
+        self.persistence
+            .complete_shard_split(tenant_id, old_shard_count)
+            .await?;
+
+        fail::fail_point!("shard-split-post-complete", |_| Err(
+            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
+        ));
+
+        // Replace all the shards we just split with their children: this phase is infallible.
+        let (response, child_locations, waiters) =
+            self.tenant_shard_split_commit_inmem(tenant_id, new_shard_count, new_stripe_size);
+
+        // Send compute notifications for all the new shards
+        let mut failed_notifications = Vec::new();
+        for (child_id, child_ps, stripe_size) in child_locations {
+            if let Err(e) = self
+                .compute_hook
+                .notify(child_id, child_ps, stripe_size, &self.cancel)
+                .await
+            {
+                tracing::warn!("Failed to update compute of {}->{} during split, proceeding anyway to complete split ({e})",
+                        child_id, child_ps);
+                failed_notifications.push(child_id);
+            }
+        }
+
+        // If we failed any compute notifications, make a note to retry later.
+        if !failed_notifications.is_empty() {
+            // Ordering: persist before setting the flag in memory, as in [`Self::process_results`]
+            for failed in &failed_notifications {
+                persist_pending_compute_notification(&self.persistence, *failed, true).await;
+            }
+            let mut locked = self.inner.write().unwrap();
+            for failed in &failed_notifications {
+                if let Some(shard) = locked.tenants.get_mut(failed) {
+                    shard.pending_compute_notification = true;
+                }
+            }
+        }
+
+        Ok((response, waiters))
+    }
+
+    pub(crate) async fn tenant_shard_migrate(
+        &self,
+        tenant_shard_id: TenantShardId,
+        migrate_req: TenantShardMigrateRequest,
+        reconcile_timeout_override: Option<Duration>,
+    ) -> Result<TenantShardMigrateResponse, ApiError> {
+        // Attaching a detached tenant updates its persistent policy: as in
+        // [`Self::tenant_update_policy`], this requires an exclusive lock.
+        let mut attach_detached = false;
+        let _tenant_lock = if migrate_req.migration_config.attach_detached {
+            let tenant_id = tenant_shard_id.tenant_id;
+            let lock = trace_exclusive_lock(
+                &self.tenant_op_locks,
+                tenant_id,
+                TenantOperations::UpdatePolicy,
+            )
+            .await;
+
+            {
+                // Validate the node before persisting anything
+                let locked = self.inner.read().unwrap();
+                if !locked.nodes.contains_key(&migrate_req.node_id) {
+                    return Err(ApiError::BadRequest(anyhow::anyhow!(
+                        "Node {} not found",
+                        migrate_req.node_id
+                    )));
+                }
+                attach_detached = matches!(
+                    locked.tenants.get(&tenant_shard_id),
+                    Some(shard) if shard.policy == PlacementPolicy::Detached
+                );
+            }
+            if attach_detached {
+                self.persistence
+                    .update_tenant_shard(
+                        TenantFilter::Tenant(tenant_id),
+                        Some(PlacementPolicy::Attached(0)),
+                        None,
+                        None,
+                        None,
+                        None,
+                    )
+                    .await?;
+            }
+            Some(lock)
+        } else {
+            None
+        };
+
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            if attach_detached {
+                // The other shards of the tenant are scheduled as usual: only the one we are
+                // migrating has a chosen location.
+                let mut schedule_context = ScheduleContext::default();
+                let tenant_range = TenantShardId::tenant_range(tenant_shard_id.tenant_id);
+                for (shard_id, shard) in tenants.range_mut(tenant_range) {
+                    shard.policy = PlacementPolicy::Attached(0);
+                    tracing::info!(tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                                   "Updated placement policy to Attached(0) to migrate");
+                    if *shard_id != tenant_shard_id {
+                        // Not fatal to the migration, and the policy change is already persisted:
+                        // the error is also recorded on the shard, for operators to see.
+                        if let Err(e) = shard.schedule(scheduler, &mut schedule_context) {
+                            tracing::warn!(
+                                tenant_id=%shard_id.tenant_id, shard_id=%shard_id.shard_slug(),
+                                "Failed to schedule shard attached to migrate another: {e}"
+                            );
+                        }
+                        self.maybe_reconcile_shard(shard, nodes);
+                    }
+                }
+            }
+
+            let Some(node) = nodes.get(&migrate_req.node_id) else {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Node {} not found",
+                    migrate_req.node_id
+                )));
+            };
+
+            if !node.is_available() {
+                // Warn but proceed: the caller may intend to manually adjust the placement of
+                // a shard even if the node is down, e.g. if intervening during an incident.
+                tracing::warn!("Migrating to unavailable node {node}");
+            }
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            if shard.intent.get_attached() == &Some(migrate_req.node_id) {
+                // No-op case: we will still proceed to wait for reconciliation in case it is
+                // incomplete from an earlier update to the intent.
+                tracing::info!("Migrating: intent is unchanged {:?}", shard.intent);
+            } else {
+                let old_attached = *shard.intent.get_attached();
+
+                match shard.policy {
+                    PlacementPolicy::Attached(n) => {
+                        // If our new attached node was a secondary or read replica, it no longer should be.
+                        shard
+                            .intent
+                            .remove_secondary(scheduler, migrate_req.node_id);
+                        shard
+                            .intent
+                            .remove_read_replica(scheduler, migrate_req.node_id);
+
+                        // If we were already attached to something, demote that to a secondary
+                        if let Some(old_attached) = old_attached {
+                            if n > 0 {
+                                // Remove other secondaries to make room for the location we'll demote
+                                while shard.intent.get_secondary().len() >= n {
+                                    shard.intent.pop_secondary(scheduler);
+                                }
+
+                                shard.intent.push_secondary(scheduler, old_attached);
+                            }
+                        }
+
+                        shard
+                            .intent
+                            .set_attached(scheduler, Some(migrate_req.node_id));
+                    }
+                    PlacementPolicy::Secondary => {
+                        shard.intent.clear(scheduler);
+                        shard.intent.push_secondary(scheduler, migrate_req.node_id);
+                    }
+                    PlacementPolicy::Detached => {
+                        return Err(ApiError::BadRequest(anyhow::anyhow!(
+                            "Cannot migrate a tenant that is PlacementPolicy::Detached: \
+                             configure it to an attached policy first, or set attach_detached"
+                        )))
+                    }
+                }
+
+                tracing::info!("Migrating: new intent {:?}", shard.intent);
+                shard.sequence = shard.sequence.next();
+            }
+
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter
+                .wait_timeout(reconcile_timeout(
+                    reconcile_timeout_override,
+                    RECONCILE_TIMEOUT,
+                ))
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        } else {
+            tracing::info!("Migration is a no-op");
+        }
+
+        Ok(TenantShardMigrateResponse {})
+    }
+
+    /// Move a shard's secondary location from one node to another, for example to warm up a node
+    /// ahead of a planned migration of the attached location there.  Like any other placement,
+    /// the background optimizer may later choose to move it again.
+    pub(crate) async fn tenant_shard_migrate_secondary(
+        &self,
+        tenant_shard_id: TenantShardId,
+        from_node_id: NodeId,
+        to_node_id: NodeId,
+    ) -> Result<TenantShardMigrateResponse, ApiError> {
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let Some(node) = nodes.get(&to_node_id) else {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Node {to_node_id} not found"
+                )));
+            };
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            if !shard.intent.get_secondary().contains(&from_node_id) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Shard has no secondary location on node {from_node_id}"
+                )));
+            }
+            if shard.intent.get_attached() == &Some(to_node_id) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Node {to_node_id} is the shard's attached location"
+                )));
+            }
+            if shard.intent.references(to_node_id) {
+                return Err(ApiError::Conflict(format!(
+                    "Shard already has a location on node {to_node_id}"
+                )));
+            }
+
+            if !node.is_available() {
+                // Warn but proceed, as for migrations of the attached location
+                tracing::warn!("Migrating secondary to unavailable node {node}");
+            }
+
+            shard.intent.remove_secondary(scheduler, from_node_id);
+            shard.intent.push_secondary(scheduler, to_node_id);
+            tracing::info!("Migrating secondary: new intent {:?}", shard.intent);
+            shard.sequence = shard.sequence.next();
+
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter
+                .wait_timeout(RECONCILE_TIMEOUT)
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        }
+
+        Ok(TenantShardMigrateResponse {})
+    }
+
+    /// Add a read replica for a shard: an extra location in AttachedMulti mode that can serve reads
+    /// alongside the attached location.  If no node is specified, the scheduler picks one.
+    ///
+    /// Read replicas share the attached location's generation, and are only held in memory: they
+    /// are not recreated if the storage controller restarts.
+    pub(crate) async fn tenant_shard_add_read_replica(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantShardReadReplicaRequest,
+    ) -> Result<TenantShardReadReplicaResponse, ApiError> {
+        let (node_id, waiter) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            if !matches!(shard.policy, PlacementPolicy::Attached(_)) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Cannot add a read replica to a shard with policy {:?}: configure it to an attached policy first",
+                    shard.policy
+                )));
+            }
+            if shard.intent.get_attached().is_none() || shard.generation.is_none() {
+                return Err(ApiError::PreconditionFailed(
+                    "Shard is not attached yet, retry when it has been scheduled".into(),
+                ));
+            }
+
+            let node_id = match req.node_id {
+                Some(node_id) => {
+                    let Some(node) = nodes.get(&node_id) else {
+                        return Err(ApiError::BadRequest(anyhow::anyhow!(
+                            "Node {node_id} not found"
+                        )));
+                    };
+                    if shard.intent.references(node_id) {
+                        return Err(ApiError::Conflict(format!(
+                            "Shard already has a location on node {node_id}"
+                        )));
+                    }
+                    if !node.is_available() {
+                        tracing::warn!("Adding read replica on unavailable node {node}");
+                    }
+                    node_id
+                }
+                None => scheduler
+                    .schedule_shard(&shard.intent.all_pageservers(), &ScheduleContext::default())
+                    .map_err(|e| ApiError::ResourceUnavailable(format!("{e}").into()))?,
+            };
+
+            shard.intent.push_read_replica(scheduler, node_id);
+            tracing::info!("Adding read replica: new intent {:?}", shard.intent);
+            shard.sequence = shard.sequence.next();
+
+            (node_id, self.maybe_reconcile_shard(shard, nodes))
+        };
+
+        if let Some(waiter) = waiter {
+            waiter
+                .wait_timeout(RECONCILE_TIMEOUT)
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        }
+
+        Ok(TenantShardReadReplicaResponse { node_id })
+    }
+
+    /// Remove a read replica added with [`Self::tenant_shard_add_read_replica`].  The location is
+    /// detached from its pageserver.
+    pub(crate) async fn tenant_shard_remove_read_replica(
+        &self,
+        tenant_shard_id: TenantShardId,
+        node_id: NodeId,
+    ) -> Result<(), ApiError> {
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            if !shard.intent.get_read_replicas().contains(&node_id) {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Shard has no read replica on node {node_id}").into(),
+                ));
+            }
+
+            shard.intent.remove_read_replica(scheduler, node_id);
+            tracing::info!("Removing read replica: new intent {:?}", shard.intent);
+            shard.sequence = shard.sequence.next();
+
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter
+                .wait_timeout(RECONCILE_TIMEOUT)
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Move all of a tenant's locations off one node, e.g. to get a noisy neighbor off a node
+    /// without draining it: other tenants on the node are not disturbed.
+    ///
+    /// Returns the shards whose locations were moved.
+    pub(crate) async fn tenant_evacuate_node(
+        &self,
+        tenant_id: TenantId,
+        node_id: NodeId,
+    ) -> Result<TenantEvacuateNodeResponse, ApiError> {
+        let (shards, waiters) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            if !nodes.contains_key(&node_id) {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Node {node_id} not found"
+                )));
+            }
+
+            // Accumulate the tenant's locations before moving any of them, so that replacements
+            // are spread out away from the tenant's other shards.
+            let mut schedule_context = ScheduleContext::default();
+            for (_tenant_shard_id, shard) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
+                schedule_context.avoid(&shard.intent.all_pageservers());
+                if let Some(attached) = shard.intent.get_attached() {
+                    schedule_context.push_attached(*attached);
+                }
+            }
+
+            let mut found = false;
+            let mut shards = Vec::new();
+            let mut waiters = Vec::new();
+            for (tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(tenant_id))
+            {
+                found = true;
+                if shard.evacuate_node(scheduler, node_id, &schedule_context)? {
+                    tracing::info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                                   "Evacuating from node {node_id}: new intent {:?}", shard.intent);
+                    shard.sequence = shard.sequence.next();
+                    shards.push(*tenant_shard_id);
+                    if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                        waiters.push(waiter);
+                    }
+                }
+            }
+
+            if !found {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+                ));
+            }
+
+            (shards, waiters)
+        };
+
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        Ok(TenantEvacuateNodeResponse { shards })
+    }
+
+    /// Move the attachments of some tenants off a node by promoting their secondary locations,
+    /// as [`Self::drain_node`] does, but leave the node's scheduling policy and its other tenants
+    /// alone.  Use this to get a few problematic tenants off a node that should stay in service.
+    pub(crate) async fn migrate_tenants_off_node(
+        &self,
+        node_id: NodeId,
+        tenant_ids: Vec<TenantId>,
+    ) -> Result<NodeMigrateTenantsResponse, ApiError> {
+        let shard_ids = {
+            let locked = self.inner.read().unwrap();
+            if !locked.nodes.contains_key(&node_id) {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Node {node_id} not registered").into(),
+                ));
+            }
+
+            let mut shard_ids = Vec::new();
+            for tenant_id in tenant_ids {
+                let mut found = false;
+                for (tid, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id)) {
+                    found = true;
+                    if *shard.intent.get_attached() == Some(node_id) {
+                        shard_ids.push(*tid);
+                    }
+                }
+
+                if !found {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+                    ));
+                }
+            }
+            shard_ids
+        };
+
+        let mut migrated = Vec::new();
+        let mut failed = Vec::new();
+        let mut waiters = Vec::new();
+        let mut shard_ids = shard_ids.into_iter().peekable();
+        while shard_ids.peek().is_some() {
+            if self.cancel.is_cancelled() {
+                return Err(ApiError::ShuttingDown);
+            }
+
+            {
+                let mut locked = self.inner.write().unwrap();
+                let (nodes, tenants, scheduler) = locked.parts_mut();
+
+                while waiters.len() < MAX_RECONCILES_PER_OPERATION {
+                    let Some(tid) = shard_ids.next() else {
+                        break;
+                    };
+
+                    // The shard may have been moved or deleted since we listed it
+                    let Some(tenant_shard) = tenants.get_mut(&tid) else {
+                        continue;
+                    };
+                    if *tenant_shard.intent.get_attached() != Some(node_id) {
+                        continue;
+                    }
+
+                    match tenant_shard.reschedule_to_secondary(None, scheduler) {
+                        Err(e) => {
+                            tracing::warn!(
+                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                "Scheduling error when migrating off pageserver {node_id}: {e}"
+                            );
+                            failed.push(tid);
+                        }
+                        Ok(()) => {
+                            tracing::info!(
+                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                "Migrated shard off node {node_id}: new intent {:?}", tenant_shard.intent
+                            );
+                            migrated.push(tid);
+                            if let Some(waiter) = self.maybe_reconcile_shard(tenant_shard, nodes) {
+                                waiters.push(waiter);
+                            }
+                        }
+                    }
+                }
+            }
+
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+        }
+
+        // Give the last reconciles a chance to finish: any still running carry on in the background
+        let waiters = self
+            .await_waiters_remainder(waiters, RECONCILE_TIMEOUT)
+            .await;
+        if !waiters.is_empty() {
+            tracing::info!(
+                "{} reconciles still in progress after migrating off node {node_id}",
+                waiters.len()
+            );
+        }
+
+        Ok(NodeMigrateTenantsResponse { migrated, failed })
+    }
+
+    /// Move a tenant's shards to explicitly chosen locations, e.g. for a scripted rebalance.  The
+    /// whole placement is validated before any shard is changed, and only the locations that
+    /// differ from the current intent are changed.
+    ///
+    /// Returns the shards whose placement changed.
+    pub(crate) async fn tenant_set_placement(
+        &self,
+        tenant_id: TenantId,
+        req: TenantSetPlacementRequest,
+    ) -> Result<TenantSetPlacementResponse, ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::SetPlacement,
+        )
+        .await;
+
+        let (shards, waiters) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            match tenants.range(TenantShardId::tenant_range(tenant_id)).next() {
+                None => {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+                    ))
+                }
+                Some((_, shard)) if !matches!(shard.splitting, SplitState::Idle) => {
+                    return Err(ApiError::PreconditionFailed("Tenant is splitting".into()))
+                }
+                Some(_) => {}
+            }
+
+            validate_placement(tenant_id, &req.shards, tenants, nodes)?;
+
+            let mut shards = Vec::new();
+            let mut waiters = Vec::new();
+            for shard_placement in req.shards {
+                let tenant_shard_id = shard_placement.tenant_shard_id;
+                let shard = tenants
+                    .get_mut(&tenant_shard_id)
+                    .expect("Checked by validate_placement");
+
+                for node_id in shard_placement
+                    .attached
+                    .iter()
+                    .chain(&shard_placement.secondaries)
+                {
+                    if !nodes.get(node_id).is_some_and(|n| n.is_available()) {
+                        // Warn but proceed, as in Self::tenant_shard_migrate
+                        tracing::warn!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                                       "Placing on unavailable node {node_id}");
+                    }
+                }
+
+                if shard.set_placement(
+                    scheduler,
+                    shard_placement.attached,
+                    &shard_placement.secondaries,
+                ) {
+                    tracing::info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                                   "Set placement: new intent {:?}", shard.intent);
+                    shard.sequence = shard.sequence.next();
+                    shards.push(tenant_shard_id);
+                    if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                        waiters.push(waiter);
+                    }
+                }
+            }
+
+            (shards, waiters)
+        };
+
+        self.await_waiters(waiters, RECONCILE_TIMEOUT)
+            .await
+            .map_err(|e| self.reconcile_wait_error(e))?;
+
+        Ok(TenantSetPlacementResponse { shards })
+    }
+
+    /// Report whether a tenant's shards have as many secondary locations ready to take over as their
+    /// placement policies ask for.  Unlike [`Self::tenant_placement_status`], this looks at observed
+    /// state rather than just intent, so a secondary that has been scheduled but not yet configured
+    /// on its pageserver does not count: use this to decide whether it is safe to take a node down.
+    pub(crate) fn tenant_redundancy_status(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantRedundancyStatusResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        let shards = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .map(|(tenant_shard_id, shard)| {
+                let secondaries_wanted = match shard.policy {
+                    PlacementPolicy::Attached(n) => n,
+                    PlacementPolicy::Secondary => 1,
+                    PlacementPolicy::Detached => 0,
+                };
+                let secondaries_ready = shard.get_redundant_secondaries(&locked.nodes);
+                TenantRedundancyStatusShard {
+                    tenant_shard_id: *tenant_shard_id,
+                    secondaries_wanted,
+                    secondaries_scheduled: shard.intent.get_secondary().clone(),
+                    redundant: secondaries_ready.len() >= secondaries_wanted,
+                    secondaries_ready,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if shards.is_empty() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        }
+
+        Ok(TenantRedundancyStatusResponse {
+            tenant_id,
+            redundant: shards.iter().all(|s| s.redundant),
+            shards,
+        })
+    }
+
+    /// Report whether a tenant's shards have the locations that their placement policies ask for,
+    /// and whether there are enough schedulable nodes for the policies to be met at all.
+    pub(crate) fn tenant_placement_status(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantPlacementStatusResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+
+        let schedulable_nodes = locked
+            .nodes
+            .values()
+            .filter(|n| matches!(n.may_schedule(), MaySchedule::Yes(_)))
+            .count();
+
+        let shards = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .map(|(tenant_shard_id, shard)| {
+                // Each location must be on a different node
+                let (want_attached, want_secondaries) = match shard.policy {
+                    PlacementPolicy::Attached(n) => (true, n),
+                    PlacementPolicy::Secondary => (false, 1),
+                    PlacementPolicy::Detached => (false, 0),
+                };
+                let want_locations = want_attached as usize + want_secondaries;
+
+                let attached = *shard.intent.get_attached();
+                let secondary = shard.intent.get_secondary().clone();
+                TenantPlacementStatusShard {
+                    tenant_shard_id: *tenant_shard_id,
+                    policy: shard.policy.clone(),
+                    satisfied: attached.is_some() == want_attached
+                        && secondary.len() == want_secondaries,
+                    satisfiable: schedulable_nodes >= want_locations,
+                    attached,
+                    secondary,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if shards.is_empty() {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        }
+
+        Ok(TenantPlacementStatusResponse {
+            tenant_id,
+            schedulable_nodes,
+            shards,
+        })
+    }
+
+    /// Improve the placement of one tenant's shards immediately, rather than waiting for background
+    /// optimization to get to it one change at a time.  This applies the same optimizations as
+    /// [`Self::optimize_all`] until there are none left for the tenant, and then reconciles.
+    ///
+    /// Unlike background optimization, this does not check that secondary locations are warm before
+    /// cutting over to them, so reads may be slower for a time after moving an attachment.
+    ///
+    /// Returns the shards whose locations changed.
+    pub(crate) async fn tenant_rebalance(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantRebalanceResponse, ApiError> {
+        // Optimizations converge, but bound the loop in case of a bug that makes them flap
+        const MAX_STEPS: usize = 1000;
+
+        let (shards, waiters) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let mut tenant_shards = tenants
+                .range_mut(TenantShardId::tenant_range(tenant_id))
+                .map(|(_id, shard)| shard)
+                .collect::<Vec<_>>();
+            if tenant_shards.is_empty() {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+                ));
+            }
+            if tenant_shards.iter().any(|s| {
+                !matches!(s.splitting, SplitState::Idle)
+                    || matches!(s.policy, PlacementPolicy::Detached)
+            }) {
+                return Err(ApiError::PreconditionFailed(
+                    "Tenant is splitting or detached".into(),
+                ));
+            }
+
+            let mut changed = BTreeSet::new();
+            for step in 0.. {
+                if step == MAX_STEPS {
+                    tracing::warn!("Rebalance did not converge after {MAX_STEPS} steps");
+                    break;
+                }
+
+                let mut schedule_context = ScheduleContext::default();
+                schedule_context.mode = ScheduleMode::Speculative;
+                for shard in tenant_shards.iter() {
+                    schedule_context.avoid(&shard.intent.all_pageservers());
+                    if let Some(attached) = shard.intent.get_attached() {
+                        schedule_context.push_attached(*attached);
+                    }
+                }
+
+                let mut applied = false;
+                for shard in tenant_shards.iter_mut() {
+                    if !matches!(shard.get_scheduling_policy(), ShardSchedulingPolicy::Active) {
+                        continue;
+                    }
+
+                    let optimization = shard
+                        .optimize_attachment(nodes, &schedule_context)
+                        .or_else(|| shard.optimize_secondary(scheduler, &schedule_context));
+                    if let Some(optimization) = optimization {
+                        if shard.apply_optimization(scheduler, optimization) {
+                            changed.insert(shard.tenant_shard_id);
+                            applied = true;
+                            break;
+                        }
+                    }
+                }
+
+                if !applied {
+                    break;
+                }
+            }
+
+            let mut waiters = Vec::new();
+            for shard in tenant_shards {
+                if changed.contains(&shard.tenant_shard_id) {
+                    tracing::info!(tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                                   "Rebalanced: new intent {:?}", shard.intent);
+                    if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                        waiters.push(waiter);
+                    }
+                }
+            }
+
+            (changed.into_iter().collect::<Vec<_>>(), waiters)
+        };
+
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        Ok(TenantRebalanceResponse { shards })
+    }
+
+    /// Swap the attached location of a shard with its single secondary location: the warm
+    /// secondary is promoted to attached, and the previously attached location is demoted
+    /// to secondary.  This is a planned failover, as opposed to the more general
+    /// [`Self::tenant_shard_migrate`].
+    pub(crate) async fn tenant_shard_failover(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<(), ApiError> {
+        // Exclusive, so that the shard can't start splitting while we change its intent
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_shard_id.tenant_id,
+            TenantOperations::Failover,
+        )
+        .await;
+
+        let waiter = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            // A split that failed partway leaves its shards splitting until it is aborted
+            if !matches!(shard.splitting, SplitState::Idle) {
+                return Err(ApiError::PreconditionFailed("Tenant is splitting".into()));
+            }
+
+            let Some(old_attached) = *shard.intent.get_attached() else {
+                return Err(ApiError::BadRequest(anyhow::anyhow!(
+                    "Cannot fail over a tenant shard that has no attached location"
+                )));
+            };
+
+            let new_attached = match shard.intent.get_secondary().as_slice() {
+                [secondary] => *secondary,
+                [] => {
+                    return Err(ApiError::BadRequest(anyhow::anyhow!(
+                        "Cannot fail over a tenant shard that has no secondary location"
+                    )))
+                }
+                secondaries => {
+                    return Err(ApiError::BadRequest(anyhow::anyhow!(
+                        "Cannot fail over a tenant shard with {} secondary locations: use migrate instead",
+                        secondaries.len()
+                    )))
+                }
+            };
+
+            if let Some(node) = nodes.get(&new_attached) {
+                if !node.is_available() {
+                    // Warn but proceed, as in migrations: the caller may be intervening during an incident.
+                    tracing::warn!("Failing over to unavailable node {node}");
+                }
+            }
+
+            shard.intent.demote_attached(scheduler, old_attached);
+            shard.intent.promote_attached(scheduler, new_attached);
+
+            tracing::info!("Failing over: new intent {:?}", shard.intent);
+            shard.sequence = shard.sequence.next();
+
+            self.maybe_reconcile_shard(shard, nodes)
+        };
+
+        if let Some(waiter) = waiter {
+            waiter
+                .wait_timeout(RECONCILE_TIMEOUT)
+                .await
+                .map_err(|e| self.reconcile_wait_error(e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Ask the attached location of a single shard to upload its heatmap, e.g. to refresh it before
+    /// promoting one of its secondary locations.  Returns the pageserver's response status.
+    pub(crate) async fn tenant_shard_heatmap_upload(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<StatusCode, ApiError> {
+        let node = {
+            let locked = self.inner.read().unwrap();
+            let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant shard not found").into(),
+                ));
+            };
+
+            let Some(node_id) = shard.intent.get_attached() else {
+                return Err(ApiError::PreconditionFailed(
+                    "Tenant shard is not attached".into(),
+                ));
+            };
+
+            locked
+                .nodes
+                .get(node_id)
+                .expect("Pageservers may not be deleted while referenced")
+                .clone()
+        };
+
+        match node
+            .with_client_retries(
+                |client| async move { client.tenant_heatmap_upload(tenant_shard_id).await },
+                &self.config.jwt_token,
+                1,
+                3,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await
+        {
+            None => Err(ApiError::ShuttingDown),
+            Some(Err(e)) => Err(passthrough_api_error(&node, e)),
+            Some(Ok(status)) => Ok(status),
+        }
+    }
+
+    /// This is for debug/support only: we simply drop all state for a tenant, without
+    /// detaching or deleting it on pageservers.
+    pub(crate) async fn tenant_drop(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        self.persistence.delete_tenant(tenant_id).await?;
+
+        let mut locked = self.inner.write().unwrap();
+        let (_nodes, tenants, scheduler) = locked.parts_mut();
+        let mut shards = Vec::new();
+        for (tenant_shard_id, _) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
+            shards.push(*tenant_shard_id);
+        }
+
+        for shard_id in shards {
+            if let Some(mut shard) = tenants.remove(&shard_id) {
+                shard.intent.clear(scheduler);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// This is for debug/support only: assuming tenant data is already present in S3, we "create" a
+    /// tenant with a very high generation number so that it will see the existing data.
+    pub(crate) async fn tenant_import(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantCreateResponse, ApiError> {
+        // Pick an arbitrary available pageserver to use for scanning the tenant in remote storage
+        let maybe_node = {
+            self.inner
+                .read()
+                .unwrap()
+                .nodes
+                .values()
+                .find(|n| n.is_available())
+                .cloned()
+        };
+        let Some(node) = maybe_node else {
+            return Err(ApiError::BadRequest(anyhow::anyhow!("No nodes available")));
+        };
+
+        let client = PageserverClient::new(
+            node.get_id(),
+            node.base_url(),
+            self.config.jwt_token.as_deref(),
+        );
+
+        let scan_result = client
+            .tenant_scan_remote_storage(tenant_id)
+            .await
+            .map_err(|e| passthrough_api_error(&node, e))?;
+
+        // A post-split tenant may contain a mixture of shard counts in remote storage: pick the highest count.
+        let Some(shard_count) = scan_result
+            .shards
+            .iter()
+            .map(|s| s.tenant_shard_id.shard_count)
+            .max()
+        else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("No shards found").into(),
+            ));
+        };
+
+        // Ideally we would set each newly imported shard's generation independently, but for correctness it is sufficient
+        // to
+        let generation = scan_result
+            .shards
+            .iter()
+            .map(|s| s.generation)
+            .max()
+            .expect("We already validated >0 shards");
+
+        // FIXME: we have no way to recover the shard stripe size from contents of remote storage: this will
+        // only work if they were using the default stripe size.
+        let stripe_size = ShardParameters::DEFAULT_STRIPE_SIZE;
+
+        let (response, waiters) = self
+            .do_tenant_create(TenantCreateRequest {
+                new_tenant_id: TenantShardId::unsharded(tenant_id),
+                generation,
+
+                shard_parameters: ShardParameters {
+                    count: shard_count,
+                    stripe_size,
+                },
+                placement_policy: Some(PlacementPolicy::Attached(0)), // No secondaries, for convenient debug/hacking
+
+                // There is no way to know what the tenant's config was: revert to defaults
+                //
+                // TODO: remove `switch_aux_file_policy` once we finish auxv2 migration
+                //
+                // we write to both v1+v2 storage, so that the test case can use either storage format for testing
+                config: TenantConfig {
+                    switch_aux_file_policy: Some(models::AuxFilePolicy::CrossValidation),
+                    ..TenantConfig::default()
+                },
+            })
+            .await?;
+
+        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
+            // Since this is a debug/support operation, all kinds of weird issues are possible (e.g. this
+            // tenant doesn't exist in the control plane), so don't fail the request if it can't fully
+            // reconcile, as reconciliation includes notifying compute.
+            tracing::warn!(%tenant_id, "Reconcile not done yet while importing tenant ({e})");
+        }
+
+        Ok(response)
+    }
+
+    /// The observed state of a single shard on every node where we might have a location for it.  This
+    /// is a subset of [`Self::tenants_dump`], for inspecting one shard's reconcile decisions.
+    pub(crate) fn tenant_shard_observed(
+        &self,
+        tenant_shard_id: TenantShardId,
+    ) -> Result<ObservedState, ApiError> {
+        let locked = self.inner.read().unwrap();
+        let Some(shard) = locked.tenants.get(&tenant_shard_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant shard not found").into(),
+            ));
+        };
+
+        Ok(shard.observed.clone())
+    }
+
+    /// For debug/support: a full JSON dump of TenantShards.  Returns a response so that
+    /// we don't have to make TenantShard clonable in the return path.
+    pub(crate) fn tenants_dump(
+        &self,
+        gzip: bool,
+    ) -> Result<hyper::Response<hyper::Body>, ApiError> {
+        let json = {
+            let locked = self.inner.read().unwrap();
+            let result = locked.tenants.values().collect::<Vec<_>>();
+            serialize_debug_dump(&result, self.config.max_debug_dump_bytes)?
+        };
+        debug_dump_response(json, self.config.max_debug_dump_bytes, gzip)
+    }
+
+    /// Check the consistency of in-memory state vs. persistent state, and check that the
+    /// scheduler's statistics are up to date.
+    ///
+    /// These consistency checks expect an **idle** system.  If changes are going on while
+    /// we run, then we can falsely indicate a consistency issue.  This is sufficient for end-of-test
+    /// checks, but not suitable for running continuously in the background in the field.
+    pub(crate) async fn consistency_check(&self) -> Result<(), ApiError> {
+        let (mut expect_nodes, mut expect_shards) = {
+            let locked = self.inner.read().unwrap();
+
+            locked
+                .scheduler
+                .consistency_check(locked.nodes.values(), locked.tenants.values())
+                .context("Scheduler checks")
+                .map_err(ApiError::InternalServerError)?;
+
+            let expect_nodes = locked
+                .nodes
+                .values()
+                .map(|n| n.to_persistent())
+                .collect::<Vec<_>>();
+
+            let expect_shards = locked
+                .tenants
+                .values()
+                .map(|t| t.to_persistent())
+                .collect::<Vec<_>>();
+
+            // This method can only validate the state of an idle system: if a reconcile is in
+            // progress, fail out early to avoid giving false errors on state that won't match
+            // between database and memory under a ReconcileResult is processed.
+            for t in locked.tenants.values() {
+                if t.reconciler.is_some() {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shard {} reconciliation in progress",
+                        t.tenant_shard_id
+                    )));
+                }
+            }
+
+            (expect_nodes, expect_shards)
+        };
+
+        let mut nodes = self.persistence.list_nodes().await?;
+        expect_nodes.sort_by_key(|n| n.node_id);
+        nodes.sort_by_key(|n| n.node_id);
+
+        if nodes != expect_nodes {
+            tracing::error!("Consistency check failed on nodes.");
+            tracing::error!(
+                "Nodes in memory: {}",
+                serde_json::to_string(&expect_nodes)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            tracing::error!(
+                "Nodes in database: {}",
+                serde_json::to_string(&nodes)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Node consistency failure"
+            )));
+        }
+
+        let mut shards = self.persistence.list_tenant_shards().await?;
+        shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
+        expect_shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
+
+        if shards != expect_shards {
+            tracing::error!("Consistency check failed on shards.");
+            tracing::error!(
+                "Shards in memory: {}",
+                serde_json::to_string(&expect_shards)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            tracing::error!(
+                "Shards in database: {}",
+                serde_json::to_string(&shards)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Shard consistency failure"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Like [`Self::consistency_check`], but usable while the system is busy, e.g. as a periodic
+    /// integrity check in production: rather than failing if anything is reconciling, we only compare
+    /// the nodes and shards that were quiescent throughout, and report which ones we skipped.
+    pub(crate) async fn consistency_check_relaxed(
+        &self,
+    ) -> Result<ConsistencyCheckRelaxedResponse, ApiError> {
+        // Snapshot memory on both sides of reading the database, so that we can leave out anything
+        // that changed meanwhile: it may have been written to the database but not yet to memory.
+        let (nodes_before, shards_before) = self.consistency_snapshot()?;
+
+        let db_nodes = self
+            .persistence
+            .list_nodes()
+            .await?
+            .into_iter()
+            .map(|n| (NodeId(n.node_id as u64), n))
+            .collect();
+        let db_shards = self
+            .persistence
+            .list_tenant_shards()
+            .await?
+            .into_iter()
+            .map(|tsp| {
+                let tenant_shard_id = tsp.get_tenant_shard_id();
+                tenant_shard_id.map(|id| (id, tsp))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .map_err(|e| {
+                ApiError::InternalServerError(anyhow::anyhow!("Invalid shard in database: {e}"))
+            })?;
+
+        let (nodes_after, shards_after) = self.consistency_snapshot()?;
+
+        let nodes = compare_quiescent(nodes_before, nodes_after, db_nodes);
+        let shards = compare_quiescent(shards_before, shards_after, db_shards);
+
+        for (node_id, memory, database) in &nodes.mismatched {
+            tracing::error!(
+                "Consistency check failed on node {node_id}: in memory {}, in database {}",
+                serde_json::to_string(memory)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?,
+                serde_json::to_string(database)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+        }
+        for (tenant_shard_id, memory, database) in &shards.mismatched {
+            tracing::error!(
+                "Consistency check failed on shard {tenant_shard_id}: in memory {}, in database {}",
+                serde_json::to_string(memory)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?,
+                serde_json::to_string(database)
+                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+            );
+        }
+        if !nodes.mismatched.is_empty() || !shards.mismatched.is_empty() {
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "Consistency failure: {} nodes and {} shards differ between memory and database",
+                nodes.mismatched.len(),
+                shards.mismatched.len()
+            )));
+        }
+
+        Ok(ConsistencyCheckRelaxedResponse {
+            checked_nodes: nodes.checked,
+            skipped_nodes: nodes.skipped,
+            checked_shards: shards.checked,
+            skipped_shards: shards.skipped,
+        })
+    }
+
+    /// For [`Self::consistency_check_relaxed`]: the persistent state of nodes and shards in memory,
+    /// or None for shards that are busy reconciling or splitting.
+    #[allow(clippy::type_complexity)]
+    fn consistency_snapshot(
+        &self,
+    ) -> Result<
+        (
+            BTreeMap<NodeId, Option<NodePersistence>>,
+            BTreeMap<TenantShardId, Option<TenantShardPersistence>>,
+        ),
+        ApiError,
+    > {
+        let locked = self.inner.read().unwrap();
+
+        locked
+            .scheduler
+            .consistency_check(locked.nodes.values(), locked.tenants.values())
+            .context("Scheduler checks")
+            .map_err(ApiError::InternalServerError)?;
+
+        let nodes = locked
+            .nodes
+            .iter()
+            .map(|(node_id, node)| (*node_id, Some(node.to_persistent())))
+            .collect();
+        let shards = locked
+            .tenants
+            .iter()
+            .map(|(tenant_shard_id, shard)| {
+                let busy = shard.reconciler.is_some() || shard.splitting != SplitState::Idle;
+                (*tenant_shard_id, (!busy).then(|| shard.to_persistent()))
+            })
+            .collect();
+
+        Ok((nodes, shards))
+    }
+
+    /// For debug/support: a JSON dump of the [`Scheduler`].  Returns a response so that
+    /// we don't have to make TenantShard clonable in the return path.
+    pub(crate) fn scheduler_dump(
+        &self,
+        gzip: bool,
+    ) -> Result<hyper::Response<hyper::Body>, ApiError> {
+        let json = {
+            let locked = self.inner.read().unwrap();
+            serialize_debug_dump(&locked.scheduler, self.config.max_debug_dump_bytes)?
+        };
+        debug_dump_response(json, self.config.max_debug_dump_bytes, gzip)
+    }
+
+    /// Dump nodes, tenant shards, scheduler and any ongoing node operation together.  Unlike calling
+    /// [`Self::tenants_dump`] and [`Self::scheduler_dump`] separately, these are all read under the
+    /// same lock, so the snapshot is consistent.
+    pub(crate) fn state_snapshot(
+        &self,
+        gzip: bool,
+    ) -> Result<hyper::Response<hyper::Body>, ApiError> {
+        let json = {
+            let locked = self.inner.read().unwrap();
+            let snapshot = StateSnapshot {
+                version: STATE_SNAPSHOT_VERSION,
+                nodes: locked.nodes.values().collect(),
+                tenants: locked.tenants.values().collect(),
+                scheduler: &locked.scheduler,
+                ongoing_operations: locked
+                    .ongoing_operations
+                    .values()
+                    .map(|op| op.operation.to_string())
+                    .collect(),
+            };
+            serialize_debug_dump(&snapshot, self.config.max_debug_dump_bytes)?
+        };
+        debug_dump_response(json, self.config.max_debug_dump_bytes, gzip)
+    }
+
+    /// This is for debug/support only: we simply drop all state for a tenant, without
+    /// detaching or deleting it on pageservers.  We do not try and re-schedule any
+    /// tenants that were on this node.
+    ///
+    /// To remove a node that is still in service, use [`Self::node_delete`].
+    pub(crate) async fn node_drop(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.persistence.delete_node(node_id).await?;
+        self.node_forget(node_id);
+
+        Ok(())
+    }
 
-        async fn delete_one(
-            tenant_shard_id: TenantShardId,
-            timeline_id: TimelineId,
-            node: Node,
-            jwt: Option<String>,
-        ) -> Result<StatusCode, ApiError> {
-            tracing::info!(
-                "Deleting timeline on shard {tenant_shard_id}/{timeline_id}, attached to node {node}",
-            );
+    /// Remove a node from service: move all its locations elsewhere, detach anything left on
+    /// the pageserver, and only then forget the node.  Refuses up front if some shard on the
+    /// node would have nowhere to go.  If a later step fails, the node is left paused, and the
+    /// deletion may be retried.
+    pub(crate) async fn node_delete(&self, node_id: NodeId) -> Result<(), ApiError> {
+        {
+            let locked = self.inner.read().unwrap();
+            let node = locked.nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {node_id} not registered").into(),
+            ))?;
 
-            let client = PageserverClient::new(node.get_id(), node.base_url(), jwt.as_deref());
-            client
-                .timeline_delete(tenant_shard_id, timeline_id)
-                .await
-                .map_err(|e| {
-                    ApiError::InternalServerError(anyhow::anyhow!(
-                    "Error deleting timeline {timeline_id} on {tenant_shard_id} on node {node}: {e}",
-                ))
-                })
-        }
+            // Deletion moves shards around by itself: don't interleave it with operations on
+            // any other node.
+            if let Some(ongoing) = locked.ongoing_operations.values().next() {
+                return Err(ApiError::PreconditionFailed(
+                    format!(
+                        "Background operation already ongoing for node: {}",
+                        ongoing.operation
+                    )
+                    .into(),
+                ));
+            }
 
-        let statuses = self
-            .tenant_for_shards(targets, |tenant_shard_id: TenantShardId, node: Node| {
-                Box::pin(delete_one(
-                    tenant_shard_id,
-                    timeline_id,
-                    node,
-                    self.config.jwt_token.clone(),
-                ))
-            })
-            .await?;
+            if !node.is_available() {
+                return Err(ApiError::ResourceUnavailable(
+                    format!("Node {node_id} is currently unavailable").into(),
+                ));
+            }
 
-        // If any shards >0 haven't finished deletion yet, don't start deletion on shard zero
-        if statuses.iter().any(|s| s != &StatusCode::NOT_FOUND) {
-            return Ok(StatusCode::ACCEPTED);
+            check_node_evacuable(node_id, &locked.nodes, locked.tenants.iter())?;
         }
 
-        // Delete shard zero last: this is not strictly necessary, but since a caller's GET on a timeline will be routed
-        // to shard zero, it gives a more obvious behavior that a GET returns 404 once the deletion is done.
-        let shard_zero_status = delete_one(
-            shard_zero.0,
-            timeline_id,
-            shard_zero.1,
-            self.config.jwt_token.clone(),
+        // Stop anything new being scheduled onto the node while we empty it.
+        self.node_configure(
+            node_id,
+            None,
+            Some(NodeSchedulingPolicy::Pause),
+            NodeTransitionSource::Controller,
+            None,
         )
         .await?;
 
-        Ok(shard_zero_status)
-    }
+        let _node_lock =
+            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Delete).await;
 
-    /// When you need to send an HTTP request to the pageserver that holds shard0 of a tenant, this
-    /// function looks up and returns node. If the tenant isn't found, returns Err(ApiError::NotFound)
-    pub(crate) fn tenant_shard0_node(
-        &self,
-        tenant_id: TenantId,
-    ) -> Result<(Node, TenantShardId), ApiError> {
-        let locked = self.inner.read().unwrap();
-        let Some((tenant_shard_id, shard)) = locked
-            .tenants
-            .range(TenantShardId::tenant_range(tenant_id))
-            .next()
-        else {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
-            ));
-        };
+        let (node, waiters) = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+            let node = nodes.get(&node_id).cloned().ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {node_id} not registered").into(),
+            ))?;
 
-        // TODO: should use the ID last published to compute_hook, rather than the intent: the intent might
-        // point to somewhere we haven't attached yet.
-        let Some(node_id) = shard.intent.get_attached() else {
-            tracing::warn!(
-                tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
-                "Shard not scheduled (policy {:?}), cannot generate pass-through URL",
-                shard.policy
-            );
-            return Err(ApiError::Conflict(
-                "Cannot call timeline API on non-attached tenant".to_string(),
-            ));
+            let tenant_ids = tenants
+                .iter()
+                .filter(|(_, shard)| shard.intent.references(node_id))
+                .map(|(tenant_shard_id, _)| tenant_shard_id.tenant_id)
+                .collect::<BTreeSet<_>>();
+
+            let mut waiters = Vec::new();
+            for tenant_id in tenant_ids {
+                // Spread replacements away from the tenant's other shards, as in
+                // [`Self::tenant_evacuate_node`]
+                let mut schedule_context = ScheduleContext::default();
+                for (_, shard) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
+                    schedule_context.avoid(&shard.intent.all_pageservers());
+                    if let Some(attached) = shard.intent.get_attached() {
+                        schedule_context.push_attached(*attached);
+                    }
+                }
+
+                for (tenant_shard_id, shard) in
+                    tenants.range_mut(TenantShardId::tenant_range(tenant_id))
+                {
+                    match shard.evacuate_node(scheduler, node_id, &schedule_context) {
+                        Ok(true) => {}
+                        Ok(false) => continue,
+                        Err(e) => {
+                            return Err(ApiError::PreconditionFailed(
+                                format!(
+                                    "Node {node_id} is paused but not deleted: cannot move {tenant_shard_id} ({e})"
+                                )
+                                .into(),
+                            ));
+                        }
+                    }
+                    tracing::info!(tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(),
+                                   "Evacuating from node {node_id}: new intent {:?}", shard.intent);
+                    shard.sequence = shard.sequence.next();
+                    if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                        waiters.push(waiter);
+                    }
+                }
+            }
+
+            (node, waiters)
         };
 
-        let Some(node) = locked.nodes.get(node_id) else {
-            // This should never happen
-            return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                "Shard refers to nonexistent node"
-            )));
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        // Reconciliation has detached everything we know of: clean up anything else the
+        // pageserver still has, so that nothing is left behind once we forget the node.
+        let configs = match node
+            .with_client_retries(
+                |client| async move { client.list_location_config().await },
+                &self.config.jwt_token,
+                1,
+                5,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await
+        {
+            None => return Err(ApiError::ShuttingDown),
+            Some(Err(e)) => return Err(passthrough_api_error(&node, e)),
+            Some(Ok(configs)) => configs,
         };
 
-        Ok((node.clone(), *tenant_shard_id))
+        for (tenant_shard_id, conf) in configs.tenant_shards {
+            if matches!(conf, Some(conf) if conf.mode == LocationConfigMode::Detached) {
+                continue;
+            }
+
+            tracing::info!("Detaching {tenant_shard_id} from node {node_id}");
+            match node
+                .with_client_retries(
+                    |client| async move {
+                        let config = LocationConfig {
+                            mode: LocationConfigMode::Detached,
+                            generation: None,
+                            secondary_conf: None,
+                            shard_number: tenant_shard_id.shard_number.0,
+                            shard_count: tenant_shard_id.shard_count.literal(),
+                            shard_stripe_size: 0,
+                            tenant_conf: models::TenantConfig::default(),
+                        };
+                        client
+                            .location_config(tenant_shard_id, config, None, false)
+                            .await
+                    },
+                    &self.config.jwt_token,
+                    1,
+                    5,
+                    SHORT_RECONCILE_TIMEOUT,
+                    &self.cancel,
+                )
+                .await
+            {
+                None => return Err(ApiError::ShuttingDown),
+                Some(Err(e)) => return Err(passthrough_api_error(&node, e)),
+                Some(Ok(())) => {}
+            }
+        }
+
+        self.persistence.delete_node(node_id).await?;
+        self.node_forget(node_id);
+
+        tracing::info!("Deleted node {node_id}");
+
+        Ok(())
     }
 
-    pub(crate) fn tenant_locate(
-        &self,
-        tenant_id: TenantId,
-    ) -> Result<TenantLocateResponse, ApiError> {
-        let locked = self.inner.read().unwrap();
-        tracing::info!("Locating shards for tenant {tenant_id}");
+    /// Drop our in-memory state for a node that has been removed from the database
+    fn node_forget(&self, node_id: NodeId) {
+        let mut locked = self.inner.write().unwrap();
 
-        let mut result = Vec::new();
-        let mut shard_params: Option<ShardParameters> = None;
+        for shard in locked.tenants.values_mut() {
+            shard.deref_node(node_id);
+        }
 
-        for (tenant_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-        {
-            let node_id =
-                shard
-                    .intent
-                    .get_attached()
-                    .ok_or(ApiError::BadRequest(anyhow::anyhow!(
-                        "Cannot locate a tenant that is not attached"
-                    )))?;
+        let mut nodes = (*locked.nodes).clone();
+        nodes.remove(&node_id);
+        locked.nodes = Arc::new(nodes);
 
-            let node = locked
+        locked.scheduler.node_remove(node_id);
+    }
+
+    pub(crate) async fn node_list(&self) -> Result<Vec<Node>, ApiError> {
+        let nodes = {
+            self.inner
+                .read()
+                .unwrap()
                 .nodes
-                .get(&node_id)
-                .expect("Pageservers may not be deleted while referenced");
+                .values()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
 
-            result.push(node.shard_location(*tenant_shard_id));
+        Ok(nodes)
+    }
 
-            match &shard_params {
-                None => {
-                    shard_params = Some(ShardParameters {
-                        stripe_size: shard.shard.stripe_size,
-                        count: shard.shard.count,
-                    });
-                }
-                Some(params) => {
-                    if params.stripe_size != shard.shard.stripe_size {
-                        // This should never happen.  We enforce at runtime because it's simpler than
-                        // adding an extra per-tenant data structure to store the things that should be the same
-                        return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                            "Inconsistent shard stripe size parameters!"
-                        )));
+    pub(crate) async fn get_node(&self, node_id: NodeId) -> Result<Node, ApiError> {
+        self.inner
+            .read()
+            .unwrap()
+            .nodes
+            .get(&node_id)
+            .cloned()
+            .ok_or(ApiError::NotFound(
+                format!("Node {node_id} not registered").into(),
+            ))
+    }
+
+    /// The recent availability and scheduling policy transitions of a node, oldest first
+    pub(crate) fn node_history(&self, node_id: NodeId) -> Result<NodeHistoryResponse, ApiError> {
+        let locked = self.inner.read().unwrap();
+        let Some(node) = locked.nodes.get(&node_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Node {node_id} not registered").into(),
+            ));
+        };
+
+        Ok(NodeHistoryResponse {
+            node_id,
+            transitions: node.get_history().cloned().collect(),
+        })
+    }
+
+    pub(crate) async fn node_register(
+        &self,
+        register_req: NodeRegisterRequest,
+    ) -> Result<(), ApiError> {
+        let _node_lock = trace_exclusive_lock(
+            &self.node_op_locks,
+            register_req.node_id,
+            NodeOperations::Register,
+        )
+        .await;
+
+        let capabilities_changed = {
+            let locked = self.inner.read().unwrap();
+            if let Some(node) = locked.nodes.get(&register_req.node_id) {
+                // Note that we do not do a total equality of the struct, because we don't require
+                // the availability/scheduling states to agree for a POST to be idempotent.
+                if node.registration_match(&register_req) {
+                    tracing::info!(
+                        "Node {} re-registered with matching address",
+                        register_req.node_id
+                    );
+                    // Registrations which do not mention capabilities (e.g. from the CLI)
+                    // leave the ones the node reported for itself in place.
+                    if register_req.capabilities.is_none()
+                        || node.get_capabilities() == register_req.capabilities
+                    {
+                        return Ok(());
                     }
+                    true
+                } else {
+                    // TODO: decide if we want to allow modifying node addresses without removing and re-adding
+                    // the node.  Safest/simplest thing is to refuse it, and usually we deploy with
+                    // a fixed address through the lifetime of a node.
+                    tracing::warn!(
+                        "Node {} tried to register with different address",
+                        register_req.node_id
+                    );
+                    return Err(ApiError::Conflict(
+                        "Node is already registered with different address".to_string(),
+                    ));
                 }
+            } else {
+                false
             }
+        };
+
+        if capabilities_changed {
+            // A node reports its capabilities every time it starts, so they change when it is
+            // upgraded or downgraded.
+            tracing::info!(
+                "Node {} capabilities changed to {:?}",
+                register_req.node_id,
+                register_req.capabilities
+            );
+            self.persistence
+                .update_node_capabilities(register_req.node_id, register_req.capabilities)
+                .await?;
+
+            let mut locked = self.inner.write().unwrap();
+            let mut new_nodes = (*locked.nodes).clone();
+            if let Some(node) = new_nodes.get_mut(&register_req.node_id) {
+                node.set_capabilities(register_req.capabilities);
+            }
+            locked.nodes = Arc::new(new_nodes);
+            return Ok(());
         }
 
-        if result.is_empty() {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("No shards for this tenant ID found").into(),
+        // We do not require that a node is actually online when registered (it will start life
+        // with it's  availability set to Offline), but we _do_ require that its DNS record exists. We're
+        // therefore not immune to asymmetric L3 connectivity issues, but we are protected against nodes
+        // that register themselves with a broken DNS config.  We check only the HTTP hostname, because
+        // the postgres hostname might only be resolvable to clients (e.g. if we're on a different VPC than clients).
+        if tokio::net::lookup_host(format!(
+            "{}:{}",
+            register_req.listen_http_addr, register_req.listen_http_port
+        ))
+        .await
+        .is_err()
+        {
+            // If we have a transient DNS issue, it's up to the caller to retry their registration.  Because
+            // we can't robustly distinguish between an intermittent issue and a totally bogus DNS situation,
+            // we return a soft 503 error, to encourage callers to retry past transient issues.
+            return Err(ApiError::ResourceUnavailable(
+                format!(
+                    "Node {} tried to register with unknown DNS name '{}'",
+                    register_req.node_id, register_req.listen_http_addr
+                )
+                .into(),
             ));
         }
-        let shard_params = shard_params.expect("result is non-empty, therefore this is set");
-        tracing::info!(
-            "Located tenant {} with params {:?} on shards {}",
-            tenant_id,
-            shard_params,
-            result
-                .iter()
-                .map(|s| format!("{:?}", s))
-                .collect::<Vec<_>>()
-                .join(",")
-        );
 
-        Ok(TenantLocateResponse {
-            shards: result,
-            shard_params,
-        })
-    }
+        // Ordering: we must persist the new node _before_ adding it to in-memory state.
+        // This ensures that before we use it for anything or expose it via any external
+        // API, it is guaranteed to be available after a restart.
+        let mut new_node = Node::new(
+            register_req.node_id,
+            register_req.listen_http_addr,
+            register_req.listen_http_port,
+            register_req.listen_pg_addr,
+            register_req.listen_pg_port,
+        );
+        new_node.set_capabilities(register_req.capabilities);
 
-    /// Returns None if the input iterator of shards does not include a shard with number=0
-    fn tenant_describe_impl<'a>(
-        &self,
-        shards: impl Iterator<Item = &'a TenantShard>,
-    ) -> Option<TenantDescribeResponse> {
-        let mut shard_zero = None;
-        let mut describe_shards = Vec::new();
+        // TODO: idempotency if the node already exists in the database
+        self.persistence.insert_node(&new_node).await?;
 
-        for shard in shards {
-            if shard.tenant_shard_id.is_shard_zero() {
-                shard_zero = Some(shard);
-            }
+        let mut locked = self.inner.write().unwrap();
+        let mut new_nodes = (*locked.nodes).clone();
 
-            describe_shards.push(TenantDescribeResponseShard {
-                tenant_shard_id: shard.tenant_shard_id,
-                node_attached: *shard.intent.get_attached(),
-                node_secondary: shard.intent.get_secondary().to_vec(),
-                last_error: shard
-                    .last_error
-                    .lock()
-                    .unwrap()
-                    .as_ref()
-                    .map(|e| format!("{e}"))
-                    .unwrap_or("".to_string())
-                    .clone(),
-                is_reconciling: shard.reconciler.is_some(),
-                is_pending_compute_notification: shard.pending_compute_notification,
-                is_splitting: matches!(shard.splitting, SplitState::Splitting),
-                scheduling_policy: *shard.get_scheduling_policy(),
-            })
-        }
+        locked.scheduler.node_upsert(&new_node);
+        new_nodes.insert(register_req.node_id, new_node);
 
-        let shard_zero = shard_zero?;
+        locked.nodes = Arc::new(new_nodes);
 
-        Some(TenantDescribeResponse {
-            tenant_id: shard_zero.tenant_shard_id.tenant_id,
-            shards: describe_shards,
-            stripe_size: shard_zero.shard.stripe_size,
-            policy: shard_zero.policy.clone(),
-            config: shard_zero.config.clone(),
-        })
+        tracing::info!(
+            "Registered pageserver {}, now have {} pageservers",
+            register_req.node_id,
+            locked.nodes.len()
+        );
+        Ok(())
     }
 
-    pub(crate) fn tenant_describe(
+    pub(crate) async fn node_configure(
         &self,
-        tenant_id: TenantId,
-    ) -> Result<TenantDescribeResponse, ApiError> {
-        let locked = self.inner.read().unwrap();
-
-        self.tenant_describe_impl(
-            locked
-                .tenants
-                .range(TenantShardId::tenant_range(tenant_id))
-                .map(|(_k, v)| v),
-        )
-        .ok_or_else(|| ApiError::NotFound(anyhow::anyhow!("Tenant {tenant_id} not found").into()))
-    }
-
-    pub(crate) fn tenant_list(&self) -> Vec<TenantDescribeResponse> {
-        let locked = self.inner.read().unwrap();
+        node_id: NodeId,
+        availability: Option<NodeAvailability>,
+        scheduling: Option<NodeSchedulingPolicy>,
+        source: NodeTransitionSource,
+        offline_reason: Option<String>,
+    ) -> Result<(), ApiError> {
+        let _node_lock =
+            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Configure).await;
 
-        let mut result = Vec::new();
-        for (_tenant_id, tenant_shards) in
-            &locked.tenants.iter().group_by(|(id, _shard)| id.tenant_id)
-        {
-            result.push(
-                self.tenant_describe_impl(tenant_shards.map(|(_k, v)| v))
-                    .expect("Groups are always non-empty"),
-            );
+        if let Some(scheduling) = scheduling {
+            // Scheduling is a persistent part of Node: we must write updates to the database before
+            // applying them in memory
+            self.persistence.update_node(node_id, scheduling).await?;
         }
 
-        result
-    }
-
-    #[instrument(skip_all, fields(tenant_id=%op.tenant_id))]
-    async fn abort_tenant_shard_split(
-        &self,
-        op: &TenantShardSplitAbort,
-    ) -> Result<(), TenantShardSplitAbortError> {
-        // Cleaning up a split:
-        // - Parent shards are not destroyed during a split, just detached.
-        // - Failed pageserver split API calls can leave the remote node with just the parent attached,
-        //   just the children attached, or both.
-        //
-        // Therefore our work to do is to:
-        // 1. Clean up storage controller's internal state to just refer to parents, no children
-        // 2. Call out to pageservers to ensure that children are detached
-        // 3. Call out to pageservers to ensure that parents are attached.
+        // If we're activating a node, then before setting it active we must reconcile any shard locations
+        // on that node, in case it is out of sync, e.g. due to being unavailable during controller startup,
+        // by calling [`Self::node_activate_reconcile`]
         //
-        // Crash safety:
-        // - If the storage controller stops running during this cleanup *after* clearing the splitting state
-        //   from our database, then [`Self::startup_reconcile`] will regard child attachments as garbage
-        //   and detach them.
-        // - TODO: If the storage controller stops running during this cleanup *before* clearing the splitting state
-        //   from our database, then we will re-enter this cleanup routine on startup.
+        // The transition we calculate here remains valid later in the function because we hold the op lock on the node:
+        // nothing else can mutate its availability while we run.
+        let availability_transition = if let Some(input_availability) = availability {
+            let (activate_node, availability_transition) = {
+                let locked = self.inner.read().unwrap();
+                let Some(node) = locked.nodes.get(&node_id) else {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Node {} not registered", node_id).into(),
+                    ));
+                };
 
-        let TenantShardSplitAbort {
-            tenant_id,
-            new_shard_count,
-            new_stripe_size,
-            ..
-        } = op;
+                (
+                    node.clone(),
+                    node.get_availability_transition(input_availability),
+                )
+            };
 
-        // First abort persistent state, if any exists.
-        match self
-            .persistence
-            .abort_shard_split(*tenant_id, *new_shard_count)
-            .await?
-        {
-            AbortShardSplitStatus::Aborted => {
-                // Proceed to roll back any child shards created on pageservers
-            }
-            AbortShardSplitStatus::Complete => {
-                // The split completed (we might hit that path if e.g. our database transaction
-                // to write the completion landed in the database, but we dropped connection
-                // before seeing the result).
-                //
-                // We must update in-memory state to reflect the successful split.
-                self.tenant_shard_split_commit_inmem(
-                    *tenant_id,
-                    *new_shard_count,
-                    *new_stripe_size,
-                );
-                return Ok(());
+            if matches!(availability_transition, AvailabilityTransition::ToActive) {
+                self.node_activate_reconcile(activate_node, &_node_lock)
+                    .await?;
             }
-        }
+            availability_transition
+        } else {
+            AvailabilityTransition::Unchanged
+        };
 
-        // Clean up in-memory state, and accumulate the list of child locations that need detaching
-        let detach_locations: Vec<(Node, TenantShardId)> = {
-            let mut detach_locations = Vec::new();
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
+        // Apply changes from the request to our in-memory state for the Node
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
 
-            for (tenant_shard_id, shard) in
-                tenants.range_mut(TenantShardId::tenant_range(op.tenant_id))
-            {
-                if shard.shard.count == op.new_shard_count {
-                    // Surprising: the phase of [`Self::do_tenant_shard_split`] which inserts child shards in-memory
-                    // is infallible, so if we got an error we shouldn't have got that far.
-                    tracing::warn!(
-                        "During split abort, child shard {tenant_shard_id} found in-memory"
-                    );
-                    continue;
-                }
+        let mut new_nodes = (**nodes).clone();
 
-                // Add the children of this shard to this list of things to detach
-                if let Some(node_id) = shard.intent.get_attached() {
-                    for child_id in tenant_shard_id.split(*new_shard_count) {
-                        detach_locations.push((
-                            nodes
-                                .get(node_id)
-                                .expect("Intent references nonexistent node")
-                                .clone(),
-                            child_id,
-                        ));
-                    }
-                } else {
-                    tracing::warn!(
-                        "During split abort, shard {tenant_shard_id} has no attached location"
-                    );
-                }
+        let Some(node) = new_nodes.get_mut(&node_id) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Node not registered").into(),
+            ));
+        };
 
-                tracing::info!("Restoring parent shard {tenant_shard_id}");
-                shard.splitting = SplitState::Idle;
-                if let Err(e) = shard.schedule(scheduler, &mut ScheduleContext::default()) {
-                    // If this shard can't be scheduled now (perhaps due to offline nodes or
-                    // capacity issues), that must not prevent us rolling back a split.  In this
-                    // case it should be eventually scheduled in the background.
-                    tracing::warn!("Failed to schedule {tenant_shard_id} during shard abort: {e}")
-                }
+        node.apply_transition(availability, scheduling, source, offline_reason);
 
-                self.maybe_reconcile_shard(shard, nodes);
-            }
+        // Update the scheduler, in case the elegibility of the node for new shards has changed
+        scheduler.node_upsert(node);
 
-            // We don't expect any new_shard_count shards to exist here, but drop them just in case
-            tenants.retain(|_id, s| s.shard.count != *new_shard_count);
+        let new_nodes = Arc::new(new_nodes);
 
-            detach_locations
-        };
+        // Modify scheduling state for any Tenants that are affected by a change in the node's availability state.
+        match availability_transition {
+            AvailabilityTransition::ToOffline => {
+                tracing::info!("Node {} transition to offline", node_id);
+                let mut tenants_affected: usize = 0;
 
-        for (node, child_id) in detach_locations {
-            if !node.is_available() {
-                // An unavailable node cannot be cleaned up now: to avoid blocking forever, we will permit this, and
-                // rely on the reconciliation that happens when a node transitions to Active to clean up. Since we have
-                // removed child shards from our in-memory state and database, the reconciliation will implicitly remove
-                // them from the node.
-                tracing::warn!("Node {node} unavailable, can't clean up during split abort. It will be cleaned up when it is reactivated.");
-                continue;
-            }
+                for (tenant_shard_id, tenant_shard) in tenants {
+                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
+                        // When a node goes offline, we set its observed configuration to None, indicating unknown: we will
+                        // not assume our knowledge of the node's configuration is accurate until it comes back online
+                        observed_loc.conf = None;
+                    }
 
-            // Detach the remote child.  If the pageserver split API call is still in progress, this call will get
-            // a 503 and retry, up to our limit.
-            tracing::info!("Detaching {child_id} on {node}...");
-            match node
-                .with_client_retries(
-                    |client| async move {
-                        let config = LocationConfig {
-                            mode: LocationConfigMode::Detached,
-                            generation: None,
-                            secondary_conf: None,
-                            shard_number: child_id.shard_number.0,
-                            shard_count: child_id.shard_count.literal(),
-                            // Stripe size and tenant config don't matter when detaching
-                            shard_stripe_size: 0,
-                            tenant_conf: TenantConfig::default(),
-                        };
+                    if single_node_deployment(self.config.single_node, &new_nodes) {
+                        // Nowhere else to put the shards: don't try rescheduling them
+                        continue;
+                    }
 
-                        client.location_config(child_id, config, None, false).await
-                    },
-                    &self.config.jwt_token,
-                    1,
-                    10,
-                    Duration::from_secs(5),
-                    &self.cancel,
-                )
-                .await
-            {
-                Some(Ok(_)) => {}
-                Some(Err(e)) => {
-                    // We failed to communicate with the remote node.  This is problematic: we may be
-                    // leaving it with a rogue child shard.
-                    tracing::warn!(
-                        "Failed to detach child {child_id} from node {node} during abort"
-                    );
-                    return Err(e.into());
-                }
-                None => {
-                    // Cancellation: we were shutdown or the node went offline. Shutdown is fine, we'll
-                    // clean up on restart. The node going offline requires a retry.
-                    return Err(TenantShardSplitAbortError::Unavailable);
-                }
-            };
-        }
+                    if !new_nodes
+                        .values()
+                        .any(|n| matches!(n.may_schedule(), MaySchedule::Yes(_)))
+                    {
+                        // Special case for when all nodes are unavailable and/or unschedulable: there is no point
+                        // trying to reschedule since there's nowhere else to go. Without this
+                        // branch we incorrectly detach tenants in response to node unavailability.
+                        continue;
+                    }
 
-        tracing::info!("Successfully aborted split");
-        Ok(())
-    }
+                    if tenant_shard.intent.demote_attached(scheduler, node_id) {
+                        tenant_shard.sequence = tenant_shard.sequence.next();
 
-    /// Infallible final stage of [`Self::tenant_shard_split`]: update the contents
-    /// of the tenant map to reflect the child shards that exist after the split.
-    fn tenant_shard_split_commit_inmem(
-        &self,
-        tenant_id: TenantId,
-        new_shard_count: ShardCount,
-        new_stripe_size: Option<ShardStripeSize>,
-    ) -> (
-        TenantShardSplitResponse,
-        Vec<(TenantShardId, NodeId, ShardStripeSize)>,
-        Vec<ReconcilerWaiter>,
-    ) {
-        let mut response = TenantShardSplitResponse {
-            new_shards: Vec::new(),
-        };
-        let mut child_locations = Vec::new();
-        let mut waiters = Vec::new();
+                        // TODO: populate a ScheduleContext including all shards in the same tenant_id (only matters
+                        // for tenants without secondary locations: if they have a secondary location, then this
+                        // schedule() call is just promoting an existing secondary)
+                        let mut schedule_context = ScheduleContext::default();
 
-        {
-            let mut locked = self.inner.write().unwrap();
+                        match tenant_shard.schedule(scheduler, &mut schedule_context) {
+                            Err(e) => {
+                                // It is possible that some tenants will become unschedulable when too many pageservers
+                                // go offline: in this case there isn't much we can do other than make the issue observable,
+                                // which schedule() does by recording the error on the shard for tenant_describe.
+                                tracing::warn!(%tenant_shard_id, "Scheduling error when marking pageserver {} offline: {e}", node_id);
+                            }
+                            Ok(()) => {
+                                if self
+                                    .maybe_reconcile_shard(tenant_shard, &new_nodes)
+                                    .is_some()
+                                {
+                                    tenants_affected += 1;
+                                };
+                            }
+                        }
+                    }
+                }
+                tracing::info!(
+                    "Launched {} reconciler tasks for tenants affected by node {} going offline",
+                    tenants_affected,
+                    node_id
+                )
+            }
+            AvailabilityTransition::ToActive => {
+                tracing::info!("Node {} transition to active", node_id);
+                // When a node comes back online, we must reconcile any tenant that has a None observed
+                // location on the node.
+                for tenant_shard in locked.tenants.values_mut() {
+                    // If a reconciliation is already in progress, rely on the previous scheduling
+                    // decision and skip triggering a new reconciliation.
+                    if tenant_shard.reconciler.is_some() {
+                        continue;
+                    }
 
-            let parent_ids = locked
-                .tenants
-                .range(TenantShardId::tenant_range(tenant_id))
-                .map(|(shard_id, _)| *shard_id)
-                .collect::<Vec<_>>();
+                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
+                        if observed_loc.conf.is_none() {
+                            self.maybe_reconcile_shard(tenant_shard, &new_nodes);
+                        }
+                    }
+                }
 
-            let (nodes, tenants, scheduler) = locked.parts_mut();
-            for parent_id in parent_ids {
-                let child_ids = parent_id.split(new_shard_count);
+                // TODO: in the background, we should balance work back onto this pageserver
+            }
+            AvailabilityTransition::Unchanged => {
+                tracing::debug!("Node {} no availability change during config", node_id);
+            }
+        }
 
-                let (pageserver, generation, policy, parent_ident, config) = {
-                    let mut old_state = tenants
-                        .remove(&parent_id)
-                        .expect("It was present, we just split it");
+        locked.nodes = new_nodes;
 
-                    // A non-splitting state is impossible, because [`Self::tenant_shard_split`] holds
-                    // a TenantId lock and passes it through to [`TenantShardSplitAbort`] in case of cleanup:
-                    // nothing else can clear this.
-                    assert!(matches!(old_state.splitting, SplitState::Splitting));
+        Ok(())
+    }
 
-                    let old_attached = old_state.intent.get_attached().unwrap();
-                    old_state.intent.clear(scheduler);
-                    let generation = old_state.generation.expect("Shard must have been attached");
-                    (
-                        old_attached,
-                        generation,
-                        old_state.policy,
-                        old_state.shard,
-                        old_state.config,
-                    )
-                };
+    /// Stop scheduling new shards onto a node, without moving the ones already there.  This is
+    /// the lightweight counterpart of [`Self::start_node_drain`]: it only sets the node's
+    /// scheduling policy to [`NodeSchedulingPolicy::Pause`].
+    pub(crate) async fn node_cordon(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.node_set_cordon(node_id, NodeSchedulingPolicy::Pause)
+            .await
+    }
 
-                let mut schedule_context = ScheduleContext::default();
-                for child in child_ids {
-                    let mut child_shard = parent_ident;
-                    child_shard.number = child.shard_number;
-                    child_shard.count = child.shard_count;
-                    if let Some(stripe_size) = new_stripe_size {
-                        child_shard.stripe_size = stripe_size;
-                    }
+    /// Undo [`Self::node_cordon`], making the node eligible for new shards again.
+    pub(crate) async fn node_uncordon(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.node_set_cordon(node_id, NodeSchedulingPolicy::Active)
+            .await
+    }
 
-                    let mut child_observed: HashMap<NodeId, ObservedStateLocation> = HashMap::new();
-                    child_observed.insert(
-                        pageserver,
-                        ObservedStateLocation {
-                            conf: Some(attached_location_conf(
-                                generation,
-                                &child_shard,
-                                &config,
-                                &policy,
-                            )),
-                        },
-                    );
+    async fn node_set_cordon(
+        &self,
+        node_id: NodeId,
+        policy: NodeSchedulingPolicy,
+    ) -> Result<(), ApiError> {
+        let node_policy = {
+            let locked = self.inner.read().unwrap();
+            let node = locked.nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+            node.get_scheduling()
+        };
 
-                    let mut child_state = TenantShard::new(child, child_shard, policy.clone());
-                    child_state.intent = IntentState::single(scheduler, Some(pageserver));
-                    child_state.observed = ObservedState {
-                        locations: child_observed,
-                    };
-                    child_state.generation = Some(generation);
-                    child_state.config = config.clone();
+        // A drain or fill owns the node's scheduling policy until it completes or is cancelled:
+        // overwriting it from here would confuse the background operation.
+        match node_policy {
+            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Pause => {
+                self.node_configure(
+                    node_id,
+                    None,
+                    Some(policy),
+                    NodeTransitionSource::Operator,
+                    None,
+                )
+                .await
+            }
+            other => Err(ApiError::PreconditionFailed(
+                format!("Node {node_id} has scheduling policy {other:?}").into(),
+            )),
+        }
+    }
 
-                    // The child's TenantShard::splitting is intentionally left at the default value of Idle,
-                    // as at this point in the split process we have succeeded and this part is infallible:
-                    // we will never need to do any special recovery from this state.
+    pub(crate) async fn start_node_drain(
+        self: &Arc<Self>,
+        node_id: NodeId,
+    ) -> Result<(), ApiError> {
+        let (ongoing_op, node_available, node_policy, schedulable_nodes_count) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+            let schedulable_nodes_count = nodes
+                .iter()
+                .filter(|(_, n)| matches!(n.may_schedule(), MaySchedule::Yes(_)))
+                .count();
 
-                    child_locations.push((child, pageserver, child_shard.stripe_size));
+            (
+                locked
+                    .ongoing_operations
+                    .get(&node_id)
+                    .map(|ongoing| ongoing.operation),
+                node.is_available(),
+                node.get_scheduling(),
+                schedulable_nodes_count,
+            )
+        };
 
-                    if let Err(e) = child_state.schedule(scheduler, &mut schedule_context) {
-                        // This is not fatal, because we've implicitly already got an attached
-                        // location for the child shard.  Failure here just means we couldn't
-                        // find a secondary (e.g. because cluster is overloaded).
-                        tracing::warn!("Failed to schedule child shard {child}: {e}");
-                    }
-                    // In the background, attach secondary locations for the new shards
-                    if let Some(waiter) = self.maybe_reconcile_shard(&mut child_state, nodes) {
-                        waiters.push(waiter);
-                    }
+        if let Some(ongoing) = ongoing_op {
+            return Err(ApiError::PreconditionFailed(
+                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            ));
+        }
 
-                    tenants.insert(child, child_state);
-                    response.new_shards.push(child);
-                }
-            }
-            (response, child_locations, waiters)
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
         }
-    }
 
-    async fn tenant_shard_split_start_secondaries(
-        &self,
-        tenant_id: TenantId,
-        waiters: Vec<ReconcilerWaiter>,
-    ) {
-        // Wait for initial reconcile of child shards, this creates the secondary locations
-        if let Err(e) = self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
-            // This is not a failure to split: it's some issue reconciling the new child shards, perhaps
-            // their secondaries couldn't be attached.
-            tracing::warn!("Failed to reconcile after split: {e}");
-            return;
+        if schedulable_nodes_count == 0 {
+            return Err(ApiError::PreconditionFailed(
+                "No other schedulable nodes to drain to".into(),
+            ));
         }
 
-        // Take the state lock to discover the attached & secondary intents for all shards
-        let (attached, secondary) = {
-            let locked = self.inner.read().unwrap();
-            let mut attached = Vec::new();
-            let mut secondary = Vec::new();
+        match node_policy {
+            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Pause => {
+                self.node_configure(
+                    node_id,
+                    None,
+                    Some(NodeSchedulingPolicy::Draining),
+                    NodeTransitionSource::Controller,
+                    None,
+                )
+                .await?;
 
-            for (tenant_shard_id, shard) in
-                locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-            {
-                let Some(node_id) = shard.intent.get_attached() else {
-                    // Unexpected.  Race with a PlacementPolicy change?
-                    tracing::warn!(
-                        "No attached node on {tenant_shard_id} immediately after shard split!"
-                    );
-                    continue;
-                };
+                let cancel = self.cancel.child_token();
+                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
 
-                let Some(secondary_node_id) = shard.intent.get_secondary().first() else {
-                    // No secondary location.  Nothing for us to do.
-                    continue;
-                };
+                self.inner.write().unwrap().ongoing_operations.insert(
+                    node_id,
+                    OperationHandler {
+                        operation: Operation::Drain(Drain { node_id }),
+                        cancel: cancel.clone(),
+                    },
+                );
 
-                let attached_node = locked
-                    .nodes
-                    .get(node_id)
-                    .expect("Pageservers may not be deleted while referenced");
+                tokio::task::spawn({
+                    let service = self.clone();
+                    let cancel = cancel.clone();
+                    async move {
+                        let _gate_guard = gate_guard;
 
-                let secondary_node = locked
-                    .nodes
-                    .get(secondary_node_id)
-                    .expect("Pageservers may not be deleted while referenced");
+                        scopeguard::defer! {
+                            let prev = service
+                                .inner
+                                .write()
+                                .unwrap()
+                                .ongoing_operations
+                                .remove(&node_id);
 
-                attached.push((*tenant_shard_id, attached_node.clone()));
-                secondary.push((*tenant_shard_id, secondary_node.clone()));
+                            if let Some(Operation::Drain(removed_drain)) = prev.map(|h| h.operation) {
+                                assert_eq!(removed_drain.node_id, node_id, "We always take the same operation");
+                            } else {
+                                panic!("We always remove the same operation")
+                            }
+                        }
+
+                        tracing::info!(%node_id, "Drain background operation starting");
+                        let res = service.drain_node(node_id, cancel).await;
+                        match res {
+                            Ok(()) => {
+                                tracing::info!(%node_id, "Drain background operation completed successfully");
+                            }
+                            Err(OperationError::Cancelled) => {
+                                tracing::info!(%node_id, "Drain background operation was cancelled");
+                            }
+                            Err(err) => {
+                                tracing::error!(%node_id, "Drain background operation encountered: {err}")
+                            }
+                        }
+                    }
+                });
             }
-            (attached, secondary)
+            NodeSchedulingPolicy::Draining => {
+                return Err(ApiError::Conflict(format!(
+                    "Node {node_id} has drain in progress"
+                )));
+            }
+            policy => {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Node {node_id} cannot be drained due to {policy:?} policy").into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn cancel_node_drain(&self, node_id: NodeId) -> Result<(), ApiError> {
+        let (node_available, node_policy) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
+
+            (node.is_available(), node.get_scheduling())
         };
 
-        if secondary.is_empty() {
-            // No secondary locations; nothing for us to do
-            return;
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
         }
 
-        for result in self
-            .tenant_for_shards_api(
-                attached,
-                |tenant_shard_id, client| async move {
-                    client.tenant_heatmap_upload(tenant_shard_id).await
-                },
-                1,
-                1,
-                SHORT_RECONCILE_TIMEOUT,
-                &self.cancel,
-            )
-            .await
-        {
-            if let Err(e) = result {
-                tracing::warn!("Error calling heatmap upload after shard split: {e}");
-                return;
-            }
+        if !matches!(node_policy, NodeSchedulingPolicy::Draining) {
+            return Err(ApiError::PreconditionFailed(
+                format!("Node {node_id} has no drain in progress").into(),
+            ));
         }
 
-        for result in self
-            .tenant_for_shards_api(
-                secondary,
-                |tenant_shard_id, client| async move {
-                    client
-                        .tenant_secondary_download(tenant_shard_id, Some(Duration::ZERO))
-                        .await
-                },
-                1,
-                1,
-                SHORT_RECONCILE_TIMEOUT,
-                &self.cancel,
-            )
-            .await
-        {
-            if let Err(e) = result {
-                tracing::warn!("Error calling secondary download after shard split: {e}");
-                return;
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operations.get(&node_id) {
+            if let Operation::Drain(drain) = op_handler.operation {
+                if drain.node_id == node_id {
+                    tracing::info!("Cancelling background drain operation for node {node_id}");
+                    op_handler.cancel.cancel();
+                    return Ok(());
+                }
             }
         }
-    }
-
-    pub(crate) async fn tenant_shard_split(
-        &self,
-        tenant_id: TenantId,
-        split_req: TenantShardSplitRequest,
-    ) -> Result<TenantShardSplitResponse, ApiError> {
-        // TODO: return 503 if we get stuck waiting for this lock
-        // (issue https://github.com/neondatabase/neon/issues/7108)
-        let _tenant_lock = trace_exclusive_lock(
-            &self.tenant_op_locks,
-            tenant_id,
-            TenantOperations::ShardSplit,
-        )
-        .await;
-
-        let new_shard_count = ShardCount::new(split_req.new_shard_count);
-        let new_stripe_size = split_req.new_stripe_size;
 
-        // Validate the request and construct parameters.  This phase is fallible, but does not require
-        // rollback on errors, as it does no I/O and mutates no state.
-        let shard_split_params = match self.prepare_tenant_shard_split(tenant_id, split_req)? {
-            ShardSplitAction::NoOp(resp) => return Ok(resp),
-            ShardSplitAction::Split(params) => params,
-        };
+        Err(ApiError::PreconditionFailed(
+            format!("Node {node_id} has no drain in progress").into(),
+        ))
+    }
 
-        // Execute this split: this phase mutates state and does remote I/O on pageservers.  If it fails,
-        // we must roll back.
-        let r = self
-            .do_tenant_shard_split(tenant_id, shard_split_params)
-            .await;
+    pub(crate) async fn start_node_fill(self: &Arc<Self>, node_id: NodeId) -> Result<(), ApiError> {
+        let (ongoing_op, node_available, node_policy, total_nodes_count) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
 
-        let (response, waiters) = match r {
-            Ok(r) => r,
-            Err(e) => {
-                // Split might be part-done, we must do work to abort it.
-                tracing::warn!("Enqueuing background abort of split on {tenant_id}");
-                self.abort_tx
-                    .send(TenantShardSplitAbort {
-                        tenant_id,
-                        new_shard_count,
-                        new_stripe_size,
-                        _tenant_lock,
-                    })
-                    // Ignore error sending: that just means we're shutting down: aborts are ephemeral so it's fine to drop it.
-                    .ok();
-                return Err(e);
-            }
+            (
+                locked
+                    .ongoing_operations
+                    .get(&node_id)
+                    .map(|ongoing| ongoing.operation),
+                node.is_available(),
+                node.get_scheduling(),
+                nodes.len(),
+            )
         };
 
-        // The split is now complete.  As an optimization, we will trigger all the child shards to upload
-        // a heatmap immediately, and all their secondary locations to start downloading: this avoids waiting
-        // for the background heatmap/download interval before secondaries get warm enough to migrate shards
-        // in [`Self::optimize_all`]
-        self.tenant_shard_split_start_secondaries(tenant_id, waiters)
-            .await;
-        Ok(response)
-    }
+        if let Some(ongoing) = ongoing_op {
+            return Err(ApiError::PreconditionFailed(
+                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            ));
+        }
 
-    fn prepare_tenant_shard_split(
-        &self,
-        tenant_id: TenantId,
-        split_req: TenantShardSplitRequest,
-    ) -> Result<ShardSplitAction, ApiError> {
-        fail::fail_point!("shard-split-validation", |_| Err(ApiError::BadRequest(
-            anyhow::anyhow!("failpoint")
-        )));
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
 
-        let mut policy = None;
-        let mut config = None;
-        let mut shard_ident = None;
-        // Validate input, and calculate which shards we will create
-        let (old_shard_count, targets) =
-            {
-                let locked = self.inner.read().unwrap();
+        if total_nodes_count <= 1 {
+            return Err(ApiError::PreconditionFailed(
+                "No other nodes to fill from".into(),
+            ));
+        }
 
-                let pageservers = locked.nodes.clone();
+        match node_policy {
+            NodeSchedulingPolicy::Active => {
+                self.node_configure(
+                    node_id,
+                    None,
+                    Some(NodeSchedulingPolicy::Filling),
+                    NodeTransitionSource::Controller,
+                    None,
+                )
+                .await?;
 
-                let mut targets = Vec::new();
+                let cancel = self.cancel.child_token();
+                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
 
-                // In case this is a retry, count how many already-split shards we found
-                let mut children_found = Vec::new();
-                let mut old_shard_count = None;
+                self.inner.write().unwrap().ongoing_operations.insert(
+                    node_id,
+                    OperationHandler {
+                        operation: Operation::Fill(Fill { node_id }),
+                        cancel: cancel.clone(),
+                    },
+                );
 
-                for (tenant_shard_id, shard) in
-                    locked.tenants.range(TenantShardId::tenant_range(tenant_id))
-                {
-                    match shard.shard.count.count().cmp(&split_req.new_shard_count) {
-                        Ordering::Equal => {
-                            //  Already split this
-                            children_found.push(*tenant_shard_id);
-                            continue;
-                        }
-                        Ordering::Greater => {
-                            return Err(ApiError::BadRequest(anyhow::anyhow!(
-                                "Requested count {} but already have shards at count {}",
-                                split_req.new_shard_count,
-                                shard.shard.count.count()
-                            )));
-                        }
-                        Ordering::Less => {
-                            // Fall through: this shard has lower count than requested,
-                            // is a candidate for splitting.
-                        }
-                    }
+                tokio::task::spawn({
+                    let service = self.clone();
+                    let cancel = cancel.clone();
+                    async move {
+                        let _gate_guard = gate_guard;
 
-                    match old_shard_count {
-                        None => old_shard_count = Some(shard.shard.count),
-                        Some(old_shard_count) => {
-                            if old_shard_count != shard.shard.count {
-                                // We may hit this case if a caller asked for two splits to
-                                // different sizes, before the first one is complete.
-                                // e.g. 1->2, 2->4, where the 4 call comes while we have a mixture
-                                // of shard_count=1 and shard_count=2 shards in the map.
-                                return Err(ApiError::Conflict(
-                                    "Cannot split, currently mid-split".to_string(),
-                                ));
+                        scopeguard::defer! {
+                            let prev = service
+                                .inner
+                                .write()
+                                .unwrap()
+                                .ongoing_operations
+                                .remove(&node_id);
+
+                            if let Some(Operation::Fill(removed_fill)) = prev.map(|h| h.operation) {
+                                assert_eq!(removed_fill.node_id, node_id, "We always take the same operation");
+                            } else {
+                                panic!("We always remove the same operation")
                             }
                         }
-                    }
-                    if policy.is_none() {
-                        policy = Some(shard.policy.clone());
-                    }
-                    if shard_ident.is_none() {
-                        shard_ident = Some(shard.shard);
-                    }
-                    if config.is_none() {
-                        config = Some(shard.config.clone());
-                    }
-
-                    if tenant_shard_id.shard_count.count() == split_req.new_shard_count {
-                        tracing::info!(
-                            "Tenant shard {} already has shard count {}",
-                            tenant_shard_id,
-                            split_req.new_shard_count
-                        );
-                        continue;
-                    }
-
-                    let node_id = shard.intent.get_attached().ok_or(ApiError::BadRequest(
-                        anyhow::anyhow!("Cannot split a tenant that is not attached"),
-                    ))?;
-
-                    let node = pageservers
-                        .get(&node_id)
-                        .expect("Pageservers may not be deleted while referenced");
-
-                    targets.push(ShardSplitTarget {
-                        parent_id: *tenant_shard_id,
-                        node: node.clone(),
-                        child_ids: tenant_shard_id
-                            .split(ShardCount::new(split_req.new_shard_count)),
-                    });
-                }
 
-                if targets.is_empty() {
-                    if children_found.len() == split_req.new_shard_count as usize {
-                        return Ok(ShardSplitAction::NoOp(TenantShardSplitResponse {
-                            new_shards: children_found,
-                        }));
-                    } else {
-                        // No shards found to split, and no existing children found: the
-                        // tenant doesn't exist at all.
-                        return Err(ApiError::NotFound(
-                            anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
-                        ));
+                        tracing::info!(%node_id, "Fill background operation starting");
+                        let res = service.fill_node(node_id, cancel).await;
+                        match res {
+                            Ok(()) => {
+                                tracing::info!(%node_id, "Fill background operation completed successfully");
+                            }
+                            Err(OperationError::Cancelled) => {
+                                tracing::info!(%node_id, "Fill background operation was cancelled");
+                            }
+                            Err(err) => {
+                                tracing::error!(%node_id, "Fill background operation encountered: {err}")
+                            }
+                        }
                     }
-                }
-
-                (old_shard_count, targets)
-            };
-
-        // unwrap safety: we would have returned above if we didn't find at least one shard to split
-        let old_shard_count = old_shard_count.unwrap();
-        let shard_ident = if let Some(new_stripe_size) = split_req.new_stripe_size {
-            // This ShardIdentity will be used as the template for all children, so this implicitly
-            // applies the new stripe size to the children.
-            let mut shard_ident = shard_ident.unwrap();
-            if shard_ident.count.count() > 1 && shard_ident.stripe_size != new_stripe_size {
-                return Err(ApiError::BadRequest(anyhow::anyhow!("Attempted to change stripe size ({:?}->{new_stripe_size:?}) on a tenant with multiple shards", shard_ident.stripe_size)));
+                });
             }
+            NodeSchedulingPolicy::Filling => {
+                return Err(ApiError::Conflict(format!(
+                    "Node {node_id} has fill in progress"
+                )));
+            }
+            policy => {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Node {node_id} cannot be filled due to {policy:?} policy").into(),
+                ));
+            }
+        }
 
-            shard_ident.stripe_size = new_stripe_size;
-            tracing::info!("applied  stripe size {}", shard_ident.stripe_size.0);
-            shard_ident
-        } else {
-            shard_ident.unwrap()
-        };
-        let policy = policy.unwrap();
-        let config = config.unwrap();
-
-        Ok(ShardSplitAction::Split(ShardSplitParams {
-            old_shard_count,
-            new_shard_count: ShardCount::new(split_req.new_shard_count),
-            new_stripe_size: split_req.new_stripe_size,
-            targets,
-            policy,
-            config,
-            shard_ident,
-        }))
+        Ok(())
     }
 
-    async fn do_tenant_shard_split(
-        &self,
-        tenant_id: TenantId,
-        params: ShardSplitParams,
-    ) -> Result<(TenantShardSplitResponse, Vec<ReconcilerWaiter>), ApiError> {
-        // FIXME: we have dropped self.inner lock, and not yet written anything to the database: another
-        // request could occur here, deleting or mutating the tenant.  begin_shard_split checks that the
-        // parent shards exist as expected, but it would be neater to do the above pre-checks within the
-        // same database transaction rather than pre-check in-memory and then maybe-fail the database write.
-        // (https://github.com/neondatabase/neon/issues/6676)
-
-        let ShardSplitParams {
-            old_shard_count,
-            new_shard_count,
-            new_stripe_size,
-            mut targets,
-            policy,
-            config,
-            shard_ident,
-        } = params;
+    pub(crate) async fn cancel_node_fill(&self, node_id: NodeId) -> Result<(), ApiError> {
+        let (node_available, node_policy) = {
+            let locked = self.inner.read().unwrap();
+            let nodes = &locked.nodes;
+            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Node {} not registered", node_id).into(),
+            ))?;
 
-        // Drop any secondary locations: pageservers do not support splitting these, and in any case the
-        // end-state for a split tenant will usually be to have secondary locations on different nodes.
-        // The reconciliation calls in this block also implicitly cancel+barrier wrt any ongoing reconciliation
-        // at the time of split.
-        let waiters = {
-            let mut locked = self.inner.write().unwrap();
-            let mut waiters = Vec::new();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
-            for target in &mut targets {
-                let Some(shard) = tenants.get_mut(&target.parent_id) else {
-                    // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "Shard {} not found",
-                        target.parent_id
-                    )));
-                };
+            (node.is_available(), node.get_scheduling())
+        };
 
-                if shard.intent.get_attached() != &Some(target.node.get_id()) {
-                    // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
-                    return Err(ApiError::Conflict(format!(
-                        "Shard {} unexpectedly rescheduled during split",
-                        target.parent_id
-                    )));
-                }
+        if !node_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Node {node_id} is currently unavailable").into(),
+            ));
+        }
 
-                // Irrespective of PlacementPolicy, clear secondary locations from intent
-                shard.intent.clear_secondary(scheduler);
+        if !matches!(node_policy, NodeSchedulingPolicy::Filling) {
+            return Err(ApiError::PreconditionFailed(
+                format!("Node {node_id} has no fill in progress").into(),
+            ));
+        }
 
-                // Run Reconciler to execute detach fo secondary locations.
-                if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
-                    waiters.push(waiter);
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operations.get(&node_id) {
+            if let Operation::Fill(fill) = op_handler.operation {
+                if fill.node_id == node_id {
+                    tracing::info!("Cancelling background drain operation for node {node_id}");
+                    op_handler.cancel.cancel();
+                    return Ok(());
                 }
             }
-            waiters
-        };
-        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+        }
 
-        // Before creating any new child shards in memory or on the pageservers, persist them: this
-        // enables us to ensure that we will always be able to clean up if something goes wrong.  This also
-        // acts as the protection against two concurrent attempts to split: one of them will get a database
-        // error trying to insert the child shards.
-        let mut child_tsps = Vec::new();
-        for target in &targets {
-            let mut this_child_tsps = Vec::new();
-            for child in &target.child_ids {
-                let mut child_shard = shard_ident;
-                child_shard.number = child.shard_number;
-                child_shard.count = child.shard_count;
+        Err(ApiError::PreconditionFailed(
+            format!("Node {node_id} has no fill in progress").into(),
+        ))
+    }
 
-                tracing::info!(
-                    "Create child shard persistence with stripe size {}",
-                    shard_ident.stripe_size.0
-                );
+    /// Helper for methods that will try and call pageserver APIs for
+    /// a tenant, such as timeline CRUD: they cannot proceed unless the tenant
+    /// is attached somewhere.
+    fn ensure_attached_schedule(
+        &self,
+        mut locked: std::sync::RwLockWriteGuard<'_, ServiceState>,
+        tenant_id: TenantId,
+    ) -> Result<Vec<ReconcilerWaiter>, anyhow::Error> {
+        let mut waiters = Vec::new();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
 
-                this_child_tsps.push(TenantShardPersistence {
-                    tenant_id: child.tenant_id.to_string(),
-                    shard_number: child.shard_number.0 as i32,
-                    shard_count: child.shard_count.literal() as i32,
-                    shard_stripe_size: shard_ident.stripe_size.0 as i32,
-                    // Note: this generation is a placeholder, [`Persistence::begin_shard_split`] will
-                    // populate the correct generation as part of its transaction, to protect us
-                    // against racing with changes in the state of the parent.
-                    generation: None,
-                    generation_pageserver: Some(target.node.get_id().0 as i64),
-                    placement_policy: serde_json::to_string(&policy).unwrap(),
-                    config: serde_json::to_string(&config).unwrap(),
-                    splitting: SplitState::Splitting,
+        let mut schedule_context = ScheduleContext::default();
+        for (tenant_shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
+            shard.schedule(scheduler, &mut schedule_context)?;
 
-                    // Scheduling policies do not carry through to children
-                    scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
-                        .unwrap(),
-                });
-            }
+            // The shard's policies may not result in an attached location being scheduled: this
+            // is an error because our caller needs it attached somewhere.
+            if shard.intent.get_attached().is_none() {
+                return Err(anyhow::anyhow!(
+                    "Tenant {tenant_id} not scheduled to be attached"
+                ));
+            };
 
-            child_tsps.push((target.parent_id, this_child_tsps));
-        }
+            if shard.stably_attached().is_some() {
+                // We do not require the shard to be totally up to date on reconciliation: we just require
+                // that it has been attached on the intended node.   Other dirty state such as unattached secondary
+                // locations, or compute hook notifications can be ignored.
+                continue;
+            }
 
-        if let Err(e) = self
-            .persistence
-            .begin_shard_split(old_shard_count, tenant_id, child_tsps)
-            .await
-        {
-            match e {
-                DatabaseError::Query(diesel::result::Error::DatabaseError(
-                    DatabaseErrorKind::UniqueViolation,
-                    _,
-                )) => {
-                    // Inserting a child shard violated a unique constraint: we raced with another call to
-                    // this function
-                    tracing::warn!("Conflicting attempt to split {tenant_id}: {e}");
-                    return Err(ApiError::Conflict("Tenant is already splitting".into()));
-                }
-                _ => return Err(ApiError::InternalServerError(e.into())),
+            if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
+                tracing::info!("Waiting for shard {tenant_shard_id} to reconcile, in order to ensure it is attached");
+                waiters.push(waiter);
             }
         }
-        fail::fail_point!("shard-split-post-begin", |_| Err(
-            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
-        ));
+        Ok(waiters)
+    }
 
-        // Now that I have persisted the splitting state, apply it in-memory.  This is infallible, so
-        // callers may assume that if splitting is set in memory, then it was persisted, and if splitting
-        // is not set in memory, then it was not persisted.
-        {
-            let mut locked = self.inner.write().unwrap();
-            for target in &targets {
-                if let Some(parent_shard) = locked.tenants.get_mut(&target.parent_id) {
-                    parent_shard.splitting = SplitState::Splitting;
-                    // Put the observed state to None, to reflect that it is indeterminate once we start the
-                    // split operation.
-                    parent_shard
-                        .observed
-                        .locations
-                        .insert(target.node.get_id(), ObservedStateLocation { conf: None });
+    async fn ensure_attached_wait(&self, tenant_id: TenantId) -> Result<(), ApiError> {
+        let ensure_waiters = {
+            let locked = self.inner.write().unwrap();
+
+            // Check if the tenant is splitting: in this case, even if it is attached,
+            // we must act as if it is not: this blocks e.g. timeline creation/deletion
+            // operations during the split.
+            for (_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id)) {
+                if !matches!(shard.splitting, SplitState::Idle) {
+                    return Err(ApiError::ResourceUnavailable(
+                        "Tenant shards are currently splitting".into(),
+                    ));
                 }
             }
+
+            self.ensure_attached_schedule(locked, tenant_id)
+                .map_err(ApiError::InternalServerError)?
+        };
+
+        let deadline = Instant::now().checked_add(Duration::from_secs(5)).unwrap();
+        for waiter in ensure_waiters {
+            let timeout = deadline.duration_since(Instant::now());
+            waiter.wait_timeout(timeout).await?;
         }
 
-        // TODO: issue split calls concurrently (this only matters once we're splitting
-        // N>1 shards into M shards -- initially we're usually splitting 1 shard into N).
+        Ok(())
+    }
 
-        for target in &targets {
-            let ShardSplitTarget {
-                parent_id,
-                node,
-                child_ids,
-            } = target;
-            let client = PageserverClient::new(
-                node.get_id(),
-                node.base_url(),
-                self.config.jwt_token.as_deref(),
-            );
-            let response = client
-                .tenant_shard_split(
-                    *parent_id,
-                    TenantShardSplitRequest {
-                        new_shard_count: new_shard_count.literal(),
-                        new_stripe_size,
-                    },
-                )
-                .await
-                .map_err(|e| ApiError::Conflict(format!("Failed to split {}: {}", parent_id, e)))?;
+    /// Wrap [`TenantShard`] reconciliation methods with acquisition of [`Gate`] and [`ReconcileUnits`],
+    fn maybe_reconcile_shard(
+        &self,
+        shard: &mut TenantShard,
+        nodes: &Arc<HashMap<NodeId, Node>>,
+    ) -> Option<ReconcilerWaiter> {
+        let reconcile_needed = shard.get_reconcile_needed(nodes);
 
-            fail::fail_point!("shard-split-post-remote", |_| Err(ApiError::Conflict(
-                "failpoint".to_string()
-            )));
+        match reconcile_needed {
+            ReconcileNeeded::No => return None,
+            ReconcileNeeded::WaitExisting(waiter) => return Some(waiter),
+            ReconcileNeeded::Yes => {
+                // Fall through to try and acquire units for spawning reconciler
+            }
+        };
 
-            tracing::info!(
-                "Split {} into {}",
-                parent_id,
-                response
-                    .new_shards
-                    .iter()
-                    .map(|s| format!("{:?}", s))
-                    .collect::<Vec<_>>()
-                    .join(",")
-            );
+        let units = match self.reconciler_concurrency.clone().try_acquire_owned() {
+            Ok(u) => ReconcileUnits::new(u),
+            Err(_) => {
+                tracing::info!(tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
+                    "Concurrency limited: enqueued for reconcile later");
+                if !shard.delayed_reconcile {
+                    let delayed_reconcile_tx = if shard.tenant_shard_id.is_shard_zero()
+                        || shard.priority == ReconcilePriority::High
+                    {
+                        &self.delayed_reconcile_priority_tx
+                    } else {
+                        &self.delayed_reconcile_tx
+                    };
+                    match delayed_reconcile_tx.try_send(shard.tenant_shard_id) {
+                        Err(TrySendError::Closed(_)) => {
+                            // Weird mid-shutdown case?
+                        }
+                        Err(TrySendError::Full(_)) => {
+                            // It is safe to skip sending our ID in the channel: we will eventually get retried by the background reconcile task.
+                            tracing::warn!(
+                                "Many shards are waiting to reconcile: delayed_reconcile queue is full"
+                            );
+                            METRICS_REGISTRY
+                                .metrics_group
+                                .storage_controller_reconcile_delayed_queue_full
+                                .inc();
+                        }
+                        Ok(()) => {
+                            shard.delayed_reconcile = true;
+                            shard.delayed_reconcile_since = Some(Instant::now());
+                        }
+                    }
+                }
+                self.publish_reconcile_queue_metrics();
 
-            if &response.new_shards != child_ids {
-                // This should never happen: the pageserver should agree with us on how shard splits work.
-                return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                    "Splitting shard {} resulted in unexpected IDs: {:?} (expected {:?})",
-                    parent_id,
-                    response.new_shards,
-                    child_ids
-                )));
+                // We won't spawn a reconciler, but we will construct a waiter that waits for the shard's sequence
+                // number to advance.  When this function is eventually called again and succeeds in getting units,
+                // it will spawn a reconciler that makes this waiter complete.
+                return Some(shard.future_reconcile_waiter());
             }
-        }
+        };
 
-        // TODO: if the pageserver restarted concurrently with our split API call,
-        // the actual generation of the child shard might differ from the generation
-        // we expect it to have.  In order for our in-database generation to end up
-        // correct, we should carry the child generation back in the response and apply it here
-        // in complete_shard_split (and apply the correct generation in memory)
-        // (or, we can carry generation in the request and reject the request if
-        //  it doesn't match, but that requires more retry logic on this side)
+        let Ok(gate_guard) = self.gate.enter() else {
+            // Gate closed: we're shutting down, drop out.
+            return None;
+        };
 
-        self.persistence
-            .complete_shard_split(tenant_id, old_shard_count)
-            .await?;
+        let waiter = shard.spawn_reconciler(
+            &self.result_tx,
+            nodes,
+            &self.compute_hook,
+            &self.config,
+            &self.persistence,
+            &self.frozen_generations,
+            units,
+            gate_guard,
+            &self.cancel,
+        );
+        self.publish_reconcile_queue_metrics();
+        waiter
+    }
 
-        fail::fail_point!("shard-split-post-complete", |_| Err(
-            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
-        ));
+    /// Update the gauges that show whether reconciles are keeping up: how many reconcilers hold
+    /// concurrency units, and how many shards are queued waiting for one.
+    fn publish_reconcile_queue_metrics(&self) {
+        let limit = self
+            .reconciler_concurrency_limit
+            .load(AtomicOrdering::Relaxed);
+        let in_flight = limit.saturating_sub(self.reconciler_concurrency.available_permits());
+
+        let metrics = &METRICS_REGISTRY.metrics_group;
+        metrics
+            .storage_controller_reconcile_in_flight
+            .set(in_flight as i64);
+        metrics
+            .storage_controller_reconcile_delayed_queue_depth
+            .set(self.delayed_reconcile_queue_depth() as i64);
+    }
 
-        // Replace all the shards we just split with their children: this phase is infallible.
-        let (response, child_locations, waiters) =
-            self.tenant_shard_split_commit_inmem(tenant_id, new_shard_count, new_stripe_size);
+    /// Check all tenants for pending reconciliation work, and reconcile those in need.
+    /// Additionally, reschedule tenants that require it.
+    ///
+    /// Returns how many reconciliation tasks were started, or `1` if no reconciles were
+    /// spawned but some _would_ have been spawned if `reconciler_concurrency` units where
+    /// available.  A return value of 0 indicates that everything is fully reconciled already.
+    fn reconcile_all(&self) -> usize {
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
+        let pageservers = nodes.clone();
 
-        // Send compute notifications for all the new shards
-        let mut failed_notifications = Vec::new();
-        for (child_id, child_ps, stripe_size) in child_locations {
-            if let Err(e) = self
-                .compute_hook
-                .notify(child_id, child_ps, stripe_size, &self.cancel)
-                .await
-            {
-                tracing::warn!("Failed to update compute of {}->{} during split, proceeding anyway to complete split ({e})",
-                        child_id, child_ps);
-                failed_notifications.push(child_id);
-            }
+        let mut reconciles_spawned = 0;
+        for shard in reconcile_order(tenants) {
+            self.reconcile_all_shard(shard, &pageservers, &mut reconciles_spawned);
         }
 
-        // If we failed any compute notifications, make a note to retry later.
-        if !failed_notifications.is_empty() {
-            let mut locked = self.inner.write().unwrap();
-            for failed in failed_notifications {
-                if let Some(shard) = locked.tenants.get_mut(&failed) {
-                    shard.pending_compute_notification = true;
-                }
+        reconciles_spawned
+    }
+
+    /// Like [`Self::reconcile_all`], but only for the shards of `tenant_ids`: useful for converging
+    /// a group of tenants that the caller just changed, without sweeping every shard.
+    pub(crate) fn reconcile_tenants(&self, tenant_ids: &[TenantId]) -> usize {
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
+        let pageservers = nodes.clone();
+
+        let mut reconciles_spawned = 0;
+        for tenant_shard_id in tenant_shards_reconcile_order(tenants, tenant_ids) {
+            if let Some(shard) = tenants.get_mut(&tenant_shard_id) {
+                self.reconcile_all_shard(shard, &pageservers, &mut reconciles_spawned);
             }
         }
 
-        Ok((response, waiters))
+        reconciles_spawned
     }
 
-    pub(crate) async fn tenant_shard_migrate(
+    /// One shard's step of [`Self::reconcile_all`]
+    fn reconcile_all_shard(
         &self,
-        tenant_shard_id: TenantShardId,
-        migrate_req: TenantShardMigrateRequest,
-    ) -> Result<TenantShardMigrateResponse, ApiError> {
-        let waiter = {
-            let mut locked = self.inner.write().unwrap();
-            let (nodes, tenants, scheduler) = locked.parts_mut();
+        shard: &mut TenantShard,
+        pageservers: &Arc<HashMap<NodeId, Node>>,
+        reconciles_spawned: &mut usize,
+    ) {
+        // Skip checking if this shard is already enqueued for reconciliation
+        if shard.delayed_reconcile && self.reconciler_concurrency.available_permits() == 0 {
+            // If there is something delayed, then return a nonzero count so that
+            // callers like reconcile_all_now do not incorrectly get the impression
+            // that the system is in a quiescent state.
+            *reconciles_spawned = std::cmp::max(1, *reconciles_spawned);
+            return;
+        }
 
-            let Some(node) = nodes.get(&migrate_req.node_id) else {
-                return Err(ApiError::BadRequest(anyhow::anyhow!(
-                    "Node {} not found",
-                    migrate_req.node_id
-                )));
-            };
+        // Eventual consistency: if an earlier reconcile job failed, and the shard is still
+        // dirty, spawn another rone
+        if self.maybe_reconcile_shard(shard, pageservers).is_some() {
+            *reconciles_spawned += 1;
+        } else if shard.reconcile_backoff_remaining().is_some()
+            && shard.reconcile_needs_attention().is_none()
+        {
+            // Likewise, a shard backing off after a failure will be retried shortly.  Shards that
+            // need attention are left out, as they are not expected to converge by themselves.
+            *reconciles_spawned = std::cmp::max(1, *reconciles_spawned);
+        }
+    }
 
-            if !node.is_available() {
-                // Warn but proceed: the caller may intend to manually adjust the placement of
-                // a shard even if the node is down, e.g. if intervening during an incident.
-                tracing::warn!("Migrating to unavailable node {node}");
-            }
+    /// Sweep all shards for stale observed state: see [`TenantShard::prune_observed`].  This
+    /// catches entries that were left behind before their node became unavailable.
+    fn prune_observed_all(&self) -> usize {
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, _scheduler) = locked.parts_mut();
 
-            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
-                return Err(ApiError::NotFound(
-                    anyhow::anyhow!("Tenant shard not found").into(),
-                ));
-            };
+        let pruned: usize = tenants
+            .values_mut()
+            .map(|shard| shard.prune_observed(nodes))
+            .sum();
+        if pruned > 0 {
+            tracing::info!("Pruned {pruned} stale observed locations");
+        }
 
-            if shard.intent.get_attached() == &Some(migrate_req.node_id) {
-                // No-op case: we will still proceed to wait for reconciliation in case it is
-                // incomplete from an earlier update to the intent.
-                tracing::info!("Migrating: intent is unchanged {:?}", shard.intent);
-            } else {
-                let old_attached = *shard.intent.get_attached();
+        pruned
+    }
 
-                match shard.policy {
-                    PlacementPolicy::Attached(n) => {
-                        // If our new attached node was a secondary, it no longer should be.
-                        shard.intent.remove_secondary(scheduler, migrate_req.node_id);
+    /// `optimize` in this context means identifying shards which have valid scheduled locations, but
+    /// could be scheduled somewhere better:
+    /// - Cutting over to a secondary if the node with the secondary is more lightly loaded
+    ///    * e.g. after a node fails then recovers, to move some work back to it
+    /// - Cutting over to a secondary if it improves the spread of shard attachments within a tenant
+    ///    * e.g. after a shard split, the initial attached locations will all be on the node where
+    ///      we did the split, but are probably better placed elsewhere.
+    /// - Creating new secondary locations if it improves the spreading of a sharded tenant
+    ///    * e.g. after a shard split, some locations will be on the same node (where the split
+    ///     happened), and will probably be better placed elsewhere.
+    ///
+    /// To put it more briefly: whereas the scheduler respects soft constraints in a ScheduleContext at
+    /// the time of scheduling, this function looks for cases where a better-scoring location is available
+    /// according to those same soft constraints.
+    async fn optimize_all(&self) -> usize {
+        // Synchronous prepare: scan shards for possible scheduling optimizations
+        let candidate_work = self.optimize_all_plan();
+        let candidate_work_len = candidate_work.len();
 
-                        // If we were already attached to something, demote that to a secondary
-                        if let Some(old_attached) = old_attached {
-                            if n > 0 {
-                                // Remove other secondaries to make room for the location we'll demote
-                                while shard.intent.get_secondary().len() >= n {
-                                    shard.intent.pop_secondary(scheduler);
-                                }
+        // Asynchronous validate: I/O to pageservers to make sure shards are in a good state to apply validation
+        let validated_work = self.optimize_all_validate(candidate_work).await;
 
-                                shard.intent.push_secondary(scheduler, old_attached);
-                            }
-                        }
+        let was_work_filtered = validated_work.len() != candidate_work_len;
 
-                        shard.intent.set_attached(scheduler, Some(migrate_req.node_id));
-                    }
-                    PlacementPolicy::Secondary => {
-                        shard.intent.clear(scheduler);
-                        shard.intent.push_secondary(scheduler, migrate_req.node_id);
-                    }
-                    PlacementPolicy::Detached => {
-                        return Err(ApiError::BadRequest(anyhow::anyhow!(
-                            "Cannot migrate a tenant that is PlacementPolicy::Detached: configure it to an attached policy first"
-                        )))
-                    }
+        // Synchronous apply: update the shards' intent states according to validated optimisations
+        let mut reconciles_spawned = 0;
+        let mut locked = self.inner.write().unwrap();
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        let applied = apply_optimizations(
+            tenants,
+            scheduler,
+            validated_work,
+            self.config.max_optimizations_exec_per_pass,
+        );
+        for tenant_shard_id in applied {
+            if let Some(shard) = tenants.get_mut(&tenant_shard_id) {
+                if self.maybe_reconcile_shard(shard, nodes).is_some() {
+                    reconciles_spawned += 1;
                 }
-
-                tracing::info!("Migrating: new intent {:?}", shard.intent);
-                shard.sequence = shard.sequence.next();
             }
+        }
 
-            self.maybe_reconcile_shard(shard, nodes)
-        };
-
-        if let Some(waiter) = waiter {
-            waiter.wait_timeout(RECONCILE_TIMEOUT).await?;
-        } else {
-            tracing::info!("Migration is a no-op");
+        if was_work_filtered {
+            // If we filtered any work out during validation, ensure we return a nonzero value to indicate
+            // to callers that the system is not in a truly quiet state, it's going to do some work as soon
+            // as these validations start passing.
+            reconciles_spawned = std::cmp::max(reconciles_spawned, 1);
         }
 
-        Ok(TenantShardMigrateResponse {})
+        reconciles_spawned
     }
 
-    /// This is for debug/support only: we simply drop all state for a tenant, without
-    /// detaching or deleting it on pageservers.
-    pub(crate) async fn tenant_drop(&self, tenant_id: TenantId) -> Result<(), ApiError> {
-        self.persistence.delete_tenant(tenant_id).await?;
-
+    fn optimize_all_plan(&self) -> Vec<(TenantShardId, ScheduleOptimization)> {
         let mut locked = self.inner.write().unwrap();
-        let (_nodes, tenants, scheduler) = locked.parts_mut();
-        let mut shards = Vec::new();
-        for (tenant_shard_id, _) in tenants.range(TenantShardId::tenant_range(tenant_id)) {
-            shards.push(*tenant_shard_id);
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        plan_optimizations(
+            nodes,
+            tenants,
+            scheduler,
+            self.config.max_optimizations_plan_per_pass,
+        )
+    }
+
+    async fn optimize_all_validate(
+        &self,
+        candidate_work: Vec<(TenantShardId, ScheduleOptimization)>,
+    ) -> Vec<(TenantShardId, ScheduleOptimization)> {
+        // Take a clone of the node map to use outside the lock in async validation phase
+        let validation_nodes = { self.inner.read().unwrap().nodes.clone() };
+
+        let mut want_secondary_status = Vec::new();
+
+        // Validate our plans: this is an async phase where we may do I/O to pageservers to
+        // check that the state of locations is acceptable to run the optimization, such as
+        // checking that a secondary location is sufficiently warmed-up to cleanly cut over
+        // in a live migration.
+        let mut validated_work = Vec::new();
+        for (tenant_shard_id, optimization) in candidate_work {
+            match optimization.action {
+                ScheduleOptimizationAction::MigrateAttachment(MigrateAttachment {
+                    old_attached_node_id: _,
+                    new_attached_node_id,
+                }) => {
+                    match validation_nodes.get(&new_attached_node_id) {
+                        None => {
+                            // Node was dropped between planning and validation
+                        }
+                        Some(node) => {
+                            if !node.is_available() {
+                                tracing::info!("Skipping optimization migration of {tenant_shard_id} to {new_attached_node_id} because node unavailable");
+                            } else {
+                                // Accumulate optimizations that require fetching secondary status, so that we can execute these
+                                // remote API requests concurrently.
+                                want_secondary_status.push((
+                                    tenant_shard_id,
+                                    node.clone(),
+                                    optimization,
+                                ));
+                            }
+                        }
+                    }
+                }
+                ScheduleOptimizationAction::ReplaceSecondary(_) => {
+                    // No extra checks needed to replace a secondary: this does not interrupt client access
+                    validated_work.push((tenant_shard_id, optimization))
+                }
+            };
         }
 
-        for shard_id in shards {
-            if let Some(mut shard) = tenants.remove(&shard_id) {
-                shard.intent.clear(scheduler);
+        // Call into pageserver API to find out if the destination secondary location is warm enough for a reasonably smooth migration: we
+        // do this so that we avoid spawning a Reconciler that would have to wait minutes/hours for a destination to warm up: that reconciler
+        // would hold a precious reconcile semaphore unit the whole time it was waiting for the destination to warm up.
+        let results = self
+            .tenant_for_shards_api(
+                want_secondary_status
+                    .iter()
+                    .map(|i| (i.0, i.1.clone()))
+                    .collect(),
+                |tenant_shard_id, client| async move {
+                    client.tenant_secondary_status(tenant_shard_id).await
+                },
+                1,
+                1,
+                SHORT_RECONCILE_TIMEOUT,
+                &self.cancel,
+            )
+            .await;
+
+        for ((tenant_shard_id, node, optimization), secondary_status) in
+            want_secondary_status.into_iter().zip(results.into_iter())
+        {
+            match secondary_status {
+                Err(e) => {
+                    tracing::info!("Skipping migration of {tenant_shard_id} to {node}, error querying secondary: {e}");
+                }
+                Ok(progress) => {
+                    // We require secondary locations to have less than 10GiB of downloads pending before we will use
+                    // them in an optimization
+                    const DOWNLOAD_FRESHNESS_THRESHOLD: u64 = 10 * 1024 * 1024 * 1024;
+
+                    if progress.heatmap_mtime.is_none()
+                        || progress.bytes_total < DOWNLOAD_FRESHNESS_THRESHOLD
+                            && progress.bytes_downloaded != progress.bytes_total
+                        || progress.bytes_total - progress.bytes_downloaded
+                            > DOWNLOAD_FRESHNESS_THRESHOLD
+                    {
+                        tracing::info!("Skipping migration of {tenant_shard_id} to {node} because secondary isn't ready: {progress:?}");
+                    } else {
+                        // Location looks ready: proceed
+                        tracing::info!(
+                            "{tenant_shard_id} secondary on {node} is warm enough for migration: {progress:?}"
+                        );
+                        validated_work.push((tenant_shard_id, optimization))
+                    }
+                }
             }
         }
 
-        Ok(())
+        validated_work
     }
 
-    /// This is for debug/support only: assuming tenant data is already present in S3, we "create" a
-    /// tenant with a very high generation number so that it will see the existing data.
-    pub(crate) async fn tenant_import(
-        &self,
-        tenant_id: TenantId,
-    ) -> Result<TenantCreateResponse, ApiError> {
-        // Pick an arbitrary available pageserver to use for scanning the tenant in remote storage
-        let maybe_node = {
-            self.inner
-                .read()
-                .unwrap()
-                .nodes
-                .values()
-                .find(|n| n.is_available())
-                .cloned()
-        };
-        let Some(node) = maybe_node else {
-            return Err(ApiError::BadRequest(anyhow::anyhow!("No nodes available")));
+    /// Ask pageservers for their largest shards, and return the tenants that auto-splitting would split,
+    /// biggest first: the first of these is what the next [`Self::autosplit_tenants`] pass will split.
+    /// Sizes are measured by [`Config::split_sorting`].
+    pub(crate) async fn autosplit_candidates(&self) -> Vec<TenantShardCountRecommendation> {
+        let AutosplitConfig {
+            split_threshold,
+            split_to,
+        } = self.get_autosplit_config();
+        let Some(split_threshold) = split_threshold else {
+            // Auto-splitting is disabled
+            return Vec::new();
         };
 
-        let client = PageserverClient::new(
-            node.get_id(),
-            node.base_url(),
-            self.config.jwt_token.as_deref(),
-        );
+        let nodes = self.inner.read().unwrap().nodes.clone();
 
-        let scan_result = client
-            .tenant_scan_remote_storage(tenant_id)
-            .await
-            .map_err(|e| passthrough_api_error(&node, e))?;
+        let mut top_n = Vec::new();
 
-        // A post-split tenant may contain a mixture of shard counts in remote storage: pick the highest count.
-        let Some(shard_count) = scan_result
-            .shards
-            .iter()
-            .map(|s| s.tenant_shard_id.shard_count)
-            .max()
-        else {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("No shards found").into(),
-            ));
+        // Call into each node to look for big tenants
+        let top_n_request = TopTenantShardsRequest {
+            // Logical size is a signal of the user's intent to run a large database, whereas resident
+            // size bounds the disk space impact of one shard but can be a symptom of compaction issues:
+            // which one we use is configurable.
+            order_by: self.config.split_sorting.clone(),
+            limit: 10,
+            where_shards_lt: Some(split_to),
+            where_gt: Some(split_threshold),
         };
-
-        // Ideally we would set each newly imported shard's generation independently, but for correctness it is sufficient
-        // to
-        let generation = scan_result
-            .shards
-            .iter()
-            .map(|s| s.generation)
-            .max()
-            .expect("We already validated >0 shards");
-
-        // FIXME: we have no way to recover the shard stripe size from contents of remote storage: this will
-        // only work if they were using the default stripe size.
-        let stripe_size = ShardParameters::DEFAULT_STRIPE_SIZE;
-
-        let (response, waiters) = self
-            .do_tenant_create(TenantCreateRequest {
-                new_tenant_id: TenantShardId::unsharded(tenant_id),
-                generation,
-
-                shard_parameters: ShardParameters {
-                    count: shard_count,
-                    stripe_size,
-                },
-                placement_policy: Some(PlacementPolicy::Attached(0)), // No secondaries, for convenient debug/hacking
-
-                // There is no way to know what the tenant's config was: revert to defaults
-                //
-                // TODO: remove `switch_aux_file_policy` once we finish auxv2 migration
-                //
-                // we write to both v1+v2 storage, so that the test case can use either storage format for testing
-                config: TenantConfig {
-                    switch_aux_file_policy: Some(models::AuxFilePolicy::CrossValidation),
-                    ..TenantConfig::default()
-                },
-            })
-            .await?;
-
-        if let Err(e) = self.await_waiters(waiters, SHORT_RECONCILE_TIMEOUT).await {
-            // Since this is a debug/support operation, all kinds of weird issues are possible (e.g. this
-            // tenant doesn't exist in the control plane), so don't fail the request if it can't fully
-            // reconcile, as reconciliation includes notifying compute.
-            tracing::warn!(%tenant_id, "Reconcile not done yet while importing tenant ({e})");
+        for node in nodes.values() {
+            let request_ref = &top_n_request;
+            match node
+                .with_client_retries(
+                    |client| async move {
+                        let request = request_ref.clone();
+                        client.top_tenant_shards(request.clone()).await
+                    },
+                    &self.config.jwt_token,
+                    3,
+                    3,
+                    Duration::from_secs(5),
+                    &self.cancel,
+                )
+                .await
+            {
+                Some(Ok(node_top_n)) => {
+                    top_n.extend(node_top_n.shards.into_iter());
+                }
+                Some(Err(mgmt_api::Error::Cancelled)) => {
+                    continue;
+                }
+                Some(Err(e)) => {
+                    tracing::warn!("Failed to fetch top N tenants from {node}: {e}");
+                    continue;
+                }
+                None => {
+                    // Node is shutting down
+                    continue;
+                }
+            };
         }
 
-        Ok(response)
-    }
-
-    /// For debug/support: a full JSON dump of TenantShards.  Returns a response so that
-    /// we don't have to make TenantShard clonable in the return path.
-    pub(crate) fn tenants_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
-        let serialized = {
+        let ranked = {
             let locked = self.inner.read().unwrap();
-            let result = locked.tenants.values().collect::<Vec<_>>();
-            serde_json::to_string(&result).map_err(|e| ApiError::InternalServerError(e.into()))?
+            rank_autosplit_candidates(&top_n, &locked.tenants, &self.config.split_sorting)
         };
 
-        hyper::Response::builder()
-            .status(hyper::StatusCode::OK)
-            .header(hyper::header::CONTENT_TYPE, "application/json")
-            .body(hyper::Body::from(serialized))
-            .map_err(|e| ApiError::InternalServerError(e.into()))
-    }
-
-    /// Check the consistency of in-memory state vs. persistent state, and check that the
-    /// scheduler's statistics are up to date.
-    ///
-    /// These consistency checks expect an **idle** system.  If changes are going on while
-    /// we run, then we can falsely indicate a consistency issue.  This is sufficient for end-of-test
-    /// checks, but not suitable for running continuously in the background in the field.
-    pub(crate) async fn consistency_check(&self) -> Result<(), ApiError> {
-        let (mut expect_nodes, mut expect_shards) = {
-            let locked = self.inner.read().unwrap();
-
-            locked
-                .scheduler
-                .consistency_check(locked.nodes.values(), locked.tenants.values())
-                .context("Scheduler checks")
-                .map_err(ApiError::InternalServerError)?;
-
-            let expect_nodes = locked
-                .nodes
-                .values()
-                .map(|n| n.to_persistent())
-                .collect::<Vec<_>>();
-
-            let expect_shards = locked
-                .tenants
-                .values()
-                .map(|t| t.to_persistent())
-                .collect::<Vec<_>>();
+        // Remember tenant sizes for [`Self::recommend_shard_count`]
+        self.inner
+            .write()
+            .unwrap()
+            .tenant_sizes
+            .extend(ranked.iter().copied());
 
-            // This method can only validate the state of an idle system: if a reconcile is in
-            // progress, fail out early to avoid giving false errors on state that won't match
-            // between database and memory under a ReconcileResult is processed.
-            for t in locked.tenants.values() {
-                if t.reconciler.is_some() {
-                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                        "Shard {} reconciliation in progress",
-                        t.tenant_shard_id
-                    )));
-                }
-            }
+        // Only consider tenants that would benefit from a larger shard count
+        ranked
+            .into_iter()
+            .filter_map(|(tenant_id, _size)| self.recommend_shard_count(tenant_id).ok())
+            .filter(|r| r.recommended_shard_count.count() > r.shard_count.count())
+            .collect()
+    }
 
-            (expect_nodes, expect_shards)
+    /// Look for shards which are oversized and in need of splitting
+    async fn autosplit_tenants(self: &Arc<Self>) {
+        let Some(split_threshold) = self.get_autosplit_config().split_threshold else {
+            // Auto-splitting is disabled
+            return;
         };
 
-        let mut nodes = self.persistence.list_nodes().await?;
-        expect_nodes.sort_by_key(|n| n.node_id);
-        nodes.sort_by_key(|n| n.node_id);
-
-        if nodes != expect_nodes {
-            tracing::error!("Consistency check failed on nodes.");
-            tracing::error!(
-                "Nodes in memory: {}",
-                serde_json::to_string(&expect_nodes)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            tracing::error!(
-                "Nodes in database: {}",
-                serde_json::to_string(&nodes)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                "Node consistency failure"
-            )));
+        if let Some(window) = self.config.autosplit_window {
+            if !time_in_window(window, chrono::Utc::now().time()) {
+                tracing::debug!("Not auto-splitting: outside maintenance window {window:?}");
+                return;
+            }
         }
 
-        let mut shards = self.persistence.list_tenant_shards().await?;
-        shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
-        expect_shards.sort_by_key(|tsp| (tsp.tenant_id.clone(), tsp.shard_number, tsp.shard_count));
+        // Pick the biggest tenant to split first
+        let Some(split_candidate) = self.autosplit_candidates().await.into_iter().next() else {
+            tracing::debug!("No split-elegible shards found");
+            return;
+        };
+        let new_shard_count = split_candidate.recommended_shard_count;
 
-        if shards != expect_shards {
-            tracing::error!("Consistency check failed on shards.");
-            tracing::error!(
-                "Shards in memory: {}",
-                serde_json::to_string(&expect_shards)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
-            );
-            tracing::error!(
-                "Shards in database: {}",
-                serde_json::to_string(&shards)
-                    .map_err(|e| ApiError::InternalServerError(e.into()))?
+        // Don't split if the children couldn't all be placed: they would end up without secondaries,
+        // leaving the tenant worse off than it is now.  Wait for capacity to be added instead.
+        let schedulable = {
+            let locked = self.inner.read().unwrap();
+            split_children_schedulable(
+                &locked.scheduler,
+                locked
+                    .tenants
+                    .range(TenantShardId::tenant_range(split_candidate.tenant_id))
+                    .map(|(_id, shard)| shard),
+            )
+        };
+        if !schedulable {
+            tracing::warn!(
+                tenant_id=%split_candidate.tenant_id,
+                "Deferring auto-split to {new_shard_count:?} shards: not enough schedulable nodes for child shards"
             );
-            return Err(ApiError::InternalServerError(anyhow::anyhow!(
-                "Shard consistency failure"
-            )));
+            METRICS_REGISTRY
+                .metrics_group
+                .storage_controller_autosplit_deferred
+                .inc();
+            return;
         }
 
-        Ok(())
-    }
-
-    /// For debug/support: a JSON dump of the [`Scheduler`].  Returns a response so that
-    /// we don't have to make TenantShard clonable in the return path.
-    pub(crate) fn scheduler_dump(&self) -> Result<hyper::Response<hyper::Body>, ApiError> {
-        let serialized = {
-            let locked = self.inner.read().unwrap();
-            serde_json::to_string(&locked.scheduler)
-                .map_err(|e| ApiError::InternalServerError(e.into()))?
-        };
+        // We spawn a task to run this, so it's exactly like some external API client requesting it.  We don't
+        // want to block the background reconcile loop on this.
+        tracing::info!("Auto-splitting tenant for size threshold {split_threshold}: current size {split_candidate:?}");
 
-        hyper::Response::builder()
-            .status(hyper::StatusCode::OK)
-            .header(hyper::header::CONTENT_TYPE, "application/json")
-            .body(hyper::Body::from(serialized))
-            .map_err(|e| ApiError::InternalServerError(e.into()))
+        let this = self.clone();
+        tokio::spawn(
+            async move {
+                match this
+                    .tenant_shard_split(
+                        split_candidate.tenant_id,
+                        TenantShardSplitRequest {
+                            new_shard_count: new_shard_count.literal(),
+                            new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE),
+                        },
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        tracing::info!("Successful auto-split");
+                    }
+                    Err(e) => {
+                        tracing::error!("Auto-split failed: {e}");
+                    }
+                }
+            }
+            .instrument(tracing::info_span!("auto_split", tenant_id=%split_candidate.tenant_id)),
+        );
     }
 
-    /// This is for debug/support only: we simply drop all state for a tenant, without
-    /// detaching or deleting it on pageservers.  We do not try and re-schedule any
-    /// tenants that were on this node.
+    /// Advise how many shards a tenant should have, based on its last known size and the
+    /// configured split threshold.  This is what [`Self::autosplit_tenants`] splits tenants to.
     ///
-    /// TODO: proper node deletion API that unhooks things more gracefully
-    pub(crate) async fn node_drop(&self, node_id: NodeId) -> Result<(), ApiError> {
-        self.persistence.delete_node(node_id).await?;
-
-        let mut locked = self.inner.write().unwrap();
+    /// If we have not learned the tenant's size yet, the recommendation is its current shard count.
+    pub(crate) fn recommend_shard_count(
+        &self,
+        tenant_id: TenantId,
+    ) -> Result<TenantShardCountRecommendation, ApiError> {
+        let AutosplitConfig {
+            split_threshold,
+            split_to,
+        } = self.get_autosplit_config();
+        let Some(split_threshold) = split_threshold else {
+            return Err(ApiError::PreconditionFailed(
+                "Auto-splitting is disabled: no split threshold is configured".into(),
+            ));
+        };
 
-        for shard in locked.tenants.values_mut() {
-            shard.deref_node(node_id);
-        }
+        let locked = self.inner.read().unwrap();
+        let Some((_, shard)) = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .next()
+        else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {tenant_id} not found").into(),
+            ));
+        };
 
-        let mut nodes = (*locked.nodes).clone();
-        nodes.remove(&node_id);
-        locked.nodes = Arc::new(nodes);
+        let shard_count = shard.shard.count;
+        let size = locked.tenant_sizes.get(&tenant_id).copied();
+        let recommended_shard_count = match size {
+            Some(size) => {
+                let recommended = recommended_shard_count(size, split_threshold, split_to);
+                // Never recommend merging: we only advise on splits
+                if recommended.count() > shard_count.count() {
+                    recommended
+                } else {
+                    shard_count
+                }
+            }
+            None => shard_count,
+        };
 
-        locked.scheduler.node_remove(node_id);
+        Ok(TenantShardCountRecommendation {
+            tenant_id,
+            shard_count,
+            size,
+            recommended_shard_count,
+        })
+    }
 
-        Ok(())
+    pub(crate) fn get_autosplit_config(&self) -> AutosplitConfig {
+        *self.autosplit_config.read().unwrap()
     }
 
-    pub(crate) async fn node_list(&self) -> Result<Vec<Node>, ApiError> {
-        let nodes = {
-            self.inner
-                .read()
-                .unwrap()
-                .nodes
-                .values()
-                .cloned()
-                .collect::<Vec<_>>()
+    /// Change the settings used by [`Self::autosplit_tenants`], without restarting.  These are not
+    /// persisted: on restart, we go back to [`Config::split_threshold`] and the default `split_to`.
+    pub(crate) fn update_autosplit_config(
+        &self,
+        split_threshold: Option<u64>,
+        split_to: ShardCount,
+    ) -> Result<AutosplitConfig, ApiError> {
+        validate_split_to(split_to)?;
+
+        let config = AutosplitConfig {
+            split_threshold,
+            split_to,
         };
+        let mut locked = self.autosplit_config.write().unwrap();
+        tracing::info!(
+            "Updating auto-split config from {:?} to {config:?}",
+            *locked
+        );
+        *locked = config;
 
-        Ok(nodes)
-    }
-
-    pub(crate) async fn get_node(&self, node_id: NodeId) -> Result<Node, ApiError> {
-        self.inner
-            .read()
-            .unwrap()
-            .nodes
-            .get(&node_id)
-            .cloned()
-            .ok_or(ApiError::NotFound(
-                format!("Node {node_id} not registered").into(),
-            ))
+        Ok(config)
     }
 
-    pub(crate) async fn node_register(
-        &self,
-        register_req: NodeRegisterRequest,
-    ) -> Result<(), ApiError> {
-        let _node_lock = trace_exclusive_lock(
-            &self.node_op_locks,
-            register_req.node_id,
-            NodeOperations::Register,
-        )
-        .await;
+    /// Useful for tests: run whatever work a background [`Self::reconcile_all`] would have done, but
+    /// also wait for any generated Reconcilers to complete.  Calling this until it returns zero should
+    /// put the system into a quiescent state where future background reconciliations won't do anything.
+    pub(crate) async fn reconcile_all_now(&self) -> Result<usize, ReconcileWaitError> {
+        let reconciles_spawned = self.reconcile_all();
+        let reconciles_spawned = if reconciles_spawned == 0 {
+            // Only optimize when we are otherwise idle
+            self.optimize_all().await
+        } else {
+            reconciles_spawned
+        };
 
-        {
+        let waiters = {
+            let mut waiters = Vec::new();
             let locked = self.inner.read().unwrap();
-            if let Some(node) = locked.nodes.get(&register_req.node_id) {
-                // Note that we do not do a total equality of the struct, because we don't require
-                // the availability/scheduling states to agree for a POST to be idempotent.
-                if node.registration_match(&register_req) {
-                    tracing::info!(
-                        "Node {} re-registered with matching address",
-                        register_req.node_id
-                    );
-                    return Ok(());
-                } else {
-                    // TODO: decide if we want to allow modifying node addresses without removing and re-adding
-                    // the node.  Safest/simplest thing is to refuse it, and usually we deploy with
-                    // a fixed address through the lifetime of a node.
-                    tracing::warn!(
-                        "Node {} tried to register with different address",
-                        register_req.node_id
-                    );
-                    return Err(ApiError::Conflict(
-                        "Node is already registered with different address".to_string(),
-                    ));
+            for (_tenant_shard_id, shard) in locked.tenants.iter() {
+                if let Some(waiter) = shard.get_waiter() {
+                    waiters.push(waiter);
                 }
             }
-        }
+            waiters
+        };
 
-        // We do not require that a node is actually online when registered (it will start life
-        // with it's  availability set to Offline), but we _do_ require that its DNS record exists. We're
-        // therefore not immune to asymmetric L3 connectivity issues, but we are protected against nodes
-        // that register themselves with a broken DNS config.  We check only the HTTP hostname, because
-        // the postgres hostname might only be resolvable to clients (e.g. if we're on a different VPC than clients).
-        if tokio::net::lookup_host(format!(
-            "{}:{}",
-            register_req.listen_http_addr, register_req.listen_http_port
-        ))
-        .await
-        .is_err()
-        {
-            // If we have a transient DNS issue, it's up to the caller to retry their registration.  Because
-            // we can't robustly distinguish between an intermittent issue and a totally bogus DNS situation,
-            // we return a soft 503 error, to encourage callers to retry past transient issues.
-            return Err(ApiError::ResourceUnavailable(
-                format!(
-                    "Node {} tried to register with unknown DNS name '{}'",
-                    register_req.node_id, register_req.listen_http_addr
-                )
-                .into(),
-            ));
-        }
+        let waiter_count = waiters.len();
+        match self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
+            Ok(()) => {}
+            Err(ReconcileWaitError::Failed(_, reconcile_error))
+                if matches!(*reconcile_error, ReconcileError::Cancel) =>
+            {
+                // Ignore reconciler cancel errors: this reconciler might have shut down
+                // because some other change superceded it.  We will return a nonzero number,
+                // so the caller knows they might have to call again to quiesce the system.
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        };
 
-        // Ordering: we must persist the new node _before_ adding it to in-memory state.
-        // This ensures that before we use it for anything or expose it via any external
-        // API, it is guaranteed to be available after a restart.
-        let new_node = Node::new(
-            register_req.node_id,
-            register_req.listen_http_addr,
-            register_req.listen_http_port,
-            register_req.listen_pg_addr,
-            register_req.listen_pg_port,
+        tracing::info!(
+            "{} reconciles in reconcile_all, {} waiters",
+            reconciles_spawned,
+            waiter_count
         );
 
-        // TODO: idempotency if the node already exists in the database
-        self.persistence.insert_node(&new_node).await?;
-
-        let mut locked = self.inner.write().unwrap();
-        let mut new_nodes = (*locked.nodes).clone();
+        Ok(std::cmp::max(waiter_count, reconciles_spawned))
+    }
 
-        locked.scheduler.node_upsert(&new_node);
-        new_nodes.insert(register_req.node_id, new_node);
+    pub async fn shutdown(&self) {
+        // Note that this already stops processing any results from reconciles: so
+        // we do not expect that our [`TenantShard`] objects will reach a neat
+        // final state.
+        self.cancel.cancel();
 
-        locked.nodes = Arc::new(new_nodes);
+        // The cancellation tokens in [`crate::reconciler::Reconciler`] are children
+        // of our cancellation token, so we do not need to explicitly cancel each of
+        // them.
 
-        tracing::info!(
-            "Registered pageserver {}, now have {} pageservers",
-            register_req.node_id,
-            locked.nodes.len()
-        );
-        Ok(())
+        // Background tasks and reconcilers hold gate guards: this waits for them all
+        // to complete.
+        self.gate.close().await;
     }
 
-    pub(crate) async fn node_configure(
+    /// Drain a node by moving the shards attached to it as primaries.
+    /// This is a long running operation and it should run as a separate Tokio task.
+    pub(crate) async fn drain_node(
         &self,
         node_id: NodeId,
-        availability: Option<NodeAvailability>,
-        scheduling: Option<NodeSchedulingPolicy>,
-    ) -> Result<(), ApiError> {
-        let _node_lock =
-            trace_exclusive_lock(&self.node_op_locks, node_id, NodeOperations::Configure).await;
-
-        if let Some(scheduling) = scheduling {
-            // Scheduling is a persistent part of Node: we must write updates to the database before
-            // applying them in memory
-            self.persistence.update_node(node_id, scheduling).await?;
-        }
-
-        // If we're activating a node, then before setting it active we must reconcile any shard locations
-        // on that node, in case it is out of sync, e.g. due to being unavailable during controller startup,
-        // by calling [`Self::node_activate_reconcile`]
-        //
-        // The transition we calculate here remains valid later in the function because we hold the op lock on the node:
-        // nothing else can mutate its availability while we run.
-        let availability_transition = if let Some(input_availability) = availability {
-            let (activate_node, availability_transition) = {
-                let locked = self.inner.read().unwrap();
-                let Some(node) = locked.nodes.get(&node_id) else {
-                    return Err(ApiError::NotFound(
-                        anyhow::anyhow!("Node {} not registered", node_id).into(),
-                    ));
-                };
-
-                (
-                    node.clone(),
-                    node.get_availability_transition(input_availability),
-                )
-            };
-
-            if matches!(availability_transition, AvailabilityTransition::ToActive) {
-                self.node_activate_reconcile(activate_node, &_node_lock)
-                    .await?;
-            }
-            availability_transition
-        } else {
-            AvailabilityTransition::Unchanged
-        };
-
-        // Apply changes from the request to our in-memory state for the Node
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-
-        let mut new_nodes = (**nodes).clone();
-
-        let Some(node) = new_nodes.get_mut(&node_id) else {
-            return Err(ApiError::NotFound(
-                anyhow::anyhow!("Node not registered").into(),
-            ));
-        };
-
-        if let Some(availability) = &availability {
-            node.set_availability(*availability);
-        }
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        let mut last_inspected_shard: Option<TenantShardId> = None;
+        let mut inspected_all_shards = false;
+        let mut waiters = Vec::new();
 
-        if let Some(scheduling) = scheduling {
-            node.set_scheduling(scheduling);
-        }
+        while !inspected_all_shards {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(
+                        node_id,
+                        None,
+                        Some(NodeSchedulingPolicy::Active),
+                        NodeTransitionSource::Controller,
+                        None,
+                    )
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
 
-        // Update the scheduler, in case the elegibility of the node for new shards has changed
-        scheduler.node_upsert(node);
+            {
+                let mut locked = self.inner.write().unwrap();
+                let budget = operation_reconcile_budget(locked.ongoing_operations.len());
+                let (nodes, tenants, scheduler) = locked.parts_mut();
 
-        let new_nodes = Arc::new(new_nodes);
+                let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
+                    format!("node {node_id} was removed").into(),
+                ))?;
 
-        // Modify scheduling state for any Tenants that are affected by a change in the node's availability state.
-        match availability_transition {
-            AvailabilityTransition::ToOffline => {
-                tracing::info!("Node {} transition to offline", node_id);
-                let mut tenants_affected: usize = 0;
+                let current_policy = node.get_scheduling();
+                if !matches!(current_policy, NodeSchedulingPolicy::Draining) {
+                    // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
+                    // about it
+                    return Err(OperationError::NodeStateChanged(
+                        format!("node {node_id} changed state to {current_policy:?}").into(),
+                    ));
+                }
 
-                for (tenant_shard_id, tenant_shard) in tenants {
-                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
-                        // When a node goes offline, we set its observed configuration to None, indicating unknown: we will
-                        // not assume our knowledge of the node's configuration is accurate until it comes back online
-                        observed_loc.conf = None;
+                let mut cursor = tenants.iter_mut().skip_while({
+                    let skip_past = last_inspected_shard;
+                    move |(tid, _)| match skip_past {
+                        Some(last) => **tid != last,
+                        None => false,
                     }
+                });
 
-                    if new_nodes.len() == 1 {
-                        // Special case for single-node cluster: there is no point trying to reschedule
-                        // any tenant shards: avoid doing so, in order to avoid spewing warnings about
-                        // failures to schedule them.
-                        continue;
-                    }
+                while waiters.len() < budget {
+                    let (tid, tenant_shard) = match cursor.next() {
+                        Some(some) => some,
+                        None => {
+                            inspected_all_shards = true;
+                            break;
+                        }
+                    };
 
-                    if !new_nodes
-                        .values()
-                        .any(|n| matches!(n.may_schedule(), MaySchedule::Yes(_)))
-                    {
-                        // Special case for when all nodes are unavailable and/or unschedulable: there is no point
-                        // trying to reschedule since there's nowhere else to go. Without this
-                        // branch we incorrectly detach tenants in response to node unavailability.
+                    // If the shard is not attached to the node being drained, skip it.
+                    if *tenant_shard.intent.get_attached() != Some(node_id) {
+                        last_inspected_shard = Some(*tid);
                         continue;
                     }
 
-                    if tenant_shard.intent.demote_attached(scheduler, node_id) {
-                        tenant_shard.sequence = tenant_shard.sequence.next();
-
-                        // TODO: populate a ScheduleContext including all shards in the same tenant_id (only matters
-                        // for tenants without secondary locations: if they have a secondary location, then this
-                        // schedule() call is just promoting an existing secondary)
-                        let mut schedule_context = ScheduleContext::default();
+                    match tenant_shard.reschedule_to_secondary(None, scheduler) {
+                        Err(e) => {
+                            tracing::warn!(
+                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                "Scheduling error when draining pageserver {} : {e}", node_id
+                            );
+                        }
+                        Ok(()) => {
+                            let scheduled_to = tenant_shard.intent.get_attached();
+                            tracing::info!(
+                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                "Rescheduled shard while draining node {}: {} -> {:?}",
+                                node_id,
+                                node_id,
+                                scheduled_to
+                            );
 
-                        match tenant_shard.schedule(scheduler, &mut schedule_context) {
-                            Err(e) => {
-                                // It is possible that some tenants will become unschedulable when too many pageservers
-                                // go offline: in this case there isn't much we can do other than make the issue observable.
-                                // TODO: give TenantShard a scheduling error attribute to be queried later.
-                                tracing::warn!(%tenant_shard_id, "Scheduling error when marking pageserver {} offline: {e}", node_id);
-                            }
-                            Ok(()) => {
-                                if self
-                                    .maybe_reconcile_shard(tenant_shard, &new_nodes)
-                                    .is_some()
-                                {
-                                    tenants_affected += 1;
-                                };
+                            let waiter = self.maybe_reconcile_shard(tenant_shard, nodes);
+                            if let Some(some) = waiter {
+                                waiters.push(some);
                             }
                         }
                     }
+
+                    last_inspected_shard = Some(*tid);
                 }
-                tracing::info!(
-                    "Launched {} reconciler tasks for tenants affected by node {} going offline",
-                    tenants_affected,
-                    node_id
-                )
             }
-            AvailabilityTransition::ToActive => {
-                tracing::info!("Node {} transition to active", node_id);
-                // When a node comes back online, we must reconcile any tenant that has a None observed
-                // location on the node.
-                for tenant_shard in locked.tenants.values_mut() {
-                    // If a reconciliation is already in progress, rely on the previous scheduling
-                    // decision and skip triggering a new reconciliation.
-                    if tenant_shard.reconciler.is_some() {
-                        continue;
-                    }
 
-                    if let Some(observed_loc) = tenant_shard.observed.locations.get_mut(&node_id) {
-                        if observed_loc.conf.is_none() {
-                            self.maybe_reconcile_shard(tenant_shard, &new_nodes);
-                        }
-                    }
-                }
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
 
-                // TODO: in the background, we should balance work back onto this pageserver
-            }
-            AvailabilityTransition::Unchanged => {
-                tracing::debug!("Node {} no availability change during config", node_id);
-            }
+            failpoint_support::sleep_millis_async!("sleepy-drain-loop");
         }
 
-        locked.nodes = new_nodes;
+        while !waiters.is_empty() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(
+                        node_id,
+                        None,
+                        Some(NodeSchedulingPolicy::Active),
+                        NodeTransitionSource::Controller,
+                        None,
+                    )
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
 
-        Ok(())
-    }
+            tracing::info!("Awaiting {} pending drain reconciliations", waiters.len());
 
-    pub(crate) async fn start_node_drain(
-        self: &Arc<Self>,
-        node_id: NodeId,
-    ) -> Result<(), ApiError> {
-        let (ongoing_op, node_available, node_policy, schedulable_nodes_count) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-            let schedulable_nodes_count = nodes
-                .iter()
-                .filter(|(_, n)| matches!(n.may_schedule(), MaySchedule::Yes(_)))
-                .count();
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
+        }
 
-            (
-                locked
-                    .ongoing_operation
-                    .as_ref()
-                    .map(|ongoing| ongoing.operation),
-                node.is_available(),
-                node.get_scheduling(),
-                schedulable_nodes_count,
+        // At this point we have done the best we could to drain shards from this node.
+        // Set the node scheduling policy to `[NodeSchedulingPolicy::PauseForRestart]`
+        // to complete the drain.
+        if let Err(err) = self
+            .node_configure(
+                node_id,
+                None,
+                Some(NodeSchedulingPolicy::PauseForRestart),
+                NodeTransitionSource::Controller,
+                None,
             )
-        };
-
-        if let Some(ongoing) = ongoing_op {
-            return Err(ApiError::PreconditionFailed(
-                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            .await
+        {
+            // This is not fatal. Anything that is polling the node scheduling policy to detect
+            // the end of the drain operations will hang, but all such places should enforce an
+            // overall timeout. The scheduling policy will be updated upon node re-attach and/or
+            // by the counterpart fill operation.
+            return Err(OperationError::FinalizeError(
+                format!(
+                    "Failed to finalise drain of {node_id} by setting scheduling policy to PauseForRestart: {err}"
+                )
+                .into(),
             ));
         }
 
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
-        }
+        Ok(())
+    }
 
-        if schedulable_nodes_count == 0 {
-            return Err(ApiError::PreconditionFailed(
-                "No other schedulable nodes to drain to".into(),
-            ));
-        }
+    /// Create a node fill plan (pick secondaries to promote) that meets the following requirements:
+    /// 1. The node should be filled until it reaches the expected cluster average of
+    /// attached shards. If there are not enough secondaries on the node, the plan stops early.
+    /// 2. Select tenant shards to promote such that the number of attached shards is balanced
+    /// throughout the cluster. We achieve this by picking tenant shards from each node,
+    /// starting from the ones with the largest number of attached shards, until the node
+    /// reaches the expected cluster average.
+    /// 3. Avoid promoting more shards of the same tenant than required. The upper bound
+    /// for the number of tenants from the same shard promoted to the node being filled is:
+    /// shard count for the tenant divided by the number of nodes in the cluster.
+    fn fill_node_plan(&self, node_id: NodeId) -> Vec<TenantShardId> {
+        let locked = self.inner.read().unwrap();
+        let fill_requirement = locked.scheduler.compute_fill_requirement(node_id);
 
-        match node_policy {
-            NodeSchedulingPolicy::Active | NodeSchedulingPolicy::Pause => {
-                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Draining))
-                    .await?;
+        let mut tids_by_node = locked
+            .tenants
+            .iter()
+            .filter_map(|(tid, tenant_shard)| {
+                if tenant_shard.intent.get_secondary().contains(&node_id) {
+                    if let Some(primary) = tenant_shard.intent.get_attached() {
+                        return Some((*primary, *tid));
+                    }
+                }
+
+                None
+            })
+            .into_group_map();
+
+        let expected_attached = locked.scheduler.expected_attached_shard_count();
+        let nodes_by_load = locked.scheduler.nodes_by_attached_shard_count();
 
-                let cancel = self.cancel.child_token();
-                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+        let mut promoted_per_tenant: HashMap<TenantId, usize> = HashMap::new();
+        let mut plan = Vec::new();
 
-                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
-                    operation: Operation::Drain(Drain { node_id }),
-                    cancel: cancel.clone(),
-                });
+        for (node_id, attached) in nodes_by_load {
+            let available = locked
+                .nodes
+                .get(&node_id)
+                .map_or(false, |n| n.is_available());
+            if !available {
+                continue;
+            }
 
-                tokio::task::spawn({
-                    let service = self.clone();
-                    let cancel = cancel.clone();
-                    async move {
-                        let _gate_guard = gate_guard;
+            if plan.len() >= fill_requirement
+                || tids_by_node.is_empty()
+                || attached <= expected_attached
+            {
+                break;
+            }
 
-                        scopeguard::defer! {
-                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+            let can_take = attached - expected_attached;
+            let needed = fill_requirement - plan.len();
+            let mut take = std::cmp::min(can_take, needed);
 
-                            if let Some(Operation::Drain(removed_drain)) = prev.map(|h| h.operation) {
-                                assert_eq!(removed_drain.node_id, node_id, "We always take the same operation");
-                            } else {
-                                panic!("We always remove the same operation")
+            let mut remove_node = false;
+            while take > 0 {
+                match tids_by_node.get_mut(&node_id) {
+                    Some(tids) => match tids.pop() {
+                        Some(tid) => {
+                            let max_promote_for_tenant = std::cmp::max(
+                                tid.shard_count.count() as usize / locked.nodes.len(),
+                                1,
+                            );
+                            let promoted = promoted_per_tenant.entry(tid.tenant_id).or_default();
+                            if *promoted < max_promote_for_tenant {
+                                plan.push(tid);
+                                *promoted += 1;
+                                take -= 1;
                             }
                         }
-
-                        tracing::info!(%node_id, "Drain background operation starting");
-                        let res = service.drain_node(node_id, cancel).await;
-                        match res {
-                            Ok(()) => {
-                                tracing::info!(%node_id, "Drain background operation completed successfully");
-                            }
-                            Err(OperationError::Cancelled) => {
-                                tracing::info!(%node_id, "Drain background operation was cancelled");
-                            }
-                            Err(err) => {
-                                tracing::error!(%node_id, "Drain background operation encountered: {err}")
-                            }
+                        None => {
+                            remove_node = true;
+                            break;
                         }
+                    },
+                    None => {
+                        break;
                     }
-                });
-            }
-            NodeSchedulingPolicy::Draining => {
-                return Err(ApiError::Conflict(format!(
-                    "Node {node_id} has drain in progress"
-                )));
+                }
             }
-            policy => {
-                return Err(ApiError::PreconditionFailed(
-                    format!("Node {node_id} cannot be drained due to {policy:?} policy").into(),
-                ));
+
+            if remove_node {
+                tids_by_node.remove(&node_id);
             }
         }
 
-        Ok(())
+        plan
     }
 
-    pub(crate) async fn cancel_node_drain(&self, node_id: NodeId) -> Result<(), ApiError> {
-        let (node_available, node_policy) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
+    /// Compute the plan that [`Self::start_node_fill`] would execute for a node, without executing
+    /// it: operators may use this to check that a fill would not over-concentrate a tenant's shards
+    /// on the node.  The plan may differ from the one eventually executed if the cluster changes.
+    pub(crate) fn fill_node_preview(
+        &self,
+        node_id: NodeId,
+    ) -> Result<NodeFillPreviewResponse, ApiError> {
+        if !self.inner.read().unwrap().nodes.contains_key(&node_id) {
+            return Err(ApiError::NotFound(
                 anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-
-            (node.is_available(), node.get_scheduling())
-        };
-
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
             ));
         }
 
-        if !matches!(node_policy, NodeSchedulingPolicy::Draining) {
-            return Err(ApiError::PreconditionFailed(
-                format!("Node {node_id} has no drain in progress").into(),
-            ));
-        }
+        let plan = self.fill_node_plan(node_id);
 
-        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
-            if let Operation::Drain(drain) = op_handler.operation {
-                if drain.node_id == node_id {
-                    tracing::info!("Cancelling background drain operation for node {node_id}");
-                    op_handler.cancel.cancel();
-                    return Ok(());
-                }
-            }
-        }
+        let locked = self.inner.read().unwrap();
+        let shards = plan
+            .into_iter()
+            .filter_map(|tenant_shard_id| {
+                let shard = locked.tenants.get(&tenant_shard_id)?;
+                Some(NodeFillPreviewShard {
+                    tenant_shard_id,
+                    from_node_id: (*shard.intent.get_attached())?,
+                })
+            })
+            .collect();
 
-        Err(ApiError::PreconditionFailed(
-            format!("Node {node_id} has no drain in progress").into(),
-        ))
+        Ok(NodeFillPreviewResponse { shards })
     }
 
-    pub(crate) async fn start_node_fill(self: &Arc<Self>, node_id: NodeId) -> Result<(), ApiError> {
-        let (ongoing_op, node_available, node_policy, total_nodes_count) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
-
-            (
-                locked
-                    .ongoing_operation
-                    .as_ref()
-                    .map(|ongoing| ongoing.operation),
-                node.is_available(),
-                node.get_scheduling(),
-                nodes.len(),
-            )
-        };
-
-        if let Some(ongoing) = ongoing_op {
-            return Err(ApiError::PreconditionFailed(
-                format!("Background operation already ongoing for node: {}", ongoing).into(),
-            ));
-        }
-
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
-        }
+    /// A fill was interrupted because its node changed state.  If the node is still in the Filling
+    /// policy (i.e. it became unavailable), set it back to Active: otherwise it would stay Filling
+    /// until it restarts, and further fills would be refused.
+    async fn finalize_interrupted_fill(
+        &self,
+        node_id: NodeId,
+        err: OperationError,
+    ) -> OperationError {
+        let still_filling = self
+            .inner
+            .read()
+            .unwrap()
+            .nodes
+            .get(&node_id)
+            .map(|node| matches!(node.get_scheduling(), NodeSchedulingPolicy::Filling))
+            .unwrap_or(false);
 
-        if total_nodes_count <= 1 {
-            return Err(ApiError::PreconditionFailed(
-                "No other nodes to fill from".into(),
-            ));
+        if still_filling {
+            if let Err(finalize_err) = self
+                .node_configure(
+                    node_id,
+                    None,
+                    Some(NodeSchedulingPolicy::Active),
+                    NodeTransitionSource::Controller,
+                    None,
+                )
+                .await
+            {
+                return OperationError::FinalizeError(
+                    format!(
+                        "Failed to finalise interrupted fill of {node_id} ({err}) by setting scheduling policy to Active: {finalize_err}"
+                    )
+                    .into(),
+                );
+            }
         }
 
-        match node_policy {
-            NodeSchedulingPolicy::Active => {
-                self.node_configure(node_id, None, Some(NodeSchedulingPolicy::Filling))
-                    .await?;
+        err
+    }
 
-                let cancel = self.cancel.child_token();
-                let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+    /// Fill a node by promoting its secondaries until the cluster is balanced
+    /// with regards to attached shard counts. Note that this operation only
+    /// makes sense as a counterpart to the drain implemented in [`Service::drain_node`].
+    /// This is a long running operation and it should run as a separate Tokio task.
+    pub(crate) async fn fill_node(
+        &self,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        // TODO(vlad): Currently this operates on the assumption that all
+        // secondaries are warm. This is not always true (e.g. we just migrated the
+        // tenant). Take that into consideration by checking the secondary status.
+        let mut tids_to_promote = self.fill_node_plan(node_id);
+        let mut waiters = Vec::new();
 
-                self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
-                    operation: Operation::Fill(Fill { node_id }),
-                    cancel: cancel.clone(),
-                });
+        // Execute the plan we've composed above. Before aplying each move from the plan,
+        // we validate to ensure that it has not gone stale in the meantime.
+        while !tids_to_promote.is_empty() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(
+                        node_id,
+                        None,
+                        Some(NodeSchedulingPolicy::Active),
+                        NodeTransitionSource::Controller,
+                        None,
+                    )
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
 
-                tokio::task::spawn({
-                    let service = self.clone();
-                    let cancel = cancel.clone();
-                    async move {
-                        let _gate_guard = gate_guard;
+            let check = check_fill_target(&self.inner.read().unwrap().nodes, node_id);
+            if let Err(err) = check {
+                return Err(self.finalize_interrupted_fill(node_id, err).await);
+            }
 
-                        scopeguard::defer! {
-                            let prev = service.inner.write().unwrap().ongoing_operation.take();
+            {
+                let mut locked = self.inner.write().unwrap();
+                let budget = operation_reconcile_budget(locked.ongoing_operations.len());
+                let (nodes, tenants, scheduler) = locked.parts_mut();
 
-                            if let Some(Operation::Fill(removed_fill)) = prev.map(|h| h.operation) {
-                                assert_eq!(removed_fill.node_id, node_id, "We always take the same operation");
-                            } else {
-                                panic!("We always remove the same operation")
+                while waiters.len() < budget {
+                    if let Some(tid) = tids_to_promote.pop() {
+                        if let Some(tenant_shard) = tenants.get_mut(&tid) {
+                            // If the node being filled is not a secondary anymore,
+                            // skip the promotion.
+                            if !tenant_shard.intent.get_secondary().contains(&node_id) {
+                                continue;
                             }
-                        }
 
-                        tracing::info!(%node_id, "Fill background operation starting");
-                        let res = service.fill_node(node_id, cancel).await;
-                        match res {
-                            Ok(()) => {
-                                tracing::info!(%node_id, "Fill background operation completed successfully");
-                            }
-                            Err(OperationError::Cancelled) => {
-                                tracing::info!(%node_id, "Fill background operation was cancelled");
-                            }
-                            Err(err) => {
-                                tracing::error!(%node_id, "Fill background operation encountered: {err}")
+                            let previously_attached_to = *tenant_shard.intent.get_attached();
+                            match tenant_shard.reschedule_to_secondary(Some(node_id), scheduler) {
+                                Err(e) => {
+                                    tracing::warn!(
+                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                        "Scheduling error when filling pageserver {} : {e}", node_id
+                                    );
+                                }
+                                Ok(()) => {
+                                    tracing::info!(
+                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
+                                        "Rescheduled shard while filling node {}: {:?} -> {}",
+                                        node_id,
+                                        previously_attached_to,
+                                        node_id
+                                    );
+
+                                    if let Some(waiter) =
+                                        self.maybe_reconcile_shard(tenant_shard, nodes)
+                                    {
+                                        waiters.push(waiter);
+                                    }
+                                }
                             }
                         }
+                    } else {
+                        break;
                     }
-                });
-            }
-            NodeSchedulingPolicy::Filling => {
-                return Err(ApiError::Conflict(format!(
-                    "Node {node_id} has fill in progress"
-                )));
-            }
-            policy => {
-                return Err(ApiError::PreconditionFailed(
-                    format!("Node {node_id} cannot be filled due to {policy:?} policy").into(),
-                ));
+                }
             }
+
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
         }
 
-        Ok(())
-    }
+        while !waiters.is_empty() {
+            if cancel.is_cancelled() {
+                match self
+                    .node_configure(
+                        node_id,
+                        None,
+                        Some(NodeSchedulingPolicy::Active),
+                        NodeTransitionSource::Controller,
+                        None,
+                    )
+                    .await
+                {
+                    Ok(()) => return Err(OperationError::Cancelled),
+                    Err(err) => {
+                        return Err(OperationError::FinalizeError(
+                            format!(
+                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
+                                node_id, err
+                            )
+                            .into(),
+                        ));
+                    }
+                }
+            }
 
-    pub(crate) async fn cancel_node_fill(&self, node_id: NodeId) -> Result<(), ApiError> {
-        let (node_available, node_policy) = {
-            let locked = self.inner.read().unwrap();
-            let nodes = &locked.nodes;
-            let node = nodes.get(&node_id).ok_or(ApiError::NotFound(
-                anyhow::anyhow!("Node {} not registered", node_id).into(),
-            ))?;
+            let check = check_fill_target(&self.inner.read().unwrap().nodes, node_id);
+            if let Err(err) = check {
+                return Err(self.finalize_interrupted_fill(node_id, err).await);
+            }
 
-            (node.is_available(), node.get_scheduling())
-        };
+            tracing::info!("Awaiting {} pending fill reconciliations", waiters.len());
 
-        if !node_available {
-            return Err(ApiError::ResourceUnavailable(
-                format!("Node {node_id} is currently unavailable").into(),
-            ));
+            waiters = self
+                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
+                .await;
         }
 
-        if !matches!(node_policy, NodeSchedulingPolicy::Filling) {
-            return Err(ApiError::PreconditionFailed(
-                format!("Node {node_id} has no fill in progress").into(),
+        if let Err(err) = self
+            .node_configure(
+                node_id,
+                None,
+                Some(NodeSchedulingPolicy::Active),
+                NodeTransitionSource::Controller,
+                None,
+            )
+            .await
+        {
+            // This isn't a huge issue since the filling process starts upon request. However, it
+            // will prevent the next drain from starting. The only case in which this can fail
+            // is database unavailability. Such a case will require manual intervention.
+            return Err(OperationError::FinalizeError(
+                format!("Failed to finalise fill of {node_id} by setting scheduling policy to Active: {err}")
+                    .into(),
             ));
         }
 
-        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
-            if let Operation::Fill(fill) = op_handler.operation {
-                if fill.node_id == node_id {
-                    tracing::info!("Cancelling background drain operation for node {node_id}");
-                    op_handler.cancel.cancel();
-                    return Ok(());
-                }
-            }
-        }
-
-        Err(ApiError::PreconditionFailed(
-            format!("Node {node_id} has no fill in progress").into(),
-        ))
+        Ok(())
     }
+}
 
-    /// Helper for methods that will try and call pageserver APIs for
-    /// a tenant, such as timeline CRUD: they cannot proceed unless the tenant
-    /// is attached somewhere.
-    fn ensure_attached_schedule(
-        &self,
-        mut locked: std::sync::RwLockWriteGuard<'_, ServiceState>,
-        tenant_id: TenantId,
-    ) -> Result<Vec<ReconcilerWaiter>, anyhow::Error> {
-        let mut waiters = Vec::new();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut schedule_context = ScheduleContext::default();
-        for (tenant_shard_id, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
-            shard.schedule(scheduler, &mut schedule_context)?;
+    use pageserver_api::controller_api::NodeCapabilities;
 
-            // The shard's policies may not result in an attached location being scheduled: this
-            // is an error because our caller needs it attached somewhere.
-            if shard.intent.get_attached().is_none() {
-                return Err(anyhow::anyhow!(
-                    "Tenant {tenant_id} not scheduled to be attached"
-                ));
-            };
+    use crate::scheduler::test_utils::make_test_nodes;
+    use crate::tenant_shard::tests::make_test_tenant;
 
-            if shard.stably_attached().is_some() {
-                // We do not require the shard to be totally up to date on reconciliation: we just require
-                // that it has been attached on the intended node.   Other dirty state such as unattached secondary
-                // locations, or compute hook notifications can be ignored.
-                continue;
-            }
+    #[test]
+    fn ttl_cache() {
+        let refreshes = std::cell::Cell::new(0);
+        let refresh = || {
+            refreshes.set(refreshes.get() + 1);
+            refreshes.get()
+        };
 
-            if let Some(waiter) = self.maybe_reconcile_shard(shard, nodes) {
-                tracing::info!("Waiting for shard {tenant_shard_id} to reconcile, in order to ensure it is attached");
-                waiters.push(waiter);
-            }
-        }
-        Ok(waiters)
+        // Within the TTL, the first value is served
+        let cache = TtlCache::new(Duration::from_secs(3600));
+        assert_eq!(cache.get_or_refresh(refresh), 1);
+        assert_eq!(cache.get_or_refresh(refresh), 1);
+        assert_eq!(refreshes.get(), 1);
+
+        // Once the TTL has passed, the value is refreshed
+        let cache = TtlCache::new(Duration::ZERO);
+        assert_eq!(cache.get_or_refresh(refresh), 2);
+        assert_eq!(cache.get_or_refresh(refresh), 3);
     }
 
-    async fn ensure_attached_wait(&self, tenant_id: TenantId) -> Result<(), ApiError> {
-        let ensure_waiters = {
-            let locked = self.inner.write().unwrap();
+    #[test]
+    fn shard_count_recommendation() {
+        const GIB: u64 = 1024 * 1024 * 1024;
+        let max = ShardCount::new(8);
 
-            // Check if the tenant is splitting: in this case, even if it is attached,
-            // we must act as if it is not: this blocks e.g. timeline creation/deletion
-            // operations during the split.
-            for (_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id)) {
-                if !matches!(shard.splitting, SplitState::Idle) {
-                    return Err(ApiError::ResourceUnavailable(
-                        "Tenant shards are currently splitting".into(),
-                    ));
-                }
-            }
+        assert_eq!(recommended_shard_count(0, GIB, max), ShardCount::new(1));
+        assert_eq!(recommended_shard_count(GIB, GIB, max), ShardCount::new(1));
+        assert_eq!(
+            recommended_shard_count(GIB + 1, GIB, max),
+            ShardCount::new(2)
+        );
+        assert_eq!(
+            recommended_shard_count(3 * GIB, GIB, max),
+            ShardCount::new(4)
+        );
+        assert_eq!(recommended_shard_count(100 * GIB, GIB, max), max);
+        assert_eq!(recommended_shard_count(u64::MAX, 1, max), max);
+    }
 
-            self.ensure_attached_schedule(locked, tenant_id)
-                .map_err(ApiError::InternalServerError)?
+    #[test]
+    fn secondary_progress_aggregation() {
+        let tenant_id = TenantId::generate();
+        let shard = |number| TenantShardId {
+            tenant_id,
+            shard_number: ShardNumber(number),
+            shard_count: ShardCount::new(4),
+        };
+        let mtime = |secs| {
+            Some(utils::serde_system_time::SystemTime(
+                std::time::UNIX_EPOCH + Duration::from_secs(secs),
+            ))
+        };
+        let progress = |heatmap_mtime, bytes_downloaded, bytes_total| SecondaryProgress {
+            heatmap_mtime,
+            layers_downloaded: 1,
+            layers_total: 2,
+            bytes_downloaded,
+            bytes_total,
         };
 
-        let deadline = Instant::now().checked_add(Duration::from_secs(5)).unwrap();
-        for waiter in ensure_waiters {
-            let timeout = deadline.duration_since(Instant::now());
-            waiter.wait_timeout(timeout).await?;
-        }
+        // Totals hide a lagging shard: the least ready one is picked out
+        let response = aggregate_secondary_progress(&[
+            (shard(0), progress(mtime(300), 100, 100)),
+            (shard(1), progress(mtime(100), 10, 100)),
+            (shard(2), progress(mtime(200), 0, 0)),
+        ]);
+        assert_eq!(response.progress.bytes_downloaded, 110);
+        assert_eq!(response.progress.bytes_total, 200);
+        assert_eq!(response.progress.layers_total, 6);
+        assert_eq!(response.progress.heatmap_mtime, mtime(300));
+        assert_eq!(response.min_heatmap_mtime, mtime(100));
+        assert_eq!(response.least_ready_shard, Some(shard(1)));
+        assert_eq!(response.least_ready_bytes_ratio, Some(0.1));
+
+        // A location that hasn't downloaded a heatmap yet is the least ready, whatever its bytes
+        let response = aggregate_secondary_progress(&[
+            (shard(0), progress(mtime(300), 10, 100)),
+            (shard(3), progress(None, 0, 0)),
+        ]);
+        assert_eq!(response.progress.heatmap_mtime, mtime(300));
+        assert_eq!(response.min_heatmap_mtime, None);
+        assert_eq!(response.least_ready_shard, Some(shard(3)));
+        assert_eq!(response.least_ready_bytes_ratio, Some(0.0));
+
+        // Ties go to the lowest shard, regardless of the order responses arrived in
+        let response = aggregate_secondary_progress(&[
+            (shard(2), progress(mtime(100), 50, 100)),
+            (shard(1), progress(mtime(100), 50, 100)),
+        ]);
+        assert_eq!(response.least_ready_shard, Some(shard(1)));
+    }
 
-        Ok(())
+    #[test]
+    fn autosplit_window() {
+        let time = |h, m| chrono::NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let daytime = (time(9, 0), time(17, 0));
+        assert!(time_in_window(daytime, time(9, 0)));
+        assert!(time_in_window(daytime, time(12, 30)));
+        assert!(!time_in_window(daytime, time(17, 0)));
+        assert!(!time_in_window(daytime, time(3, 0)));
+
+        // A window that ends before it starts spans midnight
+        let overnight = (time(22, 0), time(4, 0));
+        assert!(time_in_window(overnight, time(23, 0)));
+        assert!(time_in_window(overnight, time(0, 0)));
+        assert!(time_in_window(overnight, time(3, 59)));
+        assert!(!time_in_window(overnight, time(4, 0)));
+        assert!(!time_in_window(overnight, time(12, 0)));
     }
 
-    /// Wrap [`TenantShard`] reconciliation methods with acquisition of [`Gate`] and [`ReconcileUnits`],
-    fn maybe_reconcile_shard(
-        &self,
-        shard: &mut TenantShard,
-        nodes: &Arc<HashMap<NodeId, Node>>,
-    ) -> Option<ReconcilerWaiter> {
-        let reconcile_needed = shard.get_reconcile_needed(nodes);
+    #[test]
+    fn split_schedulability() {
+        // A single node can hold the attached children, but not their secondaries
+        let nodes = make_test_nodes(1);
+        let mut scheduler = Scheduler::new(nodes.values());
 
-        match reconcile_needed {
-            ReconcileNeeded::No => return None,
-            ReconcileNeeded::WaitExisting(waiter) => return Some(waiter),
-            ReconcileNeeded::Yes => {
-                // Fall through to try and acquire units for spawning reconciler
-            }
-        };
+        let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
+        assert!(!split_children_schedulable(&scheduler, shards.iter()));
+        for shard in &mut shards {
+            shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+        }
+        assert!(!split_children_schedulable(&scheduler, shards.iter()));
 
-        let units = match self.reconciler_concurrency.clone().try_acquire_owned() {
-            Ok(u) => ReconcileUnits::new(u),
-            Err(_) => {
-                tracing::info!(tenant_id=%shard.tenant_shard_id.tenant_id, shard_id=%shard.tenant_shard_id.shard_slug(),
-                    "Concurrency limited: enqueued for reconcile later");
-                if !shard.delayed_reconcile {
-                    match self.delayed_reconcile_tx.try_send(shard.tenant_shard_id) {
-                        Err(TrySendError::Closed(_)) => {
-                            // Weird mid-shutdown case?
-                        }
-                        Err(TrySendError::Full(_)) => {
-                            // It is safe to skip sending our ID in the channel: we will eventually get retried by the background reconcile task.
-                            tracing::warn!(
-                                "Many shards are waiting to reconcile: delayed_reconcile queue is full"
-                            );
-                        }
-                        Ok(()) => {
-                            shard.delayed_reconcile = true;
-                        }
-                    }
-                }
+        // Without secondaries, there is nothing more to place
+        let mut unreplicated = make_test_tenant(PlacementPolicy::Attached(0), ShardCount::new(1));
+        unreplicated[0]
+            .intent
+            .set_attached(&mut scheduler, Some(NodeId(1)));
+        assert!(split_children_schedulable(&scheduler, unreplicated.iter()));
+
+        // Once capacity is added, the secondaries can be placed
+        let nodes = make_test_nodes(2);
+        let mut bigger_scheduler = Scheduler::new(nodes.values());
+        let mut bigger_shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
+        for shard in &mut bigger_shards {
+            shard
+                .intent
+                .set_attached(&mut bigger_scheduler, Some(NodeId(1)));
+        }
+        assert!(split_children_schedulable(
+            &bigger_scheduler,
+            bigger_shards.iter()
+        ));
 
-                // We won't spawn a reconciler, but we will construct a waiter that waits for the shard's sequence
-                // number to advance.  When this function is eventually called again and succeeds in getting units,
-                // it will spawn a reconciler that makes this waiter complete.
-                return Some(shard.future_reconcile_waiter());
+        for shard in shards.iter_mut().chain(unreplicated.iter_mut()) {
+            shard.intent.clear(&mut scheduler);
+        }
+        for shard in &mut bigger_shards {
+            shard.intent.clear(&mut bigger_scheduler);
+        }
+    }
+
+    #[test]
+    fn stripe_sizes() {
+        let mut tenants = BTreeMap::new();
+        let mut add_tenant = |shard_count, stripe_size| {
+            for mut shard in
+                make_test_tenant(PlacementPolicy::Attached(0), ShardCount::new(shard_count))
+            {
+                shard.shard.stripe_size = ShardStripeSize(stripe_size);
+                tenants.insert(shard.tenant_shard_id, shard);
             }
         };
+        add_tenant(1, 32768);
+        add_tenant(4, 32768);
+        add_tenant(2, 2048);
+
+        // Tenants are counted once each, regardless of shard count
+        let sizes = stripe_sizes_in_use(&tenants);
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes.get(&ShardStripeSize(32768)), Some(&2));
+        assert_eq!(sizes.get(&ShardStripeSize(2048)), Some(&1));
+    }
 
-        let Ok(gate_guard) = self.gate.enter() else {
-            // Gate closed: we're shutting down, drop out.
-            return None;
+    #[test]
+    fn autosplit_ranking() {
+        let big_logical = TenantId::generate();
+        let big_resident = TenantId::generate();
+        let item = |tenant_id, shard_number, resident_size, max_logical_size| TopTenantShardItem {
+            id: TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(shard_number),
+                shard_count: ShardCount::new(2),
+            },
+            resident_size,
+            physical_size: resident_size,
+            max_logical_size,
         };
+        let top_n = vec![
+            item(big_logical, 0, 100, 1000),
+            item(big_logical, 1, 200, 3000),
+            item(big_resident, 0, 500, 2000),
+        ];
+
+        // The ranking key matches the configured size, and the largest shard stands for its tenant
+        assert_eq!(
+            rank_autosplit_candidates(&top_n, &BTreeMap::new(), &TenantSorting::MaxLogicalSize),
+            vec![(big_logical, 3000), (big_resident, 2000)]
+        );
+        assert_eq!(
+            rank_autosplit_candidates(&top_n, &BTreeMap::new(), &TenantSorting::ResidentSize),
+            vec![(big_resident, 500), (big_logical, 200)]
+        );
+    }
 
-        shard.spawn_reconciler(
-            &self.result_tx,
-            nodes,
-            &self.compute_hook,
-            &self.config,
-            &self.persistence,
-            units,
-            gate_guard,
-            &self.cancel,
+    #[tokio::test]
+    async fn passthrough_error_codes() {
+        let nodes = make_test_nodes(1);
+        let node = nodes.values().next().unwrap();
+
+        let check = |e, expect_code: PassthroughErrorCode, expect_status: u16| async move {
+            let response = passthrough_api_error(node, e).into_response();
+            assert_eq!(response.status().as_u16(), expect_status);
+
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let body: utils::http::error::HttpErrorBody = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body.code.as_deref(), Some(<&str>::from(expect_code)));
+            assert!(body.msg.contains(&node.to_string()) || body.msg == "Shutting down");
+        };
+
+        // Errors are told apart by their codes, keeping the same statuses as without codes
+        check(
+            mgmt_api::Error::ApiError(StatusCode::SERVICE_UNAVAILABLE, "busy".to_string()),
+            PassthroughErrorCode::NodeUnavailable,
+            503,
+        )
+        .await;
+        check(
+            mgmt_api::Error::ReceiveErrorBody("connection reset".to_string()),
+            PassthroughErrorCode::NodeUnavailable,
+            503,
+        )
+        .await;
+        check(
+            mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, "no tenant".to_string()),
+            PassthroughErrorCode::NotFound,
+            404,
+        )
+        .await;
+        check(
+            mgmt_api::Error::ApiError(StatusCode::FORBIDDEN, "bad token".to_string()),
+            PassthroughErrorCode::NodeUnauthorized,
+            500,
+        )
+        .await;
+        check(
+            mgmt_api::Error::ApiError(StatusCode::BAD_REQUEST, "invalid".to_string()),
+            PassthroughErrorCode::NodeRejected,
+            409,
+        )
+        .await;
+        check(
+            mgmt_api::Error::Cancelled,
+            PassthroughErrorCode::ShuttingDown,
+            503,
         )
+        .await;
     }
 
-    /// Check all tenants for pending reconciliation work, and reconcile those in need.
-    /// Additionally, reschedule tenants that require it.
-    ///
-    /// Returns how many reconciliation tasks were started, or `1` if no reconciles were
-    /// spawned but some _would_ have been spawned if `reconciler_concurrency` units where
-    /// available.  A return value of 0 indicates that everything is fully reconciled already.
-    fn reconcile_all(&self) -> usize {
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, _scheduler) = locked.parts_mut();
-        let pageservers = nodes.clone();
-
-        let mut schedule_context = ScheduleContext::default();
+    #[test]
+    fn node_evacuability() {
+        let mut nodes = make_test_nodes(2);
+        let mut scheduler = Scheduler::new(nodes.values());
 
-        let mut reconciles_spawned = 0;
-        for (tenant_shard_id, shard) in tenants.iter_mut() {
-            if tenant_shard_id.is_shard_zero() {
-                schedule_context = ScheduleContext::default();
-            }
+        let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(1));
+        let shard = &mut shards[0];
+        shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+        shard.intent.push_secondary(&mut scheduler, NodeId(2));
+        let tenant_shard_id = shard.tenant_shard_id;
 
-            // Skip checking if this shard is already enqueued for reconciliation
-            if shard.delayed_reconcile && self.reconciler_concurrency.available_permits() == 0 {
-                // If there is something delayed, then return a nonzero count so that
-                // callers like reconcile_all_now do not incorrectly get the impression
-                // that the system is in a quiescent state.
-                reconciles_spawned = std::cmp::max(1, reconciles_spawned);
-                continue;
-            }
+        // Both nodes already hold a location for the shard
+        let check = |nodes: &HashMap<NodeId, Node>, shard: &TenantShard| {
+            check_node_evacuable(NodeId(1), nodes, std::iter::once((&tenant_shard_id, shard)))
+        };
+        assert!(matches!(
+            check(&nodes, shard),
+            Err(ApiError::PreconditionFailed(_))
+        ));
 
-            // Eventual consistency: if an earlier reconcile job failed, and the shard is still
-            // dirty, spawn another rone
-            if self.maybe_reconcile_shard(shard, &pageservers).is_some() {
-                reconciles_spawned += 1;
-            }
+        // A third node gives it somewhere to go, unless that node may not be scheduled on
+        let mut third = make_test_nodes(3).remove(&NodeId(3)).unwrap();
+        nodes.insert(NodeId(3), third.clone());
+        assert!(check(&nodes, shard).is_ok());
 
-            schedule_context.avoid(&shard.intent.all_pageservers());
-        }
+        third.set_scheduling(NodeSchedulingPolicy::Pause);
+        nodes.insert(NodeId(3), third);
+        assert!(matches!(
+            check(&nodes, shard),
+            Err(ApiError::PreconditionFailed(_))
+        ));
 
-        reconciles_spawned
+        // Shards that are not on the node don't matter
+        assert!(check_node_evacuable(
+            NodeId(3),
+            &nodes,
+            std::iter::once((&tenant_shard_id, &*shard))
+        )
+        .is_ok());
+
+        shard.intent.clear(&mut scheduler);
     }
 
-    /// `optimize` in this context means identifying shards which have valid scheduled locations, but
-    /// could be scheduled somewhere better:
-    /// - Cutting over to a secondary if the node with the secondary is more lightly loaded
-    ///    * e.g. after a node fails then recovers, to move some work back to it
-    /// - Cutting over to a secondary if it improves the spread of shard attachments within a tenant
-    ///    * e.g. after a shard split, the initial attached locations will all be on the node where
-    ///      we did the split, but are probably better placed elsewhere.
-    /// - Creating new secondary locations if it improves the spreading of a sharded tenant
-    ///    * e.g. after a shard split, some locations will be on the same node (where the split
-    ///     happened), and will probably be better placed elsewhere.
-    ///
-    /// To put it more briefly: whereas the scheduler respects soft constraints in a ScheduleContext at
-    /// the time of scheduling, this function looks for cases where a better-scoring location is available
-    /// according to those same soft constraints.
-    async fn optimize_all(&self) -> usize {
-        // Limit on how many shards' optmizations each call to this function will execute.  Combined
-        // with the frequency of background calls, this acts as an implicit rate limit that runs a small
-        // trickle of optimizations in the background, rather than executing a large number in parallel
-        // when a change occurs.
-        const MAX_OPTIMIZATIONS_EXEC_PER_PASS: usize = 2;
+    #[test]
+    fn quiescent_comparison() {
+        let before = BTreeMap::from([
+            (1, Some("a")),
+            (2, None),
+            (3, Some("c")),
+            (4, Some("d")),
+            (5, Some("e")),
+        ]);
+        let after = BTreeMap::from([
+            (1, Some("a")),
+            (2, Some("b")),
+            (3, Some("c2")),
+            (4, Some("d")),
+            (5, Some("e")),
+            (6, Some("f")),
+        ]);
+        let database = BTreeMap::from([(1, "a"), (2, "b"), (3, "c2"), (4, "dx"), (7, "g")]);
 
-        // Synchronous prepare: scan shards for possible scheduling optimizations
-        let candidate_work = self.optimize_all_plan();
-        let candidate_work_len = candidate_work.len();
+        let result = compare_quiescent(before, after, database);
 
-        // Asynchronous validate: I/O to pageservers to make sure shards are in a good state to apply validation
-        let validated_work = self.optimize_all_validate(candidate_work).await;
+        // Busy, changed, new and database-only objects are skipped, even if they would match
+        assert_eq!(result.skipped, vec![2, 3, 5, 6, 7]);
+        assert_eq!(result.checked, 2);
+        assert_eq!(result.mismatched, vec![(4, "d", "dx")]);
+    }
 
-        let was_work_filtered = validated_work.len() != candidate_work_len;
+    #[test]
+    fn background_period_jitter() {
+        let period = Duration::from_secs(20);
+        for _ in 0..100 {
+            let jittered = period_jitter(period, 20);
+            assert!(jittered >= Duration::from_secs(16));
+            assert!(jittered < Duration::from_secs(24));
+        }
 
-        // Synchronous apply: update the shards' intent states according to validated optimisations
-        let mut reconciles_spawned = 0;
-        let mut optimizations_applied = 0;
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-        for (tenant_shard_id, optimization) in validated_work {
-            let Some(shard) = tenants.get_mut(&tenant_shard_id) else {
-                // Shard was dropped between planning and execution;
-                continue;
-            };
-            if shard.apply_optimization(scheduler, optimization) {
-                optimizations_applied += 1;
-                if self.maybe_reconcile_shard(shard, nodes).is_some() {
-                    reconciles_spawned += 1;
-                }
-            }
+        // No jitter, and zero periods, are left alone
+        assert_eq!(period_jitter(period, 0), period);
+        assert_eq!(period_jitter(Duration::ZERO, 20), Duration::ZERO);
+    }
 
-            if optimizations_applied >= MAX_OPTIMIZATIONS_EXEC_PER_PASS {
-                break;
-            }
-        }
+    #[test]
+    fn node_scan_timeout_scaling() {
+        let scan_timeout = STARTUP_SCAN_TIMEOUT_DEFAULT;
 
-        if was_work_filtered {
-            // If we filtered any work out during validation, ensure we return a nonzero value to indicate
-            // to callers that the system is not in a truly quiet state, it's going to do some work as soon
-            // as these validations start passing.
-            reconciles_spawned = std::cmp::max(reconciles_spawned, 1);
+        // Small clusters get the same timeout regardless of size
+        for node_count in [0, 1, 10] {
+            assert_eq!(
+                node_scan_request_timeout(node_count, scan_timeout),
+                Duration::from_secs(1)
+            );
         }
 
-        reconciles_spawned
+        assert_eq!(
+            node_scan_request_timeout(11, scan_timeout),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            node_scan_request_timeout(95, scan_timeout),
+            Duration::from_secs(10)
+        );
+
+        // Never longer than the scan as a whole
+        assert_eq!(node_scan_request_timeout(1000, scan_timeout), scan_timeout);
     }
 
-    fn optimize_all_plan(&self) -> Vec<(TenantShardId, ScheduleOptimization)> {
-        let mut schedule_context = ScheduleContext::default();
+    #[test]
+    fn split_to_validation() {
+        for count in [2, 8, 64, 128] {
+            assert!(validate_split_to(ShardCount::new(count)).is_ok());
+        }
+        for count in [0, 1, 3, 12, 255] {
+            assert!(matches!(
+                validate_split_to(ShardCount::new(count)),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
 
-        let mut tenant_shards: Vec<&TenantShard> = Vec::new();
+    #[test]
+    fn split_stripe_size_validation() {
+        for pages in [1, 2048, 32768, SPLIT_STRIPE_SIZE_LIMIT.0] {
+            assert!(validate_split_stripe_size(ShardStripeSize(pages)).is_ok());
+        }
+        for pages in [0, SPLIT_STRIPE_SIZE_LIMIT.0 + 1, u32::MAX] {
+            assert!(matches!(
+                validate_split_stripe_size(ShardStripeSize(pages)),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
 
-        // How many candidate optimizations we will generate, before evaluating them for readniess: setting
-        // this higher than the execution limit gives us a chance to execute some work even if the first
-        // few optimizations we find are not ready.
-        const MAX_OPTIMIZATIONS_PLAN_PER_PASS: usize = 8;
+    #[test]
+    fn reconcile_throughput() {
+        let start = Instant::now();
+        let minutes = |m: u64| start + Duration::from_secs(m * 60);
+        let mut throughput = ReconcileThroughput::new(start);
 
-        let mut work = Vec::new();
+        throughput.record(start, true);
+        throughput.record(start + Duration::from_secs(30), true);
 
-        let mut locked = self.inner.write().unwrap();
-        let (nodes, tenants, scheduler) = locked.parts_mut();
-        for (tenant_shard_id, shard) in tenants.iter() {
-            if tenant_shard_id.is_shard_zero() {
-                // Reset accumulators on the first shard in a tenant
-                schedule_context = ScheduleContext::default();
-                schedule_context.mode = ScheduleMode::Speculative;
-                tenant_shards.clear();
-            }
+        // Shortly after startup, the rate is not inflated by dividing by a tiny period
+        assert_eq!(
+            throughput.per_minute(start + Duration::from_secs(30)),
+            (2.0, 0.0)
+        );
 
-            if work.len() >= MAX_OPTIMIZATIONS_PLAN_PER_PASS {
-                break;
-            }
+        throughput.record(minutes(1) + Duration::from_secs(30), false);
+        assert_eq!(throughput.per_minute(minutes(2)), (1.0, 0.5));
 
-            match shard.get_scheduling_policy() {
-                ShardSchedulingPolicy::Active => {
-                    // Ok to do optimization
-                }
-                ShardSchedulingPolicy::Essential
-                | ShardSchedulingPolicy::Pause
-                | ShardSchedulingPolicy::Stop => {
-                    // Policy prevents optimizing this shard.
-                    continue;
-                }
-            }
+        // Until a whole window has passed, the rate is over the time since startup
+        assert_eq!(throughput.per_minute(minutes(4)), (0.5, 0.25));
 
-            // Accumulate the schedule context for all the shards in a tenant: we must have
-            // the total view of all shards before we can try to optimize any of them.
-            schedule_context.avoid(&shard.intent.all_pageservers());
-            if let Some(attached) = shard.intent.get_attached() {
-                schedule_context.push_attached(*attached);
-            }
-            tenant_shards.push(shard);
-
-            // Once we have seen the last shard in the tenant, proceed to search across all shards
-            // in the tenant for optimizations
-            if shard.shard.number.0 == shard.shard.count.count() - 1 {
-                if tenant_shards.iter().any(|s| s.reconciler.is_some()) {
-                    // Do not start any optimizations while another change to the tenant is ongoing: this
-                    // is not necessary for correctness, but simplifies operations and implicitly throttles
-                    // optimization changes to happen in a "trickle" over time.
-                    continue;
-                }
+        // Then it is over the window
+        throughput.record(minutes(9) + Duration::from_secs(30), true);
+        assert_eq!(throughput.per_minute(minutes(10)), (0.1, 0.1));
 
-                if tenant_shards.iter().any(|s| {
-                    !matches!(s.splitting, SplitState::Idle)
-                        || matches!(s.policy, PlacementPolicy::Detached)
-                }) {
-                    // Never attempt to optimize a tenant that is currently being split, or
-                    // a tenant that is meant to be detached
-                    continue;
-                }
+        // Reconciles that completed before the window fall out of it
+        assert_eq!(throughput.per_minute(minutes(20)), (0.0, 0.0));
+        throughput.record(minutes(20), false);
+        assert_eq!(throughput.buckets.len(), 1);
+        assert_eq!(throughput.per_minute(minutes(20)), (0.0, 0.1));
+    }
 
-                // TODO: optimization calculations are relatively expensive: create some fast-path for
-                // the common idle case (avoiding the search on tenants that we have recently checked)
+    #[test]
+    fn shard_zero_reconciles_first() {
+        let mut tenants = BTreeMap::new();
+        for _ in 0..3 {
+            for shard in make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(4)) {
+                tenants.insert(shard.tenant_shard_id, shard);
+            }
+        }
 
-                for shard in &tenant_shards {
-                    if let Some(optimization) =
-                        // If idle, maybe ptimize attachments: if a shard has a secondary location that is preferable to
-                        // its primary location based on soft constraints, cut it over.
-                        shard.optimize_attachment(nodes, &schedule_context)
-                    {
-                        work.push((shard.tenant_shard_id, optimization));
-                        break;
-                    } else if let Some(optimization) =
-                        // If idle, maybe optimize secondary locations: if a shard has a secondary location that would be
-                        // better placed on another node, based on ScheduleContext, then adjust it.  This
-                        // covers cases like after a shard split, where we might have too many shards
-                        // in the same tenant with secondary locations on the node where they originally split.
-                        shard.optimize_secondary(scheduler, &schedule_context)
-                    {
-                        work.push((shard.tenant_shard_id, optimization));
-                        break;
-                    }
+        // reconcile_all visits every shard zero before any other shard
+        let order = reconcile_order(&mut tenants)
+            .map(|shard| shard.tenant_shard_id)
+            .collect::<Vec<_>>();
+        assert_eq!(order.len(), 12);
+        assert!(order[..3].iter().all(|id| id.is_shard_zero()));
+        assert!(order[3..].iter().all(|id| !id.is_shard_zero()));
+
+        // Shards delayed for lack of reconciler units: shard zero gets units first, even if it
+        // was delayed after other shards
+        let (tx, mut rx) = tokio::sync::mpsc::channel(MAX_DELAYED_RECONCILES);
+        let (priority_tx, mut priority_rx) = tokio::sync::mpsc::channel(MAX_DELAYED_RECONCILES);
+        tx.try_send(order[3]).unwrap();
+        tx.try_send(order[4]).unwrap();
+        priority_tx.try_send(order[0]).unwrap();
+        let mut next = || next_delayed_reconcile(&mut priority_rx, &mut rx);
+        assert_eq!(next(), Some(order[0]));
+        assert_eq!(next(), Some(order[3]));
+        assert_eq!(next(), Some(order[4]));
+        assert_eq!(next(), None);
+    }
 
-                    // TODO: extend this mechanism to prefer attaching on nodes with fewer attached
-                    // tenants (i.e. extend schedule state to distinguish attached from secondary counts),
-                    // for the total number of attachments on a node (not just within a tenant.)
-                }
+    #[test]
+    fn high_priority_reconciles_first() {
+        let mut tenants = BTreeMap::new();
+        let mut tenant_ids = Vec::new();
+        for priority in [
+            ReconcilePriority::Low,
+            ReconcilePriority::Normal,
+            ReconcilePriority::High,
+        ] {
+            let shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
+            tenant_ids.push(shards[0].tenant_shard_id.tenant_id);
+            for mut shard in shards {
+                shard.priority = priority;
+                tenants.insert(shard.tenant_shard_id, shard);
             }
         }
 
-        work
+        // Each priority in turn, and within a priority shard zero first
+        let rank = |tenants: &BTreeMap<TenantShardId, TenantShard>, id: &TenantShardId| {
+            let shard = &tenants[id];
+            (shard.priority, id.is_shard_zero())
+        };
+        let expect = [
+            (ReconcilePriority::High, true),
+            (ReconcilePriority::High, false),
+            (ReconcilePriority::Normal, true),
+            (ReconcilePriority::Normal, false),
+            (ReconcilePriority::Low, true),
+            (ReconcilePriority::Low, false),
+        ];
+        let order = reconcile_order(&mut tenants)
+            .map(|shard| shard.tenant_shard_id)
+            .collect::<Vec<_>>();
+        let ranks = order
+            .iter()
+            .map(|id| rank(&tenants, id))
+            .collect::<Vec<_>>();
+        assert_eq!(ranks, expect);
+
+        let order = tenant_shards_reconcile_order(&tenants, &tenant_ids);
+        let ranks = order
+            .iter()
+            .map(|id| rank(&tenants, id))
+            .collect::<Vec<_>>();
+        assert_eq!(ranks, expect);
     }
 
-    async fn optimize_all_validate(
-        &self,
-        candidate_work: Vec<(TenantShardId, ScheduleOptimization)>,
-    ) -> Vec<(TenantShardId, ScheduleOptimization)> {
-        // Take a clone of the node map to use outside the lock in async validation phase
-        let validation_nodes = { self.inner.read().unwrap().nodes.clone() };
+    #[test]
+    fn single_node() {
+        // Inferred from the node count, including before any nodes are registered
+        assert!(single_node_deployment(None, &HashMap::new()));
+        assert!(single_node_deployment(None, &make_test_nodes(1)));
+        assert!(!single_node_deployment(None, &make_test_nodes(2)));
 
-        let mut want_secondary_status = Vec::new();
+        // Configuration overrides the node count either way
+        assert!(single_node_deployment(Some(true), &make_test_nodes(3)));
+        assert!(!single_node_deployment(Some(false), &make_test_nodes(1)));
+    }
 
-        // Validate our plans: this is an async phase where we may do I/O to pageservers to
-        // check that the state of locations is acceptable to run the optimization, such as
-        // checking that a secondary location is sufficiently warmed-up to cleanly cut over
-        // in a live migration.
-        let mut validated_work = Vec::new();
-        for (tenant_shard_id, optimization) in candidate_work {
-            match optimization.action {
-                ScheduleOptimizationAction::MigrateAttachment(MigrateAttachment {
-                    old_attached_node_id: _,
-                    new_attached_node_id,
-                }) => {
-                    match validation_nodes.get(&new_attached_node_id) {
-                        None => {
-                            // Node was dropped between planning and validation
-                        }
-                        Some(node) => {
-                            if !node.is_available() {
-                                tracing::info!("Skipping optimization migration of {tenant_shard_id} to {new_attached_node_id} because node unavailable");
-                            } else {
-                                // Accumulate optimizations that require fetching secondary status, so that we can execute these
-                                // remote API requests concurrently.
-                                want_secondary_status.push((
-                                    tenant_shard_id,
-                                    node.clone(),
-                                    optimization,
-                                ));
-                            }
-                        }
-                    }
-                }
-                ScheduleOptimizationAction::ReplaceSecondary(_) => {
-                    // No extra checks needed to replace a secondary: this does not interrupt client access
-                    validated_work.push((tenant_shard_id, optimization))
-                }
-            };
+    #[test]
+    fn reconcile_tenants_subset() {
+        let mut tenants = BTreeMap::new();
+        let mut tenant_ids = Vec::new();
+        for _ in 0..4 {
+            let shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
+            tenant_ids.push(shards[0].tenant_shard_id.tenant_id);
+            for shard in shards {
+                tenants.insert(shard.tenant_shard_id, shard);
+            }
         }
 
-        // Call into pageserver API to find out if the destination secondary location is warm enough for a reasonably smooth migration: we
-        // do this so that we avoid spawning a Reconciler that would have to wait minutes/hours for a destination to warm up: that reconciler
-        // would hold a precious reconcile semaphore unit the whole time it was waiting for the destination to warm up.
-        let results = self
-            .tenant_for_shards_api(
-                want_secondary_status
-                    .iter()
-                    .map(|i| (i.0, i.1.clone()))
-                    .collect(),
-                |tenant_shard_id, client| async move {
-                    client.tenant_secondary_status(tenant_shard_id).await
-                },
-                1,
-                1,
-                SHORT_RECONCILE_TIMEOUT,
-                &self.cancel,
-            )
-            .await;
+        // Only the requested tenants' shards are visited, shard zeros first, and each only once
+        let subset = [tenant_ids[2], tenant_ids[0], tenant_ids[2]];
+        let order = tenant_shards_reconcile_order(&tenants, &subset);
+        assert_eq!(order.len(), 4);
+        assert!(order[..2].iter().all(|id| id.is_shard_zero()));
+        assert!(order[2..].iter().all(|id| !id.is_shard_zero()));
+        assert!(order
+            .iter()
+            .all(|id| id.tenant_id == tenant_ids[0] || id.tenant_id == tenant_ids[2]));
 
-        for ((tenant_shard_id, node, optimization), secondary_status) in
-            want_secondary_status.into_iter().zip(results.into_iter())
-        {
-            match secondary_status {
-                Err(e) => {
-                    tracing::info!("Skipping migration of {tenant_shard_id} to {node}, error querying secondary: {e}");
-                }
-                Ok(progress) => {
-                    // We require secondary locations to have less than 10GiB of downloads pending before we will use
-                    // them in an optimization
-                    const DOWNLOAD_FRESHNESS_THRESHOLD: u64 = 10 * 1024 * 1024 * 1024;
+        // Unknown tenants are ignored
+        assert!(tenant_shards_reconcile_order(&tenants, &[TenantId::generate()]).is_empty());
+    }
 
-                    if progress.heatmap_mtime.is_none()
-                        || progress.bytes_total < DOWNLOAD_FRESHNESS_THRESHOLD
-                            && progress.bytes_downloaded != progress.bytes_total
-                        || progress.bytes_total - progress.bytes_downloaded
-                            > DOWNLOAD_FRESHNESS_THRESHOLD
-                    {
-                        tracing::info!("Skipping migration of {tenant_shard_id} to {node} because secondary isn't ready: {progress:?}");
-                    } else {
-                        // Location looks ready: proceed
-                        tracing::info!(
-                            "{tenant_shard_id} secondary on {node} is warm enough for migration: {progress:?}"
-                        );
-                        validated_work.push((tenant_shard_id, optimization))
-                    }
-                }
+    #[test]
+    fn autosplit_skips_canary() {
+        let mut tenants = BTreeMap::new();
+        let mut add_tenant = |canary| {
+            let mut shard = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(1))
+                .pop()
+                .unwrap();
+            shard.canary = canary;
+            let tenant_shard_id = shard.tenant_shard_id;
+            tenants.insert(tenant_shard_id, shard);
+            TopTenantShardItem {
+                id: tenant_shard_id,
+                resident_size: 1000,
+                physical_size: 1000,
+                max_logical_size: 1000,
             }
-        }
+        };
+        let top_n = vec![add_tenant(true), add_tenant(false)];
 
-        validated_work
+        let ranked = rank_autosplit_candidates(&top_n, &tenants, &TenantSorting::MaxLogicalSize);
+        assert_eq!(ranked, vec![(top_n[1].id.tenant_id, 1000)]);
     }
 
-    /// Look for shards which are oversized and in need of splitting
-    async fn autosplit_tenants(self: &Arc<Self>) {
-        let Some(split_threshold) = self.config.split_threshold else {
-            // Auto-splitting is disabled
-            return;
-        };
+    #[test]
+    fn fill_target_goes_offline() {
+        let mut nodes = make_test_nodes(2);
 
-        let nodes = self.inner.read().unwrap().nodes.clone();
+        // Only a node in the Filling policy may be filled
+        assert!(matches!(
+            check_fill_target(&nodes, NodeId(1)),
+            Err(OperationError::NodeStateChanged(_))
+        ));
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_scheduling(NodeSchedulingPolicy::Filling);
+        assert!(check_fill_target(&nodes, NodeId(1)).is_ok());
 
-        const SPLIT_TO_MAX: ShardCount = ShardCount::new(8);
+        // The fill stops if its target goes offline partway through
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Offline);
+        assert!(matches!(
+            check_fill_target(&nodes, NodeId(1)),
+            Err(OperationError::NodeStateChanged(_))
+        ));
 
-        let mut top_n = Vec::new();
+        // ...or is removed
+        assert!(matches!(
+            check_fill_target(&nodes, NodeId(3)),
+            Err(OperationError::NodeStateChanged(_))
+        ));
+    }
 
-        // Call into each node to look for big tenants
-        let top_n_request = TopTenantShardsRequest {
-            // We currently split based on logical size, for simplicity: logical size is a signal of
-            // the user's intent to run a large database, whereas physical/resident size can be symptoms
-            // of compaction issues.  Eventually we should switch to using resident size to bound the
-            // disk space impact of one shard.
-            order_by: models::TenantSorting::MaxLogicalSize,
-            limit: 10,
-            where_shards_lt: Some(SPLIT_TO_MAX),
-            where_gt: Some(split_threshold),
-        };
-        for node in nodes.values() {
-            let request_ref = &top_n_request;
-            match node
-                .with_client_retries(
-                    |client| async move {
-                        let request = request_ref.clone();
-                        client.top_tenant_shards(request.clone()).await
-                    },
-                    &self.config.jwt_token,
-                    3,
-                    3,
-                    Duration::from_secs(5),
-                    &self.cancel,
-                )
-                .await
-            {
-                Some(Ok(node_top_n)) => {
-                    top_n.extend(node_top_n.shards.into_iter());
-                }
-                Some(Err(mgmt_api::Error::Cancelled)) => {
-                    continue;
-                }
-                Some(Err(e)) => {
-                    tracing::warn!("Failed to fetch top N tenants from {node}: {e}");
-                    continue;
-                }
-                None => {
-                    // Node is shutting down
-                    continue;
-                }
-            };
+    #[test]
+    fn time_travel_timestamps() {
+        assert!(
+            validate_time_travel_timestamps("2024-06-01T00:00:00Z", "2024-06-02T00:00:00Z").is_ok()
+        );
+        assert!(
+            validate_time_travel_timestamps("2024-06-01T00:00:00Z", "2024-06-01T00:00:00Z").is_ok()
+        );
+
+        // Malformed timestamps
+        for (timestamp, done_if_after) in [
+            ("yesterday", "2024-06-02T00:00:00Z"),
+            ("2024-06-01", "2024-06-02T00:00:00Z"),
+            ("2024-06-01T00:00:00Z", "2024-13-02T00:00:00Z"),
+            ("2024-06-01T00:00:00Z", ""),
+        ] {
+            assert!(matches!(
+                validate_time_travel_timestamps(timestamp, done_if_after),
+                Err(ApiError::BadRequest(_))
+            ));
         }
 
-        // Pick the biggest tenant to split first
-        top_n.sort_by_key(|i| i.resident_size);
-        let Some(split_candidate) = top_n.into_iter().next() else {
-            tracing::debug!("No split-elegible shards found");
-            return;
+        // Out of order
+        let err = validate_time_travel_timestamps("2024-06-02T00:00:00Z", "2024-06-01T00:00:00Z")
+            .unwrap_err();
+        assert!(matches!(err, ApiError::BadRequest(_)));
+        assert!(err.to_string().contains("done_if_after"));
+    }
+
+    #[test]
+    fn tenant_limit() {
+        let mut tenants = BTreeMap::new();
+        let mut add_tenant = |shard_count| {
+            let shards =
+                make_test_tenant(PlacementPolicy::Attached(0), ShardCount::new(shard_count));
+            let tenant_id = shards[0].tenant_shard_id.tenant_id;
+            for shard in shards {
+                tenants.insert(shard.tenant_shard_id, shard);
+            }
+            tenant_id
         };
+        let existing = add_tenant(4);
+        add_tenant(1);
 
-        // We spawn a task to run this, so it's exactly like some external API client requesting it.  We don't
-        // want to block the background reconcile loop on this.
-        tracing::info!("Auto-splitting tenant for size threshold {split_threshold}: current size {split_candidate:?}");
+        // No limit
+        assert!(check_tenant_limit(&tenants, TenantId::generate(), None).is_ok());
 
-        let this = self.clone();
-        tokio::spawn(
-            async move {
-                match this
-                    .tenant_shard_split(
-                        split_candidate.id.tenant_id,
-                        TenantShardSplitRequest {
-                            // Always split to the max number of shards: this avoids stepping through
-                            // intervening shard counts and encountering the overrhead of a split+cleanup
-                            // each time as a tenant grows, and is not too expensive because our max shard
-                            // count is relatively low anyway.
-                            // This policy will be adjusted in future once we support higher shard count.
-                            new_shard_count: SPLIT_TO_MAX.literal(),
-                            new_stripe_size: Some(ShardParameters::DEFAULT_STRIPE_SIZE),
-                        },
-                    )
-                    .await
-                {
-                    Ok(_) => {
-                        tracing::info!("Successful auto-split");
-                    }
-                    Err(e) => {
-                        tracing::error!("Auto-split failed: {e}");
-                    }
-                }
-            }
-            .instrument(tracing::info_span!("auto_split", tenant_id=%split_candidate.id.tenant_id)),
-        );
+        // Below the limit: shards are not counted separately
+        assert!(check_tenant_limit(&tenants, TenantId::generate(), Some(3)).is_ok());
+
+        // At the limit, new tenants are refused but existing ones may be re-created
+        assert!(matches!(
+            check_tenant_limit(&tenants, TenantId::generate(), Some(2)),
+            Err(ApiError::ResourceUnavailable(_))
+        ));
+        assert!(check_tenant_limit(&tenants, existing, Some(2)).is_ok());
     }
 
-    /// Useful for tests: run whatever work a background [`Self::reconcile_all`] would have done, but
-    /// also wait for any generated Reconcilers to complete.  Calling this until it returns zero should
-    /// put the system into a quiescent state where future background reconciliations won't do anything.
-    pub(crate) async fn reconcile_all_now(&self) -> Result<usize, ReconcileWaitError> {
-        let reconciles_spawned = self.reconcile_all();
-        let reconciles_spawned = if reconciles_spawned == 0 {
-            // Only optimize when we are otherwise idle
-            self.optimize_all().await
-        } else {
-            reconciles_spawned
-        };
+    #[test]
+    fn tenant_shape_limits() {
+        // No limits
+        assert!(check_secondaries_limit(&PlacementPolicy::Attached(100), None).is_ok());
+        assert!(check_shards_limit(ShardCount::new(128), None).is_ok());
+
+        // Up to and including the limit
+        for policy in [
+            PlacementPolicy::Attached(0),
+            PlacementPolicy::Attached(1),
+            PlacementPolicy::Secondary,
+            PlacementPolicy::Detached,
+        ] {
+            assert!(check_secondaries_limit(&policy, Some(1)).is_ok());
+        }
+        assert!(check_shards_limit(ShardCount::new(8), Some(8)).is_ok());
+        // An unsharded tenant still has one shard
+        assert!(check_shards_limit(ShardCount::new(0), Some(1)).is_ok());
+
+        // Beyond the limit
+        assert!(matches!(
+            check_secondaries_limit(&PlacementPolicy::Attached(2), Some(1)),
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            check_secondaries_limit(&PlacementPolicy::Secondary, Some(0)),
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            check_shards_limit(ShardCount::new(16), Some(8)),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
 
-        let waiters = {
-            let mut waiters = Vec::new();
-            let locked = self.inner.read().unwrap();
-            for (_tenant_shard_id, shard) in locked.tenants.iter() {
-                if let Some(waiter) = shard.get_waiter() {
-                    waiters.push(waiter);
-                }
-            }
-            waiters
-        };
+    #[test]
+    fn reattach_preserves_multi() {
+        let nodes = make_test_nodes(2);
+        let mut scheduler = Scheduler::new(nodes.values());
 
-        let waiter_count = waiters.len();
-        match self.await_waiters(waiters, RECONCILE_TIMEOUT).await {
-            Ok(()) => {}
-            Err(ReconcileWaitError::Failed(_, reconcile_error))
-                if matches!(*reconcile_error, ReconcileError::Cancel) =>
-            {
-                // Ignore reconciler cancel errors: this reconciler might have shut down
-                // because some other change superceded it.  We will return a nonzero number,
-                // so the caller knows they might have to call again to quiesce the system.
-            }
-            Err(e) => {
-                return Err(e);
-            }
+        let mut shard = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(1))
+            .pop()
+            .unwrap();
+        shard.intent.set_attached(&mut scheduler, Some(NodeId(2)));
+        shard.intent.push_secondary(&mut scheduler, NodeId(1));
+
+        let conf = |mode| {
+            let mut conf = crate::reconciler::attached_location_conf(
+                Generation::new(1),
+                &shard.shard,
+                &shard.config,
+                &shard.policy,
+            );
+            conf.mode = mode;
+            ObservedStateLocation { conf: Some(conf) }
         };
+        let origin = conf(LocationConfigMode::AttachedStale);
+        let destination = conf(LocationConfigMode::AttachedMulti);
+
+        // Mid-migration: origin is stale, destination is multi
+        shard.observed.locations.insert(NodeId(1), origin);
+        shard.observed.locations.insert(NodeId(2), destination);
+        assert_eq!(
+            reattach_mode(&shard, NodeId(2), false),
+            LocationConfigMode::AttachedSingle
+        );
+        assert_eq!(
+            reattach_mode(&shard, NodeId(2), true),
+            LocationConfigMode::AttachedMulti
+        );
 
-        tracing::info!(
-            "{} reconciles in reconcile_all, {} waiters",
-            reconciles_spawned,
-            waiter_count
+        // The origin is never left stale
+        assert_eq!(
+            reattach_mode(&shard, NodeId(1), true),
+            LocationConfigMode::AttachedSingle
         );
 
-        Ok(std::cmp::max(waiter_count, reconciles_spawned))
-    }
+        // Once the origin is no longer attached, there is nothing to preserve
+        shard.observed.locations.remove(&NodeId(1));
+        assert_eq!(
+            reattach_mode(&shard, NodeId(2), true),
+            LocationConfigMode::AttachedSingle
+        );
 
-    pub async fn shutdown(&self) {
-        // Note that this already stops processing any results from reconciles: so
-        // we do not expect that our [`TenantShard`] objects will reach a neat
-        // final state.
-        self.cancel.cancel();
+        shard.intent.clear(&mut scheduler);
+    }
 
-        // The cancellation tokens in [`crate::reconciler::Reconciler`] are children
-        // of our cancellation token, so we do not need to explicitly cancel each of
-        // them.
+    #[test]
+    fn set_generation_checks() {
+        let mut shard = make_test_tenant(PlacementPolicy::Attached(0), ShardCount::new(1))
+            .pop()
+            .unwrap();
+        shard.override_generation(Generation::new(5));
+
+        // Moving the generation backwards requires force
+        assert!(matches!(
+            check_set_generation(&shard, Generation::new(4), false),
+            Err(ApiError::PreconditionFailed(_))
+        ));
+        assert!(check_set_generation(&shard, Generation::new(4), true).is_ok());
 
-        // Background tasks and reconcilers hold gate guards: this waits for them all
-        // to complete.
-        self.gate.close().await;
+        // Moving it forwards, or setting it again, does not
+        assert!(check_set_generation(&shard, Generation::new(5), false).is_ok());
+        assert!(check_set_generation(&shard, Generation::new(6), false).is_ok());
     }
 
-    /// Drain a node by moving the shards attached to it as primaries.
-    /// This is a long running operation and it should run as a separate Tokio task.
-    pub(crate) async fn drain_node(
-        &self,
-        node_id: NodeId,
-        cancel: CancellationToken,
-    ) -> Result<(), OperationError> {
-        let mut last_inspected_shard: Option<TenantShardId> = None;
-        let mut inspected_all_shards = false;
-        let mut waiters = Vec::new();
-
-        while !inspected_all_shards {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
-            }
+    #[test]
+    fn frozen_generations() {
+        let frozen = FrozenGenerations::default();
+        let tenant_a = TenantId::generate();
+        let tenant_b = TenantId::generate();
 
-            {
-                let mut locked = self.inner.write().unwrap();
-                let (nodes, tenants, scheduler) = locked.parts_mut();
+        assert!(frozen.check(tenant_a).is_ok());
 
-                let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
-                    format!("node {node_id} was removed").into(),
-                ))?;
+        frozen.set(tenant_a, true);
+        assert!(frozen.check(tenant_a).is_err());
+        assert!(frozen.check(tenant_b).is_ok());
+        assert_eq!(frozen.list(), vec![tenant_a]);
 
-                let current_policy = node.get_scheduling();
-                if !matches!(current_policy, NodeSchedulingPolicy::Draining) {
-                    // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
-                    // about it
-                    return Err(OperationError::NodeStateChanged(
-                        format!("node {node_id} changed state to {current_policy:?}").into(),
-                    ));
-                }
+        frozen.set(tenant_a, false);
+        assert!(frozen.check(tenant_a).is_ok());
+        assert!(frozen.list().is_empty());
+    }
 
-                let mut cursor = tenants.iter_mut().skip_while({
-                    let skip_past = last_inspected_shard;
-                    move |(tid, _)| match skip_past {
-                        Some(last) => **tid != last,
-                        None => false,
-                    }
-                });
+    #[test]
+    fn cancel_shard_split() {
+        let tracker = ShardSplitTracker::default();
 
-                while waiters.len() < MAX_RECONCILES_PER_OPERATION {
-                    let (tid, tenant_shard) = match cursor.next() {
-                        Some(some) => some,
-                        None => {
-                            inspected_all_shards = true;
-                            break;
-                        }
-                    };
+        // Nothing to cancel
+        let tenant_id = TenantId::generate();
+        assert!(matches!(
+            tracker.cancel(tenant_id),
+            Err(ApiError::NotFound(_))
+        ));
 
-                    // If the shard is not attached to the node being drained, skip it.
-                    if *tenant_shard.intent.get_attached() != Some(node_id) {
-                        last_inspected_shard = Some(*tid);
-                        continue;
-                    }
+        // Cancelling before the remote calls makes the split bail out at its next phase boundary
+        let guard = tracker.begin(tenant_id);
+        assert!(tracker.cancel(tenant_id).is_ok());
+        assert!(tracker.advance(tenant_id, ShardSplitPhase::Remote).is_err());
+        drop(guard);
+
+        // Once the remote calls have started, the split can't be cancelled
+        let guard = tracker.begin(tenant_id);
+        assert!(tracker.advance(tenant_id, ShardSplitPhase::Prepare).is_ok());
+        assert!(tracker.advance(tenant_id, ShardSplitPhase::Remote).is_ok());
+        assert!(matches!(
+            tracker.cancel(tenant_id),
+            Err(ApiError::PreconditionFailed(_))
+        ));
 
-                    match tenant_shard.reschedule_to_secondary(None, scheduler) {
-                        Err(e) => {
-                            tracing::warn!(
-                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                "Scheduling error when draining pageserver {} : {e}", node_id
-                            );
-                        }
-                        Ok(()) => {
-                            let scheduled_to = tenant_shard.intent.get_attached();
-                            tracing::info!(
-                                tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                "Rescheduled shard while draining node {}: {} -> {:?}",
-                                node_id,
-                                node_id,
-                                scheduled_to
-                            );
+        // Dropping the guard forgets the split, even if it never finished (e.g. its request was
+        // dropped)
+        drop(guard);
+        assert!(matches!(
+            tracker.cancel(tenant_id),
+            Err(ApiError::NotFound(_))
+        ));
+    }
 
-                            let waiter = self.maybe_reconcile_shard(tenant_shard, nodes);
-                            if let Some(some) = waiter {
-                                waiters.push(some);
-                            }
-                        }
-                    }
+    #[test]
+    fn available_nodes_in_preference_order() {
+        let mut nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
 
-                    last_inspected_shard = Some(*tid);
-                }
-            }
+        // Node 1 is busier than the others, so it is the last resort
+        let mut intent = IntentState::new();
+        intent.set_attached(&mut scheduler, Some(NodeId(1)));
+        let order = |scheduler: &Scheduler, nodes: &HashMap<NodeId, Node>| {
+            available_nodes_by_preference(scheduler, nodes)
+                .unwrap()
+                .iter()
+                .map(|n| n.get_id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order(&scheduler, &nodes),
+            vec![NodeId(2), NodeId(3), NodeId(1)]
+        );
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+        // Unavailable nodes are not listed at all
+        let offline = nodes.get_mut(&NodeId(2)).unwrap();
+        offline.set_availability(NodeAvailability::Offline);
+        scheduler.node_upsert(offline);
+        assert_eq!(order(&scheduler, &nodes), vec![NodeId(3), NodeId(1)]);
 
-            failpoint_support::sleep_millis_async!("sleepy-drain-loop");
+        // With every node unavailable, tenant deletion fails with a clear, retryable error
+        for node in nodes.values_mut() {
+            node.set_availability(NodeAvailability::Offline);
+            scheduler.node_upsert(node);
         }
+        assert!(matches!(
+            available_nodes_by_preference(&scheduler, &nodes),
+            Err(ApiError::ResourceUnavailable(msg))
+                if msg == "no schedulable nodes available for remote operation"
+        ));
 
-        while !waiters.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
-            }
-
-            tracing::info!("Awaiting {} pending drain reconciliations", waiters.len());
+        intent.clear(&mut scheduler);
+    }
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+    #[test]
+    fn orphaned_operations() {
+        let mut nodes = make_test_nodes(4);
+        let policies = [
+            NodeSchedulingPolicy::Draining,
+            NodeSchedulingPolicy::Filling,
+            NodeSchedulingPolicy::Pause,
+            NodeSchedulingPolicy::Draining,
+        ];
+        for (i, policy) in policies.into_iter().enumerate() {
+            let node = nodes.get_mut(&NodeId(i as u64 + 1)).unwrap();
+            node.set_scheduling(policy);
         }
+        nodes
+            .get_mut(&NodeId(4))
+            .unwrap()
+            .set_availability(NodeAvailability::Offline);
+
+        // Paused and offline nodes are left alone
+        let mut ongoing = HashMap::new();
+        let orphaned = orphaned_operation_nodes(&nodes, &ongoing);
+        assert_eq!(orphaned, HashSet::from([NodeId(1), NodeId(2)]));
+
+        // A node whose operation is still running is not orphaned
+        ongoing.insert(
+            NodeId(1),
+            OperationHandler {
+                operation: Operation::Drain(Drain { node_id: NodeId(1) }),
+                cancel: CancellationToken::new(),
+            },
+        );
+        let orphaned = orphaned_operation_nodes(&nodes, &ongoing);
+        assert_eq!(orphaned, HashSet::from([NodeId(2)]));
+
+        // Operations on several nodes may run at once
+        ongoing.insert(
+            NodeId(2),
+            OperationHandler {
+                operation: Operation::Fill(Fill { node_id: NodeId(2) }),
+                cancel: CancellationToken::new(),
+            },
+        );
+        assert!(orphaned_operation_nodes(&nodes, &ongoing).is_empty());
+    }
 
-        // At this point we have done the best we could to drain shards from this node.
-        // Set the node scheduling policy to `[NodeSchedulingPolicy::PauseForRestart]`
-        // to complete the drain.
-        if let Err(err) = self
-            .node_configure(node_id, None, Some(NodeSchedulingPolicy::PauseForRestart))
-            .await
-        {
-            // This is not fatal. Anything that is polling the node scheduling policy to detect
-            // the end of the drain operations will hang, but all such places should enforce an
-            // overall timeout. The scheduling policy will be updated upon node re-attach and/or
-            // by the counterpart fill operation.
-            return Err(OperationError::FinalizeError(
-                format!(
-                    "Failed to finalise drain of {node_id} by setting scheduling policy to PauseForRestart: {err}"
-                )
-                .into(),
-            ));
-        }
+    #[test]
+    fn location_drift_kinds() {
+        let shard = make_test_tenant(PlacementPolicy::Attached(0), ShardCount::new(1))
+            .pop()
+            .unwrap();
+        let conf = |mode, generation| {
+            let mut conf = crate::reconciler::attached_location_conf(
+                Generation::new(generation),
+                &shard.shard,
+                &shard.config,
+                &shard.policy,
+            );
+            conf.mode = mode;
+            conf
+        };
+        let observed = |conf: Option<LocationConfig>| ObservedStateLocation { conf };
+        let attached = conf(LocationConfigMode::AttachedSingle, 1);
+        let detached = conf(LocationConfigMode::Detached, 1);
+
+        // Agreement, including on having nothing
+        assert_eq!(location_drift(None, None), None);
+        assert_eq!(
+            location_drift(
+                Some(&observed(Some(attached.clone()))),
+                Some(&Some(attached.clone()))
+            ),
+            None
+        );
+        assert_eq!(
+            location_drift(Some(&observed(Some(detached.clone()))), None),
+            None
+        );
+        assert_eq!(location_drift(None, Some(&Some(detached.clone()))), None);
+
+        // Either side being unsure is not drift
+        assert_eq!(location_drift(Some(&observed(None)), None), None);
+        assert_eq!(
+            location_drift(Some(&observed(None)), Some(&Some(attached.clone()))),
+            None
+        );
+        assert_eq!(
+            location_drift(Some(&observed(Some(attached.clone()))), Some(&None)),
+            None
+        );
 
-        Ok(())
+        assert_eq!(
+            location_drift(None, Some(&Some(attached.clone()))),
+            Some(LocationDriftKind::Unexpected)
+        );
+        assert_eq!(
+            location_drift(None, Some(&None)),
+            Some(LocationDriftKind::Unexpected)
+        );
+        assert_eq!(
+            location_drift(Some(&observed(Some(attached.clone()))), None),
+            Some(LocationDriftKind::Missing)
+        );
+        assert_eq!(
+            location_drift(
+                Some(&observed(Some(attached.clone()))),
+                Some(&Some(detached))
+            ),
+            Some(LocationDriftKind::Missing)
+        );
+        for reported in [
+            conf(LocationConfigMode::AttachedStale, 1),
+            conf(LocationConfigMode::AttachedSingle, 2),
+        ] {
+            assert_eq!(
+                location_drift(
+                    Some(&observed(Some(attached.clone()))),
+                    Some(&Some(reported))
+                ),
+                Some(LocationDriftKind::Mismatch)
+            );
+        }
     }
 
-    /// Create a node fill plan (pick secondaries to promote) that meets the following requirements:
-    /// 1. The node should be filled until it reaches the expected cluster average of
-    /// attached shards. If there are not enough secondaries on the node, the plan stops early.
-    /// 2. Select tenant shards to promote such that the number of attached shards is balanced
-    /// throughout the cluster. We achieve this by picking tenant shards from each node,
-    /// starting from the ones with the largest number of attached shards, until the node
-    /// reaches the expected cluster average.
-    /// 3. Avoid promoting more shards of the same tenant than required. The upper bound
-    /// for the number of tenants from the same shard promoted to the node being filled is:
-    /// shard count for the tenant divided by the number of nodes in the cluster.
-    fn fill_node_plan(&self, node_id: NodeId) -> Vec<TenantShardId> {
-        let mut locked = self.inner.write().unwrap();
-        let fill_requirement = locked.scheduler.compute_fill_requirement(node_id);
+    #[test]
+    fn spread_shards_over_nodes() {
+        let mut nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let tenant_id = TenantId::generate();
+        let shard_ids = |count: u8| {
+            (0..count).map(move |i| TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(i),
+                shard_count: ShardCount::new(count),
+            })
+        };
 
-        let mut tids_by_node = locked
-            .tenants
-            .iter_mut()
-            .filter_map(|(tid, tenant_shard)| {
-                if tenant_shard.intent.get_secondary().contains(&node_id) {
-                    if let Some(primary) = tenant_shard.intent.get_attached() {
-                        return Some((*primary, *tid));
-                    }
-                }
+        // Shards are dealt out evenly, each to exactly one node
+        let assignments = spread_over_available_nodes(&scheduler, &nodes, shard_ids(8)).unwrap();
+        assert_eq!(assignments.len(), 3);
+        let mut sizes = assignments
+            .iter()
+            .map(|(_, shards)| shards.len())
+            .collect::<Vec<_>>();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 3, 3]);
+        let assigned = assignments
+            .iter()
+            .flat_map(|(_, shards)| shards.iter().copied())
+            .collect::<HashSet<_>>();
+        assert_eq!(assigned, shard_ids(8).collect::<HashSet<_>>());
+
+        // Unavailable nodes are not used
+        let offline = nodes.get_mut(&NodeId(1)).unwrap();
+        offline.set_availability(NodeAvailability::Offline);
+        scheduler.node_upsert(offline);
+        let assignments = spread_over_available_nodes(&scheduler, &nodes, shard_ids(4)).unwrap();
+        assert!(assignments
+            .iter()
+            .all(|(node, shards)| node.get_id() != NodeId(1) && shards.len() == 2));
 
-                None
-            })
-            .into_group_map();
+        // With every node unavailable, time travel fails with a clear, retryable error
+        for node in nodes.values_mut() {
+            node.set_availability(NodeAvailability::Offline);
+            scheduler.node_upsert(node);
+        }
+        assert!(matches!(
+            spread_over_available_nodes(&scheduler, &nodes, shard_ids(4)),
+            Err(ApiError::ResourceUnavailable(msg))
+                if msg == "no schedulable nodes available for remote operation"
+        ));
+    }
 
-        let expected_attached = locked.scheduler.expected_attached_shard_count();
-        let nodes_by_load = locked.scheduler.nodes_by_attached_shard_count();
+    #[test]
+    fn optimizations_per_pass_cap() {
+        let nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
 
-        let mut promoted_per_tenant: HashMap<TenantId, usize> = HashMap::new();
-        let mut plan = Vec::new();
+        // Several tenants whose shards are all attached to the same node, with secondaries
+        // elsewhere: every shard has an attachment optimization available.
+        let mut tenants = BTreeMap::new();
+        let mut work = Vec::new();
+        for _ in 0..3 {
+            let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
 
-        for (node_id, attached) in nodes_by_load {
-            let available = locked
-                .nodes
-                .get(&node_id)
-                .map_or(false, |n| n.is_available());
-            if !available {
-                continue;
+            let mut schedule_context = ScheduleContext::default();
+            for (shard, secondary) in shards.iter_mut().zip([NodeId(2), NodeId(3)]) {
+                shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+                shard.intent.push_secondary(&mut scheduler, secondary);
+                schedule_context.avoid(&shard.intent.all_pageservers());
+                schedule_context.push_attached(NodeId(1));
             }
 
-            if plan.len() >= fill_requirement
-                || tids_by_node.is_empty()
-                || attached <= expected_attached
-            {
-                break;
+            for shard in shards {
+                let optimization = shard
+                    .optimize_attachment(&nodes, &schedule_context)
+                    .expect("Shard should be optimized away from the crowded node");
+                work.push((shard.tenant_shard_id, optimization));
+                tenants.insert(shard.tenant_shard_id, shard);
             }
+        }
+        assert_eq!(work.len(), 6);
 
-            let can_take = attached - expected_attached;
-            let needed = fill_requirement - plan.len();
-            let mut take = std::cmp::min(can_take, needed);
+        let applied = apply_optimizations(&mut tenants, &mut scheduler, work, 2);
+        assert_eq!(applied.len(), 2);
+        assert_eq!(
+            tenants
+                .values()
+                .filter(|s| s.intent.get_attached() != &Some(NodeId(1)))
+                .count(),
+            2
+        );
 
-            let mut remove_node = false;
-            while take > 0 {
-                match tids_by_node.get_mut(&node_id) {
-                    Some(tids) => match tids.pop() {
-                        Some(tid) => {
-                            let max_promote_for_tenant = std::cmp::max(
-                                tid.shard_count.count() as usize / locked.nodes.len(),
-                                1,
-                            );
-                            let promoted = promoted_per_tenant.entry(tid.tenant_id).or_default();
-                            if *promoted < max_promote_for_tenant {
-                                plan.push(tid);
-                                *promoted += 1;
-                                take -= 1;
-                            }
-                        }
-                        None => {
-                            remove_node = true;
-                            break;
-                        }
-                    },
-                    None => {
-                        break;
-                    }
-                }
-            }
+        for shard in tenants.values_mut() {
+            shard.intent.clear(&mut scheduler);
+        }
+    }
 
-            if remove_node {
-                tids_by_node.remove(&node_id);
-            }
+    /// The background optimizer leaves canary tenants where they are, although their shards would
+    /// still move if an operator asked for a rebalance.
+    #[test]
+    fn plan_optimizations_skips_canary() {
+        let nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        // A tenant whose shards are all attached to the same node: there is an attachment
+        // optimization available for it.
+        let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2));
+        for (shard, secondary) in shards.iter_mut().zip([NodeId(2), NodeId(3)]) {
+            shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+            shard.intent.push_secondary(&mut scheduler, secondary);
+            shard.canary = true;
         }
+        let mut tenants: BTreeMap<_, _> = shards
+            .into_iter()
+            .map(|shard| (shard.tenant_shard_id, shard))
+            .collect();
 
-        plan
-    }
+        assert!(plan_optimizations(&nodes, &tenants, &scheduler, 10).is_empty());
 
-    /// Fill a node by promoting its secondaries until the cluster is balanced
-    /// with regards to attached shard counts. Note that this operation only
-    /// makes sense as a counterpart to the drain implemented in [`Service::drain_node`].
-    /// This is a long running operation and it should run as a separate Tokio task.
-    pub(crate) async fn fill_node(
-        &self,
-        node_id: NodeId,
-        cancel: CancellationToken,
-    ) -> Result<(), OperationError> {
-        // TODO(vlad): Currently this operates on the assumption that all
-        // secondaries are warm. This is not always true (e.g. we just migrated the
-        // tenant). Take that into consideration by checking the secondary status.
-        let mut tids_to_promote = self.fill_node_plan(node_id);
-        let mut waiters = Vec::new();
+        let mut schedule_context = ScheduleContext::default();
+        for shard in tenants.values() {
+            schedule_context.avoid(&shard.intent.all_pageservers());
+            schedule_context.push_attached(NodeId(1));
+        }
+        assert!(tenants.values().any(|shard| shard
+            .optimize_attachment(&nodes, &schedule_context)
+            .is_some()));
 
-        // Execute the plan we've composed above. Before aplying each move from the plan,
-        // we validate to ensure that it has not gone stale in the meantime.
-        while !tids_to_promote.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
-                    }
-                }
-            }
+        for shard in tenants.values_mut() {
+            shard.canary = false;
+        }
+        assert!(!plan_optimizations(&nodes, &tenants, &scheduler, 10).is_empty());
 
-            {
-                let mut locked = self.inner.write().unwrap();
-                let (nodes, tenants, scheduler) = locked.parts_mut();
+        for shard in tenants.values_mut() {
+            shard.intent.clear(&mut scheduler);
+        }
+    }
 
-                let node = nodes.get(&node_id).ok_or(OperationError::NodeStateChanged(
-                    format!("node {node_id} was removed").into(),
-                ))?;
+    #[tokio::test]
+    async fn startup_reconcile_concurrency() {
+        let semaphore = tokio::sync::Semaphore::new(32);
+        let cancel = CancellationToken::new();
 
-                let current_policy = node.get_scheduling();
-                if !matches!(current_policy, NodeSchedulingPolicy::Filling) {
-                    // TODO(vlad): maybe cancel pending reconciles before erroring out. need to think
-                    // about it
-                    return Err(OperationError::NodeStateChanged(
-                        format!("node {node_id} changed state to {current_policy:?}").into(),
-                    ));
-                }
+        // During startup, a reconciler holds a unit
+        let held = semaphore.try_acquire().unwrap();
+        assert_eq!(semaphore.available_permits(), 31);
 
-                while waiters.len() < MAX_RECONCILES_PER_OPERATION {
-                    if let Some(tid) = tids_to_promote.pop() {
-                        if let Some(tenant_shard) = tenants.get_mut(&tid) {
-                            // If the node being filled is not a secondary anymore,
-                            // skip the promotion.
-                            if !tenant_shard.intent.get_secondary().contains(&node_id) {
-                                continue;
-                            }
+        // Retiring the startup units waits for in-flight reconcilers
+        let retire = retire_startup_reconcile_units(&semaphore, 32, 8, &cancel);
+        tokio::pin!(retire);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut retire)
+                .await
+                .is_err()
+        );
 
-                            let previously_attached_to = *tenant_shard.intent.get_attached();
-                            match tenant_shard.reschedule_to_secondary(Some(node_id), scheduler) {
-                                Err(e) => {
-                                    tracing::warn!(
-                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                        "Scheduling error when filling pageserver {} : {e}", node_id
-                                    );
-                                }
-                                Ok(()) => {
-                                    tracing::info!(
-                                        tenant_id=%tid.tenant_id, shard_id=%tid.shard_slug(),
-                                        "Rescheduled shard while filling node {}: {:?} -> {}",
-                                        node_id,
-                                        previously_attached_to,
-                                        node_id
-                                    );
+        drop(held);
+        retire.await;
+        assert_eq!(semaphore.available_permits(), 8);
 
-                                    if let Some(waiter) =
-                                        self.maybe_reconcile_shard(tenant_shard, nodes)
-                                    {
-                                        waiters.push(waiter);
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        break;
-                    }
-                }
-            }
+        // A startup concurrency lower than the steady state is a no-op
+        retire_startup_reconcile_units(&semaphore, 4, 8, &cancel).await;
+        assert_eq!(semaphore.available_permits(), 8);
+    }
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
-        }
+    #[test]
+    fn reconcile_timeout_override() {
+        let default = Duration::from_secs(5);
+        assert_eq!(reconcile_timeout(None, default), default);
+        assert_eq!(
+            reconcile_timeout(Some(Duration::from_secs(120)), default),
+            Duration::from_secs(120)
+        );
+        assert_eq!(
+            reconcile_timeout(Some(Duration::from_secs(3600)), default),
+            MAX_RECONCILE_TIMEOUT
+        );
+    }
 
-        while !waiters.is_empty() {
-            if cancel.is_cancelled() {
-                match self
-                    .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-                    .await
-                {
-                    Ok(()) => return Err(OperationError::Cancelled),
-                    Err(err) => {
-                        return Err(OperationError::FinalizeError(
-                            format!(
-                                "Failed to finalise drain cancel of {} by setting scheduling policy to Active: {}",
-                                node_id, err
-                            )
-                            .into(),
-                        ));
+    #[test]
+    fn split_rejects_incapable_node() {
+        let mut nodes = make_test_nodes(3);
+        let tenant_id = TenantId::generate();
+        let shard_count = ShardCount::new(2);
+        let targets = |nodes: &HashMap<NodeId, Node>| {
+            (0..2)
+                .map(|i| {
+                    let parent_id = TenantShardId {
+                        tenant_id,
+                        shard_number: ShardNumber(i),
+                        shard_count,
+                    };
+                    ShardSplitTarget {
+                        parent_id,
+                        node: nodes.get(&NodeId(i as u64 + 1)).unwrap().clone(),
+                        child_ids: parent_id.split(ShardCount::new(4)),
                     }
-                }
-            }
+                })
+                .collect::<Vec<_>>()
+        };
 
-            tracing::info!("Awaiting {} pending fill reconciliations", waiters.len());
+        // Nodes which did not report capabilities, or which report shard splitting, may split
+        assert!(check_split_targets(&targets(&nodes)).is_ok());
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_capabilities(Some(NodeCapabilities::current()));
+        assert!(check_split_targets(&targets(&nodes)).is_ok());
 
-            waiters = self
-                .await_waiters_remainder(waiters, SHORT_RECONCILE_TIMEOUT)
-                .await;
+        // One incapable node is enough to reject the whole split
+        nodes
+            .get_mut(&NodeId(2))
+            .unwrap()
+            .set_capabilities(Some(NodeCapabilities::default()));
+        match check_split_targets(&targets(&nodes)) {
+            Err(ApiError::PreconditionFailed(msg)) => {
+                assert!(msg.contains("pageserver 2"), "{msg}")
+            }
+            r => panic!("Expected rejection, got {r:?}"),
         }
 
-        if let Err(err) = self
-            .node_configure(node_id, None, Some(NodeSchedulingPolicy::Active))
-            .await
-        {
-            // This isn't a huge issue since the filling process starts upon request. However, it
-            // will prevent the next drain from starting. The only case in which this can fail
-            // is database unavailability. Such a case will require manual intervention.
-            return Err(OperationError::FinalizeError(
-                format!("Failed to finalise fill of {node_id} by setting scheduling policy to Active: {err}")
-                    .into(),
-            ));
-        }
+        // Nodes not involved in the split do not matter
+        nodes
+            .get_mut(&NodeId(3))
+            .unwrap()
+            .set_capabilities(Some(NodeCapabilities::default()));
+        nodes.get_mut(&NodeId(2)).unwrap().set_capabilities(None);
+        assert!(check_split_targets(&targets(&nodes)).is_ok());
+    }
 
-        Ok(())
+    #[test]
+    fn placement_validation() {
+        let nodes = make_test_nodes(3);
+        let tenants = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(2))
+            .into_iter()
+            .map(|s| (s.tenant_shard_id, s))
+            .collect::<BTreeMap<_, _>>();
+        let tenant_shard_id = *tenants.keys().next().unwrap();
+        let tenant_id = tenant_shard_id.tenant_id;
+
+        let placement = |attached: Option<u64>, secondaries: &[u64]| TenantShardPlacement {
+            tenant_shard_id,
+            attached: attached.map(NodeId),
+            secondaries: secondaries.iter().copied().map(NodeId).collect(),
+        };
+        let validate = |spec: &[TenantShardPlacement]| {
+            validate_placement(tenant_id, spec, &tenants, &nodes).map_err(|e| e.to_string())
+        };
+
+        assert!(validate(&[placement(Some(1), &[2])]).is_ok());
+
+        // The same node can't serve as two locations of one shard
+        assert!(validate(&[placement(Some(1), &[1])])
+            .unwrap_err()
+            .contains("more than one location"));
+        // Unknown nodes
+        assert!(validate(&[placement(Some(1), &[4])])
+            .unwrap_err()
+            .contains("Node 4 not found"));
+        // Location counts must match the policy
+        assert!(validate(&[placement(Some(1), &[])])
+            .unwrap_err()
+            .contains("does not match its policy"));
+        assert!(validate(&[placement(None, &[1, 2])])
+            .unwrap_err()
+            .contains("does not match its policy"));
+        // Each shard at most once
+        assert!(
+            validate(&[placement(Some(1), &[2]), placement(Some(2), &[3])])
+                .unwrap_err()
+                .contains("more than once")
+        );
+        // Shards of another tenant
+        let other_tenant = TenantShardPlacement {
+            tenant_shard_id: TenantShardId::unsharded(TenantId::generate()),
+            attached: Some(NodeId(1)),
+            secondaries: vec![NodeId(2)],
+        };
+        assert!(validate(&[other_tenant])
+            .unwrap_err()
+            .contains("does not belong"));
     }
 }
@@ -1,17 +1,18 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
+    liveness::CountedSender,
     metrics::{self, ReconcileCompleteLabelGroup, ReconcileOutcome},
     persistence::TenantShardPersistence,
     reconciler::ReconcileUnits,
     scheduler::{AffinityScore, MaySchedule, RefCountUpdate, ScheduleContext},
 };
 use pageserver_api::controller_api::{
-    NodeSchedulingPolicy, PlacementPolicy, ShardSchedulingPolicy,
+    NodeSchedulingPolicy, PlacementPolicy, ReconcilePriority, ShardSchedulingPolicy,
 };
 use pageserver_api::{
     models::{LocationConfig, LocationConfigMode, TenantConfig},
@@ -19,9 +20,10 @@ use pageserver_api::{
 };
 use serde::Serialize;
 use tokio::task::JoinHandle;
-use tokio_util::sync::CancellationToken;
+use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::{instrument, Instrument};
 use utils::{
+    backoff::exponential_backoff_duration_seconds,
     generation::Generation,
     id::NodeId,
     seqwait::{SeqWait, SeqWaitError},
@@ -33,12 +35,20 @@ use crate::{
     node::Node,
     persistence::{split_state::SplitState, Persistence},
     reconciler::{
-        attached_location_conf, secondary_location_conf, ReconcileError, Reconciler, TargetState,
+        attached_location_conf, read_replica_location_conf, secondary_location_conf,
+        GenerationFloor, ReconcileError, Reconciler, TargetState,
     },
     scheduler::{ScheduleError, Scheduler},
-    service, Sequence,
+    service::{self, FrozenGenerations},
+    Sequence,
 };
 
+/// After this many consecutive reconcile failures towards the same intent, a shard needs attention
+const RECONCILE_FAILURES_ATTENTION: usize = 5;
+
+/// Upper bound on the backoff between reconciles of a shard whose reconciles keep failing
+const RECONCILE_FAILURE_BACKOFF_MAX: Duration = Duration::from_secs(300);
+
 /// Serialization helper
 fn read_last_error<S, T>(v: &std::sync::Mutex<Option<T>>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -54,6 +64,34 @@ where
     )
 }
 
+/// Operator-set flags on a tenant's shards: see [`TenantShard::sticky`], which is set per shard,
+/// and [`TenantShard::colocate`] and [`TenantShard::canary`], which apply to a whole tenant.
+#[derive(Clone, Copy, Debug, strum_macros::Display)]
+pub(crate) enum TenantFlag {
+    Sticky,
+    Colocate,
+    Canary,
+}
+
+/// The values of the tenant-wide [`TenantFlag`]s for a tenant, e.g. to pass them on to the
+/// children of a split.  Sticky pins one shard's particular locations, so it is not inherited.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct TenantFlags {
+    pub(crate) colocate: bool,
+    pub(crate) canary: bool,
+}
+
+impl TenantFlags {
+    /// Shards of a tenant should all have the same flags, but if they disagree then a flag set on
+    /// any of them wins.
+    pub(crate) fn merge(self, other: TenantFlags) -> TenantFlags {
+        TenantFlags {
+            colocate: self.colocate || other.colocate,
+            canary: self.canary || other.canary,
+        }
+    }
+}
+
 /// In-memory state for a particular tenant shard.
 ///
 /// This struct implement Serialize for debugging purposes, but is _not_ persisted
@@ -76,6 +114,11 @@ pub(crate) struct TenantShard {
     // API, where this tenant may only run in PlacementPolicy::Secondary.
     pub(crate) generation: Option<Generation>,
 
+    /// Raised along with [`Self::generation`] via [`Self::advance_generation`], and shared with
+    /// reconcilers so that one running with an older generation refuses to attach with it.
+    #[serde(skip)]
+    generation_floor: Arc<GenerationFloor>,
+
     // High level description of how the tenant should be set up.  Provided
     // externally.
     pub(crate) policy: PlacementPolicy,
@@ -108,6 +151,15 @@ pub(crate) struct TenantShard {
     /// is set. This flag is cleared when the tenant is popped off the delay queue.
     pub(crate) delayed_reconcile: bool,
 
+    /// When [`Self::delayed_reconcile`] was last set, to report how long the shard has been waiting.
+    #[serde(skip)]
+    pub(crate) delayed_reconcile_since: Option<Instant>,
+
+    /// The sequence of the latest [`ReconcileResult`] whose observed state was applied to this
+    /// shard.  Results from older sequences that complete later are ignored.
+    #[serde(skip)]
+    pub(crate) applied_sequence: Sequence,
+
     /// Optionally wait for reconciliation to complete up to a particular
     /// sequence number.
     #[serde(skip)]
@@ -129,21 +181,84 @@ pub(crate) struct TenantShard {
     #[serde(serialize_with = "read_last_error")]
     pub(crate) last_error: std::sync::Arc<std::sync::Mutex<Option<Arc<ReconcileError>>>>,
 
+    /// Why the last call to [`Self::schedule`] failed, if it did: a shard that can't be scheduled
+    /// (e.g. because too many nodes are offline) stays where it is until this is cleared.
+    pub(crate) last_scheduling_error: Option<String>,
+
     /// If we have a pending compute notification that for some reason we weren't able to send,
     /// set this to true. If this is set, calls to [`Self::get_reconcile_needed`] will return Yes
     /// and trigger a Reconciler run.  This is the mechanism by which compute notifications are included in the scope
-    /// of state that we publish externally in an eventually consistent way.
+    /// of state that we publish externally in an eventually consistent way.  This is persistent, so
+    /// that notifications which were outstanding when the controller restarted are retried.
     pub(crate) pending_compute_notification: bool,
 
+    /// Set if a pageserver was seen holding a newer generation for this shard than the latest we
+    /// issued, and [`crate::service::Config::fence_generation_violations`] is enabled.  While set,
+    /// we refuse to validate any generation for this shard.
+    pub(crate) generation_fenced: bool,
+
     // Support/debug tool: if something is going wrong or flapping with scheduling, this may
     // be set to a non-active state to avoid making changes while the issue is fixed.
     scheduling_policy: ShardSchedulingPolicy,
+
+    /// If set, the optimizer will not move this shard's locations.  Unlike `scheduling_policy`, this
+    /// does not prevent scheduling for correctness (e.g. when a node fails), or manual migrations.
+    pub(crate) sticky: bool,
+
+    /// Tenant-wide hint to pack this tenant's shards onto as few nodes as possible instead of spreading
+    /// them out, for small tenants where the overhead of many locations outweighs the benefit.  Set on
+    /// all shards of a tenant.  Locations of one shard are still always on distinct nodes.
+    pub(crate) colocate: bool,
+
+    /// Tenant-wide flag for tenants used to try out new controller behavior in production: background
+    /// optimization and auto-splitting leave canaries alone, so that their placement only changes when
+    /// an operator asks for it (or when needed for availability, e.g. a node failing).  Set on all shards
+    /// of a tenant.
+    pub(crate) canary: bool,
+
+    /// Tenant-wide: when reconciler units are scarce, e.g. while draining a node, shards of higher
+    /// priority tenants get them first.  Set on all shards of a tenant.
+    pub(crate) priority: ReconcilePriority,
+
+    /// Consecutive reconcile failures towards the current intent, used to back off from reconciles
+    /// that fail persistently (e.g. because a pageserver rejects the config).
+    #[serde(skip)]
+    reconcile_failures: ReconcileFailures,
+}
+
+/// The part of a shard's state that a reconciler works towards: if this changes, earlier reconcile
+/// failures say nothing about whether the next reconcile will succeed.
+#[derive(PartialEq, Eq)]
+struct ReconcileTarget {
+    policy: PlacementPolicy,
+    attached: Option<NodeId>,
+    secondary: Vec<NodeId>,
+    config: TenantConfig,
+}
+
+#[derive(Default)]
+struct ReconcileFailures {
+    target: Option<ReconcileTarget>,
+    count: usize,
+    /// Don't spawn another reconciler towards `target` before this time
+    next_retry_at: Option<Instant>,
 }
 
 #[derive(Default, Clone, Debug, Serialize)]
 pub(crate) struct IntentState {
     attached: Option<NodeId>,
     secondary: Vec<NodeId>,
+
+    /// Additional locations that serve reads in AttachedMulti mode, alongside the primary
+    /// attached location.  These share the attached location's generation, and are not
+    /// persisted: they are forgotten when the storage controller restarts.
+    read_replicas: Vec<NodeId>,
+
+    /// When any of the above last changed.  Compared with when the shard's observed state last
+    /// converged, this tells how long a shard took to realize its intent.  None if the intent
+    /// has not been set since this process started.
+    #[serde(skip)]
+    changed_at: Option<Instant>,
 }
 
 impl IntentState {
@@ -151,6 +266,8 @@ impl IntentState {
         Self {
             attached: None,
             secondary: vec![],
+            read_replicas: vec![],
+            changed_at: None,
         }
     }
     pub(crate) fn single(scheduler: &mut Scheduler, node_id: Option<NodeId>) -> Self {
@@ -160,9 +277,20 @@ impl IntentState {
         Self {
             attached: node_id,
             secondary: vec![],
+            read_replicas: vec![],
+            changed_at: node_id.map(|_| Instant::now()),
         }
     }
 
+    /// Record that the intent changed: every method that modifies the intent must call this.
+    fn touch(&mut self) {
+        self.changed_at = Some(Instant::now());
+    }
+
+    pub(crate) fn get_changed_at(&self) -> Option<Instant> {
+        self.changed_at
+    }
+
     pub(crate) fn set_attached(&mut self, scheduler: &mut Scheduler, new_attached: Option<NodeId>) {
         if self.attached != new_attached {
             if let Some(old_attached) = self.attached.take() {
@@ -172,6 +300,7 @@ impl IntentState {
                 scheduler.update_node_ref_counts(*new_attached, RefCountUpdate::Attach);
             }
             self.attached = new_attached;
+            self.touch();
         }
     }
 
@@ -190,6 +319,7 @@ impl IntentState {
 
         let demoted = self.attached;
         self.attached = Some(promote_secondary);
+        self.touch();
 
         scheduler.update_node_ref_counts(promote_secondary, RefCountUpdate::PromoteSecondary);
         if let Some(demoted) = demoted {
@@ -201,6 +331,7 @@ impl IntentState {
         debug_assert!(!self.secondary.contains(&new_secondary));
         scheduler.update_node_ref_counts(new_secondary, RefCountUpdate::AddSecondary);
         self.secondary.push(new_secondary);
+        self.touch();
     }
 
     /// It is legal to call this with a node that is not currently a secondary: that is a no-op
@@ -209,28 +340,65 @@ impl IntentState {
         if let Some(index) = index {
             scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
             self.secondary.remove(index);
+            self.touch();
         }
     }
 
     pub(crate) fn clear_secondary(&mut self, scheduler: &mut Scheduler) {
+        if self.secondary.is_empty() {
+            return;
+        }
         for secondary in self.secondary.drain(..) {
             scheduler.update_node_ref_counts(secondary, RefCountUpdate::RemoveSecondary);
         }
+        self.touch();
     }
 
     /// Remove the last secondary node from the list of secondaries
     pub(crate) fn pop_secondary(&mut self, scheduler: &mut Scheduler) {
         if let Some(node_id) = self.secondary.pop() {
             scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
+            self.touch();
+        }
+    }
+
+    pub(crate) fn push_read_replica(&mut self, scheduler: &mut Scheduler, node_id: NodeId) {
+        debug_assert!(!self.read_replicas.contains(&node_id));
+        // Read replicas hold a full copy of the tenant's data much like a secondary, so
+        // we account for them the same way in the scheduler.
+        scheduler.update_node_ref_counts(node_id, RefCountUpdate::AddSecondary);
+        self.read_replicas.push(node_id);
+        self.touch();
+    }
+
+    /// It is legal to call this with a node that is not currently a read replica: that is a no-op
+    pub(crate) fn remove_read_replica(&mut self, scheduler: &mut Scheduler, node_id: NodeId) {
+        let index = self.read_replicas.iter().position(|n| *n == node_id);
+        if let Some(index) = index {
+            scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
+            self.read_replicas.remove(index);
+            self.touch();
         }
     }
 
+    pub(crate) fn clear_read_replicas(&mut self, scheduler: &mut Scheduler) {
+        if self.read_replicas.is_empty() {
+            return;
+        }
+        for node_id in self.read_replicas.drain(..) {
+            scheduler.update_node_ref_counts(node_id, RefCountUpdate::RemoveSecondary);
+        }
+        self.touch();
+    }
+
     pub(crate) fn clear(&mut self, scheduler: &mut Scheduler) {
         if let Some(old_attached) = self.attached.take() {
             scheduler.update_node_ref_counts(old_attached, RefCountUpdate::Detach);
+            self.touch();
         }
 
         self.clear_secondary(scheduler);
+        self.clear_read_replicas(scheduler);
     }
 
     pub(crate) fn all_pageservers(&self) -> Vec<NodeId> {
@@ -240,6 +408,7 @@ impl IntentState {
         }
 
         result.extend(self.secondary.iter().copied());
+        result.extend(self.read_replicas.iter().copied());
 
         result
     }
@@ -252,6 +421,17 @@ impl IntentState {
         &self.secondary
     }
 
+    pub(crate) fn get_read_replicas(&self) -> &Vec<NodeId> {
+        &self.read_replicas
+    }
+
+    /// Whether this node is used in any role by the intent
+    pub(crate) fn references(&self, node_id: NodeId) -> bool {
+        self.attached == Some(node_id)
+            || self.secondary.contains(&node_id)
+            || self.read_replicas.contains(&node_id)
+    }
+
     /// If the node is in use as the attached location, demote it into
     /// the list of secondary locations.  This is used when a node goes offline,
     /// and we want to use a different node for attachment, but not permanently
@@ -263,6 +443,7 @@ impl IntentState {
             self.attached = None;
             self.secondary.push(node_id);
             scheduler.update_node_ref_counts(node_id, RefCountUpdate::DemoteAttached);
+            self.touch();
             true
         } else {
             false
@@ -277,7 +458,11 @@ impl Drop for IntentState {
         // other assertions with this assertion's output.  It's still wrong to leak these,
         // but if we already have a panic then we don't need to independently flag this case.
         if !(std::thread::panicking()) {
-            debug_assert!(self.attached.is_none() && self.secondary.is_empty());
+            debug_assert!(
+                self.attached.is_none()
+                    && self.secondary.is_empty()
+                    && self.read_replicas.is_empty()
+            );
         }
     }
 }
@@ -399,6 +584,10 @@ pub(crate) struct ReconcilerHandle {
     sequence: Sequence,
     handle: JoinHandle<()>,
     cancel: CancellationToken,
+    /// Fired once the reconciler's task has exited, however it exits, and the service has applied
+    /// its [`ReconcileResult`] if it sent one.  Unlike the sequence waiters, this also fires for a
+    /// reconciler that was cancelled before it sent a result.
+    done: CancellationToken,
 }
 
 pub(crate) enum ReconcileNeeded {
@@ -428,6 +617,12 @@ pub(crate) struct ReconcileResult {
 
     /// Set [`TenantShard::pending_compute_notification`] from this flag
     pub(crate) pending_compute_notification: bool,
+
+    /// How long the reconciler spent doing work, excluding time waiting for a previous reconciler
+    pub(crate) duration: Duration,
+
+    /// Fires [`ReconcilerHandle::done`] when this result is dropped after being applied
+    _done_guard: DropGuard,
 }
 
 impl ObservedState {
@@ -449,6 +644,7 @@ impl TenantShard {
             policy,
             intent: IntentState::default(),
             generation: Some(Generation::new(0)),
+            generation_floor: Arc::default(),
             shard,
             observed: ObservedState::default(),
             config: TenantConfig::default(),
@@ -456,11 +652,20 @@ impl TenantShard {
             splitting: SplitState::Idle,
             sequence: Sequence(1),
             delayed_reconcile: false,
+            delayed_reconcile_since: None,
+            reconcile_failures: ReconcileFailures::default(),
+            applied_sequence: Sequence::initial(),
             waiter: Arc::new(SeqWait::new(Sequence(0))),
             error_waiter: Arc::new(SeqWait::new(Sequence(0))),
             last_error: Arc::default(),
+            last_scheduling_error: None,
             pending_compute_notification: false,
+            generation_fenced: false,
             scheduling_policy: ShardSchedulingPolicy::default(),
+            sticky: false,
+            colocate: false,
+            canary: false,
+            priority: ReconcilePriority::default(),
         }
     }
 
@@ -542,7 +747,10 @@ impl TenantShard {
         scheduler: &mut Scheduler,
         context: &mut ScheduleContext,
     ) -> Result<(), ScheduleError> {
+        // All shards of a tenant share the hint, so this applies to the whole context
+        context.colocate = self.colocate;
         let r = self.do_schedule(scheduler, context);
+        self.last_scheduling_error = r.as_ref().err().map(|e| e.to_string());
 
         context.avoid(&self.intent.all_pageservers());
         if let Some(attached) = self.intent.get_attached() {
@@ -552,6 +760,53 @@ impl TenantShard {
         r
     }
 
+    /// Move this shard's locations off `node_id`.  An attachment there is cut over to a secondary
+    /// location if there is one, and whichever location is removed from `node_id` is replaced on
+    /// another node.  Unlike [`Self::schedule`], `node_id` is never picked as a destination, even
+    /// if it is schedulable.
+    ///
+    /// Returns true if the intent changed.
+    pub(crate) fn evacuate_node(
+        &mut self,
+        scheduler: &mut Scheduler,
+        node_id: NodeId,
+        context: &ScheduleContext,
+    ) -> Result<bool, ScheduleError> {
+        let was_attached = *self.intent.get_attached() == Some(node_id);
+        let was_read_replica = self.intent.read_replicas.contains(&node_id);
+        if !was_attached && !was_read_replica && !self.intent.secondary.contains(&node_id) {
+            return Ok(false);
+        }
+
+        // Pick the replacement before modifying intent, so that on failure the intent is unchanged.
+        // `node_id` is excluded because it is still part of our intent.
+        let replacement = scheduler.schedule_shard(&self.intent.all_pageservers(), context)?;
+
+        if was_attached {
+            match scheduler.node_preferred(&self.intent.secondary) {
+                Some(promote) => {
+                    // Cut over to a warm secondary, and replace the secondary that we used.  Demote
+                    // first so that the evacuated node becomes a secondary we can then remove.
+                    self.intent.demote_attached(scheduler, node_id);
+                    self.intent.promote_attached(scheduler, promote);
+                    self.intent.remove_secondary(scheduler, node_id);
+                    self.intent.push_secondary(scheduler, replacement);
+                }
+                None => {
+                    self.intent.set_attached(scheduler, Some(replacement));
+                }
+            }
+        } else if was_read_replica {
+            self.intent.remove_read_replica(scheduler, node_id);
+            self.intent.push_read_replica(scheduler, replacement);
+        } else {
+            self.intent.remove_secondary(scheduler, node_id);
+            self.intent.push_secondary(scheduler, replacement);
+        }
+
+        Ok(true)
+    }
+
     pub(crate) fn do_schedule(
         &mut self,
         scheduler: &mut Scheduler,
@@ -604,7 +859,14 @@ impl TenantShard {
                     self.schedule_attached(scheduler, context)?;
                 modified |= modified_attached;
 
+                // If the attachment moved onto a read replica's node, that location is now the primary
+                if self.intent.read_replicas.contains(&attached_node_id) {
+                    self.intent.remove_read_replica(scheduler, attached_node_id);
+                    modified = true;
+                }
+
                 let mut used_pageservers = vec![attached_node_id];
+                used_pageservers.extend(self.intent.read_replicas.iter().copied());
                 while self.intent.secondary.len() < secondary_count {
                     let node_id = scheduler.schedule_shard(&used_pageservers, context)?;
                     self.intent.push_secondary(scheduler, node_id);
@@ -629,10 +891,18 @@ impl TenantShard {
                     self.intent.pop_secondary(scheduler);
                     modified = true;
                 }
+                // Read replicas depend on there being an attached location
+                if !self.intent.read_replicas.is_empty() {
+                    self.intent.clear_read_replicas(scheduler);
+                    modified = true;
+                }
             }
             Detached => {
                 // Never add locations in this mode
-                if self.intent.get_attached().is_some() || !self.intent.get_secondary().is_empty() {
+                if self.intent.get_attached().is_some()
+                    || !self.intent.get_secondary().is_empty()
+                    || !self.intent.get_read_replicas().is_empty()
+                {
                     self.intent.clear(scheduler);
                     modified = true;
                 }
@@ -688,6 +958,48 @@ impl TenantShard {
         Ok(())
     }
 
+    /// Make the intent match an explicit placement, changing only the locations that differ.  Any
+    /// read replica on one of the new locations is dropped.  Returns true if the intent changed.
+    ///
+    /// The caller is responsible for checking that the placement is consistent with our policy.
+    pub(crate) fn set_placement(
+        &mut self,
+        scheduler: &mut Scheduler,
+        attached: Option<NodeId>,
+        secondaries: &[NodeId],
+    ) -> bool {
+        let mut changed = false;
+
+        // Remove unwanted secondaries first, in case one of them is to become attached
+        for node_id in self.intent.get_secondary().clone() {
+            if !secondaries.contains(&node_id) {
+                self.intent.remove_secondary(scheduler, node_id);
+                changed = true;
+            }
+        }
+
+        if *self.intent.get_attached() != attached {
+            self.intent.set_attached(scheduler, attached);
+            changed = true;
+        }
+
+        for node_id in secondaries {
+            if !self.intent.get_secondary().contains(node_id) {
+                self.intent.push_secondary(scheduler, *node_id);
+                changed = true;
+            }
+        }
+
+        for node_id in attached.iter().chain(secondaries) {
+            if self.intent.get_read_replicas().contains(node_id) {
+                self.intent.remove_read_replica(scheduler, *node_id);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
     /// Optimize attachments: if a shard has a secondary location that is preferable to
     /// its primary location based on soft constraints, switch that secondary location
     /// to be attached.
@@ -697,6 +1009,12 @@ impl TenantShard {
         nodes: &HashMap<NodeId, Node>,
         schedule_context: &ScheduleContext,
     ) -> Option<ScheduleOptimization> {
+        // Sticky shards only move on request.  The optimizer spreads shards out, which is the
+        // opposite of what co-located tenants want.
+        if self.sticky || self.colocate {
+            return None;
+        }
+
         let attached = (*self.intent.get_attached())?;
         if self.intent.secondary.is_empty() {
             // We can only do useful work if we have both attached and secondary locations: this
@@ -707,9 +1025,11 @@ impl TenantShard {
         let current_affinity_score = schedule_context.get_node_affinity(attached);
         let current_attachment_count = schedule_context.get_node_attachments(attached);
 
-        // Generate score for each node, dropping any un-schedulable nodes.
-        let all_pageservers = self.intent.all_pageservers();
-        let mut scores = all_pageservers
+        // Generate score for each node, dropping any un-schedulable nodes.  Read replicas are not
+        // candidates: we only swap between the attached location and its secondaries.
+        let mut candidates = vec![attached];
+        candidates.extend(self.intent.get_secondary().iter().copied());
+        let mut scores = candidates
             .iter()
             .flat_map(|node_id| {
                 let node = nodes.get(node_id);
@@ -780,6 +1100,12 @@ impl TenantShard {
         scheduler: &Scheduler,
         schedule_context: &ScheduleContext,
     ) -> Option<ScheduleOptimization> {
+        // Sticky shards only move on request.  The optimizer spreads shards out, which is the
+        // opposite of what co-located tenants want.
+        if self.sticky || self.colocate {
+            return None;
+        }
+
         if self.intent.secondary.is_empty() {
             // We can only do useful work if we have both attached and secondary locations: this
             // function doesn't schedule new locations, only swaps between attached and secondaries.
@@ -928,8 +1254,24 @@ impl TenantShard {
             }
         }
 
+        if !self.intent.read_replicas.is_empty() {
+            let generation = self
+                .generation
+                .expect("Attempted to configure read replica without a generation");
+            let wanted_conf =
+                read_replica_location_conf(generation, &self.shard, &self.config, &self.policy);
+            for node_id in &self.intent.read_replicas {
+                match self.observed.locations.get(node_id) {
+                    Some(conf) if conf.conf.as_ref() == Some(&wanted_conf) => {}
+                    Some(_) | None => {
+                        dirty_nodes.insert(*node_id);
+                    }
+                }
+            }
+        }
+
         for node_id in self.observed.locations.keys() {
-            if self.intent.attached != Some(*node_id) && !self.intent.secondary.contains(node_id) {
+            if !self.intent.references(*node_id) {
                 // We have observed state that isn't part of our intent: need to clean it up.
                 dirty_nodes.insert(*node_id);
             }
@@ -984,6 +1326,20 @@ impl TenantShard {
             return ReconcileNeeded::No;
         }
 
+        // Check whether we may actually do the work.  This comes before the check for an in-flight
+        // reconciler, because a stopped shard's reconciler is cancelled and must not be waited for.
+        match self.scheduling_policy {
+            ShardSchedulingPolicy::Active
+            | ShardSchedulingPolicy::Essential
+            | ShardSchedulingPolicy::Pause => {}
+            ShardSchedulingPolicy::Stop => {
+                // We only reach this point if there is work to do and we're going to skip
+                // doing it: warn it obvious why this tenant isn't doing what it ought to.
+                tracing::warn!("Skipping reconcile for policy {:?}", self.scheduling_policy);
+                return ReconcileNeeded::No;
+            }
+        }
+
         // Reconcile already in flight for the current sequence?
         if let Some(handle) = &self.reconciler {
             if handle.sequence == self.sequence {
@@ -1001,22 +1357,81 @@ impl TenantShard {
             }
         }
 
-        // Pre-checks done: finally check whether we may actually do the work
-        match self.scheduling_policy {
-            ShardSchedulingPolicy::Active
-            | ShardSchedulingPolicy::Essential
-            | ShardSchedulingPolicy::Pause => {}
-            ShardSchedulingPolicy::Stop => {
-                // We only reach this point if there is work to do and we're going to skip
-                // doing it: warn it obvious why this tenant isn't doing what it ought to.
-                tracing::warn!("Skipping reconcile for policy {:?}", self.scheduling_policy);
-                return ReconcileNeeded::No;
-            }
+        // Back off from reconciles that keep failing for the same intent, rather than retrying them
+        // in a tight loop.
+        if let Some(remaining) = self.reconcile_backoff_remaining() {
+            tracing::debug!(
+                "Skipping reconcile after {} consecutive failures, next attempt in {remaining:?}",
+                self.reconcile_failures.count
+            );
+            return ReconcileNeeded::No;
         }
 
         ReconcileNeeded::Yes
     }
 
+    fn reconcile_target(&self) -> ReconcileTarget {
+        ReconcileTarget {
+            policy: self.policy.clone(),
+            attached: *self.intent.get_attached(),
+            secondary: self.intent.get_secondary().clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    /// Called when a reconcile fails for a reason other than cancellation.  Failures only accumulate
+    /// while the intent is unchanged.  Returns true if this failure is the one that makes the shard
+    /// need attention.
+    pub(crate) fn record_reconcile_failure(&mut self) -> bool {
+        let target = self.reconcile_target();
+        if self.reconcile_failures.target.as_ref() != Some(&target) {
+            self.reconcile_failures = ReconcileFailures {
+                target: Some(target),
+                ..Default::default()
+            };
+        }
+
+        self.reconcile_failures.count += 1;
+        let backoff = Duration::from_secs_f64(exponential_backoff_duration_seconds(
+            self.reconcile_failures.count as u32,
+            1.0,
+            RECONCILE_FAILURE_BACKOFF_MAX.as_secs_f64(),
+        ));
+        self.reconcile_failures.next_retry_at = Some(Instant::now() + backoff);
+        self.reconcile_failures.count == RECONCILE_FAILURES_ATTENTION
+    }
+
+    pub(crate) fn record_reconcile_success(&mut self) {
+        self.reconcile_failures = ReconcileFailures::default();
+    }
+
+    /// If reconciles towards the current intent have failed repeatedly, the number of consecutive
+    /// failures.  A change to the intent clears this, as does a successful reconcile.
+    pub(crate) fn reconcile_needs_attention(&self) -> Option<usize> {
+        let failures = &self.reconcile_failures;
+        if failures.count >= RECONCILE_FAILURES_ATTENTION
+            && failures.target.as_ref() == Some(&self.reconcile_target())
+        {
+            Some(failures.count)
+        } else {
+            None
+        }
+    }
+
+    /// If reconciles towards the current intent have failed, how long until we may try again.  The
+    /// backoff doubles with each consecutive failure, up to [`RECONCILE_FAILURE_BACKOFF_MAX`].
+    pub(crate) fn reconcile_backoff_remaining(&self) -> Option<Duration> {
+        let failures = &self.reconcile_failures;
+        if failures.target.as_ref() != Some(&self.reconcile_target()) {
+            return None;
+        }
+
+        let remaining = failures
+            .next_retry_at?
+            .checked_duration_since(Instant::now())?;
+        Some(remaining).filter(|d| !d.is_zero())
+    }
+
     /// Ensure the sequence number is set to a value where waiting for this value will make us wait
     /// for the next reconcile: i.e. it is ahead of all completed or running reconcilers.
     ///
@@ -1058,11 +1473,12 @@ impl TenantShard {
     #[instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug()))]
     pub(crate) fn spawn_reconciler(
         &mut self,
-        result_tx: &tokio::sync::mpsc::UnboundedSender<ReconcileResult>,
+        result_tx: &CountedSender<ReconcileResult>,
         pageservers: &Arc<HashMap<NodeId, Node>>,
         compute_hook: &Arc<ComputeHook>,
         service_config: &service::Config,
         persistence: &Arc<Persistence>,
+        frozen_generations: &Arc<FrozenGenerations>,
         units: ReconcileUnits,
         gate_guard: GateGuard,
         cancel: &CancellationToken,
@@ -1074,9 +1490,7 @@ impl TenantShard {
         // Build list of nodes from which the reconciler should detach
         let mut detach = Vec::new();
         for node_id in self.observed.locations.keys() {
-            if self.intent.get_attached() != &Some(*node_id)
-                && !self.intent.secondary.contains(node_id)
-            {
+            if !self.intent.references(*node_id) {
                 detach.push(
                     pageservers
                         .get(node_id)
@@ -1107,7 +1521,9 @@ impl TenantShard {
             _resource_units: units,
             cancel: reconciler_cancel.clone(),
             persistence: persistence.clone(),
+            frozen_generations: frozen_generations.clone(),
             compute_notify_failure: false,
+            generation_floor: self.generation_floor.clone(),
         };
 
         let reconcile_seq = self.sequence;
@@ -1122,6 +1538,8 @@ impl TenantShard {
             .storage_controller_reconcile_spawn
             .inc();
         let result_tx = result_tx.clone();
+        let done = CancellationToken::new();
+        let done_guard = done.clone().drop_guard();
         let join_handle = tokio::task::spawn(
             async move {
                 // Wait for any previous reconcile task to complete before we start
@@ -1148,6 +1566,7 @@ impl TenantShard {
                 }
 
                 // Attempt to make observed state match intent state
+                let started_at = Instant::now();
                 let result = reconciler.reconcile().await;
 
                 // If we know we had a pending compute notification from some previous action, send a notification irrespective
@@ -1171,6 +1590,19 @@ impl TenantShard {
                         status: outcome_label,
                     });
 
+                // Persist the shard's pending compute notification before our result applies it in
+                // memory, so that the Service doesn't wait on the database to process results.
+                // Reconcilers for a shard run one at a time, so these writes happen in order.  When
+                // neither the old nor the new value is set, there is nothing to do.
+                if must_notify || reconciler.compute_notify_failure {
+                    service::persist_pending_compute_notification(
+                        &reconciler.persistence,
+                        reconciler.tenant_shard_id,
+                        reconciler.compute_notify_failure,
+                    )
+                    .await;
+                }
+
                 // Constructing result implicitly drops Reconciler, freeing any ReconcileUnits before the Service might
                 // try and schedule more work in response to our result.
                 let result = ReconcileResult {
@@ -1180,6 +1612,8 @@ impl TenantShard {
                     generation: reconciler.generation,
                     observed: reconciler.observed,
                     pending_compute_notification: reconciler.compute_notify_failure,
+                    duration: started_at.elapsed(),
+                    _done_guard: done_guard,
                 };
 
                 result_tx.send(result).ok();
@@ -1191,6 +1625,7 @@ impl TenantShard {
             sequence: self.sequence,
             handle: join_handle,
             cancel: reconciler_cancel,
+            done,
         });
 
         Some(ReconcilerWaiter {
@@ -1218,6 +1653,44 @@ impl TenantShard {
         }
     }
 
+    /// Secondary locations which provide redundancy right now, rather than just being intended: they
+    /// are on an available node, and we have observed them configured in secondary mode.
+    pub(crate) fn get_redundant_secondaries(&self, nodes: &HashMap<NodeId, Node>) -> Vec<NodeId> {
+        self.intent
+            .secondary
+            .iter()
+            .filter(|node_id| {
+                nodes
+                    .get(node_id)
+                    .map(|n| n.is_available())
+                    .unwrap_or(false)
+                    && matches!(
+                        self.observed.locations.get(node_id),
+                        Some(ObservedStateLocation {
+                            conf: Some(LocationConfig {
+                                mode: LocationConfigMode::Secondary,
+                                ..
+                            })
+                        })
+                    )
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Whether our current sequence has been reconciled, as seen by a [`ReconcilerWaiter`]
+    /// for that sequence.
+    pub(crate) fn get_reconcile_status(&self) -> ReconcilerStatus {
+        ReconcilerWaiter {
+            tenant_shard_id: self.tenant_shard_id,
+            seq_wait: self.waiter.clone(),
+            error_seq_wait: self.error_waiter.clone(),
+            error: self.last_error.clone(),
+            seq: self.sequence,
+        }
+        .get_status()
+    }
+
     /// Called when a ReconcileResult has been emitted and the service is updating
     /// our state: if the result is from a sequence >= my ReconcileHandle, then drop
     /// the handle to indicate there is no longer a reconciliation in progress.
@@ -1229,28 +1702,191 @@ impl TenantShard {
         }
     }
 
+    /// Called before applying a [`ReconcileResult`]'s observed state.  Returns false if a result for a
+    /// later sequence was already applied: reconcilers for the same shard can complete out of order
+    /// (e.g. a cancelled one reporting after its successor), and applying the older result's
+    /// observed state would regress it.
+    pub(crate) fn advance_applied_sequence(&mut self, sequence: Sequence) -> bool {
+        if sequence < self.applied_sequence {
+            false
+        } else {
+            self.applied_sequence = sequence;
+            true
+        }
+    }
+
+    /// Apply the generation and compute notification state carried by a [`ReconcileResult`].
+    /// Returns false if the result is stale (see [`Self::advance_applied_sequence`]), in which
+    /// case only the generation is updated and the caller must not apply its observed state.
+    pub(crate) fn accept_reconcile_result(&mut self, result: &ReconcileResult) -> bool {
+        // Usually generation should only be updated via this path, so the max() isn't
+        // needed, but it is used to handle out-of-band updates via. e.g. test hook.
+        self.generation = std::cmp::max(self.generation, result.generation);
+
+        if !self.advance_applied_sequence(result.sequence) {
+            return false;
+        }
+
+        // If the reconciler signals that it failed to notify compute, set this state on
+        // the shard so that a future [`TenantShard::maybe_reconcile`] will try again.
+        self.pending_compute_notification = result.pending_compute_notification;
+        true
+    }
+
+    /// Apply the observed state from a [`ReconcileResult`] accepted by
+    /// [`Self::accept_reconcile_result`].  A successful reconcile's observed state is `complete`
+    /// and replaces ours, while a failed one only overrides the locations it mentions.
+    pub(crate) fn apply_observed(&mut self, observed: ObservedState, complete: bool) {
+        if complete {
+            self.observed = observed;
+        } else {
+            self.observed.locations.extend(observed.locations);
+        }
+    }
+
     // If we had any state at all referring to this node ID, drop it.  Does not
     // attempt to reschedule.
     pub(crate) fn deref_node(&mut self, node_id: NodeId) {
+        if self.intent.references(node_id) {
+            self.intent.touch();
+        }
+
         if self.intent.attached == Some(node_id) {
             self.intent.attached = None;
         }
 
         self.intent.secondary.retain(|n| n != &node_id);
+        self.intent.read_replicas.retain(|n| n != &node_id);
 
         self.observed.locations.remove(&node_id);
 
         debug_assert!(!self.intent.all_pageservers().contains(&node_id));
     }
 
+    /// Drop observed locations whose state is unknown (`conf` is None), which are not part of
+    /// our intent, and which are on nodes that are not available.  Such entries would otherwise
+    /// accumulate over many migrations, as nothing will clean them up while the node is away.
+    ///
+    /// Entries on available nodes are retained, because the next reconcile must detach them.
+    /// Dropping entries on unavailable nodes is safe because a node is fully reconciled wrt
+    /// our intent when it is reactivated, in [`crate::service::Service::node_activate_reconcile`].
+    ///
+    /// Returns the number of entries dropped.
+    pub(crate) fn prune_observed(&mut self, nodes: &HashMap<NodeId, Node>) -> usize {
+        let before = self.observed.locations.len();
+
+        let intent = &self.intent;
+        self.observed.locations.retain(|node_id, observed_loc| {
+            observed_loc.conf.is_some()
+                || intent.references(*node_id)
+                || nodes
+                    .get(node_id)
+                    .map(|n| n.is_available())
+                    .unwrap_or(false)
+        });
+
+        before - self.observed.locations.len()
+    }
+
+    /// Find an observed location holding a newer generation than the latest one we know of.  Only
+    /// this controller issues generations, so this indicates a serious inconsistency, such as
+    /// another controller managing the same pageservers.
+    pub(crate) fn find_generation_violation(&self) -> Option<(NodeId, Generation)> {
+        let generation = self.generation?;
+
+        self.observed.locations.iter().find_map(|(node_id, loc)| {
+            let observed_generation = Generation::new(loc.conf.as_ref()?.generation?);
+            (observed_generation > generation).then_some((*node_id, observed_generation))
+        })
+    }
+
+    /// [`ShardSchedulingPolicy::Pause`] only freezes the intent: the shard is still reconciled
+    /// towards it, e.g. to re-notify computes or clean up after a failed reconcile.
+    /// [`ShardSchedulingPolicy::Stop`] is a full freeze: no reconciles are started, and any
+    /// in-flight reconciler is cancelled so that it makes no further pageserver API calls.
     pub(crate) fn set_scheduling_policy(&mut self, p: ShardSchedulingPolicy) {
         self.scheduling_policy = p;
+
+        if matches!(p, ShardSchedulingPolicy::Stop) {
+            self.cancel_reconciler();
+        }
+    }
+
+    /// Cancel any in-flight reconciler, without modifying intent: the shard remains dirty
+    /// and will be reconciled again later unless its scheduling policy prevents it.
+    ///
+    /// Returns true if there was a reconciler to cancel.
+    pub(crate) fn cancel_reconciler(&self) -> bool {
+        if let Some(handle) = &self.reconciler {
+            tracing::info!(
+                "Cancelling in-flight reconciler for sequence {:?}",
+                handle.sequence
+            );
+            handle.cancel.cancel();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record a generation newly issued for this shard, e.g. by incrementing it in the database.
+    /// Any reconciler still running with an older generation will refuse to attach with it.
+    pub(crate) fn advance_generation(&mut self, generation: Generation) {
+        self.generation = std::cmp::max(self.generation, Some(generation));
+        self.generation_floor.raise(generation);
+    }
+
+    /// Support/debug tool: set the generation even if it is older than the current one.  The
+    /// caller must make sure that no reconciler is running.
+    pub(crate) fn override_generation(&mut self, generation: Generation) {
+        self.generation = Some(generation);
+        self.generation_floor.reset(self.generation);
+    }
+
+    /// Like [`Self::cancel_reconciler`], but return a future that completes once the cancelled
+    /// reconciler's task has exited, and can no longer make requests to pageservers, and its result
+    /// has been applied.  A reconciler waits for its predecessor before starting, so this covers
+    /// those too.
+    pub(crate) fn cancel_reconciler_and_wait(
+        &self,
+    ) -> Option<impl std::future::Future<Output = ()>> {
+        if !self.cancel_reconciler() {
+            return None;
+        }
+
+        let done = self
+            .reconciler
+            .as_ref()
+            .expect("Reconciler was present above")
+            .done
+            .clone();
+        Some(async move { done.cancelled().await })
     }
 
     pub(crate) fn get_scheduling_policy(&self) -> &ShardSchedulingPolicy {
         &self.scheduling_policy
     }
 
+    pub(crate) fn get_flags(&self) -> TenantFlags {
+        TenantFlags {
+            colocate: self.colocate,
+            canary: self.canary,
+        }
+    }
+
+    pub(crate) fn set_flags(&mut self, flags: TenantFlags) {
+        self.colocate = flags.colocate;
+        self.canary = flags.canary;
+    }
+
+    pub(crate) fn set_flag(&mut self, flag: TenantFlag, value: bool) {
+        match flag {
+            TenantFlag::Sticky => self.sticky = value,
+            TenantFlag::Colocate => self.colocate = value,
+            TenantFlag::Canary => self.canary = value,
+        }
+    }
+
     pub(crate) fn set_last_error(&mut self, sequence: Sequence, error: ReconcileError) {
         // Ordering: always set last_error before advancing sequence, so that sequence
         // waiters are guaranteed to see a Some value when they see an error.
@@ -1270,18 +1906,28 @@ impl TenantShard {
             shard: shard_identity,
             sequence: Sequence::initial(),
             generation: tsp.generation.map(|g| Generation::new(g as u32)),
+            generation_floor: Arc::default(),
             policy: serde_json::from_str(&tsp.placement_policy).unwrap(),
             intent,
             observed: ObservedState::new(),
             config: serde_json::from_str(&tsp.config).unwrap(),
             reconciler: None,
             splitting: tsp.splitting,
+            applied_sequence: Sequence::initial(),
             waiter: Arc::new(SeqWait::new(Sequence::initial())),
             error_waiter: Arc::new(SeqWait::new(Sequence::initial())),
             last_error: Arc::default(),
-            pending_compute_notification: false,
+            last_scheduling_error: None,
+            pending_compute_notification: tsp.pending_compute_notification,
+            generation_fenced: false,
             delayed_reconcile: false,
+            delayed_reconcile_since: None,
+            reconcile_failures: ReconcileFailures::default(),
             scheduling_policy: serde_json::from_str(&tsp.scheduling_policy).unwrap(),
+            sticky: tsp.sticky,
+            colocate: tsp.colocate,
+            canary: tsp.canary,
+            priority: serde_json::from_str(&tsp.reconcile_priority).unwrap(),
         })
     }
 
@@ -1297,6 +1943,11 @@ impl TenantShard {
             config: serde_json::to_string(&self.config).unwrap(),
             splitting: SplitState::default(),
             scheduling_policy: serde_json::to_string(&self.scheduling_policy).unwrap(),
+            sticky: self.sticky,
+            colocate: self.colocate,
+            canary: self.canary,
+            pending_compute_notification: self.pending_compute_notification,
+            reconcile_priority: serde_json::to_string(&self.priority).unwrap(),
         }
     }
 }
@@ -1304,7 +1955,7 @@ impl TenantShard {
 #[cfg(test)]
 pub(crate) mod tests {
     use pageserver_api::{
-        controller_api::NodeAvailability,
+        controller_api::{NodeAvailability, UtilizationScore},
         shard::{ShardCount, ShardNumber},
     };
     use utils::id::TenantId;
@@ -1335,7 +1986,10 @@ pub(crate) mod tests {
         )
     }
 
-    fn make_test_tenant(policy: PlacementPolicy, shard_count: ShardCount) -> Vec<TenantShard> {
+    pub(crate) fn make_test_tenant(
+        policy: PlacementPolicy,
+        shard_count: ShardCount,
+    ) -> Vec<TenantShard> {
         let tenant_id = TenantId::generate();
 
         (0..shard_count.count())
@@ -1361,6 +2015,185 @@ pub(crate) mod tests {
             .collect()
     }
 
+    #[test]
+    fn generation_floor() {
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+        let floor = tenant_shard.generation_floor.clone();
+
+        tenant_shard.advance_generation(Generation::new(3));
+        assert_eq!(tenant_shard.generation, Some(Generation::new(3)));
+        assert!(matches!(
+            floor.check(Some(2)),
+            Err(ReconcileError::StaleGeneration {
+                wanted: 2,
+                known: 3
+            })
+        ));
+        assert!(floor.check(Some(3)).is_ok());
+        assert!(floor.check(None).is_ok());
+
+        // Generations never go backwards, unless overridden
+        tenant_shard.advance_generation(Generation::new(1));
+        assert_eq!(tenant_shard.generation, Some(Generation::new(3)));
+        assert!(floor.check(Some(2)).is_err());
+
+        tenant_shard.override_generation(Generation::new(1));
+        assert_eq!(tenant_shard.generation, Some(Generation::new(1)));
+        assert!(floor.check(Some(1)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn cancel_reconciler_and_wait() {
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+        assert!(tenant_shard.cancel_reconciler_and_wait().is_none());
+
+        // Stand in for a reconciler task that only exits once cancelled
+        let cancel = CancellationToken::new();
+        let done = CancellationToken::new();
+        let handle = tokio::task::spawn({
+            let cancel = cancel.clone();
+            let done_guard = done.clone().drop_guard();
+            async move {
+                let _done_guard = done_guard;
+                cancel.cancelled().await;
+            }
+        });
+        tenant_shard.reconciler = Some(ReconcilerHandle {
+            sequence: tenant_shard.sequence,
+            handle,
+            cancel: cancel.clone(),
+            done: done.clone(),
+        });
+
+        let wait = tenant_shard
+            .cancel_reconciler_and_wait()
+            .expect("Reconciler is running");
+        assert!(cancel.is_cancelled());
+        tokio::time::timeout(Duration::from_secs(10), wait)
+            .await
+            .expect("Cancelled reconciler should exit");
+        assert!(done.is_cancelled());
+    }
+
+    #[test]
+    fn intent_changed_at() {
+        let nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let mut context = ScheduleContext::default();
+
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        assert!(tenant_shard.intent.get_changed_at().is_none());
+
+        tenant_shard
+            .schedule(&mut scheduler, &mut context)
+            .expect("we have enough nodes, scheduling should work");
+        let scheduled_at = tenant_shard.intent.get_changed_at().unwrap();
+
+        // Rescheduling a shard that already has all its locations doesn't change its intent
+        tenant_shard
+            .schedule(&mut scheduler, &mut context)
+            .expect("we have enough nodes, scheduling should work");
+        assert_eq!(tenant_shard.intent.get_changed_at(), Some(scheduled_at));
+
+        // Setting the attached location to the one it already has is not a change either
+        let attached = *tenant_shard.intent.get_attached();
+        tenant_shard.intent.set_attached(&mut scheduler, attached);
+        assert_eq!(tenant_shard.intent.get_changed_at(), Some(scheduled_at));
+
+        let attached = attached.unwrap();
+        tenant_shard
+            .intent
+            .demote_attached(&mut scheduler, attached);
+        assert!(tenant_shard.intent.get_changed_at().unwrap() >= scheduled_at);
+
+        tenant_shard.intent.clear(&mut scheduler);
+    }
+
+    #[test]
+    fn redundant_secondaries() {
+        let mut nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let mut context = ScheduleContext::default();
+
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        tenant_shard
+            .schedule(&mut scheduler, &mut context)
+            .expect("we have enough nodes, scheduling should work");
+        let secondary = tenant_shard.intent.get_secondary()[0];
+
+        // A secondary that is intended but not yet reconciled provides no redundancy
+        assert!(tenant_shard.get_redundant_secondaries(&nodes).is_empty());
+
+        // Once it is observed in secondary mode, it does
+        tenant_shard.observed.locations.insert(
+            secondary,
+            ObservedStateLocation {
+                conf: Some(secondary_location_conf(
+                    &tenant_shard.shard,
+                    &tenant_shard.config,
+                )),
+            },
+        );
+        assert_eq!(
+            tenant_shard.get_redundant_secondaries(&nodes),
+            vec![secondary]
+        );
+
+        // ...until its node goes offline
+        nodes
+            .get_mut(&secondary)
+            .unwrap()
+            .set_availability(NodeAvailability::Offline);
+        assert!(tenant_shard.get_redundant_secondaries(&nodes).is_empty());
+
+        tenant_shard.intent.clear(&mut scheduler);
+    }
+
+    #[test]
+    fn last_scheduling_error() {
+        let nodes = make_test_nodes(2);
+        let mut scheduler = Scheduler::new(nodes.values().filter(|n| n.get_id() == NodeId(1)));
+        let mut context = ScheduleContext::default();
+
+        // With one node, there is nowhere to put a secondary location
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        assert!(tenant_shard.schedule(&mut scheduler, &mut context).is_err());
+        assert!(tenant_shard.last_scheduling_error.is_some());
+
+        // Once another node is available, scheduling succeeds and the error is cleared
+        scheduler.node_upsert(nodes.get(&NodeId(2)).unwrap());
+        tenant_shard
+            .schedule(&mut scheduler, &mut context)
+            .expect("we have enough nodes, scheduling should work");
+        assert!(tenant_shard.last_scheduling_error.is_none());
+
+        tenant_shard.intent.clear(&mut scheduler);
+    }
+
+    #[test]
+    fn set_placement() {
+        let nodes = make_test_nodes(3);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        assert!(tenant_shard.set_placement(&mut scheduler, Some(NodeId(1)), &[NodeId(2)]));
+
+        // Setting the same placement again is a no-op
+        assert!(!tenant_shard.set_placement(&mut scheduler, Some(NodeId(1)), &[NodeId(2)]));
+
+        // Swapping the attached and secondary locations
+        assert!(tenant_shard.set_placement(&mut scheduler, Some(NodeId(2)), &[NodeId(1)]));
+        assert_eq!(tenant_shard.intent.get_attached(), &Some(NodeId(2)));
+        assert_eq!(tenant_shard.intent.get_secondary(), &vec![NodeId(1)]);
+
+        // Moving the secondary elsewhere
+        assert!(tenant_shard.set_placement(&mut scheduler, Some(NodeId(2)), &[NodeId(3)]));
+        assert_eq!(tenant_shard.intent.all_pageservers().len(), 2);
+        assert!(!tenant_shard.intent.references(NodeId(1)));
+
+        tenant_shard.intent.clear(&mut scheduler);
+    }
+
     /// Test the scheduling behaviors used when a tenant configured for HA is subject
     /// to nodes being marked offline.
     #[test]
@@ -1492,6 +2325,254 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn stopped_shard_not_reconciled() -> anyhow::Result<()> {
+        let nodes = Arc::new(make_test_nodes(3));
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        tenant_shard
+            .schedule(&mut scheduler, &mut ScheduleContext::default())
+            .expect("we have enough nodes, scheduling should work");
+
+        // Intent has locations but nothing is observed yet: the shard is dirty
+        assert!(matches!(
+            tenant_shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::Yes
+        ));
+
+        // Pause does not prevent reconciling towards the existing intent
+        tenant_shard.set_scheduling_policy(ShardSchedulingPolicy::Pause);
+        assert!(matches!(
+            tenant_shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::Yes
+        ));
+
+        // Stop freezes the shard even though intent and observed differ
+        tenant_shard.set_scheduling_policy(ShardSchedulingPolicy::Stop);
+        assert!(tenant_shard.dirty(&nodes));
+        assert!(matches!(
+            tenant_shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::No
+        ));
+
+        tenant_shard.intent.clear(&mut scheduler);
+        Ok(())
+    }
+
+    #[test]
+    fn colocate_packs_tenant() -> anyhow::Result<()> {
+        let nodes = make_test_nodes(6);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        let mut used_nodes = |colocate: bool| -> anyhow::Result<HashSet<NodeId>> {
+            let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(4));
+            let mut context = ScheduleContext::default();
+            for shard in &mut shards {
+                shard.colocate = colocate;
+                shard.schedule(&mut scheduler, &mut context)?;
+
+                // Anti-affinity between a shard's own locations still applies
+                let attached = shard.intent.get_attached().unwrap();
+                assert!(!shard.intent.get_secondary().contains(&attached));
+            }
+
+            // The optimizer leaves a co-located tenant alone
+            if colocate {
+                assert!(shards
+                    .iter()
+                    .all(|s| s.optimize_attachment(&nodes, &context).is_none()
+                        && s.optimize_secondary(&scheduler, &context).is_none()));
+            }
+
+            let used = shards
+                .iter()
+                .flat_map(|s| s.intent.all_pageservers())
+                .collect::<HashSet<_>>();
+            for mut shard in shards {
+                shard.intent.clear(&mut scheduler);
+            }
+            Ok(used)
+        };
+
+        assert_eq!(used_nodes(true)?.len(), 2);
+        assert!(used_nodes(false)?.len() > 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reconcile_failure_backoff() {
+        let nodes = Arc::new(make_test_nodes(2));
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        // Nothing is observed, so the shard is always dirty
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+        shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+
+        // A reconcile that fails is not retried right away...
+        assert!(matches!(
+            shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::Yes
+        ));
+        assert!(!shard.record_reconcile_failure());
+        assert!(matches!(
+            shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::No
+        ));
+        let first_backoff = shard.reconcile_backoff_remaining().unwrap();
+        assert!(first_backoff <= Duration::from_secs(2));
+
+        // ...and the backoff grows with each consecutive failure, until the shard needs attention
+        for _ in 2..RECONCILE_FAILURES_ATTENTION {
+            assert!(!shard.record_reconcile_failure());
+        }
+        assert_eq!(shard.reconcile_needs_attention(), None);
+        assert!(shard.record_reconcile_failure());
+        assert_eq!(
+            shard.reconcile_needs_attention(),
+            Some(RECONCILE_FAILURES_ATTENTION)
+        );
+        assert!(shard.reconcile_backoff_remaining().unwrap() > first_backoff);
+
+        // The backoff is capped
+        for _ in 0..20 {
+            shard.record_reconcile_failure();
+        }
+        assert!(shard.reconcile_backoff_remaining().unwrap() <= RECONCILE_FAILURE_BACKOFF_MAX);
+        assert!(matches!(
+            shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::No
+        ));
+
+        // Changing the intent resets the failures
+        shard.intent.set_attached(&mut scheduler, Some(NodeId(2)));
+        assert_eq!(shard.reconcile_needs_attention(), None);
+        assert!(matches!(
+            shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::Yes
+        ));
+        assert!(!shard.record_reconcile_failure());
+
+        // So does a successful reconcile
+        for _ in 1..RECONCILE_FAILURES_ATTENTION {
+            shard.record_reconcile_failure();
+        }
+        assert!(shard.reconcile_needs_attention().is_some());
+        shard.record_reconcile_success();
+        assert_eq!(shard.reconcile_needs_attention(), None);
+        assert!(matches!(
+            shard.get_reconcile_needed(&nodes),
+            ReconcileNeeded::Yes
+        ));
+
+        shard.intent.clear(&mut scheduler);
+    }
+
+    #[test]
+    fn prune_observed_after_migrations() -> anyhow::Result<()> {
+        let mut nodes = make_test_nodes(4);
+        let mut scheduler = Scheduler::new(nodes.values());
+
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+
+        for i in 1..=4 {
+            let node_id = NodeId(i);
+            tenant_shard
+                .intent
+                .set_attached(&mut scheduler, Some(node_id));
+            tenant_shard.observed.locations.insert(
+                node_id,
+                ObservedStateLocation {
+                    conf: Some(attached_location_conf(
+                        Generation::new(i as u32),
+                        &tenant_shard.shard,
+                        &tenant_shard.config,
+                        &tenant_shard.policy,
+                    )),
+                },
+            );
+
+            if i > 1 {
+                // The previous location went offline while we were detaching from it, leaving
+                // an unknown observed state behind.
+                let prev_node_id = NodeId(i - 1);
+                tenant_shard
+                    .observed
+                    .locations
+                    .insert(prev_node_id, ObservedStateLocation { conf: None });
+                nodes
+                    .get_mut(&prev_node_id)
+                    .unwrap()
+                    .set_availability(NodeAvailability::Offline);
+
+                assert_eq!(tenant_shard.prune_observed(&nodes), 1);
+            }
+
+            // Only the current attached location remains in observed state
+            assert_eq!(
+                tenant_shard.observed.locations.keys().collect::<Vec<_>>(),
+                vec![&node_id]
+            );
+        }
+
+        // Unknown states on available nodes are retained, so that a reconcile can detach them
+        tenant_shard
+            .observed
+            .locations
+            .insert(NodeId(1), ObservedStateLocation { conf: None });
+        nodes
+            .get_mut(&NodeId(1))
+            .unwrap()
+            .set_availability(NodeAvailability::Active(UtilizationScore::worst()));
+        assert_eq!(tenant_shard.prune_observed(&nodes), 0);
+        assert_eq!(tenant_shard.observed.locations.len(), 2);
+
+        tenant_shard.intent.clear(&mut scheduler);
+        Ok(())
+    }
+
+    #[test]
+    fn generation_violation() -> anyhow::Result<()> {
+        let mut tenant_shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+        tenant_shard.generation = Some(Generation::new(2));
+
+        let attached_conf = |generation: u32| ObservedStateLocation {
+            conf: Some(LocationConfig {
+                mode: LocationConfigMode::AttachedSingle,
+                generation: Some(generation),
+                secondary_conf: None,
+                shard_number: 0,
+                shard_count: 0,
+                shard_stripe_size: 0,
+                tenant_conf: TenantConfig::default(),
+            }),
+        };
+
+        // Older and current generations are fine
+        tenant_shard
+            .observed
+            .locations
+            .insert(NodeId(1), attached_conf(1));
+        tenant_shard
+            .observed
+            .locations
+            .insert(NodeId(2), attached_conf(2));
+        assert_eq!(tenant_shard.find_generation_violation(), None);
+
+        // A generation that we never issued is a violation
+        tenant_shard
+            .observed
+            .locations
+            .insert(NodeId(3), attached_conf(3));
+        assert_eq!(
+            tenant_shard.find_generation_violation(),
+            Some((NodeId(3), Generation::new(3)))
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn optimize_attachment() -> anyhow::Result<()> {
         let nodes = make_test_nodes(3);
@@ -1650,6 +2731,204 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn evacuate_node() -> anyhow::Result<()> {
+        let nodes = make_test_nodes(4);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let context = ScheduleContext::default();
+
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        shard.intent.set_attached(&mut scheduler, Some(NodeId(1)));
+        shard.intent.push_secondary(&mut scheduler, NodeId(2));
+
+        // Evacuating a node the shard doesn't use is a no-op
+        assert!(!shard.evacuate_node(&mut scheduler, NodeId(3), &context)?);
+
+        // Evacuating the attached node cuts over to the secondary, and a new secondary
+        // is created somewhere other than the evacuated node.
+        assert!(shard.evacuate_node(&mut scheduler, NodeId(1), &context)?);
+        assert_eq!(*shard.intent.get_attached(), Some(NodeId(2)));
+        assert_eq!(shard.intent.get_secondary().len(), 1);
+        let secondary = shard.intent.get_secondary()[0];
+        assert!(secondary != NodeId(1) && secondary != NodeId(2));
+        assert_eq!(scheduler.get_node_shard_count(NodeId(1)), 0);
+
+        // Evacuating the secondary's node replaces it
+        assert!(shard.evacuate_node(&mut scheduler, secondary, &context)?);
+        assert_eq!(*shard.intent.get_attached(), Some(NodeId(2)));
+        assert_eq!(shard.intent.get_secondary().len(), 1);
+        assert!(!shard.intent.all_pageservers()[1..].contains(&secondary));
+        assert_eq!(scheduler.get_node_shard_count(secondary), 0);
+
+        shard.intent.clear(&mut scheduler);
+
+        Ok(())
+    }
+
+    /// The secondary count in [`PlacementPolicy::Attached`] is independent of there being a single
+    /// attached location: a shard may have more secondaries than attached locations.
+    #[test]
+    fn multiple_secondaries() -> anyhow::Result<()> {
+        let nodes = make_test_nodes(4);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let mut context = ScheduleContext::default();
+
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(2));
+        shard.schedule(&mut scheduler, &mut context)?;
+
+        let attached = shard.intent.get_attached().unwrap();
+        let secondary = shard.intent.get_secondary().clone();
+        assert_eq!(secondary.len(), 2);
+        assert!(!secondary.contains(&attached));
+        assert_ne!(secondary[0], secondary[1]);
+
+        // Optimization leaves the secondary count alone
+        optimize_til_idle(&nodes, &mut scheduler, std::slice::from_mut(&mut shard));
+        assert!(shard.intent.get_attached().is_some());
+        assert_eq!(shard.intent.get_secondary().len(), 2);
+
+        shard.intent.clear(&mut scheduler);
+
+        Ok(())
+    }
+
+    #[test]
+    fn out_of_order_results() {
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+
+        // A result from a newer reconciler arrives first
+        assert!(shard.advance_applied_sequence(Sequence(3)));
+
+        // A result from an older reconciler arrives later and is stale
+        assert!(!shard.advance_applied_sequence(Sequence(2)));
+        assert_eq!(shard.applied_sequence, Sequence(3));
+
+        // Results for the same or newer sequences are applied
+        assert!(shard.advance_applied_sequence(Sequence(3)));
+        assert!(shard.advance_applied_sequence(Sequence(4)));
+        assert_eq!(shard.applied_sequence, Sequence(4));
+    }
+
+    #[test]
+    fn out_of_order_results_state() {
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(0));
+        let tenant_shard_id = shard.tenant_shard_id;
+
+        let make_result = |sequence, result, node_id, pending_compute_notification| {
+            let mut observed = ObservedState::new();
+            observed
+                .locations
+                .insert(NodeId(node_id), ObservedStateLocation { conf: None });
+            ReconcileResult {
+                sequence: Sequence(sequence),
+                result,
+                tenant_shard_id,
+                generation: Some(Generation::new(sequence as u32)),
+                observed,
+                pending_compute_notification,
+                duration: Duration::ZERO,
+                _done_guard: CancellationToken::new().drop_guard(),
+            }
+        };
+        let newer = make_result(3, Ok(()), 2, false);
+        let older = make_result(2, Err(ReconcileError::Cancel), 1, true);
+
+        // Apply results the way Service::process_result does
+        let apply = |shard: &mut TenantShard, result: ReconcileResult| {
+            let complete = result.result.is_ok();
+            if shard.accept_reconcile_result(&result) {
+                shard.apply_observed(result.observed, complete);
+            }
+        };
+
+        // The newer reconciler's result arrives first and is applied
+        apply(&mut shard, newer);
+        assert!(shard.observed.locations.contains_key(&NodeId(2)));
+        assert!(!shard.pending_compute_notification);
+
+        // The older one arrives later: it must regress neither observed state nor the
+        // pending compute notification
+        apply(&mut shard, older);
+        assert_eq!(
+            shard.observed.locations.keys().collect::<Vec<_>>(),
+            vec![&NodeId(2)]
+        );
+        assert!(!shard.pending_compute_notification);
+        assert_eq!(shard.applied_sequence, Sequence(3));
+        assert_eq!(shard.generation, Some(Generation::new(3)));
+    }
+
+    #[test]
+    fn read_replicas() -> anyhow::Result<()> {
+        let nodes = make_test_nodes(4);
+        let mut scheduler = Scheduler::new(nodes.values());
+        let mut context = ScheduleContext::default();
+
+        let mut shard = make_test_tenant_shard(PlacementPolicy::Attached(1));
+        shard.schedule(&mut scheduler, &mut context)?;
+        let replica = scheduler.schedule_shard(&shard.intent.all_pageservers(), &context)?;
+        shard.intent.push_read_replica(&mut scheduler, replica);
+        assert_eq!(scheduler.get_node_shard_count(replica), 1);
+        assert!(shard.intent.references(replica));
+
+        // Scheduling again leaves the replica alone, and doesn't put a secondary on its node
+        shard.intent.clear_secondary(&mut scheduler);
+        shard.schedule(&mut scheduler, &mut context)?;
+        assert_eq!(shard.intent.get_read_replicas(), &vec![replica]);
+        assert_eq!(shard.intent.get_secondary().len(), 1);
+        assert!(!shard.intent.get_secondary().contains(&replica));
+
+        // Replicas are dropped when the shard is no longer attached
+        shard.policy = PlacementPolicy::Secondary;
+        shard.schedule(&mut scheduler, &mut context)?;
+        assert!(shard.intent.get_read_replicas().is_empty());
+        assert_eq!(scheduler.get_node_shard_count(replica), 0);
+
+        shard.intent.clear(&mut scheduler);
+
+        Ok(())
+    }
+
+    /// A sticky shard keeps its locations when the optimizer moves its siblings onto new nodes.
+    #[test]
+    fn optimize_skips_sticky() -> anyhow::Result<()> {
+        let nodes = make_test_nodes(4);
+
+        let mut scheduler = Scheduler::new([].iter());
+        scheduler.node_upsert(nodes.get(&NodeId(1)).unwrap());
+        scheduler.node_upsert(nodes.get(&NodeId(2)).unwrap());
+
+        let mut shards = make_test_tenant(PlacementPolicy::Attached(1), ShardCount::new(4));
+        let mut schedule_context = ScheduleContext::default();
+        for shard in &mut shards {
+            assert!(shard
+                .schedule(&mut scheduler, &mut schedule_context)
+                .is_ok());
+        }
+
+        shards[0].sticky = true;
+        let sticky_attached = *shards[0].intent.get_attached();
+        let sticky_secondary = shards[0].intent.get_secondary().clone();
+
+        // Adding nodes gives the optimizer somewhere better to put every shard
+        scheduler.node_upsert(nodes.get(&NodeId(3)).unwrap());
+        scheduler.node_upsert(nodes.get(&NodeId(4)).unwrap());
+        optimize_til_idle(&nodes, &mut scheduler, &mut shards);
+
+        assert_eq!(*shards[0].intent.get_attached(), sticky_attached);
+        assert_eq!(shards[0].intent.get_secondary(), &sticky_secondary);
+
+        // The non-sticky shards were still spread out onto the new nodes
+        assert!(scheduler.get_node_shard_count(NodeId(3)) > 0);
+        assert!(scheduler.get_node_shard_count(NodeId(4)) > 0);
+
+        for shard in shards.iter_mut() {
+            shard.intent.clear(&mut scheduler);
+        }
+
+        Ok(())
+    }
+
     /// Test the balancing behavior of shard scheduling: that it achieves a balance, and
     /// that it converges.
     #[test]
@@ -2110,10 +2110,12 @@ class LogUtils:
 
 
 class StorageControllerApiException(Exception):
-    def __init__(self, message, status_code: int):
+    def __init__(self, message, status_code: int, code: Optional[str] = None):
         super().__init__(message)
         self.message = message
         self.status_code = status_code
+        # Machine-readable error code, e.g. for failed requests to pageservers
+        self.code = code
 
 
 class NeonStorageController(MetricsGetter, LogUtils):
@@ -2156,11 +2158,14 @@ class NeonStorageController(MetricsGetter, LogUtils):
         try:
             res.raise_for_status()
         except requests.RequestException as e:
+            code = None
             try:
-                msg = res.json()["msg"]
+                body = res.json()
+                msg = body["msg"]
+                code = body.get("code")
             except:  # noqa: E722
                 msg = ""
-            raise StorageControllerApiException(msg, res.status_code) from e
+            raise StorageControllerApiException(msg, res.status_code, code) from e
 
     def assert_no_errors(self):
         assert_no_errors(
@@ -2266,6 +2271,29 @@ class NeonStorageController(MetricsGetter, LogUtils):
         else:
             return None
 
+    def inspect_shard(self, tenant_shard_id: TenantShardId) -> dict[str, Any]:
+        """
+        :return: the full inspect response, including the shard's observed state
+        """
+        response = self.request(
+            "POST",
+            f"{self.env.storage_controller_api}/debug/v1/inspect",
+            json={"tenant_shard_id": str(tenant_shard_id)},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
+    def detect_drift(self) -> dict[str, Any]:
+        """
+        :return: locations where pageservers differ from the storage controller's observed state
+        """
+        response = self.request(
+            "GET",
+            f"{self.env.storage_controller_api}/debug/v1/drift",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def node_register(self, node: NeonPageserver):
         body = {
             "node_id": int(node.id),
@@ -2290,6 +2318,46 @@ class NeonStorageController(MetricsGetter, LogUtils):
             headers=self.headers(TokenScope.ADMIN),
         )
 
+    def node_cordon(self, node_id):
+        log.info(f"node_cordon({node_id})")
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/node/{node_id}/cordon",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+
+    def node_uncordon(self, node_id):
+        log.info(f"node_uncordon({node_id})")
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/node/{node_id}/uncordon",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+
+    def node_delete(self, node_id):
+        """
+        Move everything off the node, detach what is left on it, then remove it
+        """
+        log.info(f"node_delete({node_id})")
+        self.request(
+            "DELETE",
+            f"{self.env.storage_controller_api}/control/v1/node/{node_id}",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+
+    def node_migrate_tenants(self, node_id, tenant_ids: list[TenantId]) -> dict[str, Any]:
+        """
+        Move the listed tenants' attachments off the node, without draining it
+        """
+        log.info(f"node_migrate_tenants({node_id}, {tenant_ids})")
+        response = self.request(
+            "POST",
+            f"{self.env.storage_controller_api}/control/v1/node/{node_id}/migrate_tenants",
+            json={"tenant_ids": [str(t) for t in tenant_ids]},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def cancel_node_drain(self, node_id):
         log.info(f"cancel_node_drain({node_id})")
         self.request(
@@ -2322,6 +2390,37 @@ class NeonStorageController(MetricsGetter, LogUtils):
         )
         return response.json()
 
+    def node_history(self, node_id) -> list[dict[str, Any]]:
+        """
+        Recent changes to the node's availability and scheduling policy, oldest first
+        """
+        response = self.request(
+            "GET",
+            f"{self.env.storage_controller_api}/control/v1/node/{node_id}/history",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()["transitions"]
+
+    def autosplit_config(self) -> dict[str, Any]:
+        response = self.request(
+            "GET",
+            f"{self.env.storage_controller_api}/control/v1/autosplit",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
+    def update_autosplit_config(self, split_threshold: Optional[int], split_to: int):
+        """
+        Change auto-splitting settings without restarting the storage controller
+        """
+        response = self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/autosplit",
+            json={"split_threshold": split_threshold, "split_to": split_to},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def node_list(self):
         response = self.request(
             "GET",
@@ -2338,6 +2437,14 @@ class NeonStorageController(MetricsGetter, LogUtils):
         )
         return response.json()
 
+    def tenant_summary(self) -> dict[str, Any]:
+        response = self.request(
+            "GET",
+            f"{self.env.storage_controller_api}/control/v1/tenant_summary",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def node_configure(self, node_id, body: dict[str, Any]):
         log.info(f"node_configure({node_id}, {body})")
         body["node_id"] = node_id
@@ -2398,6 +2505,20 @@ class NeonStorageController(MetricsGetter, LogUtils):
         shards: list[dict[str, Any]] = body["shards"]
         return shards
 
+    def tenant_set_placement(self, tenant_id: TenantId, shards: list[dict[str, Any]]):
+        """
+        Move the tenant's shards to the given locations.  Each item of `shards` is of the form
+        {"tenant_shard_id": str, "attached": int, "secondaries": [int]}.
+        """
+        log.info(f"tenant_set_placement({tenant_id}, {shards})")
+        response = self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_id}/placement",
+            json={"shards": shards},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def tenant_describe(self, tenant_id: TenantId):
         """
         :return: list of {"shard_id": "", "node_id": int, "listen_pg_addr": str, "listen_pg_port": int, "listen_http_addr: str, "listen_http_port: int}
@@ -2424,16 +2545,74 @@ class NeonStorageController(MetricsGetter, LogUtils):
         shards: list[TenantShardId] = body["new_shards"]
         return shards
 
-    def tenant_shard_migrate(self, tenant_shard_id: TenantShardId, dest_ps_id: int):
+    def tenant_shard_migrate(
+        self, tenant_shard_id: TenantShardId, dest_ps_id: int, attach_detached: bool = False
+    ):
         self.request(
             "PUT",
             f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_shard_id}/migrate",
-            json={"tenant_shard_id": str(tenant_shard_id), "node_id": dest_ps_id},
+            json={
+                "tenant_shard_id": str(tenant_shard_id),
+                "node_id": dest_ps_id,
+                "migration_config": {"attach_detached": attach_detached},
+            },
             headers=self.headers(TokenScope.ADMIN),
         )
         log.info(f"Migrated tenant {tenant_shard_id} to pageserver {dest_ps_id}")
         assert self.env.get_tenant_pageserver(tenant_shard_id).id == dest_ps_id
 
+    def tenant_shard_migrate_secondary(
+        self, tenant_shard_id: TenantShardId, from_ps_id: int, to_ps_id: int
+    ):
+        api = self.env.storage_controller_api
+        self.request(
+            "PUT",
+            f"{api}/control/v1/tenant/{tenant_shard_id}/migrate_secondary",
+            json={"from_node_id": from_ps_id, "to_node_id": to_ps_id},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        log.info(f"Migrated {tenant_shard_id} secondary from {from_ps_id} to {to_ps_id}")
+
+    def tenant_shard_cancel_reconcile(self, tenant_shard_id: TenantShardId) -> dict[str, Any]:
+        api = self.env.storage_controller_api
+        response = self.request(
+            "PUT",
+            f"{api}/control/v1/tenant/{tenant_shard_id}/cancel_reconcile",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        log.info(f"Cancelled reconcile for {tenant_shard_id}: {response.json()}")
+        return response.json()
+
+    def tenant_shard_increment_generation(self, tenant_shard_id: TenantShardId) -> dict[str, Any]:
+        api = self.env.storage_controller_api
+        response = self.request(
+            "PUT",
+            f"{api}/control/v1/tenant/{tenant_shard_id}/increment_generation",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        log.info(f"Incremented generation for {tenant_shard_id}: {response.json()}")
+        return response.json()
+
+    def tenant_shard_set_generation(
+        self, tenant_shard_id: TenantShardId, generation: int, force: bool = False
+    ):
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/debug/v1/tenant/{tenant_shard_id}/generation",
+            json={"generation": generation, "force": force},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        log.info(f"Set generation {generation} (force={force}) for {tenant_shard_id}")
+
+    def tenant_generation_freeze(self, tenant_id: TenantId, frozen: bool):
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_id}/generation_freeze",
+            json={"frozen": frozen},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        log.info(f"Set generation frozen={frozen} for {tenant_id}")
+
     def tenant_policy_update(self, tenant_id: TenantId, body: dict[str, Any]):
         log.info(f"tenant_policy_update({tenant_id}, {body})")
         self.request(
@@ -2443,6 +2622,39 @@ class NeonStorageController(MetricsGetter, LogUtils):
             headers=self.headers(TokenScope.ADMIN),
         )
 
+    def tenant_shard_detach(self, tenant_shard_id: TenantShardId, force: bool = False):
+        log.info(f"tenant_shard_detach({tenant_shard_id}, force={force})")
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_shard_id}/detach",
+            json={"force": force},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+
+    def tenant_shard_reattach(self, tenant_shard_id: TenantShardId):
+        log.info(f"tenant_shard_reattach({tenant_shard_id})")
+        self.request(
+            "PUT",
+            f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_shard_id}/reattach",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+
+    def tenant_reconcile_watch(
+        self, tenant_id: TenantId, wait_ms: Optional[int] = None
+    ) -> list[dict[str, Any]]:
+        """
+        If any of the tenant's shards are reconciling, wait for one of them to finish (or for
+        `wait_ms` to elapse), then return the reconcile status of every shard.
+        """
+        params = {"wait_ms": wait_ms} if wait_ms is not None else None
+        response = self.request(
+            "GET",
+            f"{self.env.storage_controller_api}/control/v1/tenant/{tenant_id}/reconcile_watch",
+            params=params,
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()["shards"]
+
     def tenant_import(self, tenant_id: TenantId):
         self.request(
             "POST",
@@ -2491,6 +2703,18 @@ class NeonStorageController(MetricsGetter, LogUtils):
         )
         log.info("storage controller passed consistency check")
 
+    def consistency_check_relaxed(self) -> dict[str, Any]:
+        """
+        Like consistency_check, but skips anything that is busy rather than failing: returns
+        how many nodes and shards were checked, and which were skipped.
+        """
+        response = self.request(
+            "POST",
+            f"{self.env.storage_controller_api}/debug/v1/consistency_check_relaxed",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        return response.json()
+
     def poll_node_status(
         self, node_id: int, desired_scheduling_policy: str, max_attempts: int, backoff: int
     ):
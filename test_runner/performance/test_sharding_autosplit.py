@@ -208,7 +208,9 @@ def test_sharding_autosplit(neon_env_builder: NeonEnvBuilder, pg_bin: PgBin):
     def assert_all_split():
         for tenant_id in tenants.keys():
             shards = tenant_get_shards(env, tenant_id)
-            assert len(shards) == 8
+            # The controller picks a shard count based on the tenant's size, so we only check
+            # that a split happened.
+            assert len(shards) > 1
 
     # This is not a wait_until, because we wanted the splits to happen _while_ pgbench is running: otherwise
     # this test is not properly doing its job of validating that splits work nicely under load.
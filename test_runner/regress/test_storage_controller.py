@@ -16,7 +16,7 @@ from fixtures.neon_fixtures import (
     StorageControllerApiException,
     TokenScope,
 )
-from fixtures.pageserver.http import PageserverHttpClient
+from fixtures.pageserver.http import PageserverApiException, PageserverHttpClient
 from fixtures.pageserver.utils import (
     MANY_SMALL_LAYERS_TENANT_CONFIG,
     assert_prefix_empty,
@@ -174,6 +174,484 @@ def test_storage_controller_smoke(
     env.storage_controller.consistency_check()
 
 
+def test_storage_controller_consistency_check_relaxed(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that the relaxed consistency check can run while shards are reconciling, and checks
+    everything once they are done.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_ids = [TenantId.generate() for _ in range(0, 4)]
+    for tenant_id in tenant_ids:
+        env.storage_controller.tenant_create(tenant_id, shard_count=2)
+
+    # Migrate shards back and forth, so that some are likely to be reconciling as we check
+    for tenant_id in tenant_ids:
+        for shard in env.storage_controller.locate(tenant_id):
+            tenant_shard_id = TenantShardId.parse(shard["shard_id"])
+            dest = [p.id for p in env.pageservers if p.id != shard["node_id"]][0]
+            env.storage_controller.tenant_shard_migrate(tenant_shard_id, dest)
+        result = env.storage_controller.consistency_check_relaxed()
+        assert result["checked_shards"] + len(result["skipped_shards"]) >= len(tenant_ids) * 2
+
+    env.storage_controller.reconcile_until_idle()
+    result = env.storage_controller.consistency_check_relaxed()
+    assert result == {
+        "checked_nodes": 2,
+        "skipped_nodes": [],
+        "checked_shards": len(tenant_ids) * 2,
+        "skipped_shards": [],
+    }
+
+    # The strict check agrees, now that nothing is reconciling
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_node_delete(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that deleting a node moves its shards elsewhere and detaches them from the
+    pageserver, and that deletion is refused when there is nowhere to move them to.
+    """
+    neon_env_builder.num_pageservers = 3
+    env = neon_env_builder.init_start()
+
+    tenant_ids = [TenantId.generate() for _ in range(0, 4)]
+    for tenant_id in tenant_ids:
+        env.storage_controller.tenant_create(
+            tenant_id, shard_count=2, placement_policy={"Attached": 1}
+        )
+    env.storage_controller.reconcile_until_idle()
+
+    victim = env.pageservers[0]
+    assert len(victim.http_client().tenant_list_locations()["tenant_shards"]) > 0
+
+    env.storage_controller.node_delete(victim.id)
+
+    assert victim.id not in [n["id"] for n in env.storage_controller.node_list()]
+    assert victim.http_client().tenant_list_locations()["tenant_shards"] == []
+    for tenant_id in tenant_ids:
+        for shard in env.storage_controller.tenant_describe(tenant_id)["shards"]:
+            assert victim.id not in [shard["node_attached"], *shard["node_secondary"]]
+    env.storage_controller.consistency_check()
+
+    # With two nodes left, each shard's attached and secondary locations fill them both
+    with pytest.raises(StorageControllerApiException, match="nowhere to move to"):
+        env.storage_controller.node_delete(env.pageservers[1].id)
+    assert len(env.storage_controller.node_list()) == 2
+
+
+def test_storage_controller_node_cordon(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that a cordoned node keeps the shards it already has but is not given new ones,
+    and that uncordoning it makes it schedulable again.
+    """
+    neon_env_builder.num_pageservers = 3
+    env = neon_env_builder.init_start()
+
+    existing = TenantId.generate()
+    env.storage_controller.tenant_create(existing, shard_count=3)
+    env.storage_controller.reconcile_until_idle()
+
+    victim = env.pageservers[0]
+    before = victim.http_client().tenant_list_locations()["tenant_shards"]
+    assert len(before) > 0
+
+    env.storage_controller.node_cordon(victim.id)
+    assert env.storage_controller.node_status(victim.id)["scheduling"] == "Pause"
+
+    tenant_ids = [TenantId.generate() for _ in range(0, 4)]
+    for tenant_id in tenant_ids:
+        env.storage_controller.tenant_create(tenant_id, shard_count=2)
+    env.storage_controller.reconcile_until_idle()
+
+    for tenant_id in tenant_ids:
+        for shard in env.storage_controller.tenant_describe(tenant_id)["shards"]:
+            assert victim.id not in [shard["node_attached"], *shard["node_secondary"]]
+
+    # Unlike a drain, cordoning leaves existing locations where they are
+    after = victim.http_client().tenant_list_locations()["tenant_shards"]
+    assert sorted(s[0] for s in after) == sorted(s[0] for s in before)
+
+    env.storage_controller.node_uncordon(victim.id)
+    assert env.storage_controller.node_status(victim.id)["scheduling"] == "Active"
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_tenant_summary(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that the tenant summary's counts agree with the full tenant listing.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_ids = [env.initial_tenant, TenantId.generate(), TenantId.generate()]
+    env.storage_controller.tenant_create(tenant_ids[1], shard_count=4)
+    env.storage_controller.tenant_create(tenant_ids[2])
+    env.storage_controller.reconcile_until_idle()
+
+    shards = [
+        s for t in tenant_ids for s in env.storage_controller.tenant_describe(t)["shards"]
+    ]
+    summary = env.storage_controller.tenant_summary()
+    assert summary["tenant_count"] == 3
+    assert summary["shard_count"] == len(shards) == 6
+    assert summary["reconciling_shard_count"] == 0
+    assert summary["splitting_shard_count"] == 0
+    assert summary["errored_shard_count"] == 0
+
+    attached = summary["node_attached_shards"]
+    assert sorted(attached.keys()) == sorted(str(ps.id) for ps in env.pageservers)
+    for ps in env.pageservers:
+        assert attached[str(ps.id)] == len([s for s in shards if s["node_attached"] == ps.id])
+
+
+def test_storage_controller_migrate_detached(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that migrating a shard of a detached tenant is refused, unless asked to attach it,
+    in which case the tenant's policy is updated and the shard lands on the chosen node.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id)
+    env.storage_controller.tenant_policy_update(tenant_id, {"placement": "Detached"})
+    env.storage_controller.reconcile_until_idle()
+
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    dest = env.pageservers[1].id
+    with pytest.raises(StorageControllerApiException, match="Detached"):
+        env.storage_controller.tenant_shard_migrate(tenant_shard_id, dest)
+
+    env.storage_controller.tenant_shard_migrate(tenant_shard_id, dest, attach_detached=True)
+    assert env.storage_controller.tenant_describe(tenant_id)["policy"] == {"Attached": 0}
+
+    # The policy change was persisted
+    env.storage_controller.stop()
+    env.storage_controller.start()
+    assert env.storage_controller.tenant_describe(tenant_id)["policy"] == {"Attached": 0}
+    assert env.storage_controller.tenant_describe(tenant_id)["shards"][0]["node_attached"] == dest
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_reconcile_priority(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that a tenant's reconcile priority is set on all its shards, persisted, and inherited
+    by the children of a split.
+    """
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id, shard_count=2)
+
+    def priorities():
+        shards = env.storage_controller.tenant_describe(tenant_id)["shards"]
+        return [s["priority"] for s in shards]
+
+    assert priorities() == ["Normal", "Normal"]
+
+    env.storage_controller.tenant_policy_update(tenant_id, {"priority": "High"})
+    assert priorities() == ["High", "High"]
+
+    # The priority was persisted
+    env.storage_controller.stop()
+    env.storage_controller.start()
+    assert priorities() == ["High", "High"]
+
+    env.storage_controller.tenant_shard_split(tenant_id, shard_count=4)
+    assert priorities() == ["High"] * 4
+
+    # Other policies are left alone when only the priority changes, and vice versa
+    env.storage_controller.tenant_policy_update(tenant_id, {"priority": "Low"})
+    env.storage_controller.tenant_policy_update(tenant_id, {"scheduling": "Essential"})
+    assert priorities() == ["Low"] * 4
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_inspect_shard(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that inspecting a shard reports its intent alongside what was observed on each node.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id, placement_policy={"Attached": 1})
+    env.storage_controller.reconcile_until_idle()
+
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    shard = env.storage_controller.tenant_describe(tenant_id)["shards"][0]
+    inspect = env.storage_controller.inspect_shard(tenant_shard_id)
+    assert inspect["attachment"][1] == shard["node_attached"]
+    assert inspect["secondaries"] == shard["node_secondary"]
+    assert inspect["scheduling_policy"] == "Active"
+    assert inspect["is_reconciling"] is False
+
+    modes = {o["node_id"]: o["conf"]["mode"] for o in inspect["observed"]}
+    assert modes == {
+        shard["node_attached"]: "AttachedSingle",
+        shard["node_secondary"][0]: "Secondary",
+    }
+
+    unknown = env.storage_controller.inspect_shard(TenantShardId(TenantId.generate(), 0, 0))
+    assert unknown["attachment"] is None
+    assert unknown["observed"] == []
+    assert unknown["scheduling_policy"] is None
+
+
+def test_storage_controller_detect_drift(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that locations changed on a pageserver behind the storage controller's back are
+    reported as drift, without the storage controller acting on it.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id, placement_policy={"Attached": 1})
+    env.storage_controller.reconcile_until_idle()
+
+    report = env.storage_controller.detect_drift()
+    assert report["nodes_scanned"] == 2
+    assert report["drift"] == []
+
+    shard = env.storage_controller.tenant_describe(tenant_id)["shards"][0]
+    secondary_id = shard["node_secondary"][0]
+    env.get_pageserver(secondary_id).http_client().tenant_location_conf(
+        tenant_id,
+        {
+            "mode": "Detached",
+            "secondary_conf": None,
+            "tenant_conf": {},
+            "generation": None,
+        },
+    )
+
+    report = env.storage_controller.detect_drift()
+    assert len(report["drift"]) == 1
+    drift = report["drift"][0]
+    assert drift["tenant_shard_id"] == str(TenantShardId(tenant_id, 0, 0))
+    assert drift["node_id"] == secondary_id
+    assert drift["kind"] == "Missing"
+    assert drift["observed"]["mode"] == "Secondary"
+    assert drift["reported"] is None
+
+    # The audit is read-only: the drift is still there
+    assert len(env.storage_controller.detect_drift()["drift"]) == 1
+
+
+def test_storage_controller_timeline_create_retry(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that retrying a timeline creation which only reached shard zero reuses the timeline
+    there (pageserver timeline creation is idempotent), so that all shards branch at the LSN shard
+    zero picked, and that a retry with different parameters is refused.
+    """
+    env = neon_env_builder.init_start()
+    virtual_ps_http = env.storage_controller.pageserver_api()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id, shard_count=2)
+    parent_id = TimelineId.generate()
+    virtual_ps_http.timeline_create(PgVersion.NOT_SET, tenant_id, parent_id)
+
+    # Simulate an earlier attempt that failed after creating the branch on shard zero only
+    shard_zero = TenantShardId(tenant_id, 0, 2)
+    timeline_id = TimelineId.generate()
+    ps_http = env.get_tenant_pageserver(shard_zero).http_client()
+    first = ps_http.timeline_create(
+        PgVersion.NOT_SET, shard_zero, timeline_id, ancestor_timeline_id=parent_id
+    )
+
+    virtual_ps_http.timeline_create(
+        PgVersion.NOT_SET, tenant_id, timeline_id, ancestor_timeline_id=parent_id
+    )
+    shard_one = TenantShardId(tenant_id, 1, 2)
+    ps_http = env.get_tenant_pageserver(shard_one).http_client()
+    detail = ps_http.timeline_detail(shard_one, timeline_id)
+    assert detail["ancestor_lsn"] == first["ancestor_lsn"]
+
+    with pytest.raises(PageserverApiException, match="different parameters"):
+        virtual_ps_http.timeline_create(PgVersion.NOT_SET, tenant_id, timeline_id)
+
+
+def test_storage_controller_migrate_secondary(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that a shard's secondary location can be moved to a chosen node, and that moves
+    which don't start from a secondary or would land on the attached location are refused.
+    """
+    neon_env_builder.num_pageservers = 3
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id, placement_policy={"Attached": 1})
+    env.storage_controller.reconcile_until_idle()
+
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    shard = env.storage_controller.tenant_describe(tenant_id)["shards"][0]
+    attached = shard["node_attached"]
+    secondary = shard["node_secondary"][0]
+    spare = [p.id for p in env.pageservers if p.id not in (attached, secondary)][0]
+
+    with pytest.raises(StorageControllerApiException, match="no secondary location"):
+        env.storage_controller.tenant_shard_migrate_secondary(tenant_shard_id, spare, secondary)
+    with pytest.raises(StorageControllerApiException, match="attached location"):
+        env.storage_controller.tenant_shard_migrate_secondary(tenant_shard_id, secondary, attached)
+
+    env.storage_controller.tenant_shard_migrate_secondary(tenant_shard_id, secondary, spare)
+    shard = env.storage_controller.tenant_describe(tenant_id)["shards"][0]
+    assert shard["node_attached"] == attached
+    assert shard["node_secondary"] == [spare]
+
+    # The new secondary location exists on its pageserver, and the old one is gone
+    location = env.get_pageserver(spare).http_client().tenant_get_location(tenant_shard_id)
+    assert location["mode"] == "Secondary"
+    old_locations = env.get_pageserver(secondary).http_client().tenant_list_locations()
+    assert old_locations["tenant_shards"] == []
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_shard_cancel_reconcile(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that cancelling a shard's reconcile reports whether anything was cancelled, along
+    with the locations the controller has observed for the shard.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id)
+    env.storage_controller.reconcile_until_idle()
+
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    attached = env.get_tenant_pageserver(tenant_shard_id)
+    assert attached is not None
+
+    # Nothing is reconciling, so there is nothing to cancel
+    result = env.storage_controller.tenant_shard_cancel_reconcile(tenant_shard_id)
+    assert result["cancelled"] is False
+    assert attached.id in [loc["node_id"] for loc in result["observed"]]
+
+    # Cancelling a migration leaves the shard reconcilable afterwards
+    dest = [p.id for p in env.pageservers if p.id != attached.id][0]
+    env.storage_controller.tenant_shard_migrate(tenant_shard_id, dest)
+    env.storage_controller.tenant_shard_cancel_reconcile(tenant_shard_id)
+    env.storage_controller.reconcile_until_idle()
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_increment_generation(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that forcing a generation increment invalidates the attached pageserver's old
+    generation, and that the pageserver is then given a current one.
+    """
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    env.storage_controller.tenant_create(tenant_id)
+    env.storage_controller.reconcile_until_idle()
+
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    ps = env.get_tenant_pageserver(tenant_shard_id)
+    assert ps is not None
+    old_generation = ps.http_client().tenant_list()[0]["generation"]
+
+    result = env.storage_controller.tenant_shard_increment_generation(tenant_shard_id)
+    assert result["node_id"] == ps.id
+    assert result["generation"] > old_generation
+
+    # The reconcile that follows gives the pageserver a generation at least as new
+    assert ps.http_client().tenant_list()[0]["generation"] >= result["generation"]
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_generation_freeze(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that a frozen tenant's generation is not incremented, and that a pageserver which
+    re-attaches while one of its tenants is frozen is refused until the tenant is unfrozen.
+    """
+    env = neon_env_builder.init_start()
+    env.storage_controller.allowed_errors.append(".*Generation of tenant .* is frozen.*")
+    env.pageserver.allowed_errors.append(
+        ".*calling control plane generation validation API failed.*"
+    )
+
+    frozen_tenant = TenantId.generate()
+    other_tenant = TenantId.generate()
+    env.storage_controller.tenant_create(frozen_tenant)
+    env.storage_controller.tenant_create(other_tenant)
+    env.storage_controller.reconcile_until_idle()
+
+    def generations() -> Dict[TenantId, int]:
+        return {
+            TenantId(t["id"]): t["generation"] for t in env.pageserver.http_client().tenant_list()
+        }
+
+    before = generations()
+
+    env.storage_controller.tenant_generation_freeze(frozen_tenant, True)
+    with pytest.raises(StorageControllerApiException, match="frozen"):
+        env.storage_controller.tenant_shard_increment_generation(
+            TenantShardId(frozen_tenant, 0, 0)
+        )
+
+    # The pageserver does not start up until its re-attach succeeds, so restart it in the
+    # background: its re-attach is refused while the tenant is frozen
+    env.pageserver.stop()
+    restart = threading.Thread(target=env.pageserver.start, kwargs={"timeout_in_seconds": 60})
+    restart.start()
+
+    wait_until(
+        30,
+        1,
+        lambda: env.storage_controller.assert_log_contains(
+            ".*re-attach.*Generation of tenant .* is frozen"
+        ),
+    )
+    assert restart.is_alive()
+
+    # Once unfrozen, the re-attach succeeds and both tenants are attached in new generations
+    env.storage_controller.tenant_generation_freeze(frozen_tenant, False)
+    restart.join()
+    after = generations()
+    assert after[frozen_tenant] > before[frozen_tenant]
+    assert after[other_tenant] > before[other_tenant]
+    env.storage_controller.reconcile_until_idle()
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_set_generation(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that an operator can overwrite a shard's generation, but only move it backwards
+    with force.
+    """
+    env = neon_env_builder.init_start()
+    env.storage_controller.allowed_errors.append(".*Refusing to move generation backwards.*")
+
+    tenant_id = TenantId.generate()
+    tenant_shard_id = TenantShardId(tenant_id, 0, 0)
+    env.storage_controller.tenant_create(tenant_id)
+    env.storage_controller.reconcile_until_idle()
+
+    def generation() -> int:
+        return env.pageserver.http_client().tenant_status(tenant_id)["generation"]
+
+    before = generation()
+
+    # Moving the generation forwards is applied to the pageserver
+    env.storage_controller.tenant_shard_set_generation(tenant_shard_id, before + 5)
+    env.storage_controller.reconcile_until_idle()
+    assert generation() >= before + 5
+
+    # Moving it backwards is refused without force
+    with pytest.raises(StorageControllerApiException) as e:
+        env.storage_controller.tenant_shard_set_generation(tenant_shard_id, before)
+    assert e.value.status_code == 412
+    assert generation() >= before + 5
+
+    env.storage_controller.consistency_check()
+
+
 def test_node_status_after_restart(
     neon_env_builder: NeonEnvBuilder,
 ):
@@ -206,6 +684,56 @@ def test_node_status_after_restart(
     env.storage_controller.consistency_check()
 
 
+def test_storage_controller_node_history(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that operator changes to a node's scheduling policy are recorded in its history
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    node_id = env.pageservers[0].id
+    before = env.storage_controller.node_history(node_id)
+
+    env.storage_controller.node_configure(node_id, {"scheduling": "Pause"})
+    env.storage_controller.node_configure(node_id, {"scheduling": "Pause"})
+    env.storage_controller.node_configure(node_id, {"scheduling": "Active"})
+
+    # Setting a policy the node already has is not a transition
+    history = env.storage_controller.node_history(node_id)[len(before) :]
+    assert [t["scheduling"] for t in history] == ["Pause", "Active"]
+    assert all(t["source"] == "Operator" for t in history)
+    assert all(t["availability"] == "Active" for t in history)
+    assert all(t["offline_reason"] is None for t in history)
+
+    # Other nodes are unaffected
+    other_id = env.pageservers[1].id
+    assert all(t["source"] != "Operator" for t in env.storage_controller.node_history(other_id))
+
+    with pytest.raises(StorageControllerApiException, match="not registered"):
+        env.storage_controller.node_history(1234)
+
+
+def test_storage_controller_autosplit_config(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that auto-splitting settings can be changed at runtime
+    """
+    env = neon_env_builder.init_start()
+
+    # Auto-splitting is disabled unless configured at startup
+    assert env.storage_controller.autosplit_config() == {"split_threshold": None, "split_to": 8}
+
+    expect = {"split_threshold": 1024 * 1024 * 500, "split_to": 16}
+    assert env.storage_controller.update_autosplit_config(**expect) == expect
+    assert env.storage_controller.autosplit_config() == expect
+
+    for split_to in [3, 1, 255]:
+        with pytest.raises(StorageControllerApiException, match="Cannot auto-split"):
+            env.storage_controller.update_autosplit_config(1024, split_to)
+
+    # Rejected updates leave the settings unchanged
+    assert env.storage_controller.autosplit_config() == expect
+
+
 def test_storage_controller_passthrough(
     neon_env_builder: NeonEnvBuilder,
 ):
@@ -836,6 +1364,149 @@ def test_storage_controller_tenant_conf(neon_env_builder: NeonEnvBuilder):
     env.storage_controller.consistency_check()
 
 
+def test_storage_controller_location_conf_atomic(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that if persisting a /location_config update fails partway through a multi-shard
+    tenant's shards, none of the shards are updated, in memory or in the database.
+    """
+
+    env = neon_env_builder.init_start()
+    env.storage_controller.allowed_errors.append(".*failpoint.*")
+
+    tenant_id = TenantId.generate()
+    env.neon_cli.create_tenant(tenant_id, shard_count=2)
+
+    virtual_ps_http = PageserverHttpClient(env.storage_controller_port, lambda: True)
+    http = env.storage_controller.pageserver_api()
+    original_value = http.tenant_config(tenant_id).tenant_specific_overrides.get("pitr_interval")
+
+    def update_location_conf():
+        virtual_ps_http.tenant_location_conf(
+            tenant_id,
+            {
+                "mode": "AttachedSingle",
+                "secondary_conf": None,
+                "tenant_conf": {"pitr_interval": "1h"},
+                "generation": None,
+            },
+        )
+
+    # Fail after the first shard's row has been written
+    env.storage_controller.configure_failpoints(("update-tenant-shards-partial", "return(1)"))
+    with pytest.raises(PageserverApiException, match="failpoint"):
+        update_location_conf()
+    env.storage_controller.configure_failpoints(("update-tenant-shards-partial", "off"))
+
+    def assert_unchanged():
+        readback = http.tenant_config(tenant_id)
+        assert readback.tenant_specific_overrides.get("pitr_interval") == original_value
+
+    # Neither in-memory state nor the database reflect the failed update
+    assert_unchanged()
+    env.storage_controller.stop()
+    env.storage_controller.start()
+    assert_unchanged()
+    env.storage_controller.consistency_check()
+
+    # Once the failure is cleared, the update applies to all shards
+    update_location_conf()
+    assert http.tenant_config(tenant_id).tenant_specific_overrides["pitr_interval"] == "1h"
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_shard_detach(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that one shard of a sharded tenant can be detached from all pageservers while its
+    siblings stay attached, and then be reattached.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    shard_count = 4
+    env.neon_cli.create_tenant(
+        tenant_id, shard_count=shard_count, placement_policy='{"Attached":1}'
+    )
+    shards = [TenantShardId(tenant_id, i, shard_count) for i in range(0, shard_count)]
+    victim = shards[1]
+
+    def shard_locations() -> Dict[TenantShardId, int]:
+        """How many pageservers have a location for each of the tenant's shards"""
+        counts: Dict[TenantShardId, int] = defaultdict(int)
+        for ps in env.pageservers:
+            for shard_id, _conf in ps.http_client().tenant_list_locations()["tenant_shards"]:
+                counts[TenantShardId.parse(shard_id)] += 1
+        return counts
+
+    assert all(shard_locations()[shard] == 2 for shard in shards)
+
+    env.storage_controller.tenant_shard_detach(victim)
+    env.storage_controller.reconcile_until_idle()
+
+    locations = shard_locations()
+    assert locations[victim] == 0
+    assert all(locations[shard] == 2 for shard in shards if shard != victim)
+    describe = env.storage_controller.tenant_describe(tenant_id)["shards"]
+    assert describe[1]["node_attached"] is None
+    assert describe[1]["node_secondary"] == []
+
+    # Tenant-wide operations depend on shard zero, so detaching it needs to be forced
+    with pytest.raises(StorageControllerApiException, match="force"):
+        env.storage_controller.tenant_shard_detach(shards[0])
+
+    # The detached shard stays detached across a restart, and then gets its siblings' policy back
+    env.storage_controller.stop()
+    env.storage_controller.start()
+    env.storage_controller.reconcile_until_idle()
+    assert shard_locations()[victim] == 0
+
+    env.storage_controller.tenant_shard_reattach(victim)
+    env.storage_controller.reconcile_until_idle()
+    assert all(shard_locations()[shard] == 2 for shard in shards)
+
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_reconcile_watch(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that the reconcile watch API reports each shard's reconcile status, and returns
+    promptly once there is nothing in progress.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    shard_count = 2
+    env.neon_cli.create_tenant(tenant_id, shard_count=shard_count)
+    shards = [TenantShardId(tenant_id, i, shard_count) for i in range(0, shard_count)]
+
+    def watch_until_done() -> List[Dict[str, Any]]:
+        for _ in range(0, 10):
+            status = env.storage_controller.tenant_reconcile_watch(tenant_id)
+            if all(s["status"] == "Done" for s in status):
+                return status
+        raise RuntimeError("Reconciles did not complete")
+
+    status = watch_until_done()
+    assert [TenantShardId.parse(s["tenant_shard_id"]) for s in status] == shards
+    assert all(s["last_error"] is None for s in status)
+
+    # With nothing in progress, the watch does not hold the request open
+    started = time.monotonic()
+    env.storage_controller.tenant_reconcile_watch(tenant_id, wait_ms=10000)
+    assert time.monotonic() - started < 5
+
+    # Moving a shard gives its reconciler something to do
+    dest_ps_id = next(
+        ps.id for ps in env.pageservers if ps.id != env.get_tenant_pageserver(shards[1]).id
+    )
+    env.storage_controller.tenant_shard_migrate(shards[1], dest_ps_id)
+    watch_until_done()
+
+    with pytest.raises(StorageControllerApiException, match="not found"):
+        env.storage_controller.tenant_reconcile_watch(TenantId.generate())
+
+
 def test_storage_controller_tenant_deletion(
     neon_env_builder: NeonEnvBuilder,
     compute_reconfigure_listener: ComputeReconfigure,
@@ -1106,6 +1777,88 @@ def test_storage_controller_heartbeats(
     wait_until(30, 1, storage_controller_consistent)
 
 
+def test_storage_controller_node_migrate_tenants(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that chosen tenants can be moved off a node without draining it
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start()
+
+    tenant_a = TenantId.generate()
+    env.neon_cli.create_tenant(tenant_a, placement_policy='{"Attached":1}')
+    tenant_b = TenantId.generate()
+    env.neon_cli.create_tenant(tenant_b, placement_policy='{"Attached":1}')
+    tenant_c = TenantId.generate()
+    env.neon_cli.create_tenant(tenant_c)
+
+    # Attach everything to the same pageserver
+    ps = env.pageservers[0]
+    for tenant_id in [tenant_a, tenant_b, tenant_c]:
+        env.storage_controller.tenant_shard_migrate(TenantShardId(tenant_id, 0, 0), ps.id)
+    env.storage_controller.reconcile_until_idle()
+
+    # Tenant C has no secondary to move to
+    response = env.storage_controller.node_migrate_tenants(ps.id, [tenant_a, tenant_c])
+    assert response["migrated"] == [str(TenantShardId(tenant_a, 0, 0))]
+    assert response["failed"] == [str(TenantShardId(tenant_c, 0, 0))]
+
+    env.storage_controller.reconcile_until_idle()
+    assert env.get_tenant_pageserver(tenant_a).id != ps.id
+    assert env.get_tenant_pageserver(tenant_b).id == ps.id
+    assert env.get_tenant_pageserver(tenant_c).id == ps.id
+
+    # The node stays in service
+    assert env.storage_controller.node_status(ps.id)["scheduling"] == "Active"
+
+    with pytest.raises(StorageControllerApiException, match="not found"):
+        env.storage_controller.node_migrate_tenants(ps.id, [TenantId.generate()])
+
+    env.storage_controller.consistency_check()
+
+
+def test_storage_controller_tenant_set_placement(neon_env_builder: NeonEnvBuilder):
+    """
+    Validate that a tenant's shards can be placed explicitly in one call, and that an invalid
+    placement is rejected without changing anything.
+    """
+    neon_env_builder.num_pageservers = 3
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    shard_count = 2
+    env.neon_cli.create_tenant(
+        tenant_id, shard_count=shard_count, placement_policy='{"Attached":1}'
+    )
+    shards = [TenantShardId(tenant_id, i, shard_count) for i in range(0, shard_count)]
+    ps_ids = [ps.id for ps in env.pageservers]
+
+    def placement():
+        describe = env.storage_controller.tenant_describe(tenant_id)["shards"]
+        return [(s["node_attached"], s["node_secondary"]) for s in describe]
+
+    spec = [
+        {"tenant_shard_id": str(shards[0]), "attached": ps_ids[0], "secondaries": [ps_ids[1]]},
+        {"tenant_shard_id": str(shards[1]), "attached": ps_ids[1], "secondaries": [ps_ids[2]]},
+    ]
+    env.storage_controller.tenant_set_placement(tenant_id, spec)
+    assert placement() == [(ps_ids[0], [ps_ids[1]]), (ps_ids[1], [ps_ids[2]])]
+
+    # Placing the shards where they already are changes nothing
+    assert env.storage_controller.tenant_set_placement(tenant_id, spec)["shards"] == []
+
+    # A shard can't be attached and secondary on the same node: the whole spec is rejected
+    bad_spec = [
+        {"tenant_shard_id": str(shards[0]), "attached": ps_ids[2], "secondaries": [ps_ids[0]]},
+        {"tenant_shard_id": str(shards[1]), "attached": ps_ids[2], "secondaries": [ps_ids[2]]},
+    ]
+    with pytest.raises(StorageControllerApiException, match="more than one location"):
+        env.storage_controller.tenant_set_placement(tenant_id, bad_spec)
+    assert placement() == [(ps_ids[0], [ps_ids[1]]), (ps_ids[1], [ps_ids[2]])]
+
+    env.storage_controller.reconcile_until_idle()
+    env.storage_controller.consistency_check()
+
+
 def test_storage_controller_re_attach(neon_env_builder: NeonEnvBuilder):
     """
     Exercise the behavior of the /re-attach endpoint on pageserver startup when